@@ -47,9 +47,12 @@ pub struct Name<'a> {
     pub name: &'a str,
 }
 
+#[derive(Clone, Copy)]
 pub enum SearchType {
     Artist,
     Album,
+    Track,
+    Playlist,
 }
 
 impl SearchType {
@@ -57,6 +60,8 @@ impl SearchType {
         match self {
             Self::Artist => "artist",
             Self::Album => "album",
+            Self::Track => "track",
+            Self::Playlist => "playlist",
         }
     }
 }
@@ -180,6 +185,10 @@ trait WithImages {
     fn best_image_for_width(&self, width: i32) -> Option<&Image> {
         self.best_image(|i| (width - i.width.unwrap_or(0) as i32).abs())
     }
+
+    fn largest_image(&self) -> Option<&Image> {
+        self.best_image(|i| std::cmp::Reverse(i.width.unwrap_or(0)))
+    }
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -216,6 +225,8 @@ impl WithImages for Playlist {
 pub struct PlaylistTrack {
     pub is_local: bool,
     pub track: Option<FailibleTrackItem>,
+    #[serde(default)]
+    pub added_at: Option<String>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -298,6 +309,67 @@ pub struct User {
     pub display_name: String,
 }
 
+#[derive(Deserialize, Debug, Clone)]
+pub struct SavedShow {
+    pub show: Show,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Show {
+    pub id: String,
+    pub name: String,
+    pub publisher: String,
+    #[serde(default)]
+    pub description: String,
+    pub images: Vec<Image>,
+    // Only when getting a single show (unavailable episodes come back as null)
+    pub episodes: Option<Page<Option<Episode>>>,
+}
+
+impl WithImages for Show {
+    fn images(&self) -> &[Image] {
+        &self.images[..]
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ResumePoint {
+    pub fully_played: bool,
+    pub resume_position_ms: u32,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Episode {
+    pub id: String,
+    pub uri: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub duration_ms: u32,
+    pub release_date: Option<String>,
+    pub images: Vec<Image>,
+    #[serde(default)]
+    pub explicit: bool,
+    pub resume_point: Option<ResumePoint>,
+}
+
+impl WithImages for Episode {
+    fn images(&self) -> &[Image] {
+        &self.images[..]
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ExplicitContent {
+    pub filter_enabled: bool,
+}
+
+// The logged in user, we only care about their content settings
+#[derive(Deserialize, Debug, Clone)]
+pub struct CurrentUser {
+    pub explicit_content: Option<ExplicitContent>,
+}
+
 #[derive(Deserialize, Debug, Clone)]
 pub struct Device {
     #[serde(alias = "type")]
@@ -378,6 +450,22 @@ pub struct TopTracks {
     pub tracks: Vec<TrackItem>,
 }
 
+#[derive(Deserialize, Debug, Clone)]
+pub struct RelatedArtists {
+    pub artists: Vec<Artist>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Recommendations {
+    pub tracks: Vec<TrackItem>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Tracks {
+    // Ids that don't match anything come back as null
+    pub tracks: Vec<Option<FailibleTrackItem>>,
+}
+
 #[derive(Deserialize, Debug, Clone)]
 pub struct AlbumTrackItem {
     pub id: String,
@@ -386,6 +474,23 @@ pub struct AlbumTrackItem {
     pub name: String,
     pub duration_ms: i64,
     pub artists: Vec<Artist>,
+    #[serde(default)]
+    pub explicit: bool,
+    // Only there when asking for a market, which we always do
+    pub is_playable: Option<bool>,
+    pub restrictions: Option<Restrictions>,
+}
+
+impl AlbumTrackItem {
+    pub fn is_playable(&self) -> bool {
+        self.is_playable.unwrap_or(true) && self.restrictions.is_none()
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Restrictions {
+    // e.g. "market", "product" or "explicit"
+    pub reason: String,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -393,6 +498,9 @@ pub struct TrackItem {
     #[serde(flatten)]
     pub track: AlbumTrackItem,
     pub album: Album,
+    // Not part of the track itself, but of the list it comes from
+    #[serde(skip)]
+    pub added_at: Option<String>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -418,6 +526,78 @@ impl FailibleTrackItem {
 pub struct RawSearchResults {
     pub albums: Option<Page<Album>>,
     pub artists: Option<Page<Artist>>,
+    pub tracks: Option<Page<TrackItem>>,
+    // Spotify sometimes sends null playlists in there
+    pub playlists: Option<Page<Option<Playlist>>>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Cursors {
+    pub after: Option<String>,
+}
+
+// Some endpoints (like followed artists) page with cursors rather than offsets
+#[derive(Deserialize, Debug, Clone)]
+pub struct CursorPage<T> {
+    pub items: Vec<T>,
+    pub cursors: Option<Cursors>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct RawFollowedArtists {
+    pub artists: CursorPage<Artist>,
+}
+
+impl From<RawFollowedArtists> for FollowedArtistsPage {
+    fn from(RawFollowedArtists { artists }: RawFollowedArtists) -> Self {
+        let CursorPage { items, cursors } = artists;
+        Self {
+            artists: items.into_iter().map(|a| a.into()).collect(),
+            next_cursor: cursors.and_then(|c| c.after),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct NewReleases {
+    pub albums: Page<Album>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct PlayContext {
+    pub uri: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct PlayHistoryItem {
+    pub track: FailibleTrackItem,
+    pub context: Option<PlayContext>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct RecentlyPlayedTracks {
+    pub items: Vec<PlayHistoryItem>,
+}
+
+pub enum PlayedFrom {
+    // Only its id, there's nothing else about it in the history
+    Playlist(String),
+    Album(Album),
+}
+
+impl PlayHistoryItem {
+    // Anything that isn't a playlist (an album, an artist, the saved tracks...) is shown as the track's album
+    pub fn played_from(self) -> Option<PlayedFrom> {
+        let playlist_id = self
+            .context
+            .as_ref()
+            .and_then(|c| c.uri.strip_prefix("spotify:playlist:"))
+            .map(str::to_owned);
+        match playlist_id {
+            Some(id) => Some(PlayedFrom::Playlist(id)),
+            None => Some(PlayedFrom::Album(self.track.get()?.album)),
+        }
+    }
 }
 
 impl From<Artist> for ArtistSummary {
@@ -431,14 +611,24 @@ impl From<Artist> for ArtistSummary {
 impl TryFrom<PlaylistTrack> for TrackItem {
     type Error = ();
 
-    fn try_from(PlaylistTrack { is_local, track }: PlaylistTrack) -> Result<Self, Self::Error> {
-        track.ok_or(())?.get().filter(|_| !is_local).ok_or(())
+    fn try_from(
+        PlaylistTrack {
+            is_local,
+            track,
+            added_at,
+        }: PlaylistTrack,
+    ) -> Result<Self, Self::Error> {
+        let track = track.ok_or(())?.get().filter(|_| !is_local).ok_or(())?;
+        Ok(Self { added_at, ..track })
     }
 }
 
 impl From<SavedTrack> for TrackItem {
-    fn from(track: SavedTrack) -> Self {
-        track.track
+    fn from(SavedTrack { added_at, track }: SavedTrack) -> Self {
+        Self {
+            added_at: Some(added_at),
+            ..track
+        }
     }
 }
 
@@ -472,6 +662,24 @@ impl From<TopTracks> for Vec<SongDescription> {
     }
 }
 
+impl From<Recommendations> for Vec<SongDescription> {
+    fn from(recommendations: Recommendations) -> Self {
+        Page::new(recommendations.tracks).into()
+    }
+}
+
+impl From<Tracks> for Vec<SongDescription> {
+    fn from(tracks: Tracks) -> Self {
+        let tracks: Vec<TrackItem> = tracks
+            .tracks
+            .into_iter()
+            .flatten()
+            .filter_map(FailibleTrackItem::get)
+            .collect();
+        Page::new(tracks).into()
+    }
+}
+
 impl<T> From<Page<T>> for Vec<SongDescription>
 where
     T: TryInto<TrackItem>,
@@ -487,6 +695,7 @@ impl From<(Page<AlbumTrackItem>, &Album)> for SongBatch {
         Self::from(page.map(|track| TrackItem {
             track,
             album: album.clone(),
+            added_at: None,
         }))
     }
 }
@@ -504,7 +713,12 @@ where
         let songs = page
             .into_iter()
             .filter_map(|t| {
-                let TrackItem { track, album } = t.try_into().ok()?;
+                let TrackItem {
+                    track,
+                    album,
+                    added_at,
+                } = t.try_into().ok()?;
+                let is_playable = track.is_playable();
                 let AlbumTrackItem {
                     artists,
                     id,
@@ -512,6 +726,8 @@ where
                     name,
                     duration_ms,
                     track_number,
+                    explicit,
+                    ..
                 } = track;
                 let artists = artists
                     .into_iter()
@@ -542,6 +758,9 @@ where
                     album: album_ref,
                     duration: duration_ms as u32,
                     art,
+                    added_at,
+                    explicit,
+                    is_playable,
                 })
             })
             .collect();
@@ -584,6 +803,7 @@ impl From<Album> for AlbumDescription {
             .try_into()
             .unwrap_or_else(|_| SongBatch::empty());
         let art = album.best_image_for_width(200).map(|i| i.url.clone());
+        let large_art = album.largest_image().map(|i| i.url.clone());
 
         Self {
             id: album.id,
@@ -591,6 +811,7 @@ impl From<Album> for AlbumDescription {
             artists,
             release_date: album.release_date,
             art,
+            large_art,
             songs,
             is_liked: false,
         }
@@ -647,6 +868,66 @@ impl From<Playlist> for PlaylistDescription {
     }
 }
 
+impl From<Episode> for EpisodeDescription {
+    fn from(episode: Episode) -> Self {
+        let art = episode.best_image_for_width(200).map(|i| i.url.clone());
+        let Episode {
+            id,
+            uri,
+            name,
+            description,
+            duration_ms,
+            release_date,
+            explicit,
+            resume_point,
+            ..
+        } = episode;
+        Self {
+            id,
+            uri,
+            title: name,
+            description,
+            release_date,
+            duration: duration_ms,
+            resume_position: resume_point
+                .as_ref()
+                .map(|r| r.resume_position_ms)
+                .filter(|position| *position > 0),
+            fully_played: resume_point.map(|r| r.fully_played).unwrap_or(false),
+            art,
+            explicit,
+        }
+    }
+}
+
+impl From<Page<Option<Episode>>> for Vec<EpisodeDescription> {
+    fn from(page: Page<Option<Episode>>) -> Self {
+        page.into_iter().flatten().map(|e| e.into()).collect()
+    }
+}
+
+impl From<Show> for ShowDescription {
+    fn from(show: Show) -> Self {
+        let art = show.best_image_for_width(200).map(|i| i.url.clone());
+        let Show {
+            id,
+            name,
+            publisher,
+            description,
+            episodes,
+            ..
+        } = show;
+        Self {
+            id,
+            title: name,
+            publisher,
+            description,
+            art,
+            episodes: episodes.map(|page| page.into()).unwrap_or_default(),
+        }
+    }
+}
+
 impl From<Device> for ConnectDevice {
     fn from(
         Device {
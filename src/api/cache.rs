@@ -1,9 +1,11 @@
 use core::mem::size_of;
 use futures::join;
 use regex::Regex;
+use std::collections::HashMap;
 use std::convert::From;
 use std::future::Future;
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, SystemTime};
 use thiserror::Error;
 use tokio::{fs, io};
@@ -66,6 +68,16 @@ impl CacheExpiry {
         }
     }
 
+    // What the expiry file takes on disk (there's none when it never expires)
+    fn file_size(&self) -> u64 {
+        match self {
+            Self::Never => 0,
+            Self::AtUnixTimestamp(_, etag) => {
+                (size_of::<u64>() + etag.as_ref().map_or(0, String::len)) as u64
+            }
+        }
+    }
+
     fn etag(&self) -> Option<&String> {
         match self {
             Self::Never => None,
@@ -74,9 +86,97 @@ impl CacheExpiry {
     }
 }
 
+#[derive(Clone, Copy, Debug)]
+struct IndexEntry {
+    // Including its expiry file
+    size: u64,
+    // Bumped every time the entry is used, 0 if it wasn't since we started...
+    last_access: u64,
+    // ...in which case that's all we have to go by
+    modified: SystemTime,
+}
+
+// What the entries of a cache directory take, and when they were last used,
+// so that the least recently used ones can go once there's a limit and it's exceeded
+#[derive(Default)]
+struct CacheIndex {
+    max_size: Option<u64>,
+    // Whether we looked at what was there before we started
+    scanned: bool,
+    clock: u64,
+    entries: HashMap<String, IndexEntry>,
+    // Being written right now (by that many tasks), never to be evicted
+    writing: HashMap<String, usize>,
+}
+
+impl CacheIndex {
+    fn touch(&mut self, resource: &str, size: u64) {
+        if self.max_size.is_none() {
+            return;
+        }
+        self.clock += 1;
+        let entry = IndexEntry {
+            size,
+            last_access: self.clock,
+            modified: SystemTime::now(),
+        };
+        self.entries.insert(resource.to_string(), entry);
+    }
+
+    fn start_writing(&mut self, resource: &str) {
+        *self.writing.entry(resource.to_string()).or_default() += 1;
+    }
+
+    fn stop_writing(&mut self, resource: &str) {
+        if let Some(count) = self.writing.get_mut(resource) {
+            *count -= 1;
+            if *count == 0 {
+                self.writing.remove(resource);
+            }
+        }
+    }
+
+    // The least recently used entries, until what's left fits
+    fn take_victims(&mut self) -> Vec<String> {
+        let Some(max_size) = self.max_size else {
+            return vec![];
+        };
+        let mut total: u64 = self.entries.values().map(|e| e.size).sum();
+        if total <= max_size {
+            return vec![];
+        }
+
+        let mut candidates: Vec<(&String, &IndexEntry)> = self
+            .entries
+            .iter()
+            .filter(|(resource, _)| !self.writing.contains_key(*resource))
+            .collect();
+        candidates.sort_by_key(|(_, e)| (e.last_access, e.modified));
+
+        let mut victims = vec![];
+        for (resource, entry) in candidates {
+            if total <= max_size {
+                break;
+            }
+            total = total.saturating_sub(entry.size);
+            victims.push(resource.clone());
+        }
+        for resource in victims.iter() {
+            self.entries.remove(resource);
+        }
+        victims
+    }
+}
+
+lazy_static! {
+    // Managers of the same directory share the same index
+    static ref INDEXES: Mutex<HashMap<PathBuf, Arc<Mutex<CacheIndex>>>> = Default::default();
+}
+
 #[derive(Clone)]
 pub struct CacheManager {
     root: PathBuf,
+    index: Arc<Mutex<CacheIndex>>,
 }
 
 impl CacheManager {
@@ -87,10 +187,35 @@ impl CacheManager {
 
         glib::mkdir_with_parents(&root, mask);
 
-        Some(Self { root })
+        let index = Arc::clone(INDEXES.lock().ok()?.entry(root.clone()).or_default());
+        Some(Self { root, index })
     }
 
-    fn cache_path(&self, resource: &str) -> PathBuf {
+    // In bytes, None meaning there's no limit
+    pub fn set_max_size(&self, max_size: Option<u64>) {
+        let mut index = self.index.lock().unwrap();
+        index.max_size = max_size;
+        if max_size.is_none() {
+            // Nothing to keep track of anymore
+            index.entries.clear();
+            index.scanned = false;
+        }
+    }
+
+    // How much space the cached files take, in bytes
+    pub fn size(&self) -> u64 {
+        let Ok(entries) = std::fs::read_dir(&self.root) else {
+            return 0;
+        };
+        entries
+            .flatten()
+            .filter_map(|entry| entry.metadata().ok())
+            .filter(|metadata| metadata.is_file())
+            .map(|metadata| metadata.len())
+            .sum()
+    }
+
+    pub fn cache_path(&self, resource: &str) -> PathBuf {
         self.root.join(resource)
     }
 
@@ -115,7 +240,10 @@ impl CacheManager {
                 duration.copy_from_slice(&buffer[..OFFSET]);
                 let duration = Duration::from_secs(u64::from_be_bytes(duration));
 
-                let etag = String::from_utf8(buffer[OFFSET..].to_vec()).ok();
+                // Nothing follows the timestamp when there was no etag
+                let etag = String::from_utf8(buffer[OFFSET..].to_vec())
+                    .ok()
+                    .filter(|etag| !etag.is_empty());
 
                 Ok(CacheExpiry::AtUnixTimestamp(duration, etag))
             }
@@ -133,6 +261,13 @@ impl CacheManager {
 
         let path = self.cache_path(resource);
         let (file, expiry) = join!(fs::read(&path), self.read_expiry_file(resource));
+        if let Ok(buf) = file.as_ref() {
+            let expiry_size = expiry.as_ref().map_or(0, CacheExpiry::file_size);
+            self.index
+                .lock()
+                .unwrap()
+                .touch(resource, buf.len() as u64 + expiry_size);
+        }
 
         match (file, policy) {
             (Ok(buf), CachePolicy::IgnoreExpiry) => Ok(CacheFile::Fresh(buf)),
@@ -189,6 +324,8 @@ impl CacheManager {
                 .map(|s| regex.is_match(s))
                 .unwrap_or(false);
             if matches {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                self.index.lock().unwrap().entries.remove(&name);
                 info!("Removing {}...", entry.file_name().to_str().unwrap_or(""));
                 fs::remove_file(entry.path())
                     .await
@@ -235,15 +372,128 @@ impl CacheManager {
     ) -> Result<(), CacheError> {
         let file = self.cache_path(resource);
         let meta = self.cache_meta_path(resource);
+        let size = content.len() as u64 + expiry.file_size();
+        self.index.lock().unwrap().start_writing(resource);
         let (r1, r2) = join!(
             fs::write(&file, content),
             self.set_expiry_for_path(&meta, expiry)
         );
+        {
+            let mut index = self.index.lock().unwrap();
+            index.stop_writing(resource);
+            if r1.is_ok() {
+                index.touch(resource, size);
+            }
+        }
         r1.map_err(CacheError::WriteError)?;
         r2?;
+        self.evict().await;
         Ok(())
     }
 
+    // What was there before we started counts too, it's only looked at once
+    async fn scan(&self) {
+        let mut found: HashMap<String, IndexEntry> = HashMap::new();
+        if let Ok(mut entries) = fs::read_dir(&self.root).await {
+            while let Ok(Some(entry)) = entries.next_entry().await {
+                let Ok(name) = entry.file_name().into_string() else {
+                    continue;
+                };
+                let Ok(metadata) = entry.metadata().await else {
+                    continue;
+                };
+                if !metadata.is_file() {
+                    continue;
+                }
+                // Expiry files go with their resource
+                let is_expiry = name.ends_with(EXPIRY_FILE_EXT);
+                let resource = name.strip_suffix(EXPIRY_FILE_EXT).unwrap_or(&name);
+                let found_entry = found.entry(resource.to_string()).or_insert(IndexEntry {
+                    size: 0,
+                    last_access: 0,
+                    modified: SystemTime::UNIX_EPOCH,
+                });
+                found_entry.size += metadata.len();
+                if !is_expiry {
+                    found_entry.modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                }
+            }
+        }
+
+        let mut index = self.index.lock().unwrap();
+        for (name, entry) in found {
+            index.entries.entry(name).or_insert(entry);
+        }
+        index.scanned = true;
+    }
+
+    async fn evict(&self) {
+        let scanned = {
+            let index = self.index.lock().unwrap();
+            if index.max_size.is_none() {
+                return;
+            }
+            index.scanned
+        };
+        if !scanned {
+            self.scan().await;
+        }
+
+        let victims = self.index.lock().unwrap().take_victims();
+        if victims.is_empty() {
+            return;
+        }
+        // Away from the main loop, and without holding the lock
+        let cache = self.clone();
+        let _ = tokio::task::spawn_blocking(move || cache.remove_victims(victims)).await;
+    }
+
+    // Whoever is reading them either already has them, or will fetch them again
+    fn remove_victims(&self, victims: Vec<String>) {
+        for resource in victims {
+            // Unless it was written or read again since it was picked
+            let in_use = {
+                let index = self.index.lock().unwrap();
+                index.writing.contains_key(&resource) || index.entries.contains_key(&resource)
+            };
+            if in_use {
+                continue;
+            }
+            debug!("Evicting {resource}");
+            let _ = std::fs::remove_file(self.cache_path(&resource));
+            let _ = std::fs::remove_file(self.cache_meta_path(&resource));
+        }
+    }
+
+    // Removes everything but what's being written right now, returns how many bytes were freed
+    pub async fn clear(&self) -> Result<u64, CacheError> {
+        let mut entries = fs::read_dir(&self.root)
+            .await
+            .map_err(CacheError::ReadError)?;
+
+        let mut freed = 0;
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let size = match entry.metadata().await {
+                Ok(metadata) if metadata.is_file() => metadata.len(),
+                _ => continue,
+            };
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let resource = name.strip_suffix(EXPIRY_FILE_EXT).unwrap_or(&name);
+
+            {
+                let mut index = self.index.lock().unwrap();
+                if index.writing.contains_key(resource) {
+                    continue;
+                }
+                index.entries.remove(resource);
+            }
+            if fs::remove_file(entry.path()).await.is_ok() {
+                freed += size;
+            }
+        }
+        Ok(freed)
+    }
+
     pub async fn get_or_write<O, F, E>(
         &self,
         resource: &str,
@@ -258,8 +508,15 @@ impl CacheManager {
         let file = self.read_cache_file(resource, policy).await?;
         match file {
             CacheFile::Fresh(buf) => Ok(buf),
-            CacheFile::Expired(buf, etag) => match fetch(etag).await? {
+            CacheFile::Expired(buf, etag) => match fetch(etag.clone()).await? {
                 FetchResult::NotModified(expiry) => {
+                    // A 304 doesn't always come with the etag again, the one we had still holds
+                    let expiry = match expiry {
+                        CacheExpiry::AtUnixTimestamp(at, None) => {
+                            CacheExpiry::AtUnixTimestamp(at, etag)
+                        }
+                        expiry => expiry,
+                    };
                     let meta = self.cache_meta_path(resource);
                     self.set_expiry_for_path(&meta, expiry).await?;
                     Ok(buf)
@@ -284,3 +541,261 @@ pub enum FetchResult {
     NotModified(CacheExpiry),
     Modified(Vec<u8>, CacheExpiry),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::cached_client::USER_CACHE;
+
+    fn test_cache(name: &str) -> CacheManager {
+        let root = std::env::temp_dir().join(format!("riff-test-{}-{name}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        CacheManager {
+            root,
+            index: Default::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn expired_entries_are_served_when_ignoring_expiry() {
+        let cache = test_cache("hit");
+        cache
+            .write_cache_file(
+                "me_albums_0_30.json",
+                b"[]",
+                CacheExpiry::expire_in_seconds(0, None),
+            )
+            .await
+            .unwrap();
+
+        let file = cache
+            .read_cache_file("me_albums_0_30.json", CachePolicy::IgnoreExpiry)
+            .await
+            .unwrap();
+        assert!(matches!(file, CacheFile::Fresh(buf) if buf == b"[]"));
+    }
+
+    #[tokio::test]
+    async fn missing_entries_are_reported_as_such() {
+        let cache = test_cache("miss");
+
+        let file = cache
+            .read_cache_file("me_playlists_0_30.json", CachePolicy::IgnoreExpiry)
+            .await
+            .unwrap();
+        assert!(matches!(file, CacheFile::None));
+    }
+
+    #[tokio::test]
+    async fn clearing_user_cache_keeps_other_entries() {
+        let cache = test_cache("logout");
+        for resource in [
+            "me_albums_0_30.json",
+            "me_playlists_0_30.json",
+            "album_1.json",
+        ] {
+            cache
+                .write_cache_file(resource, b"{}", CacheExpiry::Never)
+                .await
+                .unwrap();
+        }
+
+        cache.clear_cache_pattern(&USER_CACHE).await.unwrap();
+
+        let albums = cache
+            .read_cache_file("me_albums_0_30.json", CachePolicy::IgnoreExpiry)
+            .await
+            .unwrap();
+        let playlists = cache
+            .read_cache_file("me_playlists_0_30.json", CachePolicy::IgnoreExpiry)
+            .await
+            .unwrap();
+        let album = cache
+            .read_cache_file("album_1.json", CachePolicy::IgnoreExpiry)
+            .await
+            .unwrap();
+        assert!(matches!(albums, CacheFile::None));
+        assert!(matches!(playlists, CacheFile::None));
+        assert!(matches!(album, CacheFile::Fresh(_)));
+    }
+
+    #[tokio::test]
+    async fn etag_is_sent_back_and_kept_when_not_modified() {
+        let cache = test_cache("not-modified");
+        let etag = Some("\"v1\"".to_string());
+        cache
+            .write_cache_file(
+                "album_1.json",
+                b"{}",
+                CacheExpiry::expire_in_seconds(0, etag.clone()),
+            )
+            .await
+            .unwrap();
+
+        let mut sent_etag = None;
+        let content = cache
+            .get_or_write("album_1.json", CachePolicy::Default, |etag| {
+                sent_etag = etag;
+                async {
+                    Ok::<_, CacheError>(FetchResult::NotModified(CacheExpiry::expire_in_seconds(
+                        60, None,
+                    )))
+                }
+            })
+            .await
+            .unwrap();
+        assert_eq!(sent_etag, etag);
+        assert_eq!(content, b"{}");
+
+        let expiry = cache.read_expiry_file("album_1.json").await.unwrap();
+        assert!(!expiry.is_expired());
+        assert_eq!(expiry.etag(), etag.as_ref());
+    }
+
+    #[tokio::test]
+    async fn new_etag_replaces_stored_entry() {
+        let cache = test_cache("modified");
+        cache
+            .write_cache_file(
+                "playlist_1.json",
+                b"[1]",
+                CacheExpiry::expire_in_seconds(0, Some("\"v1\"".to_string())),
+            )
+            .await
+            .unwrap();
+
+        let content = cache
+            .get_or_write("playlist_1.json", CachePolicy::Default, |_| async {
+                Ok::<_, CacheError>(FetchResult::Modified(
+                    b"[2]".to_vec(),
+                    CacheExpiry::expire_in_seconds(60, Some("\"v2\"".to_string())),
+                ))
+            })
+            .await
+            .unwrap();
+        assert_eq!(content, b"[2]");
+
+        let file = cache
+            .read_cache_file("playlist_1.json", CachePolicy::Default)
+            .await
+            .unwrap();
+        assert!(matches!(file, CacheFile::Fresh(buf) if buf == b"[2]"));
+        let expiry = cache.read_expiry_file("playlist_1.json").await.unwrap();
+        assert_eq!(expiry.etag().map(String::as_str), Some("\"v2\""));
+    }
+
+    #[tokio::test]
+    async fn no_etag_is_sent_when_none_was_stored() {
+        let cache = test_cache("no-etag");
+        cache
+            .write_cache_file(
+                "artist_1.json",
+                b"{}",
+                CacheExpiry::expire_in_seconds(0, None),
+            )
+            .await
+            .unwrap();
+
+        let mut sent_etag = Some(String::new());
+        cache
+            .get_or_write("artist_1.json", CachePolicy::Default, |etag| {
+                sent_etag = etag;
+                async {
+                    Ok::<_, CacheError>(FetchResult::NotModified(CacheExpiry::expire_in_seconds(
+                        60, None,
+                    )))
+                }
+            })
+            .await
+            .unwrap();
+        assert_eq!(sent_etag, None);
+    }
+
+    #[tokio::test]
+    async fn least_recently_used_entries_are_evicted() {
+        let cache = test_cache("evict");
+        cache.set_max_size(Some(10));
+        for resource in ["album_1.json", "album_2.json"] {
+            cache
+                .write_cache_file(resource, b"[1,2]", CacheExpiry::Never)
+                .await
+                .unwrap();
+        }
+        cache
+            .read_cache_file("album_1.json", CachePolicy::IgnoreExpiry)
+            .await
+            .unwrap();
+
+        cache
+            .write_cache_file("album_3.json", b"[1,2]", CacheExpiry::Never)
+            .await
+            .unwrap();
+
+        for (resource, kept) in [
+            ("album_1.json", true),
+            ("album_2.json", false),
+            ("album_3.json", true),
+        ] {
+            let file = cache
+                .read_cache_file(resource, CachePolicy::IgnoreExpiry)
+                .await
+                .unwrap();
+            assert_eq!(matches!(file, CacheFile::Fresh(_)), kept, "{resource}");
+        }
+    }
+
+    #[tokio::test]
+    async fn expiry_files_count_towards_the_limit() {
+        let cache = test_cache("evict-expiry");
+        // Each entry takes 5 bytes, and 12 more for its expiry (the timestamp and the etag)
+        cache.set_max_size(Some(20));
+        for resource in ["album_1.json", "album_2.json"] {
+            cache
+                .write_cache_file(
+                    resource,
+                    b"[1,2]",
+                    CacheExpiry::expire_in_seconds(60, Some("\"v1\"".to_string())),
+                )
+                .await
+                .unwrap();
+        }
+
+        let first = cache
+            .read_cache_file("album_1.json", CachePolicy::IgnoreExpiry)
+            .await
+            .unwrap();
+        let second = cache
+            .read_cache_file("album_2.json", CachePolicy::IgnoreExpiry)
+            .await
+            .unwrap();
+        assert!(matches!(first, CacheFile::None));
+        assert!(!cache.cache_meta_path("album_1.json").exists());
+        assert!(matches!(second, CacheFile::Fresh(_)));
+    }
+
+    #[test]
+    fn entries_being_written_are_never_evicted() {
+        let mut index = CacheIndex {
+            max_size: Some(10),
+            ..Default::default()
+        };
+        index.touch("album_1.json", 8);
+        index.touch("album_2.json", 8);
+        index.start_writing("album_1.json");
+
+        assert_eq!(index.take_victims(), vec!["album_2.json".to_string()]);
+        assert!(index.take_victims().is_empty());
+    }
+
+    #[tokio::test]
+    async fn clearing_reports_freed_space() {
+        let cache = test_cache("clear");
+        cache
+            .write_cache_file("album_1.json", b"[1,2]", CacheExpiry::Never)
+            .await
+            .unwrap();
+
+        assert_eq!(cache.clear().await.unwrap(), 5);
+        assert_eq!(cache.size(), 0);
+    }
+}
@@ -1,16 +1,19 @@
-use futures::future::BoxFuture;
-use futures::{join, FutureExt};
+use futures::future::{try_join_all, BoxFuture};
+use futures::join;
 use regex::Regex;
 use serde::de::DeserializeOwned;
 use serde_json::from_slice;
+use std::collections::HashSet;
 use std::convert::Into;
 use std::future::Future;
 use std::sync::Arc;
 
-use super::cache::{CacheExpiry, CacheManager, CachePolicy, FetchResult};
+use super::cache::{CacheExpiry, CacheFile, CacheManager, CachePolicy, FetchResult};
 use super::client::*;
+use super::contains_batch::ContainsBatch;
+use super::in_flight::InFlight;
 use crate::app::models::*;
-use crate::player::TokenStore;
+use crate::player::RiffOauthClient;
 
 pub type SpotifyResult<T> = Result<T, SpotifyApiError>;
 
@@ -19,6 +22,9 @@ pub trait SpotifyApiClient {
 
     fn get_album(&self, id: &str) -> BoxFuture<SpotifyResult<AlbumFullDescription>>;
 
+    // Same as get_album, but never hits the network: whatever was cached, even if it expired
+    fn get_cached_album(&self, id: &str) -> BoxFuture<SpotifyResult<AlbumFullDescription>>;
+
     fn get_album_tracks(
         &self,
         id: &str,
@@ -28,6 +34,8 @@ pub trait SpotifyApiClient {
 
     fn get_playlist(&self, id: &str) -> BoxFuture<SpotifyResult<PlaylistDescription>>;
 
+    fn get_cached_playlist(&self, id: &str) -> BoxFuture<SpotifyResult<PlaylistDescription>>;
+
     fn get_playlist_tracks(
         &self,
         id: &str,
@@ -35,18 +43,37 @@ pub trait SpotifyApiClient {
         limit: usize,
     ) -> BoxFuture<SpotifyResult<SongBatch>>;
 
+    fn get_cached_playlist_tracks(
+        &self,
+        id: &str,
+        offset: usize,
+        limit: usize,
+    ) -> BoxFuture<SpotifyResult<SongBatch>>;
+
     fn get_saved_albums(
         &self,
         offset: usize,
         limit: usize,
     ) -> BoxFuture<SpotifyResult<Vec<AlbumDescription>>>;
 
+    // Same as get_saved_albums, but never hits the network: fails with NoContent if nothing was cached
+    fn get_cached_saved_albums(
+        &self,
+        offset: usize,
+        limit: usize,
+    ) -> BoxFuture<SpotifyResult<Vec<AlbumDescription>>>;
+
     fn get_saved_tracks(&self, offset: usize, limit: usize) -> BoxFuture<SpotifyResult<SongBatch>>;
 
+    fn get_tracks(&self, ids: Vec<String>) -> BoxFuture<SpotifyResult<Vec<SongDescription>>>;
+
     fn save_album(&self, id: &str) -> BoxFuture<SpotifyResult<AlbumDescription>>;
 
     fn save_tracks(&self, ids: Vec<String>) -> BoxFuture<SpotifyResult<()>>;
 
+    // Same order as the ids
+    fn are_tracks_saved(&self, ids: Vec<String>) -> BoxFuture<SpotifyResult<Vec<bool>>>;
+
     fn remove_saved_album(&self, id: &str) -> BoxFuture<SpotifyResult<()>>;
 
     fn remove_saved_tracks(&self, ids: Vec<String>) -> BoxFuture<SpotifyResult<()>>;
@@ -57,6 +84,12 @@ pub trait SpotifyApiClient {
         limit: usize,
     ) -> BoxFuture<SpotifyResult<Vec<PlaylistDescription>>>;
 
+    fn get_cached_saved_playlists(
+        &self,
+        offset: usize,
+        limit: usize,
+    ) -> BoxFuture<SpotifyResult<Vec<PlaylistDescription>>>;
+
     fn add_to_playlist(&self, id: &str, uris: Vec<String>) -> BoxFuture<SpotifyResult<()>>;
 
     fn create_new_playlist(
@@ -74,10 +107,17 @@ pub trait SpotifyApiClient {
     fn search(
         &self,
         query: &str,
+        category: SearchCategory,
         offset: usize,
         limit: usize,
     ) -> BoxFuture<SpotifyResult<SearchResults>>;
 
+    fn search_tracks(
+        &self,
+        query: &str,
+        limit: usize,
+    ) -> BoxFuture<SpotifyResult<Vec<SongDescription>>>;
+
     fn get_artist_albums(
         &self,
         id: &str,
@@ -85,8 +125,73 @@ pub trait SpotifyApiClient {
         limit: usize,
     ) -> BoxFuture<SpotifyResult<Vec<AlbumDescription>>>;
 
+    // Tracks picked by Spotify from an artist, not cached since it's never quite the same
+    fn get_artist_radio(
+        &self,
+        id: &str,
+        limit: usize,
+    ) -> BoxFuture<SpotifyResult<Vec<SongDescription>>>;
+
+    // Other artists listened to by this artist's listeners
+    fn get_related_artists(&self, id: &str) -> BoxFuture<SpotifyResult<Vec<ArtistSummary>>>;
+
+    fn is_artist_followed(&self, id: &str) -> BoxFuture<SpotifyResult<bool>>;
+
+    // Returns the artist that was just followed, so that it can be added to the library
+    // Just enough to list it with the followed artists
+    fn get_artist_summary(&self, id: &str) -> BoxFuture<SpotifyResult<ArtistSummary>>;
+
+    fn follow_artist(&self, id: &str) -> BoxFuture<SpotifyResult<()>>;
+
+    fn unfollow_artist(&self, id: &str) -> BoxFuture<SpotifyResult<()>>;
+
+    fn get_followed_artists(
+        &self,
+        after: Option<String>,
+        limit: usize,
+    ) -> BoxFuture<SpotifyResult<FollowedArtistsPage>>;
+
+    fn get_new_releases(
+        &self,
+        offset: usize,
+        limit: usize,
+    ) -> BoxFuture<SpotifyResult<Vec<AlbumDescription>>>;
+
+    fn get_top_tracks(
+        &self,
+        range: TopRange,
+        limit: usize,
+    ) -> BoxFuture<SpotifyResult<Vec<SongDescription>>>;
+
+    fn get_top_artists(
+        &self,
+        range: TopRange,
+        limit: usize,
+    ) -> BoxFuture<SpotifyResult<Vec<ArtistSummary>>>;
+
+    // Albums and playlists that tracks were recently played from, most recent first
+    fn get_recently_played(&self, limit: usize) -> BoxFuture<SpotifyResult<Vec<RecentlyPlayed>>>;
+
+    fn get_saved_shows(
+        &self,
+        offset: usize,
+        limit: usize,
+    ) -> BoxFuture<SpotifyResult<Vec<ShowDescription>>>;
+
+    fn get_show(&self, id: &str) -> BoxFuture<SpotifyResult<ShowDescription>>;
+
+    fn get_show_episodes(
+        &self,
+        id: &str,
+        offset: usize,
+        limit: usize,
+    ) -> BoxFuture<SpotifyResult<Vec<EpisodeDescription>>>;
+
     fn get_user(&self, id: &str) -> BoxFuture<SpotifyResult<UserDescription>>;
 
+    // Whether the account settings block explicit content
+    fn is_explicit_content_blocked(&self) -> BoxFuture<SpotifyResult<bool>>;
+
     fn get_user_playlists(
         &self,
         id: &str,
@@ -134,6 +239,14 @@ enum RiffCacheKey<'a> {
     SavedAlbums(usize, usize),
     SavedTracks(usize, usize),
     SavedPlaylists(usize, usize),
+    FollowedArtists(Option<&'a str>, usize),
+    SavedShows(usize, usize),
+    RecentlyPlayed(usize),
+    NewReleases(usize, usize),
+    TopTracks(&'a str, usize),
+    TopArtists(&'a str, usize),
+    // The query is hashed, it could be anything
+    Search(&'a str, &'a str, usize, usize),
     Album(&'a str),
     AlbumLiked(&'a str),
     AlbumTracks(&'a str, usize, usize),
@@ -142,16 +255,39 @@ enum RiffCacheKey<'a> {
     ArtistAlbums(&'a str, usize, usize),
     Artist(&'a str),
     ArtistTopTracks(&'a str),
+    RelatedArtists(&'a str),
+    Show(&'a str),
+    ShowEpisodes(&'a str, usize, usize),
     User(&'a str),
     UserPlaylists(&'a str, usize, usize),
 }
 
+// FNV-1a: unlike std's hashers, it gives the same result from one release to the next,
+// which matters for file names that outlive the process
+fn stable_hash(s: &str) -> u64 {
+    s.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
 impl RiffCacheKey<'_> {
     fn into_raw(self) -> String {
         match self {
             Self::SavedAlbums(offset, limit) => format!("me_albums_{offset}_{limit}.json"),
             Self::SavedTracks(offset, limit) => format!("me_tracks_{offset}_{limit}.json"),
             Self::SavedPlaylists(offset, limit) => format!("me_playlists_{offset}_{limit}.json"),
+            Self::FollowedArtists(after, limit) => {
+                format!("me_artists_{}_{limit}.json", after.unwrap_or("start"))
+            }
+            Self::SavedShows(offset, limit) => format!("me_shows_{offset}_{limit}.json"),
+            Self::RecentlyPlayed(limit) => format!("me_recent_played_{limit}.json"),
+            Self::NewReleases(offset, limit) => format!("new_releases_{offset}_{limit}.json"),
+            Self::TopTracks(range, limit) => format!("me_top_tracks_{range}_{limit}.json"),
+            Self::TopArtists(range, limit) => format!("me_top_artists_{range}_{limit}.json"),
+            Self::Search(query, kind, offset, limit) => {
+                let query = stable_hash(query);
+                format!("search_{query:x}_{kind}_{offset}_{limit}.json")
+            }
             Self::Album(id) => format!("album_{id}.json"),
             Self::AlbumTracks(id, offset, limit) => {
                 format!("album_item_{id}_{offset}_{limit}.json")
@@ -166,6 +302,11 @@ impl RiffCacheKey<'_> {
             }
             Self::Artist(id) => format!("artist_{id}.json"),
             Self::ArtistTopTracks(id) => format!("artist_top_tracks_{id}.json"),
+            Self::RelatedArtists(id) => format!("artist_related_{id}.json"),
+            Self::Show(id) => format!("show_{id}.json"),
+            Self::ShowEpisodes(id, offset, limit) => {
+                format!("show_episodes_{id}_{offset}_{limit}.json")
+            }
             Self::User(id) => format!("user_{id}.json"),
             Self::UserPlaylists(id, offset, limit) => {
                 format!("user_playlists_{id}_{offset}_{limit}.json")
@@ -179,24 +320,44 @@ lazy_static! {
     pub static ref ME_ALBUMS_CACHE: Regex = Regex::new(r"^me_albums_\w+_\w+\.json$").unwrap();
     pub static ref ME_PLAYLISTS_CACHE: Regex =
         Regex::new(r"^me_playlists_\w+_\w+\.json$").unwrap();
+    pub static ref ME_ARTISTS_CACHE: Regex = Regex::new(r"^me_artists_\w+_\w+\.json$").unwrap();
     pub static ref USER_CACHE: Regex =
-        Regex::new(r"^me_(albums|playlists|tracks)_\w+_\w+\.json$").unwrap();
+        Regex::new(r"^me_(albums|artists|playlists|recent|shows|top|tracks)_\w+_\w+\.json$")
+            .unwrap();
 }
 
+// Spotify won't let us cache that at all, but asking again every time the library is shown is a bit much
+const RECENTLY_PLAYED_MAX_AGE: u64 = 60;
+
+// Top items are computed by Spotify about once a day, no need to ask more than once an hour
+const TOP_ITEMS_MAX_AGE: u64 = 60 * 60;
+
+// Typing the same thing again shouldn't cost a request, but results do change eventually
+const SEARCH_MAX_AGE: u64 = 10 * 60;
+
+// Never more than that, each playlist in there is one more request
+const RECENTLY_PLAYED_COUNT: usize = 12;
+
 fn playlist_cache_key(id: &str) -> Regex {
     Regex::new(&format!(r"^playlist(_{id}|item_{id}_\w+_\w+)\.json$")).unwrap()
 }
 
 pub struct CachedSpotifyClient {
-    client: SpotifyClient,
-    cache: CacheManager,
+    // Shared with the requests being sent from their own task
+    client: Arc<SpotifyClient>,
+    cache: Arc<CacheManager>,
+    // Raw responses being fetched, by cache key
+    in_flight: InFlight<Vec<u8>>,
+    saved_tracks: ContainsBatch,
 }
 
 impl CachedSpotifyClient {
-    pub fn new(client: TokenStore) -> CachedSpotifyClient {
+    pub fn new(oauth_client: Arc<RiffOauthClient>) -> CachedSpotifyClient {
         CachedSpotifyClient {
-            client: SpotifyClient::new(client),
-            cache: CacheManager::for_dir("riff/net").unwrap(),
+            client: Arc::new(SpotifyClient::new(oauth_client)),
+            cache: Arc::new(CacheManager::for_dir(super::API_CACHE_DIR).unwrap()),
+            in_flight: InFlight::default(),
+            saved_tracks: ContainsBatch::default(),
         }
     }
 
@@ -209,50 +370,75 @@ impl CachedSpotifyClient {
         }
     }
 
-    async fn wrap_write<T, O, F>(write: &F, etag: Option<String>) -> SpotifyResult<FetchResult>
+    // max_age is for responses Spotify tells us not to cache, when we know they don't change that often
+    async fn fetch(
+        client: &SpotifyClient,
+        request: &DetachedRequest,
+        max_age: u64,
+        etag: Option<String>,
+    ) -> SpotifyResult<FetchResult> {
+        let response = request.attach::<()>(client).etag(etag).send().await?;
+        let expiry = CacheExpiry::expire_in_seconds(response.max_age.max(max_age), response.etag);
+        Ok(match response.kind {
+            SpotifyResponseKind::Ok(content, _) => {
+                debug!("Did not hit cache");
+                FetchResult::Modified(content.into_bytes(), expiry)
+            }
+            SpotifyResponseKind::NotModified => FetchResult::NotModified(expiry),
+        })
+    }
+
+    // Doesn't borrow self, so that it can be sent from its own task
+    fn get_or_write(
+        &self,
+        cache_key: String,
+        cache_policy: CachePolicy,
+        request: DetachedRequest,
+        max_age: u64,
+    ) -> impl Future<Output = SpotifyResult<Vec<u8>>> + Send + 'static {
+        let client = Arc::clone(&self.client);
+        let cache = Arc::clone(&self.cache);
+        async move {
+            cache
+                .get_or_write(&cache_key, cache_policy, |etag| {
+                    Self::fetch(&client, &request, max_age, etag)
+                })
+                .await
+        }
+    }
+
+    async fn cache_get_or_write<T>(
+        &self,
+        key: RiffCacheKey<'_>,
+        cache_policy: Option<CachePolicy>,
+        request: SpotifyRequest<'_, (), T>,
+    ) -> SpotifyResult<T>
     where
-        O: Future<Output = SpotifyResult<SpotifyResponse<T>>>,
-        F: Fn(Option<String>) -> O,
+        T: DeserializeOwned,
     {
-        write(etag)
-            .map(|r| {
-                let SpotifyResponse {
-                    kind,
-                    max_age,
-                    etag,
-                } = r?;
-                let expiry = CacheExpiry::expire_in_seconds(max_age, etag);
-                SpotifyResult::Ok(match kind {
-                    SpotifyResponseKind::Ok(content, _) => {
-                        debug!("Did not hit cache");
-                        FetchResult::Modified(content.into_bytes(), expiry)
-                    }
-                    SpotifyResponseKind::NotModified => FetchResult::NotModified(expiry),
-                })
-            })
+        self.cache_get_or_write_with_max_age(key, cache_policy, request, 0)
             .await
     }
 
-    async fn cache_get_or_write<T, O, F>(
+    async fn cache_get_or_write_with_max_age<T>(
         &self,
         key: RiffCacheKey<'_>,
         cache_policy: Option<CachePolicy>,
-        write: F,
+        request: SpotifyRequest<'_, (), T>,
+        max_age: u64,
     ) -> SpotifyResult<T>
     where
-        O: Future<Output = SpotifyResult<SpotifyResponse<T>>>,
-        F: Fn(Option<String>) -> O,
         T: DeserializeOwned,
     {
-        let write = &write;
+        let request = request.detach();
         let cache_key = key.into_raw();
+        let cache_policy = cache_policy.unwrap_or_else(|| self.default_cache_policy());
+        // Whoever asks for the same thing (and the same way) while it's being fetched gets the same response
         let raw = self
-            .cache
-            .get_or_write(
-                &cache_key,
-                cache_policy.unwrap_or_else(|| self.default_cache_policy()),
-                |etag| Self::wrap_write(write, etag),
-            )
+            .in_flight
+            .coalesce(&format!("{cache_key}_{cache_policy:?}"), || {
+                self.get_or_write(cache_key.clone(), cache_policy, request.clone(), max_age)
+            })
             .await?;
 
         let result = from_slice::<T>(&raw);
@@ -262,15 +448,27 @@ impl CachedSpotifyClient {
             Err(e) => {
                 dbg!(&cache_key, e);
                 let new_raw = self
-                    .cache
-                    .get_or_write(&cache_key, CachePolicy::IgnoreCached, |etag| {
-                        Self::wrap_write(write, etag)
-                    })
+                    .get_or_write(cache_key, CachePolicy::IgnoreCached, request, max_age)
                     .await?;
                 Ok(from_slice::<T>(&new_raw)?)
             }
         }
     }
+
+    async fn cache_get<T>(&self, key: RiffCacheKey<'_>) -> SpotifyResult<T>
+    where
+        T: DeserializeOwned,
+    {
+        let cache_key = key.into_raw();
+        match self
+            .cache
+            .read_cache_file(&cache_key, CachePolicy::IgnoreExpiry)
+            .await?
+        {
+            CacheFile::Fresh(raw) | CacheFile::Expired(raw, _) => Ok(from_slice::<T>(&raw)?),
+            CacheFile::None => Err(SpotifyApiError::NoContent),
+        }
+    }
 }
 
 impl SpotifyApiClient for CachedSpotifyClient {
@@ -281,12 +479,11 @@ impl SpotifyApiClient for CachedSpotifyClient {
     ) -> BoxFuture<SpotifyResult<Vec<AlbumDescription>>> {
         Box::pin(async move {
             let page = self
-                .cache_get_or_write(RiffCacheKey::SavedAlbums(offset, limit), None, |etag| {
-                    self.client
-                        .get_saved_albums(offset, limit)
-                        .etag(etag)
-                        .send()
-                })
+                .cache_get_or_write(
+                    RiffCacheKey::SavedAlbums(offset, limit),
+                    None,
+                    self.client.get_saved_albums(offset, limit),
+                )
                 .await?;
 
             let albums = page
@@ -298,21 +495,52 @@ impl SpotifyApiClient for CachedSpotifyClient {
         })
     }
 
+    fn get_cached_saved_albums(
+        &self,
+        offset: usize,
+        limit: usize,
+    ) -> BoxFuture<SpotifyResult<Vec<AlbumDescription>>> {
+        Box::pin(async move {
+            let page: Page<SavedAlbum> = self
+                .cache_get(RiffCacheKey::SavedAlbums(offset, limit))
+                .await?;
+
+            Ok(page.into_iter().map(|saved| saved.album.into()).collect())
+        })
+    }
+
     fn get_saved_tracks(&self, offset: usize, limit: usize) -> BoxFuture<SpotifyResult<SongBatch>> {
         Box::pin(async move {
             let page = self
-                .cache_get_or_write(RiffCacheKey::SavedTracks(offset, limit), None, |etag| {
-                    self.client
-                        .get_saved_tracks(offset, limit)
-                        .etag(etag)
-                        .send()
-                })
+                .cache_get_or_write(
+                    RiffCacheKey::SavedTracks(offset, limit),
+                    None,
+                    self.client.get_saved_tracks(offset, limit),
+                )
                 .await?;
 
             Ok(page.into())
         })
     }
 
+    fn get_tracks(&self, ids: Vec<String>) -> BoxFuture<SpotifyResult<Vec<SongDescription>>> {
+        Box::pin(async move {
+            let mut songs = Vec::with_capacity(ids.len());
+            // That's as many as the API lets us ask for at once
+            for chunk in ids.chunks(50) {
+                let tracks = self
+                    .client
+                    .get_tracks(chunk)
+                    .send()
+                    .await?
+                    .deserialize()
+                    .ok_or(SpotifyApiError::NoContent)?;
+                songs.extend(Vec::<SongDescription>::from(tracks));
+            }
+            Ok(songs)
+        })
+    }
+
     fn get_saved_playlists(
         &self,
         offset: usize,
@@ -320,12 +548,11 @@ impl SpotifyApiClient for CachedSpotifyClient {
     ) -> BoxFuture<SpotifyResult<Vec<PlaylistDescription>>> {
         Box::pin(async move {
             let page = self
-                .cache_get_or_write(RiffCacheKey::SavedPlaylists(offset, limit), None, |etag| {
-                    self.client
-                        .get_saved_playlists(offset, limit)
-                        .etag(etag)
-                        .send()
-                })
+                .cache_get_or_write(
+                    RiffCacheKey::SavedPlaylists(offset, limit),
+                    None,
+                    self.client.get_saved_playlists(offset, limit),
+                )
                 .await?;
 
             let albums = page
@@ -337,6 +564,20 @@ impl SpotifyApiClient for CachedSpotifyClient {
         })
     }
 
+    fn get_cached_saved_playlists(
+        &self,
+        offset: usize,
+        limit: usize,
+    ) -> BoxFuture<SpotifyResult<Vec<PlaylistDescription>>> {
+        Box::pin(async move {
+            let page: Page<Playlist> = self
+                .cache_get(RiffCacheKey::SavedPlaylists(offset, limit))
+                .await?;
+
+            Ok(page.into_iter().map(|playlist| playlist.into()).collect())
+        })
+    }
+
     fn add_to_playlist(&self, id: &str, uris: Vec<String>) -> BoxFuture<SpotifyResult<()>> {
         let id = id.to_owned();
 
@@ -429,9 +670,8 @@ impl SpotifyApiClient for CachedSpotifyClient {
         let id = id.to_owned();
 
         Box::pin(async move {
-            let album = self.cache_get_or_write(RiffCacheKey::Album(&id), None, |etag| {
-                self.client.get_album(&id).etag(etag).send()
-            });
+            let album =
+                self.cache_get_or_write(RiffCacheKey::Album(&id), None, self.client.get_album(&id));
 
             let liked = self.cache_get_or_write(
                 RiffCacheKey::AlbumLiked(&id),
@@ -440,7 +680,7 @@ impl SpotifyApiClient for CachedSpotifyClient {
                 } else {
                     CachePolicy::IgnoreExpiry
                 }),
-                |etag| self.client.is_album_saved(&id).etag(etag).send(),
+                self.client.is_album_saved(&id),
             );
 
             let (album, liked) = join!(album, liked);
@@ -452,6 +692,24 @@ impl SpotifyApiClient for CachedSpotifyClient {
         })
     }
 
+    fn get_cached_album(&self, id: &str) -> BoxFuture<SpotifyResult<AlbumFullDescription>> {
+        let id = id.to_owned();
+
+        Box::pin(async move {
+            let album: FullAlbum = self.cache_get(RiffCacheKey::Album(&id)).await?;
+            // It'll be fixed by the next actual request if we got that wrong
+            let liked: Vec<bool> = self
+                .cache_get(RiffCacheKey::AlbumLiked(&id))
+                .await
+                .unwrap_or_default();
+
+            let mut album: AlbumFullDescription = album.into();
+            album.description.is_liked = liked.first().copied().unwrap_or(false);
+
+            Ok(album)
+        })
+    }
+
     fn save_album(&self, id: &str) -> BoxFuture<SpotifyResult<AlbumDescription>> {
         let id = id.to_owned();
 
@@ -470,6 +728,21 @@ impl SpotifyApiClient for CachedSpotifyClient {
         })
     }
 
+    // Not cached, those come and go. Each id goes in a batch, along with the ids other rows asked about.
+    fn are_tracks_saved(&self, ids: Vec<String>) -> BoxFuture<SpotifyResult<Vec<bool>>> {
+        Box::pin(async move {
+            let check = move |ids: Vec<String>| async move {
+                self.client
+                    .are_tracks_saved(&ids)
+                    .send()
+                    .await?
+                    .deserialize()
+                    .ok_or(SpotifyApiError::NoContent)
+            };
+            try_join_all(ids.iter().map(|id| self.saved_tracks.contains(id, check))).await
+        })
+    }
+
     fn remove_saved_album(&self, id: &str) -> BoxFuture<SpotifyResult<()>> {
         let id = id.to_owned();
 
@@ -501,18 +774,13 @@ impl SpotifyApiClient for CachedSpotifyClient {
             let album = self.cache_get_or_write(
                 RiffCacheKey::Album(&id),
                 Some(CachePolicy::IgnoreExpiry),
-                |etag| self.client.get_album(&id).etag(etag).send(),
+                self.client.get_album(&id),
             );
 
             let songs = self.cache_get_or_write(
                 RiffCacheKey::AlbumTracks(&id, offset, limit),
                 None,
-                |etag| {
-                    self.client
-                        .get_album_tracks(&id, offset, limit)
-                        .etag(etag)
-                        .send()
-                },
+                self.client.get_album_tracks(&id, offset, limit),
             );
 
             let (album, songs) = join!(album, songs);
@@ -525,15 +793,26 @@ impl SpotifyApiClient for CachedSpotifyClient {
 
         Box::pin(async move {
             let playlist = self
-                .cache_get_or_write(RiffCacheKey::Playlist(&id), None, |etag| {
-                    self.client.get_playlist(&id).etag(etag).send()
-                })
+                .cache_get_or_write(
+                    RiffCacheKey::Playlist(&id),
+                    None,
+                    self.client.get_playlist(&id),
+                )
                 .await?;
 
             Ok(playlist.into())
         })
     }
 
+    fn get_cached_playlist(&self, id: &str) -> BoxFuture<SpotifyResult<PlaylistDescription>> {
+        let id = id.to_owned();
+
+        Box::pin(async move {
+            let playlist: Playlist = self.cache_get(RiffCacheKey::Playlist(&id)).await?;
+            Ok(playlist.into())
+        })
+    }
+
     fn get_playlist_tracks(
         &self,
         id: &str,
@@ -547,12 +826,7 @@ impl SpotifyApiClient for CachedSpotifyClient {
                 .cache_get_or_write(
                     RiffCacheKey::PlaylistTracks(&id, offset, limit),
                     None,
-                    |etag| {
-                        self.client
-                            .get_playlist_tracks(&id, offset, limit)
-                            .etag(etag)
-                            .send()
-                    },
+                    self.client.get_playlist_tracks(&id, offset, limit),
                 )
                 .await?;
 
@@ -560,6 +834,22 @@ impl SpotifyApiClient for CachedSpotifyClient {
         })
     }
 
+    fn get_cached_playlist_tracks(
+        &self,
+        id: &str,
+        offset: usize,
+        limit: usize,
+    ) -> BoxFuture<SpotifyResult<SongBatch>> {
+        let id = id.to_owned();
+
+        Box::pin(async move {
+            let songs: Page<PlaylistTrack> = self
+                .cache_get(RiffCacheKey::PlaylistTracks(&id, offset, limit))
+                .await?;
+            Ok(songs.into())
+        })
+    }
+
     fn get_artist_albums(
         &self,
         id: &str,
@@ -573,12 +863,7 @@ impl SpotifyApiClient for CachedSpotifyClient {
                 .cache_get_or_write(
                     RiffCacheKey::ArtistAlbums(&id, offset, limit),
                     None,
-                    |etag| {
-                        self.client
-                            .get_artist_albums(&id, offset, limit)
-                            .etag(etag)
-                            .send()
-                    },
+                    self.client.get_artist_albums(&id, offset, limit),
                 )
                 .await?;
 
@@ -591,20 +876,43 @@ impl SpotifyApiClient for CachedSpotifyClient {
         })
     }
 
+    fn get_artist_radio(
+        &self,
+        id: &str,
+        limit: usize,
+    ) -> BoxFuture<SpotifyResult<Vec<SongDescription>>> {
+        let id = id.to_owned();
+
+        Box::pin(async move {
+            let recommendations = self
+                .client
+                .get_recommendations_for_artist(&id, limit)
+                .send()
+                .await?
+                .deserialize()
+                .ok_or(SpotifyApiError::NoContent)?;
+
+            Ok(recommendations.into())
+        })
+    }
+
     fn get_artist(&self, id: &str) -> BoxFuture<SpotifyResult<ArtistDescription>> {
         let id = id.to_owned();
 
         Box::pin(async move {
-            let artist = self.cache_get_or_write(RiffCacheKey::Artist(&id), None, |etag| {
-                self.client.get_artist(&id).etag(etag).send()
-            });
+            let artist = self.cache_get_or_write(
+                RiffCacheKey::Artist(&id),
+                None,
+                self.client.get_artist(&id),
+            );
 
             let albums = self.get_artist_albums(&id, 0, 20);
 
-            let top_tracks =
-                self.cache_get_or_write(RiffCacheKey::ArtistTopTracks(&id), None, |etag| {
-                    self.client.get_artist_top_tracks(&id).etag(etag).send()
-                });
+            let top_tracks = self.cache_get_or_write(
+                RiffCacheKey::ArtistTopTracks(&id),
+                None,
+                self.client.get_artist_top_tracks(&id),
+            );
 
             let (artist, albums, top_tracks) = join!(artist, albums, top_tracks);
 
@@ -622,19 +930,28 @@ impl SpotifyApiClient for CachedSpotifyClient {
     fn search(
         &self,
         query: &str,
+        category: SearchCategory,
         offset: usize,
         limit: usize,
     ) -> BoxFuture<SpotifyResult<SearchResults>> {
         let query = query.to_owned();
+        let kind = match category {
+            SearchCategory::Tracks => SearchType::Track,
+            SearchCategory::Albums => SearchType::Album,
+            SearchCategory::Artists => SearchType::Artist,
+            SearchCategory::Playlists => SearchType::Playlist,
+        };
 
         Box::pin(async move {
-            let results = self
-                .client
-                .search(query, offset, limit)
-                .send()
-                .await?
-                .deserialize()
-                .ok_or(SpotifyApiError::NoContent)?;
+            let key = RiffCacheKey::Search(&query, category.api_value(), offset, limit);
+            let results: RawSearchResults = self
+                .cache_get_or_write_with_max_age(
+                    key,
+                    None,
+                    self.client.search(query.clone(), kind, offset, limit),
+                    SEARCH_MAX_AGE,
+                )
+                .await?;
 
             let albums = results
                 .albums
@@ -650,7 +967,43 @@ impl SpotifyApiClient for CachedSpotifyClient {
                 .map(|saved| saved.into())
                 .collect::<Vec<ArtistSummary>>();
 
-            Ok(SearchResults { albums, artists })
+            let tracks: Vec<SongDescription> =
+                results.tracks.map(|page| page.into()).unwrap_or_default();
+
+            let playlists = results
+                .playlists
+                .unwrap_or_default()
+                .into_iter()
+                .flatten()
+                .map(|playlist| playlist.into())
+                .collect::<Vec<PlaylistDescription>>();
+
+            Ok(SearchResults {
+                albums,
+                artists,
+                tracks,
+                playlists,
+            })
+        })
+    }
+
+    fn search_tracks(
+        &self,
+        query: &str,
+        limit: usize,
+    ) -> BoxFuture<SpotifyResult<Vec<SongDescription>>> {
+        let query = query.to_owned();
+
+        Box::pin(async move {
+            let results = self
+                .client
+                .search_tracks(query, limit)
+                .send()
+                .await?
+                .deserialize()
+                .ok_or(SpotifyApiError::NoContent)?;
+
+            Ok(results.tracks.unwrap_or_default().into())
         })
     }
 
@@ -667,12 +1020,7 @@ impl SpotifyApiClient for CachedSpotifyClient {
                 .cache_get_or_write(
                     RiffCacheKey::UserPlaylists(&id, offset, limit),
                     None,
-                    |etag| {
-                        self.client
-                            .get_user_playlists(&id, offset, limit)
-                            .etag(etag)
-                            .send()
-                    },
+                    self.client.get_user_playlists(&id, offset, limit),
                 )
                 .await?;
 
@@ -685,13 +1033,252 @@ impl SpotifyApiClient for CachedSpotifyClient {
         })
     }
 
+    fn get_related_artists(&self, id: &str) -> BoxFuture<SpotifyResult<Vec<ArtistSummary>>> {
+        let id = id.to_owned();
+
+        Box::pin(async move {
+            let related = self
+                .cache_get_or_write(
+                    RiffCacheKey::RelatedArtists(&id),
+                    None,
+                    self.client.get_related_artists(&id),
+                )
+                .await?;
+
+            Ok(related.artists.into_iter().map(|a| a.into()).collect())
+        })
+    }
+
+    fn is_artist_followed(&self, id: &str) -> BoxFuture<SpotifyResult<bool>> {
+        let id = id.to_owned();
+
+        Box::pin(async move {
+            let followed = self
+                .client
+                .is_artist_followed(&id)
+                .send()
+                .await?
+                .deserialize()
+                .ok_or(SpotifyApiError::NoContent)?;
+            Ok(followed.first().copied().unwrap_or(false))
+        })
+    }
+
+    fn get_artist_summary(&self, id: &str) -> BoxFuture<SpotifyResult<ArtistSummary>> {
+        let id = id.to_owned();
+
+        Box::pin(async move {
+            let artist = self
+                .cache_get_or_write(RiffCacheKey::Artist(&id), None, self.client.get_artist(&id))
+                .await?;
+            Ok(artist.into())
+        })
+    }
+
+    fn follow_artist(&self, id: &str) -> BoxFuture<SpotifyResult<()>> {
+        let id = id.to_owned();
+
+        Box::pin(async move {
+            self.client.follow_artist(&id).send_no_response().await?;
+            let _ = self.cache.set_expired_pattern(&ME_ARTISTS_CACHE).await;
+            Ok(())
+        })
+    }
+
+    fn unfollow_artist(&self, id: &str) -> BoxFuture<SpotifyResult<()>> {
+        let id = id.to_owned();
+
+        Box::pin(async move {
+            self.client.unfollow_artist(&id).send_no_response().await?;
+            let _ = self.cache.set_expired_pattern(&ME_ARTISTS_CACHE).await;
+            Ok(())
+        })
+    }
+
+    fn get_followed_artists(
+        &self,
+        after: Option<String>,
+        limit: usize,
+    ) -> BoxFuture<SpotifyResult<FollowedArtistsPage>> {
+        Box::pin(async move {
+            let after = after.as_deref();
+            let artists = self
+                .cache_get_or_write(
+                    RiffCacheKey::FollowedArtists(after, limit),
+                    None,
+                    self.client.get_followed_artists(after, limit),
+                )
+                .await?;
+
+            Ok(artists.into())
+        })
+    }
+
+    fn is_explicit_content_blocked(&self) -> BoxFuture<SpotifyResult<bool>> {
+        Box::pin(async move {
+            let user = self
+                .client
+                .get_current_user()
+                .send()
+                .await?
+                .deserialize()
+                .ok_or(SpotifyApiError::NoContent)?;
+            Ok(user
+                .explicit_content
+                .map(|c| c.filter_enabled)
+                .unwrap_or(false))
+        })
+    }
+
+    fn get_new_releases(
+        &self,
+        offset: usize,
+        limit: usize,
+    ) -> BoxFuture<SpotifyResult<Vec<AlbumDescription>>> {
+        Box::pin(async move {
+            let releases = self
+                .cache_get_or_write(
+                    RiffCacheKey::NewReleases(offset, limit),
+                    None,
+                    self.client.get_new_releases(offset, limit),
+                )
+                .await?;
+
+            Ok(releases.albums.into_iter().map(|a| a.into()).collect())
+        })
+    }
+
+    fn get_top_tracks(
+        &self,
+        range: TopRange,
+        limit: usize,
+    ) -> BoxFuture<SpotifyResult<Vec<SongDescription>>> {
+        Box::pin(async move {
+            let range = range.api_value();
+            let page = self
+                .cache_get_or_write_with_max_age(
+                    RiffCacheKey::TopTracks(range, limit),
+                    None,
+                    self.client.get_top_tracks(range, limit),
+                    TOP_ITEMS_MAX_AGE,
+                )
+                .await?;
+
+            Ok(page.into())
+        })
+    }
+
+    fn get_top_artists(
+        &self,
+        range: TopRange,
+        limit: usize,
+    ) -> BoxFuture<SpotifyResult<Vec<ArtistSummary>>> {
+        Box::pin(async move {
+            let range = range.api_value();
+            let page = self
+                .cache_get_or_write_with_max_age(
+                    RiffCacheKey::TopArtists(range, limit),
+                    None,
+                    self.client.get_top_artists(range, limit),
+                    TOP_ITEMS_MAX_AGE,
+                )
+                .await?;
+
+            Ok(page.into_iter().map(|a| a.into()).collect())
+        })
+    }
+
+    fn get_recently_played(&self, limit: usize) -> BoxFuture<SpotifyResult<Vec<RecentlyPlayed>>> {
+        Box::pin(async move {
+            let history: RecentlyPlayedTracks = self
+                .cache_get_or_write_with_max_age(
+                    RiffCacheKey::RecentlyPlayed(limit),
+                    None,
+                    self.client.get_recently_played(limit),
+                    RECENTLY_PLAYED_MAX_AGE,
+                )
+                .await?;
+
+            let mut seen = HashSet::new();
+            let mut played = vec![];
+            for played_from in history.items.into_iter().filter_map(|i| i.played_from()) {
+                if played.len() == RECENTLY_PLAYED_COUNT {
+                    break;
+                }
+                match played_from {
+                    PlayedFrom::Album(album) if seen.insert(album.id.clone()) => {
+                        played.push(RecentlyPlayed::Album(album.into()));
+                    }
+                    PlayedFrom::Playlist(id) if seen.insert(id.clone()) => {
+                        // It might be gone by now, or be someone else's private playlist
+                        if let Ok(playlist) = self.get_playlist(&id).await {
+                            played.push(RecentlyPlayed::Playlist(playlist));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Ok(played)
+        })
+    }
+
+    fn get_saved_shows(
+        &self,
+        offset: usize,
+        limit: usize,
+    ) -> BoxFuture<SpotifyResult<Vec<ShowDescription>>> {
+        Box::pin(async move {
+            let page = self
+                .cache_get_or_write(
+                    RiffCacheKey::SavedShows(offset, limit),
+                    None,
+                    self.client.get_saved_shows(offset, limit),
+                )
+                .await?;
+
+            Ok(page.into_iter().map(|saved| saved.show.into()).collect())
+        })
+    }
+
+    fn get_show(&self, id: &str) -> BoxFuture<SpotifyResult<ShowDescription>> {
+        let id = id.to_owned();
+
+        Box::pin(async move {
+            let show = self
+                .cache_get_or_write(RiffCacheKey::Show(&id), None, self.client.get_show(&id))
+                .await?;
+
+            Ok(show.into())
+        })
+    }
+
+    fn get_show_episodes(
+        &self,
+        id: &str,
+        offset: usize,
+        limit: usize,
+    ) -> BoxFuture<SpotifyResult<Vec<EpisodeDescription>>> {
+        let id = id.to_owned();
+
+        Box::pin(async move {
+            let page = self
+                .cache_get_or_write(
+                    RiffCacheKey::ShowEpisodes(&id, offset, limit),
+                    None,
+                    self.client.get_show_episodes(&id, offset, limit),
+                )
+                .await?;
+
+            Ok(page.into())
+        })
+    }
+
     fn get_user(&self, id: &str) -> BoxFuture<SpotifyResult<UserDescription>> {
         let id = id.to_owned();
 
         Box::pin(async move {
-            let user = self.cache_get_or_write(RiffCacheKey::User(&id), None, |etag| {
-                self.client.get_user(&id).etag(etag).send()
-            });
+            let user =
+                self.cache_get_or_write(RiffCacheKey::User(&id), None, self.client.get_user(&id));
 
             let playlists = self.get_user_playlists(&id, 0, 30);
 
@@ -847,8 +1434,15 @@ impl SpotifyApiClient for CachedSpotifyClient {
 #[cfg(test)]
 pub mod tests {
 
+    use super::RiffCacheKey;
     use crate::api::api_models::*;
 
+    #[test]
+    fn test_search_key_is_stable() {
+        let key = RiffCacheKey::Search("daft punk", "track", 0, 20).into_raw();
+        assert_eq!(key, "search_44f0ebfc50646adc_track_0_20.json");
+    }
+
     #[test]
     fn test_search_query() {
         let query = SearchQuery {
@@ -1,22 +1,30 @@
 use form_urlencoded::Serializer;
 use isahc::config::Configurable;
-use isahc::http::{method::Method, request::Builder, StatusCode, Uri};
-use isahc::{AsyncReadResponseExt, HttpClient, Request};
+use isahc::http::{method::Method, request::Builder, request::Parts, HeaderMap, StatusCode, Uri};
+use isahc::{AsyncReadResponseExt, Request};
 use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
 use serde::{de::Deserialize, Serialize};
 use serde_json::from_str;
 use std::convert::Into;
+use std::future::Future;
 use std::marker::PhantomData;
 use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 use thiserror::Error;
 
-use crate::player::TokenStore;
+use crate::player::{RiffOauthClient, SpotifyAuth, TokenStore};
 
 pub use super::api_models::*;
 use super::cache::CacheError;
+use super::proxy::ProxiedClient;
+use super::rate_limit::RateLimit;
+use super::retry::retry_transient;
+use super::token_refresh::TokenRefresh;
 
 const SPOTIFY_HOST: &str = "api.spotify.com";
+// When a 429 doesn't say how long to wait
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(5);
 
 // https://url.spec.whatwg.org/#path-percent-encode-set
 const PATH_ENCODE_SET: &AsciiSet = &CONTROLS
@@ -65,15 +73,6 @@ where
         self
     }
 
-    fn authenticated(mut self) -> Result<Self, SpotifyApiError> {
-        let token = self.client.token_store.get_cached_blocking();
-        let token = token.as_ref().ok_or(SpotifyApiError::NoToken)?;
-        self.request = self
-            .request
-            .header("Authorization", format!("Bearer {}", token.access_token));
-        Ok(self)
-    }
-
     pub(crate) fn etag(mut self, etag: Option<String>) -> Self {
         if let Some(etag) = etag {
             self.request = self.request.header("If-None-Match", etag);
@@ -99,29 +98,91 @@ where
         }
     }
 
-    pub(crate) async fn send(self) -> Result<SpotifyResponse<R>, SpotifyApiError> {
+    // The request might have to be sent twice if the token needs a refresh, so we keep its parts around
+    fn into_parts(self) -> (&'a SpotifyClient, Parts, B) {
         let Self {
             client,
             request,
             body,
             ..
-        } = self.authenticated()?;
-        client.send_req(request.body(body).unwrap()).await
+        } = self;
+        let (parts, _) = request.body(()).unwrap().into_parts();
+        (client, parts, body)
+    }
+}
+
+impl<'a, B, R> SpotifyRequest<'a, B, R>
+where
+    B: Into<isahc::AsyncBody> + Clone,
+{
+    pub(crate) async fn send(self) -> Result<SpotifyResponse<R>, SpotifyApiError> {
+        let (client, parts, body) = self.into_parts();
+        client
+            .send_with_retries(&parts.method, |token| {
+                client.send_req(authenticated_request(&parts, &token, body.clone()))
+            })
+            .await
     }
 
     pub(crate) async fn send_no_response(self) -> Result<(), SpotifyApiError> {
-        let Self {
-            client,
-            request,
-            body,
-            ..
-        } = self.authenticated()?;
+        let (client, parts, body) = self.into_parts();
         client
-            .send_req_no_response(request.body(body).unwrap())
+            .send_with_retries(&parts.method, |token| {
+                client.send_req_no_response(authenticated_request(&parts, &token, body.clone()))
+            })
             .await
     }
 }
 
+impl<'a, R> SpotifyRequest<'a, (), R> {
+    // Everything but the client, so that the request can be sent later on from anywhere
+    pub(crate) fn detach(self) -> DetachedRequest {
+        let (_, parts, _) = self.into_parts();
+        DetachedRequest {
+            method: parts.method,
+            uri: parts.uri,
+            headers: parts.headers,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct DetachedRequest {
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+}
+
+impl DetachedRequest {
+    pub(crate) fn attach<'a, R>(&self, client: &'a SpotifyClient) -> SpotifyRequest<'a, (), R> {
+        let mut request = Builder::new()
+            .method(self.method.clone())
+            .uri(self.uri.clone());
+        for (name, value) in self.headers.iter() {
+            request = request.header(name, value);
+        }
+        SpotifyRequest {
+            client,
+            request,
+            body: (),
+            _type: PhantomData,
+        }
+    }
+}
+
+fn authenticated_request<B>(parts: &Parts, token: &str, body: B) -> Request<B> {
+    let mut request = Request::builder()
+        .method(parts.method.clone())
+        .uri(parts.uri.clone());
+    for (name, value) in parts.headers.iter() {
+        request = request.header(name, value);
+    }
+    request
+        .header("Authorization", format!("Bearer {token}"))
+        .body(body)
+        .unwrap()
+}
+
 pub(crate) enum SpotifyResponseKind<T> {
     Ok(String, PhantomData<T>),
     NotModified,
@@ -156,8 +217,9 @@ pub enum SpotifyApiError {
     NoToken,
     #[error("No content from request")]
     NoContent,
-    #[error("Request rate exceeded")]
-    TooManyRequests,
+    // With how long Spotify wants us to wait
+    #[error("Rate limited, retry in {}s", .0.as_secs())]
+    RateLimited(Duration),
     #[error("Request failed ({0}): {1}")]
     BadStatus(u16, String),
     #[error(transparent)]
@@ -170,22 +232,61 @@ pub enum SpotifyApiError {
     ParseError(#[from] serde_json::Error),
     #[error(transparent)]
     ConversionError(#[from] std::string::FromUtf8Error),
+    // For errors that had to be copied, see duplicate()
+    #[error("{0}")]
+    Other(String),
+}
+
+impl SpotifyApiError {
+    // The network or Spotify having a bad moment, the same request might well work a bit later
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::ClientError(_) | Self::IoError(_) => true,
+            Self::BadStatus(status, _) => (500..600).contains(status),
+            _ => false,
+        }
+    }
+
+    // Errors can't be cloned, but the same one sometimes has to be handed to several callers:
+    // this keeps anything that can be matched on, the rest is only kept as a message
+    pub fn duplicate(&self) -> Self {
+        match self {
+            Self::InvalidToken => Self::InvalidToken,
+            Self::NoToken => Self::NoToken,
+            Self::NoContent => Self::NoContent,
+            Self::RateLimited(retry_after) => Self::RateLimited(*retry_after),
+            Self::BadStatus(status, message) => Self::BadStatus(*status, message.clone()),
+            Self::ClientError(e) => Self::ClientError(e.clone()),
+            Self::IoError(e) => Self::IoError(std::io::Error::new(e.kind(), e.to_string())),
+            e => Self::Other(e.to_string()),
+        }
+    }
 }
 
 pub(crate) struct SpotifyClient {
     token_store: TokenStore,
-    client: HttpClient,
+    token_refresh: TokenRefresh,
+    // Shared by all requests, Spotify doesn't care which one went over the limit
+    rate_limit: RateLimit,
+    client: ProxiedClient,
 }
 
 impl SpotifyClient {
-    pub(crate) fn new(token_store: TokenStore) -> Self {
-        let mut builder = HttpClient::builder();
-        if cfg!(debug_assertions) {
-            builder = builder.ssl_options(isahc::config::SslOption::DANGER_ACCEPT_INVALID_CERTS);
-        }
-        let client = builder.build().unwrap();
+    // The OAuth client is the player's, so that the token is only ever refreshed in one place
+    pub(crate) fn new(oauth_client: Arc<RiffOauthClient>) -> Self {
+        let client = ProxiedClient::with_config(|builder| {
+            if cfg!(debug_assertions) {
+                builder.ssl_options(isahc::config::SslOption::DANGER_ACCEPT_INVALID_CERTS)
+            } else {
+                builder
+            }
+        });
+        let token_store = oauth_client.token_store().clone();
+        let token_refresh = TokenRefresh::new(oauth_client);
         Self {
             token_store,
+            token_refresh,
+            rate_limit: RateLimit::default(),
             client,
         }
     }
@@ -203,6 +304,37 @@ impl SpotifyClient {
         self.token_store.get_cached_blocking().is_some()
     }
 
+    fn access_token(&self) -> Option<String> {
+        self.token_store
+            .get_cached_blocking()
+            .map(|token| token.access_token)
+    }
+
+    // Every request goes through the token refresh and the rate limit, but only GETs are
+    // sent again after a transient failure: saving an album twice would be a surprise
+    async fn send_with_retries<T, F, Fut>(
+        &self,
+        method: &Method,
+        send: F,
+    ) -> Result<T, SpotifyApiError>
+    where
+        F: Fn(String) -> Fut,
+        Fut: Future<Output = Result<T, SpotifyApiError>>,
+    {
+        let send = &send;
+        let send_once = move || {
+            self.rate_limit.send_with_retry(move || {
+                self.token_refresh
+                    .send_with_retry(move || self.access_token(), send)
+            })
+        };
+        if *method == Method::GET {
+            retry_transient(send_once).await
+        } else {
+            send_once().await
+        }
+    }
+
     fn parse_cache_control(cache_control: &str) -> Option<u64> {
         cache_control
             .split(',')
@@ -211,6 +343,17 @@ impl SpotifyClient {
             .and_then(|s| u64::from_str(s).ok())
     }
 
+    // Spotify sends a number of seconds (it could also be a date, which we don't bother with)
+    fn parse_retry_after<B>(response: &isahc::Response<B>) -> Duration {
+        response
+            .headers()
+            .get("retry-after")
+            .and_then(|header| header.to_str().ok())
+            .and_then(|s| u64::from_str(s.trim()).ok())
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_RETRY_AFTER)
+    }
+
     async fn send_req<B, T>(
         &self,
         request: Request<B>,
@@ -218,7 +361,7 @@ impl SpotifyClient {
     where
         B: Into<isahc::AsyncBody>,
     {
-        let mut result = self.client.send_async(request).await?;
+        let mut result = self.client.get().send_async(request).await?;
 
         let etag = result
             .headers()
@@ -240,7 +383,9 @@ impl SpotifyClient {
                 etag,
             }),
             StatusCode::UNAUTHORIZED => Err(SpotifyApiError::InvalidToken),
-            StatusCode::TOO_MANY_REQUESTS => Err(SpotifyApiError::TooManyRequests),
+            StatusCode::TOO_MANY_REQUESTS => Err(SpotifyApiError::RateLimited(
+                Self::parse_retry_after(&result),
+            )),
             StatusCode::NOT_MODIFIED => Ok(SpotifyResponse {
                 kind: SpotifyResponseKind::NotModified,
                 max_age: cache_control.unwrap_or(10),
@@ -260,10 +405,12 @@ impl SpotifyClient {
     where
         B: Into<isahc::AsyncBody>,
     {
-        let mut result = self.client.send_async(request).await?;
+        let mut result = self.client.get().send_async(request).await?;
         match result.status() {
             StatusCode::UNAUTHORIZED => Err(SpotifyApiError::InvalidToken),
-            StatusCode::TOO_MANY_REQUESTS => Err(SpotifyApiError::TooManyRequests),
+            StatusCode::TOO_MANY_REQUESTS => Err(SpotifyApiError::RateLimited(
+                Self::parse_retry_after(&result),
+            )),
             StatusCode::NOT_MODIFIED => Ok(()),
             s if s.is_success() => Ok(()),
             s => Err(SpotifyApiError::BadStatus(
@@ -312,6 +459,39 @@ impl SpotifyClient {
             .uri(format!("/v1/artists/{id}/top-tracks"), Some(&query))
     }
 
+    pub(crate) fn get_related_artists(&self, id: &str) -> SpotifyRequest<'_, (), RelatedArtists> {
+        self.request()
+            .method(Method::GET)
+            .uri(format!("/v1/artists/{id}/related-artists"), None)
+    }
+
+    pub(crate) fn get_recommendations_for_artist(
+        &self,
+        id: &str,
+        limit: usize,
+    ) -> SpotifyRequest<'_, (), Recommendations> {
+        let query = make_query_params()
+            .append_pair("seed_artists", id)
+            .append_pair("limit", &limit.to_string()[..])
+            .append_pair("market", "from_token")
+            .finish();
+
+        self.request()
+            .method(Method::GET)
+            .uri("/v1/recommendations".to_string(), Some(&query))
+    }
+
+    pub(crate) fn get_tracks(&self, ids: &[String]) -> SpotifyRequest<'_, (), Tracks> {
+        let query = make_query_params()
+            .append_pair("ids", &ids.join(","))
+            .append_pair("market", "from_token")
+            .finish();
+
+        self.request()
+            .method(Method::GET)
+            .uri("/v1/tracks".to_string(), Some(&query))
+    }
+
     pub(crate) fn is_album_saved(&self, id: &str) -> SpotifyRequest<'_, (), Vec<bool>> {
         let query = make_query_params().append_pair("ids", id).finish();
         self.request()
@@ -319,6 +499,64 @@ impl SpotifyClient {
             .uri("/v1/me/albums/contains".to_string(), Some(&query))
     }
 
+    pub(crate) fn are_tracks_saved(&self, ids: &[String]) -> SpotifyRequest<'_, (), Vec<bool>> {
+        let query = make_query_params()
+            .append_pair("ids", &ids.join(","))
+            .finish();
+        self.request()
+            .method(Method::GET)
+            .uri("/v1/me/tracks/contains".to_string(), Some(&query))
+    }
+
+    pub(crate) fn is_artist_followed(&self, id: &str) -> SpotifyRequest<'_, (), Vec<bool>> {
+        let query = make_query_params()
+            .append_pair("type", "artist")
+            .append_pair("ids", id)
+            .finish();
+        self.request()
+            .method(Method::GET)
+            .uri("/v1/me/following/contains".to_string(), Some(&query))
+    }
+
+    pub(crate) fn follow_artist(&self, id: &str) -> SpotifyRequest<'_, (), ()> {
+        let query = make_query_params()
+            .append_pair("type", "artist")
+            .append_pair("ids", id)
+            .finish();
+        self.request()
+            .method(Method::PUT)
+            .uri("/v1/me/following".to_string(), Some(&query))
+    }
+
+    pub(crate) fn unfollow_artist(&self, id: &str) -> SpotifyRequest<'_, (), ()> {
+        let query = make_query_params()
+            .append_pair("type", "artist")
+            .append_pair("ids", id)
+            .finish();
+        self.request()
+            .method(Method::DELETE)
+            .uri("/v1/me/following".to_string(), Some(&query))
+    }
+
+    pub(crate) fn get_followed_artists(
+        &self,
+        after: Option<&str>,
+        limit: usize,
+    ) -> SpotifyRequest<'_, (), RawFollowedArtists> {
+        let mut query = make_query_params();
+        query
+            .append_pair("type", "artist")
+            .append_pair("limit", &limit.to_string()[..]);
+        if let Some(after) = after {
+            query.append_pair("after", after);
+        }
+        let query = query.finish();
+
+        self.request()
+            .method(Method::GET)
+            .uri("/v1/me/following".to_string(), Some(&query))
+    }
+
     pub(crate) fn save_album(&self, id: &str) -> SpotifyRequest<'_, (), ()> {
         let query = make_query_params().append_pair("ids", id).finish();
         self.request()
@@ -348,9 +586,13 @@ impl SpotifyClient {
     }
 
     pub(crate) fn get_album(&self, id: &str) -> SpotifyRequest<'_, (), FullAlbum> {
+        let query = make_query_params()
+            .append_pair("market", "from_token")
+            .finish();
+
         self.request()
             .method(Method::GET)
-            .uri(format!("/v1/albums/{id}"), None)
+            .uri(format!("/v1/albums/{id}"), Some(&query))
     }
 
     pub(crate) fn get_album_tracks(
@@ -362,6 +604,7 @@ impl SpotifyClient {
         let query = make_query_params()
             .append_pair("offset", &offset.to_string()[..])
             .append_pair("limit", &limit.to_string()[..])
+            .append_pair("market", "from_token")
             .finish();
 
         self.request()
@@ -471,6 +714,7 @@ impl SpotifyClient {
         let query = make_query_params()
             .append_pair("offset", &offset.to_string()[..])
             .append_pair("limit", &limit.to_string()[..])
+            .append_pair("market", "from_token")
             .finish();
 
         self.request()
@@ -496,12 +740,13 @@ impl SpotifyClient {
     pub(crate) fn search(
         &self,
         query: String,
+        kind: SearchType,
         offset: usize,
         limit: usize,
     ) -> SpotifyRequest<'_, (), RawSearchResults> {
         let query = SearchQuery {
             query,
-            types: vec![SearchType::Album, SearchType::Artist],
+            types: vec![kind],
             limit,
             offset,
         };
@@ -511,6 +756,131 @@ impl SpotifyClient {
             .uri("/v1/search".to_string(), Some(&query.into_query_string()))
     }
 
+    pub(crate) fn search_tracks(
+        &self,
+        query: String,
+        limit: usize,
+    ) -> SpotifyRequest<'_, (), RawSearchResults> {
+        let query = SearchQuery {
+            query,
+            types: vec![SearchType::Track],
+            limit,
+            offset: 0,
+        };
+
+        self.request()
+            .method(Method::GET)
+            .uri("/v1/search".to_string(), Some(&query.into_query_string()))
+    }
+
+    pub(crate) fn get_current_user(&self) -> SpotifyRequest<'_, (), CurrentUser> {
+        self.request()
+            .method(Method::GET)
+            .uri("/v1/me".to_string(), None)
+    }
+
+    pub(crate) fn get_new_releases(
+        &self,
+        offset: usize,
+        limit: usize,
+    ) -> SpotifyRequest<'_, (), NewReleases> {
+        let query = make_query_params()
+            .append_pair("offset", &offset.to_string()[..])
+            .append_pair("limit", &limit.to_string()[..])
+            .finish();
+
+        self.request()
+            .method(Method::GET)
+            .uri("/v1/browse/new-releases".to_string(), Some(&query))
+    }
+
+    pub(crate) fn get_top_tracks(
+        &self,
+        time_range: &str,
+        limit: usize,
+    ) -> SpotifyRequest<'_, (), Page<TrackItem>> {
+        let query = make_query_params()
+            .append_pair("time_range", time_range)
+            .append_pair("limit", &limit.to_string()[..])
+            .append_pair("market", "from_token")
+            .finish();
+
+        self.request()
+            .method(Method::GET)
+            .uri("/v1/me/top/tracks".to_string(), Some(&query))
+    }
+
+    pub(crate) fn get_top_artists(
+        &self,
+        time_range: &str,
+        limit: usize,
+    ) -> SpotifyRequest<'_, (), Page<Artist>> {
+        let query = make_query_params()
+            .append_pair("time_range", time_range)
+            .append_pair("limit", &limit.to_string()[..])
+            .finish();
+
+        self.request()
+            .method(Method::GET)
+            .uri("/v1/me/top/artists".to_string(), Some(&query))
+    }
+
+    pub(crate) fn get_recently_played(
+        &self,
+        limit: usize,
+    ) -> SpotifyRequest<'_, (), RecentlyPlayedTracks> {
+        let query = make_query_params()
+            .append_pair("limit", &limit.to_string()[..])
+            .append_pair("market", "from_token")
+            .finish();
+
+        self.request()
+            .method(Method::GET)
+            .uri("/v1/me/player/recently-played".to_string(), Some(&query))
+    }
+
+    pub(crate) fn get_saved_shows(
+        &self,
+        offset: usize,
+        limit: usize,
+    ) -> SpotifyRequest<'_, (), Page<SavedShow>> {
+        let query = make_query_params()
+            .append_pair("offset", &offset.to_string()[..])
+            .append_pair("limit", &limit.to_string()[..])
+            .finish();
+
+        self.request()
+            .method(Method::GET)
+            .uri("/v1/me/shows".to_string(), Some(&query))
+    }
+
+    pub(crate) fn get_show(&self, id: &str) -> SpotifyRequest<'_, (), Show> {
+        let query = make_query_params()
+            .append_pair("market", "from_token")
+            .finish();
+
+        self.request()
+            .method(Method::GET)
+            .uri(format!("/v1/shows/{id}"), Some(&query))
+    }
+
+    pub(crate) fn get_show_episodes(
+        &self,
+        id: &str,
+        offset: usize,
+        limit: usize,
+    ) -> SpotifyRequest<'_, (), Page<Option<Episode>>> {
+        let query = make_query_params()
+            .append_pair("market", "from_token")
+            .append_pair("offset", &offset.to_string()[..])
+            .append_pair("limit", &limit.to_string()[..])
+            .finish();
+
+        self.request()
+            .method(Method::GET)
+            .uri(format!("/v1/shows/{id}/episodes"), Some(&query))
+    }
+
     pub(crate) fn get_user(&self, id: &str) -> SpotifyRequest<'_, (), User> {
         let id = utf8_percent_encode(id, PATH_ENCODE_SET);
         self.request()
@@ -651,7 +1021,7 @@ pub mod tests {
     #[tokio::test]
     async fn test_username_encoding() {
         let username = "anna.lafuente❤";
-        let client = SpotifyClient::new(TokenStore::new());
+        let client = SpotifyClient::new(Arc::new(RiffOauthClient::new(TokenStore::new())));
         let req = client.get_user(username);
         assert_eq!(
             req.request
@@ -663,6 +1033,28 @@ pub mod tests {
         );
     }
 
+    #[test]
+    fn test_retry_after() {
+        let response = isahc::Response::builder()
+            .status(StatusCode::TOO_MANY_REQUESTS)
+            .header("Retry-After", "3")
+            .body(())
+            .unwrap();
+        assert_eq!(
+            SpotifyClient::parse_retry_after(&response),
+            Duration::from_secs(3)
+        );
+
+        let response = isahc::Response::builder()
+            .status(StatusCode::TOO_MANY_REQUESTS)
+            .body(())
+            .unwrap();
+        assert_eq!(
+            SpotifyClient::parse_retry_after(&response),
+            DEFAULT_RETRY_AFTER
+        );
+    }
+
     #[test]
     fn test_search_query() {
         let query = SearchQuery {
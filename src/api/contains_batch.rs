@@ -0,0 +1,229 @@
+use futures::channel::oneshot;
+use std::future::Future;
+use std::mem;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use super::client::SpotifyApiError;
+
+// That's as many ids as the API lets us ask for at once
+const MAX_IDS: usize = 50;
+// How long ids are collected for, before asking about them
+const WINDOW: Duration = Duration::from_millis(50);
+
+type Waiter = oneshot::Sender<Result<bool, SpotifyApiError>>;
+
+#[derive(Default)]
+struct Batch {
+    // Bumped whenever the batch is taken, so that whoever was due to send it knows it's gone
+    generation: usize,
+    ids: Vec<String>,
+    waiters: Vec<Waiter>,
+}
+
+// Every row wants to know whether its track is saved: rather than one "contains" request each,
+// the ids asked about within a short window are checked together
+pub struct ContainsBatch {
+    max_ids: usize,
+    window: Duration,
+    pending: Mutex<Batch>,
+}
+
+impl Default for ContainsBatch {
+    fn default() -> Self {
+        Self::new(MAX_IDS, WINDOW)
+    }
+}
+
+// Held by the first caller of a batch while the window is open
+struct Scheduled<'a> {
+    batch: &'a ContainsBatch,
+    generation: usize,
+}
+
+impl Scheduled<'_> {
+    fn take(&self) -> Option<(Vec<String>, Vec<Waiter>)> {
+        self.batch.take(self.generation)
+    }
+}
+
+impl Drop for Scheduled<'_> {
+    // If that caller goes away, nobody would send the batch: waiters are let go and ask again
+    fn drop(&mut self) {
+        self.take();
+    }
+}
+
+async fn send_batch<F, Fut>((ids, waiters): (Vec<String>, Vec<Waiter>), check: &F)
+where
+    F: Fn(Vec<String>) -> Fut,
+    Fut: Future<Output = Result<Vec<bool>, SpotifyApiError>>,
+{
+    match check(ids).await {
+        Ok(contains) => {
+            for (i, waiter) in waiters.into_iter().enumerate() {
+                let _ = waiter.send(contains.get(i).copied().ok_or(SpotifyApiError::NoContent));
+            }
+        }
+        Err(e) => {
+            for waiter in waiters {
+                let _ = waiter.send(Err(e.duplicate()));
+            }
+        }
+    }
+}
+
+impl ContainsBatch {
+    fn new(max_ids: usize, window: Duration) -> Self {
+        Self {
+            max_ids,
+            window,
+            pending: Default::default(),
+        }
+    }
+
+    // The batch, unless it's already been sent
+    fn take(&self, generation: usize) -> Option<(Vec<String>, Vec<Waiter>)> {
+        let mut pending = self.pending.lock().unwrap();
+        if pending.generation != generation || pending.ids.is_empty() {
+            return None;
+        }
+        pending.generation += 1;
+        Some((mem::take(&mut pending.ids), mem::take(&mut pending.waiters)))
+    }
+
+    // Whichever caller fills the batch sends it right away, otherwise the first one does once the window is over
+    pub async fn contains<F, Fut>(&self, id: &str, check: F) -> Result<bool, SpotifyApiError>
+    where
+        F: Fn(Vec<String>) -> Fut,
+        Fut: Future<Output = Result<Vec<bool>, SpotifyApiError>>,
+    {
+        loop {
+            let (receiver, generation, first, full) = {
+                let mut pending = self.pending.lock().unwrap();
+                let (sender, receiver) = oneshot::channel();
+                pending.ids.push(id.to_string());
+                pending.waiters.push(sender);
+                let count = pending.ids.len();
+                (
+                    receiver,
+                    pending.generation,
+                    count == 1,
+                    count >= self.max_ids,
+                )
+            };
+
+            if full {
+                if let Some(batch) = self.take(generation) {
+                    send_batch(batch, &check).await;
+                }
+            } else if first {
+                let scheduled = Scheduled {
+                    batch: self,
+                    generation,
+                };
+                tokio::time::sleep(self.window).await;
+                if let Some(batch) = scheduled.take() {
+                    send_batch(batch, &check).await;
+                }
+            }
+
+            match receiver.await {
+                Ok(result) => return result,
+                Err(_) => debug!("Batch including {id} was dropped, asking again"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::mock_server::MockServer;
+    use futures::future::try_join_all;
+
+    // Tracks with an even id are saved
+    async fn check(server: &MockServer, ids: Vec<String>) -> Result<Vec<bool>, SpotifyApiError> {
+        server.handle_ids(ids.clone()).await?;
+        Ok(ids
+            .iter()
+            .map(|id| id.parse::<usize>().unwrap() % 2 == 0)
+            .collect())
+    }
+
+    #[tokio::test]
+    async fn test_ids_within_window_are_batched() {
+        tokio::time::pause();
+        let server = MockServer::default();
+        let batch = ContainsBatch::default();
+
+        let (first, second) =
+            futures::join!(batch.contains("1", |ids| check(&server, ids)), async {
+                tokio::time::advance(WINDOW / 2).await;
+                batch.contains("2", |ids| check(&server, ids)).await
+            });
+
+        assert_eq!((first.unwrap(), second.unwrap()), (false, true));
+        assert_eq!(server.requests(), vec![vec!["1", "2"]]);
+
+        // The window is over, that's another request
+        assert!(batch
+            .contains("4", |ids| check(&server, ids))
+            .await
+            .unwrap());
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_ids_after_window_are_not_batched() {
+        tokio::time::pause();
+        let server = MockServer::default();
+        let batch = ContainsBatch::default();
+
+        let (first, second) =
+            futures::join!(batch.contains("1", |ids| check(&server, ids)), async {
+                tokio::time::advance(WINDOW * 2).await;
+                batch.contains("2", |ids| check(&server, ids)).await
+            });
+
+        assert_eq!((first.unwrap(), second.unwrap()), (false, true));
+        assert_eq!(server.requests(), vec![vec!["1"], vec!["2"]]);
+    }
+
+    #[tokio::test]
+    async fn test_batches_are_split_at_max_ids() {
+        tokio::time::pause();
+        let server = MockServer::default();
+        let batch = ContainsBatch::default();
+
+        let ids: Vec<String> = (0..120).map(|i| i.to_string()).collect();
+        let saved = try_join_all(
+            ids.iter()
+                .map(|id| batch.contains(id, |ids| check(&server, ids))),
+        )
+        .await
+        .unwrap();
+
+        let expected: Vec<bool> = (0..120).map(|i| i % 2 == 0).collect();
+        assert_eq!(saved, expected);
+        let sizes: Vec<usize> = server.requests().iter().map(Vec::len).collect();
+        assert_eq!(sizes, vec![50, 50, 20]);
+    }
+
+    #[tokio::test]
+    async fn test_errors_reach_every_caller() {
+        tokio::time::pause();
+        let server =
+            MockServer::failing(usize::MAX, SpotifyApiError::BadStatus(503, String::new()));
+        let batch = ContainsBatch::default();
+
+        let (first, second) = futures::join!(
+            batch.contains("1", |ids| check(&server, ids)),
+            batch.contains("2", |ids| check(&server, ids)),
+        );
+
+        assert!(matches!(first, Err(SpotifyApiError::BadStatus(503, _))));
+        assert!(matches!(second, Err(SpotifyApiError::BadStatus(503, _))));
+        assert_eq!(server.requests().len(), 1);
+    }
+}
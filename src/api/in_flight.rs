@@ -0,0 +1,159 @@
+use futures::channel::oneshot;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+use super::client::SpotifyApiError;
+
+type Waiters<T> = Vec<oneshot::Sender<Result<T, SpotifyApiError>>>;
+
+// Several components tend to ask for the same thing at the same time (the home screen does, for one):
+// while a request is in flight, asking for the same key again waits for its result instead of sending another
+pub struct InFlight<T> {
+    // Whoever is waiting on the request being sent for each key
+    requests: Arc<Mutex<HashMap<String, Waiters<T>>>>,
+}
+
+impl<T> Default for InFlight<T> {
+    fn default() -> Self {
+        Self {
+            requests: Default::default(),
+        }
+    }
+}
+
+impl<T: Clone + Send + 'static> InFlight<T> {
+    // The request is sent from its own task: whoever asked first might go away, the others still want the response
+    pub async fn coalesce<F, Fut>(&self, key: &str, send: F) -> Result<T, SpotifyApiError>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, SpotifyApiError>> + Send + 'static,
+    {
+        let receiver = {
+            let mut requests = self.requests.lock().unwrap();
+            let (sender, receiver) = oneshot::channel();
+            match requests.get_mut(key) {
+                Some(waiters) => waiters.push(sender),
+                None => {
+                    requests.insert(key.to_string(), vec![sender]);
+                    tokio::spawn(Self::send(self.requests.clone(), key.to_string(), send()));
+                }
+            }
+            receiver
+        };
+        // The sender only goes away without answering if the task panicked
+        receiver.await.unwrap_or_else(|_| {
+            Err(SpotifyApiError::Other(format!(
+                "{key} could not be fetched"
+            )))
+        })
+    }
+
+    async fn send<Fut>(requests: Arc<Mutex<HashMap<String, Waiters<T>>>>, key: String, send: Fut)
+    where
+        Fut: Future<Output = Result<T, SpotifyApiError>>,
+    {
+        let result = send.await;
+        let waiters = requests.lock().unwrap().remove(&key).unwrap_or_default();
+        for waiter in waiters {
+            let _ = waiter.send(match &result {
+                Ok(value) => Ok(value.clone()),
+                Err(e) => Err(e.duplicate()),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::mock_server::MockServer;
+    use futures::future::{BoxFuture, FutureExt};
+    use std::time::Duration;
+
+    // Takes a little while to answer, so that requests overlap
+    fn slow_server(fail: bool) -> Arc<MockServer> {
+        let server = if fail {
+            MockServer::failing(usize::MAX, SpotifyApiError::BadStatus(503, String::new()))
+        } else {
+            MockServer::default()
+        };
+        Arc::new(server.with_delay(Duration::from_millis(20)))
+    }
+
+    fn request(
+        server: &Arc<MockServer>,
+    ) -> impl FnOnce() -> BoxFuture<'static, Result<usize, SpotifyApiError>> {
+        let server = Arc::clone(server);
+        move || async move { server.handle().await }.boxed()
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_requests_are_sent_once() {
+        tokio::time::pause();
+        let server = slow_server(false);
+        let in_flight = InFlight::default();
+
+        let (first, second, third) = futures::join!(
+            in_flight.coalesce("me_playlists_0_20.json", request(&server)),
+            in_flight.coalesce("me_playlists_0_20.json", request(&server)),
+            in_flight.coalesce("me_playlists_0_20.json", request(&server)),
+        );
+
+        assert_eq!((first.unwrap(), second.unwrap(), third.unwrap()), (1, 1, 1));
+        assert_eq!(server.request_count(), 1);
+        assert!(in_flight.requests.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_different_keys_are_not_coalesced() {
+        tokio::time::pause();
+        let server = slow_server(false);
+        let in_flight = InFlight::default();
+
+        let (first, second) = futures::join!(
+            in_flight.coalesce("album_1.json", request(&server)),
+            in_flight.coalesce("album_2.json", request(&server)),
+        );
+
+        assert!(first.is_ok() && second.is_ok());
+        assert_eq!(server.request_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_errors_reach_every_caller() {
+        tokio::time::pause();
+        let server = slow_server(true);
+        let in_flight = InFlight::default();
+
+        let (first, second) = futures::join!(
+            in_flight.coalesce("album_1.json", request(&server)),
+            in_flight.coalesce("album_1.json", request(&server)),
+        );
+
+        assert!(matches!(first, Err(SpotifyApiError::BadStatus(503, _))));
+        assert!(matches!(second, Err(SpotifyApiError::BadStatus(503, _))));
+        assert_eq!(server.request_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_dropped_caller_does_not_cancel_request() {
+        tokio::time::pause();
+        let server = slow_server(false);
+        let in_flight = InFlight::default();
+
+        // The first caller gives up while its request is still in flight
+        let dropped = tokio::time::timeout(
+            Duration::from_millis(5),
+            in_flight.coalesce("album_1.json", request(&server)),
+        );
+        let (dropped, waiting) = futures::join!(
+            dropped,
+            in_flight.coalesce("album_1.json", request(&server))
+        );
+
+        assert!(dropped.is_err());
+        assert_eq!(waiting.unwrap(), 1);
+        assert_eq!(server.request_count(), 1);
+    }
+}
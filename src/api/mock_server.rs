@@ -0,0 +1,64 @@
+use std::sync::Mutex;
+use std::time::Duration;
+
+use super::client::SpotifyApiError;
+
+// Pretends to be Spotify in tests: the first few requests fail with the given error, the rest succeed.
+// Every request is kept, along with the ids it was about (if any).
+pub struct MockServer {
+    failures: usize,
+    error: SpotifyApiError,
+    // How long each request takes to be answered, use with tokio::time::pause()
+    delay: Duration,
+    requests: Mutex<Vec<Vec<String>>>,
+}
+
+impl Default for MockServer {
+    fn default() -> Self {
+        Self::failing(0, SpotifyApiError::NoContent)
+    }
+}
+
+impl MockServer {
+    pub fn failing(failures: usize, error: SpotifyApiError) -> Self {
+        Self {
+            failures,
+            error,
+            delay: Duration::ZERO,
+            requests: Default::default(),
+        }
+    }
+
+    pub fn with_delay(self, delay: Duration) -> Self {
+        Self { delay, ..self }
+    }
+
+    // Succeeds with the number of the request, starting at 1
+    pub async fn handle(&self) -> Result<usize, SpotifyApiError> {
+        self.handle_ids(vec![]).await
+    }
+
+    pub async fn handle_ids(&self, ids: Vec<String>) -> Result<usize, SpotifyApiError> {
+        let count = {
+            let mut requests = self.requests.lock().unwrap();
+            requests.push(ids);
+            requests.len()
+        };
+        if !self.delay.is_zero() {
+            tokio::time::sleep(self.delay).await;
+        }
+        if count <= self.failures {
+            Err(self.error.duplicate())
+        } else {
+            Ok(count)
+        }
+    }
+
+    pub fn request_count(&self) -> usize {
+        self.requests.lock().unwrap().len()
+    }
+
+    pub fn requests(&self) -> Vec<Vec<String>> {
+        self.requests.lock().unwrap().clone()
+    }
+}
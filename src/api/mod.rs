@@ -1,15 +1,53 @@
 mod api_models;
 mod cached_client;
 mod client;
+mod contains_batch;
+mod in_flight;
+#[cfg(test)]
+mod mock_server;
+mod proxy;
+mod rate_limit;
+mod retry;
+mod token_refresh;
 
 pub mod cache;
 
 pub use cached_client::{CachedSpotifyClient, SpotifyApiClient, SpotifyResult};
 pub use client::SpotifyApiError;
+pub(crate) use proxy::ProxiedClient;
+pub use proxy::{parse_proxy, set_proxy};
+pub use token_refresh::TokenRefresher;
+
+const API_CACHE_DIR: &str = "riff/net";
 
 pub async fn clear_user_cache() -> Option<()> {
-    cache::CacheManager::for_dir("riff/net")?
+    cache::CacheManager::for_dir(API_CACHE_DIR)?
         .clear_cache_pattern(&cached_client::USER_CACHE)
         .await
         .ok()
 }
+
+// Past that (in bytes), the least recently used responses go
+pub fn set_api_cache_limit(limit: Option<u64>) {
+    if let Some(cache) = cache::CacheManager::for_dir(API_CACHE_DIR) {
+        cache.set_max_size(limit);
+    }
+}
+
+// How much space the cached responses take, in bytes
+pub fn api_cache_size() -> u64 {
+    cache::CacheManager::for_dir(API_CACHE_DIR)
+        .map(|cache| cache.size())
+        .unwrap_or(0)
+}
+
+// Returns how many bytes were freed
+pub async fn clear_api_cache() -> u64 {
+    let Some(cache) = cache::CacheManager::for_dir(API_CACHE_DIR) else {
+        return 0;
+    };
+    cache.clear().await.unwrap_or_else(|e| {
+        warn!("Could not clear the API cache: {e}");
+        0
+    })
+}
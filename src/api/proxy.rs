@@ -0,0 +1,85 @@
+use isahc::config::Configurable;
+use isahc::http::Uri;
+use isahc::{HttpClient, HttpClientBuilder};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, RwLock};
+
+// The proxy set in the preferences (or the environment), shared by all of our HTTP clients
+static PROXY: RwLock<Option<Uri>> = RwLock::new(None);
+// Bumped whenever the proxy changes, so that clients know to build themselves again
+static PROXY_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+// librespot can only go through HTTP proxies, so there's no point in taking anything else
+pub fn parse_proxy(proxy: &str) -> Option<Uri> {
+    let uri = proxy.parse::<Uri>().ok()?;
+    let scheme_supported = matches!(uri.scheme_str(), Some("http") | Some("https"));
+    (scheme_supported && uri.host().is_some()).then_some(uri)
+}
+
+// An invalid proxy is left out here, the player reports it when connecting
+pub fn set_proxy(proxy: Option<&str>) {
+    let proxy = proxy.and_then(|proxy| {
+        let uri = parse_proxy(proxy);
+        if uri.is_none() {
+            warn!("Ignoring invalid proxy: {proxy}");
+        }
+        uri
+    });
+    let mut current = PROXY.write().unwrap();
+    if *current != proxy {
+        *current = proxy;
+        PROXY_GENERATION.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+// An HTTP client that picks up changes to the proxy
+pub(crate) struct ProxiedClient {
+    configure: fn(HttpClientBuilder) -> HttpClientBuilder,
+    client: Mutex<Option<(u64, HttpClient)>>,
+}
+
+impl ProxiedClient {
+    pub(crate) fn new() -> Self {
+        Self::with_config(|builder| builder)
+    }
+
+    pub(crate) fn with_config(configure: fn(HttpClientBuilder) -> HttpClientBuilder) -> Self {
+        Self {
+            configure,
+            client: Mutex::new(None),
+        }
+    }
+
+    // Clients are cheap to clone, and share their connections
+    pub(crate) fn get(&self) -> HttpClient {
+        let generation = PROXY_GENERATION.load(Ordering::SeqCst);
+        let mut client = self.client.lock().unwrap();
+        match client.as_ref() {
+            Some((built_for, client)) if *built_for == generation => client.clone(),
+            _ => {
+                let mut builder = (self.configure)(HttpClient::builder());
+                // Otherwise the usual environment variables are used
+                if let Some(proxy) = PROXY.read().unwrap().clone() {
+                    builder = builder.proxy(Some(proxy));
+                }
+                let built = builder.build().expect("Could not create http client");
+                *client = Some((generation, built.clone()));
+                built
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_http_proxies_are_supported() {
+        assert!(parse_proxy("http://proxy:8080").is_some());
+        assert!(parse_proxy("https://proxy").is_some());
+        assert!(parse_proxy("socks5://proxy:1080").is_none());
+        assert!(parse_proxy("proxy:8080").is_none());
+        assert!(parse_proxy("not a proxy").is_none());
+    }
+}
@@ -0,0 +1,139 @@
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::Instant;
+
+use super::client::SpotifyApiError;
+
+// Attempts after the first 429, before letting the caller know
+const MAX_RETRIES: usize = 2;
+// Past that, whoever is waiting for the response is better off being told to come back later
+const MAX_WAIT: Duration = Duration::from_secs(10);
+
+// Once Spotify tells us to slow down, every request waits (not just the one that got the 429),
+// otherwise the ones sent in the meantime would get us rate limited again right away.
+#[derive(Default)]
+pub struct RateLimit {
+    // Nothing gets sent before that
+    cool_down_until: Mutex<Option<Instant>>,
+}
+
+impl RateLimit {
+    fn cool_down(&self, retry_after: Duration) {
+        let until = Instant::now() + retry_after;
+        let mut cool_down_until = self.cool_down_until.lock().unwrap();
+        if cool_down_until.is_none_or(|current| current < until) {
+            *cool_down_until = Some(until);
+        }
+    }
+
+    fn remaining_cool_down(&self) -> Option<Duration> {
+        let until = (*self.cool_down_until.lock().unwrap())?;
+        until
+            .checked_duration_since(Instant::now())
+            .filter(|remaining| !remaining.is_zero())
+    }
+
+    // Sends a request once we're allowed to, and tries again (a couple of times) if it's rate limited
+    pub async fn send_with_retry<T, F, Fut>(&self, send: F) -> Result<T, SpotifyApiError>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<T, SpotifyApiError>>,
+    {
+        let mut retries = 0;
+        loop {
+            if let Some(remaining) = self.remaining_cool_down() {
+                if remaining > MAX_WAIT {
+                    return Err(SpotifyApiError::RateLimited(remaining));
+                }
+                tokio::time::sleep(remaining).await;
+            }
+            match send().await {
+                Err(SpotifyApiError::RateLimited(retry_after)) => {
+                    self.cool_down(retry_after);
+                    if retries == MAX_RETRIES || retry_after > MAX_WAIT {
+                        return Err(SpotifyApiError::RateLimited(retry_after));
+                    }
+                    retries += 1;
+                    debug!("Rate limited, trying again in {retry_after:?}");
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::mock_server::MockServer;
+
+    fn rate_limited(failures: usize, retry_after: Duration) -> MockServer {
+        MockServer::failing(failures, SpotifyApiError::RateLimited(retry_after))
+    }
+
+    #[tokio::test]
+    async fn test_retries_after_delay() {
+        tokio::time::pause();
+        let server = rate_limited(2, Duration::from_millis(20));
+        let rate_limit = RateLimit::default();
+
+        let start = Instant::now();
+        let result = rate_limit.send_with_retry(|| server.handle()).await;
+
+        assert!(result.is_ok());
+        assert_eq!(server.request_count(), 3);
+        assert!(start.elapsed() >= Duration::from_millis(40));
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_max_retries() {
+        tokio::time::pause();
+        let server = rate_limited(usize::MAX, Duration::from_millis(10));
+        let rate_limit = RateLimit::default();
+
+        let result = rate_limit.send_with_retry(|| server.handle()).await;
+
+        assert!(matches!(result, Err(SpotifyApiError::RateLimited(_))));
+        assert_eq!(server.request_count(), MAX_RETRIES + 1);
+    }
+
+    #[tokio::test]
+    async fn test_long_cool_down_fails_right_away() {
+        tokio::time::pause();
+        let server = rate_limited(1, Duration::from_secs(60));
+        let rate_limit = RateLimit::default();
+
+        let result = rate_limit.send_with_retry(|| server.handle()).await;
+        assert!(matches!(result, Err(SpotifyApiError::RateLimited(_))));
+        assert_eq!(server.request_count(), 1);
+
+        // The next one isn't even sent
+        let result = rate_limit.send_with_retry(|| server.handle()).await;
+        assert!(
+            matches!(result, Err(SpotifyApiError::RateLimited(remaining)) if remaining > MAX_WAIT)
+        );
+        assert_eq!(server.request_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_cool_down_is_shared() {
+        tokio::time::pause();
+        let limited = rate_limited(1, Duration::from_millis(50));
+        let other = MockServer::default();
+        let rate_limit = RateLimit::default();
+
+        // The first one gets the 429 before the second one is even sent
+        let (limited_result, (other_result, other_elapsed)) =
+            futures::join!(rate_limit.send_with_retry(|| limited.handle()), async {
+                let start = Instant::now();
+                let result = rate_limit.send_with_retry(|| other.handle()).await;
+                (result, start.elapsed())
+            });
+
+        assert!(limited_result.is_ok());
+        assert!(other_result.is_ok());
+        assert!(other_elapsed >= Duration::from_millis(50));
+        assert_eq!(other.request_count(), 1);
+    }
+}
@@ -0,0 +1,102 @@
+use rand::Rng;
+use std::future::Future;
+use std::time::Duration;
+
+use super::client::SpotifyApiError;
+
+// Including the first one
+const MAX_ATTEMPTS: u32 = 3;
+// Doubled after every failed attempt
+const BASE_DELAY: Duration = Duration::from_millis(500);
+
+// Somewhere between half and all of the backoff, so that requests that failed together aren't retried together
+fn backoff_with_jitter(base_delay: Duration, attempt: u32) -> Duration {
+    let backoff = base_delay * 2u32.pow(attempt - 1);
+    let jitter = rand::thread_rng().gen_range(0.0..=0.5);
+    backoff.mul_f64(1.0 - jitter)
+}
+
+async fn retry_with<T, F, Fut>(
+    max_attempts: u32,
+    base_delay: Duration,
+    send: F,
+) -> Result<T, SpotifyApiError>
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = Result<T, SpotifyApiError>>,
+{
+    let mut attempt = 1;
+    loop {
+        match send().await {
+            Err(e) if e.is_transient() && attempt < max_attempts => {
+                let delay = backoff_with_jitter(base_delay, attempt);
+                debug!("Request failed ({e}), trying again in {delay:?}");
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+// Only for requests that can safely be sent twice: a POST that timed out might still have gone through
+pub async fn retry_transient<T, F, Fut>(send: F) -> Result<T, SpotifyApiError>
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = Result<T, SpotifyApiError>>,
+{
+    retry_with(MAX_ATTEMPTS, BASE_DELAY, send).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::mock_server::MockServer;
+    use tokio::time::Instant;
+
+    fn failing(failures: usize, status: u16) -> MockServer {
+        MockServer::failing(failures, SpotifyApiError::BadStatus(status, String::new()))
+    }
+
+    #[tokio::test]
+    async fn test_server_error_is_retried() {
+        tokio::time::pause();
+        let server = failing(2, 503);
+        let start = Instant::now();
+        let result = retry_with(3, BASE_DELAY, || server.handle()).await;
+
+        assert!(result.is_ok());
+        assert_eq!(server.request_count(), 3);
+        // Half of the backoff at least, for both retries
+        assert!(start.elapsed() >= (BASE_DELAY + BASE_DELAY * 2) / 2);
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_max_attempts() {
+        tokio::time::pause();
+        let server = failing(usize::MAX, 502);
+        let result = retry_with(3, BASE_DELAY, || server.handle()).await;
+
+        assert!(matches!(result, Err(SpotifyApiError::BadStatus(502, _))));
+        assert_eq!(server.request_count(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_client_error_is_not_retried() {
+        tokio::time::pause();
+        let server = failing(1, 404);
+        let result = retry_with(3, BASE_DELAY, || server.handle()).await;
+
+        assert!(matches!(result, Err(SpotifyApiError::BadStatus(404, _))));
+        assert_eq!(server.request_count(), 1);
+    }
+
+    #[test]
+    fn test_backoff_with_jitter() {
+        for attempt in 1..=3 {
+            let backoff = BASE_DELAY * 2u32.pow(attempt - 1);
+            let delay = backoff_with_jitter(BASE_DELAY, attempt);
+            assert!(delay >= backoff / 2 && delay <= backoff);
+        }
+    }
+}
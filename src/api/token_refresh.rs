@@ -0,0 +1,146 @@
+use futures::future::BoxFuture;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use super::client::SpotifyApiError;
+
+// Something that can get us a new access token (and put it in the token store)
+pub trait TokenRefresher: Send + Sync {
+    fn refresh(&self) -> BoxFuture<'_, bool>;
+}
+
+// Makes sure that a bunch of requests failing with a 401 at the same time
+// only result in a single refresh of the token.
+pub struct TokenRefresh {
+    refresher: Arc<dyn TokenRefresher>,
+    // Bumped every time we attempt a refresh
+    generation: AtomicU64,
+    // Held while refreshing, and remembers how the last attempt went
+    last_result: Mutex<bool>,
+}
+
+impl TokenRefresh {
+    pub fn new(refresher: Arc<dyn TokenRefresher>) -> Self {
+        Self {
+            refresher,
+            generation: AtomicU64::new(0),
+            last_result: Mutex::new(false),
+        }
+    }
+
+    // Refreshes the token, unless somebody else did since `seen_generation`
+    async fn refresh(&self, seen_generation: u64) -> bool {
+        let mut last_result = self.last_result.lock().await;
+        if self.generation.load(Ordering::SeqCst) != seen_generation {
+            return *last_result;
+        }
+        debug!("Refreshing token after a 401");
+        *last_result = self.refresher.refresh().await;
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        *last_result
+    }
+
+    // Sends a request with the current token, and if it turns out to be invalid, refreshes it and tries again (once)
+    pub async fn send_with_retry<T, G, F, Fut>(
+        &self,
+        get_token: G,
+        send: F,
+    ) -> Result<T, SpotifyApiError>
+    where
+        G: Fn() -> Option<String>,
+        F: Fn(String) -> Fut,
+        Fut: Future<Output = Result<T, SpotifyApiError>>,
+    {
+        let generation = self.generation.load(Ordering::SeqCst);
+        let token = get_token().ok_or(SpotifyApiError::NoToken)?;
+        match send(token).await {
+            Err(SpotifyApiError::InvalidToken) => {
+                if !self.refresh(generation).await {
+                    return Err(SpotifyApiError::InvalidToken);
+                }
+                let token = get_token().ok_or(SpotifyApiError::NoToken)?;
+                send(token).await
+            }
+            result => result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::RwLock;
+
+    // Pretends to be Spotify: only the latest token is accepted
+    struct MockServer {
+        token: RwLock<String>,
+        refresh_count: AtomicUsize,
+        refresh_succeeds: bool,
+    }
+
+    impl MockServer {
+        fn new(refresh_succeeds: bool) -> Arc<Self> {
+            Arc::new(Self {
+                token: RwLock::new("expired".to_string()),
+                refresh_count: AtomicUsize::new(0),
+                refresh_succeeds,
+            })
+        }
+
+        async fn handle(&self, token: String) -> Result<(), SpotifyApiError> {
+            tokio::task::yield_now().await;
+            if token == "valid" {
+                Ok(())
+            } else {
+                Err(SpotifyApiError::InvalidToken)
+            }
+        }
+    }
+
+    impl TokenRefresher for MockServer {
+        fn refresh(&self) -> BoxFuture<'_, bool> {
+            Box::pin(async move {
+                self.refresh_count.fetch_add(1, Ordering::SeqCst);
+                tokio::task::yield_now().await;
+                if self.refresh_succeeds {
+                    *self.token.write().unwrap() = "valid".to_string();
+                }
+                self.refresh_succeeds
+            })
+        }
+    }
+
+    async fn send_many(server: &Arc<MockServer>, count: usize) -> Vec<Result<(), SpotifyApiError>> {
+        let refresh = TokenRefresh::new(server.clone());
+        let requests = (0..count).map(|_| {
+            refresh.send_with_retry(
+                || Some(server.token.read().unwrap().clone()),
+                |token| server.handle(token),
+            )
+        });
+        futures::future::join_all(requests).await
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_401s_refresh_once() {
+        let server = MockServer::new(true);
+        let results = send_many(&server, 10).await;
+
+        assert!(results.iter().all(|r| r.is_ok()));
+        assert_eq!(server.refresh_count.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_failed_refresh_is_not_retried() {
+        let server = MockServer::new(false);
+        let results = send_many(&server, 10).await;
+
+        assert!(results
+            .iter()
+            .all(|r| matches!(r, Err(SpotifyApiError::InvalidToken))));
+        assert_eq!(server.refresh_count.load(Ordering::SeqCst), 1);
+    }
+}
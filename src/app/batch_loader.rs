@@ -2,6 +2,7 @@ use gettextrs::gettext;
 use std::sync::Arc;
 
 use crate::api::{SpotifyApiClient, SpotifyApiError};
+use crate::app::components::labels;
 use crate::app::models::*;
 use crate::app::AppAction;
 
@@ -44,6 +45,16 @@ impl SongsSource {
             _ => None,
         }
     }
+
+    // The other way around
+    pub fn from_spotify_uri(uri: &str) -> Option<Self> {
+        if let Some(id) = uri.strip_prefix("spotify:playlist:") {
+            Some(Self::Playlist(id.to_string()))
+        } else {
+            uri.strip_prefix("spotify:album:")
+                .map(|id| Self::Album(id.to_string()))
+        }
+    }
 }
 
 // How to query for a batch: specify a source, and a batch to get (offset + number of elements to get)
@@ -78,6 +89,30 @@ impl BatchLoader {
     where
         ActionCreator: FnOnce(SongsSource, SongBatch) -> AppAction,
     {
+        match self.fetch(&query).await {
+            Ok(batch) => Some(create_action(query.source, batch)),
+            Err(notification) => notification,
+        }
+    }
+
+    // Same as above, with some more actions for when the batch couldn't be retrieved
+    pub async fn query_or<ActionCreator>(
+        &self,
+        query: BatchQuery,
+        create_action: ActionCreator,
+        on_error: Vec<AppAction>,
+    ) -> Vec<AppAction>
+    where
+        ActionCreator: FnOnce(SongsSource, SongBatch) -> AppAction,
+    {
+        match self.fetch(&query).await {
+            Ok(batch) => vec![create_action(query.source, batch)],
+            Err(notification) => notification.into_iter().chain(on_error).collect(),
+        }
+    }
+
+    // The error is whatever the user should be told about it
+    async fn fetch(&self, query: &BatchQuery) -> Result<SongBatch, Option<AppAction>> {
         let api = Arc::clone(&self.api);
 
         let Batch {
@@ -90,9 +125,9 @@ impl BatchLoader {
         };
 
         match result {
-            Ok(batch) => Some(create_action(query.source, batch)),
+            Ok(batch) => Ok(batch),
             // No token? Why was the batch loader called? Ah, whatever
-            Err(SpotifyApiError::NoToken) => None,
+            Err(SpotifyApiError::NoToken) => Err(None),
             Err(SpotifyApiError::InvalidToken) => {
                 // Retry once, token may have been refreshed in the meantime
                 let retry = match &query.source {
@@ -104,17 +139,20 @@ impl BatchLoader {
                         api.get_album_tracks(id, offset, batch_size).await
                     }
                 };
-                match retry {
-                    Ok(batch) => Some(create_action(query.source, batch)),
-                    _ => None,
-                }
+                retry.map_err(|_| None)
+            }
+            Err(err @ SpotifyApiError::RateLimited(retry_after)) => {
+                warn!("Spotify API error: {}", err);
+                Err(Some(AppAction::ShowNotification(
+                    labels::rate_limited_label(retry_after),
+                )))
             }
             Err(err) => {
                 error!("Spotify API error: {}", err);
-                Some(AppAction::ShowNotification(gettext(
+                Err(Some(AppAction::ShowNotification(gettext(
                     // translators: This notification is the default message for unhandled errors. Logs refer to console output.
                     "An error occured. Check logs for details!",
-                )))
+                ))))
             }
         }
     }
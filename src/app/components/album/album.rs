@@ -3,10 +3,12 @@ use crate::app::dispatch::Worker;
 use crate::app::loader::ImageLoader;
 use crate::app::models::AlbumModel;
 
+use futures::future::{abortable, AbortHandle};
 use gtk::prelude::*;
 use gtk::subclass::prelude::*;
 use gtk::CompositeTemplate;
 use libadwaita::subclass::prelude::BinImpl;
+use std::cell::RefCell;
 
 mod imp {
 
@@ -26,6 +28,9 @@ mod imp {
 
         #[template_child]
         pub cover_image: TemplateChild<gtk::Picture>,
+
+        pub bindings: RefCell<Vec<glib::Binding>>,
+        pub cover_load: RefCell<Option<AbortHandle>>,
     }
 
     #[glib::object_subclass]
@@ -79,43 +84,98 @@ impl AlbumWidget {
         self.imp().cover_image.set_paintable(Some(&texture));
     }
 
-    fn bind(&self, album_model: &AlbumModel, worker: Worker) {
+    // A factory for list and grid views, so that widgets get recycled rather than created for every album
+    pub fn list_item_factory(worker: Worker) -> gtk::SignalListItemFactory {
+        let factory = gtk::SignalListItemFactory::new();
+        factory.connect_setup(|_, item| {
+            let item = item.downcast_ref::<gtk::ListItem>().unwrap();
+            item.set_child(Some(&Self::new()));
+        });
+        factory.connect_bind(move |_, item| {
+            let item = item.downcast_ref::<gtk::ListItem>().unwrap();
+            let widget = item.child().and_downcast::<Self>().unwrap();
+            let album_model = item.item().and_downcast::<AlbumModel>().unwrap();
+            widget.bind(&album_model, worker.clone());
+        });
+        factory.connect_unbind(|_, item| {
+            let item = item.downcast_ref::<gtk::ListItem>().unwrap();
+            if let Some(widget) = item.child().and_downcast::<Self>() {
+                widget.unbind();
+            }
+        });
+        factory.connect_teardown(|_, item| {
+            let item = item.downcast_ref::<gtk::ListItem>().unwrap();
+            item.set_child(None::<&gtk::Widget>);
+        });
+        factory
+    }
+
+    pub fn bind(&self, album_model: &AlbumModel, worker: Worker) {
+        // Whatever was shown before (if we're being recycled) has to go first
+        self.unbind();
+
         let widget = self.imp();
         widget.cover_image.set_overflow(gtk::Overflow::Hidden);
 
         if let Some(cover_art) = album_model.cover() {
-            let _self = self.downgrade();
-            worker.send_local_task(async move {
-                if let Some(_self) = _self.upgrade() {
-                    let loader = ImageLoader::new();
+            let loader = ImageLoader::new();
+            if let Some(image) = loader.load_cached(&cover_art, "jpg", 200, 200) {
+                self.set_image(&image);
+                self.set_loaded();
+            } else {
+                let _self = self.downgrade();
+                let (load, handle) = abortable(async move {
                     let result = loader.load_remote(&cover_art, "jpg", 200, 200).await;
-                    if let Some(image) = result.as_ref() {
+                    if let (Some(_self), Some(image)) = (_self.upgrade(), result.as_ref()) {
                         _self.set_image(image);
                         _self.set_loaded();
                     }
-                }
-            });
+                });
+                widget.cover_load.replace(Some(handle));
+                worker.send_local_task(async move {
+                    let _ = load.await;
+                });
+            }
         } else {
             self.set_loaded();
         }
 
-        album_model
-            .bind_property("album", &*widget.album_label, "label")
-            .flags(glib::BindingFlags::DEFAULT | glib::BindingFlags::SYNC_CREATE)
-            .build();
-
-        album_model
-            .bind_property("artist", &*widget.artist_label, "label")
-            .flags(glib::BindingFlags::DEFAULT | glib::BindingFlags::SYNC_CREATE)
-            .build();
-
-        if album_model.year() > 0 {
+        let mut bindings = vec![
             album_model
-                .bind_property("year", &*widget.year_label, "label")
+                .bind_property("album", &*widget.album_label, "label")
                 .flags(glib::BindingFlags::DEFAULT | glib::BindingFlags::SYNC_CREATE)
-                .build();
-        } else {
-            widget.year_label.set_visible(false);
+                .build(),
+            album_model
+                .bind_property("artist", &*widget.artist_label, "label")
+                .flags(glib::BindingFlags::DEFAULT | glib::BindingFlags::SYNC_CREATE)
+                .build(),
+        ];
+
+        let has_year = album_model.year() > 0;
+        widget.year_label.set_visible(has_year);
+        if has_year {
+            bindings.push(
+                album_model
+                    .bind_property("year", &*widget.year_label, "label")
+                    .flags(glib::BindingFlags::DEFAULT | glib::BindingFlags::SYNC_CREATE)
+                    .build(),
+            );
+        }
+
+        widget.bindings.replace(bindings);
+    }
+
+    pub fn unbind(&self) {
+        let widget = self.imp();
+        // A cover that is still loading belongs to the previous album: it's aborted (the task
+        // won't be polled again) rather than checked later, so it can never end up shown here
+        if let Some(handle) = widget.cover_load.take() {
+            handle.abort();
+        }
+        for binding in widget.bindings.take() {
+            binding.unbind();
         }
+        widget.cover_image.set_paintable(None::<&gdk::Paintable>);
+        self.remove_css_class("container--loaded");
     }
 }
@@ -1,3 +1,4 @@
+use gettextrs::gettext;
 use gtk::prelude::*;
 use gtk::subclass::prelude::*;
 use gtk::CompositeTemplate;
@@ -5,7 +6,7 @@ use std::rc::Rc;
 
 use crate::app::components::utils::wrap_flowbox_item;
 use crate::app::components::{
-    display_add_css_provider, AlbumWidget, Component, EventListener, Playlist,
+    display_add_css_provider, AlbumWidget, ArtistWidget, Component, EventListener, Playlist,
 };
 use crate::app::{models::*, ListStore};
 use crate::app::{AppEvent, BrowserEvent, Worker};
@@ -22,11 +23,26 @@ mod imp {
         #[template_child]
         pub scrolled_window: TemplateChild<gtk::ScrolledWindow>,
 
+        #[template_child]
+        pub follow_button: TemplateChild<gtk::ToggleButton>,
+
+        #[template_child]
+        pub radio_button: TemplateChild<gtk::Button>,
+
+        #[template_child]
+        pub share_button: TemplateChild<gtk::MenuButton>,
+
         #[template_child]
         pub top_tracks: TemplateChild<gtk::ListView>,
 
         #[template_child]
         pub artist_releases: TemplateChild<gtk::FlowBox>,
+
+        #[template_child]
+        pub related_section: TemplateChild<gtk::Box>,
+
+        #[template_child]
+        pub related_artists: TemplateChild<gtk::FlowBox>,
     }
 
     #[glib::object_subclass]
@@ -67,6 +83,43 @@ impl ArtistDetailsWidget {
         self.add_css_class("artist__loaded");
     }
 
+    fn connect_follow_clicked<F>(&self, f: F)
+    where
+        F: Fn() + 'static,
+    {
+        self.imp().follow_button.connect_clicked(move |button| {
+            // Until we hear back, see `set_followed`
+            button.set_sensitive(false);
+            f();
+        });
+    }
+
+    fn connect_radio_clicked<F>(&self, f: F)
+    where
+        F: Fn() + 'static,
+    {
+        self.imp().radio_button.connect_clicked(move |_| f());
+    }
+
+    fn set_share_menu(&self, menu: &gio::MenuModel, actions: &gio::ActionGroup) {
+        self.imp().share_button.set_menu_model(Some(menu));
+        self.insert_action_group("artist", Some(actions));
+    }
+
+    fn set_followed(&self, is_followed: Option<bool>) {
+        let button = &self.imp().follow_button;
+        button.set_sensitive(is_followed.is_some());
+        let is_followed = is_followed.unwrap_or(false);
+        button.set_active(is_followed);
+        if is_followed {
+            // translators: Shown on the follow button of an artist the user follows
+            button.set_label(&gettext("Following"));
+        } else {
+            // translators: Shown on the follow button of an artist the user doesn't follow
+            button.set_label(&gettext("Follow"));
+        }
+    }
+
     fn connect_bottom_edge<F>(&self, f: F)
     where
         F: Fn() + 'static,
@@ -80,6 +133,58 @@ impl ArtistDetailsWidget {
             });
     }
 
+    // The related artists sit at the very bottom, so that's how we know they'd be seen
+    fn connect_bottom_visible<F>(&self, f: F)
+    where
+        F: Fn() + Clone + 'static,
+    {
+        fn is_bottom_visible(adjustment: &gtk::Adjustment) -> bool {
+            adjustment.value() + adjustment.page_size() >= adjustment.upper() - 1.0
+        }
+
+        let adjustment = self.imp().scrolled_window.vadjustment();
+        let f_clone = f.clone();
+        adjustment.connect_value_changed(move |adjustment| {
+            if is_bottom_visible(adjustment) {
+                f_clone()
+            }
+        });
+        // The page might not be tall enough to scroll at all
+        adjustment.connect_changed(move |adjustment| {
+            if is_bottom_visible(adjustment) {
+                f()
+            }
+        });
+    }
+
+    fn set_related_visible(&self, visible: bool) {
+        self.imp().related_section.set_visible(visible);
+    }
+
+    fn bind_related_artists<F>(
+        &self,
+        worker: Worker,
+        store: &ListStore<ArtistModel>,
+        on_artist_pressed: F,
+    ) where
+        F: Fn(String) + 'static,
+    {
+        let store_clone = store.clone();
+        self.imp()
+            .related_artists
+            .bind_model(Some(store.inner()), move |item| {
+                wrap_flowbox_item(item, |artist_model: &ArtistModel| {
+                    ArtistWidget::for_model(artist_model, worker.clone())
+                })
+            });
+        self.imp()
+            .related_artists
+            .connect_child_activated(move |_, child| {
+                let artist_model = store_clone.get(child.index() as u32);
+                on_artist_pressed(artist_model.id());
+            });
+    }
+
     fn bind_artist_releases<F>(
         &self,
         worker: Worker,
@@ -114,8 +219,27 @@ pub struct ArtistDetails {
 impl ArtistDetails {
     pub fn new(model: Rc<ArtistDetailsModel>, worker: Worker) -> Self {
         model.load_artist_details(model.id.clone());
+        model.load_follow_state();
 
         let widget = ArtistDetailsWidget::new();
+        widget.set_followed(model.is_followed());
+        widget.set_share_menu(&model.share_menu(), &model.share_actions());
+
+        widget.connect_follow_clicked(clone!(
+            #[weak]
+            model,
+            move || {
+                model.toggle_follow();
+            }
+        ));
+
+        widget.connect_radio_clicked(clone!(
+            #[weak]
+            model,
+            move || {
+                model.play_radio();
+            }
+        ));
 
         widget.connect_bottom_edge(clone!(
             #[weak]
@@ -125,6 +249,14 @@ impl ArtistDetails {
             }
         ));
 
+        widget.connect_bottom_visible(clone!(
+            #[weak]
+            model,
+            move || {
+                model.load_related_artists();
+            }
+        ));
+
         if let Some(store) = model.get_list_store() {
             widget.bind_artist_releases(
                 worker.clone(),
@@ -139,6 +271,20 @@ impl ArtistDetails {
             );
         }
 
+        if let Some(store) = model.get_related_store() {
+            widget.bind_related_artists(
+                worker.clone(),
+                &store,
+                clone!(
+                    #[weak]
+                    model,
+                    move |id| {
+                        model.open_artist(id);
+                    }
+                ),
+            );
+        }
+
         let playlist = Box::new(Playlist::new(
             widget.top_tracks_widget().clone(),
             Rc::clone(&model),
@@ -171,6 +317,17 @@ impl EventListener for ArtistDetails {
             {
                 self.widget.set_loaded();
             }
+            AppEvent::BrowserEvent(BrowserEvent::ArtistFollowChanged(id))
+                if id == &self.model.id =>
+            {
+                self.widget.set_followed(self.model.is_followed());
+            }
+            AppEvent::BrowserEvent(BrowserEvent::RelatedArtistsUpdated(id))
+                if id == &self.model.id =>
+            {
+                self.widget
+                    .set_related_visible(self.model.has_related_artists());
+            }
             _ => {}
         }
         self.broadcast_event(event);
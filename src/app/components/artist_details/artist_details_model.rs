@@ -1,22 +1,28 @@
 use gio::prelude::*;
 use gio::SimpleActionGroup;
+use std::cell::Cell;
+use std::collections::HashSet;
 use std::ops::Deref;
 use std::rc::Rc;
 
 use crate::api::SpotifyApiError;
 use crate::app::components::SimpleHeaderBarModel;
-use crate::app::components::{labels, PlaylistModel};
+use crate::app::components::{check_saved_tracks, labels, share, PlaylistModel};
 use crate::app::models::*;
 use crate::app::state::SelectionContext;
 use crate::app::state::{
-    BrowserAction, BrowserEvent, PlaybackAction, SelectionAction, SelectionState,
+    BrowserAction, PlaybackAction, ScreenName, SelectionAction, SelectionState,
 };
-use crate::app::{ActionDispatcher, AppAction, AppEvent, AppModel, ListStore};
+use crate::app::{ActionDispatcher, AppAction, AppModel, ListStore};
+
+// How many tracks an artist radio starts with
+const RADIO_SIZE: usize = 50;
 
 pub struct ArtistDetailsModel {
     pub id: String,
     app_model: Rc<AppModel>,
     dispatcher: Box<dyn ActionDispatcher>,
+    related_requested: Cell<bool>,
 }
 
 impl ArtistDetailsModel {
@@ -25,12 +31,33 @@ impl ArtistDetailsModel {
             id,
             app_model,
             dispatcher,
+            related_requested: Cell::new(false),
         }
     }
 
-    pub fn get_artist_name(&self) -> Option<impl Deref<Target = String> + '_> {
+    pub fn share_actions(&self) -> gio::ActionGroup {
+        let group = SimpleActionGroup::new();
+        share::add_share_actions(
+            &group,
+            share::ShareKind::Artist,
+            &self.id,
+            self.dispatcher.box_clone(),
+        );
+        group.upcast()
+    }
+
+    pub fn share_menu(&self) -> gio::MenuModel {
+        let menu = gio::Menu::new();
+        share::append_share_items(&menu, "artist");
+        menu.upcast()
+    }
+
+    pub fn is_followed(&self) -> Option<bool> {
         self.app_model
-            .map_state_opt(|s| s.browser.artist_state(&self.id)?.artist.as_ref())
+            .get_state()
+            .browser
+            .artist_state(&self.id)?
+            .is_followed
     }
 
     pub fn get_list_store(&self) -> Option<impl Deref<Target = ListStore<AlbumModel>> + '_> {
@@ -38,6 +65,43 @@ impl ArtistDetailsModel {
             .map_state_opt(|s| Some(&s.browser.artist_state(&self.id)?.albums))
     }
 
+    pub fn get_related_store(&self) -> Option<impl Deref<Target = ListStore<ArtistModel>> + '_> {
+        self.app_model
+            .map_state_opt(|s| Some(&s.browser.artist_state(&self.id)?.related_artists))
+    }
+
+    pub fn has_related_artists(&self) -> bool {
+        self.get_related_store()
+            .map(|list| list.len() > 0)
+            .unwrap_or(false)
+    }
+
+    // Called whenever the bottom of the page is in view, only the first call does anything
+    pub fn load_related_artists(&self) -> Option<()> {
+        let state = self.app_model.get_state();
+        let artist_state = state.browser.artist_state(&self.id)?;
+        // While releases are still being loaded, the bottom of the page keeps moving away
+        if artist_state.artist.is_none()
+            || artist_state.next_page.next_offset.is_some()
+            || self.related_requested.replace(true)
+        {
+            return None;
+        }
+
+        let api = self.app_model.get_spotify();
+        let id = self.id.clone();
+        // Not worth bothering anyone if that fails, the section just stays hidden
+        self.dispatcher.dispatch_async(Box::pin(async move {
+            let artists = api
+                .get_related_artists(&id)
+                .await
+                .inspect_err(|e| debug!("Could not load the artists related to {id}: {e}"))
+                .ok()?;
+            Some(BrowserAction::SetRelatedArtists(id, artists).into())
+        }));
+        Some(())
+    }
+
     pub fn load_artist_details(&self, id: String) {
         let api = self.app_model.get_spotify();
         self.dispatcher
@@ -54,10 +118,86 @@ impl ArtistDetailsModel {
             });
     }
 
+    pub fn load_follow_state(&self) {
+        let api = self.app_model.get_spotify();
+        let id = self.id.clone();
+        self.dispatcher
+            .call_spotify_and_dispatch(move || async move {
+                api.is_artist_followed(&id)
+                    .await
+                    .map(|followed| BrowserAction::SetArtistFollowed(id, followed).into())
+            });
+    }
+
+    pub fn toggle_follow(&self) -> Option<()> {
+        let api = self.app_model.get_spotify();
+        let id = self.id.clone();
+        let follow = !self.is_followed()?;
+        // The button was toggled already, it goes back the way it was if that fails
+        let revert = vec![BrowserAction::SetArtistFollowed(id.clone(), !follow).into()];
+        self.dispatcher.call_spotify_and_dispatch_many_or(
+            move || async move {
+                if follow {
+                    api.follow_artist(&id).await?;
+                    let mut actions: Vec<AppAction> =
+                        vec![BrowserAction::SetArtistFollowed(id.clone(), true).into()];
+                    // Followed either way, the library will list it the next time it's loaded
+                    match api.get_artist_summary(&id).await {
+                        Ok(artist) => actions.push(BrowserAction::AddFollowedArtist(artist).into()),
+                        Err(e) => warn!("Could not fetch followed artist {id}: {e}"),
+                    }
+                    Ok(actions)
+                } else {
+                    // The library drops unfollowed artists by itself
+                    api.unfollow_artist(&id).await?;
+                    Ok(vec![BrowserAction::SetArtistFollowed(id, false).into()])
+                }
+            },
+            revert,
+        );
+        Some(())
+    }
+
+    pub fn play_radio(&self) -> Option<()> {
+        let api = self.app_model.get_spotify();
+        let state = self.app_model.get_state();
+        let artist_state = state.browser.artist_state(&self.id)?;
+        let name = artist_state.artist.clone()?;
+        // The top tracks are one click away already, the radio should be about something else
+        let mut seen = artist_state
+            .all_top_tracks
+            .iter()
+            .map(|s| s.id.clone())
+            .collect::<HashSet<_>>();
+        let id = self.id.clone();
+        self.dispatcher
+            .call_spotify_and_dispatch_many(move || async move {
+                let songs = api
+                    .get_artist_radio(&id, RADIO_SIZE + seen.len())
+                    .await?
+                    .into_iter()
+                    .filter(|s| seen.insert(s.id.clone()))
+                    .take(RADIO_SIZE)
+                    .collect::<Vec<_>>();
+                let Some(first) = songs.first().map(|s| s.id.clone()) else {
+                    return Ok(vec![]);
+                };
+                Ok(vec![
+                    PlaybackAction::LoadRadio(name, songs).into(),
+                    PlaybackAction::Load(first).into(),
+                ])
+            });
+        Some(())
+    }
+
     pub fn open_album(&self, id: String) {
         self.dispatcher.dispatch(AppAction::ViewAlbum(id));
     }
 
+    pub fn open_artist(&self, id: String) {
+        self.dispatcher.dispatch(AppAction::ViewArtist(id));
+    }
+
     pub fn load_more(&self) -> Option<()> {
         let api = self.app_model.get_spotify();
         let state = self.app_model.get_state();
@@ -93,18 +233,37 @@ impl PlaylistModel for ArtistDetailsModel {
         !self.app_model.get_state().playback.is_playing()
     }
 
+    fn is_offline(&self) -> bool {
+        self.app_model.get_state().logged_user.offline
+    }
+
     fn current_song_id(&self) -> Option<String> {
         self.app_model.get_state().playback.current_song_id()
     }
 
     fn play_song_at(&self, _pos: usize, id: &str) {
-        let tracks: Vec<SongDescription> = self.song_list_model().collect();
+        // The whole top 10 gets queued, not just what's shown
+        let tracks = self
+            .app_model
+            .get_state()
+            .browser
+            .artist_state(&self.id)
+            .map(|s| s.all_top_tracks.clone())
+            .unwrap_or_else(|| self.song_list_model().collect());
         self.dispatcher
             .dispatch(PlaybackAction::LoadSongs(tracks).into());
         self.dispatcher
             .dispatch(PlaybackAction::Load(id.to_string()).into());
     }
 
+    fn is_song_saved(&self, id: &str) -> Option<bool> {
+        Some(self.app_model.get_state().browser.is_track_saved(id))
+    }
+
+    fn check_saved_songs(&self, ids: Vec<String>) {
+        check_saved_tracks(&self.app_model, self.dispatcher.as_ref(), ids);
+    }
+
     fn actions_for(&self, id: &str) -> Option<gio::ActionGroup> {
         let song = self.song_list_model().get(id)?;
         let song = song.description();
@@ -115,8 +274,15 @@ impl PlaylistModel for ArtistDetailsModel {
             group.add_action(&view_artist);
         }
         group.add_action(&song.make_album_action(self.dispatcher.box_clone(), None));
-        group.add_action(&song.make_link_action(None));
+        group.add_action(&song.make_link_action(self.dispatcher.box_clone(), None));
+        group.add_action(&song.make_open_in_browser_action(None));
+        group.add_action(&song.make_toggle_saved_action(
+            self.app_model.clone(),
+            self.dispatcher.box_clone(),
+            None,
+        ));
         group.add_action(&song.make_queue_action(self.dispatcher.box_clone(), None));
+        group.add_action(&song.make_play_next_action(self.dispatcher.box_clone(), None));
 
         Some(group.upcast())
     }
@@ -134,7 +300,8 @@ impl PlaylistModel for ArtistDetailsModel {
             );
         }
 
-        menu.append(Some(&*labels::COPY_LINK), Some("song.copy_link"));
+        share::append_share_items(&menu, "song");
+        menu.append(Some(&*labels::PLAY_NEXT), Some("song.play_next"));
         menu.append(Some(&*labels::ADD_TO_QUEUE), Some("song.queue"));
         Some(menu.upcast())
     }
@@ -164,15 +331,8 @@ impl PlaylistModel for ArtistDetailsModel {
 }
 
 impl SimpleHeaderBarModel for ArtistDetailsModel {
-    fn title(&self) -> Option<String> {
-        Some(self.get_artist_name()?.clone())
-    }
-
-    fn title_updated(&self, event: &AppEvent) -> bool {
-        matches!(
-            event,
-            AppEvent::BrowserEvent(BrowserEvent::ArtistDetailsUpdated(_))
-        )
+    fn screen_name(&self) -> Option<ScreenName> {
+        Some(ScreenName::Artist(self.id.clone()))
     }
 
     fn selection_context(&self) -> Option<SelectionContext> {
@@ -0,0 +1,219 @@
+use gettextrs::gettext;
+use gio::prelude::*;
+use gtk::prelude::*;
+use gtk::subclass::prelude::*;
+use gtk::{gdk, glib, CompositeTemplate};
+use libadwaita::prelude::*;
+use libadwaita::subclass::prelude::*;
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use super::zoomable_picture::ZoomablePicture;
+use crate::app::components::EventListener;
+use crate::app::loader::ImageLoader;
+use crate::app::{AppEvent, AppModel, Worker};
+
+mod imp {
+
+    use super::*;
+
+    #[derive(Debug, Default, CompositeTemplate)]
+    #[template(resource = "/dev/diegovsky/Riff/components/artwork_viewer.ui")]
+    pub struct ArtworkViewerDialog {
+        #[template_child]
+        pub toast_overlay: TemplateChild<libadwaita::ToastOverlay>,
+
+        #[template_child]
+        pub save_button: TemplateChild<gtk::Button>,
+
+        #[template_child]
+        pub title: TemplateChild<libadwaita::WindowTitle>,
+
+        #[template_child]
+        pub picture: TemplateChild<ZoomablePicture>,
+
+        #[template_child]
+        pub spinner: TemplateChild<gtk::Spinner>,
+
+        // The full size image, as it sits in the image cache
+        pub file: RefCell<Option<PathBuf>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for ArtworkViewerDialog {
+        const NAME: &'static str = "ArtworkViewerDialog";
+        type Type = super::ArtworkViewerDialog;
+        type ParentType = libadwaita::Dialog;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for ArtworkViewerDialog {
+        fn constructed(&self) {
+            self.parent_constructed();
+            let obj = self.obj();
+            self.picture.connect_click_outside(clone!(
+                #[weak]
+                obj,
+                move || {
+                    obj.close();
+                }
+            ));
+            self.save_button.connect_clicked(clone!(
+                #[weak]
+                obj,
+                move |_| obj.save()
+            ));
+        }
+    }
+
+    impl WidgetImpl for ArtworkViewerDialog {}
+    impl AdwDialogImpl for ArtworkViewerDialog {}
+}
+
+glib::wrapper! {
+    pub struct
+    ArtworkViewerDialog(ObjectSubclass<imp::ArtworkViewerDialog>) @extends gtk::Widget, libadwaita::Dialog;
+}
+
+impl ArtworkViewerDialog {
+    pub fn new() -> Self {
+        glib::Object::new()
+    }
+
+    pub fn set_album(&self, title: &str, artist: &str) {
+        let widget = self.imp();
+        widget.title.set_title(title);
+        widget.title.set_subtitle(artist);
+    }
+
+    pub fn set_loading(&self, loading: bool) {
+        self.imp().spinner.set_visible(loading);
+    }
+
+    pub fn set_artwork_file(&self, path: &Path) {
+        match gdk::Texture::from_filename(path) {
+            Ok(texture) => self.imp().picture.set_texture(Some(texture)),
+            Err(e) => warn!("Could not load artwork: {e}"),
+        }
+    }
+
+    // Only the full size image can be saved, not the placeholder
+    pub fn set_saveable_file(&self, path: PathBuf) {
+        let widget = self.imp();
+        widget.file.replace(Some(path));
+        widget.save_button.set_sensitive(true);
+    }
+
+    fn save(&self) {
+        let Some(file) = self.imp().file.borrow().clone() else {
+            return;
+        };
+        let file_name = format!("{}.jpg", self.imp().title.title().replace('/', "-"));
+        let file_dialog = gtk::FileDialog::builder()
+            .initial_name(file_name)
+            .modal(true)
+            .build();
+        let parent = self.root().and_downcast::<gtk::Window>();
+        file_dialog.save(
+            parent.as_ref(),
+            None::<&gio::Cancellable>,
+            clone!(
+                #[weak(rename_to = _self)]
+                self,
+                move |result| {
+                    // Dismissed
+                    let Some(destination) = result.ok().and_then(|f| f.path()) else {
+                        return;
+                    };
+                    let message = match std::fs::copy(&file, &destination) {
+                        // translators: Shown after saving the artwork to a file
+                        Ok(_) => gettext("Image saved"),
+                        Err(e) => {
+                            warn!("Could not save artwork: {e}");
+                            // translators: Shown when the artwork could not be saved to a file
+                            gettext("Could not save image")
+                        }
+                    };
+                    _self
+                        .imp()
+                        .toast_overlay
+                        .add_toast(libadwaita::Toast::new(&message));
+                }
+            ),
+        );
+    }
+}
+
+// Opens the cover of an album in all its glory
+pub struct ArtworkViewer {
+    app_model: Rc<AppModel>,
+    window: gtk::Window,
+    worker: Worker,
+}
+
+impl ArtworkViewer {
+    pub fn new(app_model: Rc<AppModel>, window: gtk::Window, worker: Worker) -> Self {
+        Self {
+            app_model,
+            window,
+            worker,
+        }
+    }
+
+    fn show(&self, id: &str) {
+        let dialog = ArtworkViewerDialog::new();
+        dialog.set_content_width(self.window.width());
+        dialog.set_content_height(self.window.height());
+        dialog.set_loading(true);
+        dialog.present(Some(&self.window));
+
+        let api = self.app_model.get_spotify();
+        let id = id.to_owned();
+        self.worker.send_local_task(async move {
+            let album = match api.get_album(&id).await {
+                Ok(album) => album.description,
+                Err(e) => {
+                    warn!("Could not get album for artwork: {e}");
+                    dialog.set_loading(false);
+                    return;
+                }
+            };
+            dialog.set_album(&album.title, &album.artists_name());
+
+            // The small one is pretty much always in the cache already, it's better than nothing while we wait
+            let loader = ImageLoader::new();
+            if let Some(art) = album.art.as_ref() {
+                if let Some(path) = loader.load_remote_file(art, "jpg").await {
+                    dialog.set_artwork_file(&path);
+                }
+            }
+
+            let large_art = album.large_art.or(album.art);
+            let path = match large_art {
+                Some(url) => loader.load_remote_file(&url, "jpg").await,
+                None => None,
+            };
+            dialog.set_loading(false);
+            if let Some(path) = path {
+                dialog.set_artwork_file(&path);
+                dialog.set_saveable_file(path);
+            }
+        });
+    }
+}
+
+impl EventListener for ArtworkViewer {
+    fn on_event(&mut self, event: &AppEvent) {
+        if let AppEvent::ArtworkShown(id) = event {
+            self.show(id);
+        }
+    }
+}
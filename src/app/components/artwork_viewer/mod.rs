@@ -0,0 +1,11 @@
+#[allow(clippy::module_inception)]
+mod artwork_viewer;
+mod zoomable_picture;
+
+use gtk::prelude::StaticType;
+
+pub use artwork_viewer::*;
+
+pub fn expose_widgets() {
+    zoomable_picture::ZoomablePicture::static_type();
+}
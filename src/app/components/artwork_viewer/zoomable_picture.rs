@@ -0,0 +1,198 @@
+use gtk::prelude::*;
+use gtk::subclass::prelude::*;
+use gtk::{gdk, glib, graphene};
+use std::cell::{Cell, RefCell};
+
+const MAX_ZOOM: f64 = 8.0;
+const SCROLL_ZOOM_STEP: f64 = 1.2;
+
+mod imp {
+
+    use super::*;
+
+    #[derive(Default)]
+    pub struct ZoomablePicture {
+        pub texture: RefCell<Option<gdk::Texture>>,
+        pub zoom: Cell<f64>,
+        pub offset: Cell<(f64, f64)>,
+        // Zoom and offset when the current gesture began
+        pub gesture_origin: Cell<(f64, (f64, f64))>,
+        pub on_click_outside: RefCell<Option<Box<dyn Fn()>>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for ZoomablePicture {
+        const NAME: &'static str = "ZoomablePicture";
+        type Type = super::ZoomablePicture;
+        type ParentType = gtk::Widget;
+    }
+
+    impl ObjectImpl for ZoomablePicture {
+        fn constructed(&self) {
+            self.parent_constructed();
+            self.zoom.set(1.0);
+            self.obj().setup_gestures();
+        }
+    }
+
+    impl WidgetImpl for ZoomablePicture {
+        fn snapshot(&self, snapshot: &gtk::Snapshot) {
+            let Some(rect) = self.obj().image_rect() else {
+                return;
+            };
+            if let Some(texture) = self.texture.borrow().as_ref() {
+                snapshot.push_clip(&graphene::Rect::new(
+                    0.0,
+                    0.0,
+                    self.obj().width() as f32,
+                    self.obj().height() as f32,
+                ));
+                snapshot.append_texture(texture, &rect);
+                snapshot.pop();
+            }
+        }
+    }
+}
+
+glib::wrapper! {
+    pub struct ZoomablePicture(ObjectSubclass<imp::ZoomablePicture>) @extends gtk::Widget;
+}
+
+impl ZoomablePicture {
+    fn setup_gestures(&self) {
+        let zoom = gtk::GestureZoom::new();
+        zoom.connect_begin(clone!(
+            #[weak(rename_to = _self)]
+            self,
+            move |_, _| _self.begin_gesture()
+        ));
+        zoom.connect_scale_changed(clone!(
+            #[weak(rename_to = _self)]
+            self,
+            move |_, scale| {
+                let (zoom, _) = _self.imp().gesture_origin.get();
+                _self.set_zoom(zoom * scale);
+            }
+        ));
+        self.add_controller(zoom);
+
+        let drag = gtk::GestureDrag::new();
+        drag.connect_drag_begin(clone!(
+            #[weak(rename_to = _self)]
+            self,
+            move |_, _, _| _self.begin_gesture()
+        ));
+        drag.connect_drag_update(clone!(
+            #[weak(rename_to = _self)]
+            self,
+            move |_, x, y| {
+                let (_, (offset_x, offset_y)) = _self.imp().gesture_origin.get();
+                _self.set_offset(offset_x + x, offset_y + y);
+            }
+        ));
+        self.add_controller(drag);
+
+        let scroll = gtk::EventControllerScroll::new(gtk::EventControllerScrollFlags::VERTICAL);
+        scroll.connect_scroll(clone!(
+            #[weak(rename_to = _self)]
+            self,
+            #[upgrade_or]
+            glib::Propagation::Proceed,
+            move |_, _, dy| {
+                let zoom = _self.imp().zoom.get();
+                if dy < 0.0 {
+                    _self.set_zoom(zoom * SCROLL_ZOOM_STEP);
+                } else {
+                    _self.set_zoom(zoom / SCROLL_ZOOM_STEP);
+                }
+                glib::Propagation::Stop
+            }
+        ));
+        self.add_controller(scroll);
+
+        // Clicks don't get there if the pointer moved too much (that's a drag)
+        let click = gtk::GestureClick::new();
+        click.connect_released(clone!(
+            #[weak(rename_to = _self)]
+            self,
+            move |_, n_press, x, y| {
+                let inside = _self
+                    .image_rect()
+                    .is_some_and(|r| r.contains_point(&graphene::Point::new(x as f32, y as f32)));
+                if n_press == 2 && inside {
+                    _self.reset_zoom();
+                } else if n_press == 1 && !inside {
+                    if let Some(f) = _self.imp().on_click_outside.borrow().as_ref() {
+                        f();
+                    }
+                }
+            }
+        ));
+        self.add_controller(click);
+    }
+
+    fn begin_gesture(&self) {
+        let imp = self.imp();
+        imp.gesture_origin.set((imp.zoom.get(), imp.offset.get()));
+    }
+
+    // Where the texture ends up: fitted to the widget, then zoomed and moved around
+    fn image_rect(&self) -> Option<graphene::Rect> {
+        let imp = self.imp();
+        let texture = imp.texture.borrow();
+        let texture = texture.as_ref()?;
+        let (width, height) = (self.width() as f32, self.height() as f32);
+        let (texture_width, texture_height) = (texture.width() as f32, texture.height() as f32);
+        if texture_width <= 0.0 || texture_height <= 0.0 {
+            return None;
+        }
+
+        let scale =
+            f32::min(width / texture_width, height / texture_height) * imp.zoom.get() as f32;
+        let (w, h) = (texture_width * scale, texture_height * scale);
+        let (offset_x, offset_y) = imp.offset.get();
+        Some(graphene::Rect::new(
+            (width - w) / 2.0 + offset_x as f32,
+            (height - h) / 2.0 + offset_y as f32,
+            w,
+            h,
+        ))
+    }
+
+    fn set_zoom(&self, zoom: f64) {
+        let imp = self.imp();
+        let zoom = zoom.clamp(1.0, MAX_ZOOM);
+        imp.zoom.set(zoom);
+        if zoom <= 1.0 {
+            imp.offset.set((0.0, 0.0));
+        }
+        self.queue_draw();
+    }
+
+    fn set_offset(&self, x: f64, y: f64) {
+        let imp = self.imp();
+        // No point in moving the image around if it's not zoomed in
+        if imp.zoom.get() <= 1.0 {
+            return;
+        }
+        imp.offset.set((x, y));
+        self.queue_draw();
+    }
+
+    pub fn reset_zoom(&self) {
+        self.set_zoom(1.0);
+    }
+
+    // Keeps the zoom level, so that swapping the placeholder for the real thing isn't jarring
+    pub fn set_texture(&self, texture: Option<gdk::Texture>) {
+        self.imp().texture.replace(texture);
+        self.queue_draw();
+    }
+
+    pub fn connect_click_outside<F>(&self, f: F)
+    where
+        F: Fn() + 'static,
+    {
+        self.imp().on_click_outside.replace(Some(Box::new(f)));
+    }
+}
@@ -1,4 +1,5 @@
 use crate::app::components::display_add_css_provider;
+use crate::app::components::utils::show_offline_tooltip;
 use gettextrs::gettext;
 use gtk::prelude::*;
 use gtk::subclass::prelude::*;
@@ -32,6 +33,9 @@ mod imp {
         #[template_child]
         pub play_button: TemplateChild<gtk::Button>,
 
+        #[template_child]
+        pub menu_button: TemplateChild<gtk::MenuButton>,
+
         #[template_child]
         pub info_button: TemplateChild<gtk::Button>,
 
@@ -98,7 +102,13 @@ mod imp {
     }
 
     #[glib::derived_properties]
-    impl ObjectImpl for AlbumHeaderWidget {}
+    impl ObjectImpl for AlbumHeaderWidget {
+        fn constructed(&self) {
+            self.parent_constructed();
+            show_offline_tooltip(&*self.play_button);
+            show_offline_tooltip(&*self.like_button);
+        }
+    }
     impl WidgetImpl for AlbumHeaderWidget {}
     impl BoxImpl for AlbumHeaderWidget {}
 }
@@ -139,6 +149,16 @@ impl AlbumHeaderWidget {
         self.imp().info_button.connect_clicked(move |_| f());
     }
 
+    pub fn connect_artwork_clicked<F>(&self, f: F)
+    where
+        F: Fn() + 'static,
+    {
+        let click = gtk::GestureClick::new();
+        click.connect_released(move |_, _, _, _| f());
+        self.imp().album_art.add_controller(click);
+        self.imp().album_art.set_cursor_from_name(Some("pointer"));
+    }
+
     pub fn connect_artist_clicked<F>(&self, f: F)
     where
         F: Fn() + 'static,
@@ -149,6 +169,11 @@ impl AlbumHeaderWidget {
         });
     }
 
+    pub fn set_menu(&self, menu: &gio::MenuModel, actions: &gio::ActionGroup) {
+        self.imp().menu_button.set_menu_model(Some(menu));
+        self.insert_action_group("album", Some(actions));
+    }
+
     pub fn set_liked(&self, is_liked: bool) {
         self.imp().like_button.set_icon_name(if is_liked {
             "starred-symbolic"
@@ -157,6 +182,12 @@ impl AlbumHeaderWidget {
         });
     }
 
+    // Playing and saving need the network
+    pub fn set_offline(&self, offline: bool) {
+        self.imp().play_button.set_sensitive(!offline);
+        self.imp().like_button.set_sensitive(!offline);
+    }
+
     pub fn set_playing(&self, is_playing: bool) {
         let playback_icon = if is_playing {
             "media-playback-pause-symbolic"
@@ -175,6 +206,13 @@ impl AlbumHeaderWidget {
         self.imp().play_button.set_tooltip_text(tooltip_text);
     }
 
+    // Until the tracks are there, set_playing puts the icon back
+    pub fn set_play_pending(&self) {
+        let spinner = gtk::Spinner::new();
+        spinner.start();
+        self.imp().play_button.set_child(Some(&spinner));
+    }
+
     pub fn set_artwork(&self, pixbuf: &gdk_pixbuf::Pixbuf) {
         let texture = gdk::Texture::for_pixbuf(pixbuf);
         self.imp().album_art.set_paintable(Some(&texture));
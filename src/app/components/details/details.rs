@@ -9,11 +9,12 @@ use super::release_details::ReleaseDetailsDialog;
 use super::DetailsModel;
 
 use crate::app::components::{
-    Component, EventListener, HeaderBarComponent, HeaderBarWidget, Playlist, ScrollingHeaderWidget,
+    Component, EventListener, FilterBarWidget, HeaderBarComponent, HeaderBarWidget, Playlist,
+    PlaylistModel, ScrollingHeaderWidget,
 };
 use crate::app::dispatch::Worker;
 use crate::app::loader::ImageLoader;
-use crate::app::state::PlaybackEvent;
+use crate::app::state::{LoginEvent, PlaybackEvent, ScreenName};
 use crate::app::{AppEvent, BrowserEvent};
 
 mod imp {
@@ -31,6 +32,9 @@ mod imp {
         #[template_child]
         pub headerbar: TemplateChild<HeaderBarWidget>,
 
+        #[template_child]
+        pub filter_bar: TemplateChild<FilterBarWidget>,
+
         #[template_child]
         pub header_widget: TemplateChild<AlbumHeaderWidget>,
 
@@ -74,27 +78,11 @@ impl AlbumDetailsWidget {
         glib::Object::new()
     }
 
-    fn set_header_visible(&self, visible: bool) {
-        let widget = self.imp();
-        widget.headerbar.set_title_visible(true);
-        if visible {
-            widget.headerbar.add_classes(&["flat"]);
-        } else {
-            widget.headerbar.remove_classes(&["flat"]);
-        }
-    }
-
-    fn connect_header(&self) {
-        self.set_header_visible(false);
-        self.imp()
-            .scrolling_header
-            .connect_header_visibility(clone!(
-                #[weak(rename_to = _self)]
-                self,
-                move |visible| {
-                    _self.set_header_visible(visible);
-                }
-            ));
+    fn connect_header_visibility<F>(&self, f: F)
+    where
+        F: Fn(bool) + Clone + 'static,
+    {
+        self.imp().scrolling_header.connect_header_visibility(f);
     }
 
     fn connect_bottom_edge<F>(&self, f: F)
@@ -112,6 +100,10 @@ impl AlbumDetailsWidget {
         self.imp().album_tracks.as_ref()
     }
 
+    fn filter_bar_widget(&self) -> &FilterBarWidget {
+        self.imp().filter_bar.as_ref()
+    }
+
     fn set_loaded(&self) {
         self.imp()
             .scrolling_header
@@ -143,19 +135,37 @@ impl AlbumDetailsWidget {
         ));
     }
 
+    fn connect_artwork_clicked<F>(&self, f: F)
+    where
+        F: Fn() + 'static,
+    {
+        self.imp().header_widget.connect_artwork_clicked(f);
+    }
+
+    fn set_header_menu(&self, menu: &gio::MenuModel, actions: &gio::ActionGroup) {
+        self.imp().header_widget.set_menu(menu, actions);
+    }
+
     fn set_liked(&self, is_liked: bool) {
         self.imp().header_widget.set_liked(is_liked);
     }
 
+    fn set_offline(&self, offline: bool) {
+        self.imp().header_widget.set_offline(offline);
+    }
+
     fn set_playing(&self, is_playing: bool) {
         self.imp().header_widget.set_playing(is_playing);
     }
 
+    fn set_play_pending(&self) {
+        self.imp().header_widget.set_play_pending();
+    }
+
     fn set_album_and_artist_and_year(&self, album: &str, artist: &str, year: Option<u32>) {
         self.imp()
             .header_widget
             .set_album_and_artist_and_year(album, artist, year);
-        self.imp().headerbar.set_title_and_subtitle(album, artist);
     }
 
     fn set_artwork(&self, art: &gdk_pixbuf::Pixbuf) {
@@ -186,11 +196,14 @@ impl Details {
 
         let widget = AlbumDetailsWidget::new();
 
-        let playlist = Box::new(Playlist::new(
-            widget.album_tracks_widget().clone(),
-            model.clone(),
-            worker.clone(),
-        ));
+        let playlist = Box::new(
+            Playlist::new(
+                widget.album_tracks_widget().clone(),
+                model.clone(),
+                worker.clone(),
+            )
+            .with_filter_bar(widget.filter_bar_widget().clone()),
+        );
 
         let headerbar_widget = widget.headerbar_widget();
         let headerbar = Box::new(HeaderBarComponent::new(
@@ -209,10 +222,37 @@ impl Details {
         widget.connect_play(clone!(
             #[weak]
             model,
-            move || model.toggle_play_album()
+            #[weak(rename_to = widget)]
+            widget,
+            move || {
+                model.toggle_play_album();
+                if model.is_play_pending() {
+                    widget.set_play_pending();
+                }
+            }
         ));
 
-        widget.connect_header();
+        widget.connect_artwork_clicked(clone!(
+            #[weak]
+            model,
+            move || model.view_artwork()
+        ));
+
+        // Only once, the details get updated every time they're loaded again
+        widget.connect_artist_clicked(clone!(
+            #[weak]
+            model,
+            move || model.view_artist()
+        ));
+
+        widget.connect_header_visibility(clone!(
+            #[weak]
+            model,
+            move |visible| model.set_header_visible(visible)
+        ));
+
+        widget.set_header_menu(&model.header_menu(), &model.header_actions());
+        widget.set_offline(model.is_offline());
 
         widget.connect_bottom_edge(clone!(
             #[weak]
@@ -249,6 +289,16 @@ impl Details {
         }
     }
 
+    // Play might have been pressed before there was anything to play
+    fn update_play_pending(&self) {
+        self.model.play_if_pending();
+        if self.model.is_play_pending() {
+            self.widget.set_play_pending();
+        } else {
+            self.update_playing(self.model.is_playing());
+        }
+    }
+
     fn update_playing(&self, is_playing: bool) {
         if !self.model.album_is_playing() || !self.model.is_playing() {
             self.widget.set_playing(false);
@@ -270,12 +320,6 @@ impl Details {
                 album.year(),
             );
 
-            self.widget.connect_artist_clicked(clone!(
-                #[weak(rename_to = model)]
-                self.model,
-                move || model.view_artist()
-            ));
-
             self.modal.set_details(
                 &album.title,
                 &album.artists_name(),
@@ -322,6 +366,18 @@ impl EventListener for Details {
             {
                 self.update_details();
                 self.update_playing(true);
+                self.update_play_pending();
+            }
+            AppEvent::BrowserEvent(BrowserEvent::AlbumTracksAppended(id))
+                if id == &self.model.id =>
+            {
+                self.update_play_pending();
+            }
+            AppEvent::BrowserEvent(BrowserEvent::TracksLoadFailed(ScreenName::AlbumDetails(
+                id,
+            ))) if id == &self.model.id => {
+                self.model.cancel_play_pending();
+                self.update_play_pending();
             }
             AppEvent::BrowserEvent(BrowserEvent::AlbumSaved(id))
             | AppEvent::BrowserEvent(BrowserEvent::AlbumUnsaved(id))
@@ -329,6 +385,9 @@ impl EventListener for Details {
             {
                 self.update_liked();
             }
+            AppEvent::LoginEvent(LoginEvent::OfflineChanged(offline)) => {
+                self.widget.set_offline(*offline);
+            }
             AppEvent::PlaybackEvent(PlaybackEvent::PlaybackPaused) => {
                 self.update_playing(false);
             }
@@ -1,26 +1,32 @@
 use gettextrs::gettext;
 use gio::prelude::*;
 use gio::SimpleActionGroup;
-use std::cell::Ref;
+use std::cell::{Cell, Ref};
 use std::ops::Deref;
 use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
 use crate::api::SpotifyApiError;
-use crate::app::components::labels;
 use crate::app::components::HeaderBarModel;
 use crate::app::components::PlaylistModel;
 use crate::app::components::SimpleHeaderBarModel;
 use crate::app::components::SimpleHeaderBarModelWrapper;
+use crate::app::components::{check_saved_tracks, labels, share};
 use crate::app::dispatch::ActionDispatcher;
 use crate::app::models::*;
-use crate::app::state::SelectionContext;
-use crate::app::state::{BrowserAction, PlaybackAction, SelectionAction, SelectionState};
-use crate::app::{AppAction, AppEvent, AppModel, AppState, BatchQuery, SongsSource};
+use crate::app::state::{
+    BrowserAction, PlaybackAction, ScreenName, SelectionAction, SelectionContext, SelectionState,
+};
+use crate::app::{AppAction, AppModel, AppState, BatchQuery, SongsSource};
+use crate::settings::{is_album_gapless, set_album_gapless};
 
 pub struct DetailsModel {
     pub id: String,
     app_model: Rc<AppModel>,
     dispatcher: Box<dyn ActionDispatcher>,
+    // Play was pressed, but there wasn't anything to play yet
+    play_pending: Cell<bool>,
 }
 
 impl DetailsModel {
@@ -29,6 +35,7 @@ impl DetailsModel {
             id,
             app_model,
             dispatcher,
+            play_pending: Cell::new(false),
         }
     }
 
@@ -53,21 +60,78 @@ impl DetailsModel {
         })
     }
 
+    pub fn header_actions(&self) -> gio::ActionGroup {
+        let group = SimpleActionGroup::new();
+        let force_gapless = gio::SimpleAction::new_stateful(
+            "force_gapless",
+            None,
+            &is_album_gapless(&self.id).to_variant(),
+        );
+        let id = self.id.clone();
+        force_gapless.connect_activate(move |action, _| {
+            let gapless = !action
+                .state()
+                .and_then(|s| s.get::<bool>())
+                .unwrap_or(false);
+            set_album_gapless(&id, gapless);
+            action.set_state(&gapless.to_variant());
+        });
+        group.add_action(&force_gapless);
+        share::add_share_actions(
+            &group,
+            share::ShareKind::Album,
+            &self.id,
+            self.dispatcher.box_clone(),
+        );
+        group.upcast()
+    }
+
+    pub fn header_menu(&self) -> gio::MenuModel {
+        let menu = gio::Menu::new();
+        // translators: Album menu entry, makes this album play without gaps between tracks even if gapless playback is off
+        menu.append(
+            Some(&gettext("Force gapless for this album")),
+            Some("album.force_gapless"),
+        );
+        share::append_share_items(&menu, "album");
+        menu.upcast()
+    }
+
+    // What was cached shows up right away, and is replaced by whatever the network says once it answers
     pub fn load_album_info(&self) {
         let id = self.id.clone();
         let api = self.app_model.get_spotify();
-        self.dispatcher
-            .call_spotify_and_dispatch(move || async move {
+
+        // Once the network answered, the cached details would only be older
+        let loaded = Arc::new(AtomicBool::new(false));
+
+        let (cached_api, cached_id, cached_loaded) = (api.clone(), id.clone(), loaded.clone());
+        self.dispatcher.dispatch_async(Box::pin(async move {
+            let album = cached_api.get_cached_album(&cached_id).await.ok()?;
+            if cached_loaded.load(Ordering::SeqCst) {
+                return None;
+            }
+            Some(BrowserAction::SetAlbumDetails(Box::new(album)).into())
+        }));
+
+        let failed = BrowserAction::TracksLoadFailed(ScreenName::AlbumDetails(id.clone()));
+        self.dispatcher.call_spotify_and_dispatch_many_or(
+            move || async move {
                 let album = api.get_album(&id).await;
                 match album {
-                    Ok(album) => Ok(BrowserAction::SetAlbumDetails(Box::new(album)).into()),
+                    Ok(album) => {
+                        loaded.store(true, Ordering::SeqCst);
+                        Ok(vec![BrowserAction::SetAlbumDetails(Box::new(album)).into()])
+                    }
                     Err(SpotifyApiError::BadStatus(400, _))
                     | Err(SpotifyApiError::BadStatus(404, _)) => {
-                        Ok(BrowserAction::NavigationPop.into())
+                        Ok(vec![BrowserAction::NavigationPop.into()])
                     }
                     Err(e) => Err(e),
                 }
-            });
+            },
+            vec![failed.into()],
+        );
     }
 
     pub fn view_artist(&self) {
@@ -110,35 +174,59 @@ impl DetailsModel {
             Some(SongsSource::Album(ref id)) if id == &self.id)
     }
 
+    pub fn view_artwork(&self) {
+        self.dispatcher
+            .dispatch(AppAction::ViewArtwork(self.id.clone()));
+    }
+
+    pub fn is_play_pending(&self) -> bool {
+        self.play_pending.get()
+    }
+
+    // The tracks we were waiting for won't come
+    pub fn cancel_play_pending(&self) {
+        self.play_pending.set(false);
+    }
+
     pub fn toggle_play_album(&self) {
-        if self.get_album_description().is_some() {
-            if !self.album_is_playing() {
-                if self.state().playback.is_shuffled() {
-                    self.dispatcher
-                        .dispatch(AppAction::PlaybackAction(PlaybackAction::ToggleShuffle));
-                }
+        if !self.album_is_playing() {
+            self.play_pending.set(true);
+            self.play_if_pending();
+            return;
+        }
+        if self.state().playback.is_playing() {
+            self.dispatcher
+                .dispatch(AppAction::PlaybackAction(PlaybackAction::Pause));
+        } else {
+            self.dispatcher
+                .dispatch(AppAction::PlaybackAction(PlaybackAction::Play));
+        }
+    }
 
-                let first_song = self.song_list_model().index(0);
-                let Some(first_song) = first_song else {
-                    error!("Unable to start playback because the song list is empty");
-                    self.dispatcher
-                        .dispatch(AppAction::ShowNotification(gettext(
-                            "An error occured. Check logs for details!",
-                        )));
-                    return;
-                };
-
-                self.play_song_at(0, &first_song.get_id());
-                return;
-            }
-            if self.state().playback.is_playing() {
-                self.dispatcher
-                    .dispatch(AppAction::PlaybackAction(PlaybackAction::Pause));
-            } else {
+    // Called again whenever tracks come in, until there's something to play
+    pub fn play_if_pending(&self) {
+        if !self.play_pending.get() {
+            return;
+        }
+
+        let first_song = self.song_list_model().index(0);
+        let Some(first_song) = first_song else {
+            // The first page comes with the details, after that we have to ask for the next one
+            let loading = self.get_album_description().is_none() || self.load_more().is_some();
+            if !loading {
                 self.dispatcher
-                    .dispatch(AppAction::PlaybackAction(PlaybackAction::Play));
+                    .dispatch(AppAction::ShowNotification(labels::NOTHING_TO_PLAY.clone()));
             }
+            self.play_pending.set(loading);
+            return;
+        };
+
+        self.play_pending.set(false);
+        if self.state().playback.is_shuffled() {
+            self.dispatcher
+                .dispatch(AppAction::PlaybackAction(PlaybackAction::ToggleShuffle));
         }
+        self.play_song_at(0, &first_song.get_id());
     }
 
     pub fn load_more(&self) -> Option<()> {
@@ -151,18 +239,38 @@ impl DetailsModel {
         let id = self.id.clone();
         let next_query = query.next()?;
         let loader = self.app_model.get_batch_loader();
+        let failed = BrowserAction::TracksLoadFailed(ScreenName::AlbumDetails(id.clone()));
 
-        self.dispatcher.dispatch_async(Box::pin(async move {
+        self.dispatcher.dispatch_many_async(Box::pin(async move {
             loader
-                .query(next_query, |_s, song_batch| {
-                    BrowserAction::AppendAlbumTracks(id, Box::new(song_batch)).into()
-                })
+                .query_or(
+                    next_query,
+                    |_s, song_batch| {
+                        BrowserAction::AppendAlbumTracks(id, Box::new(song_batch)).into()
+                    },
+                    vec![failed.into()],
+                )
                 .await
         }));
 
         Some(())
     }
 
+    pub fn set_header_visible(&self, visible: bool) {
+        let name = ScreenName::AlbumDetails(self.id.clone());
+        // This gets called on every scroll event, only bother if something changed
+        let is_up_to_date = self
+            .state()
+            .browser
+            .header(&name)
+            .map(|header| header.header_visible == visible)
+            .unwrap_or(true);
+        if !is_up_to_date {
+            self.dispatcher
+                .dispatch(BrowserAction::SetHeaderVisible(name, visible).into());
+        }
+    }
+
     pub fn to_headerbar_model(self: &Rc<Self>) -> Rc<impl HeaderBarModel> {
         Rc::new(SimpleHeaderBarModelWrapper::new(
             self.clone(),
@@ -187,6 +295,10 @@ impl PlaylistModel for DetailsModel {
         !self.app_model.get_state().playback.is_playing()
     }
 
+    fn is_offline(&self) -> bool {
+        self.app_model.get_state().logged_user.offline
+    }
+
     fn show_song_covers(&self) -> bool {
         false
     }
@@ -229,6 +341,14 @@ impl PlaylistModel for DetailsModel {
         }
     }
 
+    fn is_song_saved(&self, id: &str) -> Option<bool> {
+        Some(self.app_model.get_state().browser.is_track_saved(id))
+    }
+
+    fn check_saved_songs(&self, ids: Vec<String>) {
+        check_saved_tracks(&self.app_model, self.dispatcher.as_ref(), ids);
+    }
+
     fn actions_for(&self, id: &str) -> Option<gio::ActionGroup> {
         let song = self.song_list_model().get(id)?;
         let song = song.description();
@@ -238,8 +358,15 @@ impl PlaylistModel for DetailsModel {
         for view_artist in song.make_artist_actions(self.dispatcher.box_clone(), None) {
             group.add_action(&view_artist);
         }
-        group.add_action(&song.make_link_action(None));
+        group.add_action(&song.make_link_action(self.dispatcher.box_clone(), None));
+        group.add_action(&song.make_open_in_browser_action(None));
+        group.add_action(&song.make_toggle_saved_action(
+            self.app_model.clone(),
+            self.dispatcher.box_clone(),
+            None,
+        ));
         group.add_action(&song.make_queue_action(self.dispatcher.box_clone(), None));
+        group.add_action(&song.make_play_next_action(self.dispatcher.box_clone(), None));
 
         Some(group.upcast())
     }
@@ -256,19 +383,16 @@ impl PlaylistModel for DetailsModel {
             );
         }
 
-        menu.append(Some(&*labels::COPY_LINK), Some("song.copy_link"));
+        share::append_share_items(&menu, "song");
+        menu.append(Some(&*labels::PLAY_NEXT), Some("song.play_next"));
         menu.append(Some(&*labels::ADD_TO_QUEUE), Some("song.queue"));
         Some(menu.upcast())
     }
 }
 
 impl SimpleHeaderBarModel for DetailsModel {
-    fn title(&self) -> Option<String> {
-        None
-    }
-
-    fn title_updated(&self, _: &AppEvent) -> bool {
-        false
+    fn screen_name(&self) -> Option<ScreenName> {
+        Some(ScreenName::AlbumDetails(self.id.clone()))
     }
 
     fn selection_context(&self) -> Option<SelectionContext> {
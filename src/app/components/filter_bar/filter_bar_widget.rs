@@ -0,0 +1,98 @@
+use gtk::prelude::*;
+use gtk::subclass::prelude::*;
+use gtk::CompositeTemplate;
+use libadwaita::subclass::prelude::BinImpl;
+
+mod imp {
+
+    use super::*;
+
+    #[derive(Debug, Default, CompositeTemplate)]
+    #[template(resource = "/dev/diegovsky/Riff/components/filter_bar.ui")]
+    pub struct FilterBarWidget {
+        #[template_child]
+        pub search_bar: TemplateChild<gtk::SearchBar>,
+
+        #[template_child]
+        pub entry: TemplateChild<gtk::SearchEntry>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for FilterBarWidget {
+        const NAME: &'static str = "FilterBarWidget";
+        type Type = super::FilterBarWidget;
+        type ParentType = libadwaita::Bin;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for FilterBarWidget {
+        fn constructed(&self) {
+            self.parent_constructed();
+
+            // This also makes Escape close the bar
+            self.search_bar.connect_entry(&*self.entry);
+
+            let entry = self.entry.get();
+            self.search_bar
+                .connect_search_mode_enabled_notify(move |search_bar| {
+                    if !search_bar.is_search_mode() {
+                        entry.set_text("");
+                    }
+                });
+
+            // Ctrl+F is bound to the global search, so we have to get to it first (hence the capture phase).
+            // Screens that aren't shown keep their filter bar around, so we only handle it when we're visible.
+            let controller = gtk::ShortcutController::new();
+            controller.set_scope(gtk::ShortcutScope::Global);
+            controller.set_propagation_phase(gtk::PropagationPhase::Capture);
+            controller.add_shortcut(gtk::Shortcut::new(
+                gtk::ShortcutTrigger::parse_string("<Ctrl>F"),
+                Some(gtk::CallbackAction::new(|widget, _| {
+                    let widget = widget.downcast_ref::<super::FilterBarWidget>().unwrap();
+                    if widget.is_mapped() {
+                        widget.reveal();
+                        glib::Propagation::Stop
+                    } else {
+                        glib::Propagation::Proceed
+                    }
+                })),
+            ));
+            self.obj().add_controller(controller);
+        }
+    }
+
+    impl WidgetImpl for FilterBarWidget {}
+    impl BinImpl for FilterBarWidget {}
+}
+
+glib::wrapper! {
+    pub struct FilterBarWidget(ObjectSubclass<imp::FilterBarWidget>) @extends gtk::Widget, libadwaita::Bin;
+}
+
+impl FilterBarWidget {
+    pub fn reveal(&self) {
+        self.imp().search_bar.set_search_mode(true);
+        self.imp().entry.grab_focus();
+    }
+
+    pub fn reset(&self) {
+        self.imp().search_bar.set_search_mode(false);
+        self.imp().entry.set_text("");
+    }
+
+    pub fn connect_filter_changed<F>(&self, f: F)
+    where
+        F: Fn(&str) + 'static,
+    {
+        self.imp()
+            .entry
+            .connect_search_changed(move |entry| f(&entry.text()));
+    }
+}
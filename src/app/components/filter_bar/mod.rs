@@ -0,0 +1,7 @@
+mod filter_bar_widget;
+pub use filter_bar_widget::*;
+use gtk::prelude::StaticType;
+
+pub fn expose_widgets() {
+    filter_bar_widget::FilterBarWidget::static_type();
+}
@@ -0,0 +1,67 @@
+use std::cell::Ref;
+use std::ops::Deref;
+use std::rc::Rc;
+
+use crate::app::models::*;
+use crate::app::state::HomeState;
+use crate::app::{ActionDispatcher, AppAction, AppModel, BrowserAction, ListStore};
+
+const BATCH_SIZE: usize = 30;
+
+pub struct FollowedArtistsModel {
+    app_model: Rc<AppModel>,
+    dispatcher: Box<dyn ActionDispatcher>,
+}
+
+impl FollowedArtistsModel {
+    pub fn new(app_model: Rc<AppModel>, dispatcher: Box<dyn ActionDispatcher>) -> Self {
+        Self {
+            app_model,
+            dispatcher,
+        }
+    }
+
+    fn state(&self) -> Option<Ref<'_, HomeState>> {
+        self.app_model.map_state_opt(|s| s.browser.home_state())
+    }
+
+    pub fn get_list_store(&self) -> Option<impl Deref<Target = ListStore<ArtistModel>> + '_> {
+        Some(Ref::map(self.state()?, |s| &s.followed_artists))
+    }
+
+    pub fn refresh_followed_artists(&self) {
+        let api = self.app_model.get_spotify();
+
+        self.dispatcher
+            .call_spotify_and_dispatch(move || async move {
+                api.get_followed_artists(None, BATCH_SIZE)
+                    .await
+                    .map(|artists| BrowserAction::SetFollowedArtists(artists).into())
+            });
+    }
+
+    pub fn has_artists(&self) -> bool {
+        self.get_list_store()
+            .map(|list| list.len() > 0)
+            .unwrap_or(false)
+    }
+
+    // Spotify pages followed artists with a cursor rather than an offset
+    pub fn load_more_artists(&self) -> Option<()> {
+        let api = self.app_model.get_spotify();
+        let after = self.state()?.next_artists_cursor.clone()?;
+
+        self.dispatcher
+            .call_spotify_and_dispatch(move || async move {
+                api.get_followed_artists(Some(after), BATCH_SIZE)
+                    .await
+                    .map(|artists| BrowserAction::AppendFollowedArtists(artists).into())
+            });
+
+        Some(())
+    }
+
+    pub fn open_artist(&self, artist_id: String) {
+        self.dispatcher.dispatch(AppAction::ViewArtist(artist_id));
+    }
+}
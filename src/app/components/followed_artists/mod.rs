@@ -0,0 +1,6 @@
+#[allow(clippy::module_inception)]
+mod followed_artists;
+mod followed_artists_model;
+
+pub use followed_artists::*;
+pub use followed_artists_model::*;
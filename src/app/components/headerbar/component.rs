@@ -4,15 +4,15 @@ use gtk::prelude::*;
 
 use crate::app::{
     components::{Component, EventListener, ListenerComponent},
-    state::{SelectionContext, SelectionEvent},
+    state::{HeaderState, ScreenName, SelectionContext, SelectionEvent},
     ActionDispatcher, AppAction, AppEvent, AppModel, BrowserAction, BrowserEvent,
 };
 
 use super::widget::HeaderBarWidget;
 
 pub trait HeaderBarModel {
-    fn title(&self) -> Option<String>;
-    fn title_updated(&self, event: &AppEvent) -> bool;
+    fn header(&self) -> HeaderState;
+    fn header_updated(&self, event: &AppEvent) -> bool;
     fn go_back(&self);
     fn can_go_back(&self) -> bool;
     fn selection_context(&self) -> Option<SelectionContext>;
@@ -47,11 +47,11 @@ impl DefaultHeaderBarModel {
 }
 
 impl HeaderBarModel for DefaultHeaderBarModel {
-    fn title(&self) -> Option<String> {
-        self.title.clone()
+    fn header(&self) -> HeaderState {
+        HeaderState::with_title(self.title.clone())
     }
 
-    fn title_updated(&self, _: &AppEvent) -> bool {
+    fn header_updated(&self, _: &AppEvent) -> bool {
         false
     }
 
@@ -91,8 +91,8 @@ impl HeaderBarModel for DefaultHeaderBarModel {
 }
 
 pub trait SimpleHeaderBarModel {
-    fn title(&self) -> Option<String>;
-    fn title_updated(&self, event: &AppEvent) -> bool;
+    // The screen whose header state (in the browser state) we should display, if any
+    fn screen_name(&self) -> Option<ScreenName>;
     fn selection_context(&self) -> Option<SelectionContext>;
     fn select_all(&self);
 }
@@ -121,12 +121,20 @@ impl<M> HeaderBarModel for SimpleHeaderBarModelWrapper<M>
 where
     M: SimpleHeaderBarModel + 'static,
 {
-    fn title(&self) -> Option<String> {
-        self.wrapped_model.title()
+    fn header(&self) -> HeaderState {
+        self.wrapped_model
+            .screen_name()
+            .and_then(|name| self.app_model.get_state().browser.header(&name).cloned())
+            .unwrap_or_default()
     }
 
-    fn title_updated(&self, event: &AppEvent) -> bool {
-        self.wrapped_model.title_updated(event)
+    fn header_updated(&self, event: &AppEvent) -> bool {
+        match event {
+            AppEvent::BrowserEvent(BrowserEvent::HeaderUpdated(name)) => {
+                self.wrapped_model.screen_name().as_ref() == Some(name)
+            }
+            _ => false,
+        }
     }
 
     fn go_back(&self) {
@@ -187,9 +195,10 @@ mod common {
             | AppEvent::BrowserEvent(BrowserEvent::NavigationHidden(_)) => {
                 model.cancel_selection();
                 widget.set_can_go_back(model.can_go_back());
+                widget.set_header(&model.header());
             }
-            event if model.title_updated(event) => {
-                widget.set_title(model.title().as_ref().map(|s| &s[..]));
+            event if model.header_updated(event) => {
+                widget.set_header(&model.header());
             }
             _ => {}
         }
@@ -220,7 +229,7 @@ mod common {
             move || model.go_back()
         ));
 
-        widget.set_title(model.title().as_ref().map(|s| &s[..]));
+        widget.set_header(&model.header());
         widget.set_selection_possible(model.selection_context().is_some());
         widget.set_select_all_possible(model.can_select_all());
         widget.set_can_go_back(model.can_go_back());
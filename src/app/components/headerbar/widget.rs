@@ -4,6 +4,7 @@ use gtk::CompositeTemplate;
 use libadwaita::subclass::prelude::BinImpl;
 
 use crate::app::components::labels;
+use crate::app::state::HeaderState;
 
 mod imp {
 
@@ -148,6 +149,10 @@ impl HeaderBarWidget {
             .set_title(&labels::n_songs_selected_label(count));
     }
 
+    pub fn pack_end(&self, widget: &impl IsA<gtk::Widget>) {
+        self.imp().main_header.pack_end(widget);
+    }
+
     pub fn add_classes(&self, classes: &[&str]) {
         for &class in classes {
             self.imp().main_header.add_css_class(class);
@@ -160,19 +165,16 @@ impl HeaderBarWidget {
         }
     }
 
-    pub fn set_title_visible(&self, visible: bool) {
-        self.imp().title.set_visible(visible);
-    }
-
-    pub fn set_title_and_subtitle(&self, title: &str, subtitle: &str) {
-        self.imp().title.set_title(title);
-        self.imp().title.set_subtitle(subtitle);
-    }
-
-    pub fn set_title(&self, title: Option<&str>) {
-        self.imp().title.set_visible(title.is_some());
-        if let Some(title) = title {
-            self.imp().title.set_title(title);
+    pub fn set_header(&self, header: &HeaderState) {
+        let title = &self.imp().title;
+        title.set_visible(header.title.is_some());
+        title.set_title(header.title.as_deref().unwrap_or_default());
+        title.set_subtitle(header.subtitle.as_deref().unwrap_or_default());
+        // The title fades out (see the css) while the headerbar is flat
+        if header.is_flat() {
+            self.add_classes(&["flat"]);
+        } else {
+            self.remove_classes(&["flat"]);
         }
     }
 }
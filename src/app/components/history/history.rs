@@ -0,0 +1,91 @@
+use gtk::prelude::*;
+use gtk::subclass::prelude::*;
+use gtk::CompositeTemplate;
+use std::rc::Rc;
+
+use super::HistoryModel;
+use crate::app::components::{Component, EventListener, Playlist};
+use crate::app::{AppEvent, Worker};
+use libadwaita::subclass::prelude::BinImpl;
+
+mod imp {
+
+    use super::*;
+
+    #[derive(Debug, Default, CompositeTemplate)]
+    #[template(resource = "/dev/diegovsky/Riff/components/history.ui")]
+    pub struct HistoryWidget {
+        #[template_child]
+        pub song_list: TemplateChild<gtk::ListView>,
+
+        #[template_child]
+        pub scrolled_window: TemplateChild<gtk::ScrolledWindow>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for HistoryWidget {
+        const NAME: &'static str = "HistoryWidget";
+        type Type = super::HistoryWidget;
+        type ParentType = libadwaita::Bin;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for HistoryWidget {}
+    impl WidgetImpl for HistoryWidget {}
+    impl BinImpl for HistoryWidget {}
+}
+
+glib::wrapper! {
+    pub struct HistoryWidget(ObjectSubclass<imp::HistoryWidget>) @extends gtk::Widget, libadwaita::Bin;
+}
+
+impl HistoryWidget {
+    fn new() -> Self {
+        glib::Object::new()
+    }
+
+    fn song_list_widget(&self) -> &gtk::ListView {
+        self.imp().song_list.as_ref()
+    }
+}
+
+// What was played during this session, the list itself is kept up to date by the playback state
+pub struct History {
+    widget: HistoryWidget,
+    children: Vec<Box<dyn EventListener>>,
+}
+
+impl History {
+    pub fn new(model: Rc<HistoryModel>, worker: Worker) -> Self {
+        let widget = HistoryWidget::new();
+        let playlist = Playlist::new(widget.song_list_widget().clone(), model, worker);
+
+        Self {
+            widget,
+            children: vec![Box::new(playlist)],
+        }
+    }
+}
+
+impl Component for History {
+    fn get_root_widget(&self) -> &gtk::Widget {
+        self.widget.upcast_ref()
+    }
+
+    fn get_children(&mut self) -> Option<&mut Vec<Box<dyn EventListener>>> {
+        Some(&mut self.children)
+    }
+}
+
+impl EventListener for History {
+    fn on_event(&mut self, event: &AppEvent) {
+        self.broadcast_event(event);
+    }
+}
@@ -0,0 +1,107 @@
+use gio::prelude::*;
+use gio::SimpleActionGroup;
+use std::rc::Rc;
+
+use crate::app::components::{check_saved_tracks, labels, share, PlaylistModel};
+use crate::app::models::*;
+use crate::app::state::PlaybackAction;
+use crate::app::{ActionDispatcher, AppModel};
+
+pub struct HistoryModel {
+    app_model: Rc<AppModel>,
+    dispatcher: Box<dyn ActionDispatcher>,
+}
+
+impl HistoryModel {
+    pub fn new(app_model: Rc<AppModel>, dispatcher: Box<dyn ActionDispatcher>) -> Self {
+        Self {
+            app_model,
+            dispatcher,
+        }
+    }
+}
+
+impl PlaylistModel for HistoryModel {
+    fn song_list_model(&self) -> SongListModel {
+        self.app_model.get_state().playback.history().clone()
+    }
+
+    fn is_paused(&self) -> bool {
+        !self.app_model.get_state().playback.is_playing()
+    }
+
+    fn is_offline(&self) -> bool {
+        self.app_model.get_state().logged_user.offline
+    }
+
+    fn current_song_id(&self) -> Option<String> {
+        self.app_model.get_state().playback.current_song_id()
+    }
+
+    // The song might not be in the queue anymore, so it's put back in first
+    fn play_song_at(&self, _pos: usize, id: &str) {
+        let Some(song) = self.song_list_model().get(id) else {
+            return;
+        };
+        let song = song.description().clone();
+        self.dispatcher.dispatch_many(vec![
+            PlaybackAction::QueueNext(vec![song]).into(),
+            PlaybackAction::Load(id.to_string()).into(),
+        ]);
+    }
+
+    fn autoscroll_to_playing(&self) -> bool {
+        false
+    }
+
+    fn is_song_saved(&self, id: &str) -> Option<bool> {
+        Some(self.app_model.get_state().browser.is_track_saved(id))
+    }
+
+    fn check_saved_songs(&self, ids: Vec<String>) {
+        check_saved_tracks(&self.app_model, self.dispatcher.as_ref(), ids);
+    }
+
+    fn actions_for(&self, id: &str) -> Option<gio::ActionGroup> {
+        let song = self.song_list_model().get(id)?;
+        let song = song.description();
+
+        let group = SimpleActionGroup::new();
+
+        for view_artist in song.make_artist_actions(self.dispatcher.box_clone(), None) {
+            group.add_action(&view_artist);
+        }
+        group.add_action(&song.make_album_action(self.dispatcher.box_clone(), None));
+        group.add_action(&song.make_link_action(self.dispatcher.box_clone(), None));
+        group.add_action(&song.make_open_in_browser_action(None));
+        group.add_action(&song.make_toggle_saved_action(
+            self.app_model.clone(),
+            self.dispatcher.box_clone(),
+            None,
+        ));
+        group.add_action(&song.make_play_next_action(self.dispatcher.box_clone(), None));
+        group.add_action(&song.make_queue_action(self.dispatcher.box_clone(), None));
+
+        Some(group.upcast())
+    }
+
+    fn menu_for(&self, id: &str) -> Option<gio::MenuModel> {
+        let song = self.song_list_model().get(id)?;
+        let song = song.description();
+
+        let menu = gio::Menu::new();
+        menu.append(Some(&*labels::VIEW_ALBUM), Some("song.view_album"));
+        for artist in song.artists.iter() {
+            menu.append(
+                Some(&labels::more_from_label(&artist.name)),
+                Some(&format!("song.view_artist_{}", artist.id)),
+            );
+        }
+
+        share::append_share_items(&menu, "song");
+        menu.append(Some(&*labels::PLAY_NEXT), Some("song.play_next"));
+        menu.append(Some(&*labels::ADD_TO_QUEUE), Some("song.queue"));
+
+        Some(menu.upcast())
+    }
+}
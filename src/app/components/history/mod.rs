@@ -0,0 +1,6 @@
+#[allow(clippy::module_inception)]
+mod history;
+pub use history::*;
+
+mod history_model;
+pub use history_model::*;
@@ -7,17 +7,38 @@ lazy_static! {
     // translators: This is part of a contextual menu attached to a single track; the intent is to copy the link (public URL) to a specific track.
     pub static ref COPY_LINK: String = gettext("Copy link");
 
+    // translators: This is part of a contextual menu attached to a track, album, playlist, artist or show; this entry opens its page on the Spotify website.
+    pub static ref OPEN_IN_BROWSER: String = gettext("Open in browser");
+
     // translators: This is part of a contextual menu attached to a single track; this entry adds a track at the end of the play queue.
     pub static ref ADD_TO_QUEUE: String = gettext("Add to queue");
 
     // translators: This is part of a contextual menu attached to a single track; this entry removes a track from the play queue.
     pub static ref REMOVE_FROM_QUEUE: String = gettext("Remove from queue");
 
+    // translators: This is part of a contextual menu attached to a single track; this entry moves (or adds) the track right after the one currently playing.
+    pub static ref PLAY_NEXT: String = gettext("Play next");
+
+    // translators: This notification is shown after adding tracks to the play queue, from a track's contextual menu.
+    pub static ref ADDED_TO_QUEUE: String = gettext("Added to queue");
+
     // translators: This is part of a contextual menu attached to a playlist in the sidebar; this entry deletes a playlist owned by the user.
     pub static ref DELETE_PLAYLIST: String = gettext("Delete playlist");
 
     // translators: This is part of a contextual menu attached to a playlist in the sidebar; this entry unfollows a playlist the user does not own.
     pub static ref UNFOLLOW_PLAYLIST: String = gettext("Unfollow playlist");
+
+    // translators: This is part of a contextual menu attached to a playlist in the sidebar; this entry saves the playlist to a file.
+    pub static ref EXPORT_PLAYLIST: String = gettext("Export…");
+
+    // translators: This is part of the menu of the play queue; this entry copies the tracks of the queue to the clipboard, one per line.
+    pub static ref COPY_QUEUE: String = gettext("Copy queue");
+
+    // translators: This is part of the menu of the play queue; this entry adds the tracks found in the clipboard to the queue.
+    pub static ref PASTE_QUEUE: String = gettext("Paste queue");
+
+    // translators: This notification is shown after pressing play on an album or playlist that has no playable tracks.
+    pub static ref NOTHING_TO_PLAY: String = gettext("Nothing to play here");
 }
 
 pub fn add_to_playlist_label(playlist: &str) -> String {
@@ -38,6 +59,97 @@ pub fn n_songs_selected_label(n: usize) -> String {
     ngettext!("{} song selected", "{} songs selected", n as u32, n)
 }
 
+pub fn n_tracks_found_label(n: usize) -> String {
+    // this is just to fool xgettext, it doesn't like macros (or rust for that matter) :(
+    if cfg!(debug_assertions) {
+        // translators: This shows up when pasting tracks into the queue, before choosing to replace the queue or add to it.
+        ngettext(
+            "Found {} track in the clipboard",
+            "Found {} tracks in the clipboard",
+            n as u32,
+        );
+    }
+    ngettext!(
+        "Found {} track in the clipboard",
+        "Found {} tracks in the clipboard",
+        n as u32,
+        n
+    )
+}
+
+pub fn n_lines_unresolved_label(n: usize) -> String {
+    // this is just to fool xgettext, it doesn't like macros (or rust for that matter) :(
+    if cfg!(debug_assertions) {
+        // translators: This shows up after pasting a queue, when some of the lines pasted are not tracks we could find.
+        ngettext(
+            "{} line could not be added to the queue",
+            "{} lines could not be added to the queue",
+            n as u32,
+        );
+    }
+    ngettext!(
+        "{} line could not be added to the queue",
+        "{} lines could not be added to the queue",
+        n as u32,
+        n
+    )
+}
+
+pub fn n_tracks_skipped_label(n: usize) -> String {
+    // this is just to fool xgettext, it doesn't like macros (or rust for that matter) :(
+    if cfg!(debug_assertions) {
+        // translators: This shows up when playback had to skip tracks that can't be played (e.g. not available in the user's country).
+        ngettext(
+            "Skipped {} unavailable track",
+            "Skipped {} unavailable tracks",
+            n as u32,
+        );
+    }
+    ngettext!(
+        "Skipped {} unavailable track",
+        "Skipped {} unavailable tracks",
+        n as u32,
+        n
+    )
+}
+
+pub fn importing_tracks_label(n: usize, total: usize) -> String {
+    // this is just to fool xgettext, it doesn't like macros (or rust for that matter) :(
+    if cfg!(debug_assertions) {
+        // translators: This shows up while a playlist is being imported from a file, e.g. "Importing 50 of 200 tracks…"
+        ngettext(
+            "Importing {} of {} track…",
+            "Importing {} of {} tracks…",
+            total as u32,
+        );
+    }
+    ngettext!(
+        "Importing {} of {} track…",
+        "Importing {} of {} tracks…",
+        total as u32,
+        n,
+        total
+    )
+}
+
+pub fn n_tracks_unmatched_label(n: usize) -> String {
+    // this is just to fool xgettext, it doesn't like macros (or rust for that matter) :(
+    if cfg!(debug_assertions) {
+        // translators: This shows up after importing a playlist from a file, when some of its tracks could not be found.
+        ngettext(
+            "{} track could not be found",
+            "{} tracks could not be found",
+            n as u32,
+        );
+    }
+    ngettext!(
+        "{} track could not be found",
+        "{} tracks could not be found",
+        n as u32,
+        n
+    )
+}
+
 pub fn more_from_label(artist: &str) -> String {
     // this is just to fool xgettext, it doesn't like macros (or rust for that matter) :(
     if cfg!(debug_assertions) {
@@ -47,6 +159,15 @@ pub fn more_from_label(artist: &str) -> String {
     gettext!("More from {}", glib::markup_escape_text(artist))
 }
 
+pub fn time_left_label(duration: &str) -> String {
+    // this is just to fool xgettext, it doesn't like macros (or rust for that matter) :(
+    if cfg!(debug_assertions) {
+        // translators: This is shown next to a podcast episode that was partially listened to, e.g. "12∶34 left"
+        gettext("{} left");
+    }
+    gettext!("{} left", duration)
+}
+
 pub fn album_by_artist_label(album: &str, artist: &str) -> String {
     // this is just to fool xgettext, it doesn't like macros (or rust for that matter) :(
     if cfg!(debug_assertions) {
@@ -59,3 +180,101 @@ pub fn album_by_artist_label(album: &str, artist: &str) -> String {
         glib::markup_escape_text(artist)
     )
 }
+
+pub fn artist_radio_label(artist: &str) -> String {
+    // this is just to fool xgettext, it doesn't like macros (or rust for that matter) :(
+    if cfg!(debug_assertions) {
+        // translators: This is shown above the play queue while it's playing songs similar to an artist, e.g. "<Artist> radio"
+        gettext("{} radio");
+    }
+    gettext!("{} radio", glib::markup_escape_text(artist))
+}
+
+pub fn imported_spot_settings_label() -> String {
+    // translators: This shows up once, when settings left by Spot (the app Riff was forked from) were found and copied over
+    gettext("Imported settings from Spot")
+}
+
+pub fn reconnecting_label() -> String {
+    // translators: This shows up when the connection to Spotify was lost (e.g. after a suspend), while trying to get it back
+    gettext("Connection lost, reconnecting…")
+}
+
+pub fn rate_limited_label(retry_after: std::time::Duration) -> String {
+    // At least a second, "0s" would be odd
+    let seconds = retry_after.as_secs().max(1);
+    // this is just to fool xgettext, it doesn't like macros (or rust for that matter) :(
+    if cfg!(debug_assertions) {
+        // translators: This notification shows up when Spotify refuses requests because we sent too many, e.g. "Spotify is rate limiting us, try again in 30s"
+        gettext("Spotify is rate limiting us, try again in {}s");
+    }
+    gettext!("Spotify is rate limiting us, try again in {}s", seconds)
+}
+
+pub fn offline_tooltip() -> String {
+    // translators: Tooltip of the buttons (play, save, edit...) that can't be used while offline, the rest of the app shows what was cached
+    gettext("Not available while offline")
+}
+
+pub fn cache_size_label(bytes: u64) -> String {
+    // this is just to fool xgettext, it doesn't like macros (or rust for that matter) :(
+    if cfg!(debug_assertions) {
+        // translators: This shows how much space the downloaded audio (or cached data) takes on disk, e.g. "Currently using 120 MB"
+        gettext("Currently using {}");
+    }
+    gettext!("Currently using {}", glib::format_size(bytes))
+}
+
+pub fn cache_cleared_label(bytes: u64) -> String {
+    // this is just to fool xgettext, it doesn't like macros (or rust for that matter) :(
+    if cfg!(debug_assertions) {
+        // translators: This shows up after clearing the downloaded audio (or cached data), e.g. "Freed 120 MB"
+        gettext("Freed {}");
+    }
+    gettext!("Freed {}", glib::format_size(bytes))
+}
+
+pub fn sleep_timer_minutes_left_label(n: u64) -> String {
+    // this is just to fool xgettext, it doesn't like macros (or rust for that matter) :(
+    if cfg!(debug_assertions) {
+        // translators: This is the entry to cancel the sleep timer in the menu of the playing track, e.g. "Cancel (12 minutes left)"
+        ngettext(
+            "Cancel ({} minute left)",
+            "Cancel ({} minutes left)",
+            n as u32,
+        );
+    }
+    ngettext!(
+        "Cancel ({} minute left)",
+        "Cancel ({} minutes left)",
+        n as u32,
+        n
+    )
+}
+
+pub fn scrobbler_status_label(user: Option<&str>, last_submission: Option<u64>) -> String {
+    let Some(user) = user else {
+        // translators: Shown in the preferences under Last.fm or ListenBrainz, when no account is connected
+        return gettext("Not logged in");
+    };
+    let last_submission = last_submission
+        .and_then(|at| glib::DateTime::from_unix_local(at as i64).ok())
+        .and_then(|at| at.format("%H:%M").ok());
+    match last_submission {
+        Some(at) => {
+            // this is just to fool xgettext, it doesn't like macros (or rust for that matter) :(
+            if cfg!(debug_assertions) {
+                // translators: Shown in the preferences under Last.fm or ListenBrainz, followed by the user name and the time tracks were last sent at
+                gettext("Logged in as {}, last sent at {}");
+            }
+            gettext!("Logged in as {}, last sent at {}", user, at)
+        }
+        None => {
+            if cfg!(debug_assertions) {
+                // translators: Shown in the preferences under Last.fm or ListenBrainz, followed by the user name
+                gettext("Logged in as {}");
+            }
+            gettext!("Logged in as {}", user)
+        }
+    }
+}
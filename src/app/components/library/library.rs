@@ -4,11 +4,10 @@ use gtk::CompositeTemplate;
 use std::rc::Rc;
 
 use super::LibraryModel;
-use crate::app::components::utils::wrap_flowbox_item;
-use crate::app::components::{AlbumWidget, Component, EventListener};
+use crate::app::components::{AlbumWidget, Component, EventListener, Top};
 use crate::app::dispatch::Worker;
 use crate::app::models::AlbumModel;
-use crate::app::state::LoginEvent;
+use crate::app::state::{LoginEvent, PlaybackEvent};
 use crate::app::{AppEvent, BrowserEvent, ListStore};
 
 mod imp {
@@ -22,10 +21,19 @@ mod imp {
         pub scrolled_window: TemplateChild<gtk::ScrolledWindow>,
 
         #[template_child]
-        pub flowbox: TemplateChild<gtk::FlowBox>,
+        pub gridview: TemplateChild<gtk::GridView>,
 
         #[template_child]
         pub status_page: TemplateChild<libadwaita::StatusPage>,
+
+        #[template_child]
+        pub recently_played_section: TemplateChild<gtk::Box>,
+
+        #[template_child]
+        pub recently_played: TemplateChild<gtk::ListView>,
+
+        #[template_child]
+        pub top_section: TemplateChild<gtk::Box>,
     }
 
     #[glib::object_subclass]
@@ -81,36 +89,54 @@ impl LibraryWidget {
         F: Fn(String) + Clone + 'static,
     {
         let store_clone = store.clone();
-        self.imp()
-            .flowbox
-            .bind_model(Some(store.inner()), move |item| {
-                wrap_flowbox_item(item, |album_model: &AlbumModel| {
-                    AlbumWidget::for_model(album_model, worker.clone())
-                })
-            });
-        self.imp()
-            .flowbox
-            .connect_child_activated(move |_, child| {
-                let album_model = store_clone.get(child.index() as u32);
-                on_album_pressed(album_model.uri());
-            });
+        let gridview = &self.imp().gridview;
+        gridview.set_factory(Some(&AlbumWidget::list_item_factory(worker)));
+        gridview.set_model(Some(&gtk::NoSelection::new(Some(store.inner().clone()))));
+        gridview.connect_activate(move |_, position| {
+            let album_model = store_clone.get(position);
+            on_album_pressed(album_model.uri());
+        });
+    }
+
+    fn bind_recently_played<F>(&self, worker: Worker, store: &ListStore<AlbumModel>, on_pressed: F)
+    where
+        F: Fn(usize) + 'static,
+    {
+        let list = &self.imp().recently_played;
+        list.set_factory(Some(&AlbumWidget::list_item_factory(worker)));
+        list.set_model(Some(&gtk::NoSelection::new(Some(store.inner().clone()))));
+        list.connect_activate(move |_, position| on_pressed(position as usize));
     }
 
     pub fn status_page(&self) -> &libadwaita::StatusPage {
         &self.imp().status_page
     }
+
+    pub fn recently_played_section(&self) -> &gtk::Box {
+        &self.imp().recently_played_section
+    }
+
+    fn set_top_widget(&self, widget: &gtk::Widget) {
+        self.imp().top_section.append(widget);
+    }
 }
 
+// Refresh what was recently played every few tracks
+const TRACKS_BETWEEN_REFRESHES: usize = 3;
+
 pub struct Library {
     widget: LibraryWidget,
     worker: Worker,
     model: Rc<LibraryModel>,
+    tracks_played: usize,
+    children: Vec<Box<dyn EventListener>>,
 }
 
 impl Library {
-    pub fn new(worker: Worker, model: LibraryModel) -> Self {
+    pub fn new(worker: Worker, model: LibraryModel, top: Top) -> Self {
         let model = Rc::new(model);
         let widget = LibraryWidget::new();
+        widget.set_top_widget(top.get_root_widget());
         widget.connect_bottom_edge(clone!(
             #[weak]
             model,
@@ -123,10 +149,12 @@ impl Library {
             widget,
             worker,
             model,
+            tracks_played: 0,
+            children: vec![Box::new(top)],
         }
     }
 
-    fn bind_flowbox(&self) {
+    fn bind_grid(&self) {
         self.widget.bind_albums(
             self.worker.clone(),
             &self.model.get_list_store().unwrap(),
@@ -138,6 +166,17 @@ impl Library {
                 }
             ),
         );
+        self.widget.bind_recently_played(
+            self.worker.clone(),
+            &self.model.get_recently_played_store().unwrap(),
+            clone!(
+                #[weak(rename_to = model)]
+                self.model,
+                move |position| {
+                    model.open_recently_played(position);
+                }
+            ),
+        );
     }
 }
 
@@ -145,19 +184,35 @@ impl EventListener for Library {
     fn on_event(&mut self, event: &AppEvent) {
         match event {
             AppEvent::Started => {
-                let _ = self.model.refresh_saved_albums();
-                self.bind_flowbox();
+                let _ = self.model.load_cached_saved_albums();
+                self.bind_grid();
             }
             AppEvent::LoginEvent(LoginEvent::LoginCompleted) => {
                 let _ = self.model.refresh_saved_albums();
+                self.model.refresh_recently_played();
+            }
+            AppEvent::BrowserEvent(BrowserEvent::HomeVisiblePageChanged("library")) => {
+                self.model.refresh_recently_played();
+            }
+            AppEvent::PlaybackEvent(PlaybackEvent::TrackChanged(_)) => {
+                self.tracks_played += 1;
+                if self.tracks_played.is_multiple_of(TRACKS_BETWEEN_REFRESHES) {
+                    self.model.refresh_recently_played();
+                }
             }
             AppEvent::BrowserEvent(BrowserEvent::LibraryUpdated) => {
                 self.widget
                     .status_page()
                     .set_visible(!self.model.has_albums());
             }
+            AppEvent::BrowserEvent(BrowserEvent::RecentlyPlayedUpdated) => {
+                self.widget
+                    .recently_played_section()
+                    .set_visible(self.model.has_recently_played());
+            }
             _ => {}
         }
+        self.broadcast_event(event);
     }
 }
 
@@ -165,4 +220,8 @@ impl Component for Library {
     fn get_root_widget(&self) -> &gtk::Widget {
         self.widget.as_ref()
     }
+
+    fn get_children(&mut self) -> Option<&mut Vec<Box<dyn EventListener>>> {
+        Some(&mut self.children)
+    }
 }
@@ -27,6 +27,21 @@ impl LibraryModel {
         Some(Ref::map(self.state()?, |s| &s.albums))
     }
 
+    // Renders whatever we got last time, without waiting for the network (or even for a token)
+    pub fn load_cached_saved_albums(&self) -> Option<()> {
+        let api = self.app_model.get_spotify();
+        let batch_size = self.state()?.next_albums_page.batch_size;
+
+        self.dispatcher.dispatch_async(Box::pin(async move {
+            api.get_cached_saved_albums(0, batch_size)
+                .await
+                .ok()
+                .map(|albums| BrowserAction::SetLibraryContent(albums).into())
+        }));
+
+        Some(())
+    }
+
     pub fn refresh_saved_albums(&self) -> Option<()> {
         let api = self.app_model.get_spotify();
         let batch_size = self.state()?.next_albums_page.batch_size;
@@ -41,6 +56,38 @@ impl LibraryModel {
         Some(())
     }
 
+    pub fn get_recently_played_store(
+        &self,
+    ) -> Option<impl Deref<Target = ListStore<AlbumModel>> + '_> {
+        Some(Ref::map(self.state()?, |s| &s.recently_played))
+    }
+
+    pub fn refresh_recently_played(&self) {
+        let api = self.app_model.get_spotify();
+
+        self.dispatcher
+            .call_spotify_and_dispatch(move || async move {
+                api.get_recently_played(50)
+                    .await
+                    .map(|played| BrowserAction::SetRecentlyPlayed(played).into())
+            });
+    }
+
+    pub fn has_recently_played(&self) -> bool {
+        self.get_recently_played_store()
+            .map(|list| list.len() > 0)
+            .unwrap_or(false)
+    }
+
+    pub fn open_recently_played(&self, position: usize) -> Option<()> {
+        let action = match self.state()?.recently_played_items.get(position)? {
+            RecentlyPlayed::Album(album) => AppAction::ViewAlbum(album.id.clone()),
+            RecentlyPlayed::Playlist(playlist) => AppAction::ViewPlaylist(playlist.id.clone()),
+        };
+        self.dispatcher.dispatch(action);
+        Some(())
+    }
+
     pub fn has_albums(&self) -> bool {
         self.get_list_store()
             .map(|list| list.len() > 0)
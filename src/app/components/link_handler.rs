@@ -0,0 +1,52 @@
+use gettextrs::gettext;
+use std::rc::Rc;
+
+use crate::app::components::EventListener;
+use crate::app::state::LoginEvent;
+use crate::app::{ActionDispatcher, AppAction, AppEvent, AppModel};
+
+// Opens Spotify links given to the app (from the desktop, the command line...).
+// Nothing can be shown before we're logged in, so the last link waits until then.
+pub struct LinkHandler {
+    app_model: Rc<AppModel>,
+    dispatcher: Box<dyn ActionDispatcher>,
+    pending: Option<String>,
+}
+
+impl LinkHandler {
+    pub fn new(app_model: Rc<AppModel>, dispatcher: Box<dyn ActionDispatcher>) -> Self {
+        Self {
+            app_model,
+            dispatcher,
+            pending: None,
+        }
+    }
+
+    fn open(&self, uri: &str) {
+        let action = AppAction::OpenURI(uri.to_string()).unwrap_or_else(|| {
+            // translators: Shown when the app is given a Spotify link it can't make sense of (or doesn't support yet)
+            AppAction::ShowNotification(gettext("Failed to open link!"))
+        });
+        self.dispatcher.dispatch(action);
+    }
+}
+
+impl EventListener for LinkHandler {
+    fn on_event(&mut self, event: &AppEvent) {
+        match event {
+            AppEvent::LinkOpened(uri) => {
+                if self.app_model.get_state().logged_user.user.is_some() {
+                    self.open(uri);
+                } else {
+                    self.pending = Some(uri.clone());
+                }
+            }
+            AppEvent::LoginEvent(LoginEvent::LoginCompleted) => {
+                if let Some(uri) = self.pending.take() {
+                    self.open(&uri);
+                }
+            }
+            _ => {}
+        }
+    }
+}
@@ -21,8 +21,29 @@ mod imp {
         #[template_child]
         pub login_with_spotify_button: TemplateChild<gtk::Button>,
 
+        #[template_child]
+        pub cancel_login_button: TemplateChild<gtk::Button>,
+
         #[template_child]
         pub auth_error_container: TemplateChild<gtk::Revealer>,
+
+        #[template_child]
+        pub auth_error_reason: TemplateChild<gtk::Label>,
+
+        #[template_child]
+        pub manual_login: TemplateChild<gtk::Expander>,
+
+        #[template_child]
+        pub manual_login_url: TemplateChild<gtk::Entry>,
+
+        #[template_child]
+        pub copy_login_url_button: TemplateChild<gtk::Button>,
+
+        #[template_child]
+        pub manual_login_entry: TemplateChild<gtk::Entry>,
+
+        #[template_child]
+        pub manual_login_button: TemplateChild<gtk::Button>,
     }
 
     #[glib::object_subclass]
@@ -40,7 +61,16 @@ mod imp {
         }
     }
 
-    impl ObjectImpl for LoginWindow {}
+    impl ObjectImpl for LoginWindow {
+        fn constructed(&self) {
+            self.parent_constructed();
+
+            let url = self.manual_login_url.get();
+            self.copy_login_url_button.connect_clicked(move |button| {
+                button.clipboard().set_text(&url.text());
+            });
+        }
+    }
     impl WidgetImpl for LoginWindow {}
     impl AdwWindowImpl for LoginWindow {}
     impl WindowImpl for LoginWindow {}
@@ -81,8 +111,58 @@ impl LoginWindow {
             .connect_clicked(move |_| on_login_with_spotify_button());
     }
 
-    fn show_auth_error(&self, shown: bool) {
+    fn connect_cancel_login<F>(&self, on_cancel: F)
+    where
+        F: Fn() + 'static,
+    {
+        self.imp()
+            .cancel_login_button
+            .connect_clicked(move |_| on_cancel());
+    }
+
+    // Enter in the entry works too
+    fn connect_login_with_code<F>(&self, on_code: F)
+    where
+        F: Fn(String) + 'static,
+    {
         let widget = self.imp();
+        let entry = widget.manual_login_entry.get();
+        let on_code = Rc::new(move |entry: &gtk::Entry| {
+            let code = entry.text();
+            if !code.trim().is_empty() {
+                on_code(code.to_string());
+            }
+        });
+        widget.manual_login_button.connect_clicked(clone!(
+            #[strong]
+            on_code,
+            move |_| on_code(&entry)
+        ));
+        widget
+            .manual_login_entry
+            .connect_activate(move |entry| on_code(entry));
+    }
+
+    fn show_manual_login(&self, url: &Url) {
+        let widget = self.imp();
+        widget.manual_login_url.set_text(url.as_str());
+        widget.manual_login_entry.set_text("");
+        widget.manual_login.set_visible(true);
+        widget.cancel_login_button.set_visible(true);
+    }
+
+    fn hide_manual_login(&self) {
+        let widget = self.imp();
+        widget.manual_login.set_visible(false);
+        widget.cancel_login_button.set_visible(false);
+    }
+
+    fn show_auth_error(&self, shown: bool, reason: Option<&str>) {
+        let widget = self.imp();
+        widget
+            .auth_error_reason
+            .set_text(reason.unwrap_or_default());
+        widget.auth_error_reason.set_visible(reason.is_some());
         widget.auth_error_container.set_reveal_child(shown);
     }
 }
@@ -102,8 +182,13 @@ impl Login {
         login_window.connect_close(clone!(
             #[weak]
             parent,
+            #[weak]
+            model,
             move || {
-                if let Some(app) = parent.application().as_ref() {
+                // Closing the window while adding an account means not adding it after all
+                if model.is_adding_account() {
+                    model.cancel_add_account();
+                } else if let Some(app) = parent.application().as_ref() {
                     app.quit();
                 }
             }
@@ -117,6 +202,22 @@ impl Login {
             }
         ));
 
+        login_window.connect_cancel_login(clone!(
+            #[weak]
+            model,
+            move || {
+                model.cancel_login();
+            }
+        ));
+
+        login_window.connect_login_with_code(clone!(
+            #[weak]
+            model,
+            move |code| {
+                model.login_with_code(code);
+            }
+        ));
+
         Self {
             parent,
             login_window,
@@ -138,12 +239,13 @@ impl Login {
         self.window().set_visible(false);
     }
 
-    fn reveal_error(&self) {
+    fn reveal_error(&self, reason: Option<&str>) {
         self.show_self();
-        self.login_window.show_auth_error(true);
+        self.login_window.show_auth_error(true, reason);
     }
 
     fn open_login_url(&self, url: Url) {
+        self.login_window.show_manual_login(&url);
         if open::that(url.as_str()).is_err() {
             warn!("Could not open login page");
         }
@@ -156,16 +258,27 @@ impl EventListener for Login {
             AppEvent::LoginEvent(LoginEvent::LoginCompleted) => {
                 self.hide();
             }
-            AppEvent::LoginEvent(LoginEvent::LoginFailed) => {
-                self.reveal_error();
+            AppEvent::LoginEvent(LoginEvent::LoginFailed(reason)) => {
+                // Whatever was pending is gone
+                self.login_window.hide_manual_login();
+                self.reveal_error(reason.as_deref());
+            }
+            AppEvent::LoginEvent(LoginEvent::LoginCancelled) => {
+                self.login_window.hide_manual_login();
             }
             AppEvent::LoginEvent(LoginEvent::LoginStarted(LoginStartedEvent::OpenUrl(url))) => {
                 self.open_login_url(url.clone());
             }
             AppEvent::Started => {
-                self.model.try_autologin();
+                // Let the window show up first, restoring the session happens in the background anyway
+                let model = Rc::clone(&self.model);
+                glib::idle_add_local_once(move || model.try_autologin());
             }
-            AppEvent::LoginEvent(LoginEvent::LogoutCompleted | LoginEvent::LoginShown) => {
+            AppEvent::LoginEvent(
+                LoginEvent::LogoutCompleted
+                | LoginEvent::LoginShown
+                | LoginEvent::AddAccountStarted,
+            ) => {
                 self.show_self();
             }
             _ => {}
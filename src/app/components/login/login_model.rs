@@ -1,13 +1,33 @@
+use std::rc::Rc;
+
 use crate::app::state::{LoginAction, TryLoginAction};
-use crate::app::ActionDispatcher;
+use crate::app::{ActionDispatcher, AppModel};
 
 pub struct LoginModel {
+    app_model: Rc<AppModel>,
     dispatcher: Box<dyn ActionDispatcher>,
 }
 
 impl LoginModel {
-    pub fn new(dispatcher: Box<dyn ActionDispatcher>) -> Self {
-        Self { dispatcher }
+    pub fn new(app_model: Rc<AppModel>, dispatcher: Box<dyn ActionDispatcher>) -> Self {
+        Self {
+            app_model,
+            dispatcher,
+        }
+    }
+
+    // Another account can still be gone back to
+    pub fn is_adding_account(&self) -> bool {
+        self.app_model
+            .get_state()
+            .logged_user
+            .previous_user
+            .is_some()
+    }
+
+    pub fn cancel_add_account(&self) {
+        self.dispatcher
+            .dispatch(LoginAction::CancelAddAccount.into())
     }
 
     pub fn try_autologin(&self) {
@@ -19,4 +39,13 @@ impl LoginModel {
         self.dispatcher
             .dispatch(LoginAction::TryLogin(TryLoginAction::InitLogin).into())
     }
+
+    pub fn cancel_login(&self) {
+        self.dispatcher.dispatch(LoginAction::CancelLogin.into())
+    }
+
+    pub fn login_with_code(&self, code: String) {
+        self.dispatcher
+            .dispatch(LoginAction::TryLogin(TryLoginAction::CompleteLoginWithCode(code)).into())
+    }
 }
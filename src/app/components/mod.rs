@@ -31,6 +31,21 @@ pub use settings::*;
 mod player_notifier;
 pub use player_notifier::PlayerNotifier;
 
+mod track_notifier;
+pub use track_notifier::TrackNotifier;
+
+mod link_handler;
+pub use link_handler::LinkHandler;
+
+mod playback_request_handler;
+pub use playback_request_handler::PlaybackRequestHandler;
+
+mod artwork_viewer;
+pub use artwork_viewer::ArtworkViewer;
+
+mod playlist_transfer;
+pub use playlist_transfer::PlaylistTransfer;
+
 mod library;
 pub use library::*;
 
@@ -61,6 +76,15 @@ pub use device_selector::*;
 mod saved_tracks;
 pub use saved_tracks::*;
 
+mod history;
+pub use history::*;
+
+mod top;
+pub use top::*;
+
+mod new_releases;
+pub use new_releases::*;
+
 mod user_menu;
 pub use user_menu::*;
 
@@ -70,6 +94,15 @@ pub use notification::*;
 mod saved_playlists;
 pub use saved_playlists::*;
 
+mod followed_artists;
+pub use followed_artists::*;
+
+mod saved_shows;
+pub use saved_shows::*;
+
+mod show_details;
+pub use show_details::*;
+
 mod playlist_details;
 pub use playlist_details::*;
 
@@ -85,10 +118,15 @@ pub use headerbar::*;
 mod scrolling_header;
 pub use scrolling_header::*;
 
+mod filter_bar;
+pub use filter_bar::*;
+
 pub mod utils;
 
 pub mod labels;
 
+pub mod share;
+
 pub mod sidebar;
 
 // without this the builder doesn't seen to know about the custom widgets
@@ -99,6 +137,8 @@ pub fn expose_custom_widgets() {
     device_selector::expose_widgets();
     playlist_details::expose_widgets();
     scrolling_header::expose_widgets();
+    filter_bar::expose_widgets();
+    artwork_viewer::expose_widgets();
 }
 
 impl dyn ActionDispatcher {
@@ -111,6 +151,15 @@ impl dyn ActionDispatcher {
     }
 
     fn call_spotify_and_dispatch_many<F, C>(&self, call: C)
+    where
+        C: 'static + Send + Clone + FnOnce() -> F,
+        F: Send + Future<Output = Result<Vec<AppAction>, SpotifyApiError>>,
+    {
+        self.call_spotify_and_dispatch_many_or(call, vec![])
+    }
+
+    // Same as above, with some actions to undo whatever was assumed to succeed when the call fails
+    fn call_spotify_and_dispatch_many_or<F, C>(&self, call: C, on_error: Vec<AppAction>)
     where
         C: 'static + Send + Clone + FnOnce() -> F,
         F: Send + Future<Output = Result<Vec<AppAction>, SpotifyApiError>>,
@@ -118,18 +167,31 @@ impl dyn ActionDispatcher {
         self.dispatch_many_async(Box::pin(async move {
             let first_call = call.clone();
             let result = first_call().await;
-            match result {
-                Ok(actions) => actions,
+            let mut actions = match result {
+                Ok(actions) => return actions,
                 Err(SpotifyApiError::NoToken) => vec![],
-                Err(SpotifyApiError::InvalidToken) => call().await.unwrap_or_else(|_| Vec::new()),
+                Err(SpotifyApiError::InvalidToken) => match call().await {
+                    Ok(actions) => return actions,
+                    Err(_) => vec![],
+                },
+                Err(err @ SpotifyApiError::RateLimited(retry_after)) => {
+                    warn!("Spotify API error: {}", err);
+                    crate::app::session_health::record_api_failure(&err);
+                    vec![AppAction::ShowNotification(labels::rate_limited_label(
+                        retry_after,
+                    ))]
+                }
                 Err(err) => {
                     error!("Spotify API error: {}", err);
+                    crate::app::session_health::record_api_failure(&err);
                     vec![AppAction::ShowNotification(gettext(
                         // translators: This notification is the default message for unhandled errors. Logs refer to console output.
                         "An error occured. Check logs for details!",
                     ))]
                 }
-            }
+            };
+            actions.extend(on_error);
+            actions
         }))
     }
 }
@@ -32,8 +32,13 @@ impl ScreenFactory {
             Rc::clone(&self.app_model),
             self.dispatcher.box_clone(),
         );
+        let top_model = Rc::new(TopModel::new(
+            Rc::clone(&self.app_model),
+            self.dispatcher.box_clone(),
+        ));
+        let top = Top::new(top_model, self.worker.clone());
         StandardScreen::new(
-            Library::new(self.worker.clone(), model),
+            Library::new(self.worker.clone(), model, top),
             Rc::new(screen_model),
         )
     }
@@ -58,6 +63,35 @@ impl ScreenFactory {
         )
     }
 
+    pub fn make_followed_artists(&self) -> impl ListenerComponent {
+        let model =
+            FollowedArtistsModel::new(Rc::clone(&self.app_model), self.dispatcher.box_clone());
+        let screen_model = DefaultHeaderBarModel::new(
+            Some(gettext("Artists")),
+            None,
+            Rc::clone(&self.app_model),
+            self.dispatcher.box_clone(),
+        );
+        StandardScreen::new(
+            FollowedArtists::new(self.worker.clone(), model),
+            Rc::new(screen_model),
+        )
+    }
+
+    pub fn make_saved_shows(&self) -> impl ListenerComponent {
+        let model = SavedShowsModel::new(Rc::clone(&self.app_model), self.dispatcher.box_clone());
+        let screen_model = DefaultHeaderBarModel::new(
+            Some(gettext("Podcasts")),
+            None,
+            Rc::clone(&self.app_model),
+            self.dispatcher.box_clone(),
+        );
+        StandardScreen::new(
+            SavedShows::new(self.worker.clone(), model),
+            Rc::new(screen_model),
+        )
+    }
+
     pub fn make_now_playing(&self) -> impl ListenerComponent {
         let model = Rc::new(NowPlayingModel::new(
             Rc::clone(&self.app_model),
@@ -66,6 +100,37 @@ impl ScreenFactory {
         NowPlaying::new(model, self.worker.clone())
     }
 
+    pub fn make_history(&self) -> impl ListenerComponent {
+        let screen_model = DefaultHeaderBarModel::new(
+            Some(gettext("History")),
+            None,
+            Rc::clone(&self.app_model),
+            self.dispatcher.box_clone(),
+        );
+        let model = Rc::new(HistoryModel::new(
+            Rc::clone(&self.app_model),
+            self.dispatcher.box_clone(),
+        ));
+        StandardScreen::new(
+            History::new(model, self.worker.clone()),
+            Rc::new(screen_model),
+        )
+    }
+
+    pub fn make_new_releases(&self) -> impl ListenerComponent {
+        let model = NewReleasesModel::new(Rc::clone(&self.app_model), self.dispatcher.box_clone());
+        let screen_model = DefaultHeaderBarModel::new(
+            Some(gettext("New releases")),
+            None,
+            Rc::clone(&self.app_model),
+            self.dispatcher.box_clone(),
+        );
+        StandardScreen::new(
+            NewReleases::new(self.worker.clone(), model),
+            Rc::new(screen_model),
+        )
+    }
+
     pub fn make_saved_tracks(&self) -> impl ListenerComponent {
         let screen_model = DefaultHeaderBarModel::new(
             Some(gettext("Saved tracks")),
@@ -138,4 +203,21 @@ impl ScreenFactory {
             Rc::new(screen_model),
         )
     }
+
+    pub fn make_show_details(&self, id: String) -> impl ListenerComponent {
+        let model = Rc::new(ShowDetailsModel::new(
+            id,
+            Rc::clone(&self.app_model),
+            self.dispatcher.box_clone(),
+        ));
+        let screen_model = SimpleHeaderBarModelWrapper::new(
+            Rc::clone(&model),
+            Rc::clone(&self.app_model),
+            self.dispatcher.box_clone(),
+        );
+        StandardScreen::new(
+            ShowDetails::new(model, self.worker.clone()),
+            Rc::new(screen_model),
+        )
+    }
 }
@@ -14,7 +14,11 @@ impl HomePane {
         let library = screen_factory.make_library();
         let saved_playlists = screen_factory.make_saved_playlists();
         let saved_tracks = screen_factory.make_saved_tracks();
+        let followed_artists = screen_factory.make_followed_artists();
+        let saved_shows = screen_factory.make_saved_shows();
         let now_playing = screen_factory.make_now_playing();
+        let history = screen_factory.make_history();
+        let new_releases = screen_factory.make_new_releases();
         let sidebar = screen_factory.make_sidebar(listbox);
 
         let stack = gtk::Stack::new();
@@ -41,6 +45,20 @@ impl HomePane {
             &dest.title(),
         );
 
+        let dest = SidebarDestination::FollowedArtists;
+        stack.add_titled(
+            followed_artists.get_root_widget(),
+            Option::from(dest.id()),
+            &dest.title(),
+        );
+
+        let dest = SidebarDestination::SavedShows;
+        stack.add_titled(
+            saved_shows.get_root_widget(),
+            Option::from(dest.id()),
+            &dest.title(),
+        );
+
         let dest = SidebarDestination::NowPlaying;
         stack.add_titled(
             now_playing.get_root_widget(),
@@ -48,6 +66,20 @@ impl HomePane {
             &dest.title(),
         );
 
+        let dest = SidebarDestination::History;
+        stack.add_titled(
+            history.get_root_widget(),
+            Option::from(dest.id()),
+            &dest.title(),
+        );
+
+        let dest = SidebarDestination::NewReleases;
+        stack.add_titled(
+            new_releases.get_root_widget(),
+            Option::from(dest.id()),
+            &dest.title(),
+        );
+
         Self {
             stack,
             components: vec![
@@ -55,7 +87,11 @@ impl HomePane {
                 Box::new(library),
                 Box::new(saved_playlists),
                 Box::new(saved_tracks),
+                Box::new(followed_artists),
+                Box::new(saved_shows),
                 Box::new(now_playing),
+                Box::new(history),
+                Box::new(new_releases),
             ],
         }
     }
@@ -2,7 +2,7 @@ use gtk::prelude::WidgetExt;
 use std::rc::Rc;
 
 use crate::app::components::{EventListener, ListenerComponent};
-use crate::app::state::ScreenName;
+use crate::app::state::{LoginEvent, ScreenName};
 use crate::app::{AppEvent, BrowserEvent};
 
 use super::{factory::ScreenFactory, home::HomePane, NavigationModel};
@@ -14,6 +14,8 @@ pub struct Navigation {
     home_listbox: gtk::ListBox,
     screen_factory: ScreenFactory,
     children: Vec<Box<dyn ListenerComponent>>,
+    // Only the first login is the start of a session
+    restored: bool,
 }
 
 impl Navigation {
@@ -63,6 +65,7 @@ impl Navigation {
             home_listbox,
             screen_factory,
             children: vec![],
+            restored: false,
         }
     }
 
@@ -91,6 +94,7 @@ impl Navigation {
                 Box::new(self.screen_factory.make_playlist_details(id.to_owned()))
             }
             ScreenName::User(id) => Box::new(self.screen_factory.make_user_details(id.to_owned())),
+            ScreenName::Show(id) => Box::new(self.screen_factory.make_show_details(id.to_owned())),
         };
 
         let widget = component.get_root_widget().clone();
@@ -136,6 +140,10 @@ impl EventListener for Navigation {
             AppEvent::Started => {
                 self.push_screen(&ScreenName::Home);
             }
+            AppEvent::LoginEvent(LoginEvent::LoginCompleted) if !self.restored => {
+                self.restored = true;
+                self.model.restore_navigation();
+            }
             AppEvent::BrowserEvent(BrowserEvent::NavigationPushed(name)) => {
                 self.push_screen(name);
             }
@@ -1,7 +1,29 @@
-use crate::app::state::ScreenName;
-use crate::app::{ActionDispatcher, AppModel, BrowserAction};
+use gettextrs::gettext;
 use std::ops::Deref;
 use std::rc::Rc;
+use std::sync::Arc;
+
+use crate::api::SpotifyApiClient;
+use crate::app::components::sidebar::SidebarDestination;
+use crate::app::state::ScreenName;
+use crate::app::{ActionDispatcher, AppAction, AppModel, BrowserAction};
+use crate::settings::SavedNavigation;
+
+// Things get deleted or made private, so we check before showing an empty page
+async fn screen_exists(api: &Arc<dyn SpotifyApiClient + Send + Sync>, name: &ScreenName) -> bool {
+    let result = match name {
+        ScreenName::AlbumDetails(id) => api.get_album(id).await.map(|_| ()),
+        ScreenName::Artist(id) => api.get_artist(id).await.map(|_| ()),
+        ScreenName::PlaylistDetails(id) => api.get_playlist(id).await.map(|_| ()),
+        ScreenName::User(id) => api.get_user(id).await.map(|_| ()),
+        ScreenName::Show(id) => api.get_show(id).await.map(|_| ()),
+        ScreenName::Home | ScreenName::Search => Ok(()),
+    };
+    if let Err(e) = &result {
+        warn!("Could not restore {}: {e}", name.identifier());
+    }
+    result.is_ok()
+}
 
 pub struct NavigationModel {
     app_model: Rc<AppModel>,
@@ -28,4 +50,53 @@ impl NavigationModel {
     pub fn children_count(&self) -> usize {
         self.app_model.get_state().browser.count()
     }
+
+    // Brings the user back to where they were when the app was closed
+    pub fn restore_navigation(&self) {
+        let Some(saved) = SavedNavigation::new_from_gsettings() else {
+            return;
+        };
+
+        let home_page = [
+            SidebarDestination::Library,
+            SidebarDestination::SavedTracks,
+            SidebarDestination::NowPlaying,
+            SidebarDestination::History,
+            SidebarDestination::NewReleases,
+            SidebarDestination::SavedPlaylists,
+            SidebarDestination::FollowedArtists,
+            SidebarDestination::SavedShows,
+        ]
+        .iter()
+        .map(|dest| dest.id())
+        .find(|id| *id == saved.home_page);
+        if let Some(page) = home_page {
+            self.dispatcher
+                .dispatch(BrowserAction::SetHomeVisiblePage(page).into());
+        }
+
+        if saved.screens.is_empty() {
+            return;
+        }
+        let api = self.app_model.get_spotify();
+        self.dispatcher.dispatch_many_async(Box::pin(async move {
+            for screen in saved.screens.iter() {
+                if !screen_exists(&api, screen).await {
+                    return vec![
+                        // Already there, but this also forgets about the stack we couldn't restore
+                        BrowserAction::NavigationPopTo(ScreenName::Home).into(),
+                        AppAction::ShowNotification(gettext(
+                            // translators: Shown at startup when a page that was open when the app was last closed no longer exists
+                            "Some pages from last time could not be reopened",
+                        )),
+                    ];
+                }
+            }
+            saved
+                .screens
+                .into_iter()
+                .map(|screen| BrowserAction::NavigationPush(screen).into())
+                .collect()
+        }));
+    }
 }
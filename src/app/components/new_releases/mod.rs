@@ -0,0 +1,6 @@
+#[allow(clippy::module_inception)]
+mod new_releases;
+pub use new_releases::*;
+
+mod new_releases_model;
+pub use new_releases_model::*;
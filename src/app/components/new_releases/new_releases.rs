@@ -0,0 +1,147 @@
+use gtk::subclass::prelude::*;
+use gtk::CompositeTemplate;
+use std::rc::Rc;
+
+use super::NewReleasesModel;
+use crate::app::components::{AlbumWidget, Component, EventListener};
+use crate::app::dispatch::Worker;
+use crate::app::models::AlbumModel;
+use crate::app::state::LoginEvent;
+use crate::app::{AppEvent, ListStore};
+
+mod imp {
+
+    use super::*;
+
+    #[derive(Debug, Default, CompositeTemplate)]
+    #[template(resource = "/dev/diegovsky/Riff/components/new_releases.ui")]
+    pub struct NewReleasesWidget {
+        #[template_child]
+        pub scrolled_window: TemplateChild<gtk::ScrolledWindow>,
+
+        #[template_child]
+        pub gridview: TemplateChild<gtk::GridView>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for NewReleasesWidget {
+        const NAME: &'static str = "NewReleasesWidget";
+        type Type = super::NewReleasesWidget;
+        type ParentType = gtk::Box;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for NewReleasesWidget {}
+    impl WidgetImpl for NewReleasesWidget {}
+    impl BoxImpl for NewReleasesWidget {}
+}
+
+glib::wrapper! {
+    pub struct NewReleasesWidget(ObjectSubclass<imp::NewReleasesWidget>) @extends gtk::Widget, gtk::Box;
+}
+
+impl Default for NewReleasesWidget {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NewReleasesWidget {
+    pub fn new() -> Self {
+        glib::Object::new()
+    }
+
+    fn connect_bottom_edge<F>(&self, f: F)
+    where
+        F: Fn() + 'static,
+    {
+        self.imp()
+            .scrolled_window
+            .connect_edge_reached(move |_, pos| {
+                if let gtk::PositionType::Bottom = pos {
+                    f()
+                }
+            });
+    }
+
+    fn bind_albums<F>(&self, worker: Worker, store: &ListStore<AlbumModel>, on_album_pressed: F)
+    where
+        F: Fn(String) + Clone + 'static,
+    {
+        let store_clone = store.clone();
+        let gridview = &self.imp().gridview;
+        gridview.set_factory(Some(&AlbumWidget::list_item_factory(worker)));
+        gridview.set_model(Some(&gtk::NoSelection::new(Some(store.inner().clone()))));
+        gridview.connect_activate(move |_, position| {
+            let album_model = store_clone.get(position);
+            on_album_pressed(album_model.uri());
+        });
+    }
+}
+
+pub struct NewReleases {
+    widget: NewReleasesWidget,
+    worker: Worker,
+    model: Rc<NewReleasesModel>,
+}
+
+impl NewReleases {
+    pub fn new(worker: Worker, model: NewReleasesModel) -> Self {
+        let model = Rc::new(model);
+        let widget = NewReleasesWidget::new();
+        widget.connect_bottom_edge(clone!(
+            #[weak]
+            model,
+            move || {
+                model.load_more_new_releases();
+            }
+        ));
+
+        Self {
+            widget,
+            worker,
+            model,
+        }
+    }
+
+    fn bind_grid(&self) {
+        self.widget.bind_albums(
+            self.worker.clone(),
+            &self.model.get_list_store().unwrap(),
+            clone!(
+                #[weak(rename_to = model)]
+                self.model,
+                move |id| {
+                    model.open_album(id);
+                }
+            ),
+        );
+    }
+}
+
+impl EventListener for NewReleases {
+    fn on_event(&mut self, event: &AppEvent) {
+        match event {
+            AppEvent::Started => {
+                self.bind_grid();
+            }
+            AppEvent::LoginEvent(LoginEvent::LoginCompleted) => {
+                let _ = self.model.refresh_new_releases();
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Component for NewReleases {
+    fn get_root_widget(&self) -> &gtk::Widget {
+        self.widget.as_ref()
+    }
+}
@@ -1,4 +1,5 @@
-use crate::app::components::EventListener;
+use crate::app::components::{labels, EventListener};
+use crate::app::state::PlaybackEvent;
 use crate::app::AppEvent;
 use gdk::prelude::ToVariant;
 use gettextrs::*;
@@ -42,6 +43,17 @@ impl EventListener for Notification {
             self.show(content)
         } else if let AppEvent::PlaylistCreatedNotificationShown(id) = event {
             self.show_playlist_created(id)
+        } else if let AppEvent::PlaybackEvent(PlaybackEvent::TrackBlocked(_)) = event {
+            // translators: Shown when trying to play an explicit track while explicit content is blocked (by the account settings or in the preferences)
+            self.show(&gettext("Explicit content is blocked for this account"))
+        } else if let AppEvent::PlaybackEvent(PlaybackEvent::TrackUnavailable(_)) = event {
+            // translators: Shown when trying to play a track that can't be played, usually because it isn't available in the user's country
+            self.show(&gettext("This track is unavailable"))
+        } else if let AppEvent::PlaybackEvent(PlaybackEvent::UnavailableSkipped(n)) = event {
+            self.show(&labels::n_tracks_skipped_label(*n))
+        } else if let AppEvent::PlaybackEvent(PlaybackEvent::NothingPlayable) = event {
+            // translators: Shown when playback stops because none of the tracks being played are available
+            self.show(&gettext("None of these tracks can be played"))
         }
     }
 }
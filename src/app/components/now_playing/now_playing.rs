@@ -1,12 +1,15 @@
+use gettextrs::gettext;
+use gio::SimpleAction;
 use gtk::prelude::*;
 use gtk::subclass::prelude::*;
 use gtk::CompositeTemplate;
+use libadwaita::prelude::*;
 use std::rc::Rc;
 
-use super::NowPlayingModel;
+use super::{parse_track_ids, NowPlayingModel};
 use crate::app::components::{
-    Component, DeviceSelector, DeviceSelectorWidget, EventListener, HeaderBarComponent,
-    HeaderBarWidget, Playlist,
+    labels, Component, DeviceSelector, DeviceSelectorWidget, EventListener, FilterBarWidget,
+    HeaderBarComponent, HeaderBarWidget, Playlist,
 };
 use crate::app::state::PlaybackEvent;
 use crate::app::{AppEvent, Worker};
@@ -24,11 +27,17 @@ mod imp {
         #[template_child]
         pub headerbar: TemplateChild<HeaderBarWidget>,
 
+        #[template_child]
+        pub filter_bar: TemplateChild<FilterBarWidget>,
+
         #[template_child]
         pub device_selector: TemplateChild<DeviceSelectorWidget>,
 
         #[template_child]
         pub scrolled_window: TemplateChild<gtk::ScrolledWindow>,
+
+        #[template_child]
+        pub radio_banner: TemplateChild<libadwaita::Banner>,
     }
 
     #[glib::object_subclass]
@@ -77,6 +86,10 @@ impl NowPlayingWidget {
         self.imp().song_list.as_ref()
     }
 
+    fn filter_bar_widget(&self) -> &FilterBarWidget {
+        self.imp().filter_bar.as_ref()
+    }
+
     fn headerbar_widget(&self) -> &HeaderBarWidget {
         self.imp().headerbar.as_ref()
     }
@@ -84,6 +97,56 @@ impl NowPlayingWidget {
     fn device_selector_widget(&self) -> &DeviceSelectorWidget {
         self.imp().device_selector.as_ref()
     }
+
+    fn set_radio(&self, artist: Option<&str>) {
+        let banner = &self.imp().radio_banner;
+        if let Some(artist) = artist {
+            banner.set_title(&labels::artist_radio_label(artist));
+        }
+        banner.set_revealed(artist.is_some());
+    }
+
+    fn set_queue_menu(&self, menu: &gio::MenuModel, actions: &gio::ActionGroup) {
+        let button = gtk::MenuButton::builder()
+            .icon_name("view-more-symbolic")
+            .menu_model(menu)
+            .build();
+        self.headerbar_widget().pack_end(&button);
+        let clear_button = gtk::Button::builder()
+            .icon_name("edit-clear-all-symbolic")
+            // translators: Tooltip of the button that empties the play queue, except for the current track
+            .tooltip_text(gettext("Clear upcoming tracks"))
+            .action_name("queue.clear_upcoming")
+            .build();
+        self.headerbar_widget().pack_end(&clear_button);
+        self.insert_action_group("queue", Some(actions));
+    }
+
+    // Calls back with true to replace the queue, false to append to it
+    fn ask_paste_mode(&self, count: usize, on_choice: impl Fn(bool) + 'static) {
+        let dialog = libadwaita::AlertDialog::new(
+            // translators: Title of the dialog shown when pasting tracks into the play queue
+            Some(&gettext("Paste queue")),
+            Some(&labels::n_tracks_found_label(count)),
+        );
+        dialog.add_responses(&[
+            // translators: Dialog button, pasting tracks is cancelled
+            ("cancel", gettext("Cancel").as_str()),
+            // translators: Dialog button, pasted tracks are added at the end of the queue
+            ("append", gettext("Append").as_str()),
+            // translators: Dialog button, pasted tracks take the place of the current queue
+            ("replace", gettext("Replace").as_str()),
+        ]);
+        dialog.set_response_appearance("replace", libadwaita::ResponseAppearance::Destructive);
+        dialog.set_default_response(Some("append"));
+        dialog.set_close_response("cancel");
+        dialog.connect_response(None, move |_, response| match response {
+            "append" => on_choice(false),
+            "replace" => on_choice(true),
+            _ => {}
+        });
+        dialog.present(Some(self));
+    }
 }
 
 pub struct NowPlaying {
@@ -96,6 +159,11 @@ impl NowPlaying {
     pub fn new(model: Rc<NowPlayingModel>, worker: Worker) -> Self {
         let widget = NowPlayingWidget::new();
 
+        widget.set_queue_menu(
+            &model.queue_menu(),
+            &Self::make_queue_actions(&widget, &model, &worker),
+        );
+
         widget.connect_bottom_edge(clone!(
             #[weak]
             model,
@@ -104,11 +172,10 @@ impl NowPlaying {
             }
         ));
 
-        let playlist = Box::new(Playlist::new(
-            widget.song_list_widget().clone(),
-            model.clone(),
-            worker,
-        ));
+        let playlist = Box::new(
+            Playlist::new(widget.song_list_widget().clone(), model.clone(), worker)
+                .with_filter_bar(widget.filter_bar_widget().clone()),
+        );
 
         let headerbar_widget = widget.headerbar_widget();
         let headerbar = Box::new(HeaderBarComponent::new(
@@ -121,12 +188,72 @@ impl NowPlaying {
             model.device_selector_model(),
         ));
 
+        widget.set_radio(model.radio().as_deref());
+
         Self {
             widget,
             model,
             children: vec![playlist, headerbar, device_selector],
         }
     }
+
+    fn make_queue_actions(
+        widget: &NowPlayingWidget,
+        model: &Rc<NowPlayingModel>,
+        worker: &Worker,
+    ) -> gio::ActionGroup {
+        let group = gio::SimpleActionGroup::new();
+
+        let copy = SimpleAction::new("copy", None);
+        copy.connect_activate(clone!(
+            #[weak]
+            model,
+            move |_, _| {
+                model.copy_queue();
+            }
+        ));
+        group.add_action(&copy);
+
+        let clear_upcoming = SimpleAction::new("clear_upcoming", None);
+        clear_upcoming.connect_activate(clone!(
+            #[weak]
+            model,
+            move |_, _| {
+                model.clear_upcoming();
+            }
+        ));
+        group.add_action(&clear_upcoming);
+
+        let paste = SimpleAction::new("paste", None);
+        paste.connect_activate(clone!(
+            #[weak]
+            model,
+            #[weak]
+            widget,
+            #[strong]
+            worker,
+            move |_, _| {
+                let clipboard = widget.clipboard();
+                worker.send_local_task(async move {
+                    let Ok(Some(text)) = clipboard.read_text_future().await else {
+                        return;
+                    };
+                    let (ids, unparsed) = parse_track_ids(&text);
+                    if ids.is_empty() {
+                        model.paste_tracks(ids, unparsed, false);
+                        return;
+                    }
+                    let count = ids.len();
+                    widget.ask_paste_mode(count, move |replace| {
+                        model.paste_tracks(ids.clone(), unparsed, replace);
+                    });
+                });
+            }
+        ));
+        group.add_action(&paste);
+
+        group.upcast()
+    }
 }
 
 impl Component for NowPlaying {
@@ -141,8 +268,16 @@ impl Component for NowPlaying {
 
 impl EventListener for NowPlaying {
     fn on_event(&mut self, event: &AppEvent) {
-        if let AppEvent::PlaybackEvent(PlaybackEvent::TrackChanged(_)) = event {
-            self.model.load_more();
+        match event {
+            AppEvent::PlaybackEvent(PlaybackEvent::TrackChanged(_)) => {
+                self.model.load_more();
+            }
+            // A source change always comes with this one, no need to load anything twice
+            AppEvent::PlaybackEvent(PlaybackEvent::PlaylistChanged) => {
+                self.widget.set_radio(self.model.radio().as_deref());
+                self.model.load_remaining();
+            }
+            _ => {}
         }
         self.broadcast_event(event);
     }
@@ -1,18 +1,21 @@
+use gdk::prelude::*;
+use gettextrs::gettext;
 use gio::prelude::*;
 use gio::SimpleActionGroup;
+use librespot::core::spotify_id::SpotifyId;
 use std::ops::Deref;
 use std::rc::Rc;
 
 use crate::app::components::{
-    labels, DeviceSelectorModel, HeaderBarModel, PlaylistModel, SimpleHeaderBarModel,
-    SimpleHeaderBarModelWrapper,
+    check_saved_tracks, labels, share, DeviceSelectorModel, HeaderBarModel, PlaylistModel,
+    SimpleHeaderBarModel, SimpleHeaderBarModelWrapper,
 };
-use crate::app::models::{SongDescription, SongListModel};
-use crate::app::state::Device;
+use crate::app::models::{Batch, SongDescription, SongListModel};
+use crate::app::state::{split_uri, Device};
 use crate::app::state::{
-    PlaybackAction, PlaybackState, SelectionAction, SelectionContext, SelectionState,
+    PlaybackAction, PlaybackState, ScreenName, SelectionAction, SelectionContext, SelectionState,
 };
-use crate::app::{ActionDispatcher, AppAction, AppEvent, AppModel};
+use crate::app::{ActionDispatcher, AppAction, AppModel, BatchQuery, SongsSource};
 
 pub struct NowPlayingModel {
     app_model: Rc<AppModel>,
@@ -31,6 +34,10 @@ impl NowPlayingModel {
         self.app_model.map_state(|s| &s.playback)
     }
 
+    pub fn radio(&self) -> Option<String> {
+        self.queue().radio().map(str::to_string)
+    }
+
     pub fn load_more(&self) -> Option<()> {
         let queue = self.queue();
         let loader = self.app_model.get_batch_loader();
@@ -48,6 +55,34 @@ impl NowPlayingModel {
         Some(())
     }
 
+    // The whole album or playlist ends up in the queue, one page after the other
+    // (each page that comes in triggers this again)
+    pub fn load_remaining(&self) -> Option<()> {
+        let queue = self.queue();
+        let source = queue.current_source()?.clone();
+        let batch = queue.songs().first_missing_batch()?;
+        // Playlists can be fetched 100 tracks at a time, the rest only 50
+        let batch = match source {
+            SongsSource::Playlist(_) => Batch {
+                offset: batch.offset - batch.offset % 100,
+                batch_size: 100,
+                total: batch.total,
+            },
+            _ => batch,
+        };
+        let loader = self.app_model.get_batch_loader();
+
+        self.dispatcher.dispatch_async(Box::pin(async move {
+            loader
+                .query(BatchQuery { source, batch }, |source, song_batch| {
+                    PlaybackAction::AppendPagedSongs(source, song_batch).into()
+                })
+                .await
+        }));
+
+        Some(())
+    }
+
     pub fn to_headerbar_model(self: &Rc<Self>) -> Rc<impl HeaderBarModel> {
         Rc::new(SimpleHeaderBarModelWrapper::new(
             self.clone(),
@@ -60,6 +95,62 @@ impl NowPlayingModel {
         DeviceSelectorModel::new(self.app_model.clone(), self.dispatcher.box_clone())
     }
 
+    // One spotify:track URI per line, so that it can be pasted somewhere else
+    pub fn copy_queue(&self) {
+        let uris = self
+            .queue()
+            .songs()
+            .collect()
+            .iter()
+            .map(|song| format!("spotify:track:{}", song.id))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let clipboard = gdk::Display::default().unwrap().clipboard();
+        clipboard.set_text(&uris);
+        self.dispatcher
+            .dispatch(AppAction::ShowNotification(gettext(
+                // translators: Notification shown after copying the play queue to the clipboard
+                "Queue copied to clipboard",
+            )));
+    }
+
+    pub fn paste_tracks(&self, ids: Vec<String>, unparsed: usize, replace: bool) {
+        let api = self.app_model.get_spotify();
+        self.dispatcher
+            .call_spotify_and_dispatch_many(move || async move {
+                let requested = ids.len();
+                let songs = api.get_tracks(ids).await?;
+                let unresolved = unparsed + requested.saturating_sub(songs.len());
+
+                let mut actions = vec![];
+                if !songs.is_empty() {
+                    actions.push(if replace {
+                        PlaybackAction::LoadSongs(songs).into()
+                    } else {
+                        PlaybackAction::Queue(songs).into()
+                    });
+                }
+                if unresolved > 0 {
+                    actions.push(AppAction::ShowNotification(
+                        labels::n_lines_unresolved_label(unresolved),
+                    ));
+                }
+                Ok(actions)
+            });
+    }
+
+    pub fn clear_upcoming(&self) {
+        self.dispatcher
+            .dispatch(PlaybackAction::ClearUpcoming.into());
+    }
+
+    pub fn queue_menu(&self) -> gio::MenuModel {
+        let menu = gio::Menu::new();
+        menu.append(Some(&*labels::COPY_QUEUE), Some("queue.copy"));
+        menu.append(Some(&*labels::PASTE_QUEUE), Some("queue.paste"));
+        menu.upcast()
+    }
+
     fn current_selection_context(&self) -> SelectionContext {
         let state = self.app_model.get_state();
         match state.playback.current_device() {
@@ -78,6 +169,10 @@ impl PlaylistModel for NowPlayingModel {
         !self.app_model.get_state().playback.is_playing()
     }
 
+    fn is_offline(&self) -> bool {
+        self.app_model.get_state().logged_user.offline
+    }
+
     fn current_song_id(&self) -> Option<String> {
         self.queue().current_song_id()
     }
@@ -91,6 +186,14 @@ impl PlaylistModel for NowPlayingModel {
         false // too buggy for now
     }
 
+    fn is_song_saved(&self, id: &str) -> Option<bool> {
+        Some(self.app_model.get_state().browser.is_track_saved(id))
+    }
+
+    fn check_saved_songs(&self, ids: Vec<String>) {
+        check_saved_tracks(&self.app_model, self.dispatcher.as_ref(), ids);
+    }
+
     fn actions_for(&self, id: &str) -> Option<gio::ActionGroup> {
         let queue = self.queue();
         let song = queue.songs().get(id)?;
@@ -101,7 +204,14 @@ impl PlaylistModel for NowPlayingModel {
             group.add_action(&view_artist);
         }
         group.add_action(&song.make_album_action(self.dispatcher.box_clone(), None));
-        group.add_action(&song.make_link_action(None));
+        group.add_action(&song.make_link_action(self.dispatcher.box_clone(), None));
+        group.add_action(&song.make_open_in_browser_action(None));
+        group.add_action(&song.make_toggle_saved_action(
+            self.app_model.clone(),
+            self.dispatcher.box_clone(),
+            None,
+        ));
+        group.add_action(&song.make_move_to_next_action(self.dispatcher.box_clone(), None));
         group.add_action(&song.make_dequeue_action(self.dispatcher.box_clone(), None));
 
         Some(group.upcast())
@@ -121,12 +231,31 @@ impl PlaylistModel for NowPlayingModel {
             );
         }
 
-        menu.append(Some(&*labels::COPY_LINK), Some("song.copy_link"));
+        share::append_share_items(&menu, "song");
+        menu.append(Some(&*labels::PLAY_NEXT), Some("song.move_to_next"));
         menu.append(Some(&*labels::REMOVE_FROM_QUEUE), Some("song.dequeue"));
 
         Some(menu.upcast())
     }
 
+    fn is_reorderable(&self) -> bool {
+        matches!(self.current_selection_context(), SelectionContext::Queue)
+    }
+
+    fn move_song(&self, id: &str, onto: &str) {
+        let positions = {
+            let queue = self.queue();
+            queue
+                .songs()
+                .find_index(id)
+                .zip(queue.songs().find_index(onto))
+        };
+        if let Some((from, to)) = positions {
+            self.dispatcher
+                .dispatch(PlaybackAction::MoveQueueItem { from, to }.into());
+        }
+    }
+
     fn select_song(&self, id: &str) {
         let queue = self.queue();
         if let Some(song) = queue.songs().get(id) {
@@ -154,14 +283,10 @@ impl PlaylistModel for NowPlayingModel {
 }
 
 impl SimpleHeaderBarModel for NowPlayingModel {
-    fn title(&self) -> Option<String> {
+    fn screen_name(&self) -> Option<ScreenName> {
         None
     }
 
-    fn title_updated(&self, _: &AppEvent) -> bool {
-        false
-    }
-
     fn selection_context(&self) -> Option<SelectionContext> {
         Some(self.current_selection_context())
     }
@@ -172,3 +297,49 @@ impl SimpleHeaderBarModel for NowPlayingModel {
             .dispatch(SelectionAction::Select(songs).into());
     }
 }
+
+// Accepts spotify:track URIs and open.spotify.com links, one per line
+fn parse_track_id(line: &str) -> Option<String> {
+    let (kind, id) = split_uri(line)?;
+    (kind == "track" && SpotifyId::from_base62(id).is_ok()).then(|| id.to_string())
+}
+
+// Returns the track ids found in some pasted text, and how many (non empty) lines we couldn't make sense of
+pub fn parse_track_ids(text: &str) -> (Vec<String>, usize) {
+    let mut ids = vec![];
+    let mut unparsed = 0;
+    for line in text.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        match parse_track_id(line) {
+            Some(id) => ids.push(id),
+            None => unparsed += 1,
+        }
+    }
+    (ids, unparsed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_track_ids() {
+        let text = "spotify:track:4uLU6hMCjMI75M1A2tKUQC\n\
+            \n\
+            https://open.spotify.com/track/6rqhFgbbKwnb9MLmUQDhG6?si=abcdef\n\
+            https://open.spotify.com/intl-fr/track/1301WleyT98MSxVHPZCA6M\n\
+            https://open.spotify.com/album/4uLU6hMCjMI75M1A2tKUQC\n\
+            spotify:track:nope\n\
+            hello there";
+
+        let (ids, unparsed) = parse_track_ids(text);
+        assert_eq!(
+            ids,
+            vec![
+                "4uLU6hMCjMI75M1A2tKUQC",
+                "6rqhFgbbKwnb9MLmUQDhG6",
+                "1301WleyT98MSxVHPZCA6M"
+            ]
+        );
+        assert_eq!(unparsed, 3);
+    }
+}
@@ -1,13 +1,24 @@
+use gettextrs::gettext;
+use gio::prelude::*;
+use std::convert::TryFrom;
 use std::ops::Deref;
 use std::rc::Rc;
+use std::time::Instant;
 
-use crate::app::components::EventListener;
+use crate::app::components::utils::Debouncer;
+use crate::app::components::{labels, share, DeviceSelector, DeviceSelectorModel, EventListener};
 use crate::app::models::*;
-use crate::app::state::{PlaybackAction, PlaybackEvent, ScreenName, SelectionEvent};
+use crate::app::state::{
+    LoginEvent, PlaybackAction, PlaybackEvent, ScreenName, SelectionEvent, SleepTimer,
+};
 use crate::app::{
     ActionDispatcher, AppAction, AppEvent, AppModel, AppState, BrowserAction, Worker,
 };
 
+use super::mini_player::MiniPlayerWidget;
+
+// In case the player never tells us it's done loading
+const BUFFERING_TIMEOUT_MS: u32 = 15000;
 use super::playback_widget::PlaybackWidget;
 
 pub struct PlaybackModel {
@@ -27,16 +38,31 @@ impl PlaybackModel {
         self.app_model.get_state()
     }
 
+    fn device_selector_model(&self) -> DeviceSelectorModel {
+        DeviceSelectorModel::new(self.app_model.clone(), self.dispatcher.box_clone())
+    }
+
     fn go_home(&self) {
         self.dispatcher.dispatch(AppAction::ViewNowPlaying);
         self.dispatcher
             .dispatch(BrowserAction::NavigationPopTo(ScreenName::Home).into());
     }
 
+    fn view_artwork(&self) {
+        if let Some(song) = self.current_song() {
+            self.dispatcher
+                .dispatch(AppAction::ViewArtwork(song.album.id));
+        }
+    }
+
     fn is_playing(&self) -> bool {
         self.state().playback.is_playing()
     }
 
+    fn is_offline(&self) -> bool {
+        self.state().logged_user.offline
+    }
+
     fn is_shuffled(&self) -> bool {
         self.state().playback.is_shuffled()
     }
@@ -45,6 +71,91 @@ impl PlaybackModel {
         self.app_model.get_state().playback.current_song()
     }
 
+    fn track_menu(&self, song: &SongDescription) -> (gio::MenuModel, gio::ActionGroup) {
+        let group = gio::SimpleActionGroup::new();
+        group.add_action(&song.make_link_action(self.dispatcher.box_clone(), None));
+        group.add_action(&song.make_open_in_browser_action(None));
+        let menu = gio::Menu::new();
+        share::append_share_items(&menu, "song");
+        // translators: Title of the section of the playing track menu that lets you pause playback later on
+        menu.append_section(Some(&gettext("Sleep timer")), &self.sleep_timer_menu());
+        (menu.upcast(), group.upcast())
+    }
+
+    fn sleep_timer(&self) -> Option<SleepTimer> {
+        self.state().playback.sleep_timer()
+    }
+
+    fn sleep_timer_menu(&self) -> gio::Menu {
+        let menu = gio::Menu::new();
+        match self.sleep_timer() {
+            Some(SleepTimer::Deadline(deadline)) => {
+                let secs_left = deadline.saturating_duration_since(Instant::now()).as_secs();
+                menu.append(
+                    Some(&labels::sleep_timer_minutes_left_label(
+                        secs_left.div_ceil(60),
+                    )),
+                    Some("sleep_timer.cancel"),
+                );
+            }
+            Some(SleepTimer::EndOfTrack) => {
+                menu.append(
+                    // translators: This is the entry to cancel the sleep timer when it's set to pause at the end of the current track
+                    Some(&gettext("Cancel (at the end of the track)")),
+                    Some("sleep_timer.cancel"),
+                );
+            }
+            None => {
+                // translators: These are the durations to choose from for the sleep timer
+                menu.append(Some(&gettext("15 minutes")), Some("sleep_timer.start(15)"));
+                menu.append(Some(&gettext("30 minutes")), Some("sleep_timer.start(30)"));
+                menu.append(Some(&gettext("1 hour")), Some("sleep_timer.start(60)"));
+                // translators: A sleep timer option, to pause once the current track is over
+                menu.append(Some(&gettext("End of track")), Some("sleep_timer.start(0)"));
+            }
+        }
+        menu
+    }
+
+    // Minutes from now, with 0 meaning the end of the current track
+    fn sleep_timer_actions(&self) -> gio::ActionGroup {
+        let group = gio::SimpleActionGroup::new();
+
+        let start = gio::SimpleAction::new("start", Some(glib::VariantTy::INT32));
+        let dispatcher = self.dispatcher.box_clone();
+        start.connect_activate(move |_, minutes| {
+            let timer = match minutes.and_then(|m| m.get::<i32>()) {
+                Some(0) => SleepTimer::EndOfTrack,
+                Some(minutes) => SleepTimer::in_minutes(minutes.unsigned_abs().into()),
+                None => return,
+            };
+            dispatcher.dispatch(PlaybackAction::SetSleepTimer(Some(timer)).into());
+        });
+        group.add_action(&start);
+
+        let cancel = gio::SimpleAction::new("cancel", None);
+        let dispatcher = self.dispatcher.box_clone();
+        cancel.connect_activate(move |_, _| {
+            dispatcher.dispatch(PlaybackAction::SetSleepTimer(None).into());
+        });
+        group.add_action(&cancel);
+
+        group.upcast()
+    }
+
+    fn sleep_timer_expired(&self) {
+        self.dispatcher
+            .dispatch(PlaybackAction::SleepTimerExpired.into());
+    }
+
+    fn notify_sleep_timer_finished(&self) {
+        // translators: This shows up when the sleep timer goes off
+        self.dispatcher
+            .dispatch(AppAction::ShowNotification(gettext(
+                "Playback paused by the sleep timer",
+            )));
+    }
+
     fn play_next_song(&self) {
         self.dispatcher.dispatch(PlaybackAction::Next.into());
     }
@@ -82,12 +193,16 @@ pub struct PlaybackControl {
     model: Rc<PlaybackModel>,
     widget: PlaybackWidget,
     worker: Worker,
+    buffering_timeout: Debouncer,
+    sleep_timeout: Debouncer,
+    device_selector: DeviceSelector,
 }
 
 impl PlaybackControl {
     pub fn new(model: PlaybackModel, widget: PlaybackWidget, worker: Worker) -> Self {
         let model = Rc::new(model);
 
+        widget.set_offline(model.is_offline());
         widget.connect_play_pause(clone!(
             #[weak]
             model,
@@ -123,16 +238,73 @@ impl PlaybackControl {
             model,
             move || model.go_home()
         ));
+        widget.connect_artwork_clicked(clone!(
+            #[weak]
+            model,
+            move || model.view_artwork()
+        ));
         widget.connect_volume_changed(clone!(
             #[weak]
             model,
             move |value| model.set_volume(value)
         ));
 
+        // Rebuilt on every opening, so the time left for the sleep timer is up to date
+        widget.set_sleep_timer_actions(&model.sleep_timer_actions());
+        widget.connect_track_menu_opened(clone!(
+            #[weak]
+            model,
+            #[weak]
+            widget,
+            move || {
+                if let Some(song) = model.current_song() {
+                    let (menu, actions) = model.track_menu(&song);
+                    widget.set_track_menu(&menu, &actions);
+                }
+            }
+        ));
+
+        let device_selector = DeviceSelector::new(
+            widget.device_selector_widget().clone(),
+            model.device_selector_model(),
+        );
+
         Self {
             model,
             widget,
             worker,
+            buffering_timeout: Debouncer::new(),
+            sleep_timeout: Debouncer::new(),
+            device_selector,
+        }
+    }
+
+    fn update_buffering(&self, buffering: bool) {
+        self.widget.set_buffering(buffering);
+        if buffering {
+            let widget = self.widget.clone();
+            self.buffering_timeout
+                .debounce(BUFFERING_TIMEOUT_MS, move || widget.set_buffering(false));
+        } else {
+            self.buffering_timeout.cancel();
+        }
+    }
+
+    // A glib timeout on the main loop, the state double checks the deadline when it fires
+    fn update_sleep_timer(&self) {
+        if let Some(SleepTimer::Deadline(deadline)) = self.model.sleep_timer() {
+            let ms_left = deadline
+                .saturating_duration_since(Instant::now())
+                .as_millis();
+            let model = Rc::downgrade(&self.model);
+            self.sleep_timeout
+                .debounce(u32::try_from(ms_left).unwrap_or(u32::MAX), move || {
+                    if let Some(model) = model.upgrade() {
+                        model.sleep_timer_expired();
+                    }
+                });
+        } else {
+            self.sleep_timeout.cancel();
         }
     }
 
@@ -154,6 +326,8 @@ impl PlaybackControl {
             self.widget
                 .set_title_and_artist(&song.title, &song.artists_name());
             self.widget.set_song_duration(Some(song.duration as f64));
+            let (menu, actions) = self.model.track_menu(&song);
+            self.widget.set_track_menu(&menu, &actions);
             if let Some(url) = song.art {
                 self.widget.set_artwork_from_url(url, &self.worker);
             }
@@ -169,11 +343,18 @@ impl PlaybackControl {
 
 impl EventListener for PlaybackControl {
     fn on_event(&mut self, event: &AppEvent) {
+        self.device_selector.on_event(event);
         match event {
-            AppEvent::PlaybackEvent(PlaybackEvent::PlaybackPaused)
-            | AppEvent::PlaybackEvent(PlaybackEvent::PlaybackResumed) => {
+            AppEvent::PlaybackEvent(PlaybackEvent::PlaybackPaused) => {
+                self.update_buffering(false);
+                self.update_playing();
+            }
+            AppEvent::PlaybackEvent(PlaybackEvent::PlaybackResumed) => {
                 self.update_playing();
             }
+            AppEvent::PlaybackEvent(PlaybackEvent::Buffering(buffering)) => {
+                self.update_buffering(*buffering);
+            }
             AppEvent::PlaybackEvent(PlaybackEvent::RepeatModeChanged(mode)) => {
                 self.update_repeat(mode);
             }
@@ -181,12 +362,20 @@ impl EventListener for PlaybackControl {
                 self.update_shuffled();
             }
             AppEvent::PlaybackEvent(PlaybackEvent::TrackChanged(_)) => {
+                self.update_buffering(false);
                 self.update_current_info();
             }
             AppEvent::PlaybackEvent(PlaybackEvent::PlaybackStopped) => {
+                self.update_buffering(false);
                 self.update_playing();
                 self.update_current_info();
             }
+            AppEvent::PlaybackEvent(PlaybackEvent::SleepTimerChanged) => {
+                self.update_sleep_timer();
+            }
+            AppEvent::PlaybackEvent(PlaybackEvent::SleepTimerFinished) => {
+                self.model.notify_sleep_timer_finished();
+            }
             AppEvent::PlaybackEvent(PlaybackEvent::SeekSynced(pos))
             | AppEvent::PlaybackEvent(PlaybackEvent::TrackSeeked(pos)) => {
                 self.sync_seek(*pos);
@@ -197,6 +386,60 @@ impl EventListener for PlaybackControl {
             AppEvent::PlaybackEvent(PlaybackEvent::VolumeSet(value)) => {
                 self.widget.set_volume(*value)
             }
+            AppEvent::LoginEvent(LoginEvent::OfflineChanged(offline)) => {
+                self.widget.set_offline(*offline)
+            }
+            _ => {}
+        }
+    }
+}
+
+// The controls shown in place of the whole window in mini player mode
+pub struct MiniPlayer {
+    model: PlaybackModel,
+    widget: MiniPlayerWidget,
+    worker: Worker,
+}
+
+impl MiniPlayer {
+    pub fn new(model: PlaybackModel, widget: MiniPlayerWidget, worker: Worker) -> Self {
+        Self {
+            model,
+            widget,
+            worker,
+        }
+    }
+
+    fn update_playing(&self) {
+        self.widget.set_playing(self.model.is_playing());
+    }
+
+    fn update_current_info(&self) {
+        if let Some(song) = self.model.current_song() {
+            self.widget
+                .set_title_and_artist(&song.title, &song.artists_name());
+            self.widget
+                .set_artwork_from_url(&song.id, song.art, &self.worker);
+        } else {
+            self.widget.reset_info();
+        }
+    }
+}
+
+impl EventListener for MiniPlayer {
+    fn on_event(&mut self, event: &AppEvent) {
+        match event {
+            AppEvent::PlaybackEvent(PlaybackEvent::PlaybackPaused)
+            | AppEvent::PlaybackEvent(PlaybackEvent::PlaybackResumed) => {
+                self.update_playing();
+            }
+            AppEvent::PlaybackEvent(PlaybackEvent::TrackChanged(_)) => {
+                self.update_current_info();
+            }
+            AppEvent::PlaybackEvent(PlaybackEvent::PlaybackStopped) => {
+                self.update_playing();
+                self.update_current_info();
+            }
             _ => {}
         }
     }
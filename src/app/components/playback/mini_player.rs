@@ -0,0 +1,110 @@
+use gettextrs::gettext;
+use gtk::prelude::*;
+use gtk::subclass::prelude::*;
+use gtk::{glib, CompositeTemplate};
+use std::cell::RefCell;
+
+use crate::app::loader::ImageLoader;
+use crate::app::Worker;
+
+mod imp {
+
+    use super::*;
+
+    #[derive(Debug, Default, CompositeTemplate)]
+    #[template(resource = "/dev/diegovsky/Riff/components/mini_player.ui")]
+    pub struct MiniPlayerWidget {
+        #[template_child]
+        pub artwork: TemplateChild<gtk::Picture>,
+
+        #[template_child]
+        pub song_title: TemplateChild<gtk::Label>,
+
+        #[template_child]
+        pub song_artist: TemplateChild<gtk::Label>,
+
+        #[template_child]
+        pub play_pause: TemplateChild<gtk::Button>,
+
+        // The track the artwork is for, loads finishing late for another one are dropped
+        pub artwork_track_id: RefCell<Option<String>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for MiniPlayerWidget {
+        const NAME: &'static str = "MiniPlayerWidget";
+        type Type = super::MiniPlayerWidget;
+        type ParentType = gtk::Box;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for MiniPlayerWidget {}
+    impl WidgetImpl for MiniPlayerWidget {}
+    impl BoxImpl for MiniPlayerWidget {}
+}
+
+glib::wrapper! {
+    pub struct MiniPlayerWidget(ObjectSubclass<imp::MiniPlayerWidget>) @extends gtk::Widget, gtk::Box;
+}
+
+// The compact layout of the window: the buttons trigger the same app actions as the keyboard shortcuts,
+// so there's nothing to connect here.
+impl MiniPlayerWidget {
+    pub fn set_playing(&self, is_playing: bool) {
+        let (icon, tooltip) = if is_playing {
+            ("media-playback-pause-symbolic", gettext("Pause"))
+        } else {
+            ("media-playback-start-symbolic", gettext("Play"))
+        };
+        let play_pause = &self.imp().play_pause;
+        play_pause.set_icon_name(icon);
+        play_pause.set_tooltip_text(Some(&tooltip));
+    }
+
+    pub fn set_title_and_artist(&self, title: &str, artist: &str) {
+        let widget = self.imp();
+        widget.song_title.set_text(title);
+        widget.song_artist.set_text(artist);
+    }
+
+    pub fn reset_info(&self) {
+        let widget = self.imp();
+        widget
+            .song_title
+            // translators: Short text displayed instead of a song title when nothing plays
+            .set_text(&gettext("No song playing"));
+        widget.song_artist.set_text("");
+        widget.artwork_track_id.take();
+        widget.artwork.set_paintable(None::<&gdk::Paintable>);
+    }
+
+    pub fn set_artwork_from_url(&self, track_id: &str, url: Option<String>, worker: &Worker) {
+        let widget = self.imp();
+        widget.artwork_track_id.replace(Some(track_id.to_string()));
+        widget.artwork.set_paintable(None::<&gdk::Paintable>);
+        let Some(url) = url else {
+            return;
+        };
+        let weak_self = self.downgrade();
+        let track_id = track_id.to_string();
+        worker.send_local_task(async move {
+            let loader = ImageLoader::new();
+            let result = loader.load_remote(&url, "jpg", 96, 96).await;
+            if let (Some(_self), Some(result)) = (weak_self.upgrade(), result) {
+                let widget = _self.imp();
+                if widget.artwork_track_id.borrow().as_deref() != Some(track_id.as_str()) {
+                    return;
+                }
+                let texture = gdk::Texture::for_pixbuf(&result);
+                widget.artwork.set_paintable(Some(&texture));
+            }
+        });
+    }
+}
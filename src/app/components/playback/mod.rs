@@ -1,4 +1,5 @@
 mod component;
+mod mini_player;
 mod playback_controls;
 mod playback_info;
 mod playback_info_mobile;
@@ -9,4 +10,5 @@ use glib::prelude::*;
 
 pub fn expose_widgets() {
     playback_widget::PlaybackWidget::static_type();
+    mini_player::MiniPlayerWidget::static_type();
 }
@@ -3,6 +3,7 @@ use gtk::prelude::*;
 use gtk::subclass::prelude::*;
 use gtk::{glib, CompositeTemplate};
 
+use crate::app::components::utils::show_offline_tooltip;
 use crate::app::models::RepeatMode;
 
 mod imp {
@@ -17,6 +18,9 @@ mod imp {
         #[template_child]
         pub play_pause: TemplateChild<gtk::Button>,
 
+        #[template_child]
+        pub buffering: TemplateChild<gtk::Spinner>,
+
         #[template_child]
         pub next: TemplateChild<gtk::Button>,
 
@@ -47,7 +51,14 @@ mod imp {
         }
     }
 
-    impl ObjectImpl for PlaybackControlsWidget {}
+    impl ObjectImpl for PlaybackControlsWidget {
+        fn constructed(&self) {
+            self.parent_constructed();
+            show_offline_tooltip(&*self.play_pause);
+            show_offline_tooltip(&*self.prev);
+            show_offline_tooltip(&*self.next);
+        }
+    }
     impl WidgetImpl for PlaybackControlsWidget {}
     impl BoxImpl for PlaybackControlsWidget {}
 }
@@ -76,6 +87,25 @@ impl PlaybackControlsWidget {
         playback_control.play_pause.set_tooltip_text(tooltip_text);
     }
 
+    // Shuffle and repeat can still be changed, they don't load anything
+    pub fn set_offline(&self, offline: bool) {
+        let playback_control = self.imp();
+        playback_control.play_pause.set_sensitive(!offline);
+        playback_control.prev.set_sensitive(!offline);
+        playback_control.next.set_sensitive(!offline);
+    }
+
+    pub fn set_buffering(&self, buffering: bool) {
+        let playback_control = self.imp();
+        playback_control.buffering.set_visible(buffering);
+        playback_control.buffering.set_spinning(buffering);
+        if buffering {
+            playback_control.play_pause.add_css_class("buffering");
+        } else {
+            playback_control.play_pause.remove_css_class("buffering");
+        }
+    }
+
     pub fn set_shuffled(&self, shuffled: bool) {
         self.imp().shuffle.set_active(shuffled);
     }
@@ -87,7 +117,19 @@ impl PlaybackControlsWidget {
             RepeatMode::None => "media-playlist-consecutive-symbolic",
         };
 
-        self.imp().repeat.set_icon_name(repeat_mode_icon);
+        // The icons alone are hard to tell apart
+        let translated_tooltip = match mode {
+            // translators: Tooltip of the repeat button, when the current song plays again and again
+            RepeatMode::Song => gettext("Repeat song"),
+            // translators: Tooltip of the repeat button, when the whole list starts over once finished
+            RepeatMode::Playlist => gettext("Repeat all"),
+            // translators: Tooltip of the repeat button, when playback stops at the end of the list
+            RepeatMode::None => gettext("Repeat"),
+        };
+
+        let repeat = &self.imp().repeat;
+        repeat.set_icon_name(repeat_mode_icon);
+        repeat.set_tooltip_text(Some(&translated_tooltip));
     }
 
     pub fn connect_play_pause<F>(&self, f: F)
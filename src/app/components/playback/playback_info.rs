@@ -87,4 +87,16 @@ impl PlaybackInfoWidget {
         self.imp().playing_image.set_paintable(Some(&texture));
     }
 
+    pub fn connect_artwork_clicked<F>(&self, f: F)
+    where
+        F: Fn() + 'static,
+    {
+        let click = gtk::GestureClick::new();
+        // Claiming the click keeps the button itself from taking us to the now playing view
+        click.connect_pressed(|gesture, _, _, _| {
+            gesture.set_state(gtk::EventSequenceState::Claimed);
+        });
+        click.connect_released(move |_, _, _, _| f());
+        self.imp().playing_image.add_controller(click);
+    }
 }
@@ -2,8 +2,8 @@ use gtk::prelude::*;
 use gtk::subclass::prelude::*;
 use gtk::{glib, CompositeTemplate};
 
-use crate::app::components::display_add_css_provider;
 use crate::app::components::utils::{format_duration, Clock, Debouncer};
+use crate::app::components::{display_add_css_provider, DeviceSelectorWidget};
 use crate::app::loader::ImageLoader;
 use crate::app::models::RepeatMode;
 use crate::app::Worker;
@@ -46,6 +46,12 @@ mod imp {
         #[template_child]
         pub volume_slider: TemplateChild<gtk::Scale>,
 
+        #[template_child]
+        pub track_menu: TemplateChild<gtk::MenuButton>,
+
+        #[template_child]
+        pub device_selector: TemplateChild<DeviceSelectorWidget>,
+
         pub clock: Clock,
     }
 
@@ -114,8 +120,34 @@ impl PlaybackWidget {
         widget.mobile_now_playing.set_title_and_artist(title, artist);
     }
 
+    pub fn device_selector_widget(&self) -> &DeviceSelectorWidget {
+        self.imp().device_selector.as_ref()
+    }
+
+    pub fn set_track_menu(&self, menu: &gio::MenuModel, actions: &gio::ActionGroup) {
+        let widget = self.imp();
+        widget.track_menu.set_menu_model(Some(menu));
+        widget.track_menu.insert_action_group("song", Some(actions));
+        widget.track_menu.set_visible(true);
+    }
+
+    pub fn set_sleep_timer_actions(&self, actions: &gio::ActionGroup) {
+        self.imp()
+            .track_menu
+            .insert_action_group("sleep_timer", Some(actions));
+    }
+
+    // Called right before the menu pops up, so it can be refreshed
+    pub fn connect_track_menu_opened<F>(&self, f: F)
+    where
+        F: Fn() + 'static,
+    {
+        self.imp().track_menu.set_create_popup_func(move |_| f());
+    }
+
     pub fn reset_info(&self) {
         let widget = self.imp();
+        widget.track_menu.set_visible(false);
         widget.now_playing.set_visible(false);
         widget.now_playing.reset_info();
         widget.mobile_now_playing.set_visible(false);
@@ -180,6 +212,13 @@ impl PlaybackWidget {
         widget.now_playing.connect_clicked(move |_| f_clone());
     }
 
+    pub fn connect_artwork_clicked<F>(&self, f: F)
+    where
+        F: Fn() + 'static,
+    {
+        self.imp().now_playing.connect_artwork_clicked(f);
+    }
+
     pub fn connect_seek<Seek>(&self, seek: Seek)
     where
         Seek: Fn(u32) + Clone + 'static,
@@ -223,12 +262,24 @@ impl PlaybackWidget {
         widget.mobile_controls.set_repeat_mode(mode);
     }
 
+    pub fn set_buffering(&self, buffering: bool) {
+        let widget = self.imp();
+        widget.controls.set_buffering(buffering);
+        widget.mobile_controls.set_buffering(buffering);
+    }
+
     pub fn set_shuffled(&self, shuffled: bool) {
         let widget = self.imp();
         widget.controls.set_shuffled(shuffled);
         widget.mobile_controls.set_shuffled(shuffled);
     }
 
+    pub fn set_offline(&self, offline: bool) {
+        let widget = self.imp();
+        widget.controls.set_offline(offline);
+        widget.mobile_controls.set_offline(offline);
+    }
+
     pub fn set_seekbar_visible(&self, visible: bool) {
         let widget = self.imp();
         widget.seek_bar.set_visible(visible);
@@ -0,0 +1,130 @@
+use std::rc::Rc;
+
+use crate::app::components::EventListener;
+use crate::app::models::SongBatch;
+use crate::app::state::{LoginEvent, PlaybackAction, PlaybackRequest};
+use crate::app::{ActionDispatcher, AppAction, AppEvent, AppModel, SongsSource};
+use crate::settings::SavedPlayback;
+
+// Only the first page, the rest gets loaded as the queue is played
+const BATCH_SIZE: usize = 50;
+
+// Fetches whatever was asked to be played from outside the app (e.g. MPRIS OpenUri) and plays it
+pub struct PlaybackRequestHandler {
+    app_model: Rc<AppModel>,
+    dispatcher: Box<dyn ActionDispatcher>,
+    restored: bool,
+}
+
+fn play_batch(source: SongsSource, batch: SongBatch) -> Vec<AppAction> {
+    let Some(first) = batch.songs.first().map(|song| song.id.clone()) else {
+        return vec![];
+    };
+    vec![
+        PlaybackAction::LoadPagedSongs(source, batch).into(),
+        PlaybackAction::Load(first).into(),
+    ]
+}
+
+impl PlaybackRequestHandler {
+    pub fn new(app_model: Rc<AppModel>, dispatcher: Box<dyn ActionDispatcher>) -> Self {
+        Self {
+            app_model,
+            dispatcher,
+            restored: false,
+        }
+    }
+
+    // Picks up where we were when the app was last closed, but paused.
+    // If anything went missing since then, we just start from scratch without a fuss.
+    fn restore(&self) {
+        let Some(saved) = SavedPlayback::new_from_gsettings() else {
+            return;
+        };
+        let api = self.app_model.get_spotify();
+        self.dispatcher.dispatch_many_async(Box::pin(async move {
+            let SavedPlayback {
+                context,
+                track,
+                index,
+                position,
+            } = saved;
+
+            let offset = index - index % BATCH_SIZE;
+            let batch = match context.as_deref().and_then(SongsSource::from_spotify_uri) {
+                Some(SongsSource::Playlist(id)) => api
+                    .get_playlist_tracks(&id, offset, BATCH_SIZE)
+                    .await
+                    .ok()
+                    .map(|batch| (SongsSource::Playlist(id), batch)),
+                Some(SongsSource::Album(id)) => api
+                    .get_album_tracks(&id, offset, BATCH_SIZE)
+                    .await
+                    .ok()
+                    .map(|batch| (SongsSource::Album(id), batch)),
+                _ => None,
+            };
+
+            let load = match batch {
+                Some((source, batch)) if batch.songs.iter().any(|song| song.id == track) => {
+                    PlaybackAction::LoadPagedSongs(source, batch)
+                }
+                // The track on its own, its album or playlist may have changed since then
+                _ => match api.get_tracks(vec![track.clone()]).await {
+                    Ok(songs) if !songs.is_empty() => PlaybackAction::LoadSongs(songs),
+                    _ => return vec![],
+                },
+            };
+            vec![
+                load.into(),
+                PlaybackAction::Cue {
+                    id: track,
+                    position,
+                }
+                .into(),
+            ]
+        }));
+    }
+
+    fn play(&self, request: &PlaybackRequest) {
+        let api = self.app_model.get_spotify();
+        let request = request.clone();
+        self.dispatcher
+            .call_spotify_and_dispatch_many(move || async move {
+                match request {
+                    PlaybackRequest::Track(id) => {
+                        let songs = api.get_tracks(vec![id.clone()]).await?;
+                        if songs.is_empty() {
+                            return Ok(vec![]);
+                        }
+                        Ok(vec![
+                            PlaybackAction::LoadSongs(songs).into(),
+                            PlaybackAction::Load(id).into(),
+                        ])
+                    }
+                    PlaybackRequest::Album(id) => {
+                        let batch = api.get_album_tracks(&id, 0, BATCH_SIZE).await?;
+                        Ok(play_batch(SongsSource::Album(id), batch))
+                    }
+                    PlaybackRequest::Playlist(id) => {
+                        let batch = api.get_playlist_tracks(&id, 0, BATCH_SIZE).await?;
+                        Ok(play_batch(SongsSource::Playlist(id), batch))
+                    }
+                }
+            });
+    }
+}
+
+impl EventListener for PlaybackRequestHandler {
+    fn on_event(&mut self, event: &AppEvent) {
+        match event {
+            AppEvent::PlaybackRequested(request) => self.play(request),
+            // Once we can reach the API, for the first account we log into
+            AppEvent::LoginEvent(LoginEvent::LoginCompleted) if !self.restored => {
+                self.restored = true;
+                self.restore();
+            }
+            _ => {}
+        }
+    }
+}
@@ -1,7 +1,9 @@
+use std::cell::Cell;
 use std::ops::Deref;
 use std::rc::Rc;
 
 use futures::channel::mpsc::UnboundedSender;
+use gio::prelude::*;
 use librespot::core::spotify_id::SpotifyId;
 use librespot::core::SpotifyUri;
 
@@ -11,7 +13,8 @@ use crate::app::state::{
 };
 use crate::app::{ActionDispatcher, AppAction, AppEvent, AppModel, SongsSource};
 use crate::connect::ConnectCommand;
-use crate::player::Command;
+use crate::player::{Command, PlayerService};
+use crate::settings::{episode_position, is_album_gapless};
 
 enum CurrentlyPlaying {
     WithSource {
@@ -37,7 +40,7 @@ impl CurrentlyPlaying {
 pub struct PlayerNotifier {
     app_model: Rc<AppModel>,
     dispatcher: Box<dyn ActionDispatcher>,
-    command_sender: UnboundedSender<Command>,
+    command_sender: PlayerService,
     connect_command_sender: UnboundedSender<ConnectCommand>,
 }
 
@@ -45,9 +48,32 @@ impl PlayerNotifier {
     pub fn new(
         app_model: Rc<AppModel>,
         dispatcher: Box<dyn ActionDispatcher>,
-        command_sender: UnboundedSender<Command>,
+        command_sender: PlayerService,
         connect_command_sender: UnboundedSender<ConnectCommand>,
     ) -> Self {
+        // The player needs to know about metered connections when the bitrate is set to automatic
+        let monitor = gio::NetworkMonitor::default();
+        Self::send_metered_connection(&command_sender, &monitor);
+        // ...and to reconnect once the network is back, the connection it had is likely gone
+        let was_available = Cell::new(monitor.is_network_available());
+        monitor.connect_network_changed(clone!(
+            #[strong]
+            command_sender,
+            move |monitor, available| {
+                Self::send_metered_connection(&command_sender, monitor);
+                let came_back = available && !was_available.get();
+                was_available.set(available);
+                if came_back {
+                    let _ = command_sender.unbounded_send(Command::Reconnect);
+                }
+            }
+        ));
+        monitor.connect_network_metered_notify(clone!(
+            #[strong]
+            command_sender,
+            move |monitor| Self::send_metered_connection(&command_sender, monitor)
+        ));
+
         Self {
             app_model,
             dispatcher,
@@ -56,6 +82,11 @@ impl PlayerNotifier {
         }
     }
 
+    fn send_metered_connection(command_sender: &PlayerService, monitor: &gio::NetworkMonitor) {
+        let is_metered = monitor.is_network_metered();
+        let _ = command_sender.unbounded_send(Command::SetMeteredConnection(is_metered));
+    }
+
     fn is_playing(&self) -> bool {
         self.app_model.get_state().playback.is_playing()
     }
@@ -79,6 +110,33 @@ impl PlayerNotifier {
         Some(result)
     }
 
+    // Some albums (live recordings, mixes...) are forced to play gapless
+    fn gapless_override(&self) -> Option<bool> {
+        match self.app_model.get_state().playback.current_source() {
+            Some(SongsSource::Album(id)) if is_album_gapless(id) => Some(true),
+            _ => None,
+        }
+    }
+
+    // Podcast episodes have their own kind of URI, and pick up where we left them
+    fn playable_uri(&self, id: &str) -> Option<(SpotifyUri, u32)> {
+        let spotify_id = SpotifyId::from_base62(id).ok()?;
+        let is_episode = self
+            .app_model
+            .get_state()
+            .playback
+            .songs()
+            .get(id)
+            .map(|song| song.description().is_episode())
+            .unwrap_or(false);
+        if is_episode {
+            let position = episode_position(id).unwrap_or(0);
+            Some((SpotifyUri::Episode { id: spotify_id }, position))
+        } else {
+            Some((SpotifyUri::Track { id: spotify_id }, 0))
+        }
+    }
+
     fn device(&self) -> impl Deref<Target = Device> + '_ {
         self.app_model.map_state(|s| s.playback.current_device())
     }
@@ -91,6 +149,14 @@ impl PlayerNotifier {
             LoginEvent::LoginStarted(LoginStartedEvent::CompleteLogin) => {
                 Some(Command::CompleteLogin)
             }
+            LoginEvent::LoginStarted(LoginStartedEvent::CompleteLoginWithCode(code)) => {
+                Some(Command::CompleteLoginWithCode(code.clone()))
+            }
+            LoginEvent::LoginCancelled => Some(Command::CancelLogin),
+            LoginEvent::LoginStarted(LoginStartedEvent::SwitchAccount(username)) => {
+                Some(Command::SwitchAccount(username.clone()))
+            }
+            LoginEvent::AddAccountStarted => Some(Command::AddAccount),
             LoginEvent::FreshTokenRequested => Some(Command::RefreshToken),
             LoginEvent::LogoutCompleted => Some(Command::Logout),
             _ => None,
@@ -150,27 +216,35 @@ impl PlayerNotifier {
             PlaybackEvent::VolumeSet(volume) => Some(Command::PlayerSetVolume(*volume)),
             PlaybackEvent::TrackChanged(id) => {
                 info!("track changed: {}", id);
-                SpotifyId::from_base62(id)
-                    .ok()
-                    .map(|track| Command::PlayerLoad {
-                        track: SpotifyUri::Track { id: track },
-                        resume: true,
+                self.send_command_to_local_player(Command::SetGaplessOverride(
+                    self.gapless_override(),
+                ));
+                // Usually true, unless the sleep timer paused us on the way to that track
+                let resume = self.is_playing();
+                self.playable_uri(id)
+                    .map(|(track, position_ms)| Command::PlayerLoad {
+                        track,
+                        resume,
+                        position_ms,
                     })
             }
             PlaybackEvent::SourceChanged => {
+                self.send_command_to_local_player(Command::SetGaplessOverride(
+                    self.gapless_override(),
+                ));
                 let resume = self.is_playing();
                 self.currently_playing()
-                    .and_then(|c| SpotifyId::from_base62(c.song_id()).ok())
-                    .map(|track| Command::PlayerLoad {
-                        track: SpotifyUri::Track { id: track },
+                    .and_then(|c| self.playable_uri(c.song_id()))
+                    .map(|(track, position_ms)| Command::PlayerLoad {
+                        track,
                         resume,
+                        position_ms,
                     })
             }
             PlaybackEvent::TrackSeeked(position) => Some(Command::PlayerSeek(*position)),
-            PlaybackEvent::Preload(id) => SpotifyId::from_base62(id)
-                .ok()
-                .map(|track| SpotifyUri::Track { id: track })
-                .map(Command::PlayerPreload),
+            PlaybackEvent::Preload(id) => self
+                .playable_uri(id)
+                .map(|(track, _)| Command::PlayerPreload(track)),
             _ => None,
         };
 
@@ -188,7 +262,7 @@ impl PlayerNotifier {
         self.command_sender
             .unbounded_send(command)
             .unwrap_or_else(|_| {
-                dispatcher.dispatch(AppAction::LoginAction(LoginAction::SetLoginFailure));
+                dispatcher.dispatch(AppAction::LoginAction(LoginAction::SetLoginFailure(None)));
             });
     }
 
@@ -213,6 +287,10 @@ impl EventListener for PlayerNotifier {
         match (device, event) {
             (_, AppEvent::LoginEvent(event)) => self.notify_login(event),
             (_, AppEvent::PlaybackEvent(PlaybackEvent::SwitchedDevice(d))) => self.switch_device(d),
+            // The cache is ours whatever is playing
+            (_, AppEvent::SettingsEvent(SettingsEvent::AudioCacheClearRequested)) => {
+                self.send_command_to_local_player(Command::ClearAudioCache)
+            }
             (Device::Local, AppEvent::PlaybackEvent(event)) => self.notify_local_player(event),
             (Device::Local, AppEvent::SettingsEvent(SettingsEvent::PlayerSettingsChanged)) => {
                 self.send_command_to_local_player(Command::ReloadSettings)
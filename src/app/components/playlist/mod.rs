@@ -6,3 +6,4 @@ mod song;
 pub use song::*;
 
 mod song_actions;
+pub use song_actions::check_saved_tracks;
@@ -1,17 +1,22 @@
 use gio::prelude::*;
 use gtk::prelude::*;
+use std::cell::RefCell;
+use std::collections::HashSet;
 use std::ops::Deref;
 use std::rc::Rc;
 
 use crate::app::components::utils::{ancestor, AnimatorDefault};
-use crate::app::components::{Component, EventListener, SongWidget};
+use crate::app::components::{Component, EventListener, FilterBarWidget, SongWidget};
 use crate::app::models::{SongListModel, SongModel, SongState};
-use crate::app::state::{PlaybackEvent, SelectionEvent, SelectionState};
-use crate::app::{AppEvent, Worker};
+use crate::app::state::{LoginEvent, PlaybackEvent, SelectionEvent, SelectionState};
+use crate::app::{AppEvent, BrowserEvent, Worker};
 
 pub trait PlaylistModel {
     fn is_paused(&self) -> bool;
 
+    // Nothing can be played (or saved) until the session is restored
+    fn is_offline(&self) -> bool;
+
     fn song_list_model(&self) -> SongListModel;
 
     fn current_song_id(&self) -> Option<String>;
@@ -33,6 +38,20 @@ pub trait PlaylistModel {
         None
     }
 
+    // Songs can be dragged around and removed (that's the play queue)
+    fn is_reorderable(&self) -> bool {
+        false
+    }
+    // The song with the given id was dropped onto the one with the `onto` id
+    fn move_song(&self, _id: &str, _onto: &str) {}
+
+    // Whether the song is in the user's Liked Songs, None if it can't be saved from here (no star button then)
+    fn is_song_saved(&self, _id: &str) -> Option<bool> {
+        None
+    }
+    // Some songs just showed up, the answer comes back as TrackSaved/TrackUnsaved events
+    fn check_saved_songs(&self, _ids: Vec<String>) {}
+
     fn select_song(&self, _id: &str) {}
     fn deselect_song(&self, _id: &str) {}
     fn enable_selection(&self) -> bool {
@@ -58,6 +77,7 @@ pub trait PlaylistModel {
         SongState {
             is_selected,
             is_playing,
+            is_saved: self.is_song_saved(id).unwrap_or(false),
         }
     }
 
@@ -76,6 +96,10 @@ pub struct Playlist<Model> {
     animator: AnimatorDefault,
     listview: gtk::ListView,
     model: Rc<Model>,
+    // Lowercased, empty when not filtering
+    filter_query: Rc<RefCell<String>>,
+    filter: gtk::CustomFilter,
+    filter_bar: Option<FilterBarWidget>,
 }
 
 impl<Model> Playlist<Model>
@@ -84,7 +108,36 @@ where
 {
     pub fn new(listview: gtk::ListView, model: Rc<Model>, worker: Worker) -> Self {
         let list_model = model.song_list_model();
-        let selection_model = gtk::NoSelection::new(Some(list_model.clone()));
+
+        // Connected before anything else sees the change, while the focused row still shows its song
+        let focused_id: Rc<RefCell<Option<String>>> = Default::default();
+        list_model.connect_items_changed(clone!(
+            #[weak]
+            listview,
+            #[strong]
+            focused_id,
+            move |_, _, _, _| {
+                focused_id.replace(Self::focused_song_id(&listview));
+            }
+        ));
+
+        // Filtering happens on top of the list model, so that the underlying state is left untouched
+        let filter_query: Rc<RefCell<String>> = Default::default();
+        let filter = gtk::CustomFilter::new(clone!(
+            #[strong]
+            filter_query,
+            move |item| {
+                let query = filter_query.borrow();
+                query.is_empty()
+                    || item
+                        .downcast_ref::<SongModel>()
+                        .map(|song| song.description().matches_filter(&query))
+                        .unwrap_or(false)
+            }
+        ));
+        let filter_model =
+            gtk::FilterListModel::new(Some(list_model.clone()), Some(filter.clone()));
+        let selection_model = gtk::NoSelection::new(Some(filter_model.clone()));
         let factory = gtk::SignalListItemFactory::new();
 
         listview.add_css_class("playlist");
@@ -97,10 +150,16 @@ where
         Self::set_paused(&listview, model.is_paused());
         Self::set_selection_active(&listview, model.is_selection_enabled());
 
-        factory.connect_setup(|_, item| {
-            let item = item.downcast_ref::<gtk::ListItem>().unwrap();
-            item.set_child(Some(&SongWidget::new()));
-        });
+        factory.connect_setup(clone!(
+            #[weak]
+            model,
+            move |_, item| {
+                let item = item.downcast_ref::<gtk::ListItem>().unwrap();
+                let widget = SongWidget::new();
+                Self::add_reordering(&widget, item, &model);
+                item.set_child(Some(&widget));
+            }
+        ));
 
         factory.connect_bind(clone!(
             #[weak]
@@ -110,12 +169,38 @@ where
                 let song_model = item.item().unwrap().downcast::<SongModel>().unwrap();
                 song_model.set_state(model.song_state(&song_model.get_id()));
 
+                // Unplayable tracks can't be clicked
+                item.set_activatable(song_model.description().is_playable);
+
                 let widget = item.child().unwrap().downcast::<SongWidget>().unwrap();
                 widget.bind(&song_model, worker.clone(), model.show_song_covers());
 
                 let id = &song_model.get_id();
                 widget.set_actions(model.actions_for(id).as_ref());
                 widget.set_menu(model.menu_for(id).as_ref());
+                widget.set_removable(model.is_reorderable());
+                widget.set_savable(
+                    model.is_song_saved(id).is_some() && !song_model.description().is_episode(),
+                );
+            }
+        ));
+
+        // Songs only need checking once, events keep them up to date afterwards
+        let checked_ids: Rc<RefCell<HashSet<String>>> = Default::default();
+        Self::check_saved_songs(&model, &checked_ids, &list_model, 0, list_model.n_items());
+        list_model.connect_items_changed(clone!(
+            #[weak]
+            model,
+            #[weak]
+            listview,
+            #[weak]
+            filter_model,
+            move |list_model, position, _, added| {
+                Self::check_saved_songs(&model, &checked_ids, list_model, position, added);
+                // Sorting replaces every row, the focus goes back to the song it was on
+                if let Some(id) = focused_id.take() {
+                    Self::restore_focus(&listview, &filter_model, &id);
+                }
             }
         ));
 
@@ -129,17 +214,24 @@ where
             #[weak]
             list_model,
             #[weak]
+            filter_model,
+            #[weak]
             model,
             move |_, position| {
-                let song = list_model
-                    .index_continuous(position as usize)
+                let song = filter_model
+                    .item(position)
+                    .and_downcast::<SongModel>()
                     .expect("attempt to access invalid index");
                 let song = song.description();
                 let selection_enabled = model.is_selection_enabled();
                 if selection_enabled {
                     model.toggle_select(&song.id);
-                } else {
-                    model.play_song_at(position as usize, &song.id);
+                } else if !model.is_offline() {
+                    // The position in the filtered list means nothing to the model
+                    let position = list_model
+                        .find_display_index(&song.id)
+                        .unwrap_or(position as usize);
+                    model.play_song_at(position, &song.id);
                 }
             }
         ));
@@ -160,6 +252,132 @@ where
             animator: AnimatorDefault::ease_in_out_animator(),
             listview,
             model,
+            filter_query,
+            filter,
+            filter_bar: None,
+        }
+    }
+
+    // Episodes can't be saved that way, so no need to ask
+    fn check_saved_songs(
+        model: &Rc<Model>,
+        checked_ids: &RefCell<HashSet<String>>,
+        list_model: &SongListModel,
+        position: u32,
+        count: u32,
+    ) {
+        let mut checked_ids = checked_ids.borrow_mut();
+        let ids: Vec<String> = (position..position + count)
+            .filter_map(|i| list_model.item(i).and_downcast::<SongModel>())
+            .filter(|song| !song.description().is_episode())
+            .map(|song| song.get_id())
+            .filter(|id| checked_ids.insert(id.clone()))
+            .collect();
+        if !ids.is_empty() {
+            model.check_saved_songs(ids);
+        }
+    }
+
+    fn focused_song_id(listview: &gtk::ListView) -> Option<String> {
+        let focus = listview.root()?.focus()?;
+        // Rows are the children of the listview, each holding one of our song widgets
+        let row = std::iter::successors(Some(focus), |w| w.parent())
+            .find(|w| w.parent().as_ref() == Some(listview.upcast_ref()))?;
+        row.first_child().and_downcast::<SongWidget>()?.song_id()
+    }
+
+    fn restore_focus(listview: &gtk::ListView, filter_model: &gtk::FilterListModel, id: &str) {
+        if Self::focused_song_id(listview).as_deref() == Some(id) {
+            return;
+        }
+        let position = (0..filter_model.n_items()).find(|&i| {
+            filter_model
+                .item(i)
+                .and_downcast::<SongModel>()
+                .is_some_and(|song| song.get_id() == id)
+        });
+        if let Some(position) = position {
+            listview.scroll_to(position, gtk::ListScrollFlags::FOCUS, None);
+        }
+    }
+
+    // Songs are dragged by their id, and dropped onto the one whose place they should take
+    fn add_reordering(widget: &SongWidget, item: &gtk::ListItem, model: &Rc<Model>) {
+        let song_id = |item: &gtk::ListItem| {
+            item.item()
+                .and_downcast::<SongModel>()
+                .map(|song| song.get_id())
+        };
+
+        let drag_source = gtk::DragSource::new();
+        drag_source.set_actions(gdk::DragAction::MOVE);
+        drag_source.connect_prepare(clone!(
+            #[weak]
+            model,
+            #[weak]
+            item,
+            #[upgrade_or]
+            None,
+            move |_, _, _| {
+                if !model.is_reorderable() || model.is_selection_enabled() {
+                    return None;
+                }
+                let id = song_id(&item)?;
+                Some(gdk::ContentProvider::for_value(&id.to_value()))
+            }
+        ));
+        drag_source.connect_drag_begin(clone!(
+            #[weak]
+            widget,
+            move |source, _| {
+                let icon = gtk::WidgetPaintable::new(Some(&widget));
+                source.set_icon(Some(&icon), 0, 0);
+            }
+        ));
+        widget.add_controller(drag_source);
+
+        let drop_target = gtk::DropTarget::new(String::static_type(), gdk::DragAction::MOVE);
+        drop_target.connect_drop(clone!(
+            #[weak]
+            model,
+            #[weak]
+            item,
+            #[upgrade_or]
+            false,
+            move |_, value, _, _| {
+                let (Ok(id), Some(onto)) = (value.get::<String>(), song_id(&item)) else {
+                    return false;
+                };
+                model.move_song(&id, &onto);
+                true
+            }
+        ));
+        widget.add_controller(drop_target);
+    }
+
+    // Lets the user filter the list with the given bar (Ctrl+F)
+    pub fn with_filter_bar(mut self, filter_bar: FilterBarWidget) -> Self {
+        let filter_query = Rc::clone(&self.filter_query);
+        let filter = self.filter.clone();
+        filter_bar.connect_filter_changed(move |query| {
+            filter_query.replace(query.to_lowercase());
+            filter.changed(gtk::FilterChange::Different);
+        });
+        self.filter_bar = Some(filter_bar);
+        self
+    }
+
+    fn is_filtered(&self) -> bool {
+        !self.filter_query.borrow().is_empty()
+    }
+
+    fn reset_filter(&self) {
+        if let Some(filter_bar) = self.filter_bar.as_ref() {
+            filter_bar.reset();
+        }
+        if self.is_filtered() {
+            self.filter_query.borrow_mut().clear();
+            self.filter.changed(gtk::FilterChange::LessStrict);
         }
     }
 
@@ -193,7 +411,8 @@ where
     }
 
     fn update_list(&self) {
-        let autoscroll_to_playing = self.model.autoscroll_to_playing();
+        // Positions are those of the whole list, so no autoscrolling while filtering
+        let autoscroll_to_playing = self.model.autoscroll_to_playing() && !self.is_filtered();
         let is_selection_enabled = self.model.is_selection_enabled();
 
         self.model.song_list_model().for_each(|i, model_song| {
@@ -230,10 +449,12 @@ impl SongModel {
         SongState {
             is_playing,
             is_selected,
+            is_saved,
         }: SongState,
     ) {
         self.set_playing(is_playing);
         self.set_selected(is_selected);
+        self.set_saved(is_saved);
     }
 }
 
@@ -249,15 +470,38 @@ where
             AppEvent::PlaybackEvent(PlaybackEvent::TrackChanged(_)) => {
                 self.update_list();
             }
+            AppEvent::BrowserEvent(
+                BrowserEvent::TrackSaved(id) | BrowserEvent::TrackUnsaved(id),
+            ) => {
+                // The same song can show up in a few lists at once
+                if let Some(song) = self.model.song_list_model().get(id) {
+                    song.set_saved(self.model.is_song_saved(id).unwrap_or(false));
+                }
+            }
             AppEvent::PlaybackEvent(
                 PlaybackEvent::PlaybackResumed | PlaybackEvent::PlaybackPaused,
             ) => {
                 Self::set_paused(&self.listview, self.model.is_paused());
             }
+            // The rows' actions are made when they're bound, going through that again is the way to update them
+            AppEvent::LoginEvent(LoginEvent::OfflineChanged(_)) => {
+                if let Some(factory) = self.listview.factory() {
+                    self.listview.set_factory(None::<&gtk::ListItemFactory>);
+                    self.listview.set_factory(Some(&factory));
+                }
+            }
             AppEvent::SelectionEvent(SelectionEvent::SelectionModeChanged(_)) => {
                 Self::set_selection_active(&self.listview, self.model.is_selection_enabled());
                 self.update_list();
             }
+            AppEvent::BrowserEvent(
+                BrowserEvent::NavigationPushed(_)
+                | BrowserEvent::NavigationPopped
+                | BrowserEvent::NavigationPoppedTo(_),
+            )
+            | AppEvent::NowPlayingShown => {
+                self.reset_filter();
+            }
             _ => {}
         }
     }
@@ -2,18 +2,21 @@ use crate::app::components::display_add_css_provider;
 use crate::app::loader::ImageLoader;
 use crate::app::models::SongModel;
 use crate::app::Worker;
+use gettextrs::gettext;
 use gio::MenuModel;
 use glib::subclass::InitializingObject;
 
 use gtk::prelude::*;
 use gtk::subclass::prelude::*;
 use gtk::CompositeTemplate;
+use std::cell::RefCell;
 
 mod imp {
 
     use super::*;
 
     const SONG_CLASS: &str = "song--playing";
+    const SAVED_CLASS: &str = "song__like--saved";
 
     #[derive(Debug, Default, CompositeTemplate)]
     #[template(resource = "/dev/diegovsky/Riff/components/song.ui")]
@@ -33,14 +36,26 @@ mod imp {
         #[template_child]
         pub song_artist: TemplateChild<gtk::Label>,
 
+        #[template_child]
+        pub song_explicit: TemplateChild<gtk::Label>,
+
         #[template_child]
         pub song_length: TemplateChild<gtk::Label>,
 
+        #[template_child]
+        pub like_btn: TemplateChild<gtk::Button>,
+
+        #[template_child]
+        pub remove_btn: TemplateChild<gtk::Button>,
+
         #[template_child]
         pub menu_btn: TemplateChild<gtk::MenuButton>,
 
         #[template_child]
         pub song_cover: TemplateChild<gtk::Image>,
+
+        // The song currently bound
+        pub song_id: RefCell<Option<String>>,
     }
 
     #[glib::object_subclass]
@@ -59,9 +74,10 @@ mod imp {
     }
 
     lazy_static! {
-        static ref PROPERTIES: [glib::ParamSpec; 2] = [
+        static ref PROPERTIES: [glib::ParamSpec; 3] = [
             glib::ParamSpecBoolean::builder("playing").build(),
-            glib::ParamSpecBoolean::builder("selected").build()
+            glib::ParamSpecBoolean::builder("selected").build(),
+            glib::ParamSpecBoolean::builder("saved").build()
         ];
     }
 
@@ -88,6 +104,24 @@ mod imp {
                         .expect("type conformity checked by `Object::set_property`");
                     self.song_checkbox.set_active(is_selected);
                 }
+                "saved" => {
+                    let is_saved = value
+                        .get()
+                        .expect("type conformity checked by `Object::set_property`");
+                    if is_saved {
+                        self.like_btn.set_icon_name("starred-symbolic");
+                        // translators: Tooltip of the button removing a song from the user's Liked Songs
+                        self.like_btn
+                            .set_tooltip_text(Some(&gettext("Remove from Liked Songs")));
+                        self.like_btn.add_css_class(SAVED_CLASS);
+                    } else {
+                        self.like_btn.set_icon_name("non-starred-symbolic");
+                        // translators: Tooltip of the button adding a song to the user's Liked Songs
+                        self.like_btn
+                            .set_tooltip_text(Some(&gettext("Save to Liked Songs")));
+                        self.like_btn.remove_css_class(SAVED_CLASS);
+                    }
+                }
                 _ => unimplemented!(),
             }
         }
@@ -96,6 +130,7 @@ mod imp {
             match pspec.name() {
                 "playing" => self.obj().has_css_class(SONG_CLASS).to_value(),
                 "selected" => self.song_checkbox.is_active().to_value(),
+                "saved" => self.like_btn.has_css_class(SAVED_CLASS).to_value(),
                 _ => unimplemented!(),
             }
         }
@@ -103,6 +138,18 @@ mod imp {
         fn constructed(&self) {
             self.parent_constructed();
             self.song_checkbox.set_sensitive(false);
+
+            // Right click brings up the same menu as the button
+            let secondary_click = gtk::GestureClick::new();
+            secondary_click.set_button(gdk::BUTTON_SECONDARY);
+            let menu_btn = self.menu_btn.get();
+            secondary_click.connect_pressed(move |gesture, _, _, _| {
+                if menu_btn.menu_model().is_some() {
+                    gesture.set_state(gtk::EventSequenceState::Claimed);
+                    menu_btn.popup();
+                }
+            });
+            self.obj().add_controller(secondary_click);
         }
 
         fn dispose(&self) {
@@ -144,6 +191,16 @@ impl SongWidget {
         }
     }
 
+    // The button triggers the `song.toggle_saved` action, which has to be provided with the others
+    pub fn set_savable(&self, savable: bool) {
+        self.imp().like_btn.set_visible(savable);
+    }
+
+    // The button triggers the `song.dequeue` action, which has to be provided with the others
+    pub fn set_removable(&self, removable: bool) {
+        self.imp().remove_btn.set_visible(removable);
+    }
+
     fn set_show_cover(&self, show_cover: bool) {
         let song_class = "song--cover";
         if show_cover {
@@ -153,6 +210,19 @@ impl SongWidget {
         }
     }
 
+    // Greyed out, there's no point in trying to play it
+    fn set_playable(&self, playable: bool) {
+        let unavailable_class = "song--unavailable";
+        if playable {
+            self.remove_css_class(unavailable_class);
+            self.set_tooltip_text(None);
+        } else {
+            self.add_css_class(unavailable_class);
+            // translators: Tooltip of a track that can't be played, usually because it isn't available in the user's country
+            self.set_tooltip_text(Some(&gettext("This track is unavailable")));
+        }
+    }
+
     fn set_image(&self, pixbuf: &gdk_pixbuf::Pixbuf) {
         let texture = gdk::Texture::for_pixbuf(pixbuf);
         self.imp().song_cover.set_paintable(Some(&texture));
@@ -173,14 +243,24 @@ impl SongWidget {
         }
     }
 
+    pub fn song_id(&self) -> Option<String> {
+        self.imp().song_id.borrow().clone()
+    }
+
     pub fn bind(&self, model: &SongModel, worker: Worker, show_cover: bool) {
         let widget = self.imp();
+        widget.song_id.replace(Some(model.get_id()));
 
         model.bind_title(&*widget.song_title, "label");
         model.bind_artist(&*widget.song_artist, "label");
         model.bind_duration(&*widget.song_length, "label");
         model.bind_playing(self, "playing");
         model.bind_selected(self, "selected");
+        model.bind_saved(self, "saved");
+        widget
+            .song_explicit
+            .set_visible(model.description().explicit);
+        self.set_playable(model.description().is_playable);
 
         self.set_show_cover(show_cover);
         if show_cover {
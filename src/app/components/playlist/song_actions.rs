@@ -1,9 +1,26 @@
-use gdk::prelude::*;
+use gio::prelude::*;
 use gio::SimpleAction;
+use std::rc::Rc;
 
+use crate::app::components::labels;
+use crate::app::components::share::{self, ShareKind};
 use crate::app::models::SongDescription;
-use crate::app::state::{AppAction, PlaybackAction};
-use crate::app::ActionDispatcher;
+use crate::app::state::{AppAction, BrowserAction, PlaybackAction};
+use crate::app::{ActionDispatcher, AppModel};
+
+// Asks which of those tracks are in the Liked Songs, for their star buttons.
+// Not worth bothering anyone if that fails.
+pub fn check_saved_tracks(
+    app_model: &AppModel,
+    dispatcher: &dyn ActionDispatcher,
+    ids: Vec<String>,
+) {
+    let api = app_model.get_spotify();
+    dispatcher.dispatch_async(Box::pin(async move {
+        let saved = api.are_tracks_saved(ids.clone()).await.ok()?;
+        Some(BrowserAction::SetTracksSaved(ids.into_iter().zip(saved).collect()).into())
+    }));
+}
 
 impl SongDescription {
     pub fn make_queue_action(
@@ -14,11 +31,73 @@ impl SongDescription {
         let queue = SimpleAction::new(name.unwrap_or("queue"), None);
         let song = self.clone();
         queue.connect_activate(move |_, _| {
-            dispatcher.dispatch(PlaybackAction::Queue(vec![song.clone()]).into());
+            dispatcher.dispatch_many(vec![
+                PlaybackAction::Queue(vec![song.clone()]).into(),
+                AppAction::ShowNotification(labels::ADDED_TO_QUEUE.clone()),
+            ]);
         });
         queue
     }
 
+    // Unlike `make_move_to_next_action`, the song doesn't have to be queued already
+    pub fn make_play_next_action(
+        &self,
+        dispatcher: Box<dyn ActionDispatcher>,
+        name: Option<&str>,
+    ) -> SimpleAction {
+        let play_next = SimpleAction::new(name.unwrap_or("play_next"), None);
+        let song = self.clone();
+        play_next.connect_activate(move |_, _| {
+            dispatcher.dispatch_many(vec![
+                PlaybackAction::QueueNext(vec![song.clone()]).into(),
+                AppAction::ShowNotification(labels::ADDED_TO_QUEUE.clone()),
+            ]);
+        });
+        play_next
+    }
+
+    // Saves the song to the Liked Songs, or takes it out if it's already there
+    pub fn make_toggle_saved_action(
+        &self,
+        app_model: Rc<AppModel>,
+        dispatcher: Box<dyn ActionDispatcher>,
+        name: Option<&str>,
+    ) -> SimpleAction {
+        let toggle_saved = SimpleAction::new(name.unwrap_or("toggle_saved"), None);
+        toggle_saved.set_enabled(!app_model.get_state().logged_user.offline);
+        let song = self.clone();
+        toggle_saved.connect_activate(move |action, _| {
+            let api = app_model.get_spotify();
+            let song = song.clone();
+            let is_saved = app_model.get_state().browser.is_track_saved(&song.id);
+            // Toggling again before hearing back would send the same request twice
+            action.set_enabled(false);
+            // Requests are polled on the main thread, where the action can be upgraded
+            let action = glib::SendWeakRef::from(action.downgrade());
+            let done = move || {
+                if let Some(action) = action.upgrade() {
+                    action.set_enabled(true);
+                }
+            };
+            if is_saved {
+                dispatcher.call_spotify_and_dispatch(move || async move {
+                    let result = api.remove_saved_tracks(vec![song.id.clone()]).await;
+                    done();
+                    result?;
+                    Ok(BrowserAction::RemoveSavedTracks(vec![song.id]).into())
+                });
+            } else {
+                dispatcher.call_spotify_and_dispatch(move || async move {
+                    let result = api.save_tracks(vec![song.id.clone()]).await;
+                    done();
+                    result?;
+                    Ok(BrowserAction::SaveTracks(vec![song]).into())
+                });
+            }
+        });
+        toggle_saved
+    }
+
     pub fn make_dequeue_action(
         &self,
         dispatcher: Box<dyn ActionDispatcher>,
@@ -32,17 +111,38 @@ impl SongDescription {
         dequeue
     }
 
-    pub fn make_link_action(&self, name: Option<&str>) -> SimpleAction {
+    pub fn make_move_to_next_action(
+        &self,
+        dispatcher: Box<dyn ActionDispatcher>,
+        name: Option<&str>,
+    ) -> SimpleAction {
+        let move_to_next = SimpleAction::new(name.unwrap_or("move_to_next"), None);
         let track_id = self.id.clone();
-        let copy_link = SimpleAction::new(name.unwrap_or("copy_link"), None);
-        copy_link.connect_activate(move |_, _| {
-            let link = format!("https://open.spotify.com/track/{track_id}");
-            let clipboard = gdk::Display::default().unwrap().clipboard();
-            clipboard
-                .set_content(Some(&gdk::ContentProvider::for_value(&link.to_value())))
-                .expect("Failed to set clipboard content");
+        move_to_next.connect_activate(move |_, _| {
+            dispatcher.dispatch(PlaybackAction::MoveToNext(track_id.clone()).into());
         });
-        copy_link
+        move_to_next
+    }
+
+    fn share_url(&self) -> String {
+        let kind = if self.is_episode() {
+            ShareKind::Episode
+        } else {
+            ShareKind::Track
+        };
+        share::share_url(kind, &self.id)
+    }
+
+    pub fn make_link_action(
+        &self,
+        dispatcher: Box<dyn ActionDispatcher>,
+        name: Option<&str>,
+    ) -> SimpleAction {
+        share::make_copy_link_action(self.share_url(), dispatcher, name)
+    }
+
+    pub fn make_open_in_browser_action(&self, name: Option<&str>) -> SimpleAction {
+        share::make_open_in_browser_action(self.share_url(), name)
     }
 
     pub fn make_album_action(
@@ -51,9 +151,15 @@ impl SongDescription {
         name: Option<&str>,
     ) -> SimpleAction {
         let album_id = self.album.id.clone();
+        let is_episode = self.is_episode();
         let view_album = SimpleAction::new(name.unwrap_or("view_album"), None);
         view_album.connect_activate(move |_, _| {
-            dispatcher.dispatch(AppAction::ViewAlbum(album_id.clone()));
+            // For an episode, that's its show
+            if is_episode {
+                dispatcher.dispatch(AppAction::ViewShow(album_id.clone()));
+            } else {
+                dispatcher.dispatch(AppAction::ViewAlbum(album_id.clone()));
+            }
         });
         view_album
     }
@@ -63,6 +169,7 @@ impl SongDescription {
         dispatcher: Box<dyn ActionDispatcher>,
         prefix: Option<&str>,
     ) -> Vec<SimpleAction> {
+        let is_episode = self.is_episode();
         self.artists
             .iter()
             .map(|artist| {
@@ -73,7 +180,11 @@ impl SongDescription {
                 );
                 let dispatcher = dispatcher.box_clone();
                 view_artist.connect_activate(move |_, _| {
-                    dispatcher.dispatch(AppAction::ViewArtist(id.clone()));
+                    if is_episode {
+                        dispatcher.dispatch(AppAction::ViewShow(id.clone()));
+                    } else {
+                        dispatcher.dispatch(AppAction::ViewArtist(id.clone()));
+                    }
                 });
                 view_artist
             })
@@ -8,11 +8,11 @@ use super::playlist_headerbar::PlaylistHeaderBarWidget;
 use super::PlaylistDetailsModel;
 
 use crate::app::components::{
-    Component, EventListener, Playlist, PlaylistModel, ScrollingHeaderWidget,
+    Component, EventListener, FilterBarWidget, Playlist, PlaylistModel, ScrollingHeaderWidget,
 };
 use crate::app::dispatch::Worker;
 use crate::app::loader::ImageLoader;
-use crate::app::state::{PlaybackEvent, SelectionEvent};
+use crate::app::state::{HeaderState, LoginEvent, PlaybackEvent, ScreenName, SelectionEvent};
 use crate::app::{AppEvent, BrowserEvent};
 use libadwaita::subclass::prelude::BinImpl;
 
@@ -26,6 +26,9 @@ mod imp {
         #[template_child]
         pub headerbar: TemplateChild<PlaylistHeaderBarWidget>,
 
+        #[template_child]
+        pub filter_bar: TemplateChild<FilterBarWidget>,
+
         #[template_child]
         pub scrolling_header: TemplateChild<ScrollingHeaderWidget>,
 
@@ -75,6 +78,10 @@ impl PlaylistDetailsWidget {
         self.imp().tracks.as_ref()
     }
 
+    fn filter_bar_widget(&self) -> &FilterBarWidget {
+        self.imp().filter_bar.as_ref()
+    }
+
     fn connect_bottom_edge<F>(&self, f: F)
     where
         F: Fn() + 'static,
@@ -82,27 +89,11 @@ impl PlaylistDetailsWidget {
         self.imp().scrolling_header.connect_bottom_edge(f);
     }
 
-    fn set_header_visible(&self, visible: bool) {
-        let widget = self.imp();
-        widget.headerbar.set_title_visible(true);
-        if visible {
-            widget.headerbar.add_classes(&["flat"]);
-        } else {
-            widget.headerbar.remove_classes(&["flat"]);
-        }
-    }
-
-    fn connect_header(&self) {
-        self.set_header_visible(false);
-        self.imp()
-            .scrolling_header
-            .connect_header_visibility(clone!(
-                #[weak(rename_to = _self)]
-                self,
-                move |visible| {
-                    _self.set_header_visible(visible);
-                }
-            ));
+    fn connect_header_visibility<F>(&self, f: F)
+    where
+        F: Fn(bool) + Clone + 'static,
+    {
+        self.imp().scrolling_header.connect_header_visibility(f);
     }
 
     fn set_loaded(&self) {
@@ -116,19 +107,42 @@ impl PlaylistDetailsWidget {
         self.imp().headerbar.set_editing(editing);
     }
 
+    fn set_header_menus(
+        &self,
+        sort_menu: &gio::MenuModel,
+        share_menu: &gio::MenuModel,
+        actions: &gio::ActionGroup,
+    ) {
+        self.imp()
+            .headerbar
+            .set_menus(sort_menu, share_menu, actions);
+    }
+
     fn set_editable(&self, editing: bool) {
         self.imp().headerbar.set_editable(editing);
     }
 
+    fn set_offline(&self, offline: bool) {
+        self.imp().header_widget.set_offline(offline);
+        self.imp().headerbar.set_offline(offline);
+    }
+
     fn set_info(&self, playlist: &str, owner: &str) {
         self.imp().header_widget.set_info(playlist, owner);
-        self.imp().headerbar.set_title(Some(playlist));
+    }
+
+    fn set_header(&self, header: &HeaderState) {
+        self.imp().headerbar.set_header(header);
     }
 
     fn set_playing(&self, is_playing: bool) {
         self.imp().header_widget.set_playing(is_playing);
     }
 
+    fn set_play_pending(&self) {
+        self.imp().header_widget.set_play_pending();
+    }
+
     fn set_artwork(&self, art: &gdk_pixbuf::Pixbuf) {
         self.imp().header_widget.set_artwork(art);
     }
@@ -204,15 +218,29 @@ impl PlaylistDetails {
         }
 
         let widget = PlaylistDetailsWidget::new();
-        let playlist = Box::new(Playlist::new(
-            widget.playlist_tracks_widget().clone(),
-            model.clone(),
-            worker.clone(),
-        ));
+        let playlist = Box::new(
+            Playlist::new(
+                widget.playlist_tracks_widget().clone(),
+                model.clone(),
+                worker.clone(),
+            )
+            .with_filter_bar(widget.filter_bar_widget().clone()),
+        );
 
         widget.set_editable(model.is_playlist_editable());
-
-        widget.connect_header();
+        widget.set_offline(model.is_offline());
+        widget.set_header_menus(
+            &model.sort_menu(),
+            &model.share_menu(),
+            &model.header_actions(),
+        );
+        widget.set_header(&model.header());
+
+        widget.connect_header_visibility(clone!(
+            #[weak]
+            model,
+            move |visible| model.set_header_visible(visible)
+        ));
 
         widget.connect_bottom_edge(clone!(
             #[weak]
@@ -253,7 +281,14 @@ impl PlaylistDetails {
         widget.connect_play(clone!(
             #[weak]
             model,
-            move || model.toggle_play_playlist()
+            #[weak(rename_to = widget)]
+            widget,
+            move || {
+                model.toggle_play_playlist();
+                if model.is_play_pending() {
+                    widget.set_play_pending();
+                }
+            }
         ));
 
         widget.connect_go_back(clone!(
@@ -295,6 +330,16 @@ impl PlaylistDetails {
         }
     }
 
+    // Play might have been pressed before there was anything to play
+    fn update_play_pending(&self) {
+        self.model.play_if_pending();
+        if self.model.is_play_pending() {
+            self.widget.set_play_pending();
+        } else {
+            self.update_playing(self.model.is_playing());
+        }
+    }
+
     fn update_playing(&self, is_playing: bool) {
         if !self.model.playlist_is_playing() || !self.model.is_playing() {
             self.widget.set_playing(false);
@@ -329,10 +374,37 @@ impl EventListener for PlaylistDetails {
             {
                 self.update_details();
                 self.update_playing(true);
+                self.update_play_pending();
+            }
+            AppEvent::BrowserEvent(BrowserEvent::PlaylistTracksAppended(id))
+                if id == &self.model.id =>
+            {
+                self.update_play_pending();
+                self.model.play_sorted_if_pending();
+                self.model.load_rest_if_sorted();
+            }
+            AppEvent::BrowserEvent(BrowserEvent::PlaylistSortChanged(id))
+                if id == &self.model.id =>
+            {
+                self.model.load_rest_if_sorted();
+            }
+            AppEvent::BrowserEvent(BrowserEvent::TracksLoadFailed(
+                ScreenName::PlaylistDetails(id),
+            )) if id == &self.model.id => {
+                self.model.cancel_play_pending();
+                self.update_play_pending();
+            }
+            AppEvent::BrowserEvent(BrowserEvent::HeaderUpdated(ScreenName::PlaylistDetails(
+                id,
+            ))) if id == &self.model.id => {
+                self.widget.set_header(&self.model.header());
             }
             AppEvent::SelectionEvent(SelectionEvent::SelectionModeChanged(editing)) => {
                 self.set_editing(*editing);
             }
+            AppEvent::LoginEvent(LoginEvent::OfflineChanged(offline)) => {
+                self.widget.set_offline(*offline);
+            }
             AppEvent::PlaybackEvent(PlaybackEvent::PlaybackPaused) => {
                 self.update_playing(false);
             }
@@ -1,15 +1,19 @@
 use gettextrs::gettext;
 use gio::prelude::*;
 use gio::SimpleActionGroup;
-use std::cell::Ref;
+use std::cell::{Cell, Ref, RefCell};
 use std::ops::Deref;
 use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
 use crate::api::SpotifyApiError;
-use crate::app::components::{labels, PlaylistModel};
+use crate::app::components::{check_saved_tracks, labels, share, PlaylistModel};
 use crate::app::models::*;
-use crate::app::state::SelectionContext;
-use crate::app::state::{BrowserAction, PlaybackAction, SelectionAction, SelectionState};
+use crate::app::state::{
+    BrowserAction, HeaderState, PlaybackAction, ScreenName, SelectionAction, SelectionContext,
+    SelectionState,
+};
 use crate::app::AppState;
 use crate::app::{ActionDispatcher, AppAction, AppModel, BatchQuery, SongsSource};
 
@@ -17,6 +21,10 @@ pub struct PlaylistDetailsModel {
     pub id: String,
     app_model: Rc<AppModel>,
     dispatcher: Box<dyn ActionDispatcher>,
+    // Play was pressed, but there wasn't anything to play yet
+    play_pending: Cell<bool>,
+    // A song picked while sorted, to play once the rest of the playlist is there
+    sorted_play_pending: RefCell<Option<String>>,
 }
 
 impl PlaylistDetailsModel {
@@ -25,6 +33,8 @@ impl PlaylistDetailsModel {
             id,
             app_model,
             dispatcher,
+            play_pending: Cell::new(false),
+            sorted_play_pending: RefCell::new(None),
         }
     }
 
@@ -56,57 +66,137 @@ impl PlaylistDetailsModel {
             Some(SongsSource::Playlist(ref id)) if id == &self.id)
     }
 
+    pub fn is_play_pending(&self) -> bool {
+        self.play_pending.get()
+    }
+
+    // The tracks we were waiting for won't come
+    pub fn cancel_play_pending(&self) {
+        self.play_pending.set(false);
+        self.sorted_play_pending.take();
+    }
+
     pub fn toggle_play_playlist(&self) {
-        if self.get_playlist_info().is_some() {
-            if !self.playlist_is_playing() {
-                if self.state().playback.is_shuffled() {
-                    self.dispatcher
-                        .dispatch(AppAction::PlaybackAction(PlaybackAction::ToggleShuffle));
-                }
+        if !self.playlist_is_playing() {
+            self.play_pending.set(true);
+            self.play_if_pending();
+            return;
+        }
+        if self.state().playback.is_playing() {
+            self.dispatcher
+                .dispatch(AppAction::PlaybackAction(PlaybackAction::Pause));
+        } else {
+            self.dispatcher
+                .dispatch(AppAction::PlaybackAction(PlaybackAction::Play));
+        }
+    }
 
-                let first_song = self.song_list_model().index(0);
-                let Some(first_song) = first_song else {
-                    error!("Unable to start playback because the song list is empty");
-                    self.dispatcher
-                        .dispatch(AppAction::ShowNotification(gettext(
-                            "An error occured. Check logs for details!",
-                        )));
-                    return;
-                };
-
-                self.play_song_at(0, &first_song.get_id());
-                return;
-            }
-            if self.state().playback.is_playing() {
-                self.dispatcher
-                    .dispatch(AppAction::PlaybackAction(PlaybackAction::Pause));
-            } else {
+    // Called again whenever tracks come in, until there's something to play
+    pub fn play_if_pending(&self) {
+        if !self.play_pending.get() {
+            return;
+        }
+
+        let first_song = self.song_list_model().index_continuous(0);
+        let Some(first_song) = first_song else {
+            // The first page comes with the details, after that we have to ask for the next one
+            // (the first page might have been all unavailable tracks)
+            let loading = self.get_playlist_info().is_none() || self.load_more_tracks().is_some();
+            if !loading {
                 self.dispatcher
-                    .dispatch(AppAction::PlaybackAction(PlaybackAction::Play));
+                    .dispatch(AppAction::ShowNotification(labels::NOTHING_TO_PLAY.clone()));
             }
+            self.play_pending.set(loading);
+            return;
+        };
+
+        self.play_pending.set(false);
+        if self.state().playback.is_shuffled() {
+            self.dispatcher
+                .dispatch(AppAction::PlaybackAction(PlaybackAction::ToggleShuffle));
         }
+        // The rest of the playlist gets loaded page by page as the queue goes on
+        self.play_song_at(0, &first_song.get_id());
     }
 
+    // What was cached shows up right away, and is replaced by whatever the network says once it answers
     pub fn load_playlist_info(&self) {
         let api = self.app_model.get_spotify();
         let id = self.id.clone();
-        self.dispatcher
-            .call_spotify_and_dispatch(move || async move {
+
+        // Once the network answered, the cached details would only be older
+        let loaded = Arc::new(AtomicBool::new(false));
+
+        let (cached_api, cached_id, cached_loaded) = (api.clone(), id.clone(), loaded.clone());
+        self.dispatcher.dispatch_async(Box::pin(async move {
+            let playlist = cached_api.get_cached_playlist(&cached_id).await.ok()?;
+            let playlist_tracks = cached_api
+                .get_cached_playlist_tracks(&cached_id, 0, 100)
+                .await
+                .ok()?;
+            if cached_loaded.load(Ordering::SeqCst) {
+                return None;
+            }
+            Some(
+                BrowserAction::SetPlaylistDetails(Box::new(playlist), Box::new(playlist_tracks))
+                    .into(),
+            )
+        }));
+
+        let failed = BrowserAction::TracksLoadFailed(ScreenName::PlaylistDetails(id.clone()));
+        self.dispatcher.call_spotify_and_dispatch_many_or(
+            move || async move {
                 let playlist = api.get_playlist(&id).await;
                 let playlist_tracks = api.get_playlist_tracks(&id, 0, 100).await?;
                 match playlist {
-                    Ok(playlist) => Ok(BrowserAction::SetPlaylistDetails(
-                        Box::new(playlist),
-                        Box::new(playlist_tracks),
-                    )
-                    .into()),
+                    Ok(playlist) => {
+                        loaded.store(true, Ordering::SeqCst);
+                        Ok(vec![BrowserAction::SetPlaylistDetails(
+                            Box::new(playlist),
+                            Box::new(playlist_tracks),
+                        )
+                        .into()])
+                    }
                     Err(SpotifyApiError::BadStatus(400, _))
                     | Err(SpotifyApiError::BadStatus(404, _)) => {
-                        Ok(BrowserAction::NavigationPop.into())
+                        Ok(vec![BrowserAction::NavigationPop.into()])
                     }
                     Err(e) => Err(e),
                 }
-            });
+            },
+            vec![failed.into()],
+        );
+    }
+
+    fn is_fully_loaded(&self) -> bool {
+        self.song_list_model()
+            .last_batch()
+            .is_some_and(|batch| batch.next().is_none())
+    }
+
+    // Sorting what's loaded so far would be misleading, so the rest of the playlist follows page by page
+    pub fn load_rest_if_sorted(&self) {
+        if self.song_list_model().sort() != SongSort::Custom {
+            self.load_more_tracks();
+        }
+    }
+
+    // Called again whenever tracks come in, until the whole playlist is there
+    pub fn play_sorted_if_pending(&self) {
+        let Some(id) = self.sorted_play_pending.take() else {
+            return;
+        };
+        if !self.is_fully_loaded() {
+            self.sorted_play_pending.replace(Some(id));
+            self.load_more_tracks();
+            return;
+        }
+        let source = SongsSource::Playlist(self.id.clone());
+        let songs = self.song_list_model().collect();
+        self.dispatcher.dispatch_many(vec![
+            PlaybackAction::LoadSortedSongs(source, songs).into(),
+            PlaybackAction::Load(id).into(),
+        ]);
     }
 
     pub fn load_more_tracks(&self) -> Option<()> {
@@ -120,18 +210,79 @@ impl PlaylistDetailsModel {
         let next_query = query.next()?;
         debug!("next_query = {:?}", &next_query);
         let loader = self.app_model.get_batch_loader();
+        let failed = BrowserAction::TracksLoadFailed(ScreenName::PlaylistDetails(id.clone()));
 
-        self.dispatcher.dispatch_async(Box::pin(async move {
+        self.dispatcher.dispatch_many_async(Box::pin(async move {
             loader
-                .query(next_query, |_s, song_batch| {
-                    BrowserAction::AppendPlaylistTracks(id, Box::new(song_batch)).into()
-                })
+                .query_or(
+                    next_query,
+                    |_s, song_batch| {
+                        BrowserAction::AppendPlaylistTracks(id, Box::new(song_batch)).into()
+                    },
+                    vec![failed.into()],
+                )
                 .await
         }));
 
         Some(())
     }
 
+    pub fn header_actions(&self) -> gio::ActionGroup {
+        let group = SimpleActionGroup::new();
+        let sort = gio::SimpleAction::new_stateful(
+            "sort",
+            Some(glib::VariantTy::STRING),
+            &self.song_list_model().sort().id().to_variant(),
+        );
+        let id = self.id.clone();
+        let dispatcher = self.dispatcher.box_clone();
+        sort.connect_activate(move |action, parameter| {
+            let Some(sort) = parameter.and_then(|p| p.str()).and_then(SongSort::from_id) else {
+                return;
+            };
+            action.set_state(&sort.id().to_variant());
+            dispatcher.dispatch(BrowserAction::SetPlaylistSort(id.clone(), sort).into());
+        });
+        group.add_action(&sort);
+        share::add_share_actions(
+            &group,
+            share::ShareKind::Playlist,
+            &self.id,
+            self.dispatcher.box_clone(),
+        );
+        group.upcast()
+    }
+
+    pub fn share_menu(&self) -> gio::MenuModel {
+        let menu = gio::Menu::new();
+        share::append_share_items(&menu, "playlist");
+        menu.upcast()
+    }
+
+    pub fn sort_menu(&self) -> gio::MenuModel {
+        let menu = gio::Menu::new();
+        let entries = [
+            // translators: Sort option of a playlist, the order chosen by its owner
+            (SongSort::Custom, gettext("Custom (playlist order)")),
+            // translators: Sort option of a playlist
+            (SongSort::Title, gettext("Title")),
+            // translators: Sort option of a playlist
+            (SongSort::Artist, gettext("Artist")),
+            // translators: Sort option of a playlist
+            (SongSort::Album, gettext("Album")),
+            // translators: Sort option of a playlist
+            (SongSort::Duration, gettext("Duration")),
+            // translators: Sort option of a playlist
+            (SongSort::DateAdded, gettext("Date added")),
+        ];
+        for (sort, label) in entries {
+            let item = gio::MenuItem::new(Some(&label), None);
+            item.set_action_and_target_value(Some("playlist.sort"), Some(&sort.id().to_variant()));
+            menu.append_item(&item);
+        }
+        menu.upcast()
+    }
+
     pub fn update_playlist_details(&self, title: String) {
         let api = self.app_model.get_spotify();
         let id = self.id.clone();
@@ -161,6 +312,26 @@ impl PlaylistDetailsModel {
         self.dispatcher
             .dispatch(BrowserAction::NavigationPop.into());
     }
+
+    fn screen_name(&self) -> ScreenName {
+        ScreenName::PlaylistDetails(self.id.clone())
+    }
+
+    pub fn header(&self) -> HeaderState {
+        self.state()
+            .browser
+            .header(&self.screen_name())
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn set_header_visible(&self, visible: bool) {
+        // This gets called on every scroll event, only bother if something changed
+        if self.header().header_visible != visible {
+            self.dispatcher
+                .dispatch(BrowserAction::SetHeaderVisible(self.screen_name(), visible).into());
+        }
+    }
 }
 
 impl PlaylistModel for PlaylistDetailsModel {
@@ -177,13 +348,26 @@ impl PlaylistModel for PlaylistDetailsModel {
         !self.state().playback.is_playing()
     }
 
+    fn is_offline(&self) -> bool {
+        self.state().logged_user.offline
+    }
+
     fn current_song_id(&self) -> Option<String> {
         self.state().playback.current_song_id()
     }
 
     fn play_song_at(&self, pos: usize, id: &str) {
+        let songs = self.song_list_model();
+        // Batches follow the playlist order, so when sorted we queue what's displayed instead,
+        // once all of it is there
+        if songs.sort() != SongSort::Custom {
+            self.sorted_play_pending.replace(Some(id.to_string()));
+            self.play_sorted_if_pending();
+            return;
+        }
+
         let source = SongsSource::Playlist(self.id.clone());
-        let batch = self.song_list_model().song_batch_for(pos);
+        let batch = songs.song_batch_for(pos);
         if let Some(batch) = batch {
             self.dispatcher
                 .dispatch(PlaybackAction::LoadPagedSongs(source, batch).into());
@@ -192,6 +376,14 @@ impl PlaylistModel for PlaylistDetailsModel {
         }
     }
 
+    fn is_song_saved(&self, id: &str) -> Option<bool> {
+        Some(self.app_model.get_state().browser.is_track_saved(id))
+    }
+
+    fn check_saved_songs(&self, ids: Vec<String>) {
+        check_saved_tracks(&self.app_model, self.dispatcher.as_ref(), ids);
+    }
+
     fn actions_for(&self, id: &str) -> Option<gio::ActionGroup> {
         let song = self.song_list_model().get(id)?;
         let song = song.description();
@@ -202,8 +394,15 @@ impl PlaylistModel for PlaylistDetailsModel {
             group.add_action(&view_artist);
         }
         group.add_action(&song.make_album_action(self.dispatcher.box_clone(), None));
-        group.add_action(&song.make_link_action(None));
+        group.add_action(&song.make_link_action(self.dispatcher.box_clone(), None));
+        group.add_action(&song.make_open_in_browser_action(None));
+        group.add_action(&song.make_toggle_saved_action(
+            self.app_model.clone(),
+            self.dispatcher.box_clone(),
+            None,
+        ));
         group.add_action(&song.make_queue_action(self.dispatcher.box_clone(), None));
+        group.add_action(&song.make_play_next_action(self.dispatcher.box_clone(), None));
 
         Some(group.upcast())
     }
@@ -221,7 +420,8 @@ impl PlaylistModel for PlaylistDetailsModel {
             );
         }
 
-        menu.append(Some(&*labels::COPY_LINK), Some("song.copy_link"));
+        share::append_share_items(&menu, "song");
+        menu.append(Some(&*labels::PLAY_NEXT), Some("song.play_next"));
         menu.append(Some(&*labels::ADD_TO_QUEUE), Some("song.queue"));
 
         Some(menu.upcast())
@@ -1,4 +1,5 @@
 use crate::app::components::display_add_css_provider;
+use crate::app::components::utils::show_offline_tooltip;
 use gettextrs::gettext;
 use gtk::prelude::*;
 use gtk::subclass::prelude::*;
@@ -82,7 +83,12 @@ mod imp {
     }
 
     #[glib::derived_properties]
-    impl ObjectImpl for PlaylistHeaderWidget {}
+    impl ObjectImpl for PlaylistHeaderWidget {
+        fn constructed(&self) {
+            self.parent_constructed();
+            show_offline_tooltip(&*self.play_button);
+        }
+    }
     impl WidgetImpl for PlaylistHeaderWidget {}
     impl BoxImpl for PlaylistHeaderWidget {}
 }
@@ -144,6 +150,10 @@ impl PlaylistHeaderWidget {
         widget.author_button_label.set_label(owner);
     }
 
+    pub fn set_offline(&self, offline: bool) {
+        self.imp().play_button.set_sensitive(!offline);
+    }
+
     pub fn set_playing(&self, is_playing: bool) {
         let playback_icon = if is_playing {
             "media-playback-pause-symbolic"
@@ -162,6 +172,13 @@ impl PlaylistHeaderWidget {
         self.imp().play_button.set_tooltip_text(tooltip_text);
     }
 
+    // Until the tracks are there, set_playing puts the icon back
+    pub fn set_play_pending(&self) {
+        let spinner = gtk::Spinner::new();
+        spinner.start();
+        self.imp().play_button.set_child(Some(&spinner));
+    }
+
     pub fn set_editing(&self, editing: bool) {
         let widget = self.imp();
         widget.playlist_label_entry.set_can_focus(editing);
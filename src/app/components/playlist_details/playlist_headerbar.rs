@@ -3,6 +3,9 @@ use gtk::subclass::prelude::*;
 use gtk::CompositeTemplate;
 use libadwaita::subclass::prelude::BinImpl;
 
+use crate::app::components::utils::show_offline_tooltip;
+use crate::app::state::HeaderState;
+
 mod imp {
 
     use super::*;
@@ -25,6 +28,12 @@ mod imp {
         #[template_child]
         pub edit: TemplateChild<gtk::Button>,
 
+        #[template_child]
+        pub sort: TemplateChild<gtk::MenuButton>,
+
+        #[template_child]
+        pub share: TemplateChild<gtk::MenuButton>,
+
         #[template_child]
         pub ok: TemplateChild<gtk::Button>,
 
@@ -51,7 +60,12 @@ mod imp {
         }
     }
 
-    impl ObjectImpl for PlaylistHeaderBarWidget {}
+    impl ObjectImpl for PlaylistHeaderBarWidget {
+        fn constructed(&self) {
+            self.parent_constructed();
+            show_offline_tooltip(&*self.edit);
+        }
+    }
 
     impl BuildableImpl for PlaylistHeaderBarWidget {
         fn add_child(&self, builder: &gtk::Builder, child: &glib::Object, type_: Option<&str>) {
@@ -120,6 +134,22 @@ impl PlaylistHeaderBarWidget {
         self.imp().edit.set_visible(editable);
     }
 
+    // Edits couldn't be saved
+    pub fn set_offline(&self, offline: bool) {
+        self.imp().edit.set_sensitive(!offline);
+    }
+
+    pub fn set_menus(
+        &self,
+        sort_menu: &gio::MenuModel,
+        share_menu: &gio::MenuModel,
+        actions: &gio::ActionGroup,
+    ) {
+        self.imp().sort.set_menu_model(Some(sort_menu));
+        self.imp().share.set_menu_model(Some(share_menu));
+        self.insert_action_group("playlist", Some(actions));
+    }
+
     pub fn set_editing(&self, editing: bool) {
         if editing {
             self.imp().edition_header.set_visible(true);
@@ -140,14 +170,15 @@ impl PlaylistHeaderBarWidget {
         }
     }
 
-    pub fn set_title_visible(&self, visible: bool) {
-        self.imp().title.set_visible(visible);
-    }
-
-    pub fn set_title(&self, title: Option<&str>) {
-        self.imp().title.set_visible(title.is_some());
-        if let Some(title) = title {
-            self.imp().title.set_title(title);
+    pub fn set_header(&self, header: &HeaderState) {
+        let title = &self.imp().title;
+        title.set_visible(header.title.is_some());
+        title.set_title(header.title.as_deref().unwrap_or_default());
+        title.set_subtitle(header.subtitle.as_deref().unwrap_or_default());
+        if header.is_flat() {
+            self.add_classes(&["flat"]);
+        } else {
+            self.remove_classes(&["flat"]);
         }
     }
 }
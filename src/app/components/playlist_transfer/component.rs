@@ -0,0 +1,307 @@
+use gettextrs::gettext;
+use gio::prelude::*;
+use std::cell::Cell;
+use std::collections::HashSet;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use super::playlist_file::{PlaylistFile, PlaylistFileFormat, PlaylistFileTrack};
+use crate::api::{SpotifyApiClient, SpotifyResult};
+use crate::app::components::{labels, EventListener};
+use crate::app::models::PlaylistDescription;
+use crate::app::{ActionDispatcher, AppAction, AppEvent, AppModel, Worker};
+
+type Api = Arc<dyn SpotifyApiClient + Send + Sync>;
+
+// That's as many tracks as the API lets us look up at once
+const CHECK_BATCH_SIZE: usize = 50;
+// ...and as many as we can add to a playlist at once
+const ADD_BATCH_SIZE: usize = 100;
+
+fn file_filters() -> gio::ListStore {
+    let m3u = gtk::FileFilter::new();
+    // translators: This is a file type in the dialog used to import or export a playlist
+    m3u.set_name(Some(&gettext("M3U playlist")));
+    m3u.add_suffix("m3u8");
+    m3u.add_suffix("m3u");
+
+    let json = gtk::FileFilter::new();
+    // translators: This is a file type in the dialog used to import or export a playlist
+    json.set_name(Some(&gettext("JSON playlist")));
+    json.add_suffix("json");
+
+    let filters = gio::ListStore::new::<gtk::FileFilter>();
+    filters.append(&m3u);
+    filters.append(&json);
+    filters
+}
+
+// Gets the rest of the tracks, a playlist only comes with its first page
+async fn fetch_playlist_file(
+    api: Api,
+    playlist: PlaylistDescription,
+) -> SpotifyResult<PlaylistFile> {
+    let mut songs = playlist.songs.songs;
+    let mut next = playlist.songs.batch.next();
+    while let Some(batch) = next {
+        let more = api
+            .get_playlist_tracks(&playlist.id, batch.offset, batch.batch_size)
+            .await?;
+        songs.extend(more.songs);
+        next = more.batch.next();
+    }
+    Ok(PlaylistFile {
+        name: playlist.title,
+        tracks: songs.iter().map(PlaylistFileTrack::from).collect(),
+    })
+}
+
+// Finds a spotify URI for each track, if we can
+async fn resolve_tracks(
+    api: &Api,
+    tracks: &[PlaylistFileTrack],
+    cancelled: &Cell<bool>,
+) -> Option<Vec<Option<String>>> {
+    let mut uris: Vec<Option<String>> = tracks.iter().map(|t| t.spotify_uri()).collect();
+
+    // Files can be edited by hand or come from anywhere, so the URIs have to be checked
+    let ids = uris
+        .iter()
+        .flatten()
+        .map(|uri| uri.trim_start_matches("spotify:track:").to_string())
+        .collect::<Vec<_>>();
+    let existing = match api.get_tracks(ids).await {
+        Ok(songs) => songs.into_iter().map(|s| s.uri).collect::<HashSet<_>>(),
+        Err(e) => {
+            warn!("Could not check imported tracks: {e}");
+            HashSet::new()
+        }
+    };
+    if cancelled.get() {
+        return None;
+    }
+
+    for (uri, track) in uris.iter_mut().zip(tracks.iter()) {
+        if uri.as_ref().is_some_and(|uri| existing.contains(uri)) {
+            continue;
+        }
+        *uri = None;
+        let Some(query) = track.search_query() else {
+            continue;
+        };
+        match api.search_tracks(&query, 1).await {
+            Ok(songs) => *uri = songs.into_iter().next().map(|s| s.uri),
+            Err(e) => warn!("Could not search for imported track: {e}"),
+        }
+        if cancelled.get() {
+            return None;
+        }
+    }
+    Some(uris)
+}
+
+// Exports playlists to files, and creates new ones from files
+pub struct PlaylistTransfer {
+    app_model: Rc<AppModel>,
+    dispatcher: Box<dyn ActionDispatcher>,
+    window: gtk::Window,
+    toast_overlay: libadwaita::ToastOverlay,
+    worker: Worker,
+    importing: Rc<Cell<bool>>,
+}
+
+impl PlaylistTransfer {
+    pub fn new(
+        app_model: Rc<AppModel>,
+        dispatcher: Box<dyn ActionDispatcher>,
+        window: gtk::Window,
+        toast_overlay: libadwaita::ToastOverlay,
+        worker: Worker,
+    ) -> Self {
+        Self {
+            app_model,
+            dispatcher,
+            window,
+            toast_overlay,
+            worker,
+            importing: Rc::new(Cell::new(false)),
+        }
+    }
+
+    fn export(&self, id: &str) {
+        let api = self.app_model.get_spotify();
+        let dispatcher = self.dispatcher.box_clone();
+        let window = self.window.clone();
+        let id = id.to_owned();
+        self.worker.send_local_task(async move {
+            // We need the name before asking where to save it
+            let result = match api.get_playlist(&id).await {
+                Ok(playlist) => {
+                    let file_dialog = gtk::FileDialog::builder()
+                        .initial_name(format!("{}.m3u8", playlist.title.replace('/', "-")))
+                        .modal(true)
+                        .build();
+                    file_dialog.set_filters(Some(&file_filters()));
+                    // Dismissed
+                    let Ok(picked) = file_dialog.save_future(Some(&window)).await else {
+                        return;
+                    };
+                    let format =
+                        PlaylistFileFormat::for_path(&picked.basename().unwrap_or_default());
+                    match fetch_playlist_file(api, playlist).await {
+                        Ok(file) => picked
+                            .replace_contents_future(
+                                file.serialize(format),
+                                None,
+                                false,
+                                gio::FileCreateFlags::REPLACE_DESTINATION,
+                            )
+                            .await
+                            .map(|_| ())
+                            .map_err(|(_, e)| e.to_string()),
+                        Err(e) => Err(e.to_string()),
+                    }
+                }
+                Err(e) => Err(e.to_string()),
+            };
+            let message = match result {
+                // translators: Shown after saving a playlist to a file
+                Ok(_) => gettext("Playlist exported"),
+                Err(e) => {
+                    warn!("Could not export playlist: {e}");
+                    // translators: Shown when a playlist could not be saved to a file
+                    gettext("Could not export playlist")
+                }
+            };
+            dispatcher.dispatch(AppAction::ShowNotification(message));
+        });
+    }
+
+    fn import(&self) {
+        // One at a time is plenty
+        if self.importing.get() {
+            return;
+        }
+        let Some(user_id) = self.app_model.get_state().logged_user.user.clone() else {
+            return;
+        };
+        let api = self.app_model.get_spotify();
+        let dispatcher = self.dispatcher.box_clone();
+        let window = self.window.clone();
+        let toast_overlay = self.toast_overlay.clone();
+        let importing = Rc::clone(&self.importing);
+        self.worker.send_local_task(async move {
+            let file_dialog = gtk::FileDialog::builder().modal(true).build();
+            file_dialog.set_filters(Some(&file_filters()));
+            // Dismissed
+            let Ok(picked) = file_dialog.open_future(Some(&window)).await else {
+                return;
+            };
+
+            let fallback_name = picked
+                .basename()
+                .and_then(|name| Some(name.file_stem()?.to_string_lossy().into_owned()))
+                .unwrap_or_default();
+            let file = picked
+                .load_contents_future()
+                .await
+                .ok()
+                .and_then(|(content, _)| {
+                    PlaylistFile::parse(&String::from_utf8_lossy(&content), &fallback_name)
+                });
+            let Some(file) = file else {
+                dispatcher.dispatch(AppAction::ShowNotification(gettext(
+                    // translators: Shown when the file picked to import a playlist is not one we understand
+                    "Could not read playlist file",
+                )));
+                return;
+            };
+
+            importing.set(true);
+            let cancelled = Rc::new(Cell::new(false));
+            let toast = libadwaita::Toast::builder()
+                .title(labels::importing_tracks_label(0, file.tracks.len()))
+                .timeout(0)
+                // translators: This is a button in the notification shown while a playlist is being imported
+                .button_label(gettext("Cancel"))
+                .build();
+            toast.connect_button_clicked(clone!(
+                #[strong]
+                cancelled,
+                move |_| cancelled.set(true)
+            ));
+            toast_overlay.add_toast(toast.clone());
+
+            let result = Self::import_file(api, &user_id, file, &toast, &cancelled).await;
+            toast.dismiss();
+            importing.set(false);
+
+            let actions = match result {
+                Ok(Some((playlist, unmatched))) => {
+                    let mut actions = vec![AppAction::CreatePlaylist(playlist)];
+                    if unmatched > 0 {
+                        actions.push(AppAction::ShowNotification(
+                            labels::n_tracks_unmatched_label(unmatched),
+                        ));
+                    }
+                    actions
+                }
+                // translators: Shown when importing a playlist was cancelled
+                Ok(None) => vec![AppAction::ShowNotification(gettext("Import cancelled"))],
+                Err(e) => {
+                    warn!("Could not import playlist: {e}");
+                    vec![AppAction::ShowNotification(gettext(
+                        // translators: Shown when a playlist could not be created from a file
+                        "Could not import playlist",
+                    ))]
+                }
+            };
+            dispatcher.dispatch_many(actions);
+        });
+    }
+
+    // Returns the new playlist and how many tracks we couldn't find, or None if cancelled
+    async fn import_file(
+        api: Api,
+        user_id: &str,
+        file: PlaylistFile,
+        toast: &libadwaita::Toast,
+        cancelled: &Cell<bool>,
+    ) -> SpotifyResult<Option<(PlaylistDescription, usize)>> {
+        let total = file.tracks.len();
+        let mut uris = Vec::with_capacity(total);
+        for chunk in file.tracks.chunks(CHECK_BATCH_SIZE) {
+            let Some(resolved) = resolve_tracks(&api, chunk, cancelled).await else {
+                return Ok(None);
+            };
+            uris.extend(resolved);
+            toast.set_title(&labels::importing_tracks_label(uris.len(), total));
+        }
+        let uris = uris.into_iter().flatten().collect::<Vec<_>>();
+        let unmatched = total - uris.len();
+
+        if cancelled.get() {
+            return Ok(None);
+        }
+        let playlist = api.create_new_playlist(&file.name, user_id).await?;
+        for chunk in uris.chunks(ADD_BATCH_SIZE) {
+            // Better no playlist at all than a half-imported one
+            if cancelled.get() {
+                api.unfollow_playlist(&playlist.id).await?;
+                return Ok(None);
+            }
+            api.add_to_playlist(&playlist.id, chunk.to_vec()).await?;
+        }
+        Ok(Some((playlist, unmatched)))
+    }
+}
+
+impl EventListener for PlaylistTransfer {
+    fn on_event(&mut self, event: &AppEvent) {
+        match event {
+            AppEvent::PlaylistExportRequested(id) => self.export(id),
+            AppEvent::PlaylistImportRequested => self.import(),
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,4 @@
+mod component;
+mod playlist_file;
+
+pub use component::*;
@@ -0,0 +1,291 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::app::models::SongDescription;
+
+// What we write to (and read from) exported playlist files
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct PlaylistFileTrack {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uri: Option<String>,
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub artists: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub album: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u32>,
+}
+
+impl From<&SongDescription> for PlaylistFileTrack {
+    fn from(song: &SongDescription) -> Self {
+        Self {
+            id: Some(song.id.clone()),
+            uri: Some(song.uri.clone()),
+            title: song.title.clone(),
+            artists: song.artists.iter().map(|a| a.name.clone()).collect(),
+            album: Some(song.album.name.clone()),
+            duration_ms: Some(song.duration),
+        }
+    }
+}
+
+// Accepts spotify:track:xxx URIs and open.spotify.com links
+fn parse_track_uri(s: &str) -> Option<String> {
+    let s = s.trim();
+    let id = s.strip_prefix("spotify:track:").or_else(|| {
+        s.strip_prefix("https://open.spotify.com/track/")
+            .and_then(|id| id.split(['?', '#']).next())
+    })?;
+    Some(format!("spotify:track:{id}")).filter(|_| !id.is_empty())
+}
+
+impl PlaylistFileTrack {
+    pub fn spotify_uri(&self) -> Option<String> {
+        self.uri
+            .as_deref()
+            .and_then(parse_track_uri)
+            .or_else(|| Some(format!("spotify:track:{}", self.id.as_ref()?)))
+    }
+
+    // When all we have is text, this is what we'll search for
+    pub fn search_query(&self) -> Option<String> {
+        if self.title.trim().is_empty() {
+            return None;
+        }
+        let mut query = self.artists.join(" ");
+        query.push(' ');
+        query.push_str(&self.title);
+        Some(query.trim().to_string())
+    }
+
+    fn extinf(&self) -> String {
+        let duration = self.duration_ms.map(|d| (d / 1000) as i64).unwrap_or(-1);
+        if self.artists.is_empty() {
+            format!("#EXTINF:{duration},{}", self.title)
+        } else {
+            format!(
+                "#EXTINF:{duration},{} - {}",
+                self.artists.join(", "),
+                self.title
+            )
+        }
+    }
+
+    fn from_extinf(extinf: &str) -> Self {
+        let (duration, info) = extinf.split_once(',').unwrap_or(("-1", extinf));
+        let duration_ms = duration
+            .trim()
+            .parse::<u32>()
+            .ok()
+            .map(|seconds| seconds * 1000);
+        let (artists, title) = match info.split_once(" - ") {
+            Some((artists, title)) => (
+                artists.split(", ").map(|a| a.trim().to_string()).collect(),
+                title,
+            ),
+            None => (vec![], info),
+        };
+        Self {
+            title: title.trim().to_string(),
+            artists,
+            duration_ms,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlaylistFileFormat {
+    M3u,
+    Json,
+}
+
+impl PlaylistFileFormat {
+    // M3U8 unless the file is obviously meant to be JSON
+    pub fn for_path(path: &Path) -> Self {
+        let is_json = path
+            .extension()
+            .map(|ext| ext.eq_ignore_ascii_case("json"))
+            .unwrap_or(false);
+        if is_json {
+            Self::Json
+        } else {
+            Self::M3u
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct PlaylistFile {
+    pub name: String,
+    pub tracks: Vec<PlaylistFileTrack>,
+}
+
+impl PlaylistFile {
+    pub fn serialize(&self, format: PlaylistFileFormat) -> String {
+        match format {
+            PlaylistFileFormat::Json => serde_json::to_string_pretty(self).unwrap_or_default(),
+            PlaylistFileFormat::M3u => {
+                let mut lines = vec!["#EXTM3U".to_string(), format!("#PLAYLIST:{}", self.name)];
+                for track in self.tracks.iter() {
+                    lines.push(track.extinf());
+                    lines.push(track.spotify_uri().unwrap_or_default());
+                }
+                lines.push(String::new());
+                lines.join("\n")
+            }
+        }
+    }
+
+    // Either format is accepted, whatever the file is named
+    pub fn parse(content: &str, fallback_name: &str) -> Option<Self> {
+        let content = content.trim_start_matches('\u{feff}').trim();
+        if content.starts_with('{') {
+            serde_json::from_str(content).ok()
+        } else {
+            Self::parse_m3u(content, fallback_name)
+        }
+    }
+
+    fn parse_m3u(content: &str, fallback_name: &str) -> Option<Self> {
+        let mut name = fallback_name.to_string();
+        let mut tracks = vec![];
+        let mut extinf: Option<PlaylistFileTrack> = None;
+
+        for line in content.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            if let Some(info) = line.strip_prefix("#EXTINF:") {
+                extinf = Some(PlaylistFileTrack::from_extinf(info));
+            } else if let Some(playlist_name) = line.strip_prefix("#PLAYLIST:") {
+                name = playlist_name.trim().to_string();
+            } else if !line.starts_with('#') {
+                let mut track = extinf.take().unwrap_or_default();
+                track.uri = parse_track_uri(line);
+                // A local file (from another player, maybe on another OS), its name is better than nothing
+                if track.uri.is_none() && track.title.is_empty() {
+                    let file_name = line.rsplit(['/', '\\']).next().unwrap_or(line);
+                    let stem = file_name.rsplit_once('.').map(|(stem, _)| stem);
+                    track.title = stem.unwrap_or(file_name).to_string();
+                }
+                tracks.push(track);
+            }
+        }
+
+        Some(Self { name, tracks }).filter(|p| !p.tracks.is_empty())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn playlist() -> PlaylistFile {
+        PlaylistFile {
+            name: "My playlist".to_string(),
+            tracks: vec![
+                PlaylistFileTrack {
+                    id: Some("abc".to_string()),
+                    uri: Some("spotify:track:abc".to_string()),
+                    title: "Song".to_string(),
+                    artists: vec!["Artist 1".to_string(), "Artist 2".to_string()],
+                    album: Some("Album".to_string()),
+                    duration_ms: Some(185000),
+                },
+                PlaylistFileTrack {
+                    id: Some("def".to_string()),
+                    uri: Some("spotify:track:def".to_string()),
+                    title: "Other - Song".to_string(),
+                    artists: vec!["Someone".to_string()],
+                    album: None,
+                    duration_ms: Some(60000),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_m3u_export() {
+        assert_eq!(
+            playlist().serialize(PlaylistFileFormat::M3u),
+            "#EXTM3U\n\
+            #PLAYLIST:My playlist\n\
+            #EXTINF:185,Artist 1, Artist 2 - Song\n\
+            spotify:track:abc\n\
+            #EXTINF:60,Someone - Other - Song\n\
+            spotify:track:def\n"
+        );
+    }
+
+    #[test]
+    fn test_m3u_round_trip() {
+        let content = playlist().serialize(PlaylistFileFormat::M3u);
+        let parsed = PlaylistFile::parse(&content, "fallback").unwrap();
+
+        assert_eq!(parsed.name, "My playlist");
+        let uris: Vec<_> = parsed.tracks.iter().map(|t| t.spotify_uri()).collect();
+        assert_eq!(
+            uris,
+            vec![
+                Some("spotify:track:abc".to_string()),
+                Some("spotify:track:def".to_string())
+            ]
+        );
+        assert_eq!(parsed.tracks[0].artists, vec!["Artist 1", "Artist 2"]);
+        assert_eq!(parsed.tracks[1].title, "Other - Song");
+        assert_eq!(parsed.tracks[1].duration_ms, Some(60000));
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let content = playlist().serialize(PlaylistFileFormat::Json);
+        assert_eq!(PlaylistFile::parse(&content, "fallback"), Some(playlist()));
+    }
+
+    #[test]
+    fn test_m3u_from_elsewhere() {
+        let content = "\u{feff}#EXTM3U\r\n\
+            #EXTINF:123,Artist - Title\r\n\
+            /home/me/Music/whatever.mp3\r\n\
+            \r\n\
+            C:\\Music\\Another Song.flac\r\n\
+            https://open.spotify.com/track/xyz?si=123\r\n";
+        let parsed = PlaylistFile::parse(content, "fallback").unwrap();
+
+        assert_eq!(parsed.name, "fallback");
+        assert_eq!(parsed.tracks.len(), 3);
+        assert_eq!(parsed.tracks[0].spotify_uri(), None);
+        assert_eq!(
+            parsed.tracks[0].search_query(),
+            Some("Artist Title".to_string())
+        );
+        assert_eq!(parsed.tracks[1].title, "Another Song");
+        assert_eq!(
+            parsed.tracks[2].spotify_uri(),
+            Some("spotify:track:xyz".to_string())
+        );
+    }
+
+    #[test]
+    fn test_json_by_id_only() {
+        let content = r#"{"name": "Ids", "tracks": [{"id": "abc"}, {"title": "Song"}]}"#;
+        let parsed = PlaylistFile::parse(content, "fallback").unwrap();
+
+        assert_eq!(
+            parsed.tracks[0].spotify_uri(),
+            Some("spotify:track:abc".to_string())
+        );
+        assert_eq!(parsed.tracks[1].spotify_uri(), None);
+        assert_eq!(parsed.tracks[1].search_query(), Some("Song".to_string()));
+        assert_eq!(parsed.tracks[0].search_query(), None);
+    }
+
+    #[test]
+    fn test_empty_file() {
+        assert_eq!(PlaylistFile::parse("#EXTM3U\n", "fallback"), None);
+        assert_eq!(PlaylistFile::parse("{ not json", "fallback"), None);
+    }
+}
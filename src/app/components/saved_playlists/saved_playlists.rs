@@ -145,7 +145,7 @@ impl EventListener for SavedPlaylists {
     fn on_event(&mut self, event: &AppEvent) {
         match event {
             AppEvent::Started => {
-                let _ = self.model.refresh_saved_playlists();
+                let _ = self.model.load_cached_saved_playlists();
                 self.bind_flowbox();
             }
             AppEvent::LoginEvent(LoginEvent::LoginCompleted) => {
@@ -27,6 +27,21 @@ impl SavedPlaylistsModel {
         Some(Ref::map(self.state()?, |s| &s.playlists))
     }
 
+    // Renders whatever we got last time, without waiting for the network (or even for a token)
+    pub fn load_cached_saved_playlists(&self) -> Option<()> {
+        let api = self.app_model.get_spotify();
+        let batch_size = self.state()?.next_playlists_page.batch_size;
+
+        self.dispatcher.dispatch_async(Box::pin(async move {
+            api.get_cached_saved_playlists(0, batch_size)
+                .await
+                .ok()
+                .map(|playlists| BrowserAction::SetPlaylistsContent(playlists).into())
+        }));
+
+        Some(())
+    }
+
     pub fn refresh_saved_playlists(&self) -> Option<()> {
         let api = self.app_model.get_spotify();
         let batch_size = self.state()?.next_playlists_page.batch_size;
@@ -0,0 +1,6 @@
+#[allow(clippy::module_inception)]
+mod saved_shows;
+mod saved_shows_model;
+
+pub use saved_shows::*;
+pub use saved_shows_model::*;
@@ -0,0 +1,167 @@
+use gtk::prelude::*;
+use gtk::subclass::prelude::*;
+use gtk::CompositeTemplate;
+use std::rc::Rc;
+
+use super::SavedShowsModel;
+use crate::app::components::utils::wrap_flowbox_item;
+use crate::app::components::{AlbumWidget, Component, EventListener};
+use crate::app::dispatch::Worker;
+use crate::app::models::AlbumModel;
+use crate::app::state::LoginEvent;
+use crate::app::{AppEvent, BrowserEvent, ListStore};
+
+mod imp {
+
+    use super::*;
+
+    #[derive(Debug, Default, CompositeTemplate)]
+    #[template(resource = "/dev/diegovsky/Riff/components/saved_shows.ui")]
+    pub struct SavedShowsWidget {
+        #[template_child]
+        pub scrolled_window: TemplateChild<gtk::ScrolledWindow>,
+
+        #[template_child]
+        pub flowbox: TemplateChild<gtk::FlowBox>,
+        #[template_child]
+        pub status_page: TemplateChild<libadwaita::StatusPage>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for SavedShowsWidget {
+        const NAME: &'static str = "SavedShowsWidget";
+        type Type = super::SavedShowsWidget;
+        type ParentType = gtk::Box;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for SavedShowsWidget {}
+    impl WidgetImpl for SavedShowsWidget {}
+    impl BoxImpl for SavedShowsWidget {}
+}
+
+glib::wrapper! {
+    pub struct SavedShowsWidget(ObjectSubclass<imp::SavedShowsWidget>) @extends gtk::Widget, gtk::Box;
+}
+
+impl Default for SavedShowsWidget {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SavedShowsWidget {
+    pub fn new() -> Self {
+        glib::Object::new()
+    }
+
+    fn connect_bottom_edge<F>(&self, f: F)
+    where
+        F: Fn() + 'static,
+    {
+        self.imp()
+            .scrolled_window
+            .connect_edge_reached(move |_, pos| {
+                if let gtk::PositionType::Bottom = pos {
+                    f()
+                }
+            });
+    }
+
+    // Shows look just like albums (art, title and publisher)
+    fn bind_shows<F>(&self, worker: Worker, store: &ListStore<AlbumModel>, on_show_pressed: F)
+    where
+        F: Fn(String) + Clone + 'static,
+    {
+        let store_clone = store.clone();
+        self.imp()
+            .flowbox
+            .bind_model(Some(store.inner()), move |item| {
+                wrap_flowbox_item(item, |album_model: &AlbumModel| {
+                    AlbumWidget::for_model(album_model, worker.clone())
+                })
+            });
+        self.imp().flowbox.connect_child_activated(move |_, child| {
+            let album_model = store_clone.get(child.index() as u32);
+            on_show_pressed(album_model.uri());
+        });
+    }
+
+    pub fn get_status_page(&self) -> &libadwaita::StatusPage {
+        &self.imp().status_page
+    }
+}
+
+pub struct SavedShows {
+    widget: SavedShowsWidget,
+    worker: Worker,
+    model: Rc<SavedShowsModel>,
+}
+
+impl SavedShows {
+    pub fn new(worker: Worker, model: SavedShowsModel) -> Self {
+        let model = Rc::new(model);
+
+        let widget = SavedShowsWidget::new();
+
+        widget.connect_bottom_edge(clone!(
+            #[weak]
+            model,
+            move || {
+                model.load_more_shows();
+            }
+        ));
+
+        Self {
+            widget,
+            worker,
+            model,
+        }
+    }
+
+    fn bind_flowbox(&self) {
+        self.widget.bind_shows(
+            self.worker.clone(),
+            &self.model.get_list_store().unwrap(),
+            clone!(
+                #[weak(rename_to = model)]
+                self.model,
+                move |id| {
+                    model.open_show(id);
+                }
+            ),
+        );
+    }
+}
+
+impl EventListener for SavedShows {
+    fn on_event(&mut self, event: &AppEvent) {
+        match event {
+            AppEvent::Started => {
+                self.bind_flowbox();
+            }
+            AppEvent::LoginEvent(LoginEvent::LoginCompleted) => {
+                let _ = self.model.refresh_saved_shows();
+            }
+            AppEvent::BrowserEvent(BrowserEvent::SavedShowsUpdated) => {
+                self.widget
+                    .get_status_page()
+                    .set_visible(!self.model.has_shows());
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Component for SavedShows {
+    fn get_root_widget(&self) -> &gtk::Widget {
+        self.widget.as_ref()
+    }
+}
@@ -3,7 +3,7 @@ use gio::SimpleActionGroup;
 use std::ops::Deref;
 use std::rc::Rc;
 
-use crate::app::components::{labels, PlaylistModel};
+use crate::app::components::{labels, share, PlaylistModel};
 use crate::app::models::*;
 use crate::app::state::SelectionContext;
 use crate::app::state::{PlaybackAction, SelectionAction, SelectionState};
@@ -70,24 +70,42 @@ impl PlaylistModel for SavedTracksModel {
         !self.app_model.get_state().playback.is_playing()
     }
 
+    fn is_offline(&self) -> bool {
+        self.app_model.get_state().logged_user.offline
+    }
+
     fn current_song_id(&self) -> Option<String> {
         self.app_model.get_state().playback.current_song_id()
     }
 
     fn play_song_at(&self, pos: usize, id: &str) {
-        let source = SongsSource::SavedTracks;
-        let batch = self.song_list_model().song_batch_for(pos);
-        if let Some(batch) = batch {
-            self.dispatcher
-                .dispatch(PlaybackAction::LoadPagedSongs(source, batch).into());
-            self.dispatcher
-                .dispatch(PlaybackAction::Load(id.to_string()).into());
+        let songs = self.song_list_model();
+        let Some(batch) = songs.song_batch_for(pos) else {
+            return;
+        };
+        // The batch we're playing from goes first (it sets the source), then everything else that's loaded.
+        // Pages loaded later on are appended as they come in (see app_state.rs)
+        let mut actions: Vec<AppAction> =
+            vec![PlaybackAction::LoadPagedSongs(SongsSource::SavedTracks, batch).into()];
+        let mut next = Some(Batch::first_of_size(50));
+        while let Some(batch) = next.and_then(|b| songs.song_batch_for(b.offset)) {
+            next = batch.batch.next();
+            if !batch.songs.iter().any(|s| s.id == id) {
+                actions
+                    .push(PlaybackAction::LoadPagedSongs(SongsSource::SavedTracks, batch).into());
+            }
         }
+        actions.push(PlaybackAction::Load(id.to_string()).into());
+        self.dispatcher.dispatch_many(actions);
     }
     fn autoscroll_to_playing(&self) -> bool {
         true
     }
 
+    fn is_song_saved(&self, id: &str) -> Option<bool> {
+        Some(self.app_model.get_state().browser.is_track_saved(id))
+    }
+
     fn actions_for(&self, id: &str) -> Option<gio::ActionGroup> {
         let song = self.song_list_model().get(id)?;
         let song = song.description();
@@ -98,7 +116,15 @@ impl PlaylistModel for SavedTracksModel {
             group.add_action(&view_artist);
         }
         group.add_action(&song.make_album_action(self.dispatcher.box_clone(), None));
-        group.add_action(&song.make_link_action(None));
+        group.add_action(&song.make_link_action(self.dispatcher.box_clone(), None));
+        group.add_action(&song.make_open_in_browser_action(None));
+        group.add_action(&song.make_toggle_saved_action(
+            self.app_model.clone(),
+            self.dispatcher.box_clone(),
+            None,
+        ));
+        group.add_action(&song.make_play_next_action(self.dispatcher.box_clone(), None));
+        group.add_action(&song.make_queue_action(self.dispatcher.box_clone(), None));
 
         Some(group.upcast())
     }
@@ -116,7 +142,9 @@ impl PlaylistModel for SavedTracksModel {
             );
         }
 
-        menu.append(Some(&*labels::COPY_LINK), Some("song.copy_link"));
+        share::append_share_items(&menu, "song");
+        menu.append(Some(&*labels::PLAY_NEXT), Some("song.play_next"));
+        menu.append(Some(&*labels::ADD_TO_QUEUE), Some("song.queue"));
 
         Some(menu.upcast())
     }
@@ -3,11 +3,12 @@ use gtk::subclass::prelude::*;
 use gtk::CompositeTemplate;
 use std::rc::Rc;
 
-use crate::app::components::utils::{wrap_flowbox_item, Debouncer};
-use crate::app::components::{AlbumWidget, ArtistWidget, Component, EventListener};
+use crate::app::components::utils::wrap_flowbox_item;
+use crate::app::components::{AlbumWidget, ArtistWidget, Component, EventListener, Playlist};
 use crate::app::dispatch::Worker;
-use crate::app::models::{AlbumModel, ArtistModel};
+use crate::app::models::{AlbumModel, ArtistModel, SearchCategory};
 use crate::app::state::{AppEvent, BrowserEvent};
+use crate::app::ListStore;
 
 use super::SearchResultsModel;
 mod imp {
@@ -33,10 +34,28 @@ mod imp {
         pub search_results: TemplateChild<gtk::Widget>,
 
         #[template_child]
-        pub albums_results: TemplateChild<gtk::FlowBox>,
+        pub tracks_window: TemplateChild<gtk::ScrolledWindow>,
+
+        #[template_child]
+        pub tracks_results: TemplateChild<gtk::ListView>,
+
+        #[template_child]
+        pub albums_window: TemplateChild<gtk::ScrolledWindow>,
+
+        #[template_child]
+        pub albums_results: TemplateChild<gtk::GridView>,
+
+        #[template_child]
+        pub artists_window: TemplateChild<gtk::ScrolledWindow>,
 
         #[template_child]
         pub artist_results: TemplateChild<gtk::FlowBox>,
+
+        #[template_child]
+        pub playlists_window: TemplateChild<gtk::ScrolledWindow>,
+
+        #[template_child]
+        pub playlists_results: TemplateChild<gtk::GridView>,
     }
 
     #[glib::object_subclass]
@@ -105,38 +124,90 @@ impl SearchResultsWidget {
         ));
     }
 
-    fn bind_albums_results<F>(&self, worker: Worker, store: &gio::ListStore, on_album_pressed: F)
+    pub fn connect_search_activated<F>(&self, f: F)
     where
-        F: Fn(String) + Clone + 'static,
+        F: Fn(String) + 'static,
     {
-        let store_clone = store.clone();
-        self.imp()
-            .albums_results
-            .bind_model(Some(store), move |item| {
-                wrap_flowbox_item(item, |album_model: &AlbumModel| {
-                    AlbumWidget::for_model(album_model, worker.clone())
-                })
-            });
-        self.imp()
-            .albums_results
-            .connect_child_activated(move |_, child| {
-                let index = child.index() as u32;
-                if let Some(item) = store_clone.item(index) {
-                    if let Some(album_model) = item.downcast_ref::<AlbumModel>() {
-                        on_album_pressed(album_model.uri());
-                    }
+        self.imp().search_entry.connect_activate(move |s| {
+            let query = s.text();
+            if !query.is_empty() {
+                f(query.to_string());
+            }
+        });
+    }
+
+    // Every tab scrolls on its own, and loads more of its own results
+    fn connect_bottom_edge<F>(&self, f: F)
+    where
+        F: Fn(SearchCategory) + Clone + 'static,
+    {
+        let imp = self.imp();
+        for (window, category) in [
+            (&imp.tracks_window, SearchCategory::Tracks),
+            (&imp.albums_window, SearchCategory::Albums),
+            (&imp.artists_window, SearchCategory::Artists),
+            (&imp.playlists_window, SearchCategory::Playlists),
+        ] {
+            let f = f.clone();
+            window.connect_edge_reached(move |_, pos| {
+                if let gtk::PositionType::Bottom = pos {
+                    f(category)
                 }
             });
+        }
     }
 
-    fn bind_artists_results<F>(&self, worker: Worker, store: &gio::ListStore, on_artist_pressed: F)
+    fn tracks_widget(&self) -> &gtk::ListView {
+        self.imp().tracks_results.as_ref()
+    }
+
+    fn bind_grid<F>(
+        gridview: &gtk::GridView,
+        worker: Worker,
+        store: &ListStore<AlbumModel>,
+        on_pressed: F,
+    ) where
+        F: Fn(String) + 'static,
+    {
+        let store_clone = store.clone();
+        gridview.set_factory(Some(&AlbumWidget::list_item_factory(worker)));
+        gridview.set_model(Some(&gtk::NoSelection::new(Some(store.inner().clone()))));
+        gridview.connect_activate(move |_, position| {
+            let album_model = store_clone.get(position);
+            on_pressed(album_model.uri());
+        });
+    }
+
+    fn bind_albums_results<F>(&self, worker: Worker, store: &ListStore<AlbumModel>, on_pressed: F)
     where
-        F: Fn(String) + Clone + 'static,
+        F: Fn(String) + 'static,
+    {
+        Self::bind_grid(&self.imp().albums_results, worker, store, on_pressed);
+    }
+
+    fn bind_playlists_results<F>(
+        &self,
+        worker: Worker,
+        store: &ListStore<AlbumModel>,
+        on_pressed: F,
+    ) where
+        F: Fn(String) + 'static,
+    {
+        Self::bind_grid(&self.imp().playlists_results, worker, store, on_pressed);
+    }
+
+    fn bind_artists_results<F>(
+        &self,
+        worker: Worker,
+        store: &ListStore<ArtistModel>,
+        on_artist_pressed: F,
+    ) where
+        F: Fn(String) + 'static,
     {
         let store_clone = store.clone();
         self.imp()
             .artist_results
-            .bind_model(Some(store), move |item| {
+            .bind_model(Some(store.inner()), move |item| {
                 wrap_flowbox_item(item, |artist_model: &ArtistModel| {
                     ArtistWidget::for_model(artist_model, worker.clone())
                 })
@@ -144,12 +215,8 @@ impl SearchResultsWidget {
         self.imp()
             .artist_results
             .connect_child_activated(move |_, child| {
-                let index = child.index() as u32;
-                if let Some(item) = store_clone.item(index) {
-                    if let Some(artist_model) = item.downcast_ref::<ArtistModel>() {
-                        on_artist_pressed(artist_model.id());
-                    }
-                }
+                let artist_model = store_clone.get(child.index() as u32);
+                on_artist_pressed(artist_model.id());
             });
     }
 }
@@ -157,9 +224,7 @@ impl SearchResultsWidget {
 pub struct SearchResults {
     widget: SearchResultsWidget,
     model: Rc<SearchResultsModel>,
-    album_results_model: gio::ListStore,
-    artist_results_model: gio::ListStore,
-    debouncer: Debouncer,
+    children: Vec<Box<dyn EventListener>>,
 }
 
 impl SearchResults {
@@ -167,9 +232,6 @@ impl SearchResults {
         let model = Rc::new(model);
         let widget = SearchResultsWidget::new();
 
-        let album_results_model = gio::ListStore::new::<AlbumModel>();
-        let artist_results_model = gio::ListStore::new::<ArtistModel>();
-
         widget.connect_go_back(clone!(
             #[weak]
             model,
@@ -186,73 +248,71 @@ impl SearchResults {
             }
         ));
 
-        widget.bind_albums_results(
-            worker.clone(),
-            &album_results_model,
-            clone!(
-                #[weak]
-                model,
-                move |uri| {
-                    model.open_album(uri);
-                }
-            ),
-        );
-
-        widget.bind_artists_results(
-            worker,
-            &artist_results_model,
-            clone!(
-                #[weak]
-                model,
-                move |id| {
-                    model.open_artist(id);
-                }
-            ),
-        );
+        widget.connect_search_activated(clone!(
+            #[weak]
+            model,
+            move |q| {
+                model.search_now(q);
+            }
+        ));
 
-        Self {
-            widget,
+        widget.connect_bottom_edge(clone!(
+            #[weak]
             model,
-            album_results_model,
-            artist_results_model,
-            debouncer: Debouncer::new(),
+            move |category| {
+                model.load_more(category);
+            }
+        ));
+
+        if let Some(store) = model.get_albums_store() {
+            widget.bind_albums_results(
+                worker.clone(),
+                &store,
+                clone!(
+                    #[weak]
+                    model,
+                    move |uri| {
+                        model.open_album(uri);
+                    }
+                ),
+            );
         }
-    }
 
-    fn update_results(&self) {
-        if let Some(results) = self.model.get_album_results() {
-            self.album_results_model.remove_all();
-            for album in results.iter() {
-                self.album_results_model.append(&AlbumModel::new(
-                    &album.artists_name(),
-                    &album.title,
-                    album.year(),
-                    album.art.as_ref(),
-                    &album.id,
-                ));
-            }
+        if let Some(store) = model.get_artists_store() {
+            widget.bind_artists_results(
+                worker.clone(),
+                &store,
+                clone!(
+                    #[weak]
+                    model,
+                    move |id| {
+                        model.open_artist(id);
+                    }
+                ),
+            );
         }
-        if let Some(results) = self.model.get_artist_results() {
-            self.artist_results_model.remove_all();
-            for artist in results.iter() {
-                self.artist_results_model.append(&ArtistModel::new(
-                    &artist.name,
-                    &artist.photo,
-                    &artist.id,
-                ));
-            }
+
+        if let Some(store) = model.get_playlists_store() {
+            widget.bind_playlists_results(
+                worker.clone(),
+                &store,
+                clone!(
+                    #[weak]
+                    model,
+                    move |uri| {
+                        model.open_playlist(uri);
+                    }
+                ),
+            );
         }
-    }
 
-    fn update_search_query(&self) {
-        self.debouncer.debounce(
-            600,
-            clone!(
-                #[weak(rename_to = model)]
-                self.model,
-                move || model.fetch_results()
-            ),
-        );
+        let playlist = Playlist::new(widget.tracks_widget().clone(), model.clone(), worker);
+
+        Self {
+            widget,
+            model,
+            children: vec![Box::new(playlist)],
+        }
     }
 }
 
@@ -260,19 +320,18 @@ impl Component for SearchResults {
     fn get_root_widget(&self) -> &gtk::Widget {
         self.widget.as_ref()
     }
+
+    fn get_children(&mut self) -> Option<&mut Vec<Box<dyn EventListener>>> {
+        Some(&mut self.children)
+    }
 }
 
 impl EventListener for SearchResults {
     fn on_event(&mut self, app_event: &AppEvent) {
-        match app_event {
-            AppEvent::BrowserEvent(BrowserEvent::SearchUpdated) => {
-                self.get_root_widget().grab_focus();
-                self.update_search_query();
-            }
-            AppEvent::BrowserEvent(BrowserEvent::SearchResultsUpdated) => {
-                self.update_results();
-            }
-            _ => {}
+        if let AppEvent::BrowserEvent(BrowserEvent::SearchUpdated) = app_event {
+            self.get_root_widget().grab_focus();
+            self.model.fetch_results();
         }
+        self.broadcast_event(app_event);
     }
 }
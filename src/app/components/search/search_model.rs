@@ -1,13 +1,30 @@
+use gio::prelude::*;
+use gio::SimpleActionGroup;
+use std::cell::Ref;
 use std::ops::Deref;
 use std::rc::Rc;
 
+use crate::app::components::utils::Debouncer;
+use crate::app::components::{check_saved_tracks, labels, share, PlaylistModel};
 use crate::app::dispatch::ActionDispatcher;
 use crate::app::models::*;
-use crate::app::state::{AppAction, AppModel, BrowserAction};
+use crate::app::state::{AppAction, AppModel, BrowserAction, PlaybackAction, SearchState};
+use crate::app::ListStore;
+
+const CATEGORIES: [SearchCategory; 4] = [
+    SearchCategory::Tracks,
+    SearchCategory::Albums,
+    SearchCategory::Artists,
+    SearchCategory::Playlists,
+];
+
+// How long to wait after the last keystroke before searching
+const SEARCH_DEBOUNCE_MS: u32 = 300;
 
 pub struct SearchResultsModel {
     app_model: Rc<AppModel>,
     dispatcher: Box<dyn ActionDispatcher>,
+    debouncer: Debouncer,
 }
 
 impl SearchResultsModel {
@@ -15,45 +32,78 @@ impl SearchResultsModel {
         Self {
             app_model,
             dispatcher,
+            debouncer: Debouncer::new(),
         }
     }
 
+    fn state(&self) -> Option<Ref<'_, SearchState>> {
+        self.app_model.map_state_opt(|s| s.browser.search_state())
+    }
+
     pub fn go_back(&self) {
         self.dispatcher
             .dispatch(BrowserAction::NavigationPop.into());
     }
 
     pub fn search(&self, query: String) {
+        let dispatcher = self.dispatcher.box_clone();
+        self.debouncer.debounce(SEARCH_DEBOUNCE_MS, move || {
+            dispatcher.dispatch(BrowserAction::Search(query.clone()).into());
+        });
+    }
+
+    // When pressing enter, no need to wait
+    pub fn search_now(&self, query: String) {
+        self.debouncer.cancel();
         self.dispatcher
             .dispatch(BrowserAction::Search(query).into());
     }
 
-    fn get_query(&self) -> Option<impl Deref<Target = String> + '_> {
-        self.app_model
-            .map_state_opt(|s| Some(&s.browser.search_state()?.query).filter(|s| !s.is_empty()))
+    // First page of every tab, so that switching tabs shows something right away
+    pub fn fetch_results(&self) {
+        for category in CATEGORIES {
+            self.load_more(category);
+        }
     }
 
-    pub fn fetch_results(&self) {
+    pub fn load_more(&self, category: SearchCategory) -> Option<()> {
         let api = self.app_model.get_spotify();
-        if let Some(query) = self.get_query() {
-            let query = query.to_owned();
-            self.dispatcher
-                .call_spotify_and_dispatch(move || async move {
-                    api.search(&query, 0, 5)
-                        .await
-                        .map(|results| BrowserAction::SetSearchResults(Box::new(results)).into())
-                });
-        }
+        let state = self.state()?;
+        let query = Some(state.query.clone()).filter(|q| !q.is_empty())?;
+        let generation = state.generation;
+        let next_page = state.next_page(category);
+        let offset = next_page.next_offset?;
+        let batch_size = next_page.batch_size;
+
+        // The generation goes along with the results, which are dropped if the query changed since
+        self.dispatcher
+            .call_spotify_and_dispatch(move || async move {
+                api.search(&query, category, offset, batch_size)
+                    .await
+                    .map(|results| {
+                        BrowserAction::AppendSearchResults(
+                            generation,
+                            category,
+                            offset,
+                            Box::new(results),
+                        )
+                        .into()
+                    })
+            });
+
+        Some(())
+    }
+
+    pub fn get_albums_store(&self) -> Option<impl Deref<Target = ListStore<AlbumModel>> + '_> {
+        Some(Ref::map(self.state()?, |s| &s.albums))
     }
 
-    pub fn get_album_results(&self) -> Option<impl Deref<Target = Vec<AlbumDescription>> + '_> {
-        self.app_model
-            .map_state_opt(|s| Some(&s.browser.search_state()?.album_results))
+    pub fn get_artists_store(&self) -> Option<impl Deref<Target = ListStore<ArtistModel>> + '_> {
+        Some(Ref::map(self.state()?, |s| &s.artists))
     }
 
-    pub fn get_artist_results(&self) -> Option<impl Deref<Target = Vec<ArtistSummary>> + '_> {
-        self.app_model
-            .map_state_opt(|s| Some(&s.browser.search_state()?.artist_results))
+    pub fn get_playlists_store(&self) -> Option<impl Deref<Target = ListStore<AlbumModel>> + '_> {
+        Some(Ref::map(self.state()?, |s| &s.playlists))
     }
 
     pub fn open_album(&self, id: String) {
@@ -63,4 +113,93 @@ impl SearchResultsModel {
     pub fn open_artist(&self, id: String) {
         self.dispatcher.dispatch(AppAction::ViewArtist(id));
     }
+
+    pub fn open_playlist(&self, id: String) {
+        self.dispatcher.dispatch(AppAction::ViewPlaylist(id));
+    }
+}
+
+impl PlaylistModel for SearchResultsModel {
+    fn song_list_model(&self) -> SongListModel {
+        self.state()
+            .expect("illegal attempt to read search_state")
+            .tracks
+            .clone()
+    }
+
+    fn is_paused(&self) -> bool {
+        !self.app_model.get_state().playback.is_playing()
+    }
+
+    fn is_offline(&self) -> bool {
+        self.app_model.get_state().logged_user.offline
+    }
+
+    fn current_song_id(&self) -> Option<String> {
+        self.app_model.get_state().playback.current_song_id()
+    }
+
+    // Whatever was loaded so far gets queued
+    fn play_song_at(&self, _pos: usize, id: &str) {
+        let tracks = self.song_list_model().collect();
+        self.dispatcher.dispatch_many(vec![
+            PlaybackAction::LoadSongs(tracks).into(),
+            PlaybackAction::Load(id.to_string()).into(),
+        ]);
+    }
+
+    fn autoscroll_to_playing(&self) -> bool {
+        false
+    }
+
+    fn is_song_saved(&self, id: &str) -> Option<bool> {
+        Some(self.app_model.get_state().browser.is_track_saved(id))
+    }
+
+    fn check_saved_songs(&self, ids: Vec<String>) {
+        check_saved_tracks(&self.app_model, self.dispatcher.as_ref(), ids);
+    }
+
+    fn actions_for(&self, id: &str) -> Option<gio::ActionGroup> {
+        let song = self.song_list_model().get(id)?;
+        let song = song.description();
+
+        let group = SimpleActionGroup::new();
+
+        for view_artist in song.make_artist_actions(self.dispatcher.box_clone(), None) {
+            group.add_action(&view_artist);
+        }
+        group.add_action(&song.make_album_action(self.dispatcher.box_clone(), None));
+        group.add_action(&song.make_link_action(self.dispatcher.box_clone(), None));
+        group.add_action(&song.make_open_in_browser_action(None));
+        group.add_action(&song.make_toggle_saved_action(
+            self.app_model.clone(),
+            self.dispatcher.box_clone(),
+            None,
+        ));
+        group.add_action(&song.make_play_next_action(self.dispatcher.box_clone(), None));
+        group.add_action(&song.make_queue_action(self.dispatcher.box_clone(), None));
+
+        Some(group.upcast())
+    }
+
+    fn menu_for(&self, id: &str) -> Option<gio::MenuModel> {
+        let song = self.song_list_model().get(id)?;
+        let song = song.description();
+
+        let menu = gio::Menu::new();
+        menu.append(Some(&*labels::VIEW_ALBUM), Some("song.view_album"));
+        for artist in song.artists.iter() {
+            menu.append(
+                Some(&labels::more_from_label(&artist.name)),
+                Some(&format!("song.view_artist_{}", artist.id)),
+            );
+        }
+
+        share::append_share_items(&menu, "song");
+        menu.append(Some(&*labels::PLAY_NEXT), Some("song.play_next"));
+        menu.append(Some(&*labels::ADD_TO_QUEUE), Some("song.queue"));
+
+        Some(menu.upcast())
+    }
 }
@@ -1,11 +1,18 @@
-use crate::app::components::EventListener;
+use crate::api::{api_cache_size, parse_proxy};
+use crate::app::components::{labels, EventListener};
+use crate::app::session_health;
+use crate::app::state::{ScrobblerService, ScrobblerStatus, SettingsEvent};
 use crate::app::AppEvent;
-use crate::settings::RiffSettings;
+use crate::player::{audio_cache_size, list_alsa_devices};
+use crate::scrobbler;
 
+use gettextrs::gettext;
+use gio::SimpleAction;
 use gtk::prelude::*;
 use gtk::subclass::prelude::*;
 use gtk::CompositeTemplate;
 use libadwaita::prelude::*;
+use std::rc::Rc;
 
 use super::SettingsModel;
 
@@ -15,6 +22,7 @@ mod imp {
 
     use super::*;
     use libadwaita::subclass::prelude::*;
+    use std::cell::Cell;
 
     #[derive(Debug, Default, CompositeTemplate)]
     #[template(resource = "/dev/diegovsky/Riff/components/settings.ui")]
@@ -22,23 +30,136 @@ mod imp {
         #[template_child]
         pub player_bitrate: TemplateChild<libadwaita::ComboRow>,
 
+        #[template_child]
+        pub player_bitrate_unmetered: TemplateChild<libadwaita::ComboRow>,
+
+        #[template_child]
+        pub player_bitrate_metered: TemplateChild<libadwaita::ComboRow>,
+
         #[template_child]
         pub alsa_device: TemplateChild<gtk::Entry>,
 
         #[template_child]
         pub alsa_device_row: TemplateChild<libadwaita::ActionRow>,
 
+        #[template_child]
+        pub alsa_devices_button: TemplateChild<gtk::MenuButton>,
+
+        #[template_child]
+        pub alsa_mixer_row: TemplateChild<libadwaita::ExpanderRow>,
+
+        #[template_child]
+        pub alsa_mixer_device: TemplateChild<libadwaita::EntryRow>,
+
+        #[template_child]
+        pub alsa_mixer_control: TemplateChild<libadwaita::EntryRow>,
+
+        #[template_child]
+        pub pipewire_target: TemplateChild<gtk::Entry>,
+
+        #[template_child]
+        pub pipewire_target_row: TemplateChild<libadwaita::ActionRow>,
+
         #[template_child]
         pub audio_backend: TemplateChild<libadwaita::ComboRow>,
 
+        #[template_child]
+        pub audio_format: TemplateChild<libadwaita::ComboRow>,
+
         #[template_child]
         pub gapless_playback: TemplateChild<libadwaita::ActionRow>,
 
+        #[template_child]
+        pub fade_duration: TemplateChild<libadwaita::SpinRow>,
+
+        #[template_child]
+        pub volume_normalization: TemplateChild<libadwaita::ActionRow>,
+
+        #[template_child]
+        pub volume_normalization_pregain: TemplateChild<libadwaita::SpinRow>,
+
+        #[template_child]
+        pub volume_normalization_type: TemplateChild<libadwaita::ComboRow>,
+
+        #[template_child]
+        pub cache_audio: TemplateChild<libadwaita::ActionRow>,
+
+        #[template_child]
+        pub audio_cache_size: TemplateChild<libadwaita::SpinRow>,
+
+        #[template_child]
+        pub audio_cache_usage: TemplateChild<libadwaita::ActionRow>,
+
+        #[template_child]
+        pub clear_audio_cache: TemplateChild<gtk::Button>,
+
+        #[template_child]
+        pub api_cache_size: TemplateChild<libadwaita::SpinRow>,
+
+        #[template_child]
+        pub api_cache_usage: TemplateChild<libadwaita::ActionRow>,
+
+        #[template_child]
+        pub clear_api_cache: TemplateChild<gtk::Button>,
+
+        #[template_child]
+        pub track_notifications: TemplateChild<libadwaita::ActionRow>,
+
+        #[template_child]
+        pub scrobbling: TemplateChild<libadwaita::ActionRow>,
+
+        #[template_child]
+        pub lastfm_account: TemplateChild<libadwaita::ActionRow>,
+
+        #[template_child]
+        pub lastfm_login: TemplateChild<gtk::Button>,
+
+        #[template_child]
+        pub listenbrainz_account: TemplateChild<libadwaita::ActionRow>,
+
+        #[template_child]
+        pub listenbrainz_logout: TemplateChild<gtk::Button>,
+
+        #[template_child]
+        pub listenbrainz_token: TemplateChild<libadwaita::PasswordEntryRow>,
+
+        #[template_child]
+        pub background_playback: TemplateChild<libadwaita::ActionRow>,
+
+        #[template_child]
+        pub block_explicit: TemplateChild<libadwaita::ActionRow>,
+
+        #[template_child]
+        pub restore_navigation: TemplateChild<libadwaita::ActionRow>,
+
+        #[template_child]
+        pub session_health: TemplateChild<libadwaita::ActionRow>,
+
+        #[template_child]
+        pub session_summary: TemplateChild<gtk::Label>,
+
+        #[template_child]
+        pub copy_session_summary: TemplateChild<gtk::Button>,
+
         #[template_child]
         pub ap_port: TemplateChild<gtk::Entry>,
 
+        #[template_child]
+        pub proxy_row: TemplateChild<libadwaita::ExpanderRow>,
+
+        #[template_child]
+        pub proxy_url: TemplateChild<libadwaita::EntryRow>,
+
+        #[template_child]
+        pub redirect_port: TemplateChild<gtk::Entry>,
+
+        #[template_child]
+        pub client_id: TemplateChild<gtk::Entry>,
+
         #[template_child]
         pub theme: TemplateChild<libadwaita::ComboRow>,
+
+        pub lastfm_logged_in: Cell<bool>,
     }
 
     #[glib::object_subclass]
@@ -77,11 +198,140 @@ impl SettingsDialog {
         let dialog: Self = glib::Object::new();
 
         dialog.bind_backend_and_device();
+        dialog.bind_alsa_devices();
+        dialog.bind_automatic_bitrate();
         dialog.bind_settings();
         dialog.connect_theme_select();
+        dialog.connect_proxy_validation();
+        dialog.show_session_summary();
+        dialog.update_audio_cache_usage();
+        dialog.update_api_cache_usage();
+        dialog.set_scrobbler_status(ScrobblerService::LastFm, &ScrobblerStatus::default());
+        dialog.set_scrobbler_status(ScrobblerService::ListenBrainz, &ScrobblerStatus::default());
+        dialog
+            .imp()
+            .lastfm_account
+            .set_visible(scrobbler::is_available());
         dialog
     }
 
+    fn set_scrobbler_status(&self, service: ScrobblerService, status: &ScrobblerStatus) {
+        let subtitle =
+            labels::scrobbler_status_label(status.user.as_deref(), status.last_submission);
+        match service {
+            ScrobblerService::LastFm => self.set_lastfm_status(&subtitle, status.user.is_some()),
+            ScrobblerService::ListenBrainz => {
+                let widget = self.imp();
+                widget.listenbrainz_account.set_subtitle(&subtitle);
+                widget
+                    .listenbrainz_logout
+                    .set_visible(status.user.is_some());
+            }
+        }
+    }
+
+    fn set_lastfm_status(&self, subtitle: &str, logged_in: bool) {
+        let widget = self.imp();
+        widget.lastfm_logged_in.set(logged_in);
+        widget.lastfm_account.set_subtitle(subtitle);
+        let label = if logged_in {
+            // translators: Button in the preferences, to stop scrobbling to the Last.fm account
+            gettext("Log Out")
+        } else {
+            // translators: Button in the preferences, opens the browser to connect a Last.fm account
+            gettext("Log In")
+        };
+        widget.lastfm_login.set_label(&label);
+    }
+
+    fn connect_lastfm_login<F, G>(&self, on_login: F, on_logout: G)
+    where
+        F: Fn() + 'static,
+        G: Fn() + 'static,
+    {
+        self.imp().lastfm_login.connect_clicked(clone!(
+            #[weak(rename_to = _self)]
+            self,
+            move |_| {
+                if _self.imp().lastfm_logged_in.get() {
+                    on_logout();
+                } else {
+                    on_login();
+                }
+            }
+        ));
+    }
+
+    fn connect_listenbrainz<F, G>(&self, on_token: F, on_logout: G)
+    where
+        F: Fn(String) + 'static,
+        G: Fn() + 'static,
+    {
+        let widget = self.imp();
+        // The token isn't shown again once applied, the account's name is
+        widget.listenbrainz_token.connect_apply(move |row| {
+            on_token(row.text().to_string());
+            row.set_text("");
+        });
+        widget
+            .listenbrainz_logout
+            .connect_clicked(move |_| on_logout());
+    }
+
+    fn update_audio_cache_usage(&self) {
+        self.imp()
+            .audio_cache_usage
+            .set_subtitle(&labels::cache_size_label(audio_cache_size()));
+    }
+
+    fn audio_cache_cleared(&self, freed: u64) {
+        self.update_audio_cache_usage();
+        self.imp().clear_audio_cache.set_sensitive(true);
+        self.add_toast(libadwaita::Toast::new(&labels::cache_cleared_label(freed)));
+    }
+
+    fn update_api_cache_usage(&self) {
+        self.imp()
+            .api_cache_usage
+            .set_subtitle(&labels::cache_size_label(api_cache_size()));
+    }
+
+    fn api_cache_cleared(&self, freed: u64) {
+        self.update_api_cache_usage();
+        self.imp().clear_api_cache.set_sensitive(true);
+        self.add_toast(libadwaita::Toast::new(&labels::cache_cleared_label(freed)));
+    }
+
+    fn connect_clear_api_cache<F>(&self, on_clear: F)
+    where
+        F: Fn() + 'static,
+    {
+        self.imp().clear_api_cache.connect_clicked(move |button| {
+            button.set_sensitive(false);
+            on_clear();
+        });
+    }
+
+    fn connect_clear_audio_cache<F>(&self, on_clear: F)
+    where
+        F: Fn() + 'static,
+    {
+        self.imp().clear_audio_cache.connect_clicked(move |button| {
+            // Until we hear back from the player
+            button.set_sensitive(false);
+            on_clear();
+        });
+    }
+
+    fn show_session_summary(&self) {
+        let widget = self.imp();
+        let summary = session_health::summary(&session_health::last_sessions());
+        widget.session_summary.set_label(&summary);
+        widget.copy_session_summary.connect_clicked(move |button| {
+            button.clipboard().set_text(&summary);
+        });
+    }
+
     fn bind_backend_and_device(&self) {
         let widget = self.imp();
 
@@ -98,48 +348,135 @@ impl SettingsDialog {
             .bind_property("selected", alsa_device_row, "visible")
             .transform_to(|_, value: u32| Some(value == 1))
             .build();
+        audio_backend
+            .bind_property("selected", &*widget.alsa_mixer_row, "visible")
+            .transform_to(|_, value: u32| Some(value == 1))
+            .build();
+        audio_backend
+            .bind_property("selected", &*widget.pipewire_target_row, "visible")
+            .transform_to(|_, value: u32| Some(value == 3))
+            .build();
 
         if audio_backend.selected() == 0 {
             alsa_device_row.set_visible(false);
+            widget.alsa_mixer_row.set_visible(false);
         }
+        widget
+            .pipewire_target_row
+            .set_visible(audio_backend.selected() == 3);
     }
 
-    fn bind_settings(&self) {
+    // Picking a device just fills in the entry, which can still be edited by hand
+    fn bind_alsa_devices(&self) {
         let widget = self.imp();
-        let settings = gio::Settings::new(SETTINGS);
 
-        let player_bitrate = widget
-            .player_bitrate
-            .downcast_ref::<libadwaita::ComboRow>()
-            .unwrap();
+        let group = gio::SimpleActionGroup::new();
+        let pick = SimpleAction::new("pick", Some(glib::VariantTy::STRING));
+        let entry = widget.alsa_device.clone();
+        pick.connect_activate(move |_, device| {
+            if let Some(device) = device.and_then(|d| d.get::<String>()) {
+                entry.set_text(&device);
+            }
+        });
+        group.add_action(&pick);
+        widget
+            .alsa_devices_button
+            .insert_action_group("alsa", Some(&group));
+
+        // Devices come and go, so they're looked up again every time the list is opened
+        widget.alsa_devices_button.set_create_popup_func(|button| {
+            let menu = gio::Menu::new();
+            for (device, label) in list_alsa_devices() {
+                let item = gio::MenuItem::new(Some(&format!("{label} ({device})")), None);
+                item.set_action_and_target_value(Some("alsa.pick"), Some(&device.to_variant()));
+                menu.append_item(&item);
+            }
+            button.set_menu_model(Some(&menu));
+        });
+    }
+
+    // The bitrates for each kind of connection only matter when it's set to automatic
+    fn bind_automatic_bitrate(&self) {
+        let widget = self.imp();
+        for row in [
+            &*widget.player_bitrate_unmetered,
+            &*widget.player_bitrate_metered,
+        ] {
+            widget
+                .player_bitrate
+                .bind_property("selected", row, "visible")
+                .transform_to(|_, value: u32| Some(value == 3))
+                .sync_create()
+                .build();
+        }
+    }
+
+    fn bind_bitrate(settings: &gio::Settings, key: &str, row: &libadwaita::ComboRow) {
+        // The metered/unmetered rows don't offer "automatic", so don't select past their last item
+        let last = row
+            .model()
+            .map_or(0, |model| model.n_items().saturating_sub(1));
         settings
-            .bind("player-bitrate", player_bitrate, "selected")
-            .mapping(|variant, _| {
+            .bind(key, row, "selected")
+            .mapping(move |variant, _| {
                 variant.str().map(|s| {
-                    match s {
+                    let index: u32 = match s {
                         "96" => 0,
                         "160" => 1,
                         "320" => 2,
+                        "automatic" => 3,
                         _ => unreachable!(),
-                    }
-                    .to_value()
+                    };
+                    index.min(last).to_value()
                 })
             })
             .set_mapping(|value, _| {
-                value.get::<u32>().ok().map(|u| {
-                    match u {
-                        0 => "96",
-                        1 => "160",
-                        2 => "320",
-                        _ => unreachable!(),
-                    }
-                    .to_variant()
-                })
+                let bitrate = match value.get::<u32>().ok()? {
+                    0 => "96",
+                    1 => "160",
+                    2 => "320",
+                    3 => "automatic",
+                    _ => return None,
+                };
+                Some(bitrate.to_variant())
             })
             .build();
+    }
+
+    fn bind_settings(&self) {
+        let widget = self.imp();
+        let settings = gio::Settings::new(SETTINGS);
+
+        Self::bind_bitrate(&settings, "player-bitrate", &widget.player_bitrate);
+        Self::bind_bitrate(
+            &settings,
+            "player-bitrate-unmetered",
+            &widget.player_bitrate_unmetered,
+        );
+        Self::bind_bitrate(
+            &settings,
+            "player-bitrate-metered",
+            &widget.player_bitrate_metered,
+        );
 
         let alsa_device = widget.alsa_device.downcast_ref::<gtk::Entry>().unwrap();
         settings.bind("alsa-device", alsa_device, "text").build();
+        settings
+            .bind(
+                "alsa-hardware-mixer",
+                &*widget.alsa_mixer_row,
+                "enable-expansion",
+            )
+            .build();
+        settings
+            .bind("alsa-mixer-device", &*widget.alsa_mixer_device, "text")
+            .build();
+        settings
+            .bind("alsa-mixer-control", &*widget.alsa_mixer_control, "text")
+            .build();
+        settings
+            .bind("pipewire-target", &*widget.pipewire_target, "text")
+            .build();
 
         let audio_backend = widget
             .audio_backend
@@ -153,6 +490,7 @@ impl SettingsDialog {
                         "pulseaudio" => 0,
                         "alsa" => 1,
                         "gstreamer" => 2,
+                        "pipewire" => 3,
                         _ => unreachable!(),
                     }
                     .to_value()
@@ -164,6 +502,39 @@ impl SettingsDialog {
                         0 => "pulseaudio",
                         1 => "alsa",
                         2 => "gstreamer",
+                        3 => "pipewire",
+                        _ => unreachable!(),
+                    }
+                    .to_variant()
+                })
+            })
+            .build();
+
+        settings
+            .bind("audio-format", &*widget.audio_format, "selected")
+            .mapping(|variant, _| {
+                variant.str().map(|s| {
+                    match s {
+                        "s16" => 0,
+                        "s24" => 1,
+                        "s24-3" => 2,
+                        "s32" => 3,
+                        "f32" => 4,
+                        "f64" => 5,
+                        _ => unreachable!(),
+                    }
+                    .to_value()
+                })
+            })
+            .set_mapping(|value, _| {
+                value.get::<u32>().ok().map(|u| {
+                    match u {
+                        0 => "s16",
+                        1 => "s24",
+                        2 => "s24-3",
+                        3 => "s32",
+                        4 => "f32",
+                        5 => "f64",
                         _ => unreachable!(),
                     }
                     .to_variant()
@@ -183,6 +554,154 @@ impl SettingsDialog {
             )
             .build();
 
+        settings
+            .bind("fade-duration", &*widget.fade_duration, "value")
+            .mapping(|variant, _| variant.get::<u32>().map(|s| (s as f64).to_value()))
+            .set_mapping(|value, _| value.get::<f64>().ok().map(|f| (f as u32).to_variant()))
+            .build();
+
+        let volume_normalization = widget
+            .volume_normalization
+            .downcast_ref::<libadwaita::ActionRow>()
+            .unwrap();
+        settings
+            .bind(
+                "volume-normalization",
+                &volume_normalization.activatable_widget().unwrap(),
+                "active",
+            )
+            .build();
+
+        settings
+            .bind(
+                "volume-normalization-pregain",
+                &*widget.volume_normalization_pregain,
+                "value",
+            )
+            .build();
+
+        settings
+            .bind(
+                "volume-normalization-type",
+                &*widget.volume_normalization_type,
+                "selected",
+            )
+            .mapping(|variant, _| {
+                variant.str().map(|s| {
+                    match s {
+                        "auto" => 0,
+                        "track" => 1,
+                        "album" => 2,
+                        _ => unreachable!(),
+                    }
+                    .to_value()
+                })
+            })
+            .set_mapping(|value, _| {
+                value.get::<u32>().ok().map(|u| {
+                    match u {
+                        0 => "auto",
+                        1 => "track",
+                        2 => "album",
+                        _ => unreachable!(),
+                    }
+                    .to_variant()
+                })
+            })
+            .build();
+
+        let track_notifications = widget
+            .track_notifications
+            .downcast_ref::<libadwaita::ActionRow>()
+            .unwrap();
+        settings
+            .bind(
+                "track-notifications",
+                &track_notifications.activatable_widget().unwrap(),
+                "active",
+            )
+            .build();
+
+        let scrobbling = widget
+            .scrobbling
+            .downcast_ref::<libadwaita::ActionRow>()
+            .unwrap();
+        settings
+            .bind(
+                "scrobbling",
+                &scrobbling.activatable_widget().unwrap(),
+                "active",
+            )
+            .build();
+
+        let background_playback = widget
+            .background_playback
+            .downcast_ref::<libadwaita::ActionRow>()
+            .unwrap();
+        settings
+            .bind(
+                "background-playback",
+                &background_playback.activatable_widget().unwrap(),
+                "active",
+            )
+            .build();
+
+        let block_explicit = widget
+            .block_explicit
+            .downcast_ref::<libadwaita::ActionRow>()
+            .unwrap();
+        settings
+            .bind(
+                "block-explicit",
+                &block_explicit.activatable_widget().unwrap(),
+                "active",
+            )
+            .build();
+
+        let restore_navigation = widget
+            .restore_navigation
+            .downcast_ref::<libadwaita::ActionRow>()
+            .unwrap();
+        settings
+            .bind(
+                "restore-navigation",
+                &restore_navigation.activatable_widget().unwrap(),
+                "active",
+            )
+            .build();
+
+        let session_health = widget
+            .session_health
+            .downcast_ref::<libadwaita::ActionRow>()
+            .unwrap();
+        settings
+            .bind(
+                "session-health",
+                &session_health.activatable_widget().unwrap(),
+                "active",
+            )
+            .build();
+
+        settings
+            .bind(
+                "cache-audio",
+                &widget.cache_audio.activatable_widget().unwrap(),
+                "active",
+            )
+            .build();
+
+        settings
+            .bind("audio-cache-size", &*widget.audio_cache_size, "value")
+            .mapping(|variant, _| variant.get::<u32>().map(|s| (s as f64).to_value()))
+            .set_mapping(|value, _| value.get::<f64>().ok().map(|f| (f as u32).to_variant()))
+            .build();
+
+        settings
+            .bind("api-cache-size", &*widget.api_cache_size, "value")
+            .mapping(|variant, _| variant.get::<u32>().map(|s| (s as f64).to_value()))
+            .set_mapping(|value, _| value.get::<f64>().ok().map(|f| (f as u32).to_variant()))
+            .build();
+
         let ap_port = widget.ap_port.downcast_ref::<gtk::Entry>().unwrap();
         settings
             .bind("ap-port", ap_port, "text")
@@ -190,6 +709,30 @@ impl SettingsDialog {
             .set_mapping(|value, _| value.get::<u32>().ok().map(|u| u.to_variant()))
             .build();
 
+        settings
+            .bind("proxy-enabled", &*widget.proxy_row, "enable-expansion")
+            .build();
+        // Only addresses we can go through are saved
+        settings
+            .bind("proxy-url", &*widget.proxy_url, "text")
+            .set_mapping(|value, _| {
+                let proxy = value.get::<String>().ok()?;
+                let valid = proxy.trim().is_empty() || parse_proxy(proxy.trim()).is_some();
+                valid.then(|| proxy.to_variant())
+            })
+            .build();
+
+        settings
+            .bind("client-id", &*widget.client_id, "text")
+            .build();
+
+        let redirect_port = widget.redirect_port.downcast_ref::<gtk::Entry>().unwrap();
+        settings
+            .bind("oauth-redirect-port", redirect_port, "text")
+            .mapping(|variant, _| variant.get::<u32>().map(|s| s.to_value()))
+            .set_mapping(|value, _| value.get::<u32>().ok().map(|u| u.to_variant()))
+            .build();
+
         let theme = widget.theme.downcast_ref::<libadwaita::ComboRow>().unwrap();
         settings
             .bind("theme-preference", theme, "selected")
@@ -235,6 +778,20 @@ impl SettingsDialog {
         });
     }
 
+    fn connect_proxy_validation(&self) {
+        let proxy_url = &*self.imp().proxy_url;
+        let validate = |entry: &libadwaita::EntryRow| {
+            let proxy = entry.text();
+            if proxy.trim().is_empty() || parse_proxy(proxy.trim()).is_some() {
+                entry.remove_css_class("error");
+            } else {
+                entry.add_css_class("error");
+            }
+        };
+        validate(proxy_url);
+        proxy_url.connect_changed(validate);
+    }
+
     fn connect_close<F>(&self, on_close: F)
     where
         F: Fn() + 'static,
@@ -246,26 +803,67 @@ impl SettingsDialog {
     }
 }
 
+#[derive(Clone)]
 pub struct Settings {
     parent: gtk::Window,
     settings_dialog: SettingsDialog,
+    model: Rc<SettingsModel>,
 }
 
 impl Settings {
     pub fn new(parent: gtk::Window, model: SettingsModel) -> Self {
         let settings_dialog = SettingsDialog::new();
-
-        settings_dialog.connect_close(move || {
-            let new_settings = RiffSettings::new_from_gsettings().unwrap_or_default();
-            if model.settings().player_settings != new_settings.player_settings {
-                model.stop_player();
-            }
-            model.set_settings();
-        });
+        let model = Rc::new(model);
+
+        settings_dialog.connect_clear_audio_cache(clone!(
+            #[strong]
+            model,
+            move || model.clear_audio_cache()
+        ));
+
+        settings_dialog.connect_clear_api_cache(clone!(
+            #[strong]
+            model,
+            move || model.clear_api_cache()
+        ));
+
+        settings_dialog.connect_lastfm_login(
+            clone!(
+                #[strong]
+                model,
+                move || model.lastfm_login()
+            ),
+            clone!(
+                #[strong]
+                model,
+                move || model.lastfm_logout()
+            ),
+        );
+
+        settings_dialog.connect_listenbrainz(
+            clone!(
+                #[strong]
+                model,
+                move |token| model.set_listenbrainz_token(token)
+            ),
+            clone!(
+                #[strong]
+                model,
+                move || model.listenbrainz_logout()
+            ),
+        );
+
+        // The player picks up what it needs without stopping the current track
+        settings_dialog.connect_close(clone!(
+            #[strong]
+            model,
+            move || model.set_settings()
+        ));
 
         Self {
             parent,
             settings_dialog,
+            model,
         }
     }
 
@@ -273,11 +871,35 @@ impl Settings {
         self.settings_dialog.upcast_ref::<libadwaita::Dialog>()
     }
 
+    fn update_scrobbler_status(&self, service: ScrobblerService) {
+        self.settings_dialog
+            .set_scrobbler_status(service, &self.model.scrobbler_status(service));
+    }
+
     pub fn show_self(&self) {
+        // It might have grown since last time
+        self.settings_dialog.update_audio_cache_usage();
+        self.settings_dialog.update_api_cache_usage();
+        for service in [ScrobblerService::LastFm, ScrobblerService::ListenBrainz] {
+            self.update_scrobbler_status(service);
+        }
         self.dialog().present(Some(&self.parent));
     }
 }
 
 impl EventListener for Settings {
-    fn on_event(&mut self, _: &AppEvent) {}
+    fn on_event(&mut self, event: &AppEvent) {
+        match event {
+            AppEvent::SettingsEvent(SettingsEvent::AudioCacheCleared(freed)) => {
+                self.settings_dialog.audio_cache_cleared(*freed);
+            }
+            AppEvent::SettingsEvent(SettingsEvent::ApiCacheCleared(freed)) => {
+                self.settings_dialog.api_cache_cleared(*freed);
+            }
+            AppEvent::SettingsEvent(SettingsEvent::ScrobblerStatusChanged(service)) => {
+                self.update_scrobbler_status(*service);
+            }
+            _ => {}
+        }
+    }
 }
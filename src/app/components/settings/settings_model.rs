@@ -1,8 +1,10 @@
-use crate::app::state::{PlaybackAction, SettingsAction};
-use crate::app::{ActionDispatcher, AppModel};
-use crate::settings::RiffSettings;
 use std::rc::Rc;
 
+use crate::api::{set_api_cache_limit, set_proxy};
+use crate::app::state::{PlaybackAction, ScrobblerService, ScrobblerStatus, SettingsAction};
+use crate::app::{ActionDispatcher, AppModel};
+use crate::settings::{api_cache_limit, block_explicit_enabled, proxy};
+
 pub struct SettingsModel {
     app_model: Rc<AppModel>,
     dispatcher: Box<dyn ActionDispatcher>,
@@ -16,17 +18,53 @@ impl SettingsModel {
         }
     }
 
-    pub fn stop_player(&self) {
-        self.dispatcher.dispatch(PlaybackAction::Stop.into());
+    pub fn scrobbler_status(&self, service: ScrobblerService) -> ScrobblerStatus {
+        self.app_model
+            .get_state()
+            .settings
+            .scrobbler_status(service)
+            .clone()
+    }
+
+    pub fn lastfm_login(&self) {
+        self.dispatcher.dispatch(SettingsAction::LastFmLogin.into());
+    }
+
+    pub fn lastfm_logout(&self) {
+        self.dispatcher
+            .dispatch(SettingsAction::LastFmLogout.into());
+    }
+
+    pub fn set_listenbrainz_token(&self, token: String) {
+        if !token.trim().is_empty() {
+            self.dispatcher
+                .dispatch(SettingsAction::SetListenBrainzToken(token).into());
+        }
+    }
+
+    pub fn listenbrainz_logout(&self) {
+        self.dispatcher
+            .dispatch(SettingsAction::SetListenBrainzToken(String::new()).into());
     }
 
     pub fn set_settings(&self) {
+        set_api_cache_limit(api_cache_limit());
+        set_proxy(proxy().as_deref());
+        self.dispatcher.dispatch_many(vec![
+            SettingsAction::ChangeSettings.into(),
+            PlaybackAction::SetBlockExplicit(block_explicit_enabled()).into(),
+        ]);
+    }
+
+    pub fn clear_audio_cache(&self) {
         self.dispatcher
-            .dispatch(SettingsAction::ChangeSettings.into());
+            .dispatch(SettingsAction::ClearAudioCache.into());
     }
 
-    pub fn settings(&self) -> RiffSettings {
-        let state = self.app_model.get_state();
-        state.settings.settings.clone()
+    pub fn clear_api_cache(&self) {
+        self.dispatcher.dispatch_async(Box::pin(async {
+            let freed = crate::api::clear_api_cache().await;
+            Some(SettingsAction::ApiCacheCleared(freed).into())
+        }));
     }
 }
@@ -0,0 +1,119 @@
+use gettextrs::gettext;
+use gtk::prelude::*;
+
+use super::labels;
+use crate::app::{ActionDispatcher, AppAction};
+
+// What can be shared, named like in open.spotify.com links
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShareKind {
+    Track,
+    Episode,
+    Album,
+    Playlist,
+    Artist,
+    Show,
+}
+
+impl ShareKind {
+    fn path(self) -> &'static str {
+        match self {
+            Self::Track => "track",
+            Self::Episode => "episode",
+            Self::Album => "album",
+            Self::Playlist => "playlist",
+            Self::Artist => "artist",
+            Self::Show => "show",
+        }
+    }
+}
+
+// Also works when given a full spotify:xxx:id URI
+pub fn share_url(kind: ShareKind, id: &str) -> String {
+    let id = id.rsplit(':').next().unwrap_or(id);
+    format!("https://open.spotify.com/{}/{id}", kind.path())
+}
+
+pub fn copy_link(url: &str, dispatcher: &dyn ActionDispatcher) {
+    let Some(display) = gdk::Display::default() else {
+        warn!("No display to copy {url} to");
+        return;
+    };
+    display.clipboard().set_text(url);
+    // translators: Notification shown after copying a link to the clipboard
+    dispatcher.dispatch(AppAction::ShowNotification(gettext("Link copied")));
+}
+
+pub fn open_in_browser(url: &str) {
+    if open::that(url).is_err() {
+        warn!("Could not open {url}");
+    }
+}
+
+pub fn make_copy_link_action(
+    url: String,
+    dispatcher: Box<dyn ActionDispatcher>,
+    name: Option<&str>,
+) -> gio::SimpleAction {
+    let action = gio::SimpleAction::new(name.unwrap_or("copy_link"), None);
+    action.connect_activate(move |_, _| copy_link(&url, dispatcher.as_ref()));
+    action
+}
+
+pub fn make_open_in_browser_action(url: String, name: Option<&str>) -> gio::SimpleAction {
+    let action = gio::SimpleAction::new(name.unwrap_or("open_in_browser"), None);
+    action.connect_activate(move |_, _| open_in_browser(&url));
+    action
+}
+
+// Both actions above, with their default names
+pub fn add_share_actions(
+    group: &gio::SimpleActionGroup,
+    kind: ShareKind,
+    id: &str,
+    dispatcher: Box<dyn ActionDispatcher>,
+) {
+    let url = share_url(kind, id);
+    group.add_action(&make_copy_link_action(url.clone(), dispatcher, None));
+    group.add_action(&make_open_in_browser_action(url, None));
+}
+
+// The menu entries for these actions, in the given action group
+pub fn append_share_items(menu: &gio::Menu, group: &str) {
+    menu.append(
+        Some(&*labels::COPY_LINK),
+        Some(&format!("{group}.copy_link")),
+    );
+    menu.append(
+        Some(&*labels::OPEN_IN_BROWSER),
+        Some(&format!("{group}.open_in_browser")),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_share_urls() {
+        let cases = [
+            (ShareKind::Track, "https://open.spotify.com/track/abc"),
+            (ShareKind::Episode, "https://open.spotify.com/episode/abc"),
+            (ShareKind::Album, "https://open.spotify.com/album/abc"),
+            (ShareKind::Playlist, "https://open.spotify.com/playlist/abc"),
+            (ShareKind::Artist, "https://open.spotify.com/artist/abc"),
+            (ShareKind::Show, "https://open.spotify.com/show/abc"),
+        ];
+        for (kind, url) in cases {
+            assert_eq!(share_url(kind, "abc"), url);
+        }
+    }
+
+    #[test]
+    fn test_share_url_from_uri() {
+        assert_eq!(
+            share_url(ShareKind::Episode, "spotify:episode:abc"),
+            "https://open.spotify.com/episode/abc"
+        );
+    }
+}
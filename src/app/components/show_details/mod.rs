@@ -0,0 +1,6 @@
+#[allow(clippy::module_inception)]
+mod show_details;
+pub use show_details::*;
+
+mod show_details_model;
+pub use show_details_model::*;
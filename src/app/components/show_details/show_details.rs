@@ -0,0 +1,239 @@
+use gettextrs::gettext;
+use gtk::prelude::*;
+use gtk::subclass::prelude::*;
+use gtk::CompositeTemplate;
+use libadwaita::prelude::*;
+use std::rc::Rc;
+
+use crate::app::components::utils::format_duration;
+use crate::app::components::{labels, Component, EventListener};
+use crate::app::loader::ImageLoader;
+use crate::app::models::*;
+use crate::app::{AppEvent, BrowserEvent, Worker};
+
+use super::ShowDetailsModel;
+
+mod imp {
+
+    use super::*;
+
+    #[derive(Debug, Default, CompositeTemplate)]
+    #[template(resource = "/dev/diegovsky/Riff/components/show_details.ui")]
+    pub struct ShowDetailsWidget {
+        #[template_child]
+        pub scrolled_window: TemplateChild<gtk::ScrolledWindow>,
+
+        #[template_child]
+        pub art: TemplateChild<gtk::Image>,
+
+        #[template_child]
+        pub title: TemplateChild<gtk::Label>,
+
+        #[template_child]
+        pub publisher: TemplateChild<gtk::Label>,
+
+        #[template_child]
+        pub description: TemplateChild<gtk::Label>,
+
+        #[template_child]
+        pub share_button: TemplateChild<gtk::MenuButton>,
+
+        #[template_child]
+        pub episodes: TemplateChild<gtk::ListBox>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for ShowDetailsWidget {
+        const NAME: &'static str = "ShowDetailsWidget";
+        type Type = super::ShowDetailsWidget;
+        type ParentType = gtk::Box;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for ShowDetailsWidget {}
+    impl WidgetImpl for ShowDetailsWidget {}
+    impl BoxImpl for ShowDetailsWidget {}
+}
+
+glib::wrapper! {
+    pub struct ShowDetailsWidget(ObjectSubclass<imp::ShowDetailsWidget>) @extends gtk::Widget, gtk::Box;
+}
+
+impl ShowDetailsWidget {
+    fn new() -> Self {
+        glib::Object::new()
+    }
+
+    fn set_show(&self, show: &ShowDescription, worker: &Worker) {
+        let widget = self.imp();
+        widget.title.set_text(&show.title);
+        widget.publisher.set_text(&show.publisher);
+        widget.description.set_text(&show.description);
+
+        if let Some(url) = show.art.clone() {
+            let _self = self.downgrade();
+            worker.send_local_task(async move {
+                let loader = ImageLoader::new();
+                let pixbuf = loader.load_remote(&url, "jpg", 160, 160).await;
+                if let (Some(_self), Some(pixbuf)) = (_self.upgrade(), pixbuf) {
+                    let texture = gdk::Texture::for_pixbuf(&pixbuf);
+                    _self.imp().art.set_paintable(Some(&texture));
+                }
+            });
+        }
+    }
+
+    fn set_share_menu(&self, menu: &gio::MenuModel, actions: &gio::ActionGroup) {
+        self.imp().share_button.set_menu_model(Some(menu));
+        self.insert_action_group("show", Some(actions));
+    }
+
+    fn connect_bottom_edge<F>(&self, f: F)
+    where
+        F: Fn() + 'static,
+    {
+        self.imp()
+            .scrolled_window
+            .connect_edge_reached(move |_, pos| {
+                if let gtk::PositionType::Bottom = pos {
+                    f()
+                }
+            });
+    }
+
+    fn add_episode<F>(&self, episode: &EpisodeDescription, subtitle: &str, on_play: F)
+    where
+        F: Fn() + 'static,
+    {
+        let play_button = gtk::Button::builder()
+            .icon_name("media-playback-start-symbolic")
+            // translators: This is the tooltip of the button that plays a podcast episode
+            .tooltip_text(gettext("Play"))
+            .valign(gtk::Align::Center)
+            .css_classes(["flat", "circular"])
+            .build();
+        play_button.connect_clicked(move |_| on_play());
+
+        let row = libadwaita::ActionRow::builder()
+            .title(&episode.title)
+            .subtitle(subtitle)
+            .use_markup(false)
+            .activatable_widget(&play_button)
+            .build();
+        row.add_suffix(&play_button);
+        self.imp().episodes.append(&row);
+    }
+
+    fn clear_episodes(&self) {
+        self.imp().episodes.remove_all();
+    }
+}
+
+// Release date, duration, and how far along we are
+fn episode_subtitle(episode: &EpisodeDescription, position: Option<u32>) -> String {
+    let mut parts = vec![];
+    if let Some(date) = episode.release_date.as_ref() {
+        parts.push(date.clone());
+    }
+    parts.push(format_duration(episode.duration.into()));
+    match position {
+        Some(position) if position > 0 => {
+            let left = episode.duration.saturating_sub(position);
+            parts.push(labels::time_left_label(&format_duration(left.into())));
+        }
+        // translators: This is shown next to a podcast episode that was listened to until the end
+        _ if episode.fully_played => parts.push(gettext("Played")),
+        _ => {}
+    }
+    parts.join(" · ")
+}
+
+pub struct ShowDetails {
+    model: Rc<ShowDetailsModel>,
+    widget: ShowDetailsWidget,
+    worker: Worker,
+    // How many episodes have a row already
+    shown_episodes: usize,
+}
+
+impl ShowDetails {
+    pub fn new(model: Rc<ShowDetailsModel>, worker: Worker) -> Self {
+        model.load_show_details(model.id.clone());
+
+        let widget = ShowDetailsWidget::new();
+        widget.set_share_menu(&model.share_menu(), &model.share_actions());
+
+        widget.connect_bottom_edge(clone!(
+            #[weak]
+            model,
+            move || {
+                model.load_more();
+            }
+        ));
+
+        Self {
+            model,
+            widget,
+            worker,
+            shown_episodes: 0,
+        }
+    }
+
+    fn update_details(&mut self) {
+        let Some(episodes) = self.model.get_episodes() else {
+            return;
+        };
+        // The show got reloaded, start over
+        if episodes.len() < self.shown_episodes {
+            self.widget.clear_episodes();
+            self.shown_episodes = 0;
+        }
+        if self.shown_episodes == 0 {
+            if let Some(show) = self.model.get_show() {
+                self.widget.set_show(&show, &self.worker);
+            }
+        }
+        for episode in episodes.iter().skip(self.shown_episodes) {
+            let subtitle = episode_subtitle(episode, self.model.resume_position(episode));
+            let id = episode.id.clone();
+            self.widget.add_episode(
+                episode,
+                &subtitle,
+                clone!(
+                    #[weak(rename_to = model)]
+                    self.model,
+                    move || {
+                        model.play_episode(&id);
+                    }
+                ),
+            );
+        }
+        self.shown_episodes = episodes.len();
+    }
+}
+
+impl Component for ShowDetails {
+    fn get_root_widget(&self) -> &gtk::Widget {
+        self.widget.as_ref()
+    }
+}
+
+impl EventListener for ShowDetails {
+    fn on_event(&mut self, event: &AppEvent) {
+        match event {
+            AppEvent::BrowserEvent(BrowserEvent::ShowDetailsUpdated(id))
+                if id == &self.model.id =>
+            {
+                self.update_details();
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,116 @@
+use gio::prelude::*;
+use gio::SimpleActionGroup;
+use std::ops::Deref;
+use std::rc::Rc;
+
+use crate::app::components::{share, SimpleHeaderBarModel};
+use crate::app::models::*;
+use crate::app::state::{BrowserAction, PlaybackAction, ScreenName, SelectionContext};
+use crate::app::{ActionDispatcher, AppModel};
+use crate::settings::{episode_position, set_episode_position};
+
+pub struct ShowDetailsModel {
+    pub id: String,
+    app_model: Rc<AppModel>,
+    dispatcher: Box<dyn ActionDispatcher>,
+}
+
+impl ShowDetailsModel {
+    pub fn new(id: String, app_model: Rc<AppModel>, dispatcher: Box<dyn ActionDispatcher>) -> Self {
+        Self {
+            id,
+            app_model,
+            dispatcher,
+        }
+    }
+
+    pub fn share_actions(&self) -> gio::ActionGroup {
+        let group = SimpleActionGroup::new();
+        share::add_share_actions(
+            &group,
+            share::ShareKind::Show,
+            &self.id,
+            self.dispatcher.box_clone(),
+        );
+        group.upcast()
+    }
+
+    pub fn share_menu(&self) -> gio::MenuModel {
+        let menu = gio::Menu::new();
+        share::append_share_items(&menu, "show");
+        menu.upcast()
+    }
+
+    pub fn get_show(&self) -> Option<impl Deref<Target = ShowDescription> + '_> {
+        self.app_model
+            .map_state_opt(|s| s.browser.show_state(&self.id)?.show.as_ref())
+    }
+
+    pub fn get_episodes(&self) -> Option<impl Deref<Target = Vec<EpisodeDescription>> + '_> {
+        self.app_model
+            .map_state_opt(|s| Some(&s.browser.show_state(&self.id)?.episodes))
+    }
+
+    // What we remember locally wins over what Spotify says, it's more likely to be recent
+    pub fn resume_position(&self, episode: &EpisodeDescription) -> Option<u32> {
+        episode_position(&episode.id).or(episode.resume_position)
+    }
+
+    pub fn load_show_details(&self, id: String) {
+        let api = self.app_model.get_spotify();
+        self.dispatcher
+            .call_spotify_and_dispatch(move || async move {
+                api.get_show(&id)
+                    .await
+                    .map(|show| BrowserAction::SetShowDetails(Box::new(show)).into())
+            });
+    }
+
+    pub fn load_more(&self) -> Option<()> {
+        let api = self.app_model.get_spotify();
+        let state = self.app_model.get_state();
+        let next_page = &state.browser.show_state(&self.id)?.next_page;
+
+        let id = next_page.data.clone();
+        let batch_size = next_page.batch_size;
+        let offset = next_page.next_offset?;
+        self.dispatcher
+            .call_spotify_and_dispatch(move || async move {
+                api.get_show_episodes(&id, offset, batch_size)
+                    .await
+                    .map(|episodes| BrowserAction::AppendShowEpisodes(id, episodes).into())
+            });
+
+        Some(())
+    }
+
+    pub fn play_episode(&self, id: &str) -> Option<()> {
+        let song = {
+            let show = self.get_show()?;
+            let episodes = self.get_episodes()?;
+            let episode = episodes.iter().find(|e| e.id == id)?;
+            // The player only knows about our own positions, so we hand it Spotify's if that's all we have
+            if let (None, Some(position)) = (episode_position(id), episode.resume_position) {
+                set_episode_position(id, position);
+            }
+            SongDescription::from((episode, &*show))
+        };
+        self.dispatcher.dispatch_many(vec![
+            PlaybackAction::LoadSongs(vec![song]).into(),
+            PlaybackAction::Load(id.to_string()).into(),
+        ]);
+        Some(())
+    }
+}
+
+impl SimpleHeaderBarModel for ShowDetailsModel {
+    fn screen_name(&self) -> Option<ScreenName> {
+        Some(ScreenName::Show(self.id.clone()))
+    }
+
+    fn selection_context(&self) -> Option<SelectionContext> {
+        None
+    }
+
+    fn select_all(&self) {}
+}
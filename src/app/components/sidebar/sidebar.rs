@@ -9,6 +9,7 @@ use super::{
     sidebar_row::SidebarRow,
     SidebarDestination, SidebarItem, CREATE_PLAYLIST_ITEM, SAVED_PLAYLISTS_SECTION,
 };
+use crate::app::components::share::{self, ShareKind};
 use crate::app::models::{AlbumModel, PlaylistSummary};
 use crate::app::state::ScreenName;
 use crate::app::{
@@ -16,7 +17,7 @@ use crate::app::{
     EventListener,
 };
 
-const NUM_FIXED_ENTRIES: u32 = 6;
+const NUM_FIXED_ENTRIES: u32 = 8;
 const NUM_PLAYLISTS: usize = 20;
 
 pub struct SidebarModel {
@@ -81,12 +82,25 @@ impl SidebarModel {
             })
     }
 
+    pub(super) fn copy_playlist_link(&self, id: &str) {
+        let url = share::share_url(ShareKind::Playlist, id);
+        share::copy_link(&url, self.dispatcher.as_ref());
+    }
+
+    pub(super) fn export_playlist(&self, id: String) {
+        self.dispatcher.dispatch(AppAction::ExportPlaylist(id));
+    }
+
     fn navigate(&self, dest: SidebarDestination) {
         let actions = match dest {
             SidebarDestination::Library
             | SidebarDestination::SavedTracks
             | SidebarDestination::NowPlaying
-            | SidebarDestination::SavedPlaylists => {
+            | SidebarDestination::History
+            | SidebarDestination::NewReleases
+            | SidebarDestination::SavedPlaylists
+            | SidebarDestination::FollowedArtists
+            | SidebarDestination::SavedShows => {
                 vec![
                     BrowserAction::NavigationPopTo(ScreenName::Home).into(),
                     BrowserAction::SetHomeVisiblePage(dest.id()).into(),
@@ -125,9 +139,19 @@ impl Sidebar {
         list_store.append(&SidebarItem::from_destination(
             SidebarDestination::SavedTracks,
         ));
+        list_store.append(&SidebarItem::from_destination(
+            SidebarDestination::FollowedArtists,
+        ));
+        list_store.append(&SidebarItem::from_destination(
+            SidebarDestination::SavedShows,
+        ));
         list_store.append(&SidebarItem::from_destination(
             SidebarDestination::NowPlaying,
         ));
+        list_store.append(&SidebarItem::from_destination(SidebarDestination::History));
+        list_store.append(&SidebarItem::from_destination(
+            SidebarDestination::NewReleases,
+        ));
         list_store.append(&SidebarItem::playlists_section());
         list_store.append(&SidebarItem::create_playlist_item());
 
@@ -8,7 +8,11 @@ use crate::app::models::PlaylistSummary;
 const LIBRARY: &str = "library";
 const SAVED_TRACKS: &str = "saved_tracks";
 const NOW_PLAYING: &str = "now_playing";
+const HISTORY: &str = "history";
+const NEW_RELEASES: &str = "new_releases";
 const SAVED_PLAYLISTS: &str = "saved_playlists";
+const FOLLOWED_ARTISTS: &str = "followed_artists";
+const SAVED_SHOWS: &str = "saved_shows";
 const PLAYLIST: &str = "playlist";
 pub const SAVED_PLAYLISTS_SECTION: &str = "saved_playlists_section";
 pub const CREATE_PLAYLIST_ITEM: &str = "create_playlist";
@@ -18,7 +22,11 @@ pub enum SidebarDestination {
     Library,
     SavedTracks,
     NowPlaying,
+    History,
+    NewReleases,
     SavedPlaylists,
+    FollowedArtists,
+    SavedShows,
     Playlist(PlaylistSummary),
 }
 
@@ -28,7 +36,11 @@ impl SidebarDestination {
             Self::Library => LIBRARY,
             Self::SavedTracks => SAVED_TRACKS,
             Self::NowPlaying => NOW_PLAYING,
+            Self::History => HISTORY,
+            Self::NewReleases => NEW_RELEASES,
             Self::SavedPlaylists => SAVED_PLAYLISTS,
+            Self::FollowedArtists => FOLLOWED_ARTISTS,
+            Self::SavedShows => SAVED_SHOWS,
             Self::Playlist(_) => PLAYLIST,
         }
     }
@@ -41,8 +53,16 @@ impl SidebarDestination {
             Self::SavedTracks => gettext("Saved tracks"),
             // translators: This is a sidebar entry to browse to saved playlists.
             Self::NowPlaying => gettext("Now playing"),
+            // translators: This is a sidebar entry to browse to the tracks played during this session.
+            Self::History => gettext("History"),
+            // translators: This is a sidebar entry to browse to albums that were just released.
+            Self::NewReleases => gettext("New releases"),
             // translators: This is a sidebar entry that marks that the entries below are playlists.
             Self::SavedPlaylists => gettext("Playlists"),
+            // translators: This is a sidebar entry to browse to followed artists.
+            Self::FollowedArtists => gettext("Artists"),
+            // translators: This is a sidebar entry to browse to saved podcasts.
+            Self::SavedShows => gettext("Podcasts"),
             Self::Playlist(PlaylistSummary { title, .. }) => title.clone(),
         }
     }
@@ -52,7 +72,11 @@ impl SidebarDestination {
             Self::Library => "library-music-symbolic",
             Self::SavedTracks => "starred-symbolic",
             Self::NowPlaying => "music-queue-symbolic",
+            Self::History => "document-open-recent-symbolic",
+            Self::NewReleases => "media-optical-symbolic",
             Self::SavedPlaylists => "view-app-grid-symbolic",
+            Self::FollowedArtists => "avatar-default-symbolic",
+            Self::SavedShows => "audio-input-microphone-symbolic",
             Self::Playlist(_) => "playlist2-symbolic",
         }
     }
@@ -102,7 +126,11 @@ impl SidebarItem {
                 LIBRARY => Some(SidebarDestination::Library),
                 SAVED_TRACKS => Some(SidebarDestination::SavedTracks),
                 NOW_PLAYING => Some(SidebarDestination::NowPlaying),
+                HISTORY => Some(SidebarDestination::History),
+                NEW_RELEASES => Some(SidebarDestination::NewReleases),
                 SAVED_PLAYLISTS => Some(SidebarDestination::SavedPlaylists),
+                FOLLOWED_ARTISTS => Some(SidebarDestination::FollowedArtists),
+                SAVED_SHOWS => Some(SidebarDestination::SavedShows),
                 PLAYLIST => Some(SidebarDestination::Playlist(PlaylistSummary {
                     id: data,
                     title,
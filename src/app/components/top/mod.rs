@@ -0,0 +1,6 @@
+#[allow(clippy::module_inception)]
+mod top;
+pub use top::*;
+
+mod top_model;
+pub use top_model::*;
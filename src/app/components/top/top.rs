@@ -0,0 +1,222 @@
+use gtk::prelude::*;
+use gtk::subclass::prelude::*;
+use gtk::CompositeTemplate;
+use std::rc::Rc;
+
+use super::TopModel;
+use crate::app::components::utils::wrap_flowbox_item;
+use crate::app::components::{AlbumWidget, ArtistWidget, Component, EventListener};
+use crate::app::models::{AlbumModel, ArtistModel, TopRange};
+use crate::app::state::LoginEvent;
+use crate::app::{AppEvent, BrowserEvent, ListStore, Worker};
+
+mod imp {
+
+    use super::*;
+
+    #[derive(Debug, Default, CompositeTemplate)]
+    #[template(resource = "/dev/diegovsky/Riff/components/top.ui")]
+    pub struct TopWidget {
+        #[template_child]
+        pub short_range: TemplateChild<gtk::ToggleButton>,
+
+        #[template_child]
+        pub medium_range: TemplateChild<gtk::ToggleButton>,
+
+        #[template_child]
+        pub long_range: TemplateChild<gtk::ToggleButton>,
+
+        #[template_child]
+        pub tracks_section: TemplateChild<gtk::Box>,
+
+        #[template_child]
+        pub play_button: TemplateChild<gtk::Button>,
+
+        #[template_child]
+        pub tracks: TemplateChild<gtk::ListView>,
+
+        #[template_child]
+        pub artists_section: TemplateChild<gtk::Box>,
+
+        #[template_child]
+        pub artists: TemplateChild<gtk::FlowBox>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for TopWidget {
+        const NAME: &'static str = "TopWidget";
+        type Type = super::TopWidget;
+        type ParentType = gtk::Box;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for TopWidget {}
+    impl WidgetImpl for TopWidget {}
+    impl BoxImpl for TopWidget {}
+}
+
+glib::wrapper! {
+    pub struct TopWidget(ObjectSubclass<imp::TopWidget>) @extends gtk::Widget, gtk::Box;
+}
+
+impl TopWidget {
+    fn new() -> Self {
+        glib::Object::new()
+    }
+
+    fn connect_range_changed<F>(&self, f: F)
+    where
+        F: Fn(TopRange) + Clone + 'static,
+    {
+        let imp = self.imp();
+        for (button, range) in [
+            (&imp.short_range, TopRange::Short),
+            (&imp.medium_range, TopRange::Medium),
+            (&imp.long_range, TopRange::Long),
+        ] {
+            let f = f.clone();
+            button.connect_toggled(move |button| {
+                if button.is_active() {
+                    f(range);
+                }
+            });
+        }
+    }
+
+    fn connect_play<F>(&self, f: F)
+    where
+        F: Fn() + 'static,
+    {
+        self.imp().play_button.connect_clicked(move |_| f());
+    }
+
+    fn bind_artists<F>(&self, worker: Worker, store: &ListStore<ArtistModel>, on_artist_pressed: F)
+    where
+        F: Fn(String) + 'static,
+    {
+        let store_clone = store.clone();
+        self.imp()
+            .artists
+            .bind_model(Some(store.inner()), move |item| {
+                wrap_flowbox_item(item, |artist_model: &ArtistModel| {
+                    ArtistWidget::for_model(artist_model, worker.clone())
+                })
+            });
+        self.imp().artists.connect_child_activated(move |_, child| {
+            let artist_model = store_clone.get(child.index() as u32);
+            on_artist_pressed(artist_model.id());
+        });
+    }
+
+    fn bind_tracks<F>(&self, worker: Worker, store: &ListStore<AlbumModel>, on_pressed: F)
+    where
+        F: Fn(usize) + 'static,
+    {
+        let list = &self.imp().tracks;
+        list.set_factory(Some(&AlbumWidget::list_item_factory(worker)));
+        list.set_model(Some(&gtk::NoSelection::new(Some(store.inner().clone()))));
+        list.connect_activate(move |_, position| on_pressed(position as usize));
+    }
+
+    fn set_sections_visible(&self, tracks: bool, artists: bool) {
+        let imp = self.imp();
+        imp.tracks_section.set_visible(tracks);
+        imp.artists_section.set_visible(artists);
+        self.set_visible(tracks || artists);
+    }
+}
+
+// The user's most listened tracks and artists, over a few possible time ranges,
+// shown as sections of the library page
+pub struct Top {
+    widget: TopWidget,
+    worker: Worker,
+    model: Rc<TopModel>,
+}
+
+impl Top {
+    pub fn new(model: Rc<TopModel>, worker: Worker) -> Self {
+        let widget = TopWidget::new();
+
+        widget.connect_range_changed(clone!(
+            #[weak]
+            model,
+            move |range| {
+                model.set_range(range);
+            }
+        ));
+
+        widget.connect_play(clone!(
+            #[weak]
+            model,
+            move || {
+                model.play_all();
+            }
+        ));
+
+        Self {
+            widget,
+            worker,
+            model,
+        }
+    }
+
+    fn bind(&self) {
+        self.widget.bind_tracks(
+            self.worker.clone(),
+            &self.model.get_tracks_store().unwrap(),
+            clone!(
+                #[weak(rename_to = model)]
+                self.model,
+                move |position| {
+                    model.play_at(position);
+                }
+            ),
+        );
+        self.widget.bind_artists(
+            self.worker.clone(),
+            &self.model.get_artists_store().unwrap(),
+            clone!(
+                #[weak(rename_to = model)]
+                self.model,
+                move |id| {
+                    model.open_artist(id);
+                }
+            ),
+        );
+    }
+}
+
+impl Component for Top {
+    fn get_root_widget(&self) -> &gtk::Widget {
+        self.widget.upcast_ref()
+    }
+}
+
+impl EventListener for Top {
+    fn on_event(&mut self, event: &AppEvent) {
+        match event {
+            AppEvent::Started => {
+                self.bind();
+            }
+            AppEvent::LoginEvent(LoginEvent::LoginCompleted)
+            | AppEvent::BrowserEvent(BrowserEvent::HomeVisiblePageChanged("library"))
+            | AppEvent::BrowserEvent(BrowserEvent::TopRangeChanged(_)) => {
+                self.model.refresh();
+            }
+            AppEvent::BrowserEvent(BrowserEvent::TopTracksUpdated)
+            | AppEvent::BrowserEvent(BrowserEvent::TopArtistsUpdated) => {
+                self.widget
+                    .set_sections_visible(self.model.has_tracks(), self.model.has_artists());
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use std::cell::Ref;
+use std::ops::Deref;
+use std::rc::Rc;
+
+use crate::app::models::*;
+use crate::app::state::{HomeState, PlaybackAction};
+use crate::app::{ActionDispatcher, AppAction, AppModel, BrowserAction, ListStore};
+
+const TOP_TRACKS_COUNT: usize = 20;
+const TOP_ARTISTS_COUNT: usize = 20;
+
+pub struct TopModel {
+    app_model: Rc<AppModel>,
+    dispatcher: Box<dyn ActionDispatcher>,
+}
+
+impl TopModel {
+    pub fn new(app_model: Rc<AppModel>, dispatcher: Box<dyn ActionDispatcher>) -> Self {
+        Self {
+            app_model,
+            dispatcher,
+        }
+    }
+
+    fn state(&self) -> Option<Ref<'_, HomeState>> {
+        self.app_model.map_state_opt(|s| s.browser.home_state())
+    }
+
+    pub fn get_artists_store(&self) -> Option<impl Deref<Target = ListStore<ArtistModel>> + '_> {
+        Some(Ref::map(self.state()?, |s| &s.top_artists))
+    }
+
+    pub fn get_tracks_store(&self) -> Option<impl Deref<Target = ListStore<AlbumModel>> + '_> {
+        Some(Ref::map(self.state()?, |s| &s.top_tracks))
+    }
+
+    pub fn has_tracks(&self) -> bool {
+        self.get_tracks_store()
+            .map(|list| list.len() > 0)
+            .unwrap_or(false)
+    }
+
+    pub fn has_artists(&self) -> bool {
+        self.get_artists_store()
+            .map(|list| list.len() > 0)
+            .unwrap_or(false)
+    }
+
+    pub fn set_range(&self, range: TopRange) {
+        self.dispatcher
+            .dispatch(BrowserAction::SetTopRange(range).into());
+    }
+
+    // Both are cached per range, so switching back and forth doesn't cost anything
+    pub fn refresh(&self) -> Option<()> {
+        let range = self.state()?.top_range;
+
+        let api = self.app_model.get_spotify();
+        self.dispatcher
+            .call_spotify_and_dispatch(move || async move {
+                api.get_top_tracks(range, TOP_TRACKS_COUNT)
+                    .await
+                    .map(|tracks| BrowserAction::SetTopTracks(range, tracks).into())
+            });
+
+        let api = self.app_model.get_spotify();
+        self.dispatcher
+            .call_spotify_and_dispatch(move || async move {
+                api.get_top_artists(range, TOP_ARTISTS_COUNT)
+                    .await
+                    .map(|artists| BrowserAction::SetTopArtists(range, artists).into())
+            });
+
+        Some(())
+    }
+
+    pub fn play_all(&self) -> Option<()> {
+        self.play_at(0)
+    }
+
+    // The whole list gets queued, as if it were a playlist
+    pub fn play_at(&self, position: usize) -> Option<()> {
+        if self.app_model.get_state().logged_user.offline {
+            return None;
+        }
+        let tracks = self.state()?.top_track_items.clone();
+        let id = tracks.get(position)?.id.clone();
+        self.dispatcher.dispatch_many(vec![
+            PlaybackAction::LoadSongs(tracks).into(),
+            PlaybackAction::Load(id).into(),
+        ]);
+        Some(())
+    }
+
+    pub fn open_artist(&self, id: String) {
+        self.dispatcher.dispatch(AppAction::ViewArtist(id));
+    }
+}
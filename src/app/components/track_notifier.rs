@@ -0,0 +1,89 @@
+use gettextrs::gettext;
+use gio::prelude::*;
+use gtk::prelude::*;
+use std::rc::Rc;
+
+use crate::app::components::EventListener;
+use crate::app::loader::ImageLoader;
+use crate::app::models::SongDescription;
+use crate::app::state::PlaybackEvent;
+use crate::app::{AppEvent, AppModel, Worker};
+use crate::settings::track_notifications_enabled;
+
+// Using the same id every time means a new notification replaces the previous one
+const NOTIFICATION_ID: &str = "track-changed";
+
+// Shows a desktop notification when the track changes while the window is in the background
+pub struct TrackNotifier {
+    app_model: Rc<AppModel>,
+    window: gtk::Window,
+    worker: Worker,
+}
+
+impl TrackNotifier {
+    pub fn new(app_model: Rc<AppModel>, window: gtk::Window, worker: Worker) -> Self {
+        Self {
+            app_model,
+            window,
+            worker,
+        }
+    }
+
+    fn make_notification(song: &SongDescription) -> gio::Notification {
+        let notification = gio::Notification::new(&song.title);
+        notification.set_body(Some(&format!(
+            "{} — {}",
+            song.artists_name(),
+            song.album.name
+        )));
+        // translators: Button of the notification shown when the track changes, skips to the next one
+        notification.add_button(&gettext("Next"), "app.player_next");
+        // translators: Button of the notification shown when the track changes, pauses playback
+        notification.add_button(&gettext("Pause"), "app.toggle_playback");
+        notification
+    }
+
+    fn notify_track_changed(&self, id: &str) {
+        if self.window.is_active() || !track_notifications_enabled() {
+            return;
+        }
+
+        let Some(app) = self.window.application() else {
+            return;
+        };
+        let Some(song) = self.app_model.get_state().playback.current_song() else {
+            return;
+        };
+
+        let notification = Self::make_notification(&song);
+        let Some(art) = song.art else {
+            app.send_notification(Some(NOTIFICATION_ID), &notification);
+            return;
+        };
+
+        let app_model = Rc::clone(&self.app_model);
+        let id = id.to_owned();
+        self.worker.send_local_task(async move {
+            // Most of the time the playback widget already put the art in the cache
+            let path = ImageLoader::new().load_remote_file(&art, "jpg").await;
+
+            // The track might have been skipped while we were busy
+            if app_model.get_state().playback.current_song_id().as_ref() != Some(&id) {
+                return;
+            }
+
+            if let Some(path) = path {
+                notification.set_icon(&gio::FileIcon::new(&gio::File::for_path(path)));
+            }
+            app.send_notification(Some(NOTIFICATION_ID), &notification);
+        });
+    }
+}
+
+impl EventListener for TrackNotifier {
+    fn on_event(&mut self, event: &AppEvent) {
+        if let AppEvent::PlaybackEvent(PlaybackEvent::TrackChanged(id)) = event {
+            self.notify_track_changed(id);
+        }
+    }
+}
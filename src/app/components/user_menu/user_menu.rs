@@ -7,9 +7,11 @@ use std::rc::Rc;
 use super::UserMenuModel;
 use crate::app::components::{EventListener, Settings};
 use crate::app::{state::LoginEvent, AppEvent};
+use crate::player::transitions_summary;
 
 pub struct UserMenu {
     user_button: gtk::MenuButton,
+    settings: Settings,
     model: Rc<UserMenuModel>,
 }
 
@@ -37,8 +39,47 @@ impl UserMenu {
             logout
         });
 
+        action_group.add_action(&{
+            let switch_account = SimpleAction::new("switch_account", Some(glib::VariantTy::STRING));
+            switch_account.connect_activate(clone!(
+                #[weak]
+                model,
+                move |_, username| {
+                    if let Some(username) = username.and_then(|u| u.get::<String>()) {
+                        model.switch_account(username);
+                    }
+                }
+            ));
+            switch_account
+        });
+
+        action_group.add_action(&{
+            let add_account = SimpleAction::new("add_account", None);
+            add_account.connect_activate(clone!(
+                #[weak]
+                model,
+                move |_, _| {
+                    model.add_account();
+                }
+            ));
+            add_account
+        });
+
+        action_group.add_action(&{
+            let import = SimpleAction::new("import", None);
+            import.connect_activate(clone!(
+                #[weak]
+                model,
+                move |_, _| {
+                    model.import_playlist();
+                }
+            ));
+            import
+        });
+
         action_group.add_action(&{
             let settings_action = SimpleAction::new("settings", None);
+            let settings = settings.clone();
             settings_action.connect_activate(move |_, _| {
                 settings.show_self();
             });
@@ -53,6 +94,8 @@ impl UserMenu {
                 #[weak]
                 parent,
                 move |_, _| {
+                    // Kept up to date for bug reports, see the troubleshooting page
+                    about.set_debug_info(&transitions_summary());
                     about.present(Some(&parent));
                 }
             ));
@@ -61,7 +104,11 @@ impl UserMenu {
 
         user_button.insert_action_group("menu", Some(&action_group));
 
-        Self { user_button, model }
+        Self {
+            user_button,
+            settings,
+            model,
+        }
     }
 
     fn update_menu(&self) {
@@ -69,6 +116,11 @@ impl UserMenu {
         // translators: This is a menu entry.
         menu.append(Some(&gettext("Preferences")), Some("menu.settings"));
         // translators: This is a menu entry.
+        menu.append(
+            Some(&gettext("Mini player")),
+            Some("app.toggle_mini_player"),
+        );
+        // translators: This is a menu entry.
         menu.append(Some(&gettext("About")), Some("menu.about"));
         // translators: This is a menu entry.
         menu.append(Some(&gettext("Quit")), Some("app.quit"));
@@ -76,7 +128,23 @@ impl UserMenu {
         if let Some(username) = self.model.username() {
             let user_menu = gio::Menu::new();
             // translators: This is a menu entry.
+            user_menu.append(Some(&gettext("Import playlist…")), Some("menu.import"));
+            // translators: This is a menu entry.
             user_menu.append(Some(&gettext("Log out")), Some("menu.logout"));
+
+            let accounts_menu = gio::Menu::new();
+            for account in self.model.other_accounts() {
+                let item = gio::MenuItem::new(Some(&account), None);
+                item.set_action_and_target_value(
+                    Some("menu.switch_account"),
+                    Some(&account.to_variant()),
+                );
+                accounts_menu.append_item(&item);
+            }
+            // translators: This is a menu entry, to log in with another Spotify account (while remembering the current one)
+            accounts_menu.append(Some(&gettext("Add account…")), Some("menu.add_account"));
+            // translators: This is a menu entry, it opens a submenu listing the Spotify accounts that can be switched to
+            user_menu.append_submenu(Some(&gettext("Switch account")), &accounts_menu);
             menu.insert_section(0, Some(&username), &user_menu);
         }
 
@@ -90,8 +158,13 @@ impl EventListener for UserMenu {
             AppEvent::LoginEvent(LoginEvent::LoginCompleted) | AppEvent::Started => {
                 self.update_menu();
                 self.model.fetch_user_playlists();
+                self.model.fetch_content_filter();
+            }
+            AppEvent::LoginEvent(LoginEvent::AccountsChanged | LoginEvent::AddAccountStarted) => {
+                self.update_menu();
             }
             _ => {}
         }
+        self.settings.on_event(event);
     }
 }
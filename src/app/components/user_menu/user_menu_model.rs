@@ -1,7 +1,7 @@
 use crate::api::clear_user_cache;
 use crate::app::credentials::Credentials;
 use crate::app::state::{LoginAction, PlaybackAction};
-use crate::app::{ActionDispatcher, AppModel};
+use crate::app::{ActionDispatcher, AppAction, AppModel};
 use std::ops::Deref;
 use std::rc::Rc;
 
@@ -32,6 +32,47 @@ impl UserMenuModel {
         }));
     }
 
+    // Usernames of the other accounts we can switch to
+    pub fn other_accounts(&self) -> Vec<String> {
+        let state = self.app_model.get_state();
+        let current = state.logged_user.user.as_ref();
+        state
+            .logged_user
+            .accounts
+            .iter()
+            .filter(|&account| Some(account) != current)
+            .cloned()
+            .collect()
+    }
+
+    pub fn switch_account(&self, username: String) {
+        self.dispatcher.dispatch(PlaybackAction::Stop.into());
+        self.dispatcher
+            .dispatch(LoginAction::SwitchAccount(username).into());
+    }
+
+    pub fn add_account(&self) {
+        self.dispatcher.dispatch(PlaybackAction::Stop.into());
+        self.dispatcher.dispatch(LoginAction::AddAccount.into());
+    }
+
+    pub fn import_playlist(&self) {
+        self.dispatcher.dispatch(AppAction::ImportPlaylist);
+    }
+
+    pub fn fetch_content_filter(&self) {
+        if self.username().is_none() {
+            return;
+        }
+        let api = self.app_model.get_spotify();
+        self.dispatcher
+            .call_spotify_and_dispatch(move || async move {
+                api.is_explicit_content_blocked()
+                    .await
+                    .map(|blocked| PlaybackAction::SetAccountBlocksExplicit(blocked).into())
+            });
+    }
+
     pub fn fetch_user_playlists(&self) {
         let api = self.app_model.get_spotify();
         if let Some(current_user) = self.username() {
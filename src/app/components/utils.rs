@@ -3,6 +3,8 @@ use std::cell::Cell;
 use std::rc::Rc;
 use std::time::Duration;
 
+use crate::app::components::labels;
+
 #[derive(Clone)]
 pub struct Clock {
     interval_ms: u32,
@@ -74,6 +76,12 @@ impl Debouncer {
             previous_source.remove();
         }
     }
+
+    pub fn cancel(&self) {
+        if let Some(previous_source) = self.0.take() {
+            previous_source.remove();
+        }
+    }
 }
 
 pub struct Animator<EasingFn> {
@@ -156,6 +164,19 @@ pub fn wrap_flowbox_item<
     child.upcast::<gtk::Widget>()
 }
 
+// For buttons that need the network, which are insensitive while offline: tooltips still show up
+// on insensitive widgets, so that's where we say why. The usual tooltip is left untouched.
+pub fn show_offline_tooltip(widget: &impl IsA<gtk::Widget>) {
+    widget.set_has_tooltip(true);
+    widget.connect_query_tooltip(|widget, _, _, _, tooltip| {
+        if widget.is_sensitive() {
+            return false;
+        }
+        tooltip.set_text(Some(&labels::offline_tooltip()));
+        true
+    });
+}
+
 pub fn format_duration(duration: f64) -> String {
     let seconds = (duration / 1000.0) as i32;
     let hours = seconds.div_euclid(3600);
@@ -1,20 +1,31 @@
 use gtk::prelude::*;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::rc::Rc;
 
 use crate::app::components::EventListener;
+use crate::app::state::LoginEvent;
 use crate::app::{AppEvent, AppModel};
-use crate::settings::WindowGeometry;
+use crate::dbus::request_background;
+use crate::settings::{
+    background_playback_enabled, set_episode_position, SavedPlayback, WindowGeometry,
+};
 
 thread_local! {
     static WINDOW_GEOMETRY: RefCell<WindowGeometry> = const { RefCell::new(WindowGeometry {
         width: 0, height: 0, is_maximized: false
     }) };
+    // While the mini player is shown, the size of the window isn't the one we want to remember
+    static MINI_PLAYER: Cell<bool> = const { Cell::new(false) };
 }
 
+const MINI_PLAYER_WIDTH: i32 = 320;
+const MINI_PLAYER_HEIGHT: i32 = 120;
+
 pub struct MainWindow {
     initial_window_geometry: WindowGeometry,
     window: libadwaita::ApplicationWindow,
+    window_stack: gtk::Stack,
+    offline_banner: libadwaita::Banner,
 }
 
 impl MainWindow {
@@ -22,6 +33,8 @@ impl MainWindow {
         initial_window_geometry: WindowGeometry,
         app_model: Rc<AppModel>,
         window: libadwaita::ApplicationWindow,
+        window_stack: gtk::Stack,
+        offline_banner: libadwaita::Banner,
     ) -> Self {
         window.connect_close_request(clone!(
             #[weak]
@@ -30,7 +43,12 @@ impl MainWindow {
             glib::Propagation::Proceed,
             move |window| {
                 let state = app_model.get_state();
-                if state.playback.is_playing() {
+                // Backgrounding makes no sense if we're not even logged in
+                let background = background_playback_enabled() && state.logged_user.user.is_some();
+                if background {
+                    request_background();
+                }
+                if background || state.playback.is_playing() {
                     window.set_visible(false);
                     glib::Propagation::Stop
                 } else {
@@ -43,14 +61,29 @@ impl MainWindow {
         window.connect_default_width_notify(Self::save_window_geometry);
         window.connect_maximized_notify(Self::save_window_geometry);
 
-        window.connect_unrealize(|_| {
-            debug!("saving geometry");
-            WINDOW_GEOMETRY.with(|g| g.borrow().save());
-        });
+        window.connect_unrealize(clone!(
+            #[weak]
+            app_model,
+            move |_| {
+                debug!("saving geometry");
+                WINDOW_GEOMETRY.with(|g| g.borrow().save());
+                // We're quitting, so that's the last chance to remember where we were in an episode
+                let state = app_model.get_state();
+                if let Some((id, position)) = state.playback.episode_progress() {
+                    set_episode_position(&id, position);
+                }
+                // Or in general, in case the last position sync is a bit old
+                if let Some(saved) = SavedPlayback::from_playback(&state.playback) {
+                    saved.save();
+                }
+            }
+        ));
 
         Self {
             initial_window_geometry,
             window,
+            window_stack,
+            offline_banner,
         }
     }
 
@@ -65,11 +98,44 @@ impl MainWindow {
         self.window.present();
     }
 
+    // Also brings the window back if it was hidden to keep playing in the background
     fn raise(&self) {
+        self.window.set_visible(true);
         self.window.present();
     }
 
+    // Same as using the app menu
+    fn quit(&self) {
+        if let Some(app) = self.window.application() {
+            app.activate_action("quit", None);
+        }
+    }
+
+    // Swaps the whole window for the mini player (or back), and goes back to the size we had before.
+    // Note: there's no always-on-top with GTK4 (it's up to the compositor), so we don't even try.
+    fn toggle_mini_player(&self) {
+        if MINI_PLAYER.get() {
+            let geometry = WINDOW_GEOMETRY.with(|g| g.borrow().clone());
+            MINI_PLAYER.set(false);
+            self.window_stack.set_visible_child_name("full");
+            self.window
+                .set_default_size(geometry.width, geometry.height);
+            if geometry.is_maximized {
+                self.window.maximize();
+            }
+        } else {
+            MINI_PLAYER.set(true);
+            self.window.unmaximize();
+            self.window_stack.set_visible_child_name("mini");
+            self.window
+                .set_default_size(MINI_PLAYER_WIDTH, MINI_PLAYER_HEIGHT);
+        }
+    }
+
     fn save_window_geometry<W: GtkWindowExt>(window: &W) {
+        if MINI_PLAYER.get() {
+            return;
+        }
         let (width, height) = window.default_size();
         let is_maximized = window.is_maximized();
         WINDOW_GEOMETRY.with(|g| {
@@ -88,6 +154,12 @@ impl EventListener for MainWindow {
         match event {
             AppEvent::Started => self.start(),
             AppEvent::Raised => self.raise(),
+            AppEvent::QuitRequested => self.quit(),
+            AppEvent::MiniPlayerToggled => self.toggle_mini_player(),
+            // Dropped once we're logged in again, which happens by itself when the network is back
+            AppEvent::LoginEvent(LoginEvent::OfflineChanged(offline)) => {
+                self.offline_banner.set_revealed(*offline)
+            }
             _ => {}
         }
     }
@@ -7,6 +7,9 @@ pub struct Credentials {
     pub access_token: String,
     pub refresh_token: String,
     pub token_expiry_time: Option<SystemTime>,
+    // The custom client ID the token was obtained with (tokens only work with it), if any
+    #[serde(default)]
+    pub client_id: Option<String>,
 }
 
 impl Credentials {
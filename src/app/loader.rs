@@ -5,6 +5,7 @@ use isahc::{AsyncBody, AsyncReadResponseExt, HttpClient, Response};
 use std::collections::hash_map::DefaultHasher;
 use std::hash::Hasher;
 use std::io::{Error, ErrorKind, Write};
+use std::path::PathBuf;
 
 // A wrapper to be able to implement the Write trait on a PixbufLoader
 struct LocalPixbufLoader<'a>(&'a PixbufLoader);
@@ -54,6 +55,36 @@ impl ImageLoader {
         client.get_async(url).await.ok()
     }
 
+    // Makes sure an image is in the cache and returns its path, without downloading it again if it is
+    pub async fn load_remote_file(&self, url: &str, ext: &str) -> Option<PathBuf> {
+        let resource = Self::resource_for(url, ext);
+        let path = self.cache.cache_path(&resource[..]);
+        // An error here most likely means we can't read the file either, so download it again
+        if !tokio::fs::try_exists(&path).await.unwrap_or(false) {
+            let mut resp = Self::get_image(url).await?;
+            let mut buffer = vec![];
+            resp.copy_to(&mut buffer).await.ok()?;
+            self.cache
+                .write_cache_file(&resource[..], &buffer[..], CacheExpiry::Never)
+                .await
+                .ok()?;
+        }
+        Some(path)
+    }
+
+    // The path of an image if it was downloaded already, without ever downloading it
+    pub fn cached_file(&self, url: &str, ext: &str) -> Option<PathBuf> {
+        let resource = Self::resource_for(url, ext);
+        Some(self.cache.cache_path(&resource[..])).filter(|path| path.exists())
+    }
+
+    // Only looks in the cache, so it can be used without waiting: nothing is returned if the image was never downloaded
+    pub fn load_cached(&self, url: &str, ext: &str, width: i32, height: i32) -> Option<Pixbuf> {
+        let resource = Self::resource_for(url, ext);
+        let path = self.cache.cache_path(&resource[..]);
+        Pixbuf::from_file_at_scale(path, width, height, false).ok()
+    }
+
     pub async fn load_remote(
         &self,
         url: &str,
@@ -1,5 +1,9 @@
-use crate::settings::{RiffSettings, StateTracker};
-use crate::{api::CachedSpotifyClient, player::TokenStore};
+use crate::app::state::PlaybackAction;
+use crate::settings::{block_explicit_enabled, RiffSettings, StateTracker};
+use crate::{
+    api::CachedSpotifyClient,
+    player::{RiffOauthClient, TokenStore},
+};
 use futures::channel::mpsc::UnboundedSender;
 use std::rc::Rc;
 use std::sync::Arc;
@@ -25,6 +29,8 @@ pub mod credentials;
 pub mod loader;
 
 pub mod rng;
+
+pub mod session_health;
 pub use rng::LazyRandomIndex;
 
 // Where all the app logic happens
@@ -49,8 +55,10 @@ impl App {
         worker: Worker,
     ) -> Self {
         let state = AppState::new();
-        let token_store = TokenStore::new();
-        let spotify_client = Arc::new(CachedSpotifyClient::new(token_store.clone()));
+        let oauth_client = Arc::new(RiffOauthClient::new(TokenStore::new()));
+        crate::api::set_api_cache_limit(crate::settings::api_cache_limit());
+        crate::api::set_proxy(settings.player_settings.proxy.as_deref());
+        let spotify_client = Arc::new(CachedSpotifyClient::new(oauth_client.clone()));
         let model = Rc::new(AppModel::new(state, spotify_client));
 
         // Non widget components
@@ -60,10 +68,11 @@ impl App {
                 &settings,
                 Box::new(ActionDispatcherImpl::new(sender.clone(), worker.clone())),
                 sender.clone(),
-                token_store,
+                oauth_client,
             ),
-            Box::new(StateTracker::new_from_gsettings()),
+            Box::new(StateTracker::new_from_gsettings(Rc::clone(&model))),
             App::make_dbus(Rc::clone(&model), sender.clone()),
+            App::make_scrobbler(Rc::clone(&model), sender.clone()),
         ];
 
         Self {
@@ -94,6 +103,16 @@ impl App {
         for action in self.settings.player_settings.actions() {
             sender.unbounded_send(action).unwrap();
         }
+        sender
+            .unbounded_send(PlaybackAction::SetBlockExplicit(block_explicit_enabled()).into())
+            .unwrap();
+        if self.settings.imported_from_spot {
+            sender
+                .unbounded_send(AppAction::ShowNotification(
+                    labels::imported_spot_settings_label(),
+                ))
+                .unwrap();
+        }
 
         // All components that will be available initially
         let mut components: Vec<Box<dyn EventListener>> = vec![
@@ -105,7 +124,13 @@ impl App {
                 dispatcher.box_clone(),
                 worker.clone(),
             ),
-            App::make_login(builder, dispatcher.box_clone()),
+            App::make_mini_player(
+                builder,
+                Rc::clone(model),
+                dispatcher.box_clone(),
+                worker.clone(),
+            ),
+            App::make_login(builder, Rc::clone(model), dispatcher.box_clone()),
             App::make_navigation(
                 builder,
                 Rc::clone(model),
@@ -113,8 +138,18 @@ impl App {
                 worker.clone(),
             ),
             App::make_search_button(builder, dispatcher.box_clone()),
+            App::make_playback_request_handler(Rc::clone(model), dispatcher.box_clone()),
+            App::make_link_handler(Rc::clone(model), dispatcher.box_clone()),
+            App::make_playlist_transfer(
+                builder,
+                Rc::clone(model),
+                dispatcher.box_clone(),
+                worker.clone(),
+            ),
             App::make_user_menu(builder, Rc::clone(model), dispatcher),
             App::make_notification(builder),
+            App::make_track_notifier(builder, Rc::clone(model), worker.clone()),
+            App::make_artwork_viewer(builder, Rc::clone(model), worker.clone()),
         ];
 
         self.components.append(&mut components);
@@ -126,7 +161,7 @@ impl App {
         settings: &RiffSettings,
         dispatcher: Box<dyn ActionDispatcher>,
         sender: UnboundedSender<AppAction>,
-        token_store: TokenStore,
+        oauth_client: Arc<RiffOauthClient>,
     ) -> Box<impl EventListener> {
         let api = app_model.get_spotify();
         Box::new(PlayerNotifier::new(
@@ -136,7 +171,7 @@ impl App {
             crate::player::start_player_service(
                 settings.player_settings.clone(),
                 sender.clone(),
-                token_store,
+                oauth_client,
             ),
             // or with a Spotify Connect device
             crate::connect::start_connect_server(api, sender),
@@ -151,13 +186,29 @@ impl App {
         Box::new(crate::dbus::start_dbus_server(app_model, sender))
     }
 
+    // Scrobbles to Last.fm on its own thread
+    fn make_scrobbler(
+        app_model: Rc<AppModel>,
+        sender: UnboundedSender<AppAction>,
+    ) -> Box<impl EventListener> {
+        Box::new(crate::scrobbler::start_scrobbler(app_model, sender))
+    }
+
     fn make_window(
         settings: &RiffSettings,
         builder: &gtk::Builder,
         app_model: Rc<AppModel>,
     ) -> Box<impl EventListener> {
         let window: libadwaita::ApplicationWindow = builder.object("window").unwrap();
-        Box::new(MainWindow::new(settings.window.clone(), app_model, window))
+        let window_stack: gtk::Stack = builder.object("window_stack").unwrap();
+        let offline_banner: libadwaita::Banner = builder.object("offline_banner").unwrap();
+        Box::new(MainWindow::new(
+            settings.window.clone(),
+            app_model,
+            window,
+            window_stack,
+            offline_banner,
+        ))
     }
 
     fn make_navigation(
@@ -182,9 +233,13 @@ impl App {
         ))
     }
 
-    fn make_login(builder: &gtk::Builder, dispatcher: Box<dyn ActionDispatcher>) -> Box<Login> {
+    fn make_login(
+        builder: &gtk::Builder,
+        app_model: Rc<AppModel>,
+        dispatcher: Box<dyn ActionDispatcher>,
+    ) -> Box<Login> {
         let parent: gtk::Window = builder.object("window").unwrap();
-        let model = LoginModel::new(dispatcher);
+        let model = LoginModel::new(app_model, dispatcher);
         Box::new(Login::new(parent, model))
     }
 
@@ -213,6 +268,20 @@ impl App {
         ))
     }
 
+    fn make_mini_player(
+        builder: &gtk::Builder,
+        app_model: Rc<AppModel>,
+        dispatcher: Box<dyn ActionDispatcher>,
+        worker: Worker,
+    ) -> Box<impl EventListener> {
+        let model = PlaybackModel::new(app_model, dispatcher);
+        Box::new(MiniPlayer::new(
+            model,
+            builder.object("mini_player").unwrap(),
+            worker,
+        ))
+    }
+
     fn make_search_button(
         builder: &gtk::Builder,
         dispatcher: Box<dyn ActionDispatcher>,
@@ -228,7 +297,7 @@ impl App {
         dispatcher: Box<dyn ActionDispatcher>,
     ) -> Box<UserMenu> {
         let parent: gtk::Window = builder.object("window").unwrap();
-        let settings_model = SettingsModel::new(app_model.clone(), dispatcher.box_clone());
+        let settings_model = SettingsModel::new(Rc::clone(&app_model), dispatcher.box_clone());
         let settings = Settings::new(parent.clone(), settings_model);
 
         let button: gtk::MenuButton = builder.object("user").unwrap();
@@ -243,6 +312,55 @@ impl App {
         Box::new(Notification::new(toast_overlay))
     }
 
+    fn make_track_notifier(
+        builder: &gtk::Builder,
+        app_model: Rc<AppModel>,
+        worker: Worker,
+    ) -> Box<TrackNotifier> {
+        let window: gtk::Window = builder.object("window").unwrap();
+        Box::new(TrackNotifier::new(app_model, window, worker))
+    }
+
+    fn make_artwork_viewer(
+        builder: &gtk::Builder,
+        app_model: Rc<AppModel>,
+        worker: Worker,
+    ) -> Box<ArtworkViewer> {
+        let window: gtk::Window = builder.object("window").unwrap();
+        Box::new(ArtworkViewer::new(app_model, window, worker))
+    }
+
+    fn make_playlist_transfer(
+        builder: &gtk::Builder,
+        app_model: Rc<AppModel>,
+        dispatcher: Box<dyn ActionDispatcher>,
+        worker: Worker,
+    ) -> Box<PlaylistTransfer> {
+        let window: gtk::Window = builder.object("window").unwrap();
+        let toast_overlay: libadwaita::ToastOverlay = builder.object("main").unwrap();
+        Box::new(PlaylistTransfer::new(
+            app_model,
+            dispatcher,
+            window,
+            toast_overlay,
+            worker,
+        ))
+    }
+
+    fn make_playback_request_handler(
+        app_model: Rc<AppModel>,
+        dispatcher: Box<dyn ActionDispatcher>,
+    ) -> Box<PlaybackRequestHandler> {
+        Box::new(PlaybackRequestHandler::new(app_model, dispatcher))
+    }
+
+    fn make_link_handler(
+        app_model: Rc<AppModel>,
+        dispatcher: Box<dyn ActionDispatcher>,
+    ) -> Box<LinkHandler> {
+        Box::new(LinkHandler::new(app_model, dispatcher))
+    }
+
     // Main handler called in a loop
     fn handle(&mut self, action: AppAction) {
         let starting = matches!(&action, &AppAction::Start);
@@ -254,6 +372,7 @@ impl App {
         // (AppAction::Start is special and is used to setup the initial components)
         if !events.is_empty() && starting {
             self.add_ui_components();
+            debug!("Started after {:?}", crate::PROCESS_START.elapsed());
         }
 
         // ...and notify every component that we know.
@@ -61,10 +61,33 @@ pub struct AlbumRef {
     pub name: String,
 }
 
-#[derive(Clone, Debug)]
+// Only the field matching the category that was searched is filled
+#[derive(Clone, Debug, Default)]
 pub struct SearchResults {
     pub albums: Vec<AlbumDescription>,
     pub artists: Vec<ArtistSummary>,
+    pub tracks: Vec<SongDescription>,
+    pub playlists: Vec<PlaylistDescription>,
+}
+
+// Each tab of the search results is its own query
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SearchCategory {
+    Tracks,
+    Albums,
+    Artists,
+    Playlists,
+}
+
+impl SearchCategory {
+    pub fn api_value(self) -> &'static str {
+        match self {
+            Self::Tracks => "track",
+            Self::Albums => "album",
+            Self::Artists => "artist",
+            Self::Playlists => "playlist",
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -74,6 +97,8 @@ pub struct AlbumDescription {
     pub artists: Vec<ArtistRef>,
     pub release_date: Option<String>,
     pub art: Option<String>,
+    // Highest resolution we know of, for the artwork viewer
+    pub large_art: Option<String>,
     pub songs: SongBatch,
     pub is_liked: bool,
 }
@@ -148,6 +173,11 @@ pub struct SongDescription {
     pub album: AlbumRef,
     pub duration: u32,
     pub art: Option<String>,
+    // When it was added to the playlist (or the liked songs), as an ISO 8601 date
+    pub added_at: Option<String>,
+    pub explicit: bool,
+    // Not available in the user's country, for instance
+    pub is_playable: bool,
 }
 
 impl SongDescription {
@@ -158,6 +188,20 @@ impl SongDescription {
             .collect::<Vec<String>>()
             .join(", ")
     }
+
+    // Podcast episodes go through the same queue as tracks
+    pub fn is_episode(&self) -> bool {
+        self.uri.starts_with("spotify:episode:")
+    }
+
+    // For the find-as-you-type filter, expects a lowercase query
+    pub fn matches_filter(&self, query: &str) -> bool {
+        self.title.to_lowercase().contains(query)
+            || self
+                .artists
+                .iter()
+                .any(|a| a.name.to_lowercase().contains(query))
+    }
 }
 
 impl Hash for SongDescription {
@@ -170,6 +214,8 @@ impl Hash for SongDescription {
 pub struct SongState {
     pub is_playing: bool,
     pub is_selected: bool,
+    // In the user's Liked Songs
+    pub is_saved: bool,
 }
 
 // A batch of SONGS
@@ -239,6 +285,103 @@ pub struct ArtistSummary {
     pub photo: Option<String>,
 }
 
+#[derive(Clone, Debug)]
+pub struct FollowedArtistsPage {
+    pub artists: Vec<ArtistSummary>,
+    // Where to resume from to get the next batch, if there's any left
+    pub next_cursor: Option<String>,
+}
+
+// How far back the user's top tracks and artists go
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TopRange {
+    // About 4 weeks
+    Short,
+    // About 6 months
+    #[default]
+    Medium,
+    // Everything since the account was created
+    Long,
+}
+
+impl TopRange {
+    pub fn api_value(self) -> &'static str {
+        match self {
+            Self::Short => "short_term",
+            Self::Medium => "medium_term",
+            Self::Long => "long_term",
+        }
+    }
+}
+
+// What the user played tracks from recently (rather than the tracks themselves)
+#[derive(Clone, Debug)]
+pub enum RecentlyPlayed {
+    Album(AlbumDescription),
+    Playlist(PlaylistDescription),
+}
+
+impl RecentlyPlayed {
+    pub fn id(&self) -> &str {
+        match self {
+            Self::Album(album) => &album.id,
+            Self::Playlist(playlist) => &playlist.id,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ShowDescription {
+    pub id: String,
+    pub title: String,
+    pub publisher: String,
+    pub description: String,
+    pub art: Option<String>,
+    // Only the first page of them
+    pub episodes: Vec<EpisodeDescription>,
+}
+
+#[derive(Clone, Debug)]
+pub struct EpisodeDescription {
+    pub id: String,
+    pub uri: String,
+    pub title: String,
+    pub description: String,
+    pub release_date: Option<String>,
+    // In milliseconds, like the resume position (episodes can easily last hours)
+    pub duration: u32,
+    // Where Spotify says the user left off
+    pub resume_position: Option<u32>,
+    pub fully_played: bool,
+    pub art: Option<String>,
+    pub explicit: bool,
+}
+
+// The show stands in for both the artist and the album, so that the episode shows up as "title - show" when playing
+impl From<(&EpisodeDescription, &ShowDescription)> for SongDescription {
+    fn from((episode, show): (&EpisodeDescription, &ShowDescription)) -> Self {
+        Self {
+            id: episode.id.clone(),
+            track_number: None,
+            uri: episode.uri.clone(),
+            title: episode.title.clone(),
+            artists: vec![ArtistRef {
+                id: show.id.clone(),
+                name: show.title.clone(),
+            }],
+            album: AlbumRef {
+                id: show.id.clone(),
+                name: show.title.clone(),
+            },
+            duration: episode.duration,
+            art: episode.art.clone().or_else(|| show.art.clone()),
+            added_at: None,
+            explicit: episode.explicit,
+            is_playable: true,
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct UserDescription {
     pub id: String,
@@ -295,6 +438,9 @@ mod tests {
             duration: 1000,
             art: None,
             track_number: None,
+            added_at: None,
+            explicit: false,
+            is_playable: true,
         }
     }
 
@@ -310,4 +456,19 @@ mod tests {
         assert_eq!(&batches.get(0).unwrap().songs.get(0).unwrap().id, "1");
         assert_eq!(&batches.get(1).unwrap().songs.get(0).unwrap().id, "3");
     }
+
+    #[test]
+    fn filter_song() {
+        let mut song = song("1");
+        song.artists = vec![ArtistRef {
+            id: "".to_string(),
+            name: "Some Artist".to_string(),
+        }];
+
+        assert!(song.matches_filter(""));
+        assert!(song.matches_filter("itl"));
+        assert!(song.matches_filter("some art"));
+        assert!(!song.matches_filter("Title"));
+        assert!(!song.matches_filter("other"));
+    }
 }
@@ -55,6 +55,46 @@ impl From<PlaylistDescription> for AlbumModel {
     }
 }
 
+impl From<&RecentlyPlayed> for AlbumModel {
+    fn from(played: &RecentlyPlayed) -> Self {
+        match played {
+            RecentlyPlayed::Album(album) => album.into(),
+            RecentlyPlayed::Playlist(playlist) => playlist.into(),
+        }
+    }
+}
+
+// Shown along with albums, with the cover of the one it's from
+impl From<&SongDescription> for AlbumModel {
+    fn from(song: &SongDescription) -> Self {
+        AlbumModel::new(
+            &song.artists_name(),
+            &song.title,
+            None,
+            song.art.as_ref(),
+            &song.id,
+        )
+    }
+}
+
+impl From<&ShowDescription> for AlbumModel {
+    fn from(show: &ShowDescription) -> Self {
+        AlbumModel::new(
+            &show.publisher,
+            &show.title,
+            None,
+            show.art.as_ref(),
+            &show.id,
+        )
+    }
+}
+
+impl From<&ArtistSummary> for ArtistModel {
+    fn from(artist: &ArtistSummary) -> Self {
+        ArtistModel::new(&artist.name, &artist.photo, &artist.id)
+    }
+}
+
 impl From<SongDescription> for SongModel {
     fn from(song: SongDescription) -> Self {
         SongModel::new(song)
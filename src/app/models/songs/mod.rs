@@ -1,5 +1,6 @@
 // The underlying data structure for a list of songs
 mod support;
+pub use support::SongSort;
 
 // A GObject wrapper around that list
 mod song_list_model;
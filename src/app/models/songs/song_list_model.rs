@@ -137,10 +137,18 @@ impl SongListModel {
         self.inner().last_batch()
     }
 
+    pub fn refresh_batch(&mut self, song_batch: &SongBatch) -> bool {
+        self.inner_mut().refresh_batch(song_batch)
+    }
+
     pub fn needed_batch_for(&self, i: usize) -> Option<Batch> {
         self.inner().needed_batch_for(i)
     }
 
+    pub fn first_missing_batch(&self) -> Option<Batch> {
+        self.inner().first_missing_batch()
+    }
+
     pub fn partial_len(&self) -> usize {
         self.inner().partial_len()
     }
@@ -159,6 +167,19 @@ impl SongListModel {
         SongListModelPending::new(Some(range), self)
     }
 
+    pub fn sort(&self) -> SongSort {
+        self.inner().sort()
+    }
+
+    pub fn set_sort(&mut self, sort: SongSort) -> SongListModelPending {
+        let change = self.inner_mut().set_sort(sort);
+        SongListModelPending::new(change, self)
+    }
+
+    pub fn find_display_index(&self, song_id: &str) -> Option<usize> {
+        self.inner().find_display_index(song_id)
+    }
+
     pub fn find_index(&self, song_id: &str) -> Option<usize> {
         self.inner().find_index(song_id)
     }
@@ -178,6 +199,11 @@ impl SongListModel {
         SongListModelPending::new(swap, self)
     }
 
+    pub fn move_to(&mut self, from: usize, to: usize) -> SongListModelPending {
+        let change = self.inner_mut().move_to(from, to);
+        SongListModelPending::new(change, self)
+    }
+
     pub fn clear(&mut self) -> SongListModelPending {
         let removed = self.inner_mut().clear();
         SongListModelPending::new(Some(removed), self)
@@ -27,6 +27,10 @@ impl SongModel {
         self.set_property("selected", is_selected);
     }
 
+    pub fn set_saved(&self, is_saved: bool) {
+        self.set_property("saved", is_saved);
+    }
+
     pub fn get_playing(&self) -> bool {
         self.property("playing")
     }
@@ -87,10 +91,26 @@ impl SongModel {
         );
     }
 
+    pub fn bind_saved(&self, o: &impl ObjectType, property: &str) {
+        self.imp().push_binding(
+            self.bind_property("saved", o, property)
+                .flags(glib::BindingFlags::DEFAULT | glib::BindingFlags::SYNC_CREATE)
+                .build(),
+        );
+    }
+
     pub fn unbind_all(&self) {
         self.imp().unbind_all(self);
     }
 
+    // Same object (so the rows showing it stay as they are), with up to date details
+    pub fn set_description(&self, song: SongDescription) {
+        self.imp().song.replace(Some(song));
+        for property in ["index", "title", "artist", "duration", "art"] {
+            self.notify(property);
+        }
+    }
+
     pub fn description(&self) -> impl Deref<Target = SongDescription> + '_ {
         Ref::map(self.imp().song.borrow(), |s| {
             s.as_ref().expect("song set at constructor")
@@ -150,7 +170,7 @@ mod imp {
     }
 
     lazy_static! {
-        static ref PROPERTIES: [glib::ParamSpec; 8] = [
+        static ref PROPERTIES: [glib::ParamSpec; 9] = [
             glib::ParamSpecString::builder("id").read_only().build(),
             glib::ParamSpecUInt::builder("index").read_only().build(),
             glib::ParamSpecString::builder("title").read_only().build(),
@@ -167,6 +187,9 @@ mod imp {
             glib::ParamSpecBoolean::builder("selected")
                 .readwrite()
                 .build(),
+            glib::ParamSpecBoolean::builder("saved")
+                .readwrite()
+                .build(),
         ];
     }
 
@@ -181,20 +204,27 @@ mod imp {
                     let is_playing = value
                         .get()
                         .expect("type conformity checked by `Object::set_property`");
-                    let SongState { is_selected, .. } = self.state.get();
                     self.state.set(SongState {
                         is_playing,
-                        is_selected,
+                        ..self.state.get()
                     });
                 }
                 "selected" => {
                     let is_selected = value
                         .get()
                         .expect("type conformity checked by `Object::set_property`");
-                    let SongState { is_playing, .. } = self.state.get();
                     self.state.set(SongState {
-                        is_playing,
                         is_selected,
+                        ..self.state.get()
+                    });
+                }
+                "saved" => {
+                    let is_saved = value
+                        .get()
+                        .expect("type conformity checked by `Object::set_property`");
+                    self.state.set(SongState {
+                        is_saved,
+                        ..self.state.get()
                     });
                 }
                 _ => unimplemented!(),
@@ -248,6 +278,7 @@ mod imp {
                     .to_value(),
                 "playing" => self.state.get().is_playing.to_value(),
                 "selected" => self.state.get().is_selected.to_value(),
+                "saved" => self.state.get().is_saved.to_value(),
                 _ => unimplemented!(),
             }
         }
@@ -1,3 +1,4 @@
+use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::convert::{TryFrom, TryInto};
 
@@ -117,11 +118,70 @@ impl ListRangeUpdate {
     }
 }
 
+// How a list of songs is displayed, without touching the order of the source itself
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SongSort {
+    // Whatever order the source gives us (e.g. the playlist order)
+    #[default]
+    Custom,
+    Title,
+    Artist,
+    Album,
+    Duration,
+    DateAdded,
+}
+
+impl SongSort {
+    pub fn id(self) -> &'static str {
+        match self {
+            Self::Custom => "custom",
+            Self::Title => "title",
+            Self::Artist => "artist",
+            Self::Album => "album",
+            Self::Duration => "duration",
+            Self::DateAdded => "date_added",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        match id {
+            "custom" => Some(Self::Custom),
+            "title" => Some(Self::Title),
+            "artist" => Some(Self::Artist),
+            "album" => Some(Self::Album),
+            "duration" => Some(Self::Duration),
+            "date_added" => Some(Self::DateAdded),
+            _ => None,
+        }
+    }
+
+    fn compare(self, a: &SongDescription, b: &SongDescription) -> Ordering {
+        match self {
+            Self::Custom => Ordering::Equal,
+            Self::Title => a.title.to_lowercase().cmp(&b.title.to_lowercase()),
+            Self::Artist => a
+                .artists_name()
+                .to_lowercase()
+                .cmp(&b.artists_name().to_lowercase()),
+            Self::Album => a
+                .album
+                .name
+                .to_lowercase()
+                .cmp(&b.album.name.to_lowercase())
+                .then(a.track_number.cmp(&b.track_number)),
+            Self::Duration => a.duration.cmp(&b.duration),
+            // ISO 8601 dates, so comparing strings works just fine
+            Self::DateAdded => a.added_at.cmp(&b.added_at),
+        }
+    }
+}
+
 // A list of songs that supports
 // - batch loading (with non contiguous batches if songs are accessed in random order)
 // - O(1) time access to a song by its id
 // - manually adding content (not batched), when managing a queue for instance
 // - tracking the affected range after a mutation
+// - being displayed sorted, while batches keep following the order of the source
 //
 // Note: the mutated ranges are given in terms of LOADED tracks. The theoretical size of the list is not accounted for.
 // This is to ease the work of updating the UI: we want to know what loaded/visible elements have moved around.
@@ -139,6 +199,9 @@ pub struct SongList {
     // Why not a Vec? We could have batch 1, 2, NOT 3, then 4
     batches: HashMap<usize, Vec<String>>,
     indexed_songs: HashMap<String, SongModel>,
+    sort: SongSort,
+    // Loaded songs in display order, only maintained when sorted
+    sorted_ids: Vec<String>,
 }
 
 impl SongList {
@@ -150,6 +213,8 @@ impl SongList {
             last_batch_key: 0,
             batches: Default::default(),
             indexed_songs: Default::default(),
+            sort: SongSort::Custom,
+            sorted_ids: vec![],
         }
     }
 
@@ -157,10 +222,84 @@ impl SongList {
         self.batch_size
     }
 
-    pub fn iter(&self) -> impl Iterator<Item = &SongModel> {
+    pub fn sort(&self) -> SongSort {
+        self.sort
+    }
+
+    fn is_sorted(&self) -> bool {
+        self.sort != SongSort::Custom
+    }
+
+    // Iterate over loaded songs, in display order
+    pub fn iter(&self) -> Box<dyn Iterator<Item = &SongModel> + '_> {
         let indexed_songs = &self.indexed_songs;
-        self.iter_ids_from(0)
-            .filter_map(move |(_, id)| indexed_songs.get(id))
+        if self.is_sorted() {
+            Box::new(
+                self.sorted_ids
+                    .iter()
+                    .filter_map(move |id| indexed_songs.get(id)),
+            )
+        } else {
+            Box::new(
+                self.iter_ids_from(0)
+                    .filter_map(move |(_, id)| indexed_songs.get(id)),
+            )
+        }
+    }
+
+    fn compare_ids(&self, a: &str, b: &str) -> Ordering {
+        match (self.indexed_songs.get(a), self.indexed_songs.get(b)) {
+            (Some(a), Some(b)) => self.sort.compare(&a.description(), &b.description()),
+            _ => Ordering::Equal,
+        }
+    }
+
+    fn sort_all(&mut self) {
+        let mut ids: Vec<String> = self.iter_ids_from(0).map(|(_, id)| id.clone()).collect();
+        // Stable, so that equal songs keep the order of the source
+        ids.sort_by(|a, b| self.compare_ids(a, b));
+        self.sorted_ids = ids;
+    }
+
+    // Puts newly loaded songs where they belong (after the songs they're equal to)
+    fn insert_all_sorted(&mut self, ids: Vec<String>) -> ListRangeUpdate {
+        for id in ids.iter() {
+            let position = self
+                .sorted_ids
+                .partition_point(|other| self.compare_ids(other, id) != Ordering::Greater);
+            self.sorted_ids.insert(position, id.clone());
+        }
+
+        // New songs are spread around, the affected range goes from the first one to the last one
+        let first = self.sorted_ids.iter().position(|id| ids.contains(id));
+        let last = self.sorted_ids.iter().rposition(|id| ids.contains(id));
+        match (first, last) {
+            (Some(first), Some(last)) => {
+                let affected = last - first + 1;
+                ListRangeUpdate(first as i32, (affected - ids.len()) as i32, affected as i32)
+            }
+            _ => ListRangeUpdate::inserted(0, 0),
+        }
+    }
+
+    // When the whole list may have moved around: everything that was loaded is replaced
+    fn resort(&mut self, previous_len: usize) -> ListRangeUpdate {
+        self.sort_all();
+        ListRangeUpdate(0, previous_len as i32, self.total_loaded as i32)
+    }
+
+    pub fn set_sort(&mut self, sort: SongSort) -> Option<ListRangeUpdate> {
+        if sort == self.sort {
+            return None;
+        }
+        self.sort = sort;
+        if self.is_sorted() {
+            Some(self.resort(self.total_loaded))
+        } else {
+            self.sorted_ids.clear();
+            let len = self.total_loaded as i32;
+            Some(ListRangeUpdate(0, len, len))
+        }
     }
 
     // How many songs we actually have at the moment
@@ -197,6 +336,11 @@ impl SongList {
             .map(|(pos, _)| pos)
     }
 
+    // Same, but among the loaded songs in display order (what index_continuous expects)
+    pub fn find_display_index(&self, song_id: &str) -> Option<usize> {
+        self.iter().position(|s| s.description().id == song_id)
+    }
+
     // Iterate over batches (in a given batch range), returning a tuple with the index of a song and its id
     fn iter_range(&self, a: usize, b: usize) -> impl Iterator<Item = (usize, &'_ String)> {
         let batch_size = self.batch_size;
@@ -227,26 +371,42 @@ impl SongList {
 
     pub fn clear(&mut self) -> ListRangeUpdate {
         let len = self.partial_len();
+        let sort = self.sort;
         *self = Self::new_sized(self.batch_size);
+        self.sort = sort;
         ListRangeUpdate::removed(0, len)
     }
 
     pub fn remove(&mut self, ids: &[String]) -> ListRangeUpdate {
         let len = self.total_loaded;
+        // Songs before the first removed one don't move: leaving them out of the affected range
+        // lets the view keep its focus and scroll position
+        let first_removed = self
+            .iter_ids_from(0)
+            .position(|(_, s)| ids.contains(s))
+            .unwrap_or(len);
+        let mut kept = 0;
         let mut batches = HashMap::<usize, Vec<String>>::default();
         self.iter_ids_from(0)
             .filter(|(_, s)| !ids.contains(s))
             // Removing is expensive, we have to recreate all batches
             .for_each(|(_, next)| {
+                kept += 1;
                 Self::batches_add(&mut batches, self.batch_size, next);
             });
         self.last_batch_key = batches.len().saturating_sub(1);
         self.batches = batches;
-        let removed = ids.len();
+        let removed = len.saturating_sub(kept);
         self.total = self.total.saturating_sub(removed);
-        self.total_loaded = self.total_loaded.saturating_sub(removed);
-        // Lazy computation of the affected range, basically assume everything has changed
-        ListRangeUpdate(0, len as i32, self.total_loaded as i32)
+        self.total_loaded = kept;
+        if self.is_sorted() {
+            return self.resort(len);
+        }
+        ListRangeUpdate(
+            first_removed as i32,
+            (len - first_removed) as i32,
+            kept.saturating_sub(first_removed) as i32,
+        )
     }
 
     pub fn append(&mut self, songs: Vec<SongDescription>) -> ListRangeUpdate {
@@ -261,6 +421,9 @@ impl SongList {
                 .insert(song.id.clone(), SongModel::new(song));
         }
         self.last_batch_key = self.batches.len().saturating_sub(1);
+        if self.is_sorted() {
+            return self.resort(self.total_loaded - songs_len);
+        }
         ListRangeUpdate::inserted(insertion_start, songs_len)
     }
 
@@ -283,6 +446,9 @@ impl SongList {
         self.total_loaded = self.total_loaded.saturating_add(songs_len);
         self.last_batch_key = batches.len().saturating_sub(1);
         self.batches = batches;
+        if self.is_sorted() {
+            return self.resort(self.total_loaded - songs_len);
+        }
 
         // But it's a bit easier to computer the visibly affected range :)
         ListRangeUpdate::inserted(insertion_start, songs_len)
@@ -321,7 +487,7 @@ impl SongList {
 
         let insertion_start = self.estimated_len(index);
         let len = songs.len();
-        let ids = songs
+        let ids: Vec<String> = songs
             .into_iter()
             .map(|song| {
                 let song_id = song.id.clone();
@@ -331,11 +497,15 @@ impl SongList {
             })
             .collect();
 
-        self.batches.insert(index, ids);
+        self.batches.insert(index, ids.clone());
         self.total = batch.total;
         self.total_loaded += len;
         self.last_batch_key = usize::max(self.last_batch_key, index);
 
+        if self.is_sorted() {
+            return Some(self.insert_all_sorted(ids));
+        }
+
         Some(ListRangeUpdate::inserted(insertion_start, len))
     }
 
@@ -348,7 +518,8 @@ impl SongList {
     }
 
     pub fn swap(&mut self, a: usize, b: usize) -> Option<ListRangeUpdate> {
-        if a == b {
+        // Moving songs around in a sorted list makes no sense
+        if a == b || self.is_sorted() {
             return None;
         }
         let a_value = self.index_mut(a).map(std::mem::take);
@@ -364,6 +535,27 @@ impl SongList {
         Some(ListRangeUpdate::updated(a).merge(ListRangeUpdate::updated(b)))
     }
 
+    // Moves the song at `from` to `to`, the ones in between are shifted by one
+    pub fn move_to(&mut self, from: usize, to: usize) -> Option<ListRangeUpdate> {
+        if from == to || self.is_sorted() || self.index(from).is_none() || self.index(to).is_none()
+        {
+            return None;
+        }
+        // One step at a time, like a bubble
+        if from < to {
+            (from..to).for_each(|i| {
+                self.swap(i, i + 1);
+            });
+        } else {
+            (to..from).rev().for_each(|i| {
+                self.swap(i, i + 1);
+            });
+        }
+        let start = usize::min(from, to);
+        let len = from.abs_diff(to) + 1;
+        Some(ListRangeUpdate(start as i32, len as i32, len as i32))
+    }
+
     // Get the song at i (if the index is valid AND has been loaded)
     pub fn index(&self, i: usize) -> Option<&SongModel> {
         let batch_size = self.batch_size;
@@ -375,8 +567,11 @@ impl SongList {
             .and_then(move |id| indexed_songs.get(id))
     }
 
-    // Get the i-th loaded song. VERY different!
+    // Get the i-th loaded song (as displayed). VERY different!
     pub fn index_continuous(&self, i: usize) -> Option<&SongModel> {
+        if self.is_sorted() {
+            return self.indexed_songs.get(self.sorted_ids.get(i)?);
+        }
         let batch_size = self.batch_size;
         let bi = i / batch_size;
         let batch = (0..=self.last_batch_key)
@@ -404,6 +599,13 @@ impl SongList {
         }
     }
 
+    // The first batch (in order) that isn't loaded yet, if any
+    pub fn first_missing_batch(&self) -> Option<Batch> {
+        (0..self.total)
+            .step_by(self.batch_size)
+            .find_map(|i| self.needed_batch_for(i))
+    }
+
     // Get the full song batch that contains i
     pub fn song_batch_for(&self, i: usize) -> Option<SongBatch> {
         let total = self.total;
@@ -439,6 +641,27 @@ impl SongList {
     pub fn get(&self, id: &str) -> Option<&SongModel> {
         self.indexed_songs.get(id)
     }
+
+    // When this exact batch (same songs, same order) is already loaded, only the total and the
+    // details of the songs are updated: the list stays as it is, and so does the focus within it
+    pub fn refresh_batch(&mut self, song_batch: &SongBatch) -> bool {
+        let batch = &song_batch.batch;
+        let is_loaded = batch.batch_size == self.batch_size
+            && self
+                .batches
+                .get(&(batch.offset / batch.batch_size))
+                .map(|ids| ids.iter().eq(song_batch.songs.iter().map(|s| &s.id)))
+                .unwrap_or(false);
+        if is_loaded {
+            self.total = batch.total;
+            for song in song_batch.songs.iter() {
+                if let Some(model) = self.indexed_songs.get(&song.id) {
+                    model.set_description(song.clone());
+                }
+            }
+        }
+        is_loaded
+    }
 }
 
 #[cfg(test)]
@@ -469,9 +692,24 @@ mod tests {
             duration: 1000,
             art: None,
             track_number: None,
+            added_at: None,
+            explicit: false,
+            is_playable: true,
         }
     }
 
+    fn titled_batch(id: usize, titles: [&str; 2]) -> SongBatch {
+        let mut batch = batch(id);
+        for (song, title) in batch.songs.iter_mut().zip(titles) {
+            song.title = title.to_string();
+        }
+        batch
+    }
+
+    fn ids(list: &SongList) -> Vec<String> {
+        list.iter().map(|s| s.description().id.clone()).collect()
+    }
+
     fn batch(id: usize) -> SongBatch {
         let offset = id * 2;
         SongBatch {
@@ -595,6 +833,21 @@ mod tests {
         assert_eq!(list.partial_len(), 8);
     }
 
+    // Rows bound to loaded songs must not be touched by a new page, or the view loses its focus
+    #[test]
+    fn test_pages_leave_loaded_songs_alone() {
+        let mut list = SongList::new_from_initial_batch(batch(0));
+        let loaded: Vec<SongModel> = list.iter().cloned().collect();
+
+        let ListRangeUpdate(position, removed, _) = list.add(batch(1)).unwrap();
+        assert_eq!((position, removed), (2, 0));
+        let ListRangeUpdate(position, removed, _) = list.append(vec![song("song4")]);
+        assert_eq!((position, removed), (4, 0));
+
+        let still_loaded: Vec<SongModel> = list.iter().take(2).cloned().collect();
+        assert_eq!(still_loaded, loaded);
+    }
+
     #[test]
     fn test_find_non_contiguous() {
         let mut list = SongList::new_from_initial_batch(batch(0));
@@ -636,6 +889,54 @@ mod tests {
         assert!(list_iter.next().is_none());
     }
 
+    #[test]
+    fn test_remove_with_range() {
+        let mut list = SongList::new_from_initial_batch(batch(0));
+        list.add(batch(1));
+
+        // song0 and song1 are left untouched
+        let range = list.remove(&["song2".to_string()]);
+        assert_eq!(range, ListRangeUpdate(2, 2, 1));
+
+        let range = list.remove(&["song42".to_string()]);
+        assert_eq!(range, ListRangeUpdate(3, 0, 0));
+        assert_eq!(list.partial_len(), 3);
+    }
+
+    #[test]
+    fn test_refresh_batch() {
+        let mut list = SongList::new_from_initial_batch(batch(0));
+        list.add(batch(1));
+        let song0 = list.get("song0").cloned().unwrap();
+
+        let mut refreshed = titled_batch(0, ["Renamed", "Title"]);
+        refreshed.batch.total = 12;
+        assert!(list.refresh_batch(&refreshed));
+        assert_eq!(list.len(), 12);
+        // Same object, up to date
+        assert_eq!(list.get("song0"), Some(&song0));
+        assert_eq!(song0.description().title, "Renamed");
+
+        assert!(!list.refresh_batch(&batch(2)));
+        let mut reordered = batch(0);
+        reordered.songs.reverse();
+        assert!(!list.refresh_batch(&reordered));
+    }
+
+    #[test]
+    fn test_first_missing_batch() {
+        let mut list = SongList::new_from_initial_batch(batch(0));
+        list.add(batch(2));
+        assert_eq!(list.first_missing_batch().map(|b| b.offset), Some(2));
+
+        list.add(batch(1));
+        assert_eq!(list.first_missing_batch().map(|b| b.offset), Some(6));
+
+        list.add(batch(3));
+        list.add(batch(4));
+        assert!(list.first_missing_batch().is_none());
+    }
+
     #[test]
     fn test_batch_for() {
         let mut list = SongList::new_from_initial_batch(batch(0));
@@ -683,4 +984,59 @@ mod tests {
         assert_eq!(list_iter.next().unwrap().description().id, "song0");
         assert!(list_iter.next().is_none());
     }
+
+    #[test]
+    fn test_move_to() {
+        let mut list = SongList::new_sized(2);
+        list.append(vec![
+            song("song0"),
+            song("song1"),
+            song("song2"),
+            song("song3"),
+        ]);
+
+        assert_eq!(list.move_to(3, 1), Some(ListRangeUpdate(1, 3, 3)));
+        assert_eq!(ids(&list), vec!["song0", "song3", "song1", "song2"]);
+
+        assert_eq!(list.move_to(0, 2), Some(ListRangeUpdate(0, 3, 3)));
+        assert_eq!(ids(&list), vec!["song3", "song1", "song0", "song2"]);
+
+        assert_eq!(list.move_to(1, 1), None);
+        assert_eq!(list.move_to(1, 4), None);
+    }
+
+    #[test]
+    fn test_sort() {
+        let mut list = SongList::new_from_initial_batch(titled_batch(0, ["b", "c"]));
+        list.add(titled_batch(1, ["a", "d"]));
+
+        let range = list.set_sort(SongSort::Title);
+        assert_eq!(range, Some(ListRangeUpdate(0, 4, 4)));
+        assert_eq!(ids(&list), vec!["song2", "song0", "song1", "song3"]);
+        assert_eq!(list.index_continuous(0).unwrap().description().id, "song2");
+        // Batches are left untouched
+        assert_eq!(list.index(0).unwrap().description().id, "song0");
+
+        assert_eq!(list.set_sort(SongSort::Title), None);
+        assert_eq!(list.find_index("song2"), Some(2));
+        assert_eq!(list.find_display_index("song2"), Some(0));
+        list.set_sort(SongSort::Custom);
+        assert_eq!(ids(&list), vec!["song0", "song1", "song2", "song3"]);
+    }
+
+    #[test]
+    fn test_add_sorted() {
+        let mut list = SongList::new_from_initial_batch(titled_batch(0, ["b", "d"]));
+        list.set_sort(SongSort::Title);
+
+        let range = list.add(titled_batch(1, ["a", "c"]));
+        // [b, d] -> [a, b, c, d]
+        assert_eq!(range, Some(ListRangeUpdate(0, 1, 3)));
+        assert_eq!(ids(&list), vec!["song2", "song0", "song3", "song1"]);
+        assert_eq!(list.partial_len(), 4);
+
+        let range = list.remove(&["song0".to_string()]);
+        assert_eq!(range, ListRangeUpdate(0, 4, 3));
+        assert_eq!(ids(&list), vec!["song2", "song3", "song1"]);
+    }
 }
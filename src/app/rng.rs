@@ -58,6 +58,59 @@ impl LazyRandomIndex {
         self.indices.truncate(size);
     }
 
+    // Some indices are gone (e.g. tracks removed from a playlist), the ones after them are shifted down
+    // What's left keeps the same order, generated or not
+    pub fn remove(&mut self, removed: &[usize]) {
+        let generated_removed = self
+            .indices
+            .iter()
+            .take(self.generated)
+            .filter(|i| removed.contains(i))
+            .count();
+        self.generated -= generated_removed;
+        self.indices = self
+            .indices
+            .iter()
+            .filter(|i| !removed.contains(i))
+            .map(|i| i - removed.iter().filter(|r| *r < i).count())
+            .collect();
+    }
+
+    // Moves whatever is mapped from `from` so that it's mapped from `to`, shifting the mappings in between
+    // `to` has to be generated already (or be the next one to generate, when moving something up)
+    pub fn move_to(&mut self, from: usize, to: usize) {
+        let len = self.indices.len();
+        if from >= len || to >= len || to > self.generated || (from < to && to >= self.generated) {
+            return;
+        }
+        let index = self.indices.remove(from);
+        self.indices.insert(to, index);
+        if from >= self.generated {
+            self.generated += 1;
+        }
+    }
+
+    // The indices were moved around (e.g. tracks reordered in a playlist), the mapping follows them
+    // so that the playing order stays the same
+    pub fn remap<F>(&mut self, f: F)
+    where
+        F: Fn(usize) -> usize,
+    {
+        self.indices.iter_mut().for_each(|i| *i = f(*i));
+    }
+
+    // Everything up to `n` (excluded) is mapped to itself, as if it had been picked in order
+    pub fn reset_in_order(&mut self, n: usize) {
+        let len = self.indices.len();
+        self.indices = (0..len).collect();
+        self.generated = usize::min(n, len);
+    }
+
+    // Where some index is, in the mapping (the reverse of `get`)
+    pub fn position(&self, index: usize) -> Option<usize> {
+        self.indices.iter().position(|i| *i == index)
+    }
+
     // Get the index (for instance in a playlist) of the i-th next element to play
     pub fn get(&self, i: usize) -> Option<usize> {
         if i >= self.generated || i >= self.indices.len() {
@@ -195,4 +248,54 @@ mod tests {
         index.reset_picking_first(2);
         assert_eq!(index.get(0), Some(2));
     }
+
+    #[test]
+    fn test_remove() {
+        let mut index = LazyRandomIndex::from(rng_for_test());
+        index.grow(5);
+        index.next_until(2);
+        let values = [index.get(0), index.get(1), index.get(2)];
+
+        let removed = values[1].unwrap();
+        index.remove(&[removed]);
+
+        // The others are still there, in the same order, shifted down if they came after the removed one
+        let shifted = |v: Option<usize>| v.map(|v| if v > removed { v - 1 } else { v });
+        assert_eq!(index.get(0), shifted(values[0]));
+        assert_eq!(index.get(1), shifted(values[2]));
+        assert_eq!(index.get(2), None);
+
+        index.next_until(3);
+        let mut all = (0..4).filter_map(|i| index.get(i)).collect::<Vec<_>>();
+        all.sort();
+        assert_eq!(all, vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_move_to() {
+        let mut index = LazyRandomIndex::from(rng_for_test());
+        index.grow(5);
+        index.next_until(1);
+        let first = index.get(0);
+
+        // Something that wasn't picked yet comes up right after the first one
+        let not_picked = (0..5).find(|i| Some(*i) != first && Some(*i) != index.get(1));
+        let from = index.position(not_picked.unwrap()).unwrap();
+        index.move_to(from, 1);
+        assert_eq!(index.get(0), first);
+        assert_eq!(index.get(1), not_picked);
+        assert!(index.get(2).is_some());
+    }
+
+    #[test]
+    fn test_reset_in_order() {
+        let mut index = LazyRandomIndex::from(rng_for_test());
+        index.grow(5);
+        index.next_until(4);
+
+        index.reset_in_order(2);
+        assert_eq!(index.get(0), Some(0));
+        assert_eq!(index.get(1), Some(1));
+        assert_eq!(index.get(2), None);
+    }
 }
@@ -0,0 +1,292 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::api::SpotifyApiError;
+
+// A purely local record of how sessions went (nothing is ever sent anywhere),
+// so that people can paste something meaningful in bug reports.
+
+const MAX_RECORDS: usize = 10;
+// Exists while the app is running: finding it at startup means the last session did not exit cleanly
+const MARKER_FILE: &str = "session.running";
+const RECORDS_FILE: &str = "sessions.json";
+
+lazy_static! {
+    static ref SESSION: Mutex<Option<SessionHealth>> = Mutex::new(None);
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct SessionRecord {
+    pub started_at: u64,
+    pub duration: u64,
+    pub clean_exit: bool,
+    pub player_restarts: u32,
+    pub api_failures: BTreeMap<String, u32>,
+}
+
+struct SessionHealth {
+    dir: PathBuf,
+    current: SessionRecord,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn default_dir() -> PathBuf {
+    glib::user_data_dir().join("riff")
+}
+
+impl SessionHealth {
+    fn new(dir: PathBuf, started_at: u64) -> Self {
+        Self {
+            dir,
+            current: SessionRecord {
+                started_at,
+                ..Default::default()
+            },
+        }
+    }
+
+    fn start(&self) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        // The marker holds whatever we knew about the last session before it went away
+        if let Ok(content) = fs::read(self.dir.join(MARKER_FILE)) {
+            if let Ok(record) = serde_json::from_slice::<SessionRecord>(&content) {
+                self.push_record(SessionRecord {
+                    clean_exit: false,
+                    ..record
+                })?;
+            }
+        }
+        self.write_marker()
+    }
+
+    fn write_marker(&self) -> io::Result<()> {
+        fs::write(
+            self.dir.join(MARKER_FILE),
+            serde_json::to_vec(&self.current)?,
+        )
+    }
+
+    fn update(&mut self, now: u64, f: impl FnOnce(&mut SessionRecord)) -> io::Result<()> {
+        f(&mut self.current);
+        self.current.duration = now.saturating_sub(self.current.started_at);
+        self.write_marker()
+    }
+
+    fn finish(&mut self, now: u64) -> io::Result<()> {
+        self.current.duration = now.saturating_sub(self.current.started_at);
+        self.current.clean_exit = true;
+        self.push_record(self.current.clone())?;
+        fs::remove_file(self.dir.join(MARKER_FILE))
+    }
+
+    fn records(&self) -> Vec<SessionRecord> {
+        fs::read(self.dir.join(RECORDS_FILE))
+            .ok()
+            .and_then(|content| serde_json::from_slice(&content).ok())
+            .unwrap_or_default()
+    }
+
+    fn push_record(&self, record: SessionRecord) -> io::Result<()> {
+        let mut records = self.records();
+        records.push(record);
+        let extra = records.len().saturating_sub(MAX_RECORDS);
+        records.drain(..extra);
+        fs::write(self.dir.join(RECORDS_FILE), serde_json::to_vec(&records)?)
+    }
+}
+
+fn failure_class(err: &SpotifyApiError) -> String {
+    match err {
+        SpotifyApiError::InvalidToken | SpotifyApiError::NoToken => "auth".to_string(),
+        SpotifyApiError::NoContent => "no_content".to_string(),
+        SpotifyApiError::RateLimited(_) => "rate_limited".to_string(),
+        SpotifyApiError::BadStatus(status, _) => format!("http_{status}"),
+        SpotifyApiError::ClientError(_) => "network".to_string(),
+        SpotifyApiError::IoError(_) | SpotifyApiError::CacheError(_) => "io".to_string(),
+        SpotifyApiError::ParseError(_) | SpotifyApiError::ConversionError(_) => "parse".to_string(),
+        SpotifyApiError::Other(_) => "other".to_string(),
+    }
+}
+
+fn format_duration(seconds: u64) -> String {
+    let (hours, minutes) = (seconds / 3600, (seconds % 3600) / 60);
+    if hours > 0 {
+        format!("{hours}h{minutes:02}m")
+    } else {
+        format!("{minutes}m")
+    }
+}
+
+fn format_record(record: &SessionRecord) -> String {
+    let started_at = glib::DateTime::from_unix_utc(record.started_at as i64)
+        .and_then(|d| d.format("%Y-%m-%d %H:%M UTC"))
+        .map(|d| d.to_string())
+        .unwrap_or_else(|_| record.started_at.to_string());
+    let exit = if record.clean_exit {
+        "clean"
+    } else {
+        "UNCLEAN"
+    };
+    let failures = if record.api_failures.is_empty() {
+        "none".to_string()
+    } else {
+        record
+            .api_failures
+            .iter()
+            .map(|(class, count)| format!("{class} x{count}"))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    format!(
+        "{started_at} | {} | {exit} | player restarts: {} | API failures: {failures}",
+        format_duration(record.duration),
+        record.player_restarts
+    )
+}
+
+// Not translated on purpose, it's meant to be pasted in bug reports
+pub fn summary(records: &[SessionRecord]) -> String {
+    if records.is_empty() {
+        return "No sessions recorded yet".to_string();
+    }
+    let unclean = records.iter().filter(|r| !r.clean_exit).count();
+    let mut lines = vec![format!(
+        "Last {} sessions, {unclean} unclean exit(s)",
+        records.len()
+    )];
+    lines.extend(records.iter().rev().map(format_record));
+    lines.join("\n")
+}
+
+fn with_session(f: impl FnOnce(&mut SessionHealth) -> io::Result<()>) {
+    if let Some(session) = SESSION.lock().unwrap().as_mut() {
+        if let Err(e) = f(session) {
+            warn!("Could not update session health record: {e}");
+        }
+    }
+}
+
+// Nothing is recorded unless the user opted in
+pub fn start_session(enabled: bool) {
+    if !enabled {
+        return;
+    }
+    let session = SessionHealth::new(default_dir(), now());
+    if let Err(e) = session.start() {
+        warn!("Could not start session health record: {e}");
+        return;
+    }
+    SESSION.lock().unwrap().replace(session);
+}
+
+pub fn end_session() {
+    with_session(|session| session.finish(now()));
+}
+
+pub fn record_player_restart() {
+    with_session(|session| session.update(now(), |r| r.player_restarts += 1));
+}
+
+pub fn record_api_failure(err: &SpotifyApiError) {
+    let class = failure_class(err);
+    with_session(|session| {
+        session.update(now(), |r| *r.api_failures.entry(class).or_insert(0) += 1)
+    });
+}
+
+pub fn last_sessions() -> Vec<SessionRecord> {
+    SessionHealth::new(default_dir(), 0).records()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("riff-session-test-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn test_unclean_exit_is_detected() {
+        let dir = test_dir("unclean");
+
+        let mut crashed = SessionHealth::new(dir.clone(), 1000);
+        crashed.start().unwrap();
+        crashed.update(1060, |r| r.player_restarts += 1).unwrap();
+        // ...and we never call finish()
+
+        let mut session = SessionHealth::new(dir.clone(), 2000);
+        session.start().unwrap();
+        session.finish(2120).unwrap();
+
+        let records = session.records();
+        assert_eq!(records.len(), 2);
+        assert!(!records[0].clean_exit);
+        assert_eq!(records[0].duration, 60);
+        assert_eq!(records[0].player_restarts, 1);
+        assert!(records[1].clean_exit);
+        assert_eq!(records[1].duration, 120);
+        assert!(!dir.join(MARKER_FILE).exists());
+    }
+
+    #[test]
+    fn test_records_are_rotated() {
+        let dir = test_dir("rotation");
+
+        for i in 0..15 {
+            let mut session = SessionHealth::new(dir.clone(), i);
+            session.start().unwrap();
+            session.finish(i + 1).unwrap();
+        }
+
+        let records = SessionHealth::new(dir, 0).records();
+        assert_eq!(records.len(), MAX_RECORDS);
+        assert_eq!(records[0].started_at, 5);
+        assert_eq!(records[MAX_RECORDS - 1].started_at, 14);
+    }
+
+    #[test]
+    fn test_summary() {
+        let mut api_failures = BTreeMap::new();
+        api_failures.insert("http_500".to_string(), 2);
+        api_failures.insert("network".to_string(), 1);
+        let records = vec![
+            SessionRecord {
+                started_at: 0,
+                duration: 3720,
+                clean_exit: true,
+                player_restarts: 0,
+                api_failures: BTreeMap::new(),
+            },
+            SessionRecord {
+                started_at: 86400,
+                duration: 300,
+                clean_exit: false,
+                player_restarts: 2,
+                api_failures,
+            },
+        ];
+
+        assert_eq!(
+            summary(&records),
+            "Last 2 sessions, 1 unclean exit(s)\n\
+            1970-01-02 00:00 UTC | 5m | UNCLEAN | player restarts: 2 | API failures: http_500 x2, network x1\n\
+            1970-01-01 00:00 UTC | 1h02m | clean | player restarts: 0 | API failures: none"
+        );
+        assert_eq!(summary(&[]), "No sessions recorded yet");
+    }
+}
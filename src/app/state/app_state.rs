@@ -1,6 +1,6 @@
 use std::borrow::Cow;
 
-use crate::app::models::{PlaylistDescription, PlaylistSummary};
+use crate::app::models::{FollowedArtistsPage, PlaylistDescription, PlaylistSummary};
 use crate::app::state::{
     browser_state::{BrowserAction, BrowserEvent, BrowserState},
     login_state::{LoginAction, LoginEvent, LoginState},
@@ -9,6 +9,7 @@ use crate::app::state::{
     settings_state::{SettingsAction, SettingsEvent, SettingsState},
     ScreenName, UpdatableState,
 };
+use crate::app::SongsSource;
 
 // It's a big one...
 // All possible actions!
@@ -24,8 +25,18 @@ pub enum AppAction {
     SettingsAction(SettingsAction),
     Start,
     Raise,
+    Quit,
+    ToggleMiniPlayer,
+    RequestPlayback(PlaybackRequest),
+    // A spotify: URI or an open.spotify.com link, to be opened once logged in
+    OpenLink(String),
     ShowNotification(String),
     ViewNowPlaying,
+    // Album id
+    ViewArtwork(String),
+    // Playlist id
+    ExportPlaylist(String),
+    ImportPlaylist,
     // Cross-state actions
     QueueSelection,
     DequeueSelection,
@@ -40,33 +51,79 @@ pub enum AppAction {
     RemovePlaylist(String),
 }
 
+// Something to start playing right away, e.g. when asked through MPRIS
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PlaybackRequest {
+    Track(String),
+    Album(String),
+    Playlist(String),
+}
+
+// Splits spotify:<kind>:<id>, or the equivalent https://open.spotify.com/<kind>/<id> link
+pub fn split_uri(uri: &str) -> Option<(&str, &str)> {
+    if let Some(path) = uri.strip_prefix("https://open.spotify.com/") {
+        return split_link(path);
+    }
+
+    let mut parts = uri.split(':');
+    if parts.next()? != "spotify" {
+        return None;
+    }
+
+    // Might start with /// because of https://gitlab.gnome.org/GNOME/glib/-/issues/1886/
+    let kind = parts.next()?;
+    let kind = kind.strip_prefix("///").unwrap_or(kind);
+    let data = parts.next().filter(|d| !d.is_empty())?;
+    Some((kind, data))
+}
+
+// Links may have a locale before the kind (/intl-fr/album/...) and some tracking after the id (?si=...)
+fn split_link(path: &str) -> Option<(&str, &str)> {
+    let path = path.split(['?', '#']).next()?;
+    let mut parts = path
+        .split('/')
+        .filter(|part| !part.is_empty() && !part.starts_with("intl-"));
+    let kind = parts.next()?;
+    let data = parts.next()?;
+    Some((kind, data))
+}
+
 // Not actual actions, just neat wrappers
 impl AppAction {
     // An action to open a Spotify URI
     #[allow(non_snake_case)]
     pub fn OpenURI(uri: String) -> Option<Self> {
         debug!("parsing {}", &uri);
-        let mut parts = uri.split(':');
-        if parts.next()? != "spotify" {
-            return None;
-        }
-
-        // Might start with /// because of https://gitlab.gnome.org/GNOME/glib/-/issues/1886/
-        let action = parts
-            .next()?
-            .strip_prefix("///")
-            .filter(|p| !p.is_empty())?;
-        let data = parts.next()?;
+        let (action, data) = split_uri(&uri)?;
 
         match action {
             "album" => Some(Self::ViewAlbum(data.to_string())),
             "artist" => Some(Self::ViewArtist(data.to_string())),
             "playlist" => Some(Self::ViewPlaylist(data.to_string())),
             "user" => Some(Self::ViewUser(data.to_string())),
+            "show" => Some(Self::ViewShow(data.to_string())),
+            // No page for a single track, so we just play it
+            "track" => Some(Self::RequestPlayback(PlaybackRequest::Track(
+                data.to_string(),
+            ))),
             _ => None,
         }
     }
 
+    // An action to play what a Spotify URI points to
+    #[allow(non_snake_case)]
+    pub fn PlayURI(uri: &str) -> Option<Self> {
+        let (kind, id) = split_uri(uri)?;
+        let id = id.to_string();
+        let request = match kind {
+            "track" => PlaybackRequest::Track(id),
+            "album" => PlaybackRequest::Album(id),
+            "playlist" => PlaybackRequest::Playlist(id),
+            _ => return None,
+        };
+        Some(Self::RequestPlayback(request))
+    }
+
     #[allow(non_snake_case)]
     pub fn ViewAlbum(id: String) -> Self {
         BrowserAction::NavigationPush(ScreenName::AlbumDetails(id)).into()
@@ -87,6 +144,11 @@ impl AppAction {
         BrowserAction::NavigationPush(ScreenName::User(id)).into()
     }
 
+    #[allow(non_snake_case)]
+    pub fn ViewShow(id: String) -> Self {
+        BrowserAction::NavigationPush(ScreenName::Show(id)).into()
+    }
+
     #[allow(non_snake_case)]
     pub fn ViewSearch() -> Self {
         BrowserAction::NavigationPush(ScreenName::Search).into()
@@ -103,9 +165,16 @@ pub enum AppEvent {
     LoginEvent(LoginEvent),
     Started,
     Raised,
+    QuitRequested,
+    MiniPlayerToggled,
+    PlaybackRequested(PlaybackRequest),
+    LinkOpened(String),
     NotificationShown(String),
     PlaylistCreatedNotificationShown(String),
     NowPlayingShown,
+    ArtworkShown(String),
+    PlaylistExportRequested(String),
+    PlaylistImportRequested,
     SettingsEvent(SettingsEvent),
 }
 
@@ -131,6 +200,52 @@ impl AppState {
         }
     }
 
+    // Whoever logs in next should not see the previous user's library or pages, even briefly
+    fn forget_user_content(&mut self) -> Vec<AppEvent> {
+        let mut events = forward_action(
+            BrowserAction::NavigationPopTo(ScreenName::Home),
+            &mut self.browser,
+        );
+        let mut more_events =
+            forward_action(BrowserAction::SetLibraryContent(vec![]), &mut self.browser);
+        events.append(&mut more_events);
+        let mut more_events = forward_action(
+            BrowserAction::SetPlaylistsContent(vec![]),
+            &mut self.browser,
+        );
+        events.append(&mut more_events);
+        let mut more_events = forward_action(
+            BrowserAction::SetFollowedArtists(FollowedArtistsPage {
+                artists: vec![],
+                next_cursor: None,
+            }),
+            &mut self.browser,
+        );
+        events.append(&mut more_events);
+        let mut more_events =
+            forward_action(BrowserAction::SetSavedShows(vec![]), &mut self.browser);
+        events.append(&mut more_events);
+        let mut more_events =
+            forward_action(BrowserAction::SetRecentlyPlayed(vec![]), &mut self.browser);
+        events.append(&mut more_events);
+        let range = self
+            .browser
+            .home_state()
+            .map(|home| home.top_range)
+            .unwrap_or_default();
+        let mut more_events = forward_action(
+            BrowserAction::SetTopTracks(range, vec![]),
+            &mut self.browser,
+        );
+        events.append(&mut more_events);
+        let mut more_events = forward_action(
+            BrowserAction::SetTopArtists(range, vec![]),
+            &mut self.browser,
+        );
+        events.append(&mut more_events);
+        events
+    }
+
     pub fn update_state(&mut self, message: AppAction) -> Vec<AppEvent> {
         match message {
             AppAction::Start if !self.started => {
@@ -141,7 +256,14 @@ impl AppState {
             // they're here just to have a consistent way of doing things (always an Action)
             AppAction::ShowNotification(c) => vec![AppEvent::NotificationShown(c)],
             AppAction::ViewNowPlaying => vec![AppEvent::NowPlayingShown],
+            AppAction::ViewArtwork(id) => vec![AppEvent::ArtworkShown(id)],
+            AppAction::ExportPlaylist(id) => vec![AppEvent::PlaylistExportRequested(id)],
+            AppAction::ImportPlaylist => vec![AppEvent::PlaylistImportRequested],
             AppAction::Raise => vec![AppEvent::Raised],
+            AppAction::Quit => vec![AppEvent::QuitRequested],
+            AppAction::ToggleMiniPlayer => vec![AppEvent::MiniPlayerToggled],
+            AppAction::RequestPlayback(request) => vec![AppEvent::PlaybackRequested(request)],
+            AppAction::OpenLink(uri) => vec![AppEvent::LinkOpened(uri)],
             // Cross-state actions: multiple "substates" are affected by these actions, that's why they're handled here
             // Might need some clean-up
             AppAction::QueueSelection => {
@@ -158,12 +280,10 @@ impl AppState {
                     .into_iter()
                     .map(|s| s.id)
                     .collect();
-                self.playback.dequeue(&tracks);
-
-                vec![
-                    SelectionEvent::SelectionModeChanged(false).into(),
-                    PlaybackEvent::PlaylistChanged.into(),
-                ]
+                let mut events = vec![SelectionEvent::SelectionModeChanged(false).into()];
+                let dequeued = self.playback.dequeue(&tracks);
+                events.extend(dequeued.into_iter().map(AppEvent::from));
+                events
             }
             AppAction::MoveDownSelection => {
                 let mut selection = self.selection.peek_selection();
@@ -256,6 +376,34 @@ impl AppState {
                 events.append(&mut more_events);
                 events
             }
+            AppAction::LoginAction(LoginAction::Logout) => {
+                let mut events = forward_action(LoginAction::Logout, &mut self.logged_user);
+                events.append(&mut self.forget_user_content());
+                events
+            }
+            // Same as logging out, as far as what's shown goes
+            AppAction::LoginAction(
+                action @ (LoginAction::AddAccount | LoginAction::SwitchAccount(_)),
+            ) => {
+                let mut events = forward_action(action, &mut self.logged_user);
+                events.append(&mut self.forget_user_content());
+                events
+            }
+            // More liked songs while we're playing from them: the queue gets them too
+            AppAction::BrowserAction(BrowserAction::AppendSavedTracks(song_batch))
+                if self.playback.current_source() == Some(&SongsSource::SavedTracks) =>
+            {
+                let mut events = forward_action(
+                    PlaybackAction::LoadPagedSongs(SongsSource::SavedTracks, *song_batch.clone()),
+                    &mut self.playback,
+                );
+                let mut more_events = forward_action(
+                    BrowserAction::AppendSavedTracks(song_batch),
+                    &mut self.browser,
+                );
+                events.append(&mut more_events);
+                events
+            }
             // As for all other actions, we forward them to the substates :)
             AppAction::PlaybackAction(a) => forward_action(a, &mut self.playback),
             AppAction::BrowserAction(a) => forward_action(a, &mut self.browser),
@@ -281,3 +429,54 @@ where
         .map(|e| e.into())
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::state::LoginStartedEvent;
+
+    #[test]
+    fn test_split_uri() {
+        assert_eq!(split_uri("spotify:album:123"), Some(("album", "123")));
+        assert_eq!(split_uri("spotify:///track:456"), Some(("track", "456")));
+        assert_eq!(split_uri("spotify:album:"), None);
+        assert_eq!(split_uri("https://example.com/album/123"), None);
+    }
+
+    #[test]
+    fn test_split_link() {
+        assert_eq!(
+            split_uri("https://open.spotify.com/playlist/123?si=abc"),
+            Some(("playlist", "123"))
+        );
+        assert_eq!(
+            split_uri("https://open.spotify.com/intl-fr/artist/456"),
+            Some(("artist", "456"))
+        );
+        assert_eq!(split_uri("https://open.spotify.com/album"), None);
+    }
+
+    #[test]
+    fn test_switching_account_resets_navigation() {
+        let mut state = AppState::new();
+        state.update_state(LoginAction::SetLoginSuccess("first".to_string()).into());
+        state.update_state(
+            BrowserAction::NavigationPush(ScreenName::AlbumDetails("a".to_string())).into(),
+        );
+        assert_eq!(state.browser.count(), 2);
+
+        state.update_state(LoginAction::AddAccount.into());
+        assert_eq!(state.browser.count(), 1);
+        assert_eq!(state.logged_user.previous_user.as_deref(), Some("first"));
+
+        // Given up on, so back to the first account
+        let events = state.update_state(LoginAction::CancelAddAccount.into());
+        assert!(events.iter().any(|e| matches!(
+            e,
+            AppEvent::LoginEvent(LoginEvent::LoginStarted(LoginStartedEvent::SwitchAccount(
+                username
+            ))) if username == "first"
+        )));
+        assert!(state.logged_user.previous_user.is_none());
+    }
+}
@@ -1,6 +1,6 @@
 use super::{
-    AppAction, AppEvent, ArtistState, DetailsState, HomeState, PlaylistDetailsState, ScreenName,
-    SearchState, UpdatableState, UserState,
+    AppAction, AppEvent, ArtistState, DetailsState, HeaderState, HomeState, PlaylistDetailsState,
+    ScreenName, SearchState, ShowState, UpdatableState, UserState,
 };
 use crate::app::models::*;
 use std::borrow::Cow;
@@ -14,6 +14,13 @@ pub enum BrowserAction {
     SetLibraryContent(Vec<AlbumDescription>),
     PrependPlaylistsContent(Vec<PlaylistDescription>),
     AppendLibraryContent(Vec<AlbumDescription>),
+    SetRecentlyPlayed(Vec<RecentlyPlayed>),
+    SetNewReleases(Vec<AlbumDescription>),
+    AppendNewReleases(Vec<AlbumDescription>),
+    SetTopRange(TopRange),
+    // The range is the one they were fetched for, in case it changed in the meantime
+    SetTopTracks(TopRange, Vec<SongDescription>),
+    SetTopArtists(TopRange, Vec<ArtistSummary>),
     SetPlaylistsContent(Vec<PlaylistDescription>),
     AppendPlaylistsContent(Vec<PlaylistDescription>),
     RemoveTracksFromPlaylist(String, Vec<String>),
@@ -23,10 +30,21 @@ pub enum BrowserAction {
     SetPlaylistDetails(Box<PlaylistDescription>, Box<SongBatch>),
     UpdatePlaylistName(PlaylistSummary),
     AppendPlaylistTracks(String, Box<SongBatch>),
+    SetPlaylistSort(String, SongSort),
+    // The tracks of that album or playlist screen couldn't be loaded
+    TracksLoadFailed(ScreenName),
+    // Whether the (big) header of a screen is scrolled into view
+    SetHeaderVisible(ScreenName, bool),
     Search(String),
-    SetSearchResults(Box<SearchResults>),
+    // The search generation and offset they were fetched for, in case the query changed since
+    AppendSearchResults(usize, SearchCategory, usize, Box<SearchResults>),
     SetArtistDetails(Box<ArtistDescription>),
     AppendArtistReleases(String, Vec<AlbumDescription>),
+    SetArtistFollowed(String, bool),
+    SetRelatedArtists(String, Vec<ArtistSummary>),
+    SetFollowedArtists(FollowedArtistsPage),
+    AppendFollowedArtists(FollowedArtistsPage),
+    AddFollowedArtist(ArtistSummary),
     NavigationPush(ScreenName),
     NavigationPop,
     NavigationPopTo(ScreenName),
@@ -38,6 +56,12 @@ pub enum BrowserAction {
     AppendSavedTracks(Box<SongBatch>),
     SaveTracks(Vec<SongDescription>),
     RemoveSavedTracks(Vec<String>),
+    // Whether some tracks are in the user's Liked Songs, as asked to the API
+    SetTracksSaved(Vec<(String, bool)>),
+    SetSavedShows(Vec<ShowDescription>),
+    AppendSavedShows(Vec<ShowDescription>),
+    SetShowDetails(Box<ShowDescription>),
+    AppendShowEpisodes(String, Vec<EpisodeDescription>),
 }
 
 impl From<BrowserAction> for AppAction {
@@ -51,15 +75,26 @@ pub enum BrowserEvent {
     NavigationHidden(bool),
     HomeVisiblePageChanged(&'static str),
     LibraryUpdated,
+    RecentlyPlayedUpdated,
+    NewReleasesUpdated,
+    TopRangeChanged(TopRange),
+    TopTracksUpdated,
+    TopArtistsUpdated,
     SavedPlaylistsUpdated,
     AlbumDetailsLoaded(String),
     AlbumTracksAppended(String),
     PlaylistDetailsLoaded(String),
     PlaylistTracksAppended(String),
     PlaylistTracksRemoved(String),
+    PlaylistSortChanged(String),
+    TracksLoadFailed(ScreenName),
+    HeaderUpdated(ScreenName),
     SearchUpdated,
-    SearchResultsUpdated,
+    SearchResultsUpdated(SearchCategory),
     ArtistDetailsUpdated(String),
+    ArtistFollowChanged(String),
+    RelatedArtistsUpdated(String),
+    FollowedArtistsUpdated,
     NavigationPushed(ScreenName),
     NavigationPopped,
     NavigationPoppedTo(ScreenName),
@@ -67,6 +102,10 @@ pub enum BrowserEvent {
     AlbumUnsaved(String),
     UserDetailsUpdated(String),
     SavedTracksUpdated,
+    TrackSaved(String),
+    TrackUnsaved(String),
+    SavedShowsUpdated,
+    ShowDetailsUpdated(String),
 }
 
 impl From<BrowserEvent> for AppEvent {
@@ -83,6 +122,7 @@ pub enum BrowserScreen {
     Artist(Box<ArtistState>),
     PlaylistDetails(Box<PlaylistDetailsState>),
     User(Box<UserState>),
+    Show(Box<ShowState>),
 }
 
 impl BrowserScreen {
@@ -100,6 +140,7 @@ impl BrowserScreen {
                 BrowserScreen::PlaylistDetails(Box::new(PlaylistDetailsState::new(id.to_string())))
             }
             ScreenName::User(id) => BrowserScreen::User(Box::new(UserState::new(id.to_string()))),
+            ScreenName::Show(id) => BrowserScreen::Show(Box::new(ShowState::new(id.to_string()))),
         }
     }
 
@@ -112,6 +153,28 @@ impl BrowserScreen {
             Self::Artist(state) => &mut **state,
             Self::PlaylistDetails(state) => &mut **state,
             Self::User(state) => &mut **state,
+            Self::Show(state) => &mut **state,
+        }
+    }
+
+    // What the headerbar should show for this screen, if it cares
+    fn header(&self) -> Option<&HeaderState> {
+        match self {
+            Self::AlbumDetails(state) => Some(&state.header),
+            Self::Artist(state) => Some(&state.header),
+            Self::PlaylistDetails(state) => Some(&state.header),
+            Self::Show(state) => Some(&state.header),
+            _ => None,
+        }
+    }
+
+    fn header_mut(&mut self) -> Option<&mut HeaderState> {
+        match self {
+            Self::AlbumDetails(state) => Some(&mut state.header),
+            Self::Artist(state) => Some(&mut state.header),
+            Self::PlaylistDetails(state) => Some(&mut state.header),
+            Self::Show(state) => Some(&mut state.header),
+            _ => None,
         }
     }
 }
@@ -127,6 +190,7 @@ impl NamedScreen for BrowserScreen {
             Self::Artist(state) => &state.name,
             Self::PlaylistDetails(state) => &state.name,
             Self::User(state) => &state.name,
+            Self::Show(state) => &state.name,
         }
     }
 }
@@ -270,6 +334,12 @@ impl BrowserState {
         })
     }
 
+    pub fn is_track_saved(&self, id: &str) -> bool {
+        self.home_state()
+            .map(|home| home.saved_track_ids.contains(id))
+            .unwrap_or(false)
+    }
+
     pub fn details_state(&self, id: &str) -> Option<&DetailsState> {
         extract_state!(self, BrowserScreen::AlbumDetails(state) if state.id == id => state)
     }
@@ -290,6 +360,32 @@ impl BrowserState {
         extract_state!(self, BrowserScreen::User(state) if state.id == id => state)
     }
 
+    pub fn show_state(&self, id: &str) -> Option<&ShowState> {
+        extract_state!(self, BrowserScreen::Show(state) if state.id == id => state)
+    }
+
+    pub fn header(&self, name: &ScreenName) -> Option<&HeaderState> {
+        self.navigation
+            .iter_rev()
+            .find(|screen| screen.name() == name)
+            .and_then(|screen| screen.header())
+    }
+
+    fn set_header_visible(&mut self, name: &ScreenName, visible: bool) -> Vec<BrowserEvent> {
+        let header = self
+            .navigation
+            .iter_mut()
+            .find(|screen| screen.name() == name)
+            .and_then(|screen| screen.header_mut());
+        match header {
+            Some(header) if header.header_visible != visible => {
+                header.header_visible = visible;
+                vec![BrowserEvent::HeaderUpdated(name.clone())]
+            }
+            _ => vec![],
+        }
+    }
+
     // If a screen we want to push is already in the stack
     // we just pop all the way back to it
     fn push_if_needed(&mut self, name: &ScreenName) -> Vec<BrowserEvent> {
@@ -333,6 +429,13 @@ impl UpdatableState for BrowserState {
                 events
             }
             BrowserAction::NavigationPush(name) => self.push_if_needed(name),
+            BrowserAction::SetHeaderVisible(name, visible) => {
+                self.set_header_visible(name, *visible)
+            }
+            // Nothing to keep, the screen only has to stop waiting
+            BrowserAction::TracksLoadFailed(name) => {
+                vec![BrowserEvent::TracksLoadFailed(name.clone())]
+            }
             BrowserAction::NavigationPopTo(name) => {
                 self.navigation.pop_to(name);
                 vec![BrowserEvent::NavigationPoppedTo(name.clone())]
@@ -438,4 +541,87 @@ pub mod tests {
         assert_eq!(state.count(), 2);
         assert_eq!(events, vec![BrowserEvent::NavigationPoppedTo(new_screen)]);
     }
+
+    fn album_details(id: &str, title: &str) -> BrowserAction {
+        BrowserAction::SetAlbumDetails(Box::new(AlbumFullDescription {
+            description: AlbumDescription {
+                id: id.to_owned(),
+                title: title.to_owned(),
+                artists: vec![ArtistRef {
+                    id: "artist".to_owned(),
+                    name: "Artist".to_owned(),
+                }],
+                release_date: None,
+                art: None,
+                large_art: None,
+                songs: SongBatch::empty(),
+                is_liked: false,
+            },
+            release_details: AlbumReleaseDetails {
+                label: "".to_owned(),
+                copyright_text: "".to_owned(),
+                total_tracks: 0,
+            },
+        }))
+    }
+
+    #[test]
+    fn test_header_push_and_scroll() {
+        let mut state = BrowserState::new();
+        let album = ScreenName::AlbumDetails("album".to_string());
+        assert_eq!(state.header(&ScreenName::Home), None);
+
+        state.update_with(Cow::Owned(BrowserAction::NavigationPush(album.clone())));
+        let header = state.header(&album).unwrap();
+        assert_eq!(header.title, None);
+        assert!(header.is_flat());
+
+        let events = state.update_with(Cow::Owned(album_details("album", "Title")));
+        assert!(events.contains(&BrowserEvent::HeaderUpdated(album.clone())));
+        let header = state.header(&album).unwrap();
+        assert_eq!(header.title.as_deref(), Some("Title"));
+        assert_eq!(header.subtitle.as_deref(), Some("Artist"));
+        // The big header is still visible, so the headerbar blends into it
+        assert!(header.is_flat());
+
+        let events = state.update_with(Cow::Owned(BrowserAction::SetHeaderVisible(
+            album.clone(),
+            false,
+        )));
+        assert_eq!(events, vec![BrowserEvent::HeaderUpdated(album.clone())]);
+        assert!(!state.header(&album).unwrap().is_flat());
+
+        // Nothing changed, nothing to report
+        let events = state.update_with(Cow::Owned(BrowserAction::SetHeaderVisible(
+            album.clone(),
+            false,
+        )));
+        assert_eq!(events, vec![]);
+    }
+
+    #[test]
+    fn test_header_preserved_across_navigation() {
+        let mut state = BrowserState::new();
+        let album = ScreenName::AlbumDetails("album".to_string());
+        let artist = ScreenName::Artist("artist".to_string());
+
+        state.update_with(Cow::Owned(BrowserAction::NavigationPush(album.clone())));
+        state.update_with(Cow::Owned(album_details("album", "Title")));
+        state.update_with(Cow::Owned(BrowserAction::SetHeaderVisible(
+            album.clone(),
+            false,
+        )));
+
+        state.update_with(Cow::Owned(BrowserAction::NavigationPush(artist.clone())));
+        let header = state.header(&artist).unwrap();
+        assert_eq!(header.title, None);
+        assert!(!header.is_flat());
+
+        state.update_with(Cow::Owned(BrowserAction::NavigationPop));
+        assert_eq!(state.current_screen(), &album);
+        assert_eq!(state.header(&artist), None);
+        let header = state.header(&album).unwrap();
+        assert_eq!(header.title.as_deref(), Some("Title"));
+        assert!(!header.is_flat());
+    }
 }
@@ -11,6 +11,8 @@ pub enum TryLoginAction {
     Restore,
     InitLogin,
     CompleteLogin,
+    // The redirect URL (or its code) pasted by the user
+    CompleteLoginWithCode(String),
 }
 
 #[derive(Clone, Debug)]
@@ -18,12 +20,21 @@ pub enum LoginAction {
     ShowLogin,
     OpenLoginUrl(Url),
     TryLogin(TryLoginAction),
+    CancelLogin,
+    AddAccount,
+    // Going back to the account that was put aside
+    CancelAddAccount,
+    SwitchAccount(String),
+    SetAccounts(Vec<String>),
     SetLoginSuccess(String),
     SetUserPlaylists(Vec<PlaylistSummary>),
     UpdateUserPlaylist(PlaylistSummary),
     PrependUserPlaylist(Vec<PlaylistSummary>),
     RemoveUserPlaylist(String),
-    SetLoginFailure,
+    // With the reason, when there's more to say than "it failed"
+    SetLoginFailure(Option<String>),
+    // The session couldn't be restored for lack of network
+    SetOffline,
     RefreshToken,
     TokenRefreshed,
     Logout,
@@ -40,6 +51,8 @@ pub enum LoginStartedEvent {
     Restore,
     InitLogin,
     CompleteLogin,
+    CompleteLoginWithCode(String),
+    SwitchAccount(String),
     OpenUrl(Url),
 }
 
@@ -49,7 +62,13 @@ pub enum LoginEvent {
     LoginStarted(LoginStartedEvent),
     LoginCompleted,
     UserPlaylistsLoaded,
-    LoginFailed,
+    LoginFailed(Option<String>),
+    // Whether what's shown comes from the cache, while waiting for the network to come back
+    OfflineChanged(bool),
+    LoginCancelled,
+    // The current account was put aside, the login window is needed for the new one
+    AddAccountStarted,
+    AccountsChanged,
     FreshTokenRequested,
     RefreshTokenCompleted,
     LogoutCompleted,
@@ -69,6 +88,24 @@ pub struct LoginState {
     pub playlists: Vec<PlaylistSummary>,
     // Playlist IDs for O(1) ownership checks
     pub playlist_ids: HashSet<String>,
+    // Usernames of the accounts that can be switched to (including the current one)
+    pub accounts: Vec<String>,
+    // Why the last login attempt failed, if we know
+    pub login_error: Option<String>,
+    // Logged in from a previous run, but the session couldn't be restored for lack of network
+    pub offline: bool,
+    // Who was logged in before adding another account, until that's done
+    pub previous_user: Option<String>,
+}
+
+impl LoginState {
+    fn set_offline(&mut self, offline: bool) -> Option<AppEvent> {
+        if self.offline == offline {
+            return None;
+        }
+        self.offline = offline;
+        Some(LoginEvent::OfflineChanged(offline).into())
+    }
 }
 
 impl UpdatableState for LoginState {
@@ -89,11 +126,49 @@ impl UpdatableState for LoginState {
             LoginAction::TryLogin(TryLoginAction::CompleteLogin) => {
                 vec![LoginEvent::LoginStarted(LoginStartedEvent::CompleteLogin).into()]
             }
+            LoginAction::TryLogin(TryLoginAction::CompleteLoginWithCode(code)) => {
+                vec![
+                    LoginEvent::LoginStarted(LoginStartedEvent::CompleteLoginWithCode(code)).into(),
+                ]
+            }
             LoginAction::SetLoginSuccess(username) => {
                 self.user = Some(username);
-                vec![LoginEvent::LoginCompleted.into()]
+                self.previous_user = None;
+                self.login_error = None;
+                let back_online = self.set_offline(false);
+                back_online
+                    .into_iter()
+                    .chain([LoginEvent::LoginCompleted.into()])
+                    .collect()
+            }
+            LoginAction::CancelLogin => {
+                self.login_error = None;
+                vec![LoginEvent::LoginCancelled.into()]
+            }
+            LoginAction::AddAccount => {
+                self.previous_user = self.user.take();
+                vec![LoginEvent::AddAccountStarted.into()]
+            }
+            LoginAction::CancelAddAccount => match self.previous_user.take() {
+                Some(username) => {
+                    vec![
+                        LoginEvent::LoginStarted(LoginStartedEvent::SwitchAccount(username)).into(),
+                    ]
+                }
+                None => vec![],
+            },
+            LoginAction::SwitchAccount(username) => {
+                vec![LoginEvent::LoginStarted(LoginStartedEvent::SwitchAccount(username)).into()]
+            }
+            LoginAction::SetAccounts(accounts) => {
+                self.accounts = accounts;
+                vec![LoginEvent::AccountsChanged.into()]
+            }
+            LoginAction::SetLoginFailure(reason) => {
+                self.login_error.clone_from(&reason);
+                vec![LoginEvent::LoginFailed(reason).into()]
             }
-            LoginAction::SetLoginFailure => vec![LoginEvent::LoginFailed.into()],
+            LoginAction::SetOffline => self.set_offline(true).into_iter().collect(),
             LoginAction::RefreshToken => vec![LoginEvent::FreshTokenRequested.into()],
             LoginAction::TokenRefreshed => {
                 // translators: This notification is shown when, after some inactivity, the session is successfully restored. The user might have to repeat its last action.
@@ -104,7 +179,12 @@ impl UpdatableState for LoginState {
             }
             LoginAction::Logout => {
                 self.user = None;
-                vec![LoginEvent::LogoutCompleted.into()]
+                self.previous_user = None;
+                let back_online = self.set_offline(false);
+                back_online
+                    .into_iter()
+                    .chain([LoginEvent::LogoutCompleted.into()])
+                    .collect()
             }
             LoginAction::SetUserPlaylists(playlists) => {
                 self.playlist_ids = playlists.iter().map(|p| p.id.clone()).collect();
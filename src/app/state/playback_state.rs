@@ -1,10 +1,31 @@
 use std::borrow::Cow;
-use std::time::Instant;
+use std::convert::TryFrom;
+use std::time::{Duration, Instant};
 
 use crate::app::models::*;
 use crate::app::state::{AppAction, AppEvent, UpdatableState};
 use crate::app::{BatchQuery, LazyRandomIndex, SongsSource};
 
+// Episodes we stop listening to this close to the end are considered done
+const EPISODE_END_MARGIN_MS: u32 = 30_000;
+// How many songs are kept in the history
+const HISTORY_SIZE: usize = 200;
+// Going back to the previous track only works at the start of the current one, after that it's restarted
+const PREVIOUS_TRACK_MARGIN_MS: u64 = 3_000;
+
+// When the sleep timer should pause playback
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SleepTimer {
+    Deadline(Instant),
+    EndOfTrack,
+}
+
+impl SleepTimer {
+    pub fn in_minutes(minutes: u64) -> Self {
+        Self::Deadline(Instant::now() + Duration::from_secs(minutes * 60))
+    }
+}
+
 #[derive(Debug)]
 pub struct PlaybackState {
     available_devices: Vec<ConnectDevice>,
@@ -14,11 +35,24 @@ pub struct PlaybackState {
     // The actual list like thing backing the currently playing tracks
     songs: SongListModel,
     list_position: Option<usize>,
+    // What played during this session, most recent first (whether it ended or was skipped)
+    history: SongListModel,
     seek_position: PositionMillis,
     source: Option<SongsSource>,
+    // The artist whose radio is playing, if that's what the queue is
+    radio: Option<String>,
     repeat: RepeatMode,
     is_playing: bool,
     is_shuffled: bool,
+    // Kept across track changes, until it goes off or gets cancelled
+    sleep_timer: Option<SleepTimer>,
+    // Explicit tracks can be blocked by the account settings, or by the user themselves
+    explicit_blocked_by_account: bool,
+    explicit_blocked_by_user: bool,
+    // Unavailable tracks skipped since something last played, so that they're reported all at once
+    skipped_unavailable: usize,
+    // Tracks the player failed to load in a row, we give up once that's all of them
+    failed_loads: usize,
 }
 
 // Most mutatings methods shouldn't be pub
@@ -40,6 +74,80 @@ impl PlaybackState {
         self.repeat
     }
 
+    pub fn sleep_timer(&self) -> Option<SleepTimer> {
+        self.sleep_timer
+    }
+
+    pub fn is_explicit_blocked(&self) -> bool {
+        self.explicit_blocked_by_account || self.explicit_blocked_by_user
+    }
+
+    // Not available in the user's country, for instance
+    fn is_unavailable(&self, id: &str) -> bool {
+        self.songs
+            .get(id)
+            .map(|song| !song.description().is_playable)
+            .unwrap_or(false)
+    }
+
+    fn is_blocked(&self, id: &str) -> bool {
+        self.is_unavailable(id)
+            || (self.is_explicit_blocked()
+                && self
+                    .songs
+                    .find_index(id)
+                    .and_then(|i| self.songs.index(i))
+                    .map(|song| song.description().explicit)
+                    .unwrap_or(false))
+    }
+
+    fn is_unavailable_at(&self, position: usize) -> bool {
+        self.index(position)
+            .map(|song| !song.is_playable)
+            .unwrap_or(false)
+    }
+
+    // Same as above, but for a position in the playing order
+    fn is_blocked_at(&self, position: usize) -> bool {
+        self.is_unavailable_at(position)
+            || (self.is_explicit_blocked()
+                && self
+                    .index(position)
+                    .map(|song| song.explicit)
+                    .unwrap_or(false))
+    }
+
+    fn has_unavailable(&self) -> bool {
+        self.songs
+            .map_collect(|song| song.is_playable)
+            .contains(&false)
+    }
+
+    // Unavailable tracks stepped over when moving forward from one position to the other
+    fn unavailable_between(&self, from: usize, to: usize) -> usize {
+        let len = self.songs.len();
+        (1..len)
+            .map(|k| (from + k) % len)
+            .take_while(|&i| i != to)
+            .filter(|&i| self.is_unavailable_at(i))
+            .count()
+    }
+
+    // Steps through the list until something we're allowed to play comes up
+    fn find_playable<F>(&self, from: usize, step: F) -> Option<usize>
+    where
+        F: Fn(usize) -> Option<usize>,
+    {
+        let mut i = step(from)?;
+        for _ in 0..self.songs.len() {
+            if !self.is_blocked_at(i) {
+                return Some(i);
+            }
+            i = step(i)?;
+        }
+        None
+    }
+
     // Whatever batch of songs we would need to grab if we were to play the next track
     pub fn next_query(&self) -> Option<BatchQuery> {
         let next_index = self.next_index()?;
@@ -66,10 +174,50 @@ impl PlaybackState {
         Some(song?.into_description())
     }
 
+    pub fn history(&self) -> &SongListModel {
+        &self.history
+    }
+
+    // Songs played again move back to the top
+    fn remember(&mut self, song: SongDescription) {
+        if self.history.find_index(&song.id).is_some() {
+            self.history.remove(std::slice::from_ref(&song.id)).commit();
+        }
+        self.history.prepend(vec![song]).commit();
+        let oldest = self
+            .history
+            .collect()
+            .into_iter()
+            .skip(HISTORY_SIZE)
+            .map(|song| song.id)
+            .collect::<Vec<_>>();
+        if !oldest.is_empty() {
+            self.history.remove(&oldest).commit();
+        }
+    }
+
+    // Goes back to the last song played, if it can still be found in the list
+    fn back_in_history(&mut self) -> Option<String> {
+        let last = self.history.index(0)?.description().id.clone();
+        if self.current_song_id().as_ref() == Some(&last) {
+            return None;
+        }
+        let position = self
+            .position_of(&last)
+            .filter(|&position| !self.is_blocked_at(position))?;
+        self.history.remove(&[last]).commit();
+        self.seek_position.set(0, true);
+        self.play_index(position)
+    }
+
     pub fn current_source(&self) -> Option<&SongsSource> {
         self.source.as_ref()
     }
 
+    pub fn radio(&self) -> Option<&str> {
+        self.radio.as_deref()
+    }
+
     pub fn current_song_index(&self) -> Option<usize> {
         self.list_position
     }
@@ -82,13 +230,31 @@ impl PlaybackState {
         self.index(self.list_position?)
     }
 
+    // In milliseconds
+    pub fn position_ms(&self) -> u32 {
+        u32::try_from(self.seek_position.current()).unwrap_or(u32::MAX)
+    }
+
+    // Where we are in the current song, if it's a podcast episode (so that we can resume it later)
+    pub fn episode_progress(&self) -> Option<(String, u32)> {
+        let song = self.current_song().filter(|song| song.is_episode())?;
+        let position = self.position_ms();
+        // Close enough to the end, next time it'll start over
+        if position.saturating_add(EPISODE_END_MARGIN_MS) >= song.duration {
+            Some((song.id, 0))
+        } else {
+            Some((song.id, position))
+        }
+    }
+
+    // What will play once the current track is over, which is what the player should preload
     fn next_id(&self) -> Option<String> {
-        self.next_index()
-            .and_then(|i| Some(self.songs().index(i)?.description().id.clone()))
+        self.index_after_end().and_then(|i| Some(self.index(i)?.id))
     }
 
     fn clear(&mut self, source: Option<SongsSource>) -> SongListModelPending {
         self.source = source;
+        self.radio = None;
         self.index = Default::default();
         self.list_position = None;
         self.songs.clear()
@@ -113,44 +279,172 @@ impl PlaybackState {
         self.index.grow(self.songs.len());
     }
 
+    // Songs are identified by their id, so those already there aren't added again
     pub fn queue(&mut self, tracks: Vec<SongDescription>) {
         self.source = None;
+        self.radio = None;
         self.songs.append(tracks).commit();
         self.index.grow(self.songs.len());
     }
 
-    pub fn dequeue(&mut self, ids: &[String]) {
+    // Same, but the songs come right after the current one (in the order they were given)
+    fn queue_next(&mut self, tracks: Vec<SongDescription>) {
+        let count = tracks.len();
+        self.queue(tracks);
+        // They're last in the playing order too, shuffled or not: the last one is moved first to keep them in order
+        for _ in 0..count {
+            self.move_position_to_next(self.songs.len() - 1);
+        }
+    }
+
+    // Queuing something while nothing is playing starts playing it
+    fn queued(&mut self, first: Option<String>) -> Vec<PlaybackEvent> {
+        let mut events = vec![PlaybackEvent::PlaylistChanged];
+        if self.list_position.is_none() {
+            if let Some(id) = first.filter(|id| !self.is_blocked(id) && self.play(id)) {
+                events.push(PlaybackEvent::TrackChanged(id));
+                events.push(PlaybackEvent::PlaybackResumed);
+            }
+        }
+        events
+    }
+
+    // Where a song is in the playing order
+    fn position_of(&self, id: &str) -> Option<usize> {
+        let index = self.songs.find_index(id)?;
+        if self.is_shuffled {
+            self.index.position(index)
+        } else {
+            Some(index)
+        }
+    }
+
+    // What should play if the current track goes away, leaving out the `removed` ones
+    fn replacement_for(&mut self, removed: &[String]) -> Option<String> {
+        let position = self.list_position?;
+        let len = self.songs.len();
+        if self.is_shuffled {
+            self.index.next_until(len.saturating_sub(1));
+        }
+        let wraps = self.repeat == RepeatMode::Playlist;
+        (position + 1..len)
+            .chain((0..position).filter(|_| wraps))
+            .filter(|&i| !self.is_blocked_at(i))
+            .filter_map(|i| self.index(i))
+            .map(|song| song.id)
+            .find(|id| !removed.contains(id))
+    }
+
+    // Removing the current track moves on to the next one
+    pub fn dequeue(&mut self, ids: &[String]) -> Vec<PlaybackEvent> {
         let current_id = self.current_song_id();
+        let current_removed = current_id.as_ref().is_some_and(|id| ids.contains(id));
+        let next_id = if current_removed {
+            self.replacement_for(ids)
+        } else {
+            current_id
+        };
+
+        let removed = ids
+            .iter()
+            .filter_map(|id| self.songs.find_index(id))
+            .collect::<Vec<_>>();
+        if removed.is_empty() {
+            return vec![];
+        }
         self.songs.remove(ids).commit();
-        self.list_position = current_id.and_then(|id| self.songs.find_index(&id));
-        self.index.shrink(self.songs.len());
+        self.index.remove(&removed);
+        self.list_position = next_id.as_ref().and_then(|id| self.position_of(id));
+
+        let mut events = vec![PlaybackEvent::PlaylistChanged];
+        if current_removed {
+            match (self.list_position, next_id) {
+                (Some(position), Some(id)) => {
+                    self.play_index(position);
+                    events.push(PlaybackEvent::TrackChanged(id));
+                    events.push(PlaybackEvent::PlaybackResumed);
+                }
+                _ => {
+                    self.stop();
+                    events.push(PlaybackEvent::PlaybackStopped);
+                }
+            }
+        }
+        events
     }
 
-    // Update the current playing track (identified by a position in the list) if we're swapping songs
-    fn swap_pos(&mut self, index: usize, other_index: usize) {
-        let len = self.songs.len();
-        self.list_position = self
+    // Moves a song so that it comes right after the current one (or first, if nothing is playing)
+    fn move_to_next(&mut self, id: &str) -> bool {
+        match self.position_of(id) {
+            Some(position) => self.move_position_to_next(position),
+            None => false,
+        }
+    }
+
+    // Same, with the song's position in the playing order
+    fn move_position_to_next(&mut self, position: usize) -> bool {
+        let target = match self.list_position {
+            Some(current) if position == current => return false,
+            Some(current) if position > current => current + 1,
+            Some(current) => current,
+            None => 0,
+        };
+        if position == target {
+            return false;
+        }
+
+        if self.is_shuffled {
+            // The list itself stays as it is, only the playing order changes
+            self.index.move_to(position, target);
+        } else if !self.songs.move_to(position, target).commit() {
+            return false;
+        }
+        // It was before the current track, which is now one step closer to the start
+        if let Some(current) = self.list_position.filter(|&current| position < current) {
+            self.list_position = Some(current - 1);
+        }
+        true
+    }
+
+    // Only keeps what's been played so far, up to the current track
+    fn clear_upcoming(&mut self) -> bool {
+        let kept: Vec<SongDescription> = self
             .list_position
-            .map(|position| match position {
-                i if i == index => other_index,
-                i if i == other_index => index,
-                _ => position,
-            })
-            .map(|p| usize::min(p, len - 1))
+            .map(|position| (0..=position).filter_map(|i| self.index(i)).collect())
+            .unwrap_or_default();
+        if kept.len() == self.songs.len() {
+            return false;
+        }
+        let count = kept.len();
+        self.set_queue(kept);
+        // In the order they were played, which is now the order of the list
+        self.index.reset_in_order(count);
+        self.list_position = count.checked_sub(1);
+        true
+    }
+
+    // Moves a song somewhere else in the list (positions are in the list, not in the playing order)
+    fn move_item(&mut self, from: usize, to: usize) -> bool {
+        if !self.songs.move_to(from, to).commit() {
+            return false;
+        }
+        if self.is_shuffled {
+            // Same songs in the same order, they're just found somewhere else
+            self.index.remap(|i| moved_index(i, from, to));
+        } else {
+            self.list_position = self.list_position.map(|p| moved_index(p, from, to));
+        }
+        true
     }
 
     pub fn move_down(&mut self, id: &str) -> Option<usize> {
         let index = self.songs.find_index(id)?;
-        self.songs.move_down(index).commit();
-        self.swap_pos(index + 1, index);
-        Some(index)
+        self.move_item(index, index + 1).then_some(index)
     }
 
     pub fn move_up(&mut self, id: &str) -> Option<usize> {
         let index = self.songs.find_index(id).filter(|&index| index > 0)?;
-        self.songs.move_up(index).commit();
-        self.swap_pos(index - 1, index);
-        Some(index)
+        self.move_item(index, index - 1).then_some(index)
     }
 
     fn play(&mut self, id: &str) -> bool {
@@ -180,6 +474,7 @@ impl PlaybackState {
         self.list_position = None;
         self.is_playing = false;
         self.seek_position.set(0, false);
+        self.failed_loads = 0;
     }
 
     fn play_index(&mut self, index: usize) -> Option<String> {
@@ -190,46 +485,130 @@ impl PlaybackState {
         self.current_song_id()
     }
 
+    // When shuffling, we can only tell which tracks to skip once we know the order they come in
+    fn prepare_skipping(&mut self) {
+        if self.is_shuffled && (self.is_explicit_blocked() || self.has_unavailable()) {
+            self.index.next_until(self.songs.len().saturating_sub(1));
+        }
+    }
+
     fn play_next(&mut self) -> Option<String> {
+        self.prepare_skipping();
         self.next_index().and_then(|i| {
             self.seek_position.set(0, true);
             self.play_index(i)
         })
     }
 
+    // Unlike skipping, the end of a track plays it again if we're repeating it
+    fn play_after_end(&mut self) -> Option<String> {
+        self.prepare_skipping();
+        self.index_after_end().and_then(|i| {
+            self.seek_position.set(0, true);
+            self.play_index(i)
+        })
+    }
+
+    // Skipping while repeating a single song moves on anyway (wrapping around, like the official clients)
+    fn skipping_repeat(&self) -> RepeatMode {
+        match self.repeat {
+            RepeatMode::Song => RepeatMode::Playlist,
+            mode => mode,
+        }
+    }
+
     pub fn next_index(&self) -> Option<usize> {
+        self.next_index_for(self.skipping_repeat())
+    }
+
+    fn index_after_end(&self) -> Option<usize> {
+        self.next_index_for(self.repeat)
+    }
+
+    fn next_index_for(&self, repeat: RepeatMode) -> Option<usize> {
         let len = self.songs.len();
-        self.list_position.and_then(|p| match self.repeat {
+        self.list_position.and_then(|p| match repeat {
             RepeatMode::Song => Some(p),
-            RepeatMode::Playlist if len != 0 => Some((p + 1) % len),
-            RepeatMode::None => Some(p + 1).filter(|&i| i < len),
+            RepeatMode::Playlist if len != 0 => self.find_playable(p, |i| Some((i + 1) % len)),
+            RepeatMode::None => self.find_playable(p, |i| Some(i + 1).filter(|&i| i < len)),
             _ => None,
         })
     }
 
     fn play_prev(&mut self) -> Option<String> {
+        // Only jump to the previous track if we aren't more than a few seconds into the current track.
+        // Otherwise, seek to the start of the current track.
+        // (This replicates the behavior of official Spotify clients.)
+        if self.seek_position.current() > PREVIOUS_TRACK_MARGIN_MS {
+            self.seek_position.set(0, true);
+            return None;
+        }
+        // What actually played before, which isn't always what comes before in the list (e.g. after picking a track)
+        if let Some(id) = self.back_in_history() {
+            return Some(id);
+        }
+        self.prepare_skipping();
         self.prev_index().and_then(|i| {
-            // Only jump to the previous track if we aren't more than 2 seconds (2,000 ms) into the current track.
-            // Otherwise, seek to the start of the current track.
-            // (This replicates the behavior of official Spotify clients.)
-            if self.seek_position.current() <= 2000 {
-                self.seek_position.set(0, true);
-                self.play_index(i)
-            } else {
-                self.seek_position.set(0, true);
-                None
-            }
+            self.seek_position.set(0, true);
+            self.play_index(i)
         })
     }
 
     pub fn prev_index(&self) -> Option<usize> {
         let len = self.songs.len();
-        self.list_position.and_then(|p| match self.repeat {
-            RepeatMode::Song => Some(p),
-            RepeatMode::Playlist if len != 0 => Some((if p == 0 { len } else { p }) - 1),
-            RepeatMode::None => Some(p).filter(|&i| i > 0).map(|i| i - 1),
-            _ => None,
-        })
+        self.list_position
+            .and_then(|p| match self.skipping_repeat() {
+                RepeatMode::Playlist if len != 0 => {
+                    self.find_playable(p, |i| Some((if i == 0 { len } else { i }) - 1))
+                }
+                RepeatMode::None => self.find_playable(p, |i| i.checked_sub(1)),
+                _ => None,
+            })
+    }
+
+    // The playlist changed, and what comes next might have been preloaded already: the player needs to know
+    fn changed_with_preload(&self, preloaded: Option<String>) -> Vec<PlaybackEvent> {
+        let mut events = vec![PlaybackEvent::PlaylistChanged];
+        let next = self
+            .next_id()
+            .filter(|next| self.list_position.is_some() && Some(next) != preloaded.as_ref());
+        if let Some(next) = next {
+            events.push(PlaybackEvent::Preload(next));
+        }
+        events
+    }
+
+    fn advanced_to(&mut self, next: Option<String>) -> Vec<PlaybackEvent> {
+        if let Some(id) = next {
+            vec![
+                PlaybackEvent::TrackChanged(id),
+                PlaybackEvent::PlaybackResumed,
+            ]
+        } else {
+            self.stop();
+            let mut events = vec![PlaybackEvent::PlaybackStopped];
+            events.extend(self.report_skipped());
+            events
+        }
+    }
+
+    // Moves forward, counting the unavailable tracks that had to be skipped on the way
+    fn advance<F>(&mut self, play: F) -> Vec<PlaybackEvent>
+    where
+        F: FnOnce(&mut Self) -> Option<String>,
+    {
+        let from = self.list_position;
+        let next = play(self);
+        if let (Some(from), Some(to), true) = (from, self.list_position, next.is_some()) {
+            self.skipped_unavailable += self.unavailable_between(from, to);
+        }
+        self.advanced_to(next)
+    }
+
+    // Skipped tracks are only reported once something plays (or nothing will), to show a single notification
+    fn report_skipped(&mut self) -> Option<PlaybackEvent> {
+        let skipped = std::mem::take(&mut self.skipped_unavailable);
+        (skipped > 0).then_some(PlaybackEvent::UnavailableSkipped(skipped))
     }
 
     fn toggle_play(&mut self) -> Option<bool> {
@@ -247,10 +626,27 @@ impl PlaybackState {
         }
     }
 
+    // The current track keeps playing, only its place in the playing order changes
     fn set_shuffled(&mut self, shuffled: bool) {
+        let current = self.list_position.and_then(|position| {
+            if self.is_shuffled {
+                self.index.get(position)
+            } else {
+                Some(position)
+            }
+        });
         self.is_shuffled = shuffled;
-        let old = self.list_position.replace(0).unwrap_or(0);
-        self.index.reset_picking_first(old);
+        match current {
+            Some(index) if shuffled => {
+                self.index.reset_picking_first(index);
+                self.list_position = Some(0);
+                // So that whatever comes next can be preloaded
+                self.index.next_until(1);
+            }
+            Some(index) => self.list_position = Some(index),
+            // Nothing to keep in place, picking a track to play resets the order anyway
+            None => {}
+        }
     }
 
     pub fn available_devices(&self) -> &Vec<ConnectDevice> {
@@ -262,6 +658,16 @@ impl PlaybackState {
     }
 }
 
+// Where the song at index `i` ends up once the one at `from` was moved to `to`
+fn moved_index(i: usize, from: usize, to: usize) -> usize {
+    match i {
+        i if i == from => to,
+        i if from < to && (from..=to).contains(&i) => i - 1,
+        i if to < from && (to..from).contains(&i) => i + 1,
+        i => i,
+    }
+}
+
 impl Default for PlaybackState {
     fn default() -> Self {
         Self {
@@ -270,11 +676,18 @@ impl Default for PlaybackState {
             index: LazyRandomIndex::default(),
             songs: SongListModel::new(50),
             list_position: None,
+            history: SongListModel::new(50),
             seek_position: PositionMillis::new(1.0),
             source: None,
+            radio: None,
             repeat: RepeatMode::None,
             is_playing: false,
             is_shuffled: false,
+            sleep_timer: None,
+            explicit_blocked_by_account: false,
+            explicit_blocked_by_user: false,
+            skipped_unavailable: 0,
+            failed_loads: 0,
         }
     }
 }
@@ -292,17 +705,45 @@ pub enum PlaybackAction {
     Seek(u32),
     // I can't remember the diff betweek Seek and SyncSeek right now. Probably the source of the action
     SyncSeek(u32),
+    // The player is waiting for the track to load (or not anymore)
+    SetBuffering(bool),
     Load(String),
+    // Picks a track without playing it, at some position (e.g. where we were last time)
+    Cue { id: String, position: u32 },
     LoadSongs(Vec<SongDescription>),
+    // Artist name, and what Spotify picked for their radio
+    LoadRadio(String, Vec<SongDescription>),
     LoadPagedSongs(SongsSource, SongBatch),
+    // All of a source, in an order of our own (so nothing more is loaded)
+    LoadSortedSongs(SongsSource, Vec<SongDescription>),
+    // More of what's being played, dropped if something else is being played by now
+    AppendPagedSongs(SongsSource, SongBatch),
     SetVolume(f64),
     Next,
     Previous,
+    // The current track is over, unlike Next this honors repeating a single song
+    EndOfTrack,
+    // The player failed to load that track
+    TrackUnavailable(String),
     Preload,
     Queue(Vec<SongDescription>),
+    // Queued right after the current track
+    QueueNext(Vec<SongDescription>),
     Dequeue(String),
+    // Play some queued track right after the current one
+    MoveToNext(String),
+    // Positions in the list, e.g. after dragging a song around in the queue
+    MoveQueueItem { from: usize, to: usize },
+    ClearUpcoming,
     SwitchDevice(Device),
     SetAvailableDevices(Vec<ConnectDevice>),
+    // From the account settings
+    SetAccountBlocksExplicit(bool),
+    // From our own settings
+    SetBlockExplicit(bool),
+    SetSleepTimer(Option<SleepTimer>),
+    // The timeout for the sleep timer deadline fired
+    SleepTimerExpired,
 }
 
 impl From<PlaybackAction> for AppAction {
@@ -333,6 +774,20 @@ pub enum PlaybackEvent {
     PlaybackStopped,
     SwitchedDevice(Device),
     AvailableDevicesChanged,
+    // Explicit content is blocked, so this one was refused
+    TrackBlocked(String),
+    // Can't be played at all, e.g. not available in the user's country
+    TrackUnavailable(String),
+    // Some unavailable tracks were skipped to get to the next playable one
+    UnavailableSkipped(usize),
+    // Stopped after failing to play anything in the list
+    NothingPlayable,
+    // We're leaving an episode (id, position in ms), it should be picked up from there later
+    EpisodeProgress(String, u32),
+    Buffering(bool),
+    SleepTimerChanged,
+    // Playback got paused by the sleep timer
+    SleepTimerFinished,
 }
 
 impl From<PlaybackEvent> for AppEvent {
@@ -341,13 +796,10 @@ impl From<PlaybackEvent> for AppEvent {
     }
 }
 
-impl UpdatableState for PlaybackState {
-    type Action = PlaybackAction;
-    type Event = PlaybackEvent;
-
+impl PlaybackState {
     // Main "reducer" :)
-    fn update_with(&mut self, action: Cow<Self::Action>) -> Vec<Self::Event> {
-        match action.into_owned() {
+    fn reduce(&mut self, action: PlaybackAction) -> Vec<PlaybackEvent> {
+        match action {
             PlaybackAction::TogglePlay => {
                 if let Some(playing) = self.toggle_play() {
                     if playing {
@@ -393,17 +845,58 @@ impl UpdatableState for PlaybackState {
                 self.set_shuffled(!self.is_shuffled);
                 vec![PlaybackEvent::ShuffleChanged(self.is_shuffled)]
             }
-            PlaybackAction::Next => {
-                if let Some(id) = self.play_next() {
+            PlaybackAction::Next => self.advance(Self::play_next),
+            PlaybackAction::EndOfTrack if self.sleep_timer == Some(SleepTimer::EndOfTrack) => {
+                self.sleep_timer = None;
+                // Move on, but don't start the next track
+                let mut events = match self.play_after_end() {
+                    Some(id) => {
+                        self.toggle_play();
+                        vec![
+                            PlaybackEvent::TrackChanged(id),
+                            PlaybackEvent::PlaybackPaused,
+                        ]
+                    }
+                    None => {
+                        self.stop();
+                        vec![PlaybackEvent::PlaybackStopped]
+                    }
+                };
+                events.push(PlaybackEvent::SleepTimerChanged);
+                events.push(PlaybackEvent::SleepTimerFinished);
+                events
+            }
+            PlaybackAction::EndOfTrack => self.advance(Self::play_after_end),
+            // The player couldn't load the current track, we move on unless we've already gone around
+            // (if that's not the current track anymore, we've moved on already)
+            PlaybackAction::TrackUnavailable(id)
+                if self.current_song_id().as_deref() == Some(id.as_str()) =>
+            {
+                self.skipped_unavailable += 1;
+                self.failed_loads += 1;
+                if self.failed_loads >= self.songs.len() {
+                    self.skipped_unavailable = 0;
+                    self.stop();
                     vec![
-                        PlaybackEvent::TrackChanged(id),
-                        PlaybackEvent::PlaybackResumed,
+                        PlaybackEvent::PlaybackStopped,
+                        PlaybackEvent::NothingPlayable,
                     ]
                 } else {
-                    self.stop();
-                    vec![PlaybackEvent::PlaybackStopped]
+                    self.advance(Self::play_next)
                 }
             }
+            PlaybackAction::Cue { id, position } => {
+                if !self.play(&id) {
+                    return vec![];
+                }
+                self.toggle_play();
+                self.seek_position.set(position as u64, false);
+                vec![
+                    PlaybackEvent::TrackChanged(id),
+                    PlaybackEvent::PlaybackPaused,
+                    PlaybackEvent::TrackSeeked(position),
+                ]
+            }
             PlaybackAction::Stop => {
                 self.stop();
                 vec![PlaybackEvent::PlaybackStopped]
@@ -418,6 +911,12 @@ impl UpdatableState for PlaybackState {
                     vec![PlaybackEvent::TrackSeeked(0)]
                 }
             }
+            PlaybackAction::Load(id) if self.is_unavailable(&id) => {
+                vec![PlaybackEvent::TrackUnavailable(id)]
+            }
+            PlaybackAction::Load(id) if self.is_blocked(&id) => {
+                vec![PlaybackEvent::TrackBlocked(id)]
+            }
             PlaybackAction::Load(id) => {
                 if self.play(&id) {
                     vec![
@@ -451,29 +950,82 @@ impl UpdatableState for PlaybackState {
                 self.set_batch(Some(source), batch);
                 vec![PlaybackEvent::PlaylistChanged, PlaybackEvent::SourceChanged]
             }
+            PlaybackAction::AppendPagedSongs(source, batch)
+                if Some(&source) == self.source.as_ref() =>
+            {
+                if self.add_batch(batch) {
+                    vec![PlaybackEvent::PlaylistChanged]
+                } else {
+                    vec![]
+                }
+            }
             PlaybackAction::LoadSongs(tracks) => {
                 self.set_queue(tracks);
                 vec![PlaybackEvent::PlaylistChanged, PlaybackEvent::SourceChanged]
             }
+            PlaybackAction::LoadSortedSongs(source, tracks) => {
+                self.set_queue(tracks);
+                self.source = Some(source);
+                vec![PlaybackEvent::PlaylistChanged, PlaybackEvent::SourceChanged]
+            }
+            PlaybackAction::LoadRadio(artist, tracks) => {
+                self.set_queue(tracks);
+                self.radio = Some(artist);
+                vec![PlaybackEvent::PlaylistChanged, PlaybackEvent::SourceChanged]
+            }
             PlaybackAction::Queue(tracks) => {
+                let first = tracks.first().map(|t| t.id.clone());
                 self.queue(tracks);
-                vec![PlaybackEvent::PlaylistChanged]
+                self.queued(first)
             }
-            PlaybackAction::Dequeue(id) => {
-                self.dequeue(&[id]);
-                vec![PlaybackEvent::PlaylistChanged]
+            PlaybackAction::QueueNext(tracks) => {
+                let first = tracks.first().map(|t| t.id.clone());
+                self.queue_next(tracks);
+                self.queued(first)
             }
+            PlaybackAction::Dequeue(id) => self.dequeue(&[id]),
+            PlaybackAction::MoveToNext(id) => {
+                let preloaded = self.next_id();
+                if self.move_to_next(&id) {
+                    self.changed_with_preload(preloaded)
+                } else {
+                    vec![]
+                }
+            }
+            PlaybackAction::MoveQueueItem { from, to } => {
+                let preloaded = self.next_id();
+                if self.move_item(from, to) {
+                    self.changed_with_preload(preloaded)
+                } else {
+                    vec![]
+                }
+            }
+            PlaybackAction::ClearUpcoming => {
+                if self.clear_upcoming() {
+                    vec![PlaybackEvent::PlaylistChanged, PlaybackEvent::SourceChanged]
+                } else {
+                    vec![]
+                }
+            }
+            // Positions are in milliseconds
             PlaybackAction::Seek(pos) => {
-                self.seek_position.set(pos as u64 * 1000, true);
+                self.seek_position.set(pos as u64, self.is_playing());
                 vec![PlaybackEvent::TrackSeeked(pos)]
             }
+            // Means the player is actually playing something
             PlaybackAction::SyncSeek(pos) => {
-                self.seek_position.set(pos as u64 * 1000, true);
-                vec![PlaybackEvent::SeekSynced(pos)]
+                self.failed_loads = 0;
+                self.seek_position.set(pos as u64, self.is_playing());
+                let mut events = vec![PlaybackEvent::SeekSynced(pos)];
+                events.extend(self.report_skipped());
+                events
             }
             PlaybackAction::SetVolume(volume) => {
                 vec![PlaybackEvent::VolumeSet(volume)]
             }
+            PlaybackAction::SetBuffering(buffering) => {
+                vec![PlaybackEvent::Buffering(buffering)]
+            }
 
             PlaybackAction::SetAvailableDevices(list) => {
                 self.available_devices = list;
@@ -483,11 +1035,69 @@ impl UpdatableState for PlaybackState {
                 self.current_device = new_device.clone();
                 vec![PlaybackEvent::SwitchedDevice(new_device)]
             }
+            PlaybackAction::SetAccountBlocksExplicit(blocked) => {
+                self.explicit_blocked_by_account = blocked;
+                vec![]
+            }
+            PlaybackAction::SetBlockExplicit(blocked) => {
+                self.explicit_blocked_by_user = blocked;
+                vec![]
+            }
+            PlaybackAction::SetSleepTimer(timer) => {
+                self.sleep_timer = timer;
+                vec![PlaybackEvent::SleepTimerChanged]
+            }
+            PlaybackAction::SleepTimerExpired => match self.sleep_timer {
+                // The timer might have been changed since that timeout was set up
+                Some(SleepTimer::Deadline(deadline)) if deadline <= Instant::now() => {
+                    self.sleep_timer = None;
+                    let mut events = vec![PlaybackEvent::SleepTimerChanged];
+                    // Nothing to tell about if playback was paused already
+                    if self.is_playing() && self.toggle_play() == Some(false) {
+                        events.push(PlaybackEvent::PlaybackPaused);
+                        events.push(PlaybackEvent::SleepTimerFinished);
+                    }
+                    events
+                }
+                _ => vec![],
+            },
             _ => vec![],
         }
     }
 }
 
+impl UpdatableState for PlaybackState {
+    type Action = PlaybackAction;
+    type Event = PlaybackEvent;
+
+    fn update_with(&mut self, action: Cow<Self::Action>) -> Vec<Self::Event> {
+        // This has to be read before the action moves us away from the episode
+        let progress = self.episode_progress();
+        let previous = self.current_song();
+        // Going back through the history shouldn't add to it
+        let going_back = matches!(action.as_ref(), PlaybackAction::Previous);
+        let mut events = self.reduce(action.into_owned());
+        if let Some(song) =
+            previous.filter(|song| !going_back && self.current_song_id().as_ref() != Some(&song.id))
+        {
+            self.remember(song);
+        }
+        let leaving = events.iter().any(|e| {
+            matches!(
+                e,
+                PlaybackEvent::TrackChanged(_)
+                    | PlaybackEvent::SourceChanged
+                    | PlaybackEvent::PlaybackPaused
+                    | PlaybackEvent::PlaybackStopped
+            )
+        });
+        if let (true, Some((id, position))) = (leaving, progress) {
+            events.push(PlaybackEvent::EpisodeProgress(id, position));
+        }
+        events
+    }
+}
+
 // A struct to keep track of the playback position
 // Caller must call pause/play at the right time
 #[derive(Debug)]
@@ -553,6 +1163,9 @@ mod tests {
             duration: 1000,
             art: None,
             track_number: None,
+            added_at: None,
+            explicit: false,
+            is_playable: true,
         }
     }
 
@@ -601,6 +1214,352 @@ mod tests {
         assert!(!state.is_playing());
     }
 
+    fn explicit_song(id: &str) -> SongDescription {
+        SongDescription {
+            explicit: true,
+            ..song(id)
+        }
+    }
+
+    #[test]
+    fn test_skip_explicit() {
+        let mut state = PlaybackState::default();
+        state.queue(vec![
+            song("1"),
+            explicit_song("2"),
+            explicit_song("3"),
+            song("4"),
+        ]);
+        state.play("1");
+
+        // Nothing is blocked by default
+        assert_eq!(state.next_index(), Some(1));
+
+        state.update_with(Cow::Owned(PlaybackAction::SetAccountBlocksExplicit(true)));
+        assert_eq!(state.next_index(), Some(3));
+
+        state.play_next();
+        assert_eq!(state.current_song_id(), Some("4".to_string()));
+        assert_eq!(state.prev_index(), Some(0));
+        assert_eq!(state.next_index(), None);
+
+        state.update_with(Cow::Owned(PlaybackAction::SetRepeatMode(
+            RepeatMode::Playlist,
+        )));
+        assert_eq!(state.next_index(), Some(0));
+    }
+
+    fn unavailable_song(id: &str) -> SongDescription {
+        SongDescription {
+            is_playable: false,
+            ..song(id)
+        }
+    }
+
+    fn unavailable(id: &str) -> PlaybackAction {
+        PlaybackAction::TrackUnavailable(id.to_string())
+    }
+
+    fn skipped(events: &[PlaybackEvent]) -> Option<usize> {
+        events.iter().find_map(|e| match e {
+            PlaybackEvent::UnavailableSkipped(n) => Some(*n),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn test_skip_unavailable() {
+        let mut state = PlaybackState::default();
+        state.queue(vec![
+            song("1"),
+            unavailable_song("2"),
+            unavailable_song("3"),
+            song("4"),
+        ]);
+        state.play("1");
+        assert_eq!(state.next_index(), Some(3));
+
+        // Reported once the next track actually plays
+        let events = state.update_with(Cow::Owned(PlaybackAction::Next));
+        assert_eq!(state.current_song_id(), Some("4".to_string()));
+        assert_eq!(skipped(&events), None);
+        let events = state.update_with(Cow::Owned(PlaybackAction::SyncSeek(0)));
+        assert_eq!(skipped(&events), Some(2));
+
+        let events = state.update_with(Cow::Owned(PlaybackAction::Load("2".to_string())));
+        assert!(matches!(&events[..], [PlaybackEvent::TrackUnavailable(id)] if id == "2"));
+        assert_eq!(state.current_song_id(), Some("4".to_string()));
+    }
+
+    #[test]
+    fn test_nothing_playable() {
+        let mut state = PlaybackState::default();
+        state.queue(vec![song("1"), song("2"), song("3")]);
+        state.update_with(Cow::Owned(PlaybackAction::SetRepeatMode(
+            RepeatMode::Playlist,
+        )));
+        state.play("1");
+
+        // The player fails to load every single one of them
+        state.update_with(Cow::Owned(unavailable("1")));
+        assert_eq!(state.current_song_id(), Some("2".to_string()));
+        state.update_with(Cow::Owned(unavailable("2")));
+        assert_eq!(state.current_song_id(), Some("3".to_string()));
+        let events = state.update_with(Cow::Owned(unavailable("3")));
+        assert!(events
+            .iter()
+            .any(|e| matches!(e, PlaybackEvent::NothingPlayable)));
+        assert_eq!(state.current_song_id(), None);
+    }
+
+    #[test]
+    fn test_stale_track_unavailable_is_ignored() {
+        let mut state = PlaybackState::default();
+        state.queue(vec![song("1"), song("2"), song("3")]);
+        state.play("1");
+        state.update_with(Cow::Owned(PlaybackAction::Next));
+
+        // That failure came in after we moved on
+        let events = state.update_with(Cow::Owned(unavailable("1")));
+        assert!(events.is_empty());
+        assert_eq!(state.current_song_id(), Some("2".to_string()));
+    }
+
+    #[test]
+    fn test_skipped_tracks_dont_count_as_failed_loads() {
+        let mut state = PlaybackState::default();
+        state.queue(vec![
+            song("1"),
+            unavailable_song("2"),
+            song("3"),
+            unavailable_song("4"),
+            song("5"),
+        ]);
+        state.update_with(Cow::Owned(PlaybackAction::SetRepeatMode(
+            RepeatMode::Playlist,
+        )));
+        state.play("1");
+
+        // Going around twice skips as many tracks as there are in the queue
+        for _ in 0..4 {
+            state.update_with(Cow::Owned(PlaybackAction::Next));
+        }
+        assert_eq!(state.current_song_id(), Some("5".to_string()));
+
+        // That's still only one track the player failed to load
+        let events = state.update_with(Cow::Owned(unavailable("5")));
+        assert!(!events
+            .iter()
+            .any(|e| matches!(e, PlaybackEvent::NothingPlayable)));
+        assert_eq!(state.current_song_id(), Some("1".to_string()));
+    }
+
+    #[test]
+    fn test_repeat_song() {
+        let mut state = PlaybackState::default();
+        state.queue(vec![song("1"), song("2")]);
+        state.play("2");
+        state.update_with(Cow::Owned(PlaybackAction::SetRepeatMode(RepeatMode::Song)));
+
+        // That's what gapless playback should have ready
+        let events = state.update_with(Cow::Owned(PlaybackAction::Preload));
+        assert!(matches!(&events[..], [PlaybackEvent::Preload(id)] if id == "2"));
+
+        state.update_with(Cow::Owned(PlaybackAction::EndOfTrack));
+        assert_eq!(state.current_song_id(), Some("2".to_string()));
+
+        // Skipping moves on, wrapping around
+        state.update_with(Cow::Owned(PlaybackAction::Next));
+        assert_eq!(state.current_song_id(), Some("1".to_string()));
+        assert_eq!(state.repeat_mode(), RepeatMode::Song);
+    }
+
+    #[test]
+    fn test_cue() {
+        let mut state = PlaybackState::default();
+        state.queue(vec![song("1"), song("2")]);
+
+        let events = state.update_with(Cow::Owned(PlaybackAction::Cue {
+            id: "2".to_string(),
+            position: 1_000,
+        }));
+        assert!(matches!(
+            events[..],
+            [
+                PlaybackEvent::TrackChanged(_),
+                PlaybackEvent::PlaybackPaused,
+                PlaybackEvent::TrackSeeked(1_000)
+            ]
+        ));
+        assert_eq!(state.current_song_id(), Some("2".to_string()));
+        assert!(!state.is_playing());
+        assert_eq!(state.position_ms(), 1_000);
+
+        // Nothing to pick if it's gone
+        let events = state.update_with(Cow::Owned(PlaybackAction::Cue {
+            id: "3".to_string(),
+            position: 0,
+        }));
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn test_sleep_timer_end_of_track() {
+        let mut state = PlaybackState::default();
+        state.queue(vec![song("1"), song("2")]);
+        state.play("1");
+        state.update_with(Cow::Owned(PlaybackAction::SetSleepTimer(Some(
+            SleepTimer::EndOfTrack,
+        ))));
+
+        // Survives skipping around
+        state.update_with(Cow::Owned(PlaybackAction::Next));
+        state.update_with(Cow::Owned(PlaybackAction::Previous));
+        assert_eq!(state.sleep_timer(), Some(SleepTimer::EndOfTrack));
+
+        let events = state.update_with(Cow::Owned(PlaybackAction::EndOfTrack));
+        assert!(events
+            .iter()
+            .any(|e| matches!(e, PlaybackEvent::SleepTimerFinished)));
+        assert_eq!(state.current_song_id(), Some("2".to_string()));
+        assert!(!state.is_playing());
+        assert_eq!(state.sleep_timer(), None);
+    }
+
+    #[test]
+    fn test_sleep_timer_deadline() {
+        let mut state = PlaybackState::default();
+        state.queue(vec![song("1"), song("2")]);
+        state.play("1");
+
+        // A stale timeout shouldn't pause anything
+        state.update_with(Cow::Owned(PlaybackAction::SetSleepTimer(Some(
+            SleepTimer::in_minutes(15),
+        ))));
+        let events = state.update_with(Cow::Owned(PlaybackAction::SleepTimerExpired));
+        assert!(events.is_empty());
+        assert!(state.is_playing());
+
+        state.update_with(Cow::Owned(PlaybackAction::SetSleepTimer(Some(
+            SleepTimer::in_minutes(0),
+        ))));
+        let events = state.update_with(Cow::Owned(PlaybackAction::SleepTimerExpired));
+        assert!(events
+            .iter()
+            .any(|e| matches!(e, PlaybackEvent::PlaybackPaused)));
+        assert!(!state.is_playing());
+        assert_eq!(state.sleep_timer(), None);
+    }
+
+    #[test]
+    fn test_sleep_timer_while_paused() {
+        let mut state = PlaybackState::default();
+        state.queue(vec![song("1"), song("2")]);
+        state.play("1");
+        state.update_with(Cow::Owned(PlaybackAction::Pause));
+
+        state.update_with(Cow::Owned(PlaybackAction::SetSleepTimer(Some(
+            SleepTimer::in_minutes(0),
+        ))));
+        let events = state.update_with(Cow::Owned(PlaybackAction::SleepTimerExpired));
+        assert!(!events
+            .iter()
+            .any(|e| matches!(e, PlaybackEvent::SleepTimerFinished)));
+        assert!(!state.is_playing());
+        assert_eq!(state.sleep_timer(), None);
+    }
+
+    #[test]
+    fn test_repeat_playlist_wraps_around() {
+        let mut state = PlaybackState::default();
+        state.queue(vec![song("1"), song("2")]);
+        state.play("2");
+
+        let events = state.update_with(Cow::Owned(PlaybackAction::EndOfTrack));
+        assert!(matches!(events[..], [PlaybackEvent::PlaybackStopped]));
+
+        state.play("2");
+        state.update_with(Cow::Owned(PlaybackAction::SetRepeatMode(
+            RepeatMode::Playlist,
+        )));
+        state.update_with(Cow::Owned(PlaybackAction::EndOfTrack));
+        assert_eq!(state.current_song_id(), Some("1".to_string()));
+    }
+
+    #[test]
+    fn test_refuse_explicit() {
+        let mut state = PlaybackState::default();
+        state.queue(vec![song("1"), explicit_song("2")]);
+        state.update_with(Cow::Owned(PlaybackAction::SetBlockExplicit(true)));
+
+        let events = state.update_with(Cow::Owned(PlaybackAction::Load("2".to_string())));
+        assert!(matches!(
+            events.as_slice(),
+            [PlaybackEvent::TrackBlocked(id)] if id == "2"
+        ));
+        assert_eq!(state.current_song_id(), None);
+
+        state.update_with(Cow::Owned(PlaybackAction::Load("1".to_string())));
+        assert_eq!(state.current_song_id(), Some("1".to_string()));
+        assert_eq!(state.next_index(), None);
+
+        state.update_with(Cow::Owned(PlaybackAction::SetBlockExplicit(false)));
+        assert_eq!(state.next_index(), Some(1));
+    }
+
+    fn episode(id: &str, duration: u32) -> SongDescription {
+        SongDescription {
+            uri: format!("spotify:episode:{id}"),
+            duration,
+            ..song(id)
+        }
+    }
+
+    fn episode_progress(events: &[PlaybackEvent]) -> Option<(String, u32)> {
+        events.iter().find_map(|e| match e {
+            PlaybackEvent::EpisodeProgress(id, position) => Some((id.clone(), *position)),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn test_episode_progress() {
+        let three_hours = 3 * 60 * 60 * 1000;
+        let mut state = PlaybackState::default();
+        state.update_with(Cow::Owned(PlaybackAction::LoadSongs(vec![
+            episode("ep", three_hours),
+            song("track"),
+        ])));
+        state.update_with(Cow::Owned(PlaybackAction::Load("ep".to_string())));
+
+        // Seeking is in milliseconds, even two hours in
+        state.update_with(Cow::Owned(PlaybackAction::Seek(2 * 60 * 60 * 1000)));
+        let events = state.update_with(Cow::Owned(PlaybackAction::Pause));
+        let (id, position) = episode_progress(&events).unwrap();
+        assert_eq!(id, "ep");
+        assert!((7_200_000..7_201_000).contains(&position));
+
+        // Regular tracks aren't tracked
+        state.update_with(Cow::Owned(PlaybackAction::Load("track".to_string())));
+        let events = state.update_with(Cow::Owned(PlaybackAction::Pause));
+        assert_eq!(episode_progress(&events), None);
+    }
+
+    #[test]
+    fn test_episode_progress_near_the_end() {
+        let mut state = PlaybackState::default();
+        state.update_with(Cow::Owned(PlaybackAction::LoadSongs(vec![
+            episode("ep", 60 * 60 * 1000),
+            song("track"),
+        ])));
+        state.update_with(Cow::Owned(PlaybackAction::Load("ep".to_string())));
+        state.update_with(Cow::Owned(PlaybackAction::Seek(60 * 60 * 1000 - 5_000)));
+
+        let events = state.update_with(Cow::Owned(PlaybackAction::Next));
+        assert_eq!(episode_progress(&events), Some(("ep".to_string(), 0)));
+        assert_eq!(state.current_song_id(), Some("track".to_string()));
+    }
+
     #[test]
     fn test_queue() {
         let mut state = PlaybackState::default();
@@ -614,6 +1573,148 @@ mod tests {
         assert_eq!(state.songs().len(), 4);
     }
 
+    #[test]
+    fn test_queue_starts_playback() {
+        let mut state = PlaybackState::default();
+        let events = state.update_with(Cow::Owned(PlaybackAction::Queue(vec![
+            song("1"),
+            song("2"),
+        ])));
+        assert!(events
+            .iter()
+            .any(|e| matches!(e, PlaybackEvent::TrackChanged(id) if id == "1")));
+        assert_eq!(state.current_song_id(), Some("1".to_string()));
+
+        // Queuing a song twice is fine
+        state.update_with(Cow::Owned(PlaybackAction::Queue(vec![
+            song("2"),
+            song("3"),
+        ])));
+        assert_eq!(state.song_ids(), vec!["1", "2", "2", "3"]);
+        assert_eq!(state.current_song_id(), Some("1".to_string()));
+    }
+
+    #[test]
+    fn test_queue_next() {
+        let mut state = PlaybackState::default();
+        state.queue(vec![song("1"), song("2"), song("3")]);
+        state.play("2");
+
+        state.update_with(Cow::Owned(PlaybackAction::QueueNext(vec![
+            song("4"),
+            song("5"),
+        ])));
+        assert_eq!(state.song_ids(), vec!["1", "2", "4", "5", "3"]);
+        assert_eq!(state.current_song_id(), Some("2".to_string()));
+        assert_eq!(state.next_id(), Some("4".to_string()));
+
+        // Already queued, it's there twice
+        state.update_with(Cow::Owned(PlaybackAction::QueueNext(vec![song("1")])));
+        assert_eq!(state.song_ids(), vec!["1", "2", "1", "4", "5", "3"]);
+        assert_eq!(state.current_song_id(), Some("2".to_string()));
+    }
+
+    #[test]
+    fn test_history() {
+        let mut state = PlaybackState::default();
+        state.queue(vec![song("1"), song("2"), song("3")]);
+        state.update_with(Cow::Owned(PlaybackAction::Load("3".to_string())));
+        state.update_with(Cow::Owned(PlaybackAction::Load("1".to_string())));
+        state.update_with(Cow::Owned(PlaybackAction::Next));
+
+        let history = |state: &PlaybackState| {
+            state
+                .history()
+                .collect()
+                .into_iter()
+                .map(|s| s.id)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(history(&state), vec!["1", "3"]);
+
+        // What played before, rather than what comes before in the list
+        state.update_with(Cow::Owned(PlaybackAction::Previous));
+        assert_eq!(state.current_song_id(), Some("1".to_string()));
+        state.update_with(Cow::Owned(PlaybackAction::Previous));
+        assert_eq!(state.current_song_id(), Some("3".to_string()));
+        assert!(history(&state).is_empty());
+    }
+
+    #[test]
+    fn test_sorted_songs_keep_their_source() {
+        let mut state = PlaybackState::default();
+        let source = SongsSource::Playlist("p".to_string());
+
+        state.update_with(Cow::Owned(PlaybackAction::LoadSortedSongs(
+            source.clone(),
+            vec![song("2"), song("1")],
+        )));
+        state.update_with(Cow::Owned(PlaybackAction::Load("2".to_string())));
+        assert_eq!(state.current_source(), Some(&source));
+
+        // In the order given, not the playlist's
+        state.update_with(Cow::Owned(PlaybackAction::Next));
+        assert_eq!(state.current_song_id(), Some("1".to_string()));
+    }
+
+    #[test]
+    fn test_radio() {
+        let mut state = PlaybackState::default();
+        state.queue(vec![song("1"), song("2")]);
+
+        state.update_with(Cow::Owned(PlaybackAction::LoadRadio(
+            "Artist".to_string(),
+            vec![song("3"), song("4")],
+        )));
+        // Whatever was queued before is gone
+        assert_eq!(state.songs().len(), 2);
+        assert_eq!(state.radio(), Some("Artist"));
+
+        // ...and it's no longer just the radio once something else is queued
+        state.queue(vec![song("5")]);
+        assert_eq!(state.radio(), None);
+    }
+
+    #[test]
+    fn test_append_paged_songs() {
+        let batch = |offset: usize, ids: [&str; 2]| SongBatch {
+            batch: Batch {
+                offset,
+                batch_size: 50,
+                total: 100,
+            },
+            songs: ids.iter().map(|id| song(id)).collect(),
+        };
+        let source = SongsSource::Playlist("playlist".to_string());
+        let mut state = PlaybackState::default();
+        state.update_with(Cow::Owned(PlaybackAction::LoadPagedSongs(
+            source.clone(),
+            batch(0, ["1", "2"]),
+        )));
+
+        // Something else is being played by the time that page shows up
+        let events = state.update_with(Cow::Owned(PlaybackAction::AppendPagedSongs(
+            SongsSource::Album("album".to_string()),
+            batch(50, ["3", "4"]),
+        )));
+        assert!(events.is_empty());
+        assert_eq!(state.song_ids(), vec!["1", "2"]);
+
+        state.update_with(Cow::Owned(PlaybackAction::AppendPagedSongs(
+            source.clone(),
+            batch(50, ["3", "4"]),
+        )));
+        assert_eq!(state.song_ids(), vec!["1", "2", "3", "4"]);
+
+        // Loading the same page again doesn't add anything
+        let events = state.update_with(Cow::Owned(PlaybackAction::AppendPagedSongs(
+            source,
+            batch(0, ["1", "2"]),
+        )));
+        assert!(events.is_empty());
+        assert_eq!(state.song_ids(), vec!["1", "2", "3", "4"]);
+    }
+
     #[test]
     fn test_play_multiple() {
         let mut state = PlaybackState::default();
@@ -689,6 +1790,45 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_shuffle_keeps_current_track() {
+        let mut state = PlaybackState::default();
+        state.queue(vec![song("1"), song("2"), song("3"), song("4")]);
+        state.play("3");
+
+        state.set_shuffled(true);
+        assert_eq!(state.current_song_id(), Some("3".to_string()));
+        state.play_next();
+        let shuffled_id = state.current_song_id();
+        assert_ne!(shuffled_id, Some("3".to_string()));
+
+        state.set_shuffled(false);
+        assert_eq!(state.current_song_id(), shuffled_id);
+    }
+
+    #[test]
+    fn test_shuffle_when_stopped() {
+        let mut state = PlaybackState::default();
+        state.queue(vec![song("1"), song("2"), song("3")]);
+
+        state.set_shuffled(true);
+        assert_eq!(state.current_position(), None);
+        state.set_shuffled(false);
+        assert_eq!(state.current_position(), None);
+    }
+
+    #[test]
+    fn test_preload_shuffled() {
+        let mut state = PlaybackState::default();
+        state.queue(vec![song("1"), song("2"), song("3"), song("4")]);
+        state.set_shuffled(true);
+        state.play("1");
+
+        let preloaded = state.next_id();
+        state.play_next();
+        assert_eq!(state.current_song_id(), preloaded);
+    }
+
     #[test]
     fn test_shuffle_queue() {
         let mut state = PlaybackState::default();
@@ -745,6 +1885,51 @@ mod tests {
         assert_eq!(ids, vec!["1".to_string(), "2".to_string(), "3".to_string()]);
     }
 
+    #[test]
+    fn test_move_queue_item() {
+        let mut state = PlaybackState::default();
+        state.queue(vec![song("1"), song("2"), song("3"), song("4")]);
+        state.play("2");
+
+        let events =
+            state.update_with(Cow::Owned(PlaybackAction::MoveQueueItem { from: 3, to: 0 }));
+        assert_eq!(state.song_ids(), vec!["4", "1", "2", "3"]);
+        assert_eq!(state.current_song_id(), Some("2".to_string()));
+        // What was preloaded is still what comes next
+        assert!(!events
+            .iter()
+            .any(|e| matches!(e, PlaybackEvent::Preload(_))));
+
+        let events =
+            state.update_with(Cow::Owned(PlaybackAction::MoveQueueItem { from: 0, to: 3 }));
+        assert_eq!(state.song_ids(), vec!["1", "2", "3", "4"]);
+        assert!(!events
+            .iter()
+            .any(|e| matches!(e, PlaybackEvent::Preload(_))));
+
+        let events =
+            state.update_with(Cow::Owned(PlaybackAction::MoveQueueItem { from: 3, to: 2 }));
+        assert_eq!(state.song_ids(), vec!["1", "2", "4", "3"]);
+        assert!(events
+            .iter()
+            .any(|e| matches!(e, PlaybackEvent::Preload(id) if id == "4")));
+    }
+
+    #[test]
+    fn test_move_queue_item_shuffled() {
+        let mut state = PlaybackState::default();
+        state.queue(vec![song("1"), song("2"), song("3"), song("4")]);
+        state.set_shuffled(true);
+        state.play("1");
+        let upcoming = state.next_id();
+
+        state.update_with(Cow::Owned(PlaybackAction::MoveQueueItem { from: 0, to: 3 }));
+        assert_eq!(state.song_ids(), vec!["2", "3", "4", "1"]);
+        // The playing order doesn't change
+        assert_eq!(state.current_song_id(), Some("1".to_string()));
+        assert_eq!(state.next_id(), upcoming);
+    }
+
     #[test]
     fn test_dequeue_last() {
         let mut state = PlaybackState::default();
@@ -787,4 +1972,118 @@ mod tests {
         state.dequeue(&["3".to_string()]);
         assert_eq!(state.current_song_id(), None);
     }
+
+    #[test]
+    fn test_dequeue_current() {
+        let mut state = PlaybackState::default();
+        state.queue(vec![song("1"), song("2"), song("3")]);
+        state.play("2");
+
+        let events = state.dequeue(&["2".to_string()]);
+        assert!(events
+            .iter()
+            .any(|e| matches!(e, PlaybackEvent::TrackChanged(id) if id == "3")));
+        assert_eq!(state.current_song_id(), Some("3".to_string()));
+        assert_eq!(state.current_position(), Some(1));
+        assert_eq!(state.song_ids(), vec!["1".to_string(), "3".to_string()]);
+    }
+
+    #[test]
+    fn test_dequeue_shuffled() {
+        let mut state = PlaybackState::default();
+        state.queue(vec![song("1"), song("2"), song("3"), song("4")]);
+        state.set_shuffled(true);
+        state.play("3");
+        state.play_next();
+        let current = state.current_song_id().unwrap();
+        let removed = ["1", "2", "3", "4"]
+            .iter()
+            .copied()
+            .find(|id| *id != current && *id != "3")
+            .unwrap()
+            .to_string();
+
+        state.dequeue(&[removed.clone()]);
+        assert_eq!(state.current_song_id(), Some(current.clone()));
+
+        // Whatever is left still comes up exactly once
+        let mut played = vec![current];
+        while let Some(id) = state.play_next() {
+            played.push(id);
+        }
+        played.sort();
+        let mut expected = state.song_ids();
+        expected.retain(|id| id != "3");
+        assert_eq!(played, expected);
+        assert!(!played.contains(&removed));
+    }
+
+    #[test]
+    fn test_move_to_next() {
+        let mut state = PlaybackState::default();
+        state.queue(vec![song("1"), song("2"), song("3"), song("4")]);
+        state.play("2");
+
+        assert!(state.move_to_next("4"));
+        assert_eq!(state.current_song_id(), Some("2".to_string()));
+        assert_eq!(state.next_id(), Some("4".to_string()));
+
+        // Already next
+        assert!(!state.move_to_next("4"));
+
+        assert!(state.move_to_next("1"));
+        assert_eq!(state.current_song_id(), Some("2".to_string()));
+        assert_eq!(state.current_position(), Some(0));
+        assert_eq!(
+            state.song_ids(),
+            vec![
+                "2".to_string(),
+                "1".to_string(),
+                "4".to_string(),
+                "3".to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_move_to_next_shuffled() {
+        let mut state = PlaybackState::default();
+        state.queue(vec![song("1"), song("2"), song("3"), song("4")]);
+        state.set_shuffled(true);
+        state.play("2");
+        let upcoming = state.index(1).map(|song| song.id);
+        let id = ["1", "3", "4"]
+            .iter()
+            .copied()
+            .find(|id| Some(id.to_string()) != upcoming)
+            .unwrap();
+
+        assert!(state.move_to_next(id));
+        assert_eq!(state.play_next(), Some(id.to_string()));
+        // The list itself is left alone
+        assert_eq!(
+            state.song_ids(),
+            vec![
+                "1".to_string(),
+                "2".to_string(),
+                "3".to_string(),
+                "4".to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_clear_upcoming() {
+        let mut state = PlaybackState::default();
+        state.queue(vec![song("1"), song("2"), song("3"), song("4")]);
+        state.play("2");
+
+        assert!(state.clear_upcoming());
+        assert_eq!(state.song_ids(), vec!["1".to_string(), "2".to_string()]);
+        assert_eq!(state.current_song_id(), Some("2".to_string()));
+        assert_eq!(state.next_id(), None);
+
+        // Nothing left to clear
+        assert!(!state.clear_upcoming());
+    }
 }
@@ -1,5 +1,6 @@
 use std::borrow::Cow;
 use std::cmp::PartialEq;
+use std::collections::HashSet;
 
 use super::{pagination::Pagination, BrowserAction, BrowserEvent, UpdatableState};
 use crate::app::models::*;
@@ -13,6 +14,7 @@ pub enum ScreenName {
     Artist(String),
     PlaylistDetails(String),
     User(String),
+    Show(String),
 }
 
 impl ScreenName {
@@ -24,6 +26,21 @@ impl ScreenName {
             Self::Artist(s) => Cow::Owned(format!("artist_{s}")),
             Self::PlaylistDetails(s) => Cow::Owned(format!("playlist_{s}")),
             Self::User(s) => Cow::Owned(format!("user_{s}")),
+            Self::Show(s) => Cow::Owned(format!("show_{s}")),
+        }
+    }
+
+    // The opposite of the above, used to restore the navigation from a previous session
+    pub fn from_identifier(identifier: &str) -> Option<Self> {
+        match identifier.split_once('_') {
+            None if identifier == "home" => Some(Self::Home),
+            None if identifier == "search" => Some(Self::Search),
+            Some(("album", id)) if !id.is_empty() => Some(Self::AlbumDetails(id.to_string())),
+            Some(("artist", id)) if !id.is_empty() => Some(Self::Artist(id.to_string())),
+            Some(("playlist", id)) if !id.is_empty() => Some(Self::PlaylistDetails(id.to_string())),
+            Some(("user", id)) if !id.is_empty() => Some(Self::User(id.to_string())),
+            Some(("show", id)) if !id.is_empty() => Some(Self::Show(id.to_string())),
+            _ => None,
         }
     }
 }
@@ -36,6 +53,40 @@ impl PartialEq for ScreenName {
 
 impl Eq for ScreenName {}
 
+// What a screen wants its headerbar to show
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HeaderState {
+    pub title: Option<String>,
+    pub subtitle: Option<String>,
+    // Only show the title once the screen's own (big) header is scrolled away
+    pub show_when_scrolled: bool,
+    // Whether that big header is currently visible
+    pub header_visible: bool,
+}
+
+impl HeaderState {
+    pub fn with_title(title: Option<String>) -> Self {
+        Self {
+            title,
+            ..Default::default()
+        }
+    }
+
+    // For screens with a big header, that starts out visible
+    fn scrolling() -> Self {
+        Self {
+            show_when_scrolled: true,
+            header_visible: true,
+            ..Default::default()
+        }
+    }
+
+    // The headerbar blends into the big header while it's visible
+    pub fn is_flat(&self) -> bool {
+        self.show_when_scrolled && self.header_visible
+    }
+}
+
 // ALBUM details
 pub struct DetailsState {
     pub id: String,
@@ -43,6 +94,7 @@ pub struct DetailsState {
     pub content: Option<AlbumFullDescription>,
     // Read the songs from here, not content (won't get more than the initial batch of songs)
     pub songs: SongListModel,
+    pub header: HeaderState,
 }
 
 impl DetailsState {
@@ -52,6 +104,7 @@ impl DetailsState {
             name: ScreenName::AlbumDetails(id),
             content: None,
             songs: SongListModel::new(50),
+            header: HeaderState::scrolling(),
         }
     }
 }
@@ -64,9 +117,15 @@ impl UpdatableState for DetailsState {
         match action.as_ref() {
             BrowserAction::SetAlbumDetails(album) if album.description.id == self.id => {
                 let AlbumDescription { id, songs, .. } = album.description.clone();
-                self.songs.add(songs).commit();
+                // Whatever was shown before (from the cache) is out of date
+                self.songs.clear().and(move |s| s.add(songs)).commit();
+                self.header.title = Some(album.description.title.clone());
+                self.header.subtitle = Some(album.description.artists_name());
                 self.content = Some(*album.clone());
-                vec![BrowserEvent::AlbumDetailsLoaded(id)]
+                vec![
+                    BrowserEvent::AlbumDetailsLoaded(id),
+                    BrowserEvent::HeaderUpdated(self.name.clone()),
+                ]
             }
             BrowserAction::AppendAlbumTracks(id, batch) if id == &self.id => {
                 self.songs.add(*batch.clone()).commit();
@@ -100,6 +159,7 @@ pub struct PlaylistDetailsState {
     pub playlist: Option<PlaylistDescription>,
     // Read the songs from here, not content (won't get more than the initial batch of songs)
     pub songs: SongListModel,
+    pub header: HeaderState,
 }
 
 impl PlaylistDetailsState {
@@ -109,6 +169,7 @@ impl PlaylistDetailsState {
             name: ScreenName::PlaylistDetails(id),
             playlist: None,
             songs: SongListModel::new(100),
+            header: HeaderState::scrolling(),
         }
     }
 }
@@ -120,16 +181,26 @@ impl UpdatableState for PlaylistDetailsState {
     fn update_with(&mut self, action: Cow<Self::Action>) -> Vec<Self::Event> {
         match action.as_ref() {
             BrowserAction::SetPlaylistDetails(playlist, song_batch) if playlist.id == self.id => {
-                let PlaylistDescription { id, .. } = *playlist.clone();
-                self.songs.add(*song_batch.clone()).commit();
+                let PlaylistDescription { id, title, .. } = *playlist.clone();
+                let song_batch = *song_batch.clone();
+                // Whatever was shown before (from the cache) is out of date
+                self.songs.clear().and(move |s| s.add(song_batch)).commit();
+                self.header.title = Some(title);
                 self.playlist = Some(*playlist.clone());
-                vec![BrowserEvent::PlaylistDetailsLoaded(id)]
+                vec![
+                    BrowserEvent::PlaylistDetailsLoaded(id),
+                    BrowserEvent::HeaderUpdated(self.name.clone()),
+                ]
             }
             BrowserAction::UpdatePlaylistName(PlaylistSummary { id, title }) if id == &self.id => {
                 if let Some(p) = self.playlist.as_mut() {
                     p.title = title.clone();
                 }
-                vec![BrowserEvent::PlaylistDetailsLoaded(self.id.clone())]
+                self.header.title = Some(title.clone());
+                vec![
+                    BrowserEvent::PlaylistDetailsLoaded(self.id.clone()),
+                    BrowserEvent::HeaderUpdated(self.name.clone()),
+                ]
             }
             BrowserAction::AppendPlaylistTracks(id, song_batch) if id == &self.id => {
                 self.songs.add(*song_batch.clone()).commit();
@@ -139,6 +210,13 @@ impl UpdatableState for PlaylistDetailsState {
                 self.songs.remove(&uris[..]).commit();
                 vec![BrowserEvent::PlaylistTracksRemoved(self.id.clone())]
             }
+            BrowserAction::SetPlaylistSort(id, sort) if id == &self.id => {
+                if self.songs.set_sort(*sort).commit() {
+                    vec![BrowserEvent::PlaylistSortChanged(self.id.clone())]
+                } else {
+                    vec![]
+                }
+            }
             _ => vec![],
         }
     }
@@ -148,9 +226,16 @@ pub struct ArtistState {
     pub id: String,
     pub name: ScreenName,
     pub artist: Option<String>,
+    // None until we've asked Spotify
+    pub is_followed: Option<bool>,
     pub next_page: Pagination<String>,
     pub albums: ListStore<AlbumModel>,
     pub top_tracks: SongListModel,
+    // Only a few top tracks are shown, but all of them are played
+    pub all_top_tracks: Vec<SongDescription>,
+    // Only loaded once scrolled to the bottom of the page
+    pub related_artists: ListStore<ArtistModel>,
+    pub header: HeaderState,
 }
 
 impl ArtistState {
@@ -159,9 +244,13 @@ impl ArtistState {
             id: id.clone(),
             name: ScreenName::Artist(id.clone()),
             artist: None,
+            is_followed: None,
             next_page: Pagination::new(id, 20),
             albums: ListStore::new(),
             top_tracks: SongListModel::new(10),
+            all_top_tracks: vec![],
+            related_artists: ListStore::new(),
+            header: HeaderState::default(),
         }
     }
 }
@@ -179,21 +268,35 @@ impl UpdatableState for ArtistState {
                     albums,
                     mut top_tracks,
                 } = *details.clone();
+                self.header.title = Some(name.clone());
                 self.artist = Some(name);
                 self.albums
                     .replace_all(albums.into_iter().map(|a| a.into()));
                 self.next_page.reset_count(self.albums.len());
 
+                self.all_top_tracks = top_tracks.clone();
                 top_tracks.truncate(5);
                 self.top_tracks.append(top_tracks).commit();
 
-                vec![BrowserEvent::ArtistDetailsUpdated(id)]
+                vec![
+                    BrowserEvent::ArtistDetailsUpdated(id),
+                    BrowserEvent::HeaderUpdated(self.name.clone()),
+                ]
             }
             BrowserAction::AppendArtistReleases(id, albums) if id == &self.id => {
                 self.next_page.set_loaded_count(albums.len());
                 self.albums.extend(albums.iter().map(|a| a.into()));
                 vec![BrowserEvent::ArtistDetailsUpdated(self.id.clone())]
             }
+            BrowserAction::SetArtistFollowed(id, is_followed) if id == &self.id => {
+                self.is_followed = Some(*is_followed);
+                vec![BrowserEvent::ArtistFollowChanged(self.id.clone())]
+            }
+            BrowserAction::SetRelatedArtists(id, artists) if id == &self.id => {
+                self.related_artists
+                    .replace_all(artists.iter().map(|a| a.into()));
+                vec![BrowserEvent::RelatedArtistsUpdated(self.id.clone())]
+            }
             _ => vec![],
         }
     }
@@ -205,9 +308,70 @@ pub struct HomeState {
     pub visible_page: &'static str,
     pub next_albums_page: Pagination<()>,
     pub albums: ListStore<AlbumModel>,
+    pub recently_played: ListStore<AlbumModel>,
+    // Same as above, to know whether that's an album or a playlist
+    pub recently_played_items: Vec<RecentlyPlayed>,
+    pub next_releases_page: Pagination<()>,
+    pub new_releases: ListStore<AlbumModel>,
+    // Same as above, in the order we got them, to sort them again when followed artists change
+    pub new_releases_items: Vec<AlbumDescription>,
     pub next_playlists_page: Pagination<()>,
     pub playlists: ListStore<AlbumModel>,
+    pub followed_artists: ListStore<ArtistModel>,
+    pub next_artists_cursor: Option<String>,
     pub saved_tracks: SongListModel,
+    // Tracks known to be saved, whether the list above got that far or not
+    pub saved_track_ids: HashSet<String>,
+    pub next_shows_page: Pagination<()>,
+    pub shows: ListStore<AlbumModel>,
+    pub top_range: TopRange,
+    pub top_tracks: ListStore<AlbumModel>,
+    // Same as above, to queue them
+    pub top_track_items: Vec<SongDescription>,
+    pub top_artists: ListStore<ArtistModel>,
+}
+
+impl HomeState {
+    // Only reports the tracks that actually changed
+    fn set_tracks_saved(
+        &mut self,
+        tracks: impl IntoIterator<Item = (String, bool)>,
+    ) -> Vec<BrowserEvent> {
+        tracks
+            .into_iter()
+            .filter_map(|(id, saved)| {
+                if saved && self.saved_track_ids.insert(id.clone()) {
+                    Some(BrowserEvent::TrackSaved(id))
+                } else if !saved && self.saved_track_ids.remove(&id) {
+                    Some(BrowserEvent::TrackUnsaved(id))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
+impl HomeState {
+    // Releases from followed artists come first, as far as we know who they are
+    fn followed_first<'a>(&self, albums: &'a [AlbumDescription]) -> Vec<&'a AlbumDescription> {
+        let followed: HashSet<String> = self.followed_artists.iter().map(|a| a.id()).collect();
+        let mut albums: Vec<&AlbumDescription> = albums.iter().collect();
+        albums.sort_by_key(|album| !album.artists.iter().any(|a| followed.contains(&a.id)));
+        albums
+    }
+
+    // The releases already loaded are sorted again, rather than fetched again
+    fn followed_artists_updated(&mut self) -> Vec<BrowserEvent> {
+        let mut events = vec![BrowserEvent::FollowedArtistsUpdated];
+        let content = self.followed_first(&self.new_releases_items);
+        if !self.new_releases.eq(&content, |a, b| a.uri() == b.id) {
+            self.new_releases
+                .replace_all(content.into_iter().map(|a| a.into()));
+            events.push(BrowserEvent::NewReleasesUpdated);
+        }
+        events
+    }
 }
 
 impl Default for HomeState {
@@ -217,9 +381,23 @@ impl Default for HomeState {
             visible_page: "library",
             next_albums_page: Pagination::new((), 30),
             albums: ListStore::new(),
+            recently_played: ListStore::new(),
+            recently_played_items: vec![],
+            next_releases_page: Pagination::new((), 30),
+            new_releases: ListStore::new(),
+            new_releases_items: vec![],
             next_playlists_page: Pagination::new((), 30),
             playlists: ListStore::new(),
+            followed_artists: ListStore::new(),
+            next_artists_cursor: None,
             saved_tracks: SongListModel::new(50),
+            saved_track_ids: HashSet::new(),
+            next_shows_page: Pagination::new((), 30),
+            shows: ListStore::new(),
+            top_range: TopRange::default(),
+            top_tracks: ListStore::new(),
+            top_track_items: vec![],
+            top_artists: ListStore::new(),
         }
     }
 }
@@ -273,6 +451,58 @@ impl UpdatableState for HomeState {
                     vec![]
                 }
             }
+            BrowserAction::SetRecentlyPlayed(items) => {
+                if !self.recently_played.eq(items, |a, b| a.uri() == b.id()) {
+                    self.recently_played
+                        .replace_all(items.iter().map(|i| i.into()));
+                    self.recently_played_items = items.clone();
+                    vec![BrowserEvent::RecentlyPlayedUpdated]
+                } else {
+                    vec![]
+                }
+            }
+            BrowserAction::SetNewReleases(content) => {
+                self.new_releases_items.clone_from(content);
+                let content = self.followed_first(content);
+                if !self.new_releases.eq(&content, |a, b| a.uri() == b.id) {
+                    self.new_releases
+                        .replace_all(content.into_iter().map(|a| a.into()));
+                    self.next_releases_page.reset_count(self.new_releases.len());
+                    vec![BrowserEvent::NewReleasesUpdated]
+                } else {
+                    vec![]
+                }
+            }
+            BrowserAction::AppendNewReleases(content) => {
+                self.next_releases_page.set_loaded_count(content.len());
+                self.new_releases_items.extend(content.iter().cloned());
+                let content = self.followed_first(content);
+                self.new_releases
+                    .extend(content.into_iter().map(|a| a.into()));
+                vec![BrowserEvent::NewReleasesUpdated]
+            }
+            BrowserAction::SetTopRange(range) if *range != self.top_range => {
+                self.top_range = *range;
+                vec![BrowserEvent::TopRangeChanged(*range)]
+            }
+            BrowserAction::SetTopTracks(range, tracks) if *range == self.top_range => {
+                if !self.top_tracks.eq(tracks, |a, b| a.uri() == b.id) {
+                    self.top_tracks.replace_all(tracks.iter().map(|t| t.into()));
+                    self.top_track_items = tracks.clone();
+                    vec![BrowserEvent::TopTracksUpdated]
+                } else {
+                    vec![]
+                }
+            }
+            BrowserAction::SetTopArtists(range, artists) if *range == self.top_range => {
+                if !self.top_artists.eq(artists, |a, b| a.id() == b.id) {
+                    self.top_artists
+                        .replace_all(artists.iter().map(|a| a.into()));
+                    vec![BrowserEvent::TopArtistsUpdated]
+                } else {
+                    vec![]
+                }
+            }
             BrowserAction::SetPlaylistsContent(content) => {
                 if !self.playlists.eq(content, |a, b| a.uri() == b.id) {
                     self.playlists.replace_all(content.iter().map(|a| a.into()));
@@ -282,6 +512,44 @@ impl UpdatableState for HomeState {
                     vec![]
                 }
             }
+            BrowserAction::SetFollowedArtists(FollowedArtistsPage {
+                artists,
+                next_cursor,
+            }) => {
+                self.next_artists_cursor = next_cursor.clone();
+                if !self.followed_artists.eq(artists, |a, b| a.id() == b.id) {
+                    self.followed_artists
+                        .replace_all(artists.iter().map(|a| a.into()));
+                    self.followed_artists_updated()
+                } else {
+                    vec![]
+                }
+            }
+            BrowserAction::AppendFollowedArtists(FollowedArtistsPage {
+                artists,
+                next_cursor,
+            }) => {
+                self.next_artists_cursor = next_cursor.clone();
+                self.followed_artists
+                    .extend(artists.iter().map(|a| a.into()));
+                self.followed_artists_updated()
+            }
+            BrowserAction::AddFollowedArtist(artist)
+                if !self.followed_artists.iter().any(|a| a.id() == artist.id) =>
+            {
+                self.followed_artists
+                    .prepend(std::iter::once(artist.into()));
+                self.followed_artists_updated()
+            }
+            BrowserAction::SetArtistFollowed(id, false) => {
+                let position = self.followed_artists.iter().position(|a| &a.id() == id);
+                if let Some(position) = position {
+                    self.followed_artists.remove(position as u32);
+                    self.followed_artists_updated()
+                } else {
+                    vec![]
+                }
+            }
             BrowserAction::AppendPlaylistsContent(content) => {
                 self.next_playlists_page.set_loaded_count(content.len());
                 self.playlists.extend(content.iter().map(|p| p.into()));
@@ -304,13 +572,21 @@ impl UpdatableState for HomeState {
                 }
             }
             BrowserAction::AppendSavedTracks(song_batch) => {
+                let mut events = self
+                    .set_tracks_saved(song_batch.songs.iter().map(|song| (song.id.clone(), true)));
                 if self.saved_tracks.add(*song_batch.clone()).commit() {
-                    vec![BrowserEvent::SavedTracksUpdated]
-                } else {
-                    vec![]
+                    events.push(BrowserEvent::SavedTracksUpdated);
                 }
+                events
             }
             BrowserAction::SetSavedTracks(song_batch) => {
+                let mut events = self
+                    .set_tracks_saved(song_batch.songs.iter().map(|song| (song.id.clone(), true)));
+                // Refreshing with what we already have would reset the list (and the focus within it)
+                if self.saved_tracks.refresh_batch(song_batch) {
+                    events.push(BrowserEvent::SavedTracksUpdated);
+                    return events;
+                }
                 let song_batch = *song_batch.clone();
                 if self
                     .saved_tracks
@@ -318,18 +594,37 @@ impl UpdatableState for HomeState {
                     .and(|s| s.add(song_batch))
                     .commit()
                 {
-                    vec![BrowserEvent::SavedTracksUpdated]
-                } else {
-                    vec![]
+                    events.push(BrowserEvent::SavedTracksUpdated);
                 }
+                events
             }
             BrowserAction::SaveTracks(tracks) => {
                 self.saved_tracks.prepend(tracks.clone()).commit();
-                vec![BrowserEvent::SavedTracksUpdated]
+                let mut events =
+                    self.set_tracks_saved(tracks.iter().map(|song| (song.id.clone(), true)));
+                events.push(BrowserEvent::SavedTracksUpdated);
+                events
             }
             BrowserAction::RemoveSavedTracks(tracks) => {
                 self.saved_tracks.remove(&tracks[..]).commit();
-                vec![BrowserEvent::SavedTracksUpdated]
+                let mut events = self.set_tracks_saved(tracks.iter().map(|id| (id.clone(), false)));
+                events.push(BrowserEvent::SavedTracksUpdated);
+                events
+            }
+            BrowserAction::SetTracksSaved(tracks) => self.set_tracks_saved(tracks.iter().cloned()),
+            BrowserAction::SetSavedShows(content) => {
+                if !self.shows.eq(content, |a, b| a.uri() == b.id) {
+                    self.shows.replace_all(content.iter().map(|s| s.into()));
+                    self.next_shows_page.reset_count(self.shows.len());
+                    vec![BrowserEvent::SavedShowsUpdated]
+                } else {
+                    vec![]
+                }
+            }
+            BrowserAction::AppendSavedShows(content) => {
+                self.next_shows_page.set_loaded_count(content.len());
+                self.shows.extend(content.iter().map(|s| s.into()));
+                vec![BrowserEvent::SavedShowsUpdated]
             }
             _ => vec![],
         }
@@ -339,8 +634,51 @@ impl UpdatableState for HomeState {
 pub struct SearchState {
     pub name: ScreenName,
     pub query: String,
-    pub album_results: Vec<AlbumDescription>,
-    pub artist_results: Vec<ArtistSummary>,
+    // Bumped whenever the query changes, results for older generations are stale
+    pub generation: usize,
+    pub tracks: SongListModel,
+    pub next_tracks_page: Pagination<()>,
+    pub albums: ListStore<AlbumModel>,
+    pub next_albums_page: Pagination<()>,
+    pub artists: ListStore<ArtistModel>,
+    pub next_artists_page: Pagination<()>,
+    pub playlists: ListStore<AlbumModel>,
+    pub next_playlists_page: Pagination<()>,
+}
+
+impl SearchState {
+    pub fn next_page(&self, category: SearchCategory) -> &Pagination<()> {
+        match category {
+            SearchCategory::Tracks => &self.next_tracks_page,
+            SearchCategory::Albums => &self.next_albums_page,
+            SearchCategory::Artists => &self.next_artists_page,
+            SearchCategory::Playlists => &self.next_playlists_page,
+        }
+    }
+
+    fn next_page_mut(&mut self, category: SearchCategory) -> &mut Pagination<()> {
+        match category {
+            SearchCategory::Tracks => &mut self.next_tracks_page,
+            SearchCategory::Albums => &mut self.next_albums_page,
+            SearchCategory::Artists => &mut self.next_artists_page,
+            SearchCategory::Playlists => &mut self.next_playlists_page,
+        }
+    }
+
+    fn clear_results(&mut self) {
+        self.tracks.clear().commit();
+        self.albums.replace_all(std::iter::empty());
+        self.artists.replace_all(std::iter::empty());
+        self.playlists.replace_all(std::iter::empty());
+        for category in [
+            SearchCategory::Tracks,
+            SearchCategory::Albums,
+            SearchCategory::Artists,
+            SearchCategory::Playlists,
+        ] {
+            *self.next_page_mut(category) = Pagination::new((), 20);
+        }
+    }
 }
 
 impl Default for SearchState {
@@ -348,8 +686,15 @@ impl Default for SearchState {
         Self {
             name: ScreenName::Search,
             query: "".to_owned(),
-            album_results: vec![],
-            artist_results: vec![],
+            generation: 0,
+            tracks: SongListModel::new(20),
+            next_tracks_page: Pagination::new((), 20),
+            albums: ListStore::new(),
+            next_albums_page: Pagination::new((), 20),
+            artists: ListStore::new(),
+            next_artists_page: Pagination::new((), 20),
+            playlists: ListStore::new(),
+            next_playlists_page: Pagination::new((), 20),
         }
     }
 }
@@ -362,12 +707,37 @@ impl UpdatableState for SearchState {
         match action.as_ref() {
             BrowserAction::Search(query) if query != &self.query => {
                 self.query = query.clone();
+                self.generation += 1;
+                self.clear_results();
                 vec![BrowserEvent::SearchUpdated]
             }
-            BrowserAction::SetSearchResults(results) => {
-                self.album_results = results.albums.clone();
-                self.artist_results = results.artists.clone();
-                vec![BrowserEvent::SearchResultsUpdated]
+            // Results for an older query, or for a page we already got, are dropped
+            BrowserAction::AppendSearchResults(generation, category, offset, results)
+                if *generation == self.generation
+                    && self.next_page(*category).next_offset == Some(*offset) =>
+            {
+                let loaded_count = match category {
+                    SearchCategory::Tracks => {
+                        self.tracks.append(results.tracks.clone()).commit();
+                        results.tracks.len()
+                    }
+                    SearchCategory::Albums => {
+                        self.albums.extend(results.albums.iter().map(|a| a.into()));
+                        results.albums.len()
+                    }
+                    SearchCategory::Artists => {
+                        self.artists
+                            .extend(results.artists.iter().map(|a| a.into()));
+                        results.artists.len()
+                    }
+                    SearchCategory::Playlists => {
+                        self.playlists
+                            .extend(results.playlists.iter().map(|p| p.into()));
+                        results.playlists.len()
+                    }
+                };
+                self.next_page_mut(*category).set_loaded_count(loaded_count);
+                vec![BrowserEvent::SearchResultsUpdated(*category)]
             }
             _ => vec![],
         }
@@ -424,11 +794,154 @@ impl UpdatableState for UserState {
     }
 }
 
+// A podcast, and its episodes
+pub struct ShowState {
+    pub id: String,
+    pub name: ScreenName,
+    pub show: Option<ShowDescription>,
+    pub episodes: Vec<EpisodeDescription>,
+    pub next_page: Pagination<String>,
+    pub header: HeaderState,
+}
+
+impl ShowState {
+    pub fn new(id: String) -> Self {
+        Self {
+            id: id.clone(),
+            name: ScreenName::Show(id.clone()),
+            show: None,
+            episodes: vec![],
+            next_page: Pagination::new(id, 50),
+            header: HeaderState::default(),
+        }
+    }
+}
+
+impl UpdatableState for ShowState {
+    type Action = BrowserAction;
+    type Event = BrowserEvent;
+
+    fn update_with(&mut self, action: Cow<Self::Action>) -> Vec<Self::Event> {
+        match action.as_ref() {
+            BrowserAction::SetShowDetails(show) if show.id == self.id => {
+                self.episodes = show.episodes.clone();
+                self.next_page.reset_count(self.episodes.len());
+                self.header.title = Some(show.title.clone());
+                self.header.subtitle = Some(show.publisher.clone());
+                self.show = Some(*show.clone());
+                vec![
+                    BrowserEvent::ShowDetailsUpdated(self.id.clone()),
+                    BrowserEvent::HeaderUpdated(self.name.clone()),
+                ]
+            }
+            BrowserAction::AppendShowEpisodes(id, episodes) if id == &self.id => {
+                self.next_page.set_loaded_count(episodes.len());
+                self.episodes.extend(episodes.iter().cloned());
+                vec![BrowserEvent::ShowDetailsUpdated(self.id.clone())]
+            }
+            _ => vec![],
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
     use super::*;
 
+    #[test]
+    fn test_screen_name_identifier_round_trip() {
+        let names = vec![
+            ScreenName::Home,
+            ScreenName::Search,
+            ScreenName::AlbumDetails("abc".to_owned()),
+            ScreenName::Artist("def".to_owned()),
+            ScreenName::PlaylistDetails("37i9dQZF1DXcBWIGoYBM5M".to_owned()),
+            ScreenName::User("some_user_name".to_owned()),
+            ScreenName::Show("38bS44xjbVVZ3No3ByF1dJ".to_owned()),
+        ];
+        for name in names {
+            assert_eq!(ScreenName::from_identifier(&name.identifier()), Some(name));
+        }
+
+        assert_eq!(ScreenName::from_identifier("album_"), None);
+        assert_eq!(ScreenName::from_identifier("podcast_abc"), None);
+        assert_eq!(ScreenName::from_identifier(""), None);
+    }
+
+    #[test]
+    fn test_tracks_saved() {
+        let mut home = HomeState::default();
+
+        let events = home.update_with(Cow::Owned(BrowserAction::SetTracksSaved(vec![
+            ("a".to_owned(), true),
+            ("b".to_owned(), false),
+        ])));
+        assert_eq!(events, vec![BrowserEvent::TrackSaved("a".to_owned())]);
+
+        // Nothing new
+        let events = home.update_with(Cow::Owned(BrowserAction::SetTracksSaved(vec![(
+            "a".to_owned(),
+            true,
+        )])));
+        assert_eq!(events, vec![]);
+
+        let events = home.update_with(Cow::Owned(BrowserAction::RemoveSavedTracks(vec![
+            "a".to_owned()
+        ])));
+        assert!(events.contains(&BrowserEvent::TrackUnsaved("a".to_owned())));
+        assert!(home.saved_track_ids.is_empty());
+    }
+
+    #[test]
+    fn test_fresh_playlist_details_replace_cached_ones() {
+        let song = |id: &str| SongDescription {
+            id: id.to_owned(),
+            uri: "".to_owned(),
+            title: "Title".to_owned(),
+            artists: vec![],
+            album: AlbumRef {
+                id: "".to_owned(),
+                name: "".to_owned(),
+            },
+            duration: 1000,
+            art: None,
+            track_number: None,
+            added_at: None,
+            explicit: false,
+            is_playable: true,
+        };
+        let details = |ids: &[&str]| {
+            let songs = SongBatch {
+                songs: ids.iter().map(|id| song(id)).collect(),
+                batch: Batch {
+                    offset: 0,
+                    batch_size: 100,
+                    total: ids.len(),
+                },
+            };
+            let playlist = PlaylistDescription {
+                id: "id".to_owned(),
+                title: "Foo".to_owned(),
+                art: None,
+                songs: songs.clone(),
+                owner: UserRef {
+                    id: "".to_owned(),
+                    display_name: "".to_owned(),
+                },
+            };
+            BrowserAction::SetPlaylistDetails(Box::new(playlist), Box::new(songs))
+        };
+        let mut playlist_state = PlaylistDetailsState::new("id".to_owned());
+
+        // From the cache, then from the network
+        playlist_state.update_with(Cow::Owned(details(&["a", "b"])));
+        playlist_state.update_with(Cow::Owned(details(&["c"])));
+
+        let ids: Vec<String> = playlist_state.songs.map_collect(|s| s.id);
+        assert_eq!(ids, vec!["c".to_owned()]);
+    }
+
     #[test]
     fn test_next_page_no_next() {
         let mut artist_state = ArtistState::new("id".to_owned());
@@ -453,6 +966,7 @@ mod tests {
             artists: vec![],
             release_date: Some("1970-01-01".to_owned()),
             art: Some("".to_owned()),
+            large_art: None,
             songs: SongBatch::empty(),
             is_liked: false,
         };
@@ -478,4 +992,317 @@ mod tests {
         let next = &artist_state.next_page;
         assert_eq!(None, next.next_offset);
     }
+
+    #[test]
+    fn test_follow_state() {
+        let mut artist_state = ArtistState::new("id".to_owned());
+        assert_eq!(None, artist_state.is_followed);
+
+        let events = artist_state.update_with(Cow::Owned(BrowserAction::SetArtistFollowed(
+            "other_id".to_owned(),
+            true,
+        )));
+        assert!(events.is_empty());
+        assert_eq!(None, artist_state.is_followed);
+
+        let events = artist_state.update_with(Cow::Owned(BrowserAction::SetArtistFollowed(
+            "id".to_owned(),
+            true,
+        )));
+        assert_eq!(
+            events,
+            vec![BrowserEvent::ArtistFollowChanged("id".to_owned())]
+        );
+        assert_eq!(Some(true), artist_state.is_followed);
+    }
+
+    #[test]
+    fn test_search_results_for_old_query_are_ignored() {
+        let results = || {
+            Box::new(SearchResults {
+                artists: vec![ArtistSummary {
+                    id: "a".to_owned(),
+                    name: "Foo".to_owned(),
+                    photo: None,
+                }],
+                ..Default::default()
+            })
+        };
+        let mut search_state = SearchState::default();
+        search_state.update_with(Cow::Owned(BrowserAction::Search("foo".to_owned())));
+        let old_generation = search_state.generation;
+        search_state.update_with(Cow::Owned(BrowserAction::Search("foob".to_owned())));
+        let generation = search_state.generation;
+
+        let events = search_state.update_with(Cow::Owned(BrowserAction::AppendSearchResults(
+            old_generation,
+            SearchCategory::Artists,
+            0,
+            results(),
+        )));
+        assert!(events.is_empty());
+        assert_eq!(0, search_state.artists.len());
+
+        let events = search_state.update_with(Cow::Owned(BrowserAction::AppendSearchResults(
+            generation,
+            SearchCategory::Artists,
+            0,
+            results(),
+        )));
+        assert_eq!(
+            events,
+            vec![BrowserEvent::SearchResultsUpdated(SearchCategory::Artists)]
+        );
+        assert_eq!(1, search_state.artists.len());
+        assert_eq!(None, search_state.next_artists_page.next_offset);
+
+        // Same page again, e.g. scrolled to the bottom before the first one came back
+        let events = search_state.update_with(Cow::Owned(BrowserAction::AppendSearchResults(
+            generation,
+            SearchCategory::Artists,
+            0,
+            results(),
+        )));
+        assert!(events.is_empty());
+        assert_eq!(1, search_state.artists.len());
+    }
+
+    #[test]
+    fn test_related_artists() {
+        let artist = |id: &str| ArtistSummary {
+            id: id.to_owned(),
+            name: "Foo".to_owned(),
+            photo: None,
+        };
+        let mut artist_state = ArtistState::new("id".to_owned());
+
+        let events = artist_state.update_with(Cow::Owned(BrowserAction::SetRelatedArtists(
+            "other_id".to_owned(),
+            vec![artist("a")],
+        )));
+        assert!(events.is_empty());
+        assert_eq!(0, artist_state.related_artists.len());
+
+        let events = artist_state.update_with(Cow::Owned(BrowserAction::SetRelatedArtists(
+            "id".to_owned(),
+            vec![artist("a"), artist("b")],
+        )));
+        assert_eq!(
+            events,
+            vec![BrowserEvent::RelatedArtistsUpdated("id".to_owned())]
+        );
+        assert_eq!(2, artist_state.related_artists.len());
+    }
+
+    #[test]
+    fn test_followed_artists_cursor() {
+        let artist = |id: &str| ArtistSummary {
+            id: id.to_owned(),
+            name: "Foo".to_owned(),
+            photo: None,
+        };
+        let mut home_state = HomeState::default();
+        home_state.update_with(Cow::Owned(BrowserAction::SetFollowedArtists(
+            FollowedArtistsPage {
+                artists: vec![artist("a"), artist("b")],
+                next_cursor: Some("b".to_owned()),
+            },
+        )));
+        assert_eq!(Some("b".to_owned()), home_state.next_artists_cursor);
+
+        home_state.update_with(Cow::Owned(BrowserAction::AppendFollowedArtists(
+            FollowedArtistsPage {
+                artists: vec![artist("c")],
+                next_cursor: None,
+            },
+        )));
+        assert_eq!(None, home_state.next_artists_cursor);
+        assert_eq!(3, home_state.followed_artists.len());
+    }
+
+    #[test]
+    fn test_follow_unfollow_artist() {
+        let artist = |id: &str| ArtistSummary {
+            id: id.to_owned(),
+            name: "Foo".to_owned(),
+            photo: None,
+        };
+        let mut home_state = HomeState::default();
+        home_state.update_with(Cow::Owned(BrowserAction::SetFollowedArtists(
+            FollowedArtistsPage {
+                artists: vec![artist("a")],
+                next_cursor: None,
+            },
+        )));
+
+        let events =
+            home_state.update_with(Cow::Owned(BrowserAction::AddFollowedArtist(artist("b"))));
+        assert_eq!(events, vec![BrowserEvent::FollowedArtistsUpdated]);
+        assert_eq!("b", home_state.followed_artists.get(0).id());
+
+        let events =
+            home_state.update_with(Cow::Owned(BrowserAction::AddFollowedArtist(artist("b"))));
+        assert!(events.is_empty());
+        assert_eq!(2, home_state.followed_artists.len());
+
+        let events = home_state.update_with(Cow::Owned(BrowserAction::SetArtistFollowed(
+            "a".to_owned(),
+            false,
+        )));
+        assert_eq!(events, vec![BrowserEvent::FollowedArtistsUpdated]);
+        assert_eq!(1, home_state.followed_artists.len());
+    }
+
+    #[test]
+    fn test_recently_played() {
+        let playlist = |id: &str| {
+            RecentlyPlayed::Playlist(PlaylistDescription {
+                id: id.to_owned(),
+                title: "Foo".to_owned(),
+                art: None,
+                songs: SongBatch::empty(),
+                owner: UserRef {
+                    id: "me".to_owned(),
+                    display_name: "Me".to_owned(),
+                },
+            })
+        };
+        let mut home_state = HomeState::default();
+
+        let events = home_state.update_with(Cow::Owned(BrowserAction::SetRecentlyPlayed(vec![
+            playlist("a"),
+            playlist("b"),
+        ])));
+        assert_eq!(events, vec![BrowserEvent::RecentlyPlayedUpdated]);
+        assert_eq!(2, home_state.recently_played.len());
+        assert_eq!("a", home_state.recently_played_items[0].id());
+
+        let events = home_state.update_with(Cow::Owned(BrowserAction::SetRecentlyPlayed(vec![
+            playlist("a"),
+            playlist("b"),
+        ])));
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn test_top_items_for_other_range_are_ignored() {
+        let artist = |id: &str| ArtistSummary {
+            id: id.to_owned(),
+            name: "Foo".to_owned(),
+            photo: None,
+        };
+        let mut home_state = HomeState::default();
+
+        let events = home_state.update_with(Cow::Owned(BrowserAction::SetTopRange(TopRange::Long)));
+        assert_eq!(events, vec![BrowserEvent::TopRangeChanged(TopRange::Long)]);
+
+        // Still loading from before the range was changed
+        let events = home_state.update_with(Cow::Owned(BrowserAction::SetTopArtists(
+            TopRange::Medium,
+            vec![artist("a")],
+        )));
+        assert!(events.is_empty());
+        assert_eq!(0, home_state.top_artists.len());
+
+        let events = home_state.update_with(Cow::Owned(BrowserAction::SetTopArtists(
+            TopRange::Long,
+            vec![artist("a"), artist("b")],
+        )));
+        assert_eq!(events, vec![BrowserEvent::TopArtistsUpdated]);
+        assert_eq!(2, home_state.top_artists.len());
+
+        let song = |id: &str| SongDescription {
+            id: id.to_owned(),
+            uri: "".to_owned(),
+            title: "Title".to_owned(),
+            artists: vec![],
+            album: AlbumRef {
+                id: "".to_owned(),
+                name: "".to_owned(),
+            },
+            duration: 1000,
+            art: None,
+            track_number: None,
+            added_at: None,
+            explicit: false,
+            is_playable: true,
+        };
+        let events = home_state.update_with(Cow::Owned(BrowserAction::SetTopTracks(
+            TopRange::Medium,
+            vec![song("a")],
+        )));
+        assert!(events.is_empty());
+        assert_eq!(0, home_state.top_tracks.len());
+
+        let events = home_state.update_with(Cow::Owned(BrowserAction::SetTopTracks(
+            TopRange::Long,
+            vec![song("a"), song("b")],
+        )));
+        assert_eq!(events, vec![BrowserEvent::TopTracksUpdated]);
+        assert_eq!(2, home_state.top_tracks.len());
+        assert_eq!("b", home_state.top_track_items[1].id);
+    }
+
+    #[test]
+    fn test_new_releases_from_followed_artists_first() {
+        let album = |id: &str, artist: &str| AlbumDescription {
+            id: id.to_owned(),
+            title: "Foo".to_owned(),
+            artists: vec![ArtistRef {
+                id: artist.to_owned(),
+                name: "Bar".to_owned(),
+            }],
+            release_date: None,
+            art: None,
+            large_art: None,
+            songs: SongBatch::empty(),
+            is_liked: false,
+        };
+        let mut home_state = HomeState::default();
+        home_state.update_with(Cow::Owned(BrowserAction::SetFollowedArtists(
+            FollowedArtistsPage {
+                artists: vec![ArtistSummary {
+                    id: "followed".to_owned(),
+                    name: "Bar".to_owned(),
+                    photo: None,
+                }],
+                next_cursor: None,
+            },
+        )));
+
+        let events = home_state.update_with(Cow::Owned(BrowserAction::SetNewReleases(vec![
+            album("a", "someone"),
+            album("b", "followed"),
+            album("c", "someone"),
+        ])));
+        assert_eq!(events, vec![BrowserEvent::NewReleasesUpdated]);
+        let ids: Vec<String> = home_state.new_releases.iter().map(|a| a.uri()).collect();
+        assert_eq!(ids, vec!["b", "a", "c"]);
+
+        // Following someone sorts what's loaded again
+        let events = home_state.update_with(Cow::Owned(BrowserAction::AddFollowedArtist(
+            ArtistSummary {
+                id: "someone".to_owned(),
+                name: "Bar".to_owned(),
+                photo: None,
+            },
+        )));
+        assert_eq!(
+            events,
+            vec![
+                BrowserEvent::FollowedArtistsUpdated,
+                BrowserEvent::NewReleasesUpdated
+            ]
+        );
+        let ids: Vec<String> = home_state.new_releases.iter().map(|a| a.uri()).collect();
+        assert_eq!(ids, vec!["a", "b", "c"]);
+
+        // ...and unfollowing too
+        home_state.update_with(Cow::Owned(BrowserAction::SetArtistFollowed(
+            "followed".to_owned(),
+            false,
+        )));
+        let ids: Vec<String> = home_state.new_releases.iter().map(|a| a.uri()).collect();
+        assert_eq!(ids, vec!["a", "c", "b"]);
+    }
 }
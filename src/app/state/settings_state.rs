@@ -3,9 +3,44 @@ use crate::{
     settings::RiffSettings,
 };
 
+// Where played tracks can be sent
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScrobblerService {
+    LastFm,
+    ListenBrainz,
+}
+
+impl ScrobblerService {
+    pub fn id(self) -> &'static str {
+        match self {
+            Self::LastFm => "lastfm",
+            Self::ListenBrainz => "listenbrainz",
+        }
+    }
+}
+
+// What the preferences show for each of them
+#[derive(Clone, Debug, Default)]
+pub struct ScrobblerStatus {
+    pub user: Option<String>,
+    // When tracks were last sent, in seconds since the epoch
+    pub last_submission: Option<u64>,
+}
+
 #[derive(Clone, Debug)]
 pub enum SettingsAction {
     ChangeSettings,
+    ClearAudioCache,
+    // How many bytes were freed
+    AudioCacheCleared(u64),
+    ApiCacheCleared(u64),
+    LastFmLogin,
+    LastFmLogout,
+    // An empty token means we stop using ListenBrainz
+    SetListenBrainzToken(String),
+    // Who we're scrobbling for, if anyone
+    SetScrobblerUser(ScrobblerService, Option<String>),
+    ScrobblesSubmitted(ScrobblerService, u64),
 }
 
 impl From<SettingsAction> for AppAction {
@@ -17,6 +52,13 @@ impl From<SettingsAction> for AppAction {
 #[derive(Clone, Debug)]
 pub enum SettingsEvent {
     PlayerSettingsChanged,
+    AudioCacheClearRequested,
+    AudioCacheCleared(u64),
+    ApiCacheCleared(u64),
+    LastFmLoginRequested,
+    LastFmLogoutRequested,
+    ListenBrainzTokenChanged(String),
+    ScrobblerStatusChanged(ScrobblerService),
 }
 
 impl From<SettingsEvent> for AppEvent {
@@ -29,6 +71,24 @@ impl From<SettingsEvent> for AppEvent {
 pub struct SettingsState {
     // Probably shouldn't be stored, the source of truth is GSettings anyway
     pub settings: RiffSettings,
+    pub lastfm: ScrobblerStatus,
+    pub listenbrainz: ScrobblerStatus,
+}
+
+impl SettingsState {
+    pub fn scrobbler_status(&self, service: ScrobblerService) -> &ScrobblerStatus {
+        match service {
+            ScrobblerService::LastFm => &self.lastfm,
+            ScrobblerService::ListenBrainz => &self.listenbrainz,
+        }
+    }
+
+    fn scrobbler_status_mut(&mut self, service: ScrobblerService) -> &mut ScrobblerStatus {
+        match service {
+            ScrobblerService::LastFm => &mut self.lastfm,
+            ScrobblerService::ListenBrainz => &mut self.listenbrainz,
+        }
+    }
 }
 
 impl UpdatableState for SettingsState {
@@ -49,6 +109,32 @@ impl UpdatableState for SettingsState {
                     vec![]
                 }
             }
+            SettingsAction::ClearAudioCache => {
+                vec![SettingsEvent::AudioCacheClearRequested.into()]
+            }
+            SettingsAction::AudioCacheCleared(freed) => {
+                vec![SettingsEvent::AudioCacheCleared(freed).into()]
+            }
+            SettingsAction::ApiCacheCleared(freed) => {
+                vec![SettingsEvent::ApiCacheCleared(freed).into()]
+            }
+            SettingsAction::LastFmLogin => vec![SettingsEvent::LastFmLoginRequested.into()],
+            SettingsAction::LastFmLogout => vec![SettingsEvent::LastFmLogoutRequested.into()],
+            SettingsAction::SetListenBrainzToken(token) => {
+                vec![SettingsEvent::ListenBrainzTokenChanged(token).into()]
+            }
+            SettingsAction::SetScrobblerUser(service, user) => {
+                // Someone else's submissions, if anything
+                *self.scrobbler_status_mut(service) = ScrobblerStatus {
+                    user,
+                    last_submission: None,
+                };
+                vec![SettingsEvent::ScrobblerStatusChanged(service).into()]
+            }
+            SettingsAction::ScrobblesSubmitted(service, at) => {
+                self.scrobbler_status_mut(service).last_submission = Some(at);
+                vec![SettingsEvent::ScrobblerStatusChanged(service).into()]
+            }
         }
     }
 }
@@ -0,0 +1,38 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use zbus::blocking::Connection;
+use zvariant::Value;
+
+// Only ask once per run, the portal remembers the answer anyway
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+fn call_portal() -> zbus::Result<()> {
+    let connection = Connection::session()?;
+    let mut options: HashMap<&str, Value> = HashMap::new();
+    options.insert(
+        "reason",
+        Value::from("Keep playing music after the window is closed"),
+    );
+    options.insert("autostart", Value::from(false));
+    connection.call_method(
+        Some("org.freedesktop.portal.Desktop"),
+        "/org/freedesktop/portal/desktop",
+        Some("org.freedesktop.portal.Background"),
+        "RequestBackground",
+        &("", options),
+    )?;
+    Ok(())
+}
+
+// Lets the desktop (e.g. GNOME's "Background Apps") know that we're still running without a window
+pub fn request_background() {
+    if REQUESTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    thread::spawn(|| {
+        if let Err(e) = call_portal() {
+            warn!("Could not request to run in the background: {e}");
+        }
+    });
+}
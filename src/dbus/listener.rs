@@ -3,8 +3,8 @@ use std::rc::Rc;
 
 use crate::app::{
     components::EventListener,
-    models::{RepeatMode, SongDescription},
-    state::PlaybackEvent,
+    models::SongDescription,
+    state::{LoginEvent, PlaybackEvent},
     AppEvent, AppModel,
 };
 
@@ -20,6 +20,8 @@ pub enum MprisStateUpdate {
         has_next: bool,
     },
     SetPositionMs(u128),
+    // Jumped somewhere else in the track, unlike the above which is just keeping up
+    Seeked(u128),
     SetLoopStatus {
         has_prev: bool,
         loop_status: LoopStatus,
@@ -27,6 +29,13 @@ pub enum MprisStateUpdate {
     },
     SetShuffled(bool),
     SetPlaying(PlaybackStatus),
+    // The whole queue, and what's playing in it
+    SetTracks {
+        tracks: Vec<TrackMetadata>,
+        current: Option<String>,
+    },
+    // Not MPRIS, but the search provider shouldn't search anything while logged out
+    SetLoggedIn(bool),
 }
 
 pub struct AppPlaybackStateListener {
@@ -39,7 +48,7 @@ impl AppPlaybackStateListener {
         Self { app_model, sender }
     }
 
-    fn make_track_meta(&self) -> Option<TrackMetadata> {
+    fn track_meta(song: SongDescription) -> TrackMetadata {
         let SongDescription {
             id,
             title,
@@ -48,15 +57,26 @@ impl AppPlaybackStateListener {
             duration,
             art,
             ..
-        } = self.app_model.get_state().playback.current_song()?;
-        Some(TrackMetadata {
+        } = song;
+        TrackMetadata {
             id: format!("/dev/diegovsky/Riff/Track/{id}"),
             length: 1000 * duration as u64,
             title,
             album: album.name,
             artist: artists.into_iter().map(|a| a.name).collect(),
             art,
-        })
+        }
+    }
+
+    fn make_track_meta(&self) -> Option<TrackMetadata> {
+        let song = self.app_model.get_state().playback.current_song()?;
+        Some(Self::track_meta(song))
+    }
+
+    // In the same order as the queue in the app
+    fn make_track_list(&self) -> Vec<TrackMetadata> {
+        let songs = self.app_model.get_state().playback.songs().collect();
+        songs.into_iter().map(Self::track_meta).collect()
     }
 
     fn has_prev_next(&self) -> (bool, bool) {
@@ -68,12 +88,7 @@ impl AppPlaybackStateListener {
     }
 
     fn loop_status(&self) -> LoopStatus {
-        let state = self.app_model.get_state();
-        match state.playback.repeat_mode() {
-            RepeatMode::None => LoopStatus::None,
-            RepeatMode::Song => LoopStatus::Track,
-            RepeatMode::Playlist => LoopStatus::Playlist,
-        }
+        self.app_model.get_state().playback.repeat_mode().into()
     }
 
     fn update_for(&self, event: &PlaybackEvent) -> Option<MprisStateUpdate> {
@@ -105,10 +120,18 @@ impl AppPlaybackStateListener {
                     loop_status,
                 })
             }
+            PlaybackEvent::PlaylistChanged => Some(MprisStateUpdate::SetTracks {
+                tracks: self.make_track_list(),
+                current: self.make_track_meta().map(|track| track.id),
+            }),
             PlaybackEvent::ShuffleChanged(shuffled) => {
                 Some(MprisStateUpdate::SetShuffled(*shuffled))
             }
-            PlaybackEvent::TrackSeeked(pos) | PlaybackEvent::SeekSynced(pos) => {
+            PlaybackEvent::TrackSeeked(pos) => {
+                let pos = 1000 * (*pos as u128);
+                Some(MprisStateUpdate::Seeked(pos))
+            }
+            PlaybackEvent::SeekSynced(pos) => {
                 let pos = 1000 * (*pos as u128);
                 Some(MprisStateUpdate::SetPositionMs(pos))
             }
@@ -120,11 +143,19 @@ impl AppPlaybackStateListener {
 
 impl EventListener for AppPlaybackStateListener {
     fn on_event(&mut self, event: &AppEvent) {
-        if let AppEvent::PlaybackEvent(event) = event {
-            if let Some(update) = self.update_for(event) {
-                if let Err(e) = self.sender.unbounded_send(update) {
-                    log::error!("Could not send event to DBUS server");
-                }
+        let update = match event {
+            AppEvent::PlaybackEvent(event) => self.update_for(event),
+            AppEvent::LoginEvent(LoginEvent::LoginCompleted) => {
+                Some(MprisStateUpdate::SetLoggedIn(true))
+            }
+            AppEvent::LoginEvent(LoginEvent::LogoutCompleted | LoginEvent::AddAccountStarted) => {
+                Some(MprisStateUpdate::SetLoggedIn(false))
+            }
+            _ => None,
+        };
+        if let Some(update) = update {
+            if let Err(e) = self.sender.unbounded_send(update) {
+                log::error!("Could not send event to DBUS server");
             }
         }
     }
@@ -2,8 +2,10 @@ use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
 use futures::StreamExt;
 use std::rc::Rc;
 use std::thread;
+use url::Url;
 use zbus::Connection;
 
+use crate::app::loader::ImageLoader;
 use crate::app::{AppAction, AppModel};
 
 mod mpris;
@@ -11,13 +13,48 @@ pub use mpris::*;
 
 mod types;
 
+mod search_provider;
+use search_provider::RiffSearchProvider;
+
+mod background;
+pub use background::request_background;
+
 mod listener;
 use listener::*;
 
+// Must match the search provider file installed for GNOME Shell
+const SEARCH_PROVIDER_PATH: &str = "/dev/diegovsky/Riff/SearchProvider";
+
+// Lock screens and the like won't always fetch remote images, so the art is pointed to once it's on disk
+// (clients get the remote URL until then). It's looked up again on every track change, in case the cache got cleared in the meantime.
+async fn resolve_art(connection: Connection, track_id: String, url: String) -> Option<()> {
+    let path = ImageLoader::new().load_remote_file(&url, "jpg").await?;
+    let art = Url::from_file_path(path).ok()?;
+    let player_ref = connection
+        .object_server()
+        .interface::<_, RiffMprisPlayer>("/org/mpris/MediaPlayer2")
+        .await
+        .ok()?;
+    let mut player = player_ref.get_mut().await;
+    // The track might have changed while the art was downloading
+    if player
+        .state_mut()
+        .set_current_art(&track_id, art.to_string())
+    {
+        player
+            .notify_current_track_changed(player_ref.signal_context())
+            .await
+            .ok()?;
+    }
+    Some(())
+}
+
 #[tokio::main]
 async fn dbus_server(
     mpris: RiffMpris,
     player: RiffMprisPlayer,
+    track_list: RiffMprisTrackList,
+    search_provider: RiffSearchProvider,
     receiver: UnboundedReceiver<MprisStateUpdate>,
 ) -> zbus::Result<()> {
     let connection = Connection::session().await?;
@@ -29,12 +66,56 @@ async fn dbus_server(
         .object_server()
         .at("/org/mpris/MediaPlayer2", player)
         .await?;
+    connection
+        .object_server()
+        .at("/org/mpris/MediaPlayer2", track_list)
+        .await?;
     connection
         .request_name("org.mpris.MediaPlayer2.dev.diegovsky.Riff")
         .await?;
+    // MPRIS is still worth having without the search provider
+    connection
+        .object_server()
+        .at(SEARCH_PROVIDER_PATH, search_provider)
+        .await?;
+    if let Err(e) = connection
+        .request_name("dev.diegovsky.Riff.SearchProvider")
+        .await
+    {
+        warn!("Could not register the search provider: {e}");
+    }
 
     receiver
         .for_each(|update| async {
+            // The queue has its own interface
+            let update = match update {
+                MprisStateUpdate::SetTracks { tracks, current } => {
+                    if let Ok(track_list_ref) = connection
+                        .object_server()
+                        .interface::<_, RiffMprisTrackList>("/org/mpris/MediaPlayer2")
+                        .await
+                    {
+                        let mut track_list = track_list_ref.get_mut().await;
+                        let ctxt = track_list_ref.signal_context();
+                        track_list
+                            .set_tracks(tracks, current, ctxt)
+                            .await
+                            .expect("Signal emission failed");
+                    }
+                    return;
+                }
+                MprisStateUpdate::SetLoggedIn(logged_in) => {
+                    if let Ok(search_provider_ref) = connection
+                        .object_server()
+                        .interface::<_, RiffSearchProvider>(SEARCH_PROVIDER_PATH)
+                        .await
+                    {
+                        search_provider_ref.get_mut().await.set_logged_in(logged_in);
+                    }
+                    return;
+                }
+                update => update,
+            };
             if let Ok(player_ref) = connection
                 .object_server()
                 .interface::<_, RiffMprisPlayer>("/org/mpris/MediaPlayer2")
@@ -52,6 +133,13 @@ async fn dbus_server(
                         has_next,
                         current,
                     } => {
+                        // The remote URL is still better than nothing until (or if) the file is there
+                        let art_url = current
+                            .as_ref()
+                            .and_then(|track| Some((track.id.clone(), track.art.clone()?)));
+                        if let Some((track_id, url)) = art_url {
+                            tokio::spawn(resolve_art(connection.clone(), track_id, url));
+                        }
                         player.state_mut().set_has_prev(has_prev);
                         player.state_mut().set_has_next(has_next);
                         player.state_mut().set_current_track(current);
@@ -61,6 +149,10 @@ async fn dbus_server(
                         player.state_mut().set_position(position);
                         Ok(())
                     }
+                    MprisStateUpdate::Seeked(position) => {
+                        player.state_mut().set_position(position);
+                        RiffMprisPlayer::seeked(ctxt, position as i64).await
+                    }
                     MprisStateUpdate::SetLoopStatus {
                         has_prev,
                         has_next,
@@ -79,6 +171,7 @@ async fn dbus_server(
                         player.state_mut().set_playing(status);
                         player.playback_status_changed(ctxt).await
                     }
+                    MprisStateUpdate::SetTracks { .. } | MprisStateUpdate::SetLoggedIn(_) => Ok(()),
                 };
                 res.expect("Signal emission failed");
             }
@@ -93,11 +186,13 @@ pub fn start_dbus_server(
     sender: UnboundedSender<AppAction>,
 ) -> AppPlaybackStateListener {
     let mpris = RiffMpris::new(sender.clone());
-    let player = RiffMprisPlayer::new(sender);
+    let player = RiffMprisPlayer::new(sender.clone());
+    let track_list = RiffMprisTrackList::new(sender.clone());
+    let search_provider = RiffSearchProvider::new(app_model.get_spotify(), sender);
 
     let (sender, receiver) = unbounded();
 
-    thread::spawn(move || dbus_server(mpris, player, receiver));
+    thread::spawn(move || dbus_server(mpris, player, track_list, search_provider, receiver));
 
     AppPlaybackStateListener::new(app_model, sender)
 }
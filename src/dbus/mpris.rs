@@ -2,12 +2,12 @@
 #![allow(unused_variables)]
 
 use std::collections::HashMap;
-use std::convert::TryInto;
+use std::convert::{TryFrom, TryInto};
 
 use futures::channel::mpsc::UnboundedSender;
 use zbus::fdo::{Error, Result};
 use zbus::{interface, Interface, SignalContext};
-use zvariant::{ObjectPath, Value};
+use zvariant::{ObjectPath, OwnedObjectPath, OwnedValue, Value};
 
 use super::types::*;
 use crate::app::models::RepeatMode;
@@ -28,7 +28,9 @@ impl RiffMpris {
 #[interface(interface = "org.mpris.MediaPlayer2")]
 impl RiffMpris {
     fn quit(&self) -> Result<()> {
-        Err(Error::NotSupported("Not implemented".to_string()))
+        self.sender
+            .unbounded_send(AppAction::Quit)
+            .map_err(|_| Error::Failed("Could not send action".to_string()))
     }
 
     fn raise(&self) -> Result<()> {
@@ -39,7 +41,7 @@ impl RiffMpris {
 
     #[zbus(property)]
     fn can_quit(&self) -> bool {
-        false
+        true
     }
 
     #[zbus(property)]
@@ -49,7 +51,7 @@ impl RiffMpris {
 
     #[zbus(property)]
     fn has_track_list(&self) -> bool {
-        false
+        true
     }
 
     #[zbus(property)]
@@ -64,9 +66,10 @@ impl RiffMpris {
 
     #[zbus(property)]
     fn supported_uri_schemes(&self) -> Vec<String> {
-        vec![]
+        vec!["spotify".to_string()]
     }
 
+    // The installed desktop file keeps the same name for development builds
     #[zbus(property)]
     fn desktop_entry(&self) -> &'static str {
         "dev.diegovsky.Riff"
@@ -125,6 +128,17 @@ impl RiffMprisPlayer {
         )
         .await
     }
+
+    // As per spec, nothing should change when CanControl is false
+    fn ensure_can_control(&self) -> Result<()> {
+        if self.can_control() {
+            Ok(())
+        } else {
+            Err(Error::AccessDenied(
+                "Playback can't be controlled".to_string(),
+            ))
+        }
+    }
 }
 
 #[interface(interface = "org.mpris.MediaPlayer2.Player")]
@@ -136,7 +150,15 @@ impl RiffMprisPlayer {
     }
 
     pub fn open_uri(&self, Uri: &str) -> Result<()> {
-        Err(Error::NotSupported("Not implemented".to_string()))
+        let action = AppAction::PlayURI(Uri)
+            .ok_or_else(|| Error::InvalidArgs(format!("Unsupported URI: {Uri}")))?;
+        self.sender
+            .unbounded_send(action)
+            .map_err(|_| Error::Failed("Could not send action".to_string()))?;
+        // Whatever asked us to open something (a shell extension, a launcher...) expects to see it happen
+        self.sender
+            .unbounded_send(AppAction::Raise)
+            .map_err(|_| Error::Failed("Could not send action".to_string()))
     }
 
     pub fn pause(&self) -> Result<()> {
@@ -196,7 +218,8 @@ impl RiffMprisPlayer {
             return Ok(());
         }
 
-        if TrackId.to_string() != self.metadata().id {
+        // As per spec, this is ignored if the track isn't the current one anymore
+        if TrackId.as_str() != self.metadata().id {
             return Ok(());
         }
 
@@ -206,7 +229,8 @@ impl RiffMprisPlayer {
             .try_into()
             .map_err(|_| Error::Failed("Could not cast length (too large)".to_string()))?;
 
-        if Position > length {
+        // Same if it's out of the track
+        if !(0..=length).contains(&Position) {
             return Ok(());
         }
 
@@ -293,11 +317,8 @@ impl RiffMprisPlayer {
 
     #[zbus(property)]
     pub fn set_loop_status(&self, value: LoopStatus) -> zbus::Result<()> {
-        let mode = match value {
-            LoopStatus::None => RepeatMode::None,
-            LoopStatus::Track => RepeatMode::Song,
-            LoopStatus::Playlist => RepeatMode::Playlist,
-        };
+        self.ensure_can_control()?;
+        let mode = RepeatMode::from(value);
         self.sender
             .unbounded_send(PlaybackAction::SetRepeatMode(mode).into())
             .map_err(|_| Error::Failed("Could not send action".to_string()))?;
@@ -324,8 +345,9 @@ impl RiffMprisPlayer {
 
     #[zbus(property)]
     pub fn set_shuffle(&self, value: bool) -> zbus::Result<()> {
+        self.ensure_can_control()?;
         self.sender
-            .unbounded_send(PlaybackAction::ToggleShuffle.into())
+            .unbounded_send(PlaybackAction::SetShuffled(value).into())
             .map_err(|_| Error::Failed("Could not send action".to_string()))?;
         Ok(())
     }
@@ -346,3 +368,331 @@ impl RiffMprisPlayer {
         Ok(())
     }
 }
+
+const TRACK_PATH_PREFIX: &str = "/dev/diegovsky/Riff/Track/";
+
+// What TrackList uses when there's nothing. Generic over the lifetime, so that it can stand in
+// for a path borrowed from somewhere else (e.g. in unwrap_or_else)
+fn no_track<'a>() -> ObjectPath<'a> {
+    ObjectPath::from_static_str_unchecked("/org/mpris/MediaPlayer2/TrackList/NoTrack")
+}
+
+fn track_path(id: &str) -> ObjectPath<'_> {
+    ObjectPath::try_from(id).unwrap_or_else(|_| no_track())
+}
+
+// The play queue, the same as in the app
+pub struct RiffMprisTrackList {
+    tracks: Vec<TrackMetadata>,
+    sender: UnboundedSender<AppAction>,
+}
+
+impl RiffMprisTrackList {
+    pub fn new(sender: UnboundedSender<AppAction>) -> Self {
+        Self {
+            tracks: vec![],
+            sender,
+        }
+    }
+
+    fn find(&self, id: &str) -> Option<&TrackMetadata> {
+        self.tracks.iter().find(|track| track.id == id)
+    }
+
+    pub async fn set_tracks(
+        &mut self,
+        tracks: Vec<TrackMetadata>,
+        current: Option<String>,
+        ctxt: &SignalContext<'_>,
+    ) -> zbus::Result<()> {
+        let old_ids = self.tracks.iter().map(|t| t.id.clone()).collect::<Vec<_>>();
+        let new_ids = tracks.iter().map(|t| t.id.clone()).collect::<Vec<_>>();
+        self.tracks = tracks;
+
+        for change in track_list_changes(&old_ids, &new_ids) {
+            match change {
+                TrackListChange::Added(id, after) => {
+                    let Some(metadata) = self.find(&id).map(TrackMetadata::to_map) else {
+                        continue;
+                    };
+                    let after = after.as_deref().map(track_path).unwrap_or_else(no_track);
+                    Self::track_added(ctxt, metadata, after).await?;
+                }
+                TrackListChange::Removed(id) => {
+                    Self::track_removed(ctxt, track_path(&id)).await?;
+                }
+                TrackListChange::Replaced => {
+                    let current = current.as_deref().map(track_path).unwrap_or_else(no_track);
+                    Self::track_list_replaced(ctxt, self.tracks(), current).await?;
+                }
+            }
+        }
+        // As per spec, the new value isn't sent along: clients are expected to rely on the signals above
+        if old_ids != new_ids {
+            zbus::fdo::Properties::properties_changed(
+                ctxt,
+                Self::name(),
+                &HashMap::new(),
+                &["Tracks"],
+            )
+            .await?;
+        }
+        Ok(())
+    }
+}
+
+#[interface(interface = "org.mpris.MediaPlayer2.TrackList")]
+impl RiffMprisTrackList {
+    pub fn get_tracks_metadata(
+        &self,
+        TrackIds: Vec<ObjectPath<'_>>,
+    ) -> Vec<HashMap<String, OwnedValue>> {
+        TrackIds
+            .iter()
+            .filter_map(|id| self.find(id.as_str()))
+            .map(TrackMetadata::to_map)
+            .collect()
+    }
+
+    pub fn add_track(
+        &self,
+        Uri: &str,
+        AfterTrack: ObjectPath<'_>,
+        SetAsCurrent: bool,
+    ) -> Result<()> {
+        Err(Error::NotSupported("The queue can't be edited".to_string()))
+    }
+
+    pub fn remove_track(&self, TrackId: ObjectPath<'_>) -> Result<()> {
+        Err(Error::NotSupported("The queue can't be edited".to_string()))
+    }
+
+    pub fn go_to(&self, TrackId: ObjectPath<'_>) -> Result<()> {
+        // As per spec, nothing happens if the track isn't in the list
+        let Some(id) = self
+            .find(TrackId.as_str())
+            .and_then(|track| track.id.strip_prefix(TRACK_PATH_PREFIX))
+        else {
+            return Ok(());
+        };
+        self.sender
+            .unbounded_send(PlaybackAction::Load(id.to_string()).into())
+            .map_err(|_| Error::Failed("Could not send action".to_string()))
+    }
+
+    #[zbus(signal)]
+    pub async fn track_list_replaced(
+        ctxt: &SignalContext<'_>,
+        Tracks: Vec<OwnedObjectPath>,
+        CurrentTrack: ObjectPath<'_>,
+    ) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    pub async fn track_added(
+        ctxt: &SignalContext<'_>,
+        Metadata: HashMap<String, OwnedValue>,
+        AfterTrack: ObjectPath<'_>,
+    ) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    pub async fn track_removed(
+        ctxt: &SignalContext<'_>,
+        TrackId: ObjectPath<'_>,
+    ) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    pub fn tracks(&self) -> Vec<OwnedObjectPath> {
+        self.tracks
+            .iter()
+            .filter_map(|track| OwnedObjectPath::try_from(track.id.as_str()).ok())
+            .collect()
+    }
+
+    #[zbus(property)]
+    pub fn can_edit_tracks(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::state::PlaybackRequest;
+    use futures::channel::mpsc::{unbounded, UnboundedReceiver};
+    use futures::StreamExt;
+    use std::os::unix::net::UnixStream;
+    use zbus::{ConnectionBuilder, Guid, MessageStream};
+
+    // Calls OpenUri over a private (peer to peer) bus, and returns what got dispatched
+    async fn open_uri(uri: &str) -> (zbus::Result<()>, Vec<AppAction>) {
+        let (sender, mut receiver) = unbounded();
+        let (server_socket, client_socket) = UnixStream::pair().unwrap();
+
+        let server = ConnectionBuilder::unix_stream(server_socket)
+            .server(Guid::generate())
+            .unwrap()
+            .p2p()
+            .serve_at("/org/mpris/MediaPlayer2", RiffMprisPlayer::new(sender))
+            .unwrap()
+            .build();
+        let client = ConnectionBuilder::unix_stream(client_socket).p2p().build();
+        let (_server, client) = futures::try_join!(server, client).unwrap();
+
+        let result = client
+            .call_method(
+                None::<&str>,
+                "/org/mpris/MediaPlayer2",
+                Some("org.mpris.MediaPlayer2.Player"),
+                "OpenUri",
+                &(uri,),
+            )
+            .await
+            .map(|_| ());
+
+        let mut actions = vec![];
+        while let Ok(Some(action)) = receiver.try_next() {
+            actions.push(action);
+        }
+        (result, actions)
+    }
+
+    fn track(id: &str) -> TrackMetadata {
+        TrackMetadata {
+            id: format!("{TRACK_PATH_PREFIX}{id}"),
+            length: 60_000_000,
+            artist: vec![],
+            album: String::new(),
+            title: String::new(),
+            art: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_queue_changes_invalidate_tracks() {
+        let (sender, _receiver) = unbounded();
+        let (server_socket, client_socket) = UnixStream::pair().unwrap();
+
+        let server = ConnectionBuilder::unix_stream(server_socket)
+            .server(Guid::generate())
+            .unwrap()
+            .p2p()
+            .serve_at("/org/mpris/MediaPlayer2", RiffMprisTrackList::new(sender))
+            .unwrap()
+            .build();
+        let client = ConnectionBuilder::unix_stream(client_socket).p2p().build();
+        let (server, client) = futures::try_join!(server, client).unwrap();
+        let mut messages = MessageStream::from(&client);
+
+        let track_list_ref = server
+            .object_server()
+            .interface::<_, RiffMprisTrackList>("/org/mpris/MediaPlayer2")
+            .await
+            .unwrap();
+        track_list_ref
+            .get_mut()
+            .await
+            .set_tracks(vec![track("a")], None, track_list_ref.signal_context())
+            .await
+            .unwrap();
+
+        // Comes after TrackAdded
+        let (interface, invalidated) = loop {
+            let message = messages.next().await.unwrap().unwrap();
+            if message.header().member().map(|m| m.as_str()) != Some("PropertiesChanged") {
+                continue;
+            }
+            let (interface, _, invalidated): (String, HashMap<String, OwnedValue>, Vec<String>) =
+                message.body().deserialize().unwrap();
+            break (interface, invalidated);
+        };
+        assert_eq!(interface, "org.mpris.MediaPlayer2.TrackList");
+        assert_eq!(invalidated, vec!["Tracks"]);
+    }
+
+    fn seek_requested(receiver: &mut UnboundedReceiver<AppAction>) -> Option<u32> {
+        match receiver.try_next() {
+            Ok(Some(AppAction::PlaybackAction(PlaybackAction::Seek(pos)))) => Some(pos),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_set_position_checks_track() {
+        let (sender, mut receiver) = unbounded();
+        let mut player = RiffMprisPlayer::new(sender);
+        player.state_mut().set_current_track(Some(TrackMetadata {
+            id: "/dev/diegovsky/Riff/Track/current".to_string(),
+            length: 60_000_000,
+            artist: vec![],
+            album: String::new(),
+            title: String::new(),
+            art: None,
+        }));
+
+        let other = ObjectPath::try_from("/dev/diegovsky/Riff/Track/other").unwrap();
+        player.set_position(other, 30_000_000).unwrap();
+        assert_eq!(seek_requested(&mut receiver), None);
+
+        let current = ObjectPath::try_from("/dev/diegovsky/Riff/Track/current").unwrap();
+        player.set_position(current.clone(), -1).unwrap();
+        player.set_position(current.clone(), 90_000_000).unwrap();
+        assert_eq!(seek_requested(&mut receiver), None);
+
+        player.set_position(current, 30_000_000).unwrap();
+        assert_eq!(seek_requested(&mut receiver), Some(30_000));
+    }
+
+    fn requested(actions: &[AppAction]) -> Option<&PlaybackRequest> {
+        match actions {
+            [AppAction::RequestPlayback(request), AppAction::Raise] => Some(request),
+            _ => None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_open_uri() {
+        let (result, actions) = open_uri("spotify:track:4uLU6hMCjMI75M1A2tKUQC").await;
+        assert!(result.is_ok());
+        assert_eq!(
+            requested(&actions),
+            Some(&PlaybackRequest::Track(
+                "4uLU6hMCjMI75M1A2tKUQC".to_string()
+            ))
+        );
+
+        let (result, actions) = open_uri("spotify:album:1DFixLWuPkv3KT3TnV35m3").await;
+        assert!(result.is_ok());
+        assert_eq!(
+            requested(&actions),
+            Some(&PlaybackRequest::Album(
+                "1DFixLWuPkv3KT3TnV35m3".to_string()
+            ))
+        );
+
+        let (result, actions) = open_uri("spotify:playlist:37i9dQZF1DXcBWIGoYBM5M").await;
+        assert!(result.is_ok());
+        assert_eq!(
+            requested(&actions),
+            Some(&PlaybackRequest::Playlist(
+                "37i9dQZF1DXcBWIGoYBM5M".to_string()
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_open_invalid_uri() {
+        for uri in [
+            "spotify:artist:0OdUWJ0sBjDrqHygGUXeCF",
+            "https://example.com",
+            "",
+        ] {
+            let (result, actions) = open_uri(uri).await;
+            assert!(matches!(
+                result,
+                Err(zbus::Error::MethodError(name, _, _))
+                    if name.as_str() == "org.freedesktop.DBus.Error.InvalidArgs"
+            ));
+            assert!(actions.is_empty());
+        }
+    }
+}
@@ -0,0 +1,187 @@
+#![allow(unused_variables)]
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures::channel::mpsc::UnboundedSender;
+use gettextrs::gettext;
+use zbus::fdo::{Error, Result};
+use zbus::interface;
+use zvariant::{OwnedValue, Value};
+
+use crate::api::SpotifyApiClient;
+use crate::app::loader::ImageLoader;
+use crate::app::models::SearchCategory;
+use crate::app::{AppAction, BrowserAction};
+
+// The shell searches again on every keystroke, so we wait a bit in case there's more coming
+const SEARCH_DEBOUNCE: Duration = Duration::from_millis(150);
+// A few of each is plenty for the overview
+const RESULTS_PER_CATEGORY: usize = 3;
+// Not much to find with a single letter
+const MIN_QUERY_LEN: usize = 2;
+// Results of older searches are forgotten past that
+const MAX_METAS: usize = 100;
+
+#[derive(Clone, Debug)]
+struct ResultMeta {
+    name: String,
+    description: String,
+    art: Option<String>,
+}
+
+// Artists and albums in the GNOME Shell overview, identified by their Spotify URI
+pub struct RiffSearchProvider {
+    api: Arc<dyn SpotifyApiClient + Send + Sync>,
+    sender: UnboundedSender<AppAction>,
+    logged_in: bool,
+    // Bumped on every search, so that a search can tell a newer one started in the meantime
+    generation: AtomicU64,
+    // What we found so far, for when the shell asks what to show for each result
+    metas: Mutex<HashMap<String, ResultMeta>>,
+}
+
+impl RiffSearchProvider {
+    pub fn new(
+        api: Arc<dyn SpotifyApiClient + Send + Sync>,
+        sender: UnboundedSender<AppAction>,
+    ) -> Self {
+        Self {
+            api,
+            sender,
+            logged_in: false,
+            generation: AtomicU64::new(0),
+            metas: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn set_logged_in(&mut self, logged_in: bool) {
+        self.logged_in = logged_in;
+        if !logged_in {
+            self.metas.get_mut().unwrap().clear();
+        }
+    }
+
+    fn send(&self, action: AppAction) -> Result<()> {
+        self.sender
+            .unbounded_send(action)
+            .map_err(|_| Error::Failed("Could not send action".to_string()))
+    }
+
+    async fn search(&self, terms: Vec<String>) -> Vec<String> {
+        let query = terms.join(" ");
+        if !self.logged_in || query.chars().count() < MIN_QUERY_LEN {
+            return vec![];
+        }
+
+        let generation = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        tokio::time::sleep(SEARCH_DEBOUNCE).await;
+        if self.generation.load(Ordering::SeqCst) != generation {
+            return vec![];
+        }
+
+        let (artists, albums) = futures::join!(
+            self.api
+                .search(&query, SearchCategory::Artists, 0, RESULTS_PER_CATEGORY),
+            self.api
+                .search(&query, SearchCategory::Albums, 0, RESULTS_PER_CATEGORY),
+        );
+
+        let mut results = vec![];
+        let mut metas = self.metas.lock().unwrap();
+        if metas.len() + 2 * RESULTS_PER_CATEGORY > MAX_METAS {
+            metas.clear();
+        }
+        for artist in artists.map(|r| r.artists).unwrap_or_default() {
+            let id = format!("spotify:artist:{}", artist.id);
+            let meta = ResultMeta {
+                name: artist.name,
+                // translators: Description of an artist in the GNOME Shell search results
+                description: gettext("Artist"),
+                art: artist.photo,
+            };
+            metas.insert(id.clone(), meta);
+            results.push(id);
+        }
+        for album in albums.map(|r| r.albums).unwrap_or_default() {
+            let id = format!("spotify:album:{}", album.id);
+            let meta = ResultMeta {
+                description: album.artists_name(),
+                name: album.title,
+                art: album.art,
+            };
+            metas.insert(id.clone(), meta);
+            results.push(id);
+        }
+        results
+    }
+}
+
+#[interface(interface = "org.gnome.Shell.SearchProvider2")]
+impl RiffSearchProvider {
+    async fn get_initial_result_set(&self, terms: Vec<String>) -> Vec<String> {
+        self.search(terms).await
+    }
+
+    // The API doesn't narrow down results nicely, so it's just another search (cached, most of the time)
+    async fn get_subsearch_result_set(
+        &self,
+        previous_results: Vec<String>,
+        terms: Vec<String>,
+    ) -> Vec<String> {
+        self.search(terms).await
+    }
+
+    fn get_result_metas(&self, identifiers: Vec<String>) -> Vec<HashMap<String, OwnedValue>> {
+        let found: Vec<(String, ResultMeta)> = {
+            let metas = self.metas.lock().unwrap();
+            identifiers
+                .into_iter()
+                .filter_map(|id| {
+                    let meta = metas.get(&id)?.clone();
+                    Some((id, meta))
+                })
+                .collect()
+        };
+
+        let loader = ImageLoader::new();
+        let mut results = vec![];
+        for (id, meta) in found {
+            let mut entries = vec![
+                ("id", Value::from(id)),
+                ("name", Value::from(meta.name)),
+                ("description", Value::from(meta.description)),
+            ];
+            // The shell wants an icon it can load right away, and times out if we take too long,
+            // so we only use artwork that was already downloaded
+            if let Some(path) = meta.art.and_then(|art| loader.cached_file(&art, "jpg")) {
+                let path = path.to_string_lossy().into_owned();
+                entries.push(("gicon", Value::from(path)));
+            }
+            let result = entries
+                .into_iter()
+                .filter_map(|(key, value)| Some((key.to_string(), value.try_to_owned().ok()?)))
+                .collect();
+            results.push(result);
+        }
+        results
+    }
+
+    fn activate_result(
+        &self,
+        identifier: String,
+        terms: Vec<String>,
+        timestamp: u32,
+    ) -> Result<()> {
+        self.send(AppAction::Raise)?;
+        self.send(AppAction::OpenLink(identifier))
+    }
+
+    fn launch_search(&self, terms: Vec<String>, timestamp: u32) -> Result<()> {
+        self.send(AppAction::Raise)?;
+        self.send(AppAction::ViewSearch())?;
+        self.send(BrowserAction::Search(terms.join(" ")).into())
+    }
+}
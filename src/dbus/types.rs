@@ -1,7 +1,10 @@
+use std::collections::HashMap;
 use std::convert::{Into, TryFrom};
 use std::time::Instant;
 use zvariant::Type;
-use zvariant::{Dict, Signature, Str, Value};
+use zvariant::{Dict, OwnedValue, Signature, Str, Value};
+
+use crate::app::models::RepeatMode;
 
 fn boxed_value<'a, V: Into<Value<'a>>>(v: V) -> Value<'a> {
     Value::new(v.into())
@@ -42,6 +45,26 @@ impl From<LoopStatus> for Value<'_> {
     }
 }
 
+impl From<RepeatMode> for LoopStatus {
+    fn from(mode: RepeatMode) -> Self {
+        match mode {
+            RepeatMode::None => LoopStatus::None,
+            RepeatMode::Song => LoopStatus::Track,
+            RepeatMode::Playlist => LoopStatus::Playlist,
+        }
+    }
+}
+
+impl From<LoopStatus> for RepeatMode {
+    fn from(status: LoopStatus) -> Self {
+        match status {
+            LoopStatus::None => RepeatMode::None,
+            LoopStatus::Track => RepeatMode::Song,
+            LoopStatus::Playlist => RepeatMode::Playlist,
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum PlaybackStatus {
     Playing,
@@ -120,28 +143,84 @@ impl Type for TrackMetadata {
     }
 }
 
+impl TrackMetadata {
+    fn entries(self) -> Vec<(&'static str, Value<'static>)> {
+        let mut entries = vec![
+            ("mpris:trackid", Value::from(self.id)),
+            ("mpris:length", Value::from(self.length)),
+            ("xesam:title", Value::from(self.title)),
+            ("xesam:artist", Value::from(self.artist.clone())),
+            ("xesam:albumArtist", Value::from(self.artist)),
+            ("xesam:album", Value::from(self.album)),
+        ];
+        if let Some(art) = self.art {
+            entries.push(("mpris:artUrl", Value::from(art)));
+        }
+        entries
+    }
+
+    // The same as a plain map, for method replies and signals
+    pub fn to_map(&self) -> HashMap<String, OwnedValue> {
+        self.clone()
+            .entries()
+            .into_iter()
+            .filter_map(|(key, value)| Some((key.to_string(), value.try_to_owned().ok()?)))
+            .collect()
+    }
+}
+
 impl From<TrackMetadata> for Value<'_> {
     fn from(meta: TrackMetadata) -> Self {
         let mut d = Dict::new(Str::signature(), Value::signature());
-        d.append("mpris:trackid".into(), boxed_value(meta.id))
-            .unwrap();
-        d.append("mpris:length".into(), boxed_value(meta.length))
-            .unwrap();
-        d.append("xesam:title".into(), boxed_value(meta.title))
-            .unwrap();
-        d.append("xesam:artist".into(), boxed_value(meta.artist.clone()))
-            .unwrap();
-        d.append("xesam:albumArtist".into(), boxed_value(meta.artist))
-            .unwrap();
-        d.append("xesam:album".into(), boxed_value(meta.album))
-            .unwrap();
-        if let Some(art) = meta.art {
-            d.append("mpris:artUrl".into(), boxed_value(art)).unwrap();
+        for (key, value) in meta.entries() {
+            d.append(key.into(), boxed_value(value)).unwrap();
         }
         Value::Dict(d)
     }
 }
 
+// How the tracks went from one list to the other, as told by the TrackList interface signals
+#[derive(Debug, PartialEq, Eq)]
+pub enum TrackListChange {
+    // The track added, and the one it comes right after (if any)
+    Added(String, Option<String>),
+    Removed(String),
+    Replaced,
+}
+
+// What's in `longer` but not in `shorter`, if `shorter` is all in there in the same order
+fn extra_tracks<'a>(shorter: &[String], longer: &'a [String]) -> Option<Vec<(usize, &'a String)>> {
+    let mut remaining = shorter.iter().peekable();
+    let extra = longer
+        .iter()
+        .enumerate()
+        .filter(|(_, id)| remaining.next_if_eq(id).is_none())
+        .collect();
+    remaining.peek().is_none().then_some(extra)
+}
+
+// Tracks are only added or removed most of the time, anything else and the whole list is replaced
+pub fn track_list_changes(old: &[String], new: &[String]) -> Vec<TrackListChange> {
+    if old == new {
+        vec![]
+    } else if let Some(added) = extra_tracks(old, new) {
+        added
+            .into_iter()
+            .map(|(i, id)| {
+                let after = i.checked_sub(1).map(|i| new[i].clone());
+                TrackListChange::Added(id.clone(), after)
+            })
+            .collect()
+    } else if let Some(removed) = extra_tracks(new, old) {
+        removed
+            .into_iter()
+            .map(|(_, id)| TrackListChange::Removed(id.clone()))
+            .collect()
+    } else {
+        vec![TrackListChange::Replaced]
+    }
+}
+
 pub struct MprisState {
     status: PlaybackStatus,
     loop_status: LoopStatus,
@@ -213,6 +292,17 @@ impl MprisState {
         self.position.set(0, playing);
     }
 
+    // Returns whether the track was still the current one
+    pub fn set_current_art(&mut self, id: &str, art: String) -> bool {
+        match self.metadata.as_mut() {
+            Some(track) if track.id == id => {
+                track.art = Some(art);
+                true
+            }
+            _ => false,
+        }
+    }
+
     pub fn position(&self) -> u128 {
         self.position.current()
     }
@@ -245,3 +335,66 @@ impl MprisState {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    #[test]
+    fn test_loop_status_values() {
+        for (status, name) in [
+            (LoopStatus::None, "None"),
+            (LoopStatus::Track, "Track"),
+            (LoopStatus::Playlist, "Playlist"),
+        ] {
+            assert_eq!(Value::from(status), Value::from(name));
+            assert_eq!(LoopStatus::from(Value::from(name)), status);
+        }
+        // Anything else turns repeating off
+        assert_eq!(LoopStatus::from(Value::from("Album")), LoopStatus::None);
+        assert_eq!(LoopStatus::from(Value::from(true)), LoopStatus::None);
+    }
+
+    #[test]
+    fn test_loop_status_repeat_mode() {
+        for mode in [RepeatMode::None, RepeatMode::Song, RepeatMode::Playlist] {
+            assert_eq!(RepeatMode::from(LoopStatus::from(mode)), mode);
+        }
+        assert_eq!(LoopStatus::from(RepeatMode::Song), LoopStatus::Track);
+    }
+
+    #[test]
+    fn test_tracks_added() {
+        let changes = track_list_changes(&ids(&["a", "b"]), &ids(&["c", "a", "b", "d"]));
+        assert_eq!(
+            changes,
+            vec![
+                TrackListChange::Added("c".to_string(), None),
+                TrackListChange::Added("d".to_string(), Some("b".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tracks_removed() {
+        let changes = track_list_changes(&ids(&["a", "b", "c"]), &ids(&["b"]));
+        assert_eq!(
+            changes,
+            vec![
+                TrackListChange::Removed("a".to_string()),
+                TrackListChange::Removed("c".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tracks_replaced() {
+        let changes = track_list_changes(&ids(&["a", "b"]), &ids(&["b", "a"]));
+        assert_eq!(changes, vec![TrackListChange::Replaced]);
+        assert!(track_list_changes(&ids(&["a"]), &ids(&["a"])).is_empty());
+    }
+}
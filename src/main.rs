@@ -14,6 +14,7 @@ use gio::prelude::*;
 use gio::ApplicationFlags;
 use gio::SimpleAction;
 use gtk::prelude::*;
+use std::time::Instant;
 
 mod api;
 mod app;
@@ -21,13 +22,20 @@ mod config;
 mod connect;
 mod dbus;
 mod player;
+mod scrobbler;
 mod settings;
 
 use crate::app::components::expose_custom_widgets;
 use crate::app::dispatch::{spawn_task_handler, DispatchLoop};
 use crate::app::{state::PlaybackAction, App, AppAction, BrowserAction};
 
+lazy_static! {
+    // To keep an eye on how long it takes for the app to be usable
+    pub static ref PROCESS_START: Instant = Instant::now();
+}
+
 fn main() {
+    lazy_static::initialize(&PROCESS_START);
     let settings = settings::RiffSettings::new_from_gsettings().unwrap_or_default();
     setup_gtk(&settings);
 
@@ -35,7 +43,10 @@ fn main() {
     // so here goes!
     expose_custom_widgets();
 
-    let gtk_app = gtk::Application::new(Some(config::APPID), ApplicationFlags::HANDLES_OPEN);
+    let gtk_app = gtk::Application::new(
+        Some(config::APPID),
+        ApplicationFlags::HANDLES_OPEN | ApplicationFlags::HANDLES_COMMAND_LINE,
+    );
     let builder = gtk::Builder::from_resource("/dev/diegovsky/Riff/window.ui");
     let window: libadwaita::ApplicationWindow = builder.object("window").unwrap();
 
@@ -63,6 +74,14 @@ fn main() {
     );
     context.spawn_local(app.attach(dispatch_loop));
 
+    // Only the primary instance gets those, `riff next` and the like run and exit without touching the record
+    gtk_app.connect_startup(|_| {
+        app::session_health::start_session(settings::session_health_enabled());
+    });
+    gtk_app.connect_shutdown(|_| {
+        app::session_health::end_session();
+    });
+
     let sender_clone = sender.clone();
     gtk_app.connect_activate(move |gtk_app| {
         debug!("activate");
@@ -76,15 +95,15 @@ fn main() {
         }
     });
 
-    gtk_app.connect_open(move |gtk_app, targets, _| {
-        gtk_app.activate();
+    let sender_clone = sender.clone();
+    gtk_app.connect_command_line(move |gtk_app, cmdline| {
+        handle_command_line(gtk_app, cmdline, &sender_clone).into()
+    });
 
+    gtk_app.connect_open(move |gtk_app, targets, _| {
         // There should only be one target because %u is used in desktop file
         let target = &targets[0];
-        let uri = target.uri().to_string();
-        let action = AppAction::OpenURI(uri)
-            .unwrap_or_else(|| AppAction::ShowNotification(gettext("Failed to open link!")));
-        sender.unbounded_send(action).unwrap();
+        open_uri(gtk_app, target.uri().to_string(), &sender);
     });
 
     context.invoke_local(move || {
@@ -94,6 +113,66 @@ fn main() {
     std::process::exit(0);
 }
 
+fn open_uri(gtk_app: &gtk::Application, uri: String, sender: &UnboundedSender<AppAction>) {
+    gtk_app.activate();
+    sender.unbounded_send(AppAction::OpenLink(uri)).unwrap();
+}
+
+// Runs in the primary instance, whether the arguments were given to it or to another `riff` started later.
+// Verbs like `riff next` are meant for keybindings, so they just poke the running instance.
+fn handle_command_line(
+    gtk_app: &gtk::Application,
+    cmdline: &gio::ApplicationCommandLine,
+    sender: &UnboundedSender<AppAction>,
+) -> glib::ExitCode {
+    let args: Vec<String> = cmdline
+        .arguments()
+        .into_iter()
+        .skip(1)
+        .map(|arg| arg.to_string_lossy().into_owned())
+        .collect();
+
+    let action_name = match args.first().map(String::as_str) {
+        None => {
+            gtk_app.activate();
+            return glib::ExitCode::SUCCESS;
+        }
+        Some("play-pause") => "toggle_playback",
+        Some("next") => "player_next",
+        Some("previous" | "prev") => "player_prev",
+        Some("open") => {
+            let Some(uri) = args.get(1) else {
+                cmdline.printerr_literal("Usage: riff open <spotify-uri>\n");
+                return glib::ExitCode::FAILURE;
+            };
+            open_uri(gtk_app, uri.clone(), sender);
+            return glib::ExitCode::SUCCESS;
+        }
+        // That's what we get from the desktop file
+        Some(uri)
+            if uri.starts_with("spotify:") || uri.starts_with("https://open.spotify.com/") =>
+        {
+            open_uri(gtk_app, uri.to_string(), sender);
+            return glib::ExitCode::SUCCESS;
+        }
+        Some(other) => {
+            cmdline.printerr_literal(&format!(
+                "Unknown command: {other}\nUsage: riff [play-pause | next | previous | open <spotify-uri>]\n"
+            ));
+            return glib::ExitCode::FAILURE;
+        }
+    };
+
+    // If there's no window yet, we're the only instance and we'd have to log in and all before playing anything
+    if gtk_app.active_window().is_none() {
+        cmdline.printerr_literal("Riff isn't running, start it first to control playback\n");
+        return glib::ExitCode::FAILURE;
+    }
+
+    gtk_app.activate_action(action_name, None);
+    glib::ExitCode::SUCCESS
+}
+
 fn setup_gtk(settings: &settings::RiffSettings) {
     // Setup logging
     env_logger::init();
@@ -148,8 +227,10 @@ fn register_actions(app: &gtk::Application, sender: UnboundedSender<AppAction>)
         #[weak]
         app,
         move |_, _| {
+            // Not close(), which would only hide the window when playing in the background,
+            // and we want the window torn down properly so that the playback state is saved
             if let Some(existing_window) = app.active_window() {
-                existing_window.close();
+                existing_window.destroy();
             }
             app.quit();
         }
@@ -174,6 +255,12 @@ fn register_actions(app: &gtk::Application, sender: UnboundedSender<AppAction>)
         sender.clone(),
     ));
 
+    app.add_action(&make_action(
+        "toggle_mini_player",
+        AppAction::ToggleMiniPlayer,
+        sender.clone(),
+    ));
+
     app.add_action(&make_action(
         "nav_pop",
         AppAction::BrowserAction(BrowserAction::NavigationPop),
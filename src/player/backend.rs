@@ -0,0 +1,531 @@
+use futures::future::BoxFuture;
+
+use librespot::core::authentication::Credentials;
+use librespot::core::cache::Cache;
+use librespot::core::config::SessionConfig;
+use librespot::core::error::ErrorKind;
+use librespot::core::session::Session;
+use librespot::core::SpotifyUri;
+
+use librespot::playback::audio_backend::{self, SinkBuilder};
+use librespot::playback::config::{AudioFormat, PlayerConfig, VolumeCtrl};
+use librespot::playback::mixer::alsamixer::AlsaMixer;
+use librespot::playback::mixer::softmixer::SoftMixer;
+use librespot::playback::mixer::{Mixer, MixerConfig};
+use librespot::playback::player::{Player, PlayerEventChannel};
+
+use super::{AudioBackend, AudioMixer, SpotifyError, SpotifyPlayerSettings, VolumeControl};
+use crate::api::parse_proxy;
+use crate::settings::RiffSettings;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use url::Url;
+
+// Everything the player needs from librespot, the sound card and the settings.
+// Tests swap it for a fake one, so that the player can be driven without any of them.
+pub trait PlayerBackend: Send + 'static {
+    type Session: PlayerSession;
+    type Player: PlaybackHandle;
+
+    fn connect(
+        &self,
+        access_token: String,
+        settings: &SpotifyPlayerSettings,
+    ) -> BoxFuture<'static, Result<Self::Session, SpotifyError>>;
+
+    // Fails if the audio output can't be used
+    fn create_player(
+        &mut self,
+        session: Self::Session,
+        config: PlayerConfig,
+        settings: &SpotifyPlayerSettings,
+    ) -> Result<Self::Player, SpotifyError>;
+
+    fn set_volume(&mut self, volume: f64);
+
+    // For fading in and out, from another task
+    fn volume_control(&self) -> Option<VolumeControl>;
+
+    // Called before creating a player. Errors mean it fell back to the soft mixer
+    fn set_mixer(&mut self, mixer: &AudioMixer, volume: f64) -> Result<(), SpotifyError>;
+
+    // Shuts the session down (if any), and forgets whatever was cached for the user
+    fn logout(&mut self, session: Option<Self::Session>) -> BoxFuture<'static, ()>;
+
+    // Resolves to how many bytes were freed
+    fn clear_audio_cache(&self) -> BoxFuture<'static, u64>;
+
+    // The settings as they are now, they might have changed since we started
+    fn load_settings(&self) -> SpotifyPlayerSettings;
+}
+
+pub trait PlayerSession: Clone + Send + Sync + 'static {
+    fn username(&self) -> String;
+    fn reconnect(&self, access_token: String) -> BoxFuture<'static, Result<(), SpotifyError>>;
+    fn shutdown(&self);
+    // Once the connection is lost, the session can't be used anymore
+    fn is_invalid(&self) -> bool;
+}
+
+pub trait PlaybackHandle: Clone + Send + 'static {
+    fn play(&self);
+    fn pause(&self);
+    fn stop(&self);
+    fn seek(&self, position_ms: u32);
+    fn load(&self, track: SpotifyUri, start_playing: bool, position_ms: u32);
+    fn preload(&self, track: SpotifyUri);
+    fn event_channel(&self) -> PlayerEventChannel;
+}
+
+#[derive(Default)]
+pub struct LibrespotBackend {
+    mixer: Option<Arc<dyn Mixer>>,
+    // What was asked for, which might not be what we ended up with
+    mixer_setting: Option<AudioMixer>,
+}
+
+impl PlayerBackend for LibrespotBackend {
+    type Session = Session;
+    type Player = Arc<Player>;
+
+    fn connect(
+        &self,
+        access_token: String,
+        settings: &SpotifyPlayerSettings,
+    ) -> BoxFuture<'static, Result<Session, SpotifyError>> {
+        let settings = settings.clone();
+        Box::pin(async move {
+            let credentials = Credentials::with_access_token(access_token);
+            create_session(&credentials, &settings).await
+        })
+    }
+
+    fn create_player(
+        &mut self,
+        session: Session,
+        config: PlayerConfig,
+        settings: &SpotifyPlayerSettings,
+    ) -> Result<Arc<Player>, SpotifyError> {
+        let (sink, device, format) = find_sink(&settings.backend, settings.audio_format)?;
+        let volume = settings.volume;
+        let soft_volume = self
+            .mixer
+            .get_or_insert_with(|| open_soft_mixer(volume))
+            .get_soft_volume();
+
+        Ok(Player::new(config, session, soft_volume, move || {
+            sink(device, format)
+        }))
+    }
+
+    fn set_volume(&mut self, volume: f64) {
+        if let Some(mixer) = self.mixer.as_ref() {
+            mixer_set_volume(&**mixer, volume);
+        }
+    }
+
+    fn volume_control(&self) -> Option<VolumeControl> {
+        let mixer = Arc::clone(self.mixer.as_ref()?);
+        Some(Arc::new(move |volume| mixer_set_volume(&*mixer, volume)))
+    }
+
+    fn set_mixer(&mut self, mixer: &AudioMixer, volume: f64) -> Result<(), SpotifyError> {
+        if self.mixer.is_some() && self.mixer_setting.as_ref() == Some(mixer) {
+            return Ok(());
+        }
+        self.mixer_setting = Some(mixer.clone());
+        let (new_mixer, result) = match mixer {
+            AudioMixer::Soft => (open_soft_mixer(volume), Ok(())),
+            AudioMixer::Alsa { device, control } => {
+                let config = MixerConfig {
+                    device: device.clone(),
+                    control: control.clone(),
+                    ..Default::default()
+                };
+                match AlsaMixer::open(config) {
+                    Ok(mixer) => {
+                        let mixer: Arc<dyn Mixer> = Arc::new(mixer);
+                        mixer_set_volume(&*mixer, volume);
+                        (mixer, Ok(()))
+                    }
+                    Err(e) => {
+                        warn!("Could not open alsa mixer {device} ({control}): {e}");
+                        (open_soft_mixer(volume), Err(SpotifyError::MixerUnavailable))
+                    }
+                }
+            }
+        };
+        self.mixer = Some(new_mixer);
+        result
+    }
+
+    fn logout(&mut self, session: Option<Session>) -> BoxFuture<'static, ()> {
+        Box::pin(async move {
+            let _ = crate::api::clear_user_cache().await;
+            if let Some(session) = session {
+                session.shutdown();
+            }
+        })
+    }
+
+    fn clear_audio_cache(&self) -> BoxFuture<'static, u64> {
+        Box::pin(async {
+            tokio::task::spawn_blocking(|| clear_dir(&audio_cache_dir()))
+                .await
+                .unwrap_or_default()
+        })
+    }
+
+    fn load_settings(&self) -> SpotifyPlayerSettings {
+        RiffSettings::new_from_gsettings()
+            .unwrap_or_default()
+            .player_settings
+    }
+}
+
+impl PlayerSession for Session {
+    fn username(&self) -> String {
+        Session::username(self)
+    }
+
+    fn reconnect(&self, access_token: String) -> BoxFuture<'static, Result<(), SpotifyError>> {
+        let session = self.clone();
+        Box::pin(async move {
+            session
+                .connect(Credentials::with_access_token(access_token), true)
+                .await
+                .map_err(|_| SpotifyError::LoginFailed)
+        })
+    }
+
+    fn shutdown(&self) {
+        Session::shutdown(self)
+    }
+
+    fn is_invalid(&self) -> bool {
+        Session::is_invalid(self)
+    }
+}
+
+impl PlaybackHandle for Arc<Player> {
+    fn play(&self) {
+        Player::play(self)
+    }
+
+    fn pause(&self) {
+        Player::pause(self)
+    }
+
+    fn stop(&self) {
+        Player::stop(self)
+    }
+
+    fn seek(&self, position_ms: u32) {
+        Player::seek(self, position_ms)
+    }
+
+    fn load(&self, track: SpotifyUri, start_playing: bool, position_ms: u32) {
+        Player::load(self, track, start_playing, position_ms)
+    }
+
+    fn preload(&self, track: SpotifyUri) {
+        Player::preload(self, track)
+    }
+
+    fn event_channel(&self) -> PlayerEventChannel {
+        self.get_player_event_channel()
+    }
+}
+
+// Which of librespot's sinks to use, and what to pass it
+fn find_sink(
+    backend: &AudioBackend,
+    format: AudioFormat,
+) -> Result<(SinkBuilder, Option<String>, AudioFormat), SpotifyError> {
+    let (name, device) = match backend {
+        AudioBackend::GStreamer(pipeline) => ("gstreamer", Some(pipeline.clone())),
+        AudioBackend::PulseAudio => {
+            env::set_var("PULSE_PROP_application.name", "Riff");
+            ("pulseaudio", None)
+        }
+        AudioBackend::Alsa(device) => ("alsa", Some(device.clone())),
+        // librespot has no sink of its own for PipeWire, pipewire-pulse takes it from there
+        // (and knows nodes by their name, as it would a sink)
+        AudioBackend::PipeWire(target) => {
+            env::set_var("PULSE_PROP_application.name", "Riff");
+            ("pulseaudio", target.clone())
+        }
+    };
+    // librespot panics (in its own thread) when a sink is given a format it doesn't take,
+    // so we fall back to the best one it does
+    let supported = supported_formats(name);
+    let format = if supported.contains(&format) {
+        format
+    } else {
+        warn!(
+            "Audio backend {name} can't take {format:?}, using {:?}",
+            supported[0]
+        );
+        supported[0]
+    };
+    info!(
+        "using {name} ({}, {format:?})",
+        device.as_deref().unwrap_or("default")
+    );
+    // librespot might have been built without it
+    let sink = audio_backend::find(Some(name.to_string())).ok_or_else(|| {
+        warn!("Audio backend {name} is not available");
+        SpotifyError::AudioOutputUnavailable
+    })?;
+    Ok((sink, device, format))
+}
+
+fn supported_formats(sink: &str) -> &'static [AudioFormat] {
+    use AudioFormat::*;
+    match sink {
+        "alsa" | "gstreamer" => &[F64, F32, S32, S24, S24_3, S16],
+        "pulseaudio" => &[F32, S32, S24, S24_3, S16],
+        _ => &[S16],
+    }
+}
+
+// Playback devices as (device, label), as found in /proc/asound/pcm.
+// The plughw devices convert whatever we send to what the card supports, which hw wouldn't.
+pub fn list_alsa_devices() -> Vec<(String, String)> {
+    let pcm = fs::read_to_string("/proc/asound/pcm").unwrap_or_default();
+    let mut devices = vec![("default".to_string(), "default".to_string())];
+    devices.extend(parse_alsa_pcm(&pcm));
+    devices
+}
+
+// Lines look like "00-03: HDMI 0 : HDMI 0 : playback 1"
+fn parse_alsa_pcm(pcm: &str) -> Vec<(String, String)> {
+    pcm.lines()
+        .filter_map(|line| {
+            let mut fields = line.split(':').map(str::trim);
+            let (card, device) = fields.next()?.split_once('-')?;
+            let (card, device) = (card.parse::<u32>().ok()?, device.parse::<u32>().ok()?);
+            let name = fields.nth(1)?;
+            fields
+                .any(|f| f.starts_with("playback"))
+                .then(|| (format!("plughw:{card},{device}"), name.to_string()))
+        })
+        .collect()
+}
+
+fn open_soft_mixer(volume: f64) -> Arc<dyn Mixer> {
+    let mixer = Arc::new(
+        SoftMixer::open(MixerConfig {
+            // This value feels reasonable to me. Feel free to change it
+            volume_ctrl: VolumeCtrl::Log(VolumeCtrl::DEFAULT_DB_RANGE / 2.0),
+            ..Default::default()
+        })
+        .expect("Failed to create soft mixer"),
+    );
+    mixer_set_volume(&*mixer, volume);
+    mixer
+}
+
+fn mixer_set_volume(mixer: &dyn Mixer, volume: f64) {
+    mixer.set_volume((VolumeCtrl::MAX_VOLUME as f64 * volume) as u16);
+}
+
+fn cache_dir() -> PathBuf {
+    glib::user_cache_dir().join("riff").join("librespot")
+}
+
+fn audio_cache_dir() -> PathBuf {
+    cache_dir().join("audio")
+}
+
+// How much space the downloaded audio takes, in bytes
+pub fn audio_cache_size() -> u64 {
+    dir_size(&audio_cache_dir())
+}
+
+fn dir_size(path: &Path) -> u64 {
+    let Ok(entries) = fs::read_dir(path) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .map(|entry| match entry.file_type() {
+            Ok(t) if t.is_dir() => dir_size(&entry.path()),
+            _ => entry.metadata().map(|m| m.len()).unwrap_or(0),
+        })
+        .sum()
+}
+
+// Empties the directory but leaves it there, returns how many bytes were freed.
+// Whatever is being played stays readable until it's closed, and librespot recreates
+// the directories it needs when saving a file, so this is fine to do while streaming.
+fn clear_dir(path: &Path) -> u64 {
+    let Ok(entries) = fs::read_dir(path) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .map(|entry| {
+            let path = entry.path();
+            match entry.file_type() {
+                Ok(t) if t.is_dir() => {
+                    let freed = clear_dir(&path);
+                    let _ = fs::remove_dir(&path);
+                    freed
+                }
+                _ => {
+                    let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+                    match fs::remove_file(&path) {
+                        Ok(_) => size,
+                        Err(e) => {
+                            warn!("Could not remove {}: {e}", path.display());
+                            0
+                        }
+                    }
+                }
+            }
+        })
+        .sum()
+}
+
+const KNOWN_AP_PORTS: [Option<u16>; 4] = [None, Some(80), Some(443), Some(4070)];
+
+async fn create_session_with_port(
+    credentials: &Credentials,
+    ap_port: Option<u16>,
+    settings: &SpotifyPlayerSettings,
+) -> Result<Session, SpotifyError> {
+    let proxy = match settings.proxy.as_deref() {
+        Some(proxy) => Some(
+            parse_proxy(proxy)
+                .and_then(|_| Url::parse(proxy).ok())
+                .ok_or_else(|| SpotifyError::InvalidProxy(proxy.to_string()))?,
+        ),
+        None => None,
+    };
+    let session_config = SessionConfig {
+        ap_port,
+        proxy,
+        ..Default::default()
+    };
+    let root = cache_dir();
+    // Credentials and volume are tiny, only the audio can be done without
+    let cache = Cache::new(
+        Some(root.join("credentials")),
+        Some(root.join("volume")),
+        settings.cache_audio.then(audio_cache_dir),
+        settings.audio_cache_limit,
+    )
+    .map_err(|e| dbg!(e))
+    .ok();
+    let session = Session::new(session_config, cache);
+    let port = port_label(ap_port);
+    // Some networks silently drop the traffic, we'd be waiting forever
+    let connect = session.connect(credentials.clone(), true);
+    match tokio::time::timeout(settings.ap_timeout, connect).await {
+        Ok(Ok(_)) => {
+            info!("Connected to an access point (port: {port})");
+            Ok(session)
+        }
+        // No route, no DNS...: not the account's fault, that's handled like a timeout
+        Ok(Err(err)) if is_network_error(&err) => {
+            warn!("Could not connect to an access point (port: {port}): {err}");
+            session.shutdown();
+            Err(SpotifyError::TechnicalError)
+        }
+        Ok(Err(err)) => {
+            warn!("Login failure: {}", err);
+            Err(SpotifyError::LoginFailed)
+        }
+        Err(_) => {
+            warn!("Timed out connecting to an access point (port: {port})");
+            session.shutdown();
+            Err(SpotifyError::TechnicalError)
+        }
+    }
+}
+
+fn is_network_error(err: &librespot::core::Error) -> bool {
+    match err.kind {
+        ErrorKind::Unavailable | ErrorKind::DeadlineExceeded | ErrorKind::Aborted => true,
+        // Failed DNS lookups end up there, but so does anything librespot has no better kind for
+        ErrorKind::Unknown => err.error.downcast_ref::<std::io::Error>().is_some(),
+        _ => false,
+    }
+}
+
+fn port_label(ap_port: Option<u16>) -> String {
+    ap_port
+        .map(|port| port.to_string())
+        .unwrap_or_else(|| "any".to_string())
+}
+
+async fn create_session(
+    credentials: &Credentials,
+    settings: &SpotifyPlayerSettings,
+) -> Result<Session, SpotifyError> {
+    let ports_to_try = match settings.ap_port {
+        Some(_) => vec![settings.ap_port],
+        None => KNOWN_AP_PORTS.to_vec(),
+    };
+    for ap_port in ports_to_try.iter() {
+        match create_session_with_port(credentials, *ap_port, settings).await {
+            Err(SpotifyError::TechnicalError) => continue,
+            res => return res,
+        }
+    }
+    let ports = ports_to_try.into_iter().map(port_label).collect();
+    Err(SpotifyError::AccessPointUnreachable(ports))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_alsa_pcm() {
+        let pcm = "00-00: ALC887-VD Analog : ALC887-VD Analog : playback 1 : capture 1\n\
+            00-02: ALC887-VD Alt Analog : ALC887-VD Alt Analog : capture 1\n\
+            01-03: HDMI 0 : HDMI 0 : playback 1\n";
+
+        assert_eq!(
+            parse_alsa_pcm(pcm),
+            vec![
+                ("plughw:0,0".to_string(), "ALC887-VD Analog".to_string()),
+                ("plughw:1,3".to_string(), "HDMI 0".to_string()),
+            ]
+        );
+        assert!(parse_alsa_pcm("").is_empty());
+    }
+
+    #[test]
+    fn test_unsupported_audio_format() {
+        assert!(matches!(
+            find_sink(&AudioBackend::PulseAudio, AudioFormat::F64),
+            Ok((_, None, AudioFormat::F32))
+        ));
+        assert!(matches!(
+            find_sink(&AudioBackend::PulseAudio, AudioFormat::S24_3),
+            Ok((_, None, AudioFormat::S24_3))
+        ));
+        assert!(supported_formats("alsa").contains(&AudioFormat::S24_3));
+    }
+
+    #[test]
+    fn test_clear_dir() {
+        let root = env::temp_dir().join(format!("riff-test-clear-dir-{}", std::process::id()));
+        fs::create_dir_all(root.join("ab")).unwrap();
+        fs::write(root.join("ab").join("cdef"), [0u8; 100]).unwrap();
+        fs::write(root.join("ghij"), [0u8; 20]).unwrap();
+
+        assert_eq!(dir_size(&root), 120);
+        assert_eq!(clear_dir(&root), 120);
+        assert!(root.exists());
+        assert_eq!(fs::read_dir(&root).unwrap().count(), 0);
+        assert_eq!(clear_dir(&root.join("missing")), 0);
+
+        fs::remove_dir(&root).unwrap();
+    }
+}
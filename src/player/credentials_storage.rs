@@ -0,0 +1,541 @@
+use anyhow::Result;
+use futures::future::BoxFuture;
+use oo7::Keyring;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::PathBuf;
+use std::sync::Once;
+use std::time::Duration;
+
+use crate::app::credentials::Credentials;
+
+const ATTRS: &[(&str, &str)] = &[("riff_credentials", "yes")];
+// What Spot (and Riff, before it got its own) saved the credentials with
+const LEGACY_ATTRS: &[(&str, &str)] = &[("spot_credentials", "yes")];
+// Every account we've logged in with has its own copy, so that we can switch back to it
+const ACCOUNT_ATTRS: &[(&str, &str)] = &[("riff_account", "yes")];
+const USERNAME_ATTR: &str = "riff_username";
+const MAX_KEYRING_RETRIES: u32 = 3;
+const KEYRING_RETRY_DELAY: Duration = Duration::from_millis(200);
+
+fn make_attributes(username: &str) -> HashMap<&str, &str> {
+    HashMap::from([("riff_account", "yes"), (USERNAME_ATTR, username)])
+}
+
+// Where the credentials are kept: the current ones, and a copy for each account
+pub trait CredentialsBackend: Send + Sync {
+    fn load(&self) -> BoxFuture<'_, Result<Option<Credentials>>>;
+    fn save<'a>(&'a self, creds: &'a Credentials) -> BoxFuture<'a, Result<()>>;
+    fn delete(&self) -> BoxFuture<'_, Result<()>>;
+
+    fn load_account<'a>(&'a self, username: &'a str) -> BoxFuture<'a, Result<Option<Credentials>>>;
+    fn save_account<'a>(
+        &'a self,
+        username: &'a str,
+        creds: &'a Credentials,
+    ) -> BoxFuture<'a, Result<()>>;
+    fn delete_account<'a>(&'a self, username: &'a str) -> BoxFuture<'a, Result<()>>;
+    fn usernames(&self) -> BoxFuture<'_, Result<Vec<String>>>;
+
+    // Anything else we have to keep for the user (e.g. a scrobbling session), as JSON
+    fn load_secret<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<Option<Value>>>;
+    fn save_secret<'a>(
+        &'a self,
+        label: &'a str,
+        key: &'a str,
+        value: &'a Value,
+    ) -> BoxFuture<'a, Result<()>>;
+    fn delete_secret<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<()>>;
+}
+
+static LOG_BACKEND: Once = Once::new();
+
+// The Secret Service if there's one, a file otherwise (e.g. no gnome-keyring on a minimal setup)
+pub async fn open_backend() -> Box<dyn CredentialsBackend> {
+    match Keyring::new().await {
+        Ok(keyring) => {
+            LOG_BACKEND.call_once(|| info!("Storing credentials in the keyring"));
+            Box::new(KeyringBackend {
+                keyring,
+                leftovers: FileBackend::new(),
+            })
+        }
+        Err(e) => {
+            let file = FileBackend::new();
+            LOG_BACKEND.call_once(|| {
+                warn!(
+                    "No keyring available ({e}), storing credentials in {}",
+                    file.path.display()
+                )
+            });
+            Box::new(file)
+        }
+    }
+}
+
+struct KeyringBackend {
+    keyring: Keyring,
+    // Whatever was saved while there was no keyring, moved over on the next save
+    leftovers: FileBackend,
+}
+
+impl KeyringBackend {
+    async fn load_from_keyring(&self) -> Result<Option<Credentials>> {
+        if matches!(self.keyring, Keyring::File(_)) {
+            // migrate keys if inside flatpak
+            if let Err(e) = oo7::migrate(vec![ATTRS, LEGACY_ATTRS], true).await {
+                debug!("Failed to migrate system keyring: {e}");
+            }
+        }
+
+        // Attempt to unlock the keyring in case it's still locked after login
+        if let Err(e) = self.keyring.unlock().await {
+            warn!("Failed to unlock keyring: {e}");
+        }
+
+        // Retry to handle race with keyring daemon startup
+        for attempt in 0..MAX_KEYRING_RETRIES {
+            let items = self.keyring.search_items(&ATTRS).await?;
+            if let Some(item) = items.first() {
+                let item_json = item.secret().await?;
+                return Ok(Some(serde_json::from_slice(item_json.as_bytes())?));
+            }
+            if let Some(creds) = self.migrate_legacy().await? {
+                return Ok(Some(creds));
+            }
+            if attempt < MAX_KEYRING_RETRIES - 1 {
+                debug!(
+                    "Keyring empty on attempt {}, retrying in {}ms...",
+                    attempt + 1,
+                    KEYRING_RETRY_DELAY.as_millis()
+                );
+                tokio::time::sleep(KEYRING_RETRY_DELAY).await;
+            }
+        }
+        Ok(None)
+    }
+
+    // Moves the credentials saved by Spot over to our own item, once
+    async fn migrate_legacy(&self) -> Result<Option<Credentials>> {
+        let items = self.keyring.search_items(&LEGACY_ATTRS).await?;
+        let Some(item) = items.first() else {
+            return Ok(None);
+        };
+        let item_json = item.secret().await?;
+        // Spot saved something else entirely at some point, it's of no use to us
+        let creds: Credentials = match serde_json::from_slice(item_json.as_bytes()) {
+            Ok(creds) => creds,
+            Err(e) => {
+                warn!("Ignoring the credentials saved by Spot: {e}");
+                return Ok(None);
+            }
+        };
+        let encoded = serde_json::to_vec(&creds)?;
+        self.keyring
+            .create_item("Spotify Credentials", &ATTRS, &encoded, true)
+            .await?;
+        item.delete().await?;
+        info!("Moved the credentials saved by Spot");
+        Ok(Some(creds))
+    }
+
+    async fn load_account_from_keyring(&self, username: &str) -> Result<Option<Credentials>> {
+        let items = self
+            .keyring
+            .search_items(&make_attributes(username))
+            .await?;
+        match items.first() {
+            Some(item) => Ok(Some(serde_json::from_slice(
+                item.secret().await?.as_bytes(),
+            )?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn load_secret_from_keyring(&self, key: &str) -> Result<Option<Value>> {
+        let attrs: &[(&str, &str)] = &[(key, "yes")];
+        if matches!(self.keyring, Keyring::File(_)) {
+            if let Err(e) = oo7::migrate(vec![attrs], true).await {
+                debug!("Failed to migrate {key} from the system keyring: {e}");
+            }
+        }
+        let items = self.keyring.search_items(&attrs).await?;
+        match items.first() {
+            Some(item) => Ok(Some(serde_json::from_slice(
+                item.secret().await?.as_bytes(),
+            )?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn migrate_leftovers(&self) {
+        if !self.leftovers.path.exists() {
+            return;
+        }
+        let stored = self.leftovers.read();
+        for (username, creds) in stored.accounts.iter() {
+            if let Err(e) = self.save_account(username, creds).await {
+                warn!("Could not move account to the keyring, keeping the file: {e}");
+                return;
+            }
+        }
+        for (key, secret) in stored.secrets.iter() {
+            if let Err(e) = self.save_secret(&secret.label, key, &secret.value).await {
+                warn!("Could not move {key} to the keyring, keeping the file: {e}");
+                return;
+            }
+        }
+        info!("Moved credentials from the file to the keyring");
+        if let Err(e) = fs::remove_file(&self.leftovers.path) {
+            warn!("Could not remove {}: {e}", self.leftovers.path.display());
+        }
+    }
+}
+
+impl CredentialsBackend for KeyringBackend {
+    fn load(&self) -> BoxFuture<'_, Result<Option<Credentials>>> {
+        Box::pin(async move {
+            match self.load_from_keyring().await? {
+                Some(creds) => Ok(Some(creds)),
+                None => self.leftovers.load().await,
+            }
+        })
+    }
+
+    fn save<'a>(&'a self, creds: &'a Credentials) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            // We simply write our stuct as JSON and send it
+            let encoded = serde_json::to_vec(creds)?;
+            self.keyring
+                .create_item("Spotify Credentials", &ATTRS, &encoded, true)
+                .await?;
+            self.migrate_leftovers().await;
+            Ok(())
+        })
+    }
+
+    fn delete(&self) -> BoxFuture<'_, Result<()>> {
+        Box::pin(async move {
+            let result = self.keyring.search_items(&ATTRS).await?;
+            let Some(item) = result.first() else {
+                warn!("Logout attempted, but keyring is empty");
+                return self.leftovers.delete().await;
+            };
+            item.delete().await?;
+            Ok(())
+        })
+    }
+
+    fn load_account<'a>(&'a self, username: &'a str) -> BoxFuture<'a, Result<Option<Credentials>>> {
+        Box::pin(async move {
+            match self.load_account_from_keyring(username).await? {
+                Some(creds) => Ok(Some(creds)),
+                None => self.leftovers.load_account(username).await,
+            }
+        })
+    }
+
+    fn save_account<'a>(
+        &'a self,
+        username: &'a str,
+        creds: &'a Credentials,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let encoded = serde_json::to_vec(creds)?;
+            self.keyring
+                .create_item(
+                    &format!("Spotify Credentials ({username})"),
+                    &make_attributes(username),
+                    &encoded,
+                    true,
+                )
+                .await?;
+            Ok(())
+        })
+    }
+
+    fn delete_account<'a>(&'a self, username: &'a str) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let items = self
+                .keyring
+                .search_items(&make_attributes(username))
+                .await?;
+            for item in items {
+                item.delete().await?;
+            }
+            self.leftovers.delete_account(username).await
+        })
+    }
+
+    fn usernames(&self) -> BoxFuture<'_, Result<Vec<String>>> {
+        Box::pin(async move {
+            let items = self.keyring.search_items(&ACCOUNT_ATTRS).await?;
+            let mut usernames = self.leftovers.usernames().await?;
+            for item in items {
+                if let Some(username) = item.attributes().await?.remove(USERNAME_ATTR) {
+                    usernames.push(username);
+                }
+            }
+            Ok(usernames)
+        })
+    }
+
+    fn load_secret<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<Option<Value>>> {
+        Box::pin(async move {
+            match self.load_secret_from_keyring(key).await? {
+                Some(value) => Ok(Some(value)),
+                None => self.leftovers.load_secret(key).await,
+            }
+        })
+    }
+
+    fn save_secret<'a>(
+        &'a self,
+        label: &'a str,
+        key: &'a str,
+        value: &'a Value,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let attrs: &[(&str, &str)] = &[(key, "yes")];
+            let encoded = serde_json::to_vec(value)?;
+            self.keyring
+                .create_item(label, &attrs, &encoded, true)
+                .await?;
+            Ok(())
+        })
+    }
+
+    fn delete_secret<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let attrs: &[(&str, &str)] = &[(key, "yes")];
+            for item in self.keyring.search_items(&attrs).await? {
+                item.delete().await?;
+            }
+            self.leftovers.delete_secret(key).await
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct StoredCredentials {
+    #[serde(default)]
+    current: Option<Credentials>,
+    #[serde(default)]
+    accounts: HashMap<String, Credentials>,
+    #[serde(default)]
+    secrets: HashMap<String, StoredSecret>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredSecret {
+    // What the keyring will show for it, if it's ever moved there
+    label: String,
+    value: Value,
+}
+
+// A JSON file only we can read, for when there's no keyring
+struct FileBackend {
+    path: PathBuf,
+}
+
+impl FileBackend {
+    fn new() -> Self {
+        Self::at(glib::user_data_dir().join("riff").join("credentials.json"))
+    }
+
+    fn at(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    fn read(&self) -> StoredCredentials {
+        fs::read(&self.path)
+            .ok()
+            .and_then(|content| serde_json::from_slice(&content).ok())
+            .unwrap_or_default()
+    }
+
+    fn write(&self, stored: &StoredCredentials) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        // Written next to it then moved, so that it's never left half written
+        let tmp_path = self.path.with_extension("json.tmp");
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(&tmp_path)?;
+        // In case it was already there with other permissions
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+        file.write_all(&serde_json::to_vec(stored)?)?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, &self.path)?;
+        Ok(())
+    }
+
+    fn update(&self, f: impl FnOnce(&mut StoredCredentials)) -> Result<()> {
+        let mut stored = self.read();
+        f(&mut stored);
+        self.write(&stored)
+    }
+}
+
+impl CredentialsBackend for FileBackend {
+    fn load(&self) -> BoxFuture<'_, Result<Option<Credentials>>> {
+        Box::pin(async move { Ok(self.read().current) })
+    }
+
+    fn save<'a>(&'a self, creds: &'a Credentials) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move { self.update(|stored| stored.current = Some(creds.clone())) })
+    }
+
+    fn delete(&self) -> BoxFuture<'_, Result<()>> {
+        Box::pin(async move {
+            if !self.path.exists() {
+                return Ok(());
+            }
+            self.update(|stored| stored.current = None)
+        })
+    }
+
+    fn load_account<'a>(&'a self, username: &'a str) -> BoxFuture<'a, Result<Option<Credentials>>> {
+        Box::pin(async move { Ok(self.read().accounts.remove(username)) })
+    }
+
+    fn save_account<'a>(
+        &'a self,
+        username: &'a str,
+        creds: &'a Credentials,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            self.update(|stored| {
+                stored.accounts.insert(username.to_string(), creds.clone());
+            })
+        })
+    }
+
+    fn delete_account<'a>(&'a self, username: &'a str) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            if !self.path.exists() {
+                return Ok(());
+            }
+            self.update(|stored| {
+                stored.accounts.remove(username);
+            })
+        })
+    }
+
+    fn usernames(&self) -> BoxFuture<'_, Result<Vec<String>>> {
+        Box::pin(async move { Ok(self.read().accounts.into_keys().collect()) })
+    }
+
+    fn load_secret<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<Option<Value>>> {
+        Box::pin(async move { Ok(self.read().secrets.remove(key).map(|secret| secret.value)) })
+    }
+
+    fn save_secret<'a>(
+        &'a self,
+        label: &'a str,
+        key: &'a str,
+        value: &'a Value,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            self.update(|stored| {
+                let secret = StoredSecret {
+                    label: label.to_string(),
+                    value: value.clone(),
+                };
+                stored.secrets.insert(key.to_string(), secret);
+            })
+        })
+    }
+
+    fn delete_secret<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            if !self.path.exists() {
+                return Ok(());
+            }
+            self.update(|stored| {
+                stored.secrets.remove(key);
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn credentials(access_token: &str) -> Credentials {
+        Credentials {
+            access_token: access_token.to_string(),
+            refresh_token: "refresh".to_string(),
+            token_expiry_time: None,
+            client_id: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn file_backend_round_trip() {
+        let dir =
+            std::env::temp_dir().join(format!("riff-test-credentials-{}", std::process::id()));
+        let backend = FileBackend::at(dir.join("credentials.json"));
+
+        assert!(backend.load().await.unwrap().is_none());
+        backend.save(&credentials("current")).await.unwrap();
+        backend
+            .save_account("me", &credentials("mine"))
+            .await
+            .unwrap();
+
+        let mode = fs::metadata(&backend.path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(
+            backend.load().await.unwrap().unwrap().access_token,
+            "current"
+        );
+        assert_eq!(
+            backend
+                .load_account("me")
+                .await
+                .unwrap()
+                .unwrap()
+                .access_token,
+            "mine"
+        );
+        assert_eq!(backend.usernames().await.unwrap(), vec!["me"]);
+
+        let session = serde_json::json!({ "name": "me", "key": "session" });
+        backend
+            .save_secret("Last.fm Session", "riff_lastfm_session", &session)
+            .await
+            .unwrap();
+        assert_eq!(
+            backend.load_secret("riff_lastfm_session").await.unwrap(),
+            Some(session)
+        );
+        // The credentials are still there
+        assert_eq!(
+            backend.load().await.unwrap().unwrap().access_token,
+            "current"
+        );
+        backend.delete_secret("riff_lastfm_session").await.unwrap();
+        assert!(backend
+            .load_secret("riff_lastfm_session")
+            .await
+            .unwrap()
+            .is_none());
+
+        backend.delete().await.unwrap();
+        backend.delete_account("me").await.unwrap();
+        assert!(backend.load().await.unwrap().is_none());
+        assert!(backend.usernames().await.unwrap().is_empty());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,35 +1,82 @@
 use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
 use librespot::core::SpotifyUri;
+use std::cell::RefCell;
+use std::rc::Rc;
 use std::sync::Arc;
 use tokio::task;
 use url::Url;
 
-use crate::app::state::{LoginAction, PlaybackAction};
+use crate::app::components::labels;
+use crate::app::state::{LoginAction, PlaybackAction, SettingsAction};
 use crate::app::AppAction;
 #[allow(clippy::module_inception)]
 mod player;
 pub use player::*;
 
+mod backend;
+pub use backend::{audio_cache_size, list_alsa_devices};
+
 mod oauth2;
+pub use oauth2::{RiffOauthClient, SpotifyAuth, DEFAULT_REDIRECT_PORT};
+
+mod credentials_storage;
+pub use credentials_storage::open_backend;
 
 mod token_store;
 pub use token_store::*;
 
+mod transitions;
+pub use transitions::transitions_summary;
+
 #[derive(Debug, Clone)]
 pub enum Command {
     Restore,
     InitLogin,
     CompleteLogin,
+    // From the listener of the given login attempt, once the browser got back to it (or it gave up)
+    LoginListenerDone(u64),
+    // What the user pasted when the browser couldn't get back to us
+    CompleteLoginWithCode(String),
+    // Stops waiting for the browser, e.g. when the login page was closed
+    CancelLogin,
+    // Logs out without forgetting the current account, so that another one can log in
+    AddAccount,
+    // To one of the accounts we have credentials for
+    SwitchAccount(String),
     RefreshToken,
     Logout,
-    PlayerLoad { track: SpotifyUri, resume: bool },
+    // The position is where to start from, in milliseconds (used to resume podcast episodes)
+    PlayerLoad {
+        track: SpotifyUri,
+        resume: bool,
+        position_ms: u32,
+    },
     PlayerResume,
     PlayerPause,
     PlayerStop,
     PlayerSeek(u32),
     PlayerSetVolume(f64),
     PlayerPreload(SpotifyUri),
+    // Sent by the player itself, when the track it was told to play couldn't be loaded
+    PlayerLoadFailed(SpotifyUri),
+    // Overrides the gapless setting for what's about to be played (None means use the setting)
+    SetGaplessOverride(Option<bool>),
+    // Picks the bitrate when it's set to automatic
+    SetMeteredConnection(bool),
+    // The network is back, whatever connection we had is probably gone
+    Reconnect,
     ReloadSettings,
+    ClearAudioCache,
+}
+
+impl Command {
+    // Anything else can wait in the queue until the player is actually needed
+    fn needs_player(&self) -> bool {
+        matches!(
+            self,
+            Self::Restore | Self::InitLogin | Self::PlayerLoad { .. } | Self::ClearAudioCache
+        )
+    }
 }
 
 #[derive(Clone)]
@@ -45,9 +92,15 @@ impl AppPlayerDelegate {
     fn send(&self, action: AppAction) {
         self.sender.unbounded_send(action).unwrap();
     }
+}
 
+impl SpotifyPlayerDelegate for AppPlayerDelegate {
     fn end_of_track_reached(&self) {
-        self.send(PlaybackAction::Next.into())
+        self.send(PlaybackAction::EndOfTrack.into())
+    }
+
+    fn track_unavailable(&self, id: String) {
+        self.send(PlaybackAction::TrackUnavailable(id).into())
     }
 
     fn token_login_successful(&self, username: String) {
@@ -60,12 +113,24 @@ impl AppPlayerDelegate {
 
     fn report_error(&self, error: SpotifyError) {
         self.send(match error {
-            SpotifyError::LoginFailed => LoginAction::SetLoginFailure.into(),
+            SpotifyError::LoginFailed => LoginAction::SetLoginFailure(None).into(),
+            // Shown in the login window, the user might be able to do something about it
+            SpotifyError::LoginRejected(_) | SpotifyError::AuthFailed(_) => {
+                LoginAction::SetLoginFailure(Some(error.to_string())).into()
+            }
+            SpotifyError::LoginTimedOut => {
+                self.send(AppAction::ShowNotification(format!("{error}")));
+                LoginAction::SetLoginFailure(None).into()
+            }
             SpotifyError::LoggedOut => LoginAction::Logout.into(),
             _ => AppAction::ShowNotification(format!("{error}")),
         })
     }
 
+    fn buffering_changed(&self, buffering: bool) {
+        self.send(PlaybackAction::SetBuffering(buffering).into())
+    }
+
     fn notify_playback_state(&self, position: u32) {
         self.send(PlaybackAction::SyncSeek(position).into())
     }
@@ -77,40 +142,110 @@ impl AppPlayerDelegate {
     fn login_challenge_started(&self, url: Url) {
         self.send(LoginAction::OpenLoginUrl(url).into())
     }
+
+    fn session_reconnecting(&self, attempt: u32) {
+        // Once is enough
+        if attempt == 1 {
+            self.send(AppAction::ShowNotification(labels::reconnecting_label()));
+        }
+    }
+
+    fn audio_cache_cleared(&self, freed: u64) {
+        self.send(SettingsAction::AudioCacheCleared(freed).into())
+    }
+
+    fn accounts_changed(&self, usernames: Vec<String>) {
+        self.send(LoginAction::SetAccounts(usernames).into())
+    }
+
+    fn went_offline(&self) {
+        self.send(LoginAction::SetOffline.into())
+    }
 }
 
 #[tokio::main]
 async fn player_main(
     player_settings: SpotifyPlayerSettings,
     appaction_sender: UnboundedSender<AppAction>,
-    token_store: TokenStore,
+    oauth_client: Arc<RiffOauthClient>,
     sender: UnboundedSender<Command>,
     receiver: UnboundedReceiver<Command>,
 ) {
     task::spawn(async move {
         let delegate = AppPlayerDelegate::new(appaction_sender.clone());
-        let player = SpotifyPlayer::new(player_settings, delegate, token_store, sender);
+        let player = SpotifyPlayer::new(player_settings, delegate, oauth_client, sender);
         player.start(receiver).await.unwrap();
     })
     .await
     .unwrap();
 }
 
+struct PendingPlayer {
+    player_settings: SpotifyPlayerSettings,
+    appaction_sender: UnboundedSender<AppAction>,
+    oauth_client: Arc<RiffOauthClient>,
+    sender: UnboundedSender<Command>,
+    receiver: UnboundedReceiver<Command>,
+}
+
+impl PendingPlayer {
+    fn start(self) {
+        debug!("Starting player thread");
+        std::thread::spawn(move || {
+            player_main(
+                self.player_settings,
+                self.appaction_sender,
+                self.oauth_client,
+                self.sender,
+                self.receiver,
+            )
+        });
+    }
+}
+
+// Commands are queued until one of them needs the player, and only then is its thread started,
+// so that librespot doesn't get in the way of the window showing up.
+#[derive(Clone)]
+pub struct PlayerService {
+    sender: UnboundedSender<Command>,
+    pending: Rc<RefCell<Option<PendingPlayer>>>,
+}
+
+// The player thread is gone, there's no getting the command back from it
+#[derive(Debug)]
+pub struct PlayerDisconnected;
+
+impl PlayerService {
+    pub fn unbounded_send(&self, command: Command) -> Result<(), PlayerDisconnected> {
+        let needs_player = command.needs_player();
+        self.sender
+            .unbounded_send(command)
+            .map_err(|_| PlayerDisconnected)?;
+        if needs_player {
+            if let Some(pending) = self.pending.borrow_mut().take() {
+                pending.start();
+            }
+        }
+        Ok(())
+    }
+}
+
 pub fn start_player_service(
     player_settings: SpotifyPlayerSettings,
     appaction_sender: UnboundedSender<AppAction>,
-    token_store: TokenStore,
-) -> UnboundedSender<Command> {
+    // Shared with the API client, so that there's only one of us refreshing the token
+    oauth_client: Arc<RiffOauthClient>,
+) -> PlayerService {
     let (sender, receiver) = unbounded::<Command>();
-    let sender_clone = sender.clone();
-    std::thread::spawn(move || {
-        player_main(
-            player_settings,
-            appaction_sender,
-            token_store,
-            sender_clone,
-            receiver,
-        )
-    });
-    sender
+    let pending = PendingPlayer {
+        player_settings,
+        appaction_sender,
+        oauth_client,
+        sender: sender.clone(),
+        receiver,
+    };
+    PlayerService {
+        sender,
+        pending: Rc::new(RefCell::new(Some(pending))),
+    }
 }
@@ -7,18 +7,22 @@
 //! The authorization code flow is an interactive process which requires a web browser
 //! to complete. The resulting code must then be provided back from the browser to this
 //! library for exchange into an access token. Providing the code can be automatic via
-//! a spawned http server (mimicking Spotify's client), or manually via stdin. The latter
-//! is appropriate for headless systems.
+//! a spawned http server (mimicking Spotify's client), or manually by pasting the address
+//! the browser was redirected to (or just its code). The latter is appropriate when the
+//! browser can't reach us, e.g. on headless systems. Whichever comes first is used.
 
 use crate::app::credentials::Credentials;
 
-use log::{error, info, trace};
-use oauth2::reqwest::async_http_client;
+use futures::future::BoxFuture;
+use isahc::AsyncReadResponseExt;
+use log::{debug, error, info, trace, warn};
 use oauth2::{
-    basic::BasicClient, AuthUrl, AuthorizationCode, ClientId, CsrfToken, PkceCodeChallenge,
+    basic::{BasicClient, BasicErrorResponseType},
+    AuthUrl, AuthorizationCode, ClientId, CsrfToken, HttpRequest, HttpResponse, PkceCodeChallenge,
     RedirectUrl, Scope, TokenResponse, TokenUrl,
 };
 use oauth2::{PkceCodeVerifier, RefreshToken, RequestTokenError};
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::io;
 use std::net::SocketAddr;
@@ -30,9 +34,10 @@ use tokio::task::JoinHandle;
 use url::Url;
 
 use super::TokenStore;
+use crate::api::{ProxiedClient, TokenRefresher};
 
 pub const CLIENT_ID: &str = "782ae96ea60f4cdf986a766049607005";
-pub const REDIRECT_URI: &str = "http://127.0.0.1:8898/login";
+pub const DEFAULT_REDIRECT_PORT: u16 = 8898;
 pub const SCOPES: &str = "user-read-private,\
 playlist-read-private,\
 playlist-read-collaborative,\
@@ -46,61 +51,120 @@ playlist-modify-private,\
 user-modify-playback-state,\
 streaming,\
 playlist-modify-public";
+// How long we wait for the user to go through the login page
+const AUTHCODE_TIMEOUT: Duration = Duration::from_secs(5 * 60);
 
 pub struct RiffOauthClient {
-    client: BasicClient,
+    // oauth2's own http client can't go through a proxy
+    http_client: ProxiedClient,
     token_store: TokenStore,
+    // Spotify rotates refresh tokens, so two refreshes at once would leave one of them with a revoked token
+    refreshing: tokio::sync::Mutex<()>,
 }
 
 pub struct AuthcodeChallenge {
     pkce_verifier: PkceCodeVerifier,
+    // Must be the same when asking for a code and exchanging it
+    redirect_url: RedirectUrl,
     pub auth_url: Url,
+    // Checked against whatever comes back, whichever way it does
+    csrf_token: CsrfToken,
+    // The custom one from the settings when the login started, if any
+    client_id: Option<String>,
     listener: JoinHandle<Result<AuthorizationCode, OAuthError>>,
 }
 
+impl AuthcodeChallenge {
+    // Waits for the browser to be redirected to our listener
+    pub async fn listener_code(&mut self) -> Result<AuthorizationCode, OAuthError> {
+        (&mut self.listener)
+            .await
+            .map_err(|_| OAuthError::AuthCodeListenerTerminated)?
+    }
+
+    // Stops listening, the port is free again once this returns
+    pub async fn cancel(self) {
+        self.listener.abort();
+        let _ = self.listener.await;
+    }
+
+    // When the browser can't reach the listener, the user can paste the address it was sent to
+    pub fn pasted_code(&self, pasted: &str) -> Result<AuthorizationCode, OAuthError> {
+        parse_pasted(pasted, &self.csrf_token)
+    }
+}
+
+// Tokens only work with the client ID they were obtained with, so changing it means logging in again
+fn make_client(client_id: Option<&str>) -> BasicClient {
+    let auth_url =
+        AuthUrl::new("https://accounts.spotify.com/authorize".to_string()).expect("Malformed URL");
+    let token_url =
+        TokenUrl::new("https://accounts.spotify.com/api/token".to_string()).expect("Malformed URL");
+    let client_id = ClientId::new(client_id.unwrap_or(CLIENT_ID).to_string());
+    BasicClient::new(client_id, None, auth_url, Some(token_url))
+        .set_redirect_uri(make_redirect_url(DEFAULT_REDIRECT_PORT))
+}
+
 impl RiffOauthClient {
     pub fn new(token_store: TokenStore) -> Self {
-        let auth_url = AuthUrl::new("https://accounts.spotify.com/authorize".to_string())
-            .expect("Malformed URL");
-        let token_url = TokenUrl::new("https://accounts.spotify.com/api/token".to_string())
-            .expect("Malformed URL");
-        let redirect_url = RedirectUrl::new(REDIRECT_URI.to_string()).expect("Malformed URL");
-        let client = BasicClient::new(
-            ClientId::new(CLIENT_ID.to_string()),
-            None,
-            auth_url,
-            Some(token_url),
-        )
-        .set_redirect_uri(redirect_url);
         Self {
-            client,
+            http_client: ProxiedClient::new(),
             token_store,
+            refreshing: tokio::sync::Mutex::new(()),
         }
     }
 
+    async fn send(&self, request: HttpRequest) -> Result<HttpResponse, isahc::Error> {
+        let mut builder = isahc::Request::builder()
+            .method(request.method)
+            .uri(request.url.as_str());
+        for (name, value) in request.headers.iter() {
+            builder = builder.header(name, value);
+        }
+        let mut response = self
+            .http_client
+            .get()
+            .send_async(builder.body(request.body)?)
+            .await?;
+        let body = response.bytes().await?;
+        Ok(HttpResponse {
+            status_code: response.status(),
+            headers: response.headers().clone(),
+            body,
+        })
+    }
+
     pub async fn spawn_authcode_listener(
         &self,
+        redirect_port: u16,
         notify_complete: impl FnOnce() + Send + 'static,
     ) -> Result<AuthcodeChallenge, OAuthError> {
         let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
+        let redirect_url = make_redirect_url(redirect_port);
+        let addr = get_socket_address(redirect_url.as_str()).expect("Invalid redirect uri");
 
         // Generate the full authorization URL.
         // Some of these scopes are unavailable for custom client IDs. Which?
         let request_scopes: Vec<oauth2::Scope> =
             SCOPES.split(",").map(|s| Scope::new(s.into())).collect();
 
-        let (auth_url, csrf_token) = self
-            .client
+        // Read now, so that it's whatever the user just set
+        let client_id = crate::settings::custom_client_id();
+        let (auth_url, csrf_token) = make_client(client_id.as_deref())
             .authorize_url(CsrfToken::new_random)
             .add_scopes(request_scopes)
             .set_pkce_challenge(pkce_challenge)
+            .set_redirect_uri(Cow::Borrowed(&redirect_url))
             .url();
 
         Ok(AuthcodeChallenge {
             pkce_verifier,
+            redirect_url,
             auth_url,
+            csrf_token: csrf_token.clone(),
+            client_id,
             listener: tokio::task::spawn(async move {
-                let result = wait_for_authcode(csrf_token).await;
+                let result = wait_for_authcode(addr, csrf_token, AUTHCODE_TIMEOUT).await;
                 notify_complete();
                 result
             }),
@@ -109,20 +173,19 @@ impl RiffOauthClient {
 
     /// Obtain a Spotify access token using the authorization code with PKCE OAuth flow.
     /// The redirect_uri must match what is registered to the client ID.
+    /// The code can come from the listener or be pasted, the listener is stopped either way.
     pub async fn exchange_authcode(
         &self,
         challenge: AuthcodeChallenge,
+        code: AuthorizationCode,
     ) -> Result<Credentials, OAuthError> {
-        let code = challenge
-            .listener
-            .await
-            .map_err(|_| OAuthError::AuthCodeListenerTerminated)??;
+        challenge.listener.abort();
 
-        let token = self
-            .client
+        let token = make_client(challenge.client_id.as_deref())
             .exchange_code(code)
             .set_pkce_verifier(challenge.pkce_verifier)
-            .request_async(async_http_client)
+            .set_redirect_uri(Cow::Owned(challenge.redirect_url))
+            .request_async(|request| self.send(request))
             .await
             .map_err(|e| match e {
                 RequestTokenError::ServerResponse(res) => {
@@ -130,7 +193,9 @@ impl RiffOauthClient {
                         "An error occured while exchange a code: {}",
                         res.to_string()
                     );
-                    OAuthError::ExchangeCode { e: res.to_string() }
+                    OAuthError::ExchangeCode {
+                        e: describe_denial(res.error().as_ref(), res.error_description()),
+                    }
                 }
                 e => OAuthError::ExchangeCode { e: e.to_string() },
             })?;
@@ -152,38 +217,47 @@ impl RiffOauthClient {
                         .expires_in()
                         .unwrap_or_else(|| Duration::from_secs(3600)),
             ),
+            client_id: challenge.client_id,
         };
 
         self.token_store.set(token.clone()).await;
         Ok(token)
     }
 
-    pub async fn get_valid_token(&self) -> Result<Credentials, OAuthError> {
-        let token = self.token_store.get().await.ok_or(OAuthError::LoggedOut)?;
-        if token.token_expired() {
-            self.refresh_token(token).await
-        } else {
-            Ok(token)
+    pub async fn refresh_token(&self, old_token: Credentials) -> Result<Credentials, OAuthError> {
+        let _refreshing = self.refreshing.lock().await;
+        // The player and the API client share this client, the other one may have just done it
+        if let Some(current) = self.token_store.get_cached().await {
+            if current.refresh_token != old_token.refresh_token {
+                debug!("Token already refreshed");
+                return Ok(current);
+            }
         }
-    }
 
-    pub async fn refresh_token(&self, old_token: Credentials) -> Result<Credentials, OAuthError> {
-        let Ok(token) = self
-            .client
+        let token = match make_client(old_token.client_id.as_deref())
             .exchange_refresh_token(&RefreshToken::new(old_token.refresh_token))
-            .request_async(async_http_client)
+            .request_async(|request| self.send(request))
             .await
-            .inspect_err(|e| {
-                if let RequestTokenError::ServerResponse(res) = e {
-                    error!(
-                        "An error occured while refreshing the token: {}",
-                        res.to_string()
-                    );
-                }
-            })
-        else {
-            self.token_store.clear().await;
-            return Err(OAuthError::NoRefreshToken);
+        {
+            Ok(token) => token,
+            // Revoked or expired (or from another client ID), only logging in again will help
+            Err(RequestTokenError::ServerResponse(res))
+                if matches!(
+                    res.error(),
+                    BasicErrorResponseType::InvalidGrant
+                        | BasicErrorResponseType::InvalidClient
+                        | BasicErrorResponseType::UnauthorizedClient
+                ) =>
+            {
+                error!("The refresh token was rejected: {res}");
+                self.token_store.clear().await;
+                return Err(OAuthError::NoRefreshToken);
+            }
+            // Most likely the network (or Spotify having a bad day), we keep the token to try again later
+            Err(e) => {
+                warn!("An error occured while refreshing the token: {e}");
+                return Err(OAuthError::RefreshFailed { e: e.to_string() });
+            }
         };
 
         let refresh_token = token
@@ -201,30 +275,120 @@ impl RiffOauthClient {
                         .expires_in()
                         .unwrap_or_else(|| Duration::from_secs(3600)),
             ),
+            client_id: old_token.client_id,
         };
 
         self.token_store.set(new_token.clone()).await;
         Ok(new_token)
     }
+}
 
-    pub async fn refresh_token_at_expiry(&self) -> Result<Credentials, OAuthError> {
-        let Some(old_token) = self.token_store.get_cached().await.take() else {
-            return Err(OAuthError::NoRefreshToken);
-        };
+// The parts of the OAuth flow the player relies on, so that it can be tested without Spotify's accounts service
+pub trait SpotifyAuth: Send + Sync + 'static {
+    fn token_store(&self) -> &TokenStore;
+
+    // Trades the refresh token for a new access token, and saves it
+    fn refresh_token(
+        &self,
+        old_token: Credentials,
+    ) -> BoxFuture<'_, Result<Credentials, OAuthError>>;
 
-        let duration = old_token
-            .token_expiry_time
-            .and_then(|d| d.duration_since(SystemTime::now()).ok())
-            .unwrap_or(Duration::from_secs(120));
+    fn spawn_authcode_listener(
+        &self,
+        redirect_port: u16,
+        notify_complete: Box<dyn FnOnce() + Send>,
+    ) -> BoxFuture<'_, Result<AuthcodeChallenge, OAuthError>>;
 
-        info!(
-            "Refreshing token in approx {}min",
-            duration.as_secs().div_euclid(60)
-        );
-        tokio::time::sleep(duration.saturating_sub(Duration::from_secs(10))).await;
+    fn exchange_authcode(
+        &self,
+        challenge: AuthcodeChallenge,
+        code: AuthorizationCode,
+    ) -> BoxFuture<'_, Result<Credentials, OAuthError>>;
+
+    fn get_valid_token(&self) -> BoxFuture<'_, Result<Credentials, OAuthError>> {
+        Box::pin(async move {
+            let token = self
+                .token_store()
+                .get()
+                .await
+                .ok_or(OAuthError::LoggedOut)?;
+            if token.token_expired() {
+                self.refresh_token(token).await
+            } else {
+                Ok(token)
+            }
+        })
+    }
+
+    fn refresh_token_at_expiry(&self) -> BoxFuture<'_, Result<Credentials, OAuthError>> {
+        Box::pin(async move {
+            let Some(old_token) = self.token_store().get_cached().await.take() else {
+                return Err(OAuthError::NoRefreshToken);
+            };
+
+            // Already expired means a previous attempt failed, the caller waited before trying again
+            let duration = match old_token.token_expiry_time {
+                Some(expiry) => expiry.duration_since(SystemTime::now()).unwrap_or_default(),
+                None => Duration::from_secs(120),
+            };
+
+            info!(
+                "Refreshing token in approx {}min",
+                duration.as_secs().div_euclid(60)
+            );
+            tokio::time::sleep(duration.saturating_sub(Duration::from_secs(10))).await;
+
+            info!("Refreshing token...");
+            self.refresh_token(old_token).await
+        })
+    }
+}
+
+impl SpotifyAuth for RiffOauthClient {
+    fn token_store(&self) -> &TokenStore {
+        &self.token_store
+    }
+
+    fn refresh_token(
+        &self,
+        old_token: Credentials,
+    ) -> BoxFuture<'_, Result<Credentials, OAuthError>> {
+        Box::pin(RiffOauthClient::refresh_token(self, old_token))
+    }
+
+    fn spawn_authcode_listener(
+        &self,
+        redirect_port: u16,
+        notify_complete: Box<dyn FnOnce() + Send>,
+    ) -> BoxFuture<'_, Result<AuthcodeChallenge, OAuthError>> {
+        Box::pin(RiffOauthClient::spawn_authcode_listener(
+            self,
+            redirect_port,
+            notify_complete,
+        ))
+    }
+
+    fn exchange_authcode(
+        &self,
+        challenge: AuthcodeChallenge,
+        code: AuthorizationCode,
+    ) -> BoxFuture<'_, Result<Credentials, OAuthError>> {
+        Box::pin(RiffOauthClient::exchange_authcode(self, challenge, code))
+    }
+}
 
-        info!("Refreshing token...");
-        self.refresh_token(old_token).await
+// Used by the API client when Spotify tells us the token expired before we noticed
+impl TokenRefresher for RiffOauthClient {
+    fn refresh(&self) -> BoxFuture<'_, bool> {
+        Box::pin(async move {
+            let Some(old_token) = self.token_store.get_cached().await else {
+                return false;
+            };
+            self.refresh_token(old_token)
+                .await
+                .inspect_err(|e| warn!("Could not refresh token: {e}"))
+                .is_ok()
+        })
     }
 }
 
@@ -242,6 +406,9 @@ pub enum OAuthError {
     #[error("Listener terminated without accepting a connection")]
     AuthCodeListenerTerminated,
 
+    #[error("Timed out waiting for the login to complete")]
+    AuthCodeListenerTimeout,
+
     #[error("Failed to parse redirect URI from HTTP request")]
     AuthCodeListenerParse,
 
@@ -254,65 +421,131 @@ pub enum OAuthError {
     #[error("Spotify did not provide a refresh token")]
     NoRefreshToken,
 
+    #[error("Failed to refresh the token ({e})")]
+    RefreshFailed { e: String },
+
     #[error("No saved token")]
     LoggedOut,
 
     #[error("Mismatched state during auth code exchange")]
     InvalidState,
+
+    #[error("{e}")]
+    AuthorizationDenied { e: String },
 }
 
-/// Spawn HTTP server at provided socket address to accept OAuth callback and return auth code.
-async fn wait_for_authcode(expected_state: CsrfToken) -> Result<AuthorizationCode, OAuthError> {
-    let addr = get_socket_address(REDIRECT_URI).expect("Invalid redirect uri");
+impl OAuthError {
+    // Worth trying again later, the saved token is still there
+    pub fn is_transient(&self) -> bool {
+        matches!(self, Self::RefreshFailed { .. })
+    }
+}
+
+fn make_redirect_url(port: u16) -> RedirectUrl {
+    RedirectUrl::new(format!("http://127.0.0.1:{port}/login")).expect("Malformed URL")
+}
 
+/// Spawn HTTP server at provided socket address to accept OAuth callback and return auth code.
+async fn wait_for_authcode(
+    addr: SocketAddr,
+    expected_state: CsrfToken,
+    timeout: Duration,
+) -> Result<AuthorizationCode, OAuthError> {
     let listener = tokio::net::TcpListener::bind(addr)
         .await
         .map_err(|e| OAuthError::AuthCodeListenerBind { addr, e })?;
 
-    let (mut stream, _) = listener
-        .accept()
+    tokio::time::timeout(timeout, accept_authcode(&listener, expected_state))
         .await
-        .map_err(|_| OAuthError::AuthCodeListenerTerminated)?;
+        .map_err(|_| OAuthError::AuthCodeListenerTimeout)?
+}
 
-    let mut request_line = String::new();
-    let mut reader = BufReader::new(&mut stream);
-    reader
-        .read_line(&mut request_line)
-        .await
-        .map_err(|_| OAuthError::AuthCodeListenerParse)?;
+// Browsers (or anything else really) might poke the port before the actual redirect happens,
+// e.g. to get a favicon, so we keep going until we get something that looks like a code.
+async fn accept_authcode(
+    listener: &tokio::net::TcpListener,
+    expected_state: CsrfToken,
+) -> Result<AuthorizationCode, OAuthError> {
+    loop {
+        let (mut stream, _) = listener
+            .accept()
+            .await
+            .map_err(|_| OAuthError::AuthCodeListenerTerminated)?;
 
-    let (state, code) = parse_query(&request_line)?;
-    if *expected_state.secret() != *state.secret() {
-        return Err(OAuthError::InvalidState);
-    }
+        let mut request_line = String::new();
+        let mut reader = BufReader::new(&mut stream);
+        if reader.read_line(&mut request_line).await.is_err() {
+            continue;
+        }
 
-    let message = include_str!("./login.html");
-    let response = format!(
-        "HTTP/1.1 200 OK\r\ncontent-length: {}\r\n\r\n{}",
-        message.len(),
-        message
-    );
-    stream
-        .write_all(response.as_bytes())
-        .await
-        .map_err(|_| OAuthError::AuthCodeListenerWrite)?;
+        let (state, code) = match parse_query(&request_line) {
+            Ok(result) => result,
+            // Nothing else is coming
+            Err(e @ OAuthError::AuthorizationDenied { .. }) => {
+                let _ = stream
+                    .write_all(b"HTTP/1.1 403 Forbidden\r\ncontent-length: 0\r\n\r\n")
+                    .await;
+                return Err(e);
+            }
+            Err(e) => {
+                debug!("Ignoring request to the authcode listener: {e}");
+                let _ = stream
+                    .write_all(b"HTTP/1.1 404 Not Found\r\ncontent-length: 0\r\n\r\n")
+                    .await;
+                continue;
+            }
+        };
 
-    Ok(code)
+        if *expected_state.secret() != *state.secret() {
+            let _ = stream
+                .write_all(b"HTTP/1.1 400 Bad Request\r\ncontent-length: 0\r\n\r\n")
+                .await;
+            return Err(OAuthError::InvalidState);
+        }
+
+        let message = include_str!("./login.html");
+        let response = format!(
+            "HTTP/1.1 200 OK\r\ncontent-length: {}\r\n\r\n{}",
+            message.len(),
+            message
+        );
+        stream
+            .write_all(response.as_bytes())
+            .await
+            .map_err(|_| OAuthError::AuthCodeListenerWrite)?;
+
+        return Ok(code);
+    }
 }
 
 fn parse_query(request_line: &str) -> Result<(CsrfToken, AuthorizationCode), OAuthError> {
-    let query = request_line
+    let target = request_line
         .split_whitespace()
         .nth(1)
-        .ok_or(OAuthError::AuthCodeListenerParse)?
-        .split("?")
-        .nth(1)
         .ok_or(OAuthError::AuthCodeListenerParse)?;
+    parse_redirect(target)
+}
+
+// Either the whole redirect URL, or just its query (code=...&state=...)
+fn parse_redirect(redirect: &str) -> Result<(CsrfToken, AuthorizationCode), OAuthError> {
+    let query = match redirect.split_once('?') {
+        Some((_, query)) => query,
+        None if redirect.contains('=') => redirect,
+        None => return Err(OAuthError::AuthCodeListenerParse),
+    };
+    let query = query.split('#').next().unwrap_or_default();
 
     let mut query_params: HashMap<String, String> = url::form_urlencoded::parse(query.as_bytes())
         .into_owned()
         .collect();
 
+    // Spotify sends us back with an error instead of a code when it won't authorize us
+    if let Some(error) = query_params.remove("error") {
+        return Err(OAuthError::AuthorizationDenied {
+            e: describe_denial(&error, query_params.get("error_description")),
+        });
+    }
+
     let csrf_token = query_params
         .remove("state")
         .map(CsrfToken::new)
@@ -325,6 +558,34 @@ fn parse_query(request_line: &str) -> Result<(CsrfToken, AuthorizationCode), OAu
     Ok((csrf_token, code))
 }
 
+// The state is checked when we have it, a lone code is still tied to us by the PKCE verifier
+fn parse_pasted(pasted: &str, csrf_token: &CsrfToken) -> Result<AuthorizationCode, OAuthError> {
+    let pasted = pasted.trim();
+    if pasted.is_empty() {
+        return Err(OAuthError::AuthCodeNotFound);
+    }
+    if !pasted.contains(['?', '=', '/']) {
+        return Ok(AuthorizationCode::new(pasted.to_string()));
+    }
+    let (state, code) = parse_redirect(pasted)?;
+    if *csrf_token.secret() != *state.secret() {
+        return Err(OAuthError::InvalidState);
+    }
+    Ok(code)
+}
+
+// The error codes are standard (RFC 6749), not all of them are worth explaining
+fn describe_denial(error: &str, description: Option<&String>) -> String {
+    match error {
+        "access_denied" => "the access was not granted".to_string(),
+        "invalid_scope" => {
+            "the client ID is not allowed to use some of the permissions Riff needs".to_string()
+        }
+        "unauthorized_client" | "invalid_client" => "the client ID is not valid".to_string(),
+        _ => description.cloned().unwrap_or_else(|| error.to_string()),
+    }
+}
+
 // If the specified `redirect_uri` is HTTP, loopback, and contains a port,
 // then the corresponding socket address is returned.
 fn get_socket_address(redirect_uri: &str) -> Option<SocketAddr> {
@@ -384,4 +645,133 @@ mod test {
             Some(localhost_v6)
         );
     }
+
+    #[test]
+    fn parse_pasted_redirect() {
+        for pasted in [
+            "http://127.0.0.1:8898/login?code=1234&state=abc",
+            "code=1234&state=abc",
+            "http://127.0.0.1:8898/login?state=abc&code=1234#",
+        ] {
+            let (state, code) = parse_redirect(pasted).unwrap();
+            assert_eq!(state.secret(), "abc");
+            assert_eq!(code.secret(), "1234");
+        }
+        assert!(matches!(
+            parse_redirect("1234"),
+            Err(OAuthError::AuthCodeListenerParse)
+        ));
+        assert!(matches!(
+            parse_redirect("http://127.0.0.1:8898/login?state=abc"),
+            Err(OAuthError::AuthCodeNotFound)
+        ));
+    }
+
+    #[test]
+    fn parse_denied_redirect() {
+        let Err(OAuthError::AuthorizationDenied { e }) =
+            parse_redirect("http://127.0.0.1:8898/login?error=invalid_scope&state=abc")
+        else {
+            panic!("expected the authorization to be denied");
+        };
+        assert!(e.contains("permissions"));
+
+        let Err(OAuthError::AuthorizationDenied { e }) =
+            parse_redirect("error=server_error&error_description=Try+later&state=abc")
+        else {
+            panic!("expected the authorization to be denied");
+        };
+        assert_eq!(e, "Try later");
+    }
+
+    #[test]
+    fn parse_pasted_code() {
+        let csrf_token = CsrfToken::new("abc".to_string());
+        for pasted in [
+            "  http://127.0.0.1:8898/login?code=1234&state=abc\n",
+            "code=1234&state=abc",
+            "1234",
+        ] {
+            let code = parse_pasted(pasted, &csrf_token).unwrap();
+            assert_eq!(code.secret(), "1234");
+        }
+        assert!(matches!(
+            parse_pasted("code=1234&state=other", &csrf_token),
+            Err(OAuthError::InvalidState)
+        ));
+        assert!(matches!(
+            parse_pasted(" ", &csrf_token),
+            Err(OAuthError::AuthCodeNotFound)
+        ));
+    }
+
+    fn free_local_address() -> SocketAddr {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.local_addr().unwrap()
+    }
+
+    async fn get(addr: SocketAddr, path: &str) -> String {
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(format!("GET {path} HTTP/1.1\r\n\r\n").as_bytes())
+            .await
+            .unwrap();
+        let mut status_line = String::new();
+        BufReader::new(&mut stream)
+            .read_line(&mut status_line)
+            .await
+            .unwrap();
+        status_line
+    }
+
+    #[tokio::test]
+    async fn authcode_listener_ignores_unrelated_requests() {
+        let addr = free_local_address();
+        let listener = tokio::spawn(wait_for_authcode(
+            addr,
+            CsrfToken::new("state".to_string()),
+            Duration::from_secs(5),
+        ));
+        // Give it a chance to bind
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        assert!(get(addr, "/favicon.ico").await.contains("404"));
+        assert!(get(addr, "/login?state=state&code=1234")
+            .await
+            .contains("200"));
+
+        let code = listener.await.unwrap().unwrap();
+        assert_eq!(code.secret(), "1234");
+    }
+
+    #[tokio::test]
+    async fn cancelled_challenge_frees_the_port() {
+        let addr = free_local_address();
+        let csrf_token = CsrfToken::new("state".to_string());
+        let challenge = AuthcodeChallenge {
+            pkce_verifier: PkceCodeChallenge::new_random_sha256().1,
+            redirect_url: make_redirect_url(addr.port()),
+            auth_url: Url::parse("https://accounts.spotify.com/authorize").unwrap(),
+            csrf_token: csrf_token.clone(),
+            client_id: None,
+            listener: tokio::spawn(wait_for_authcode(addr, csrf_token, Duration::from_secs(5))),
+        };
+        // Give it a chance to bind
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!challenge.listener.is_finished());
+
+        challenge.cancel().await;
+        assert!(std::net::TcpListener::bind(addr).is_ok());
+    }
+
+    #[tokio::test]
+    async fn authcode_listener_times_out() {
+        let result = wait_for_authcode(
+            free_local_address(),
+            CsrfToken::new("state".to_string()),
+            Duration::from_millis(100),
+        )
+        .await;
+        assert!(matches!(result, Err(OAuthError::AuthCodeListenerTimeout)));
+    }
 }
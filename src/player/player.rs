@@ -1,48 +1,98 @@
 use futures::channel::mpsc::{UnboundedReceiver, UnboundedSender};
 use futures::stream::StreamExt;
 
-use librespot::core::authentication::Credentials;
-use librespot::core::cache::Cache;
-use librespot::core::config::SessionConfig;
-use librespot::core::session::Session;
-
-use librespot::playback::mixer::softmixer::SoftMixer;
-use librespot::playback::mixer::{Mixer, MixerConfig};
-
-use librespot::playback::audio_backend;
-use librespot::playback::config::{AudioFormat, Bitrate, PlayerConfig, VolumeCtrl};
-use librespot::playback::player::{Player, PlayerEvent, PlayerEventChannel};
+use librespot::core::SpotifyUri;
+use librespot::playback::config::{AudioFormat, Bitrate, NormalisationType, PlayerConfig};
+use librespot::playback::player::{PlayerEvent, PlayerEventChannel};
+use oauth2::AuthorizationCode;
 use url::Url;
 
 use crate::app::models::RepeatMode;
 use crate::player::AppPlayerDelegate;
 
-use super::oauth2::{AuthcodeChallenge, RiffOauthClient};
-use super::{Command, TokenStore};
+use super::backend::{LibrespotBackend, PlaybackHandle, PlayerBackend, PlayerSession};
+use super::oauth2::{AuthcodeChallenge, RiffOauthClient, SpotifyAuth, DEFAULT_REDIRECT_PORT};
+use super::transitions::record_transition;
+use super::Command;
 use crate::app::credentials;
 use crate::player::oauth2::OAuthError;
-use crate::settings::RiffSettings;
-use std::env;
 use std::error::Error;
 use std::fmt;
 use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+use tokio::task::JoinHandle;
 
 #[derive(Debug)]
 pub enum SpotifyError {
     LoginFailed,
+    LoginTimedOut,
+    LoginCodeInvalid,
+    // Spotify said no, with its reason
+    LoginRejected(String),
+    // Anything else that went wrong on the way, kept so that the user can be told what
+    AuthFailed(OAuthError),
     LoggedOut,
     PlayerNotReady,
+    AudioOutputUnavailable,
+    MixerUnavailable,
+    InvalidProxy(String),
+    // With the ports that were tried
+    AccessPointUnreachable(Vec<String>),
     TechnicalError,
 }
 
 impl Error for SpotifyError {}
 
+impl SpotifyError {
+    // Nothing wrong with the account, we just can't get to Spotify right now
+    pub fn is_network_error(&self) -> bool {
+        match self {
+            Self::AccessPointUnreachable(_) => true,
+            Self::AuthFailed(e) => e.is_transient(),
+            _ => false,
+        }
+    }
+}
+
 impl fmt::Display for SpotifyError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::LoginFailed => write!(f, "Login failed!"),
+            Self::LoginTimedOut => write!(f, "Login timed out, please try again."),
+            Self::LoginCodeInvalid => {
+                write!(f, "This isn't the address we expected, please try again.")
+            }
+            Self::LoginRejected(reason) => {
+                write!(f, "Spotify refused the login: {reason}.")
+            }
+            Self::AuthFailed(e) => write!(f, "Login failed: {e}."),
             Self::LoggedOut => write!(f, "You are logged out!"),
             Self::PlayerNotReady => write!(f, "Player is not responding."),
+            Self::AudioOutputUnavailable => {
+                write!(
+                    f,
+                    "Could not open the audio output, check the audio backend in the settings."
+                )
+            }
+            Self::MixerUnavailable => {
+                write!(
+                    f,
+                    "Could not open the hardware mixer, using software volume instead."
+                )
+            }
+            Self::InvalidProxy(proxy) => {
+                write!(
+                    f,
+                    "Invalid proxy address: {proxy}. Only HTTP proxies are supported, check the network settings."
+                )
+            }
+            Self::AccessPointUnreachable(ports) => {
+                write!(
+                    f,
+                    "Could not reach Spotify (tried ports: {}). Check your connectivity.",
+                    ports.join(", ")
+                )
+            }
             Self::TechnicalError => {
                 write!(f, "A technical error occured. Check your connectivity.")
             }
@@ -50,14 +100,24 @@ impl fmt::Display for SpotifyError {
     }
 }
 
-pub trait SpotifyPlayerDelegate {
+pub trait SpotifyPlayerDelegate: Clone + Send + 'static {
     fn end_of_track_reached(&self);
+    // The track couldn't be loaded, e.g. it isn't available in the user's country
+    fn track_unavailable(&self, id: String);
     fn login_challenge_started(&self, url: Url);
     fn token_login_successful(&self, username: String);
     fn refresh_successful(&self);
     fn report_error(&self, error: SpotifyError);
     fn notify_playback_state(&self, position: u32);
     fn preload_next_track(&self);
+    fn buffering_changed(&self, buffering: bool);
+    // Starting from 1
+    fn session_reconnecting(&self, attempt: u32);
+    fn audio_cache_cleared(&self, freed: u64);
+    // The usernames of every account we have credentials for
+    fn accounts_changed(&self, usernames: Vec<String>);
+    // The session couldn't be restored for lack of network, we'll try again once it's back
+    fn went_offline(&self);
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -65,14 +125,57 @@ pub enum AudioBackend {
     GStreamer(String),
     PulseAudio,
     Alsa(String),
+    // The node to play to, if not the default one (played through pipewire-pulse)
+    PipeWire(Option<String>),
+}
+
+// What changes the volume
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AudioMixer {
+    Soft,
+    Alsa { device: String, control: String },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BitrateSetting {
+    Fixed(Bitrate),
+    // Depends on whether the connection is metered (e.g. tethering)
+    Automatic {
+        metered: Bitrate,
+        unmetered: Bitrate,
+    },
+}
+
+impl BitrateSetting {
+    pub fn for_connection(&self, is_metered: bool) -> Bitrate {
+        match *self {
+            Self::Fixed(bitrate) => bitrate,
+            Self::Automatic { metered, .. } if is_metered => metered,
+            Self::Automatic { unmetered, .. } => unmetered,
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct SpotifyPlayerSettings {
-    pub bitrate: Bitrate,
+    pub bitrate: BitrateSetting,
     pub backend: AudioBackend,
+    pub mixer: AudioMixer,
+    pub audio_format: AudioFormat,
     pub gapless: bool,
+    // The pregain (in dB) if normalization is enabled
+    pub normalization: Option<f64>,
+    pub normalization_type: NormalisationType,
     pub ap_port: Option<u16>,
+    // When pausing and resuming, zero meaning there's no fading
+    pub fade_duration: Duration,
+    // How long to wait for each access point
+    pub ap_timeout: Duration,
+    pub proxy: Option<String>,
+    pub redirect_port: u16,
+    pub cache_audio: bool,
+    // In bytes, None meaning there's no limit
+    pub audio_cache_limit: Option<u64>,
 
     pub shuffle: bool,
     pub repeat: RepeatMode,
@@ -86,43 +189,211 @@ impl Default for SpotifyPlayerSettings {
             repeat: RepeatMode::None,
             shuffle: false,
 
-            bitrate: Bitrate::Bitrate160,
+            bitrate: BitrateSetting::Fixed(Bitrate::Bitrate160),
             gapless: true,
+            normalization: Some(0.0),
+            normalization_type: NormalisationType::Auto,
             backend: AudioBackend::PulseAudio,
+            mixer: AudioMixer::Soft,
+            audio_format: AudioFormat::default(),
             ap_port: None,
+            fade_duration: Duration::from_millis(200),
+            ap_timeout: Duration::from_secs(10),
+            proxy: None,
+            redirect_port: DEFAULT_REDIRECT_PORT,
+            cache_audio: true,
+            audio_cache_limit: Some(1024 * 1024 * 1024),
         }
     }
 }
 
-pub struct SpotifyPlayer {
+impl SpotifyPlayerSettings {
+    // Only these need a new player. The bitrate or normalization can wait for the next track,
+    // and the rest isn't up to the player at all
+    pub fn needs_restart(&self, new_settings: &Self) -> bool {
+        self.backend != new_settings.backend
+            || self.mixer != new_settings.mixer
+            || self.audio_format != new_settings.audio_format
+            || self.gapless != new_settings.gapless
+    }
+
+    // The access point and the cache are picked when connecting
+    pub fn needs_reconnect(&self, new_settings: &Self) -> bool {
+        self.ap_port != new_settings.ap_port
+            || self.proxy != new_settings.proxy
+            || self.cache_audio != new_settings.cache_audio
+            || self.audio_cache_limit != new_settings.audio_cache_limit
+    }
+}
+
+// Sets the volume of whatever mixer is in use
+pub type VolumeControl = Arc<dyn Fn(f64) + Send + Sync>;
+
+// A fade still going on
+struct Fade {
+    task: JoinHandle<()>,
+    kind: FadeKind,
+}
+
+enum FadeKind {
+    // When resuming
+    In,
+    // Before pausing
+    Out,
+    // Out and back in, seeking to that position in between
+    Seek(u32),
+}
+
+// In small steps, that's smooth enough
+async fn ramp_volume(
+    set_volume: &(dyn Fn(f64) + Send + Sync),
+    from: f64,
+    to: f64,
+    duration: Duration,
+) {
+    const STEPS: u32 = 20;
+    for step in 1..=STEPS {
+        tokio::time::sleep(duration / STEPS).await;
+        set_volume(from + (to - from) * step as f64 / STEPS as f64);
+    }
+}
+
+// Waits 1s, 2s, 4s... between attempts, up to this
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(16);
+const MAX_RECONNECT_ATTEMPTS: u32 = 5;
+// Between failed attempts at refreshing the token, doubling each time
+const REFRESH_RETRY_DELAY: Duration = Duration::from_secs(5);
+const MAX_REFRESH_RETRY_DELAY: Duration = Duration::from_secs(5 * 60);
+// How often we try restoring the session again while offline
+const OFFLINE_RETRY_DELAY: Duration = Duration::from_secs(30);
+// A track failing to load this close to the token expiring is most likely down to the token
+const TOKEN_EXPIRY_MARGIN: Duration = Duration::from_secs(60);
+
+// What the player was last told to play, so that a new player can pick up from there
+struct LoadedTrack {
+    track: SpotifyUri,
+    position_ms: u32,
+    // Since when it's been playing, if it is
+    resumed_at: Option<Instant>,
+    // Loading it already failed once
+    retried: bool,
+}
+
+impl LoadedTrack {
+    fn new(track: SpotifyUri, resume: bool, position_ms: u32) -> Self {
+        Self {
+            track,
+            position_ms,
+            resumed_at: resume.then(Instant::now),
+            retried: false,
+        }
+    }
+
+    // Close enough, whatever time was spent buffering is counted in
+    fn position_ms(&self) -> u32 {
+        let elapsed = self
+            .resumed_at
+            .map(|t| t.elapsed().as_millis() as u32)
+            .unwrap_or(0);
+        self.position_ms.saturating_add(elapsed)
+    }
+
+    fn resume(&mut self) {
+        self.resumed_at.get_or_insert_with(Instant::now);
+    }
+
+    fn pause(&mut self) {
+        self.position_ms = self.position_ms();
+        self.resumed_at = None;
+    }
+
+    fn seek(&mut self, position_ms: u32) {
+        self.position_ms = position_ms;
+        if self.resumed_at.is_some() {
+            self.resumed_at = Some(Instant::now());
+        }
+    }
+}
+
+// The backend, auth and delegate are only swapped in tests
+pub struct SpotifyPlayer<
+    B: PlayerBackend = LibrespotBackend,
+    A: SpotifyAuth = RiffOauthClient,
+    D: SpotifyPlayerDelegate = AppPlayerDelegate,
+> {
     settings: SpotifyPlayerSettings,
-    player: Option<Arc<Player>>,
-    mixer: Option<Box<dyn Mixer>>,
-    session: Option<Session>,
+    backend: B,
+    player: Option<B::Player>,
+    session: Option<B::Session>,
+    loaded: Option<LoadedTrack>,
+    fade: Option<Fade>,
+    gapless_override: Option<bool>,
+    is_metered: bool,
+    // The bitrate or normalization to use has changed, the player will be recreated on the next load
+    config_outdated: bool,
+    // Restoring the session failed for lack of network
+    offline: bool,
+    // Tries again later, the network monitor doesn't know whether Spotify is reachable
+    offline_retry: Option<JoinHandle<()>>,
 
     // Auth related stuff
-    oauth_client: Arc<RiffOauthClient>,
+    oauth_client: Arc<A>,
     auth_challenge: Option<AuthcodeChallenge>,
+    // Bumped with every challenge, to tell which one the listener's notification is about
+    login_attempt: u64,
+    token_refresh: Option<JoinHandle<()>>,
     command_sender: UnboundedSender<Command>,
 
     // Receives feedback from commands or various events in the player
-    delegate: AppPlayerDelegate,
+    delegate: D,
 }
 
 impl SpotifyPlayer {
     pub fn new(
         settings: SpotifyPlayerSettings,
         delegate: AppPlayerDelegate,
-        token_store: TokenStore,
+        oauth_client: Arc<RiffOauthClient>,
+        command_sender: UnboundedSender<Command>,
+    ) -> Self {
+        Self::with_backend(
+            settings,
+            LibrespotBackend::default(),
+            oauth_client,
+            delegate,
+            command_sender,
+        )
+    }
+}
+
+impl<B, A, D> SpotifyPlayer<B, A, D>
+where
+    B: PlayerBackend,
+    A: SpotifyAuth,
+    D: SpotifyPlayerDelegate,
+{
+    pub fn with_backend(
+        settings: SpotifyPlayerSettings,
+        backend: B,
+        oauth_client: Arc<A>,
+        delegate: D,
         command_sender: UnboundedSender<Command>,
     ) -> Self {
         Self {
             settings,
-            mixer: None,
+            backend,
             player: None,
             session: None,
-            oauth_client: Arc::new(RiffOauthClient::new(token_store)),
+            loaded: None,
+            fade: None,
+            gapless_override: None,
+            is_metered: false,
+            config_outdated: false,
+            offline: false,
+            offline_retry: None,
+            oauth_client,
             auth_challenge: None,
+            login_attempt: 0,
+            token_refresh: None,
             command_sender,
             delegate,
         }
@@ -135,211 +406,688 @@ impl SpotifyPlayer {
         }
     }
 
-    fn get_player(&self) -> Result<&Arc<Player>, SpotifyError> {
+    fn get_player(&self) -> Result<&B::Player, SpotifyError> {
         self.player.as_ref().ok_or(SpotifyError::PlayerNotReady)
     }
 
-    fn get_player_mut(&mut self) -> Result<&mut Arc<Player>, SpotifyError> {
-        self.player.as_mut().ok_or(SpotifyError::PlayerNotReady)
-    }
-
     async fn handle(&mut self, action: Command) -> Result<(), SpotifyError> {
         match action {
             Command::PlayerSetVolume(volume) => {
-                if let Some(mixer) = self.mixer.as_mut() {
-                    mixer_set_volume(&mut **mixer, volume);
-                }
+                // There might not be a mixer yet, this is what it'll start with
+                self.finish_fade().await;
+                self.settings.volume = volume;
+                self.backend.set_volume(volume);
                 Ok(())
             }
             Command::PlayerResume => {
-                self.get_player()?.play();
+                self.ensure_connected().await?;
+                let player = self.get_player()?.clone();
+                // If it was still fading out, it just never pauses
+                self.cancel_fade().await;
+                match self.fade_control() {
+                    Some(set_volume) => {
+                        let volume = self.settings.volume;
+                        let duration = self.settings.fade_duration;
+                        set_volume(0.0);
+                        player.play();
+                        let task = tokio::task::spawn(async move {
+                            ramp_volume(&*set_volume, 0.0, volume, duration).await;
+                        });
+                        self.fade = Some(Fade {
+                            task,
+                            kind: FadeKind::In,
+                        });
+                    }
+                    None => player.play(),
+                }
+                if let Some(loaded) = self.loaded.as_mut() {
+                    loaded.resume();
+                }
                 Ok(())
             }
             Command::PlayerPause => {
-                self.get_player()?.pause();
+                let player = self.get_player()?.clone();
+                self.cancel_fade().await;
+                match self.fade_control() {
+                    Some(set_volume) => {
+                        let volume = self.settings.volume;
+                        let duration = self.settings.fade_duration;
+                        let task = tokio::task::spawn(async move {
+                            ramp_volume(&*set_volume, volume, 0.0, duration).await;
+                            player.pause();
+                            set_volume(volume);
+                        });
+                        self.fade = Some(Fade {
+                            task,
+                            kind: FadeKind::Out,
+                        });
+                    }
+                    None => player.pause(),
+                }
+                if let Some(loaded) = self.loaded.as_mut() {
+                    loaded.pause();
+                }
                 Ok(())
             }
             Command::PlayerStop => {
+                self.cancel_fade().await;
                 self.get_player()?.stop();
+                self.loaded = None;
                 Ok(())
             }
             Command::PlayerSeek(position) => {
-                self.get_player()?.seek(position);
+                self.finish_fade().await;
+                let player = self.get_player()?.clone();
+                let playing = self
+                    .loaded
+                    .as_ref()
+                    .is_some_and(|loaded| loaded.resumed_at.is_some());
+                match self.fade_control().filter(|_| playing) {
+                    Some(set_volume) => {
+                        let volume = self.settings.volume;
+                        // Half of it each way, so that it doesn't take longer than pausing
+                        let duration = self.settings.fade_duration / 2;
+                        let task = tokio::task::spawn(async move {
+                            ramp_volume(&*set_volume, volume, 0.0, duration).await;
+                            player.seek(position);
+                            ramp_volume(&*set_volume, 0.0, volume, duration).await;
+                        });
+                        self.fade = Some(Fade {
+                            task,
+                            kind: FadeKind::Seek(position),
+                        });
+                    }
+                    None => player.seek(position),
+                }
+                if let Some(loaded) = self.loaded.as_mut() {
+                    loaded.seek(position);
+                }
                 Ok(())
             }
-            Command::PlayerLoad { track, resume } => {
+            Command::PlayerLoad {
+                track,
+                resume,
+                position_ms,
+            } => {
                 debug!("Player: playing track {track}");
-                self.get_player_mut()?.load(track, resume, 0);
+                // It's about to be replaced, no need to load it again if we have to reconnect
+                self.loaded = None;
+                self.cancel_fade().await;
+                self.ensure_connected().await?;
+                if self.config_outdated {
+                    info!("Recreating player, bitrate: {:?}", self.bitrate());
+                    self.recreate_player()?;
+                }
+                self.get_player()?.load(track.clone(), resume, position_ms);
+                self.loaded = Some(LoadedTrack::new(track, resume, position_ms));
                 Ok(())
             }
             Command::PlayerPreload(track) => {
-                self.get_player_mut()?.preload(track);
+                self.ensure_connected().await?;
+                self.get_player()?.preload(track);
                 Ok(())
             }
-            Command::RefreshToken => {
-                let session = self.session.as_ref().ok_or(SpotifyError::PlayerNotReady)?;
-                let token = self
+            Command::PlayerLoadFailed(track) => {
+                // Another track was loaded since, that failure is none of our business anymore
+                if self.loaded.as_ref().map(|loaded| &loaded.track) != Some(&track) {
+                    debug!("Ignoring load failure of {track}");
+                    return Ok(());
+                }
+                let token_expiring = self
                     .oauth_client
-                    .get_valid_token()
-                    .await
-                    .map_err(|_| SpotifyError::LoginFailed)?;
-                let credentials = Credentials::with_access_token(token.access_token.clone());
-                session
-                    .connect(credentials, true)
+                    .token_store()
+                    .get_cached()
                     .await
-                    .map_err(|_| SpotifyError::LoginFailed)?;
+                    .map(|token| token_expires_soon(&token))
+                    .unwrap_or(false);
+                let Some(loaded) = self
+                    .loaded
+                    .as_mut()
+                    .filter(|loaded| token_expiring && !loaded.retried)
+                else {
+                    // Nothing to do about it, the track can't be played
+                    if let Some(id) = track_id(&track) {
+                        self.delegate.track_unavailable(id);
+                    }
+                    return Ok(());
+                };
+                loaded.retried = true;
+                let track = loaded.track.clone();
+                let resume = loaded.resumed_at.is_some();
+                let position_ms = loaded.position_ms();
+
+                info!("Could not load {track}, retrying with a fresh token");
+                self.refresh_session().await?;
+                self.get_player()?.load(track, resume, position_ms);
+                Ok(())
+            }
+            Command::SetGaplessOverride(gapless_override) => {
+                let was_gapless = self.is_gapless();
+                self.gapless_override = gapless_override;
+                // Gapless can only be set when creating the player
+                if self.is_gapless() != was_gapless && self.player.is_some() {
+                    info!("Recreating player, gapless: {}", self.is_gapless());
+                    self.restart_player()?;
+                }
+                Ok(())
+            }
+            Command::SetMeteredConnection(is_metered) => {
+                let bitrate = self.bitrate();
+                self.is_metered = is_metered;
+                if self.player.is_some() && self.bitrate() != bitrate {
+                    self.config_outdated = true;
+                }
+                Ok(())
+            }
+            Command::RefreshToken => {
+                let session = self.session.clone().ok_or(SpotifyError::PlayerNotReady)?;
+                let token = match self.oauth_client.get_valid_token().await {
+                    Ok(token) => token,
+                    // No reason to log out, the token will be refreshed once we can reach Spotify
+                    Err(e) if e.is_transient() => {
+                        self.delegate.session_reconnecting(1);
+                        return Ok(());
+                    }
+                    Err(e) => return Err(SpotifyError::AuthFailed(e)),
+                };
+                session.reconnect(token.access_token).await?;
+                // The refresh loop was still waiting for the previous token to expire
+                self.spawn_token_refresh(session);
                 self.delegate.refresh_successful();
                 Ok(())
             }
             Command::Logout => {
-                self.session
-                    .take()
-                    .ok_or(SpotifyError::PlayerNotReady)?
-                    .shutdown();
-                let _ = self.player.take();
+                // Also covers the case where we got logged out without going through the user menu
+                self.set_online();
+                let had_session = self.drop_session().await;
+                let token_store = self.oauth_client.token_store();
+                token_store.clear().await;
+                self.delegate
+                    .accounts_changed(token_store.list_accounts().await);
+                if had_session {
+                    Ok(())
+                } else {
+                    Err(SpotifyError::PlayerNotReady)
+                }
+            }
+            Command::AddAccount => {
+                self.drop_session().await;
+                self.oauth_client.token_store().put_aside().await;
                 Ok(())
             }
-            Command::Restore => {
+            Command::SwitchAccount(username) => {
+                // Adding an account might have been given up halfway
+                self.cancel_login().await;
+                self.oauth_client
+                    .token_store()
+                    .switch_account(&username)
+                    .await
+                    .ok_or(SpotifyError::LoginFailed)?;
+                self.drop_session().await;
                 let credentials =
                     self.oauth_client
                         .get_valid_token()
                         .await
                         .map_err(|e| match e {
                             OAuthError::LoggedOut => SpotifyError::LoggedOut,
-                            _ => SpotifyError::LoginFailed,
+                            e => SpotifyError::AuthFailed(e),
                         })?;
 
-                info!("Restoring session");
+                info!("Switching to another account");
                 self.initial_login(credentials).await
             }
+            Command::Restore => self.restore().await,
             Command::InitLogin => {
-                let auth_url = match self.auth_challenge.as_ref() {
-                    Some(challenge) => challenge.auth_url.clone(),
-                    None => {
-                        let cmd = self.command_sender.clone();
-                        let challenge = self
-                            .oauth_client
-                            .spawn_authcode_listener(move || {
-                                cmd.unbounded_send(Command::CompleteLogin).unwrap();
-                            })
-                            .await
-                            .map_err(|_| SpotifyError::LoginFailed)?;
-                        let auth_url = challenge.auth_url.clone();
-                        self.auth_challenge = Some(challenge);
-                        auth_url
-                    }
-                };
+                // The previous attempt might have been abandoned, its listener holds the port
+                self.cancel_login().await;
+                self.login_attempt += 1;
+                let attempt = self.login_attempt;
+                let cmd = self.command_sender.clone();
+                let challenge = self
+                    .oauth_client
+                    .spawn_authcode_listener(
+                        self.settings.redirect_port,
+                        Box::new(move || {
+                            cmd.unbounded_send(Command::LoginListenerDone(attempt))
+                                .unwrap();
+                        }),
+                    )
+                    .await
+                    .map_err(SpotifyError::AuthFailed)?;
+                let auth_url = challenge.auth_url.clone();
+                self.auth_challenge = Some(challenge);
                 self.delegate.login_challenge_started(auth_url);
                 Ok(())
             }
-            Command::CompleteLogin => {
-                let Some(challenge) = self.auth_challenge.take() else {
-                    return Err(SpotifyError::LoginFailed);
-                };
+            Command::CancelLogin => {
+                self.cancel_login().await;
+                Ok(())
+            }
+            Command::CompleteLogin => self.complete_login_from_listener().await,
+            Command::LoginListenerDone(attempt) => {
+                // About a challenge since cancelled or replaced
+                if attempt != self.login_attempt {
+                    return Ok(());
+                }
+                self.complete_login_from_listener().await
+            }
+            Command::CompleteLoginWithCode(pasted) => {
+                let challenge = self
+                    .auth_challenge
+                    .as_ref()
+                    .ok_or(SpotifyError::LoginFailed)?;
+                // The challenge is kept around, so that the user can try again
+                let code = challenge.pasted_code(&pasted).map_err(|e| match e {
+                    OAuthError::AuthorizationDenied { e } => SpotifyError::LoginRejected(e),
+                    e => {
+                        warn!("Invalid login code: {e}");
+                        SpotifyError::LoginCodeInvalid
+                    }
+                })?;
+                self.complete_login(code).await
+            }
+            Command::Reconnect => {
+                // That's what we were waiting for
+                if self.offline {
+                    return self.restore().await;
+                }
+                // Not logged in, nothing to get back
+                if self.session.is_none() {
+                    return Ok(());
+                }
+                self.reconnect_with_backoff().await
+            }
+            // Doesn't need a session, the files are ours
+            Command::ClearAudioCache => {
+                let freed = self.backend.clear_audio_cache().await;
+                self.delegate.audio_cache_cleared(freed);
+                Ok(())
+            }
+            Command::ReloadSettings => {
+                let settings = self.backend.load_settings();
+                let bitrate = self.bitrate();
+                let normalization = self.settings.normalization;
+                let normalization_type = self.settings.normalization_type;
+                let needs_reconnect = self.settings.needs_reconnect(&settings);
+                let needs_restart = self.settings.needs_restart(&settings);
+                self.settings = settings;
+                // It'll all be picked up when logging in
+                if self.session.is_none() {
+                    return Ok(());
+                }
+                if needs_reconnect {
+                    crate::app::session_health::record_player_restart();
+                    self.reconnect().await
+                } else if needs_restart {
+                    crate::app::session_health::record_player_restart();
+                    self.restart_player()
+                } else {
+                    let config_changed = self.bitrate() != bitrate
+                        || self.settings.normalization != normalization
+                        || self.settings.normalization_type != normalization_type;
+                    if self.player.is_some() && config_changed {
+                        self.config_outdated = true;
+                    }
+                    Ok(())
+                }
+            }
+        }
+    }
 
-                let credentials = self
-                    .oauth_client
-                    .exchange_authcode(challenge)
-                    .await
-                    .map_err(|_| SpotifyError::LoginFailed)?;
+    async fn cancel_login(&mut self) {
+        if let Some(challenge) = self.auth_challenge.take() {
+            info!("Cancelling pending login");
+            challenge.cancel().await;
+        }
+    }
 
-                info!("Login with OAuth2");
-                self.initial_login(credentials).await
+    async fn complete_login_from_listener(&mut self) -> Result<(), SpotifyError> {
+        // The code was pasted in the meantime
+        let Some(challenge) = self.auth_challenge.as_mut() else {
+            return Ok(());
+        };
+        let code = match challenge.listener_code().await {
+            Ok(code) => code,
+            // Pasting the code is still an option
+            Err(e @ OAuthError::AuthCodeListenerBind { .. }) => {
+                warn!("{e}");
+                return Ok(());
             }
-            Command::ReloadSettings => {
-                let settings = RiffSettings::new_from_gsettings().unwrap_or_default();
-                self.settings = settings.player_settings;
+            Err(e) => {
+                self.auth_challenge = None;
+                return Err(match e {
+                    OAuthError::AuthCodeListenerTimeout => SpotifyError::LoginTimedOut,
+                    OAuthError::AuthorizationDenied { e } => SpotifyError::LoginRejected(e),
+                    e => SpotifyError::AuthFailed(e),
+                });
+            }
+        };
+        self.complete_login(code).await
+    }
 
-                let session = self.session.take().ok_or(SpotifyError::PlayerNotReady)?;
-                let new_player = self.create_player(session);
-                tokio::task::spawn(player_setup_delegate(
-                    new_player.get_player_event_channel(),
-                    self.delegate.clone(),
-                ));
-                self.player.replace(new_player);
+    async fn complete_login(&mut self, code: AuthorizationCode) -> Result<(), SpotifyError> {
+        let challenge = self
+            .auth_challenge
+            .take()
+            .ok_or(SpotifyError::LoginFailed)?;
+        let credentials = self
+            .oauth_client
+            .exchange_authcode(challenge, code)
+            .await
+            .map_err(|e| match e {
+                // e.g. the redirect URI isn't registered for a custom client ID
+                OAuthError::ExchangeCode { e } => SpotifyError::LoginRejected(e),
+                e => SpotifyError::AuthFailed(e),
+            })?;
 
-                Ok(())
+        info!("Login with OAuth2");
+        self.initial_login(credentials).await
+    }
+
+    fn is_gapless(&self) -> bool {
+        self.gapless_override.unwrap_or(self.settings.gapless)
+    }
+
+    fn bitrate(&self) -> Bitrate {
+        self.settings.bitrate.for_connection(self.is_metered)
+    }
+
+    fn recreate_player(&mut self) -> Result<(), SpotifyError> {
+        let session = self.session.clone().ok_or(SpotifyError::PlayerNotReady)?;
+        let new_player = self.create_player(session)?;
+        self.set_player(new_player);
+        Ok(())
+    }
+
+    // None if fading is off, or the backend can't do it
+    fn fade_control(&self) -> Option<VolumeControl> {
+        if self.settings.fade_duration.is_zero() {
+            return None;
+        }
+        self.backend.volume_control()
+    }
+
+    // Puts the volume back where it was, returns whether a pause was cut short.
+    // A seek that was cut short still happens (possibly again, that's harmless)
+    async fn cancel_fade(&mut self) -> bool {
+        let Some(fade) = self.fade.take() else {
+            return false;
+        };
+        if fade.task.is_finished() {
+            return false;
+        }
+        fade.task.abort();
+        // Otherwise it could still be changing the volume after we put it back
+        let _ = fade.task.await;
+        self.backend.set_volume(self.settings.volume);
+        match fade.kind {
+            FadeKind::In => false,
+            FadeKind::Out => true,
+            FadeKind::Seek(position) => {
+                if let Some(player) = self.player.as_ref() {
+                    player.seek(position);
+                }
+                false
             }
         }
     }
 
-    async fn initial_login(
+    // Whatever happens next, what was fading out should still end up paused
+    async fn finish_fade(&mut self) {
+        if self.cancel_fade().await {
+            if let Some(player) = self.player.as_ref() {
+                player.pause();
+            }
+        }
+    }
+
+    // Unlike recreate_player, this carries on with whatever was playing
+    fn restart_player(&mut self) -> Result<(), SpotifyError> {
+        self.recreate_player()?;
+        if let Some(loaded) = self.loaded.as_ref() {
+            let resume = loaded.resumed_at.is_some();
+            self.get_player()?
+                .load(loaded.track.clone(), resume, loaded.position_ms());
+        }
+        Ok(())
+    }
+
+    fn set_player(&mut self, new_player: B::Player) {
+        tokio::task::spawn(player_setup_delegate(
+            new_player.event_channel(),
+            self.delegate.clone(),
+            self.command_sender.clone(),
+            self.is_gapless(),
+        ));
+        self.player.replace(new_player);
+    }
+
+    async fn connect(
         &mut self,
         credentials: credentials::Credentials,
-    ) -> Result<(), SpotifyError> {
-        let creds = Credentials::with_access_token(&credentials.access_token);
-        let new_session = create_session(&creds, self.settings.ap_port).await?;
-        let username = new_session.username();
+    ) -> Result<B::Session, SpotifyError> {
+        let new_session = self
+            .backend
+            .connect(credentials.access_token, &self.settings)
+            .await?;
 
+        self.spawn_token_refresh(new_session.clone());
+        Ok(new_session)
+    }
+
+    // Keeps the session's token fresh, replacing whatever was refreshing it until now
+    fn spawn_token_refresh(&mut self, session: B::Session) {
         let oauth_client = Arc::clone(&self.oauth_client);
-        let session = new_session.clone();
-        tokio::task::spawn(async move {
+        let delegate = self.delegate.clone();
+        let token_refresh = tokio::task::spawn(async move {
+            let mut failures = 0;
+            let mut delay = REFRESH_RETRY_DELAY;
             loop {
-                if let Ok(token) = oauth_client.refresh_token_at_expiry().await {
-                    _ = session
-                        .connect(Credentials::with_access_token(token.access_token), true)
-                        .await;
+                match oauth_client.refresh_token_at_expiry().await {
+                    Ok(token) => {
+                        failures = 0;
+                        delay = REFRESH_RETRY_DELAY;
+                        _ = session.reconnect(token.access_token).await;
+                    }
+                    Err(e) if e.is_transient() => {
+                        failures += 1;
+                        warn!("Could not refresh the token (attempt {failures}): {e}");
+                        delegate.session_reconnecting(failures);
+                        tokio::time::sleep(delay).await;
+                        delay = (delay * 2).min(MAX_REFRESH_RETRY_DELAY);
+                    }
+                    // Nothing left to refresh, no point in spinning
+                    Err(e) => {
+                        warn!("Stopped refreshing the token: {e}");
+                        break;
+                    }
                 }
             }
         });
+        // Whatever session (or token) it was refreshing is gone
+        if let Some(previous) = self.token_refresh.replace(token_refresh) {
+            previous.abort();
+        }
+    }
 
-        let new_player = self.create_player(new_session.clone());
-        tokio::task::spawn(player_setup_delegate(
-            new_player.get_player_event_channel(),
-            self.delegate.clone(),
-        ));
+    async fn initial_login(
+        &mut self,
+        credentials: credentials::Credentials,
+    ) -> Result<(), SpotifyError> {
+        let new_session = self.connect(credentials).await?;
+        let username = new_session.username();
+        let token_store = self.oauth_client.token_store();
+        token_store.remember_account(&username).await;
+        self.delegate
+            .accounts_changed(token_store.list_accounts().await);
 
-        self.player.replace(new_player);
+        let new_player = self.create_player(new_session.clone());
         self.session.replace(new_session);
         self.delegate.token_login_successful(username);
 
+        // We're logged in even if there's no sound, picking another backend will recreate the player
+        self.set_player(new_player?);
         Ok(())
     }
 
-    fn create_player(&mut self, session: Session) -> Arc<Player> {
-        let backend = self.settings.backend.clone();
+    // Without a network, there's no point showing the login window: the app keeps going with what's cached
+    async fn restore(&mut self) -> Result<(), SpotifyError> {
+        let result = self.login_with_saved_token().await;
+        match result {
+            Err(e) if e.is_network_error() => {
+                warn!("Could not restore the session, going offline: {e}");
+                self.offline = true;
+                self.delegate.went_offline();
+                self.schedule_offline_retry();
+                Ok(())
+            }
+            result => {
+                self.set_online();
+                result
+            }
+        }
+    }
 
+    // e.g. behind a captive portal, the network is there but Spotify isn't
+    fn schedule_offline_retry(&mut self) {
+        let command_sender = self.command_sender.clone();
+        let retry = tokio::task::spawn(async move {
+            tokio::time::sleep(OFFLINE_RETRY_DELAY).await;
+            let _ = command_sender.unbounded_send(Command::Reconnect);
+        });
+        if let Some(previous) = self.offline_retry.replace(retry) {
+            previous.abort();
+        }
+    }
+
+    fn set_online(&mut self) {
+        self.offline = false;
+        if let Some(retry) = self.offline_retry.take() {
+            retry.abort();
+        }
+    }
+
+    async fn login_with_saved_token(&mut self) -> Result<(), SpotifyError> {
+        let credentials = self
+            .oauth_client
+            .get_valid_token()
+            .await
+            .map_err(|e| match e {
+                OAuthError::LoggedOut => SpotifyError::LoggedOut,
+                e => SpotifyError::AuthFailed(e),
+            })?;
+
+        info!("Restoring session");
+        self.initial_login(credentials).await
+    }
+
+    // Returns whether there was a session to drop
+    async fn drop_session(&mut self) -> bool {
+        let session = self.session.take();
+        let had_session = session.is_some();
+        self.cancel_fade().await;
+        let _ = self.player.take();
+        self.loaded = None;
+        if let Some(token_refresh) = self.token_refresh.take() {
+            token_refresh.abort();
+        }
+        self.backend.logout(session).await;
+        had_session
+    }
+
+    // Same user, new session (and player), without interrupting playback for longer than needed
+    async fn reconnect(&mut self) -> Result<(), SpotifyError> {
+        self.replace_session().await?;
+        self.restart_player()
+    }
+
+    async fn replace_session(&mut self) -> Result<(), SpotifyError> {
+        let credentials = self
+            .oauth_client
+            .get_valid_token()
+            .await
+            .map_err(|_| SpotifyError::LoginFailed)?;
+        let new_session = self.connect(credentials).await?;
+        if let Some(previous) = self.session.replace(new_session) {
+            previous.shutdown();
+        }
+        Ok(())
+    }
+
+    // Same session, with a token we know won't expire right away
+    async fn refresh_session(&mut self) -> Result<(), SpotifyError> {
+        let session = self.session.clone().ok_or(SpotifyError::PlayerNotReady)?;
+        let mut token = self
+            .oauth_client
+            .get_valid_token()
+            .await
+            .map_err(SpotifyError::AuthFailed)?;
+        if token_expires_soon(&token) {
+            token = self
+                .oauth_client
+                .refresh_token(token)
+                .await
+                .map_err(SpotifyError::AuthFailed)?;
+        }
+        session.reconnect(token.access_token).await?;
+        self.spawn_token_refresh(session);
+        Ok(())
+    }
+
+    // The network might take a moment to come back (e.g. after a suspend)
+    async fn reconnect_with_backoff(&mut self) -> Result<(), SpotifyError> {
+        let mut delay = Duration::from_secs(1);
+        for attempt in 1..=MAX_RECONNECT_ATTEMPTS {
+            self.delegate.session_reconnecting(attempt);
+            match self.replace_session().await {
+                Ok(_) => {
+                    info!("Reconnected after {attempt} attempt(s)");
+                    crate::app::session_health::record_player_restart();
+                    return self.restart_player();
+                }
+                Err(e) => warn!("Could not reconnect (attempt {attempt}): {e}"),
+            }
+            if attempt < MAX_RECONNECT_ATTEMPTS {
+                tokio::time::sleep(delay).await;
+                delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+            }
+        }
+        Err(SpotifyError::TechnicalError)
+    }
+
+    // A dead session doesn't make the player fail, it just never plays anything
+    async fn ensure_connected(&mut self) -> Result<(), SpotifyError> {
+        match self.session.as_ref() {
+            Some(session) if session.is_invalid() => {
+                warn!("Session is gone, reconnecting");
+                self.reconnect_with_backoff().await
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn create_player(&mut self, session: B::Session) -> Result<B::Player, SpotifyError> {
         let player_config = PlayerConfig {
-            gapless: self.settings.gapless,
-            bitrate: self.settings.bitrate,
+            gapless: self.is_gapless(),
+            bitrate: self.bitrate(),
+            normalisation: self.settings.normalization.is_some(),
+            normalisation_pregain_db: self.settings.normalization.unwrap_or_default(),
+            normalisation_type: self.settings.normalization_type,
             ..Default::default()
         };
+        self.config_outdated = false;
         info!("bitrate: {:?}", &player_config.bitrate);
 
-        let volume = self.settings.volume;
-        let soft_volume = self
-            .mixer
-            .get_or_insert_with(|| {
-                let mut mix = Box::new(
-                    SoftMixer::open(MixerConfig {
-                        // This value feels reasonable to me. Feel free to change it
-                        volume_ctrl: VolumeCtrl::Log(VolumeCtrl::DEFAULT_DB_RANGE / 2.0),
-                        ..Default::default()
-                    })
-                    .expect("Failed to create soft mixer"),
-                );
-                mixer_set_volume(&mut *mix, volume);
-                mix
-            })
-            .get_soft_volume();
+        // Not worth failing over, we can still use the soft mixer
+        if let Err(e) = self
+            .backend
+            .set_mixer(&self.settings.mixer, self.settings.volume)
+        {
+            self.delegate.report_error(e);
+        }
 
-        Player::new(player_config, session, soft_volume, move || match backend {
-            AudioBackend::GStreamer(pipeline) => {
-                let backend = audio_backend::find(Some("gstreamer".to_string())).unwrap();
-                backend(Some(pipeline), AudioFormat::default())
-            }
-            AudioBackend::PulseAudio => {
-                info!("using pulseaudio");
-                env::set_var("PULSE_PROP_application.name", "Riff");
-                let backend = audio_backend::find(Some("pulseaudio".to_string())).unwrap();
-                backend(None, AudioFormat::default())
-            }
-            AudioBackend::Alsa(device) => {
-                info!("using alsa ({})", &device);
-                let backend = audio_backend::find(Some("alsa".to_string())).unwrap();
-                backend(Some(device), AudioFormat::default())
-            }
-        })
+        self.backend
+            .create_player(session, player_config, &self.settings)
     }
 
     pub async fn start(self, receiver: UnboundedReceiver<Command>) -> Result<(), ()> {
@@ -353,65 +1101,59 @@ impl SpotifyPlayer {
     }
 }
 
-const KNOWN_AP_PORTS: [Option<u16>; 4] = [None, Some(80), Some(443), Some(4070)];
-
-async fn create_session_with_port(
-    credentials: &Credentials,
-    ap_port: Option<u16>,
-) -> Result<Session, SpotifyError> {
-    let session_config = SessionConfig {
-        ap_port,
-        ..Default::default()
-    };
-    let root = glib::user_cache_dir().join("riff").join("librespot");
-    let cache = Cache::new(
-        Some(root.join("credentials")),
-        Some(root.join("volume")),
-        Some(root.join("audio")),
-        None,
-    )
-    .map_err(|e| dbg!(e))
-    .ok();
-    let session = Session::new(session_config, cache);
-    match session.connect(credentials.clone(), true).await {
-        Ok(_) => Ok(session),
-        Err(err) => {
-            warn!("Login failure: {}", err);
-            Err(SpotifyError::LoginFailed)
-        }
+// The id the app knows the track by
+fn track_id(track: &SpotifyUri) -> Option<String> {
+    match track {
+        SpotifyUri::Track { id } | SpotifyUri::Episode { id } => id.to_base62().ok(),
+        _ => None,
     }
 }
 
-async fn create_session(
-    credentials: &Credentials,
-    ap_port: Option<u16>,
-) -> Result<Session, SpotifyError> {
-    match ap_port {
-        Some(_) => create_session_with_port(credentials, ap_port).await,
-        None => {
-            let mut ports_to_try = KNOWN_AP_PORTS.iter();
-            loop {
-                if let Some(next_port) = ports_to_try.next() {
-                    let res = create_session_with_port(credentials, *next_port).await;
-                    match res {
-                        Err(SpotifyError::TechnicalError) => continue,
-                        _ => break res,
-                    }
-                } else {
-                    break Err(SpotifyError::TechnicalError);
-                }
-            }
-        }
+fn token_expires_soon(token: &credentials::Credentials) -> bool {
+    match token.token_expiry_time {
+        Some(expiry) => SystemTime::now() + TOKEN_EXPIRY_MARGIN > expiry,
+        None => true,
     }
 }
 
-async fn player_setup_delegate(mut channel: PlayerEventChannel, delegate: AppPlayerDelegate) {
+async fn player_setup_delegate<D: SpotifyPlayerDelegate>(
+    mut channel: PlayerEventChannel,
+    delegate: D,
+    command_sender: UnboundedSender<Command>,
+    gapless: bool,
+) {
+    // To help debugging clicks and gaps between tracks
+    let mut track_ended_at: Option<Instant> = None;
+    // Preloading the next track can fail too, but that's none of our business yet
+    let mut loading = false;
     while let Some(event) = channel.recv().await {
         match event {
+            PlayerEvent::Loading { .. } => {
+                loading = true;
+                delegate.buffering_changed(true);
+            }
             PlayerEvent::EndOfTrack { .. } => {
+                track_ended_at = Some(Instant::now());
+                delegate.buffering_changed(false);
                 delegate.end_of_track_reached();
             }
+            // Whatever happens to the track being loaded, we're done waiting for it
+            PlayerEvent::Paused { .. } | PlayerEvent::Stopped { .. } => {
+                loading = false;
+                delegate.buffering_changed(false);
+            }
+            // The player decides whether it's worth trying again
+            PlayerEvent::Unavailable { track_id, .. } if loading => {
+                loading = false;
+                delegate.buffering_changed(false);
+                let _ = command_sender.unbounded_send(Command::PlayerLoadFailed(track_id));
+            }
             PlayerEvent::Playing { position_ms, .. } => {
+                loading = false;
+                delegate.buffering_changed(false);
+                if let Some(ended_at) = track_ended_at.take() {
+                    record_transition(ended_at.elapsed(), gapless);
+                }
                 delegate.notify_playback_state(position_ms);
             }
             PlayerEvent::TimeToPreloadNextTrack { .. } => {
@@ -423,6 +1165,820 @@ async fn player_setup_delegate(mut channel: PlayerEventChannel, delegate: AppPla
     }
 }
 
-fn mixer_set_volume(mixer: &mut dyn Mixer, volume: f64) {
-    mixer.set_volume((VolumeCtrl::MAX_VOLUME as f64 * volume) as u16);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::player::TokenStore;
+    use futures::channel::mpsc::unbounded;
+    use futures::future::BoxFuture;
+    use librespot::core::{SpotifyId, SpotifyUri};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Mutex;
+    use std::time::SystemTime;
+
+    // Everything the fakes are asked to do, in order
+    #[derive(Debug, Clone, PartialEq)]
+    enum Call {
+        RefreshToken,
+        Connect,
+        Reconnect,
+        CreatePlayer { gapless: bool, volume: f64 },
+        Load { resume: bool, position_ms: u32 },
+        Play,
+        Pause,
+        Seek(u32),
+        Shutdown,
+        LoginSuccessful(String),
+        RefreshSuccessful,
+        TrackUnavailable(String),
+        WentOffline,
+        Error(String),
+    }
+
+    type CallLog = Arc<Mutex<Vec<Call>>>;
+
+    fn record(log: &CallLog, call: Call) {
+        log.lock().unwrap().push(call);
+    }
+
+    fn calls(log: &CallLog) -> Vec<Call> {
+        log.lock().unwrap().clone()
+    }
+
+    #[derive(Clone)]
+    struct RecordingDelegate(CallLog);
+
+    impl SpotifyPlayerDelegate for RecordingDelegate {
+        fn end_of_track_reached(&self) {}
+        fn track_unavailable(&self, id: String) {
+            record(&self.0, Call::TrackUnavailable(id));
+        }
+        fn login_challenge_started(&self, _url: Url) {}
+        fn token_login_successful(&self, username: String) {
+            record(&self.0, Call::LoginSuccessful(username));
+        }
+        fn refresh_successful(&self) {
+            record(&self.0, Call::RefreshSuccessful);
+        }
+        fn report_error(&self, error: SpotifyError) {
+            record(&self.0, Call::Error(error.to_string()));
+        }
+        fn notify_playback_state(&self, _position: u32) {}
+        fn preload_next_track(&self) {}
+        fn buffering_changed(&self, _buffering: bool) {}
+        fn session_reconnecting(&self, _attempt: u32) {}
+        fn audio_cache_cleared(&self, _freed: u64) {}
+        fn accounts_changed(&self, _usernames: Vec<String>) {}
+        fn went_offline(&self) {
+            record(&self.0, Call::WentOffline);
+        }
+    }
+
+    struct FakeAuth {
+        store: TokenStore,
+        log: CallLog,
+        // Refreshing fails like it would without a network
+        offline: AtomicBool,
+    }
+
+    fn token(expires_in: Duration, expired: bool) -> credentials::Credentials {
+        let now = SystemTime::now();
+        credentials::Credentials {
+            access_token: "access".to_string(),
+            refresh_token: "refresh".to_string(),
+            token_expiry_time: Some(if expired {
+                now - expires_in
+            } else {
+                now + expires_in
+            }),
+            client_id: None,
+        }
+    }
+
+    impl SpotifyAuth for FakeAuth {
+        fn token_store(&self) -> &TokenStore {
+            &self.store
+        }
+
+        fn refresh_token(
+            &self,
+            _old_token: credentials::Credentials,
+        ) -> BoxFuture<'_, Result<credentials::Credentials, OAuthError>> {
+            Box::pin(async move {
+                record(&self.log, Call::RefreshToken);
+                if self.offline.load(Ordering::SeqCst) {
+                    return Err(OAuthError::RefreshFailed {
+                        e: "offline".to_string(),
+                    });
+                }
+                let new_token = token(Duration::from_secs(3600), false);
+                self.store.set(new_token.clone()).await;
+                Ok(new_token)
+            })
+        }
+
+        fn spawn_authcode_listener(
+            &self,
+            _redirect_port: u16,
+            _notify_complete: Box<dyn FnOnce() + Send>,
+        ) -> BoxFuture<'_, Result<AuthcodeChallenge, OAuthError>> {
+            Box::pin(async { Err(OAuthError::AuthCodeListenerTerminated) })
+        }
+
+        fn exchange_authcode(
+            &self,
+            _challenge: AuthcodeChallenge,
+            _code: AuthorizationCode,
+        ) -> BoxFuture<'_, Result<credentials::Credentials, OAuthError>> {
+            Box::pin(async { Err(OAuthError::AuthCodeListenerTerminated) })
+        }
+    }
+
+    #[derive(Clone)]
+    struct FakeSession {
+        log: CallLog,
+        invalid: Arc<AtomicBool>,
+    }
+
+    impl PlayerSession for FakeSession {
+        fn username(&self) -> String {
+            "fake_user".to_string()
+        }
+
+        fn reconnect(&self, _access_token: String) -> BoxFuture<'static, Result<(), SpotifyError>> {
+            record(&self.log, Call::Reconnect);
+            Box::pin(async { Ok(()) })
+        }
+
+        fn shutdown(&self) {
+            record(&self.log, Call::Shutdown);
+        }
+
+        fn is_invalid(&self) -> bool {
+            self.invalid.load(Ordering::SeqCst)
+        }
+    }
+
+    #[derive(Clone)]
+    struct FakePlayer(CallLog);
+
+    impl PlaybackHandle for FakePlayer {
+        fn play(&self) {
+            record(&self.0, Call::Play);
+        }
+        fn pause(&self) {
+            record(&self.0, Call::Pause);
+        }
+        fn stop(&self) {}
+        fn seek(&self, position_ms: u32) {
+            record(&self.0, Call::Seek(position_ms));
+        }
+        fn load(&self, _track: SpotifyUri, start_playing: bool, position_ms: u32) {
+            record(
+                &self.0,
+                Call::Load {
+                    resume: start_playing,
+                    position_ms,
+                },
+            );
+        }
+        fn preload(&self, _track: SpotifyUri) {}
+        fn event_channel(&self) -> PlayerEventChannel {
+            // Nothing will ever come out of it
+            tokio::sync::mpsc::unbounded_channel().1
+        }
+    }
+
+    struct FakeBackend {
+        log: CallLog,
+        // What ReloadSettings will find
+        settings: Arc<Mutex<SpotifyPlayerSettings>>,
+        audio_unavailable: bool,
+        // Whether it has a mixer that can fade
+        fades: bool,
+    }
+
+    impl PlayerBackend for FakeBackend {
+        type Session = FakeSession;
+        type Player = FakePlayer;
+
+        fn connect(
+            &self,
+            _access_token: String,
+            _settings: &SpotifyPlayerSettings,
+        ) -> BoxFuture<'static, Result<FakeSession, SpotifyError>> {
+            record(&self.log, Call::Connect);
+            let session = FakeSession {
+                log: Arc::clone(&self.log),
+                invalid: Arc::default(),
+            };
+            Box::pin(async move { Ok(session) })
+        }
+
+        fn create_player(
+            &mut self,
+            _session: FakeSession,
+            config: PlayerConfig,
+            settings: &SpotifyPlayerSettings,
+        ) -> Result<FakePlayer, SpotifyError> {
+            if self.audio_unavailable {
+                return Err(SpotifyError::AudioOutputUnavailable);
+            }
+            record(
+                &self.log,
+                Call::CreatePlayer {
+                    gapless: config.gapless,
+                    volume: settings.volume,
+                },
+            );
+            Ok(FakePlayer(Arc::clone(&self.log)))
+        }
+
+        fn set_volume(&mut self, _volume: f64) {}
+
+        fn volume_control(&self) -> Option<VolumeControl> {
+            self.fades.then(|| Arc::new(|_: f64| {}) as VolumeControl)
+        }
+
+        fn set_mixer(&mut self, _mixer: &AudioMixer, _volume: f64) -> Result<(), SpotifyError> {
+            Ok(())
+        }
+
+        fn logout(&mut self, session: Option<FakeSession>) -> BoxFuture<'static, ()> {
+            if session.is_some() {
+                record(&self.log, Call::Shutdown);
+            }
+            Box::pin(async {})
+        }
+
+        fn clear_audio_cache(&self) -> BoxFuture<'static, u64> {
+            Box::pin(async { 0 })
+        }
+
+        fn load_settings(&self) -> SpotifyPlayerSettings {
+            self.settings.lock().unwrap().clone()
+        }
+    }
+
+    struct Harness {
+        player: SpotifyPlayer<FakeBackend, FakeAuth, RecordingDelegate>,
+        log: CallLog,
+        settings: Arc<Mutex<SpotifyPlayerSettings>>,
+    }
+
+    impl Harness {
+        fn new(stored: Option<credentials::Credentials>) -> Self {
+            let log = CallLog::default();
+            let settings = Arc::new(Mutex::new(SpotifyPlayerSettings::default()));
+            let backend = FakeBackend {
+                log: Arc::clone(&log),
+                settings: Arc::clone(&settings),
+                audio_unavailable: false,
+                fades: false,
+            };
+            let auth = FakeAuth {
+                store: TokenStore::in_memory(stored),
+                log: Arc::clone(&log),
+                offline: AtomicBool::new(false),
+            };
+            let (command_sender, _) = unbounded();
+            let player = SpotifyPlayer::with_backend(
+                SpotifyPlayerSettings::default(),
+                backend,
+                Arc::new(auth),
+                RecordingDelegate(Arc::clone(&log)),
+                command_sender,
+            );
+            Self {
+                player,
+                log,
+                settings,
+            }
+        }
+
+        async fn logged_in() -> Self {
+            let mut harness = Self::new(Some(token(Duration::from_secs(3600), false)));
+            harness.run(Command::Restore).await;
+            harness.log.lock().unwrap().clear();
+            harness
+        }
+
+        // Goes through the same path as commands coming from the app
+        async fn run(&mut self, command: Command) {
+            self.player.handle_and_notify(command).await;
+        }
+    }
+
+    const TRACK_ID: &str = "4uLU6hMCjMI75M1A2tKUQC";
+
+    fn track() -> SpotifyUri {
+        SpotifyUri::Track {
+            id: SpotifyId::from_base62(TRACK_ID).unwrap(),
+        }
+    }
+
+    fn load_command() -> Command {
+        Command::PlayerLoad {
+            track: track(),
+            resume: true,
+            position_ms: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_restore_refreshes_expired_token_first() {
+        let mut harness = Harness::new(Some(token(Duration::from_secs(60), true)));
+        harness.run(Command::Restore).await;
+
+        assert_eq!(
+            calls(&harness.log),
+            vec![
+                Call::RefreshToken,
+                Call::Connect,
+                Call::CreatePlayer {
+                    gapless: true,
+                    volume: 0.7,
+                },
+                Call::LoginSuccessful("fake_user".to_string()),
+            ]
+        );
+        assert!(harness.player.session.is_some());
+        assert!(harness.player.player.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_restore_without_credentials() {
+        let mut harness = Harness::new(None);
+        harness.run(Command::Restore).await;
+
+        assert_eq!(
+            calls(&harness.log),
+            vec![Call::Error(SpotifyError::LoggedOut.to_string())]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_restore_offline_waits_for_network() {
+        let mut harness = Harness::new(Some(token(Duration::from_secs(60), true)));
+        let auth = Arc::clone(&harness.player.oauth_client);
+        auth.offline.store(true, Ordering::SeqCst);
+
+        harness.run(Command::Restore).await;
+        assert_eq!(
+            calls(&harness.log),
+            vec![Call::RefreshToken, Call::WentOffline]
+        );
+        assert!(harness.player.session.is_none());
+        assert!(auth.store.get_cached().await.is_some());
+        assert!(harness.player.offline_retry.is_some());
+
+        harness.log.lock().unwrap().clear();
+        auth.offline.store(false, Ordering::SeqCst);
+        harness.run(Command::Reconnect).await;
+        assert_eq!(
+            calls(&harness.log),
+            vec![
+                Call::RefreshToken,
+                Call::Connect,
+                Call::CreatePlayer {
+                    gapless: true,
+                    volume: 0.7,
+                },
+                Call::LoginSuccessful("fake_user".to_string()),
+            ]
+        );
+        assert!(harness.player.session.is_some());
+        assert!(harness.player.offline_retry.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_login_failure_keeps_the_reason() {
+        let mut harness = Harness::new(None);
+        harness.run(Command::InitLogin).await;
+
+        assert_eq!(
+            calls(&harness.log),
+            vec![Call::Error(
+                SpotifyError::AuthFailed(OAuthError::AuthCodeListenerTerminated).to_string()
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_volume_set_before_login() {
+        let mut harness = Harness::new(Some(token(Duration::from_secs(3600), false)));
+        harness.run(Command::PlayerSetVolume(0.35)).await;
+        harness.run(Command::Restore).await;
+
+        assert!(calls(&harness.log).contains(&Call::CreatePlayer {
+            gapless: true,
+            volume: 0.35,
+        }));
+    }
+
+    #[tokio::test]
+    async fn test_login_without_audio_output() {
+        let mut harness = Harness::new(Some(token(Duration::from_secs(3600), false)));
+        harness.player.backend.audio_unavailable = true;
+        harness.run(Command::Restore).await;
+
+        assert_eq!(
+            calls(&harness.log),
+            vec![
+                Call::Connect,
+                Call::LoginSuccessful("fake_user".to_string()),
+                Call::Error(SpotifyError::AudioOutputUnavailable.to_string()),
+            ]
+        );
+        assert!(harness.player.player.is_none());
+
+        // Picking another backend fixes it
+        harness.player.backend.audio_unavailable = false;
+        harness.settings.lock().unwrap().backend = AudioBackend::Alsa("default".to_string());
+        harness.run(Command::ReloadSettings).await;
+        assert!(harness.player.player.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_logout_drops_player_and_session() {
+        let mut harness = Harness::logged_in().await;
+        harness.run(Command::Logout).await;
+
+        assert_eq!(calls(&harness.log), vec![Call::Shutdown]);
+        assert!(harness.player.session.is_none());
+        assert!(harness.player.player.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_logout_forgets_credentials() {
+        let mut harness = Harness::logged_in().await;
+        harness.run(Command::Logout).await;
+
+        let token_store = harness.player.oauth_client.token_store();
+        assert!(token_store.get().await.is_none());
+        assert!(token_store.list_accounts().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_add_and_switch_account() {
+        let mut harness = Harness::logged_in().await;
+        harness.run(Command::AddAccount).await;
+
+        assert_eq!(calls(&harness.log), vec![Call::Shutdown]);
+        assert!(harness.player.session.is_none());
+        let token_store = harness.player.oauth_client.token_store();
+        assert!(token_store.get().await.is_none());
+        assert_eq!(token_store.list_accounts().await, vec!["fake_user"]);
+
+        harness.log.lock().unwrap().clear();
+        harness
+            .run(Command::SwitchAccount("fake_user".to_string()))
+            .await;
+        assert_eq!(
+            calls(&harness.log),
+            vec![
+                Call::Connect,
+                Call::CreatePlayer {
+                    gapless: true,
+                    volume: 0.7,
+                },
+                Call::LoginSuccessful("fake_user".to_string()),
+            ]
+        );
+
+        harness.log.lock().unwrap().clear();
+        harness
+            .run(Command::SwitchAccount("someone_else".to_string()))
+            .await;
+        assert_eq!(
+            calls(&harness.log),
+            vec![Call::Error(SpotifyError::LoginFailed.to_string())]
+        );
+        assert!(harness.player.session.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_commands_before_login() {
+        let mut harness = Harness::new(None);
+        let commands = [
+            Command::PlayerResume,
+            load_command(),
+            Command::RefreshToken,
+            Command::Logout,
+        ];
+        for command in commands {
+            let result = harness.player.handle(command).await;
+            assert!(matches!(result, Err(SpotifyError::PlayerNotReady)));
+        }
+        assert_eq!(calls(&harness.log), vec![]);
+    }
+
+    #[tokio::test]
+    async fn test_refresh_token_reconnects() {
+        let mut harness = Harness::logged_in().await;
+        harness.run(Command::RefreshToken).await;
+
+        assert_eq!(
+            calls(&harness.log),
+            vec![Call::Reconnect, Call::RefreshSuccessful]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_refresh_token_offline_keeps_credentials() {
+        let mut harness = Harness::logged_in().await;
+        let auth = Arc::clone(&harness.player.oauth_client);
+        auth.store.set(token(Duration::from_secs(60), true)).await;
+        auth.offline.store(true, Ordering::SeqCst);
+
+        harness.run(Command::RefreshToken).await;
+
+        assert_eq!(calls(&harness.log), vec![Call::RefreshToken]);
+        assert!(auth.store.get_cached().await.is_some());
+        assert!(harness.player.session.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_load_failure_near_token_expiry_retries_once() {
+        let mut harness = Harness::logged_in().await;
+        harness.run(load_command()).await;
+        let auth = Arc::clone(&harness.player.oauth_client);
+        auth.store.set(token(Duration::from_secs(20), false)).await;
+        harness.log.lock().unwrap().clear();
+
+        harness.run(Command::PlayerLoadFailed(track())).await;
+        assert!(matches!(
+            &calls(&harness.log)[..],
+            [
+                Call::RefreshToken,
+                Call::Reconnect,
+                Call::Load { resume: true, .. }
+            ]
+        ));
+
+        // Still failing, it's the track then
+        harness.log.lock().unwrap().clear();
+        harness.run(Command::PlayerLoadFailed(track())).await;
+        assert_eq!(
+            calls(&harness.log),
+            vec![Call::TrackUnavailable(TRACK_ID.to_string())]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_load_failure_with_fresh_token() {
+        let mut harness = Harness::logged_in().await;
+        harness.run(load_command()).await;
+        harness.log.lock().unwrap().clear();
+
+        harness.run(Command::PlayerLoadFailed(track())).await;
+        assert_eq!(
+            calls(&harness.log),
+            vec![Call::TrackUnavailable(TRACK_ID.to_string())]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stale_load_failure_is_ignored() {
+        let mut harness = Harness::logged_in().await;
+        harness.run(load_command()).await;
+        harness.log.lock().unwrap().clear();
+
+        // Reported for whatever was loaded before
+        let previous = SpotifyUri::Track {
+            id: SpotifyId::from_base62("6rqhFgbbKwnb9MLmUQDhG6").unwrap(),
+        };
+        harness.run(Command::PlayerLoadFailed(previous)).await;
+        assert_eq!(calls(&harness.log), vec![]);
+    }
+
+    #[tokio::test]
+    async fn test_reload_settings_recreates_player_once() {
+        let mut harness = Harness::logged_in().await;
+        harness.settings.lock().unwrap().gapless = false;
+
+        harness.run(Command::ReloadSettings).await;
+        harness.run(load_command()).await;
+
+        assert_eq!(
+            calls(&harness.log),
+            vec![
+                Call::CreatePlayer {
+                    gapless: false,
+                    volume: 0.7,
+                },
+                Call::Load {
+                    resume: true,
+                    position_ms: 0,
+                }
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_gapless_override_recreates_player_once() {
+        let mut harness = Harness::logged_in().await;
+
+        harness.run(Command::SetGaplessOverride(Some(false))).await;
+        harness.run(Command::SetGaplessOverride(Some(false))).await;
+        // Back to the setting, which is gapless already
+        harness.run(Command::SetGaplessOverride(None)).await;
+        harness.run(Command::SetGaplessOverride(Some(true))).await;
+
+        assert_eq!(
+            calls(&harness.log),
+            vec![
+                Call::CreatePlayer {
+                    gapless: false,
+                    volume: 0.7,
+                },
+                Call::CreatePlayer {
+                    gapless: true,
+                    volume: 0.7,
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reload_settings_keeps_current_track() {
+        let mut harness = Harness::logged_in().await;
+        harness.run(load_command()).await;
+        harness.run(Command::PlayerPause).await;
+        harness.run(Command::PlayerSeek(30000)).await;
+        harness.log.lock().unwrap().clear();
+
+        harness.settings.lock().unwrap().gapless = false;
+        harness.run(Command::ReloadSettings).await;
+
+        assert_eq!(
+            calls(&harness.log),
+            vec![
+                Call::CreatePlayer {
+                    gapless: false,
+                    volume: 0.7,
+                },
+                Call::Load {
+                    resume: false,
+                    position_ms: 30000,
+                }
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reload_settings_ignores_unrelated_changes() {
+        let mut harness = Harness::logged_in().await;
+        harness.run(load_command()).await;
+        harness.log.lock().unwrap().clear();
+
+        harness.settings.lock().unwrap().volume = 0.2;
+        harness.run(Command::ReloadSettings).await;
+
+        assert_eq!(calls(&harness.log), vec![]);
+    }
+
+    #[tokio::test]
+    async fn test_reload_settings_reconnects_for_new_ap_port() {
+        let mut harness = Harness::logged_in().await;
+        harness.settings.lock().unwrap().ap_port = Some(443);
+        harness.run(Command::ReloadSettings).await;
+
+        assert_eq!(
+            calls(&harness.log),
+            vec![
+                Call::Connect,
+                Call::Shutdown,
+                Call::CreatePlayer {
+                    gapless: true,
+                    volume: 0.7,
+                },
+            ]
+        );
+        assert!(harness.player.session.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_reconnects_when_session_is_lost() {
+        let mut harness = Harness::logged_in().await;
+        harness.run(load_command()).await;
+        harness.run(Command::PlayerPause).await;
+        harness.log.lock().unwrap().clear();
+
+        let session = harness.player.session.clone().unwrap();
+        session.invalid.store(true, Ordering::SeqCst);
+        harness.run(Command::PlayerResume).await;
+
+        assert_eq!(
+            calls(&harness.log),
+            vec![
+                Call::Connect,
+                Call::Shutdown,
+                Call::CreatePlayer {
+                    gapless: true,
+                    volume: 0.7,
+                },
+                // Resumed right after
+                Call::Load {
+                    resume: false,
+                    position_ms: 0,
+                },
+                Call::Play
+            ]
+        );
+        assert!(!harness.player.session.as_ref().unwrap().is_invalid());
+    }
+
+    #[tokio::test]
+    async fn test_pause_fades_out() {
+        tokio::time::pause();
+        let mut harness = Harness::logged_in().await;
+        harness.player.backend.fades = true;
+        harness.run(load_command()).await;
+        harness.log.lock().unwrap().clear();
+
+        harness.run(Command::PlayerPause).await;
+        assert_eq!(calls(&harness.log), vec![]);
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert_eq!(calls(&harness.log), vec![Call::Pause]);
+    }
+
+    #[tokio::test]
+    async fn test_resume_cancels_fading_out() {
+        tokio::time::pause();
+        let mut harness = Harness::logged_in().await;
+        harness.player.backend.fades = true;
+        harness.run(load_command()).await;
+        harness.log.lock().unwrap().clear();
+
+        harness.run(Command::PlayerPause).await;
+        harness.run(Command::PlayerResume).await;
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert_eq!(calls(&harness.log), vec![Call::Play]);
+    }
+
+    #[tokio::test]
+    async fn test_seek_fades_out_and_back_in() {
+        tokio::time::pause();
+        let mut harness = Harness::logged_in().await;
+        harness.player.backend.fades = true;
+        harness.run(load_command()).await;
+        harness.log.lock().unwrap().clear();
+
+        harness.run(Command::PlayerSeek(30000)).await;
+        assert_eq!(calls(&harness.log), vec![]);
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert_eq!(calls(&harness.log), vec![Call::Seek(30000)]);
+    }
+
+    #[tokio::test]
+    async fn test_pause_still_seeks_when_fading_for_a_seek() {
+        tokio::time::pause();
+        let mut harness = Harness::logged_in().await;
+        harness.player.backend.fades = true;
+        harness.run(load_command()).await;
+        harness.log.lock().unwrap().clear();
+
+        harness.run(Command::PlayerSeek(30000)).await;
+        harness.run(Command::PlayerPause).await;
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert_eq!(calls(&harness.log), vec![Call::Seek(30000), Call::Pause]);
+    }
+
+    #[tokio::test]
+    async fn test_reconnect_before_login() {
+        let mut harness = Harness::new(None);
+        harness.run(Command::Reconnect).await;
+        assert_eq!(calls(&harness.log), vec![]);
+    }
+
+    #[tokio::test]
+    async fn test_reload_settings_defers_bitrate_change() {
+        let mut harness = Harness::logged_in().await;
+        harness.settings.lock().unwrap().bitrate = BitrateSetting::Fixed(Bitrate::Bitrate320);
+
+        // Nothing to do until the next track
+        harness.run(Command::ReloadSettings).await;
+        assert_eq!(calls(&harness.log), vec![]);
+
+        harness.run(load_command()).await;
+        harness.run(load_command()).await;
+        assert_eq!(
+            calls(&harness.log),
+            vec![
+                Call::CreatePlayer {
+                    gapless: true,
+                    volume: 0.7,
+                },
+                Call::Load {
+                    resume: true,
+                    position_ms: 0,
+                },
+                Call::Load {
+                    resume: true,
+                    position_ms: 0,
+                }
+            ]
+        );
+    }
 }
@@ -1,16 +1,18 @@
 use anyhow::Result;
-use oo7::Keyring;
+use std::collections::HashMap;
 use std::sync::{Arc, RwLock};
-use std::time::Duration;
 
+use super::credentials_storage::open_backend;
 use crate::app::credentials::Credentials;
 
-const ATTRS: &[(&'static str, &'static str)] = &[("spot_credentials", "yes")];
-const MAX_KEYRING_RETRIES: u32 = 3;
-const KEYRING_RETRY_DELAY: Duration = Duration::from_millis(200);
-
 struct InnerTokenStore {
     storage: RwLock<Option<Credentials>>,
+    // Who the credentials above belong to, once we know
+    account: RwLock<Option<String>>,
+    // Copies of the credentials of every account (the keyring has those from previous runs)
+    accounts: RwLock<HashMap<String, Credentials>>,
+    // Whether the keyring (or the file standing in for it) backs it, which is the case outside of tests
+    persistent: bool,
 }
 
 #[derive(Clone)]
@@ -20,11 +22,21 @@ impl TokenStore {
     pub fn new() -> Self {
         Self(Arc::new(InnerTokenStore {
             storage: RwLock::new(None),
+            account: RwLock::new(None),
+            accounts: RwLock::new(HashMap::new()),
+            persistent: true,
         }))
     }
 
-    async fn keyring() -> Keyring {
-        Keyring::new().await.expect("Failed to initialize keyring")
+    // Never touches the keyring
+    #[cfg(test)]
+    pub fn in_memory(credentials: Option<Credentials>) -> Self {
+        Self(Arc::new(InnerTokenStore {
+            storage: RwLock::new(credentials),
+            account: RwLock::new(None),
+            accounts: RwLock::new(HashMap::new()),
+            persistent: false,
+        }))
     }
 
     pub fn get_cached_blocking(&self) -> Option<Credentials> {
@@ -36,72 +48,110 @@ impl TokenStore {
     }
 
     async fn retrieve(&self) -> Result<Credentials> {
-        let keyring = Self::keyring().await;
-        if matches!(keyring, Keyring::File(_)) {
-            // migrate keys if inside flatpak
-            if let Err(e) = oo7::migrate(vec![ATTRS], true).await {
-                debug!("Failed to migrate system keyring: {e}");
+        open_backend()
+            .await
+            .load()
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Empty keyring"))
+    }
+
+    // Try to clear the credentials
+    async fn logout(&self) -> Result<()> {
+        open_backend().await.delete().await
+    }
+
+    async fn save(&self, creds: &Credentials) -> Result<()> {
+        info!("Saving credentials");
+        open_backend().await.save(creds).await?;
+        info!("Saved credentials");
+        Ok(())
+    }
+
+    async fn save_account(&self, username: &str, creds: &Credentials) -> Result<()> {
+        open_backend().await.save_account(username, creds).await
+    }
+
+    async fn retrieve_account(&self, username: &str) -> Result<Credentials> {
+        open_backend()
+            .await
+            .load_account(username)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("No saved account for {username}"))
+    }
+
+    async fn delete_account(&self, username: &str) -> Result<()> {
+        open_backend().await.delete_account(username).await
+    }
+
+    async fn saved_usernames(&self) -> Result<Vec<String>> {
+        open_backend().await.usernames().await
+    }
+
+    async fn store_account(&self, username: &str, creds: Credentials) {
+        if self.0.persistent {
+            if let Err(e) = self.save_account(username, &creds).await {
+                warn!("Couldnt save account to secrets service: {e}");
             }
         }
+        self.0
+            .accounts
+            .write()
+            .unwrap()
+            .insert(username.to_string(), creds);
+    }
 
-        // Attempt to unlock the keyring in case it's still locked after login
-        if let Err(e) = keyring.unlock().await {
-            warn!("Failed to unlock keyring: {e}");
+    // Keeps a copy of the current credentials for this user, to switch back to them later
+    pub async fn remember_account(&self, username: &str) {
+        *self.0.account.write().unwrap() = Some(username.to_string());
+        if let Some(creds) = self.get_cached().await {
+            self.store_account(username, creds).await;
         }
+    }
 
-        // Retry to handle race with keyring daemon startup
-        let mut last_err = None;
-        for attempt in 0..MAX_KEYRING_RETRIES {
-            let items = keyring.search_items(&ATTRS).await?;
-            match items.first() {
-                Some(item) => {
-                    let item_json = item.secret().await?;
-                    let creds = serde_json::from_slice(item_json.as_bytes())?;
-                    return Ok(creds);
-                }
-                None => {
-                    last_err = Some(anyhow::anyhow!("Empty keyring"));
-                    if attempt < MAX_KEYRING_RETRIES - 1 {
-                        debug!(
-                            "Keyring empty on attempt {}, retrying in {}ms...",
-                            attempt + 1,
-                            KEYRING_RETRY_DELAY.as_millis()
-                        );
-                        tokio::time::sleep(KEYRING_RETRY_DELAY).await;
-                    }
-                }
+    pub async fn list_accounts(&self) -> Vec<String> {
+        let mut usernames: Vec<String> = self.0.accounts.read().unwrap().keys().cloned().collect();
+        if self.0.persistent {
+            match self.saved_usernames().await {
+                Ok(saved) => usernames.extend(saved),
+                Err(e) => warn!("Couldnt list accounts from secrets service: {e}"),
             }
         }
-
-        Err(last_err.unwrap())
+        usernames.sort();
+        usernames.dedup();
+        usernames
     }
 
-    // Try to clear the credentials
-    async fn logout(&self) -> Result<()> {
-        let result = Self::keyring().await.search_items(&ATTRS).await?;
-        let Some(item) = result.first() else {
-            warn!("Logout attempted, but keyring is empty");
-            return Ok(());
+    // The saved credentials of that user become the current ones
+    pub async fn switch_account(&self, username: &str) -> Option<Credentials> {
+        let saved = self.0.accounts.read().unwrap().get(username).cloned();
+        let creds = match saved {
+            Some(creds) => creds,
+            None if self.0.persistent => self
+                .retrieve_account(username)
+                .await
+                .inspect_err(|e| warn!("Couldnt get account from secrets service: {e}"))
+                .ok()?,
+            None => return None,
         };
-        item.delete().await?;
-        Ok(())
+        *self.0.account.write().unwrap() = Some(username.to_string());
+        self.set(creds.clone()).await;
+        Some(creds)
     }
 
-    async fn save(&self, creds: &Credentials) -> Result<()> {
-        // We simply write our stuct as JSON and send it
-        info!("Saving credentials");
-        let encoded = serde_json::to_vec(creds).unwrap();
-        Self::keyring()
-            .await
-            .create_item("Spotify Credentials", &ATTRS, &encoded, true)
-            .await?;
-        info!("Saved credentials");
-        Ok(())
+    // Makes room for another account, without forgetting the current one
+    pub async fn put_aside(&self) {
+        self.0.account.write().unwrap().take();
+        if self.0.persistent {
+            if let Err(e) = self.logout().await {
+                warn!("Couldnt clear token from secrets service: {e}");
+            }
+        }
+        self.0.storage.write().unwrap().take();
     }
 
     pub async fn get(&self) -> Option<Credentials> {
         let local = self.0.storage.read().unwrap().clone();
-        if local.is_some() {
+        if local.is_some() || !self.0.persistent {
             return local;
         }
 
@@ -119,15 +169,34 @@ impl TokenStore {
 
     pub async fn set(&self, creds: Credentials) {
         debug!("Saving token to store...");
-        if let Err(e) = self.save(&creds).await {
-            warn!("Couldnt save token to secrets service: {e}");
+        if self.0.persistent {
+            if let Err(e) = self.save(&creds).await {
+                warn!("Couldnt save token to secrets service: {e}");
+            }
+        }
+        // So that the copy is still good when switching back to it
+        let account = self.0.account.read().unwrap().clone();
+        if let Some(username) = account {
+            self.store_account(&username, creds.clone()).await;
         }
         self.0.storage.write().unwrap().replace(creds);
     }
 
+    // Forgets the current account entirely
     pub async fn clear(&self) {
-        if let Err(e) = self.logout().await {
-            warn!("Couldnt save token to secrets service: {e}");
+        if self.0.persistent {
+            if let Err(e) = self.logout().await {
+                warn!("Couldnt save token to secrets service: {e}");
+            }
+        }
+        let account = self.0.account.write().unwrap().take();
+        if let Some(username) = account {
+            self.0.accounts.write().unwrap().remove(&username);
+            if self.0.persistent {
+                if let Err(e) = self.delete_account(&username).await {
+                    warn!("Couldnt remove account from secrets service: {e}");
+                }
+            }
         }
         self.0.storage.write().unwrap().take();
     }
@@ -176,6 +245,7 @@ mod tests {
             access_token: "test_token".to_string(),
             refresh_token: "test_refresh".to_string(),
             token_expiry_time: None,
+            client_id: None,
         };
         store.0.storage.write().unwrap().replace(creds.clone());
 
@@ -184,6 +254,39 @@ mod tests {
         assert_eq!(cached.unwrap().access_token, "test_token");
     }
 
+    fn credentials(access_token: &str) -> Credentials {
+        Credentials {
+            access_token: access_token.to_string(),
+            refresh_token: "refresh".to_string(),
+            token_expiry_time: None,
+            client_id: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn switching_between_accounts() {
+        let store = TokenStore::in_memory(Some(credentials("personal")));
+        store.remember_account("me").await;
+
+        store.put_aside().await;
+        assert!(store.get().await.is_none());
+        store.set(credentials("work")).await;
+        store.remember_account("me-at-work").await;
+        assert_eq!(store.list_accounts().await, vec!["me", "me-at-work"]);
+
+        // Refreshed tokens make it to the account's copy
+        store.set(credentials("work-refreshed")).await;
+        let switched = store.switch_account("me").await.unwrap();
+        assert_eq!(switched.access_token, "personal");
+        assert_eq!(store.get().await.unwrap().access_token, "personal");
+        let switched = store.switch_account("me-at-work").await.unwrap();
+        assert_eq!(switched.access_token, "work-refreshed");
+        assert!(store.switch_account("someone-else").await.is_none());
+
+        store.clear().await;
+        assert_eq!(store.list_accounts().await, vec!["me"]);
+    }
+
     #[tokio::test]
     async fn get_returns_cached_without_hitting_keyring() {
         let store = TokenStore::new();
@@ -191,6 +294,7 @@ mod tests {
             access_token: "cached_token".to_string(),
             refresh_token: "cached_refresh".to_string(),
             token_expiry_time: None,
+            client_id: None,
         };
         store.0.storage.write().unwrap().replace(creds.clone());
 
@@ -0,0 +1,93 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::Duration;
+
+// The time between the end of a track and the next one actually playing, to help debugging
+// clicks and gaps between tracks. It ends up in the troubleshooting page of the about dialog.
+
+const MAX_TRANSITIONS: usize = 20;
+
+lazy_static! {
+    static ref TRANSITIONS: Mutex<TransitionLog> = Mutex::new(TransitionLog::default());
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Transition {
+    gap_ms: u128,
+    gapless: bool,
+}
+
+#[derive(Default)]
+struct TransitionLog(VecDeque<Transition>);
+
+impl TransitionLog {
+    fn push(&mut self, gap: Duration, gapless: bool) {
+        if self.0.len() == MAX_TRANSITIONS {
+            self.0.pop_front();
+        }
+        self.0.push_back(Transition {
+            gap_ms: gap.as_millis(),
+            gapless,
+        });
+    }
+
+    // Not translated on purpose, it's meant to be pasted in bug reports
+    fn summary(&self) -> String {
+        if self.0.is_empty() {
+            return "No track transitions yet".to_string();
+        }
+        let mut lines = vec![format!(
+            "Last {} track transitions (time between end of track and playback)",
+            self.0.len()
+        )];
+        lines.extend(self.0.iter().rev().map(|t| {
+            let mode = if t.gapless { "gapless" } else { "not gapless" };
+            format!("{}ms ({mode})", t.gap_ms)
+        }));
+        lines.join("\n")
+    }
+}
+
+pub fn record_transition(gap: Duration, gapless: bool) {
+    debug!(
+        "Track transition: {}ms between end of track and playback",
+        gap.as_millis()
+    );
+    TRANSITIONS.lock().unwrap().push(gap, gapless);
+}
+
+pub fn transitions_summary() -> String {
+    TRANSITIONS.lock().unwrap().summary()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transitions_are_rotated() {
+        let mut log = TransitionLog::default();
+        for i in 0..25 {
+            log.push(Duration::from_millis(i), true);
+        }
+
+        assert_eq!(log.0.len(), MAX_TRANSITIONS);
+        assert_eq!(log.0.front().unwrap().gap_ms, 5);
+        assert_eq!(log.0.back().unwrap().gap_ms, 24);
+    }
+
+    #[test]
+    fn test_summary() {
+        let mut log = TransitionLog::default();
+        assert_eq!(log.summary(), "No track transitions yet");
+
+        log.push(Duration::from_millis(12), true);
+        log.push(Duration::from_millis(340), false);
+        assert_eq!(
+            log.summary(),
+            "Last 2 track transitions (time between end of track and playback)\n\
+            340ms (not gapless)\n\
+            12ms (gapless)"
+        );
+    }
+}
@@ -0,0 +1,308 @@
+use futures::future::BoxFuture;
+use isahc::{AsyncReadResponseExt, Request};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::Arc;
+use thiserror::Error;
+
+use super::{secrets, Scrobble, ScrobbleError, Scrobbler};
+use crate::api::ProxiedClient;
+use crate::app::state::ScrobblerService;
+use crate::config;
+
+const API_URL: &str = "https://ws.audioscrobbler.com/2.0/";
+const AUTH_URL: &str = "https://www.last.fm/api/auth/";
+// That's as many scrobbles as a single call takes
+const MAX_SCROBBLES: usize = 50;
+
+const SESSION_KEY: &str = "riff_lastfm_session";
+
+// Only when built with an API account, see meson_options.txt
+pub fn is_available() -> bool {
+    !config::LASTFM_API_KEY.is_empty()
+}
+
+#[derive(Debug, Error)]
+pub enum LastFmError {
+    #[error(transparent)]
+    Http(#[from] isahc::Error),
+    #[error(transparent)]
+    Request(#[from] isahc::http::Error),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error("Last.fm error {code}: {message}")]
+    Api { code: u64, message: String },
+    #[error("Unexpected response from Last.fm")]
+    UnexpectedResponse,
+}
+
+impl LastFmError {
+    // The token wasn't authorized (yet) in the browser
+    pub fn is_unauthorized_token(&self) -> bool {
+        matches!(self, Self::Api { code: 14, .. })
+    }
+
+    // The session key was revoked (e.g. from the Last.fm settings), only logging in again gets us a new one
+    pub fn is_invalid_session(&self) -> bool {
+        matches!(self, Self::Api { code: 9, .. })
+    }
+
+    // Worth trying again later: we're offline, or Last.fm is having a bad day (or rate limiting us)
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::Http(_) | Self::Io(_) => true,
+            Self::Api { code, .. } => matches!(code, 11 | 16 | 29),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Session {
+    pub name: String,
+    pub key: String,
+}
+
+// Every call is signed: the md5 of the parameters sorted by name and concatenated, followed by the secret
+fn sign(params: &[(String, String)], secret: &str) -> String {
+    let mut sorted: Vec<&(String, String)> = params.iter().collect();
+    sorted.sort_by(|a, b| a.0.cmp(&b.0));
+    let mut payload: String = sorted
+        .into_iter()
+        .map(|(key, value)| format!("{key}{value}"))
+        .collect();
+    payload.push_str(secret);
+    glib::compute_checksum_for_string(glib::ChecksumType::Md5, &payload)
+        .expect("md5 is always available")
+        .to_string()
+}
+
+fn param(key: &str, value: impl ToString) -> (String, String) {
+    (key.to_string(), value.to_string())
+}
+
+// Several tracks go in a single call, each parameter being suffixed with the track's index
+fn scrobble_params(tracks: &[Scrobble]) -> Vec<(String, String)> {
+    tracks
+        .iter()
+        .enumerate()
+        .flat_map(|(i, track)| {
+            [
+                param(&format!("artist[{i}]"), &track.artist),
+                param(&format!("track[{i}]"), &track.title),
+                param(&format!("album[{i}]"), &track.album),
+                param(&format!("duration[{i}]"), track.duration_ms / 1000),
+                param(&format!("timestamp[{i}]"), track.timestamp),
+            ]
+        })
+        .collect()
+}
+
+pub struct LastFmClient {
+    api_key: &'static str,
+    secret: &'static str,
+    client: ProxiedClient,
+}
+
+impl LastFmClient {
+    pub fn new() -> Self {
+        Self {
+            api_key: config::LASTFM_API_KEY,
+            secret: config::LASTFM_API_SECRET,
+            client: ProxiedClient::new(),
+        }
+    }
+
+    async fn call(
+        &self,
+        method: &str,
+        mut params: Vec<(String, String)>,
+        session: Option<&Session>,
+    ) -> Result<Value, LastFmError> {
+        params.push(param("method", method));
+        params.push(param("api_key", self.api_key));
+        if let Some(session) = session {
+            params.push(param("sk", &session.key));
+        }
+        let signature = sign(&params, self.secret);
+        params.push(param("api_sig", signature));
+        // Not part of the signature
+        params.push(param("format", "json"));
+
+        let body = form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(params)
+            .finish();
+        let request = Request::post(API_URL)
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body(body)?;
+        let mut response = self.client.get().send_async(request).await?;
+        let json: Value = serde_json::from_str(&response.text().await?)?;
+
+        if let Some(code) = json.get("error").and_then(Value::as_u64) {
+            let message = json
+                .get("message")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string();
+            return Err(LastFmError::Api { code, message });
+        }
+        Ok(json)
+    }
+
+    pub async fn get_token(&self) -> Result<String, LastFmError> {
+        let json = self.call("auth.getToken", vec![], None).await?;
+        json.get("token")
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .ok_or(LastFmError::UnexpectedResponse)
+    }
+
+    // Where the user authorizes the token we got
+    pub fn auth_url(&self, token: &str) -> String {
+        format!("{AUTH_URL}?api_key={}&token={token}", self.api_key)
+    }
+
+    pub async fn get_session(&self, token: &str) -> Result<Session, LastFmError> {
+        let json = self
+            .call("auth.getSession", vec![param("token", token)], None)
+            .await?;
+        let session = json.get("session").ok_or(LastFmError::UnexpectedResponse)?;
+        Ok(Session::deserialize(session)?)
+    }
+
+    pub async fn update_now_playing(
+        &self,
+        session: &Session,
+        track: &Scrobble,
+    ) -> Result<(), LastFmError> {
+        let params = vec![
+            param("artist", &track.artist),
+            param("track", &track.title),
+            param("album", &track.album),
+            param("duration", track.duration_ms / 1000),
+        ];
+        self.call("track.updateNowPlaying", params, Some(session))
+            .await
+            .map(|_| ())
+    }
+
+    pub async fn scrobble(
+        &self,
+        session: &Session,
+        tracks: &[Scrobble],
+    ) -> Result<(), LastFmError> {
+        self.call("track.scrobble", scrobble_params(tracks), Some(session))
+            .await
+            .map(|_| ())
+    }
+}
+
+// The session key doesn't expire, so it's kept until we log out
+pub async fn load_session() -> anyhow::Result<Option<Session>> {
+    secrets::load(SESSION_KEY).await
+}
+
+pub async fn save_session(session: &Session) -> anyhow::Result<()> {
+    secrets::save("Last.fm Session", SESSION_KEY, session).await
+}
+
+pub async fn delete_session() -> anyhow::Result<()> {
+    secrets::delete(SESSION_KEY).await
+}
+
+impl From<LastFmError> for ScrobbleError {
+    fn from(e: LastFmError) -> Self {
+        if e.is_transient() {
+            Self::Transient(e.to_string())
+        } else if e.is_invalid_session() {
+            Self::LoggedOut(e.to_string())
+        } else {
+            Self::Rejected(e.to_string())
+        }
+    }
+}
+
+pub struct LastFmScrobbler {
+    client: Arc<LastFmClient>,
+    session: Session,
+}
+
+impl LastFmScrobbler {
+    pub fn new(client: Arc<LastFmClient>, session: Session) -> Self {
+        Self { client, session }
+    }
+}
+
+impl Scrobbler for LastFmScrobbler {
+    fn service(&self) -> ScrobblerService {
+        ScrobblerService::LastFm
+    }
+
+    fn max_batch(&self) -> usize {
+        MAX_SCROBBLES
+    }
+
+    fn now_playing<'a>(&'a self, track: &'a Scrobble) -> BoxFuture<'a, Result<(), ScrobbleError>> {
+        Box::pin(async move {
+            self.client
+                .update_now_playing(&self.session, track)
+                .await
+                .map_err(ScrobbleError::from)
+        })
+    }
+
+    fn scrobble<'a>(&'a self, tracks: &'a [Scrobble]) -> BoxFuture<'a, Result<(), ScrobbleError>> {
+        Box::pin(async move {
+            self.client
+                .scrobble(&self.session, tracks)
+                .await
+                .map_err(ScrobbleError::from)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign() {
+        let params = vec![
+            param("method", "auth.getSession"),
+            param("api_key", "key"),
+            param("token", "tok"),
+        ];
+        assert_eq!(sign(&params, "secret"), "04e870be4bb79756721b7bc1937fe83d");
+    }
+
+    #[test]
+    fn test_sign_scrobbles() {
+        let mut params = scrobble_params(&[Scrobble {
+            artist: "Artist".to_string(),
+            title: "Title".to_string(),
+            album: "Album".to_string(),
+            duration_ms: 180_500,
+            timestamp: 1700000000,
+        }]);
+        assert_eq!(params[0], param("artist[0]", "Artist"));
+        assert_eq!(params[3], param("duration[0]", "180"));
+
+        params.push(param("method", "track.scrobble"));
+        params.push(param("api_key", "key"));
+        params.push(param("sk", "SESSION"));
+        assert_eq!(sign(&params, "secret"), "e7a22f816594da83bf976c625f63e6e2");
+    }
+
+    #[test]
+    fn test_invalid_session_logs_out() {
+        let error = |code| LastFmError::Api {
+            code,
+            message: String::new(),
+        };
+        assert!(matches!(error(9).into(), ScrobbleError::LoggedOut(_)));
+        assert!(matches!(error(16).into(), ScrobbleError::Transient(_)));
+        assert!(matches!(error(13).into(), ScrobbleError::Rejected(_)));
+    }
+}
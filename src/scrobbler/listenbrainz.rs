@@ -0,0 +1,268 @@
+use futures::future::BoxFuture;
+use isahc::http::StatusCode;
+use isahc::{AsyncReadResponseExt, Request};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use thiserror::Error;
+
+use super::{secrets, Scrobble, ScrobbleError, Scrobbler};
+use crate::api::ProxiedClient;
+use crate::app::state::ScrobblerService;
+use crate::config;
+
+const API_URL: &str = "https://api.listenbrainz.org/1/";
+// Way less than what a single submission takes, but there's no need to send huge requests
+const MAX_LISTENS: usize = 100;
+
+const USER_KEY: &str = "riff_listenbrainz_token";
+
+#[derive(Debug, Error)]
+pub enum ListenBrainzError {
+    #[error(transparent)]
+    Http(#[from] isahc::Error),
+    #[error(transparent)]
+    Request(#[from] isahc::http::Error),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error("ListenBrainz error {status}: {message}")]
+    Api { status: StatusCode, message: String },
+}
+
+impl ListenBrainzError {
+    // Worth trying again later: we're offline, or ListenBrainz is having a bad day (or rate limiting us)
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::Http(_) | Self::Io(_) => true,
+            Self::Api { status, .. } => {
+                status.is_server_error() || *status == StatusCode::TOO_MANY_REQUESTS
+            }
+            _ => false,
+        }
+    }
+}
+
+impl From<ListenBrainzError> for ScrobbleError {
+    fn from(e: ListenBrainzError) -> Self {
+        if e.is_transient() {
+            Self::Transient(e.to_string())
+        } else {
+            Self::Rejected(e.to_string())
+        }
+    }
+}
+
+// The token comes from the user's ListenBrainz profile page, and stays valid until they reset it
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ListenBrainzUser {
+    pub name: String,
+    pub token: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ListenType {
+    PlayingNow,
+    Single,
+    Import,
+}
+
+impl ListenType {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::PlayingNow => "playing_now",
+            Self::Single => "single",
+            Self::Import => "import",
+        }
+    }
+}
+
+fn listens_payload(listen_type: ListenType, tracks: &[Scrobble]) -> Value {
+    let payload: Vec<Value> = tracks
+        .iter()
+        .map(|track| {
+            let mut listen = json!({
+                "track_metadata": {
+                    "artist_name": track.artist,
+                    "track_name": track.title,
+                    "release_name": track.album,
+                    "additional_info": {
+                        "duration_ms": track.duration_ms,
+                        "music_service": "spotify.com",
+                        "submission_client": "Riff",
+                        "submission_client_version": config::VERSION,
+                    },
+                },
+            });
+            // What's playing now hasn't been listened to yet
+            if listen_type != ListenType::PlayingNow {
+                listen["listened_at"] = json!(track.timestamp);
+            }
+            listen
+        })
+        .collect();
+    json!({
+        "listen_type": listen_type.as_str(),
+        "payload": payload,
+    })
+}
+
+pub struct ListenBrainzClient {
+    client: ProxiedClient,
+}
+
+impl ListenBrainzClient {
+    pub fn new() -> Self {
+        Self {
+            client: ProxiedClient::new(),
+        }
+    }
+
+    async fn send(&self, request: Request<String>) -> Result<Value, ListenBrainzError> {
+        let mut response = self.client.get().send_async(request).await?;
+        let status = response.status();
+        let text = response.text().await?;
+        if !status.is_success() {
+            let message = serde_json::from_str::<Value>(&text)
+                .ok()
+                .and_then(|json| json.get("error")?.as_str().map(str::to_string))
+                .unwrap_or(text);
+            return Err(ListenBrainzError::Api { status, message });
+        }
+        Ok(serde_json::from_str(&text)?)
+    }
+
+    // Who the token belongs to, if it's any good
+    pub async fn validate_token(&self, token: &str) -> Result<Option<String>, ListenBrainzError> {
+        let request = Request::get(format!("{API_URL}validate-token"))
+            .header("Authorization", format!("Token {token}"))
+            .body(String::new())?;
+        let json = match self.send(request).await {
+            Err(ListenBrainzError::Api { status, .. }) if status == StatusCode::UNAUTHORIZED => {
+                return Ok(None)
+            }
+            result => result?,
+        };
+        if json.get("valid").and_then(Value::as_bool) != Some(true) {
+            return Ok(None);
+        }
+        Ok(json
+            .get("user_name")
+            .and_then(Value::as_str)
+            .map(str::to_string))
+    }
+
+    async fn submit(
+        &self,
+        token: &str,
+        listen_type: ListenType,
+        tracks: &[Scrobble],
+    ) -> Result<(), ListenBrainzError> {
+        let request = Request::post(format!("{API_URL}submit-listens"))
+            .header("Authorization", format!("Token {token}"))
+            .header("Content-Type", "application/json")
+            .body(listens_payload(listen_type, tracks).to_string())?;
+        self.send(request).await.map(|_| ())
+    }
+}
+
+pub async fn load_user() -> anyhow::Result<Option<ListenBrainzUser>> {
+    secrets::load(USER_KEY).await
+}
+
+pub async fn save_user(user: &ListenBrainzUser) -> anyhow::Result<()> {
+    secrets::save("ListenBrainz Token", USER_KEY, user).await
+}
+
+pub async fn delete_user() -> anyhow::Result<()> {
+    secrets::delete(USER_KEY).await
+}
+
+pub struct ListenBrainzScrobbler {
+    client: Arc<ListenBrainzClient>,
+    user: ListenBrainzUser,
+}
+
+impl ListenBrainzScrobbler {
+    pub fn new(client: Arc<ListenBrainzClient>, user: ListenBrainzUser) -> Self {
+        Self { client, user }
+    }
+}
+
+impl Scrobbler for ListenBrainzScrobbler {
+    fn service(&self) -> ScrobblerService {
+        ScrobblerService::ListenBrainz
+    }
+
+    fn max_batch(&self) -> usize {
+        MAX_LISTENS
+    }
+
+    fn now_playing<'a>(&'a self, track: &'a Scrobble) -> BoxFuture<'a, Result<(), ScrobbleError>> {
+        Box::pin(async move {
+            self.client
+                .submit(
+                    &self.user.token,
+                    ListenType::PlayingNow,
+                    std::slice::from_ref(track),
+                )
+                .await
+                .map_err(ScrobbleError::from)
+        })
+    }
+
+    fn scrobble<'a>(&'a self, tracks: &'a [Scrobble]) -> BoxFuture<'a, Result<(), ScrobbleError>> {
+        // A single listen is the one that was just played, several are ones we couldn't send earlier
+        let listen_type = if tracks.len() == 1 {
+            ListenType::Single
+        } else {
+            ListenType::Import
+        };
+        Box::pin(async move {
+            self.client
+                .submit(&self.user.token, listen_type, tracks)
+                .await
+                .map_err(ScrobbleError::from)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track() -> Scrobble {
+        Scrobble {
+            artist: "Artist".to_string(),
+            title: "Title".to_string(),
+            album: "Album".to_string(),
+            duration_ms: 180_500,
+            timestamp: 1700000000,
+        }
+    }
+
+    #[test]
+    fn test_listens_payload() {
+        let payload = listens_payload(ListenType::Import, &[track(), track()]);
+        assert_eq!(payload["listen_type"], "import");
+        assert_eq!(payload["payload"].as_array().unwrap().len(), 2);
+
+        let listen = &payload["payload"][0];
+        assert_eq!(listen["listened_at"], 1700000000);
+        assert_eq!(listen["track_metadata"]["artist_name"], "Artist");
+        assert_eq!(listen["track_metadata"]["track_name"], "Title");
+        assert_eq!(listen["track_metadata"]["release_name"], "Album");
+        assert_eq!(
+            listen["track_metadata"]["additional_info"]["duration_ms"],
+            180_500
+        );
+    }
+
+    #[test]
+    fn test_playing_now_payload() {
+        let payload = listens_payload(ListenType::PlayingNow, &[track()]);
+        assert_eq!(payload["listen_type"], "playing_now");
+        assert!(payload["payload"][0].get("listened_at").is_none());
+    }
+}
@@ -0,0 +1,80 @@
+use futures::channel::mpsc::UnboundedSender;
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::{Scrobble, ScrobblerCommand};
+use crate::app::components::EventListener;
+use crate::app::state::{PlaybackEvent, SettingsEvent};
+use crate::app::{AppEvent, AppModel};
+use crate::settings::scrobbling_enabled;
+
+// Tells the scrobbler thread what's being played, and for how long
+pub struct ScrobblerListener {
+    app_model: Rc<AppModel>,
+    sender: UnboundedSender<ScrobblerCommand>,
+}
+
+impl ScrobblerListener {
+    pub fn new(app_model: Rc<AppModel>, sender: UnboundedSender<ScrobblerCommand>) -> Self {
+        Self { app_model, sender }
+    }
+
+    // Podcast episodes aren't scrobbled, and nothing is if it was turned off in the preferences
+    fn current_track(&self) -> Option<Scrobble> {
+        if !scrobbling_enabled() {
+            return None;
+        }
+        let song = self.app_model.get_state().playback.current_song()?;
+        if song.is_episode() {
+            return None;
+        }
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        Some(Scrobble {
+            artist: song.artists.first()?.name.clone(),
+            title: song.title,
+            album: song.album.name,
+            duration_ms: song.duration,
+            timestamp,
+        })
+    }
+
+    fn command_for(&self, event: &AppEvent) -> Option<ScrobblerCommand> {
+        match event {
+            AppEvent::PlaybackEvent(PlaybackEvent::TrackChanged(_)) => Some(
+                self.current_track()
+                    .map(ScrobblerCommand::Started)
+                    .unwrap_or(ScrobblerCommand::Stopped),
+            ),
+            AppEvent::PlaybackEvent(PlaybackEvent::PlaybackPaused) => {
+                Some(ScrobblerCommand::Paused)
+            }
+            AppEvent::PlaybackEvent(PlaybackEvent::PlaybackResumed) => {
+                Some(ScrobblerCommand::Resumed)
+            }
+            AppEvent::PlaybackEvent(PlaybackEvent::PlaybackStopped) => {
+                Some(ScrobblerCommand::Stopped)
+            }
+            AppEvent::SettingsEvent(SettingsEvent::LastFmLoginRequested) => {
+                Some(ScrobblerCommand::LastFmLogin)
+            }
+            AppEvent::SettingsEvent(SettingsEvent::LastFmLogoutRequested) => {
+                Some(ScrobblerCommand::LastFmLogout)
+            }
+            AppEvent::SettingsEvent(SettingsEvent::ListenBrainzTokenChanged(token)) => Some(
+                ScrobblerCommand::SetListenBrainzToken(token.trim().to_string()),
+            ),
+            _ => None,
+        }
+    }
+}
+
+impl EventListener for ScrobblerListener {
+    fn on_event(&mut self, event: &AppEvent) {
+        if let Some(command) = self.command_for(event) {
+            let _ = self.sender.unbounded_send(command);
+        }
+    }
+}
@@ -0,0 +1,472 @@
+use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use futures::future::BoxFuture;
+use futures::StreamExt;
+use gettextrs::gettext;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+
+use crate::app::state::{ScrobblerService, SettingsAction};
+use crate::app::{AppAction, AppModel};
+
+mod lastfm;
+pub use lastfm::is_available;
+use lastfm::*;
+
+mod listenbrainz;
+use listenbrainz::*;
+
+mod secrets;
+
+mod tracker;
+use tracker::PlayTracker;
+pub use tracker::Scrobble;
+
+mod listener;
+pub use listener::ScrobblerListener;
+
+// How often we check whether the user is done authorizing us in the browser, and for how long
+const LOGIN_POLL_INTERVAL: Duration = Duration::from_secs(3);
+const LOGIN_POLL_ATTEMPTS: u32 = 100;
+// How long we wait before trying to send what's left in the queues again
+const RETRY_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Debug)]
+pub enum ScrobblerCommand {
+    Started(Scrobble),
+    Paused,
+    Resumed,
+    Stopped,
+    LastFmLogin,
+    // Sent by the login task, once the user said yes in the browser
+    LastFmLoggedIn(Session),
+    LastFmLogout,
+    // An empty token logs out
+    SetListenBrainzToken(String),
+    // Sent once we know the token is any good
+    ListenBrainzLoggedIn(ListenBrainzUser),
+}
+
+#[derive(Debug, Error)]
+pub enum ScrobbleError {
+    // We're offline, or the service is having a bad day
+    #[error("{0}")]
+    Transient(String),
+    #[error("{0}")]
+    Rejected(String),
+    // The service doesn't know us anymore (e.g. the user revoked our access), they have to log in again
+    #[error("{0}")]
+    LoggedOut(String),
+}
+
+// A service played tracks can be sent to, once the user logged in to it
+pub trait Scrobbler: Send + Sync {
+    fn service(&self) -> ScrobblerService;
+
+    // How many tracks can be sent at once
+    fn max_batch(&self) -> usize;
+
+    fn now_playing<'a>(&'a self, track: &'a Scrobble) -> BoxFuture<'a, Result<(), ScrobbleError>>;
+
+    fn scrobble<'a>(&'a self, tracks: &'a [Scrobble]) -> BoxFuture<'a, Result<(), ScrobbleError>>;
+}
+
+// What couldn't be scrobbled (e.g. while offline), kept on disk until the next try
+struct ScrobbleQueue {
+    path: PathBuf,
+    pending: Vec<Scrobble>,
+}
+
+impl ScrobbleQueue {
+    fn load(service: ScrobblerService) -> Self {
+        let dir = glib::user_data_dir().join("riff");
+        let path = dir.join(format!("scrobbles-{}.json", service.id()));
+        // Before there were several services, Last.fm's queue was the only one
+        if service == ScrobblerService::LastFm {
+            Self::migrate(&dir.join("scrobbles.json"), &path);
+        }
+        let pending = fs::read(&path)
+            .ok()
+            .and_then(|content| serde_json::from_slice(&content).ok())
+            .unwrap_or_default();
+        Self { path, pending }
+    }
+
+    fn migrate(old_path: &Path, path: &Path) {
+        if !old_path.exists() || path.exists() {
+            return;
+        }
+        if let Err(e) = fs::rename(old_path, path) {
+            warn!("Could not move {}: {e}", old_path.display());
+        }
+    }
+
+    fn save(&self) {
+        let result = if self.pending.is_empty() {
+            fs::remove_file(&self.path).or_else(|e| match e.kind() {
+                std::io::ErrorKind::NotFound => Ok(()),
+                _ => Err(e),
+            })
+        } else {
+            serde_json::to_vec(&self.pending)
+                .map_err(std::io::Error::from)
+                .and_then(|content| {
+                    if let Some(dir) = self.path.parent() {
+                        fs::create_dir_all(dir)?;
+                    }
+                    fs::write(&self.path, content)
+                })
+        };
+        if let Err(e) = result {
+            warn!("Could not save the scrobbles for later: {e}");
+        }
+    }
+}
+
+// Each service has its own queue, so that one being down doesn't hold the others back
+struct Service {
+    scrobbler: Box<dyn Scrobbler>,
+    queue: ScrobbleQueue,
+}
+
+impl Service {
+    fn new(scrobbler: Box<dyn Scrobbler>) -> Self {
+        let queue = ScrobbleQueue::load(scrobbler.service());
+        Self { scrobbler, queue }
+    }
+
+    // Older scrobbles go first, in batches; returns whether any were sent, or that we were logged out
+    async fn flush(&mut self) -> Result<bool, ScrobbleError> {
+        let mut sent = false;
+        while !self.queue.pending.is_empty() {
+            let count = self.queue.pending.len().min(self.scrobbler.max_batch());
+            match self.scrobbler.scrobble(&self.queue.pending[..count]).await {
+                Ok(()) => sent = true,
+                Err(ScrobbleError::Transient(e)) => {
+                    debug!("Keeping {count} scrobbles for later: {e}");
+                    break;
+                }
+                Err(e @ ScrobbleError::LoggedOut(_)) => return Err(e),
+                Err(e) => warn!("Dropping {count} scrobbles: {e}"),
+            }
+            self.queue.pending.drain(..count);
+        }
+        self.queue.save();
+        Ok(sent)
+    }
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+struct ScrobblerServer {
+    lastfm: Arc<LastFmClient>,
+    listenbrainz: Arc<ListenBrainzClient>,
+    services: Vec<Service>,
+    // How long tracks are played is tracked once, for all services
+    tracker: PlayTracker,
+    // Set while some scrobbles are waiting to be sent again
+    retry_at: Option<Instant>,
+    sender: UnboundedSender<AppAction>,
+    commands: UnboundedSender<ScrobblerCommand>,
+}
+
+impl ScrobblerServer {
+    fn set_user(&self, service: ScrobblerService, user: Option<String>) {
+        let _ = self
+            .sender
+            .unbounded_send(SettingsAction::SetScrobblerUser(service, user).into());
+    }
+
+    async fn restore_sessions(&mut self) {
+        if is_available() {
+            match load_session().await {
+                Ok(Some(session)) => self.enable(self.lastfm_scrobbler(session)).await,
+                Ok(None) => {}
+                Err(e) => warn!("Could not load the Last.fm session: {e}"),
+            }
+        }
+        match load_user().await {
+            Ok(Some(user)) => self.enable(self.listenbrainz_scrobbler(user)).await,
+            Ok(None) => {}
+            Err(e) => warn!("Could not load the ListenBrainz token: {e}"),
+        }
+    }
+
+    fn lastfm_scrobbler(&self, session: Session) -> (Box<dyn Scrobbler>, String) {
+        let name = session.name.clone();
+        let scrobbler = LastFmScrobbler::new(Arc::clone(&self.lastfm), session);
+        (Box::new(scrobbler), name)
+    }
+
+    fn listenbrainz_scrobbler(&self, user: ListenBrainzUser) -> (Box<dyn Scrobbler>, String) {
+        let name = user.name.clone();
+        let scrobbler = ListenBrainzScrobbler::new(Arc::clone(&self.listenbrainz), user);
+        (Box::new(scrobbler), name)
+    }
+
+    async fn enable(&mut self, (scrobbler, user): (Box<dyn Scrobbler>, String)) {
+        let service = scrobbler.service();
+        self.services.retain(|s| s.scrobbler.service() != service);
+        self.set_user(service, Some(user));
+        self.services.push(Service::new(scrobbler));
+        self.flush().await;
+    }
+
+    fn disable(&mut self, service: ScrobblerService) {
+        self.services.retain(|s| s.scrobbler.service() != service);
+        // Nobody to scrobble them for anymore
+        let mut queue = ScrobbleQueue::load(service);
+        queue.pending.clear();
+        queue.save();
+        self.set_user(service, None);
+    }
+
+    // Forgets the session too, it's of no use anymore
+    async fn logout(&mut self, service: ScrobblerService) {
+        self.disable(service);
+        let result = match service {
+            ScrobblerService::LastFm => delete_session().await,
+            ScrobblerService::ListenBrainz => delete_user().await,
+        };
+        if let Err(e) = result {
+            warn!("Could not forget the {} session: {e}", service.id());
+        }
+    }
+
+    async fn logged_out(&mut self, service: ScrobblerService, error: ScrobbleError) {
+        warn!("Logged out of {}: {error}", service.id());
+        self.logout(service).await;
+        let message = match service {
+            // translators: Shown when Last.fm stopped accepting our session (e.g. access was revoked), scrobbling stops until the user logs in again
+            ScrobblerService::LastFm => {
+                gettext("Logged out of Last.fm, log in again to keep scrobbling")
+            }
+            // translators: Shown when ListenBrainz stopped accepting the token, scrobbling stops until the user enters one again
+            ScrobblerService::ListenBrainz => gettext("The ListenBrainz token is no longer valid"),
+        };
+        let _ = self
+            .sender
+            .unbounded_send(AppAction::ShowNotification(message));
+    }
+
+    async fn handle(&mut self, command: ScrobblerCommand) {
+        match command {
+            ScrobblerCommand::Started(track) => {
+                self.tracker.start(track.clone(), Instant::now());
+                self.now_playing(&track).await;
+            }
+            ScrobblerCommand::Paused => self.tracker.pause(Instant::now()),
+            ScrobblerCommand::Resumed => self.tracker.resume(Instant::now()),
+            ScrobblerCommand::Stopped => self.tracker.stop(),
+            ScrobblerCommand::LastFmLogin => self.lastfm_login(),
+            ScrobblerCommand::LastFmLoggedIn(session) => {
+                if let Err(e) = save_session(&session).await {
+                    warn!("Could not save the Last.fm session: {e}");
+                }
+                self.enable(self.lastfm_scrobbler(session)).await;
+            }
+            ScrobblerCommand::LastFmLogout => self.logout(ScrobblerService::LastFm).await,
+            ScrobblerCommand::SetListenBrainzToken(token) if token.is_empty() => {
+                self.logout(ScrobblerService::ListenBrainz).await
+            }
+            ScrobblerCommand::SetListenBrainzToken(token) => self.listenbrainz_login(token),
+            ScrobblerCommand::ListenBrainzLoggedIn(user) => {
+                if let Err(e) = save_user(&user).await {
+                    warn!("Could not save the ListenBrainz token: {e}");
+                }
+                self.enable(self.listenbrainz_scrobbler(user)).await;
+            }
+        }
+    }
+
+    // Nothing tells us when the user is done in the browser, so we keep asking in the background
+    fn lastfm_login(&self) {
+        let client = Arc::clone(&self.lastfm);
+        let commands = self.commands.clone();
+        let sender = self.sender.clone();
+        tokio::spawn(async move {
+            let result: Result<Session, LastFmError> = async {
+                let token = client.get_token().await?;
+                if let Err(e) = open::that(client.auth_url(&token)) {
+                    warn!("Could not open the Last.fm login page: {e}");
+                }
+                let mut attempts = 0;
+                loop {
+                    tokio::time::sleep(LOGIN_POLL_INTERVAL).await;
+                    attempts += 1;
+                    match client.get_session(&token).await {
+                        Err(e) if e.is_unauthorized_token() && attempts < LOGIN_POLL_ATTEMPTS => {}
+                        result => break result,
+                    }
+                }
+            }
+            .await;
+
+            match result {
+                Ok(session) => {
+                    let _ = commands.unbounded_send(ScrobblerCommand::LastFmLoggedIn(session));
+                }
+                Err(e) => {
+                    warn!("Could not log in to Last.fm: {e}");
+                    // translators: Shown when logging in to Last.fm (to scrobble played tracks) didn't work out
+                    let message = gettext("Could not log in to Last.fm");
+                    let _ = sender.unbounded_send(AppAction::ShowNotification(message));
+                }
+            }
+        });
+    }
+
+    // Checking the token takes a request, which shouldn't hold up the tracks being played
+    fn listenbrainz_login(&self, token: String) {
+        let client = Arc::clone(&self.listenbrainz);
+        let commands = self.commands.clone();
+        let sender = self.sender.clone();
+        tokio::spawn(async move {
+            let message = match client.validate_token(&token).await {
+                Ok(Some(name)) => {
+                    let user = ListenBrainzUser { name, token };
+                    let _ = commands.unbounded_send(ScrobblerCommand::ListenBrainzLoggedIn(user));
+                    return;
+                }
+                // translators: Shown when the ListenBrainz token entered in the preferences was refused
+                Ok(None) => gettext("Invalid ListenBrainz token"),
+                Err(e) => {
+                    warn!("Could not check the ListenBrainz token: {e}");
+                    // translators: Shown when the ListenBrainz token entered in the preferences couldn't be checked (e.g. while offline)
+                    gettext("Could not reach ListenBrainz")
+                }
+            };
+            let _ = sender.unbounded_send(AppAction::ShowNotification(message));
+        });
+    }
+
+    async fn now_playing(&mut self, track: &Scrobble) {
+        let mut logged_out = vec![];
+        for service in self.services.iter() {
+            match service.scrobbler.now_playing(track).await {
+                Ok(()) => {}
+                Err(e @ ScrobbleError::LoggedOut(_)) => {
+                    logged_out.push((service.scrobbler.service(), e))
+                }
+                Err(e) => debug!(
+                    "Could not update the track playing on {}: {e}",
+                    service.scrobbler.service().id()
+                ),
+            }
+        }
+        for (service, e) in logged_out {
+            self.logged_out(service, e).await;
+        }
+    }
+
+    async fn scrobble_due(&mut self) {
+        let Some(track) = self.tracker.take_due(Instant::now()) else {
+            return;
+        };
+        for service in self.services.iter_mut() {
+            service.queue.pending.push(track.clone());
+        }
+        self.flush().await;
+    }
+
+    async fn flush(&mut self) {
+        let mut logged_out = vec![];
+        for service in self.services.iter_mut() {
+            match service.flush().await {
+                Ok(true) => {
+                    let action =
+                        SettingsAction::ScrobblesSubmitted(service.scrobbler.service(), now_secs());
+                    let _ = self.sender.unbounded_send(action.into());
+                }
+                Ok(false) => {}
+                Err(e) => logged_out.push((service.scrobbler.service(), e)),
+            }
+        }
+        for (service, e) in logged_out {
+            self.logged_out(service, e).await;
+        }
+        let pending = self.services.iter().any(|s| !s.queue.pending.is_empty());
+        self.retry_at = pending.then(|| Instant::now() + RETRY_INTERVAL);
+    }
+}
+
+async fn when(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline.into()).await,
+        None => futures::future::pending().await,
+    }
+}
+
+#[tokio::main]
+async fn scrobbler_server(
+    mut server: ScrobblerServer,
+    mut receiver: UnboundedReceiver<ScrobblerCommand>,
+) {
+    server.restore_sessions().await;
+
+    loop {
+        let deadline = server.tracker.deadline();
+        tokio::select! {
+            command = receiver.next() => match command {
+                Some(command) => server.handle(command).await,
+                None => break,
+            },
+            _ = when(deadline) => server.scrobble_due().await,
+            _ = when(server.retry_at) => server.flush().await,
+        }
+    }
+}
+
+pub fn start_scrobbler(
+    app_model: Rc<AppModel>,
+    sender: UnboundedSender<AppAction>,
+) -> ScrobblerListener {
+    let (commands, receiver) = unbounded();
+    let server = ScrobblerServer {
+        lastfm: Arc::new(LastFmClient::new()),
+        listenbrainz: Arc::new(ListenBrainzClient::new()),
+        services: vec![],
+        tracker: PlayTracker::default(),
+        retry_at: None,
+        sender,
+        commands: commands.clone(),
+    };
+
+    thread::spawn(move || scrobbler_server(server, receiver));
+
+    ScrobblerListener::new(app_model, commands)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_old_queue_is_migrated() {
+        let dir = std::env::temp_dir().join(format!("riff-test-scrobbles-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let old_path = dir.join("scrobbles.json");
+        let path = dir.join("scrobbles-lastfm.json");
+
+        fs::write(&old_path, "[]").unwrap();
+        ScrobbleQueue::migrate(&old_path, &path);
+        assert!(!old_path.exists());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "[]");
+
+        // Whatever was queued since wins
+        fs::write(&old_path, "old").unwrap();
+        ScrobbleQueue::migrate(&old_path, &path);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "[]");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,21 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::player::open_backend;
+
+// Whatever a service needs to scrobble for the user is kept along with the credentials (as JSON), until they log out
+pub async fn load<T: DeserializeOwned>(key: &str) -> anyhow::Result<Option<T>> {
+    match open_backend().await.load_secret(key).await? {
+        Some(value) => Ok(Some(serde_json::from_value(value)?)),
+        None => Ok(None),
+    }
+}
+
+pub async fn save<T: Serialize>(label: &str, key: &str, value: &T) -> anyhow::Result<()> {
+    let value = serde_json::to_value(value)?;
+    open_backend().await.save_secret(label, key, &value).await
+}
+
+pub async fn delete(key: &str) -> anyhow::Result<()> {
+    open_backend().await.delete_secret(key).await
+}
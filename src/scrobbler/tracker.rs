@@ -0,0 +1,178 @@
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+// Last.fm's rules (which ListenBrainz follows too): tracks shorter than that don't count...
+const MIN_TRACK_DURATION: Duration = Duration::from_secs(30);
+// ...and the others count once played for half their duration, or that long
+const MAX_PLAYED_DURATION: Duration = Duration::from_secs(4 * 60);
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Scrobble {
+    pub artist: String,
+    pub title: String,
+    pub album: String,
+    pub duration_ms: u32,
+    // When the track started playing, in seconds since the epoch
+    pub timestamp: u64,
+}
+
+impl Scrobble {
+    // How long the track has to be played to be scrobbled, if at all
+    fn threshold(&self) -> Option<Duration> {
+        let duration = Duration::from_millis(self.duration_ms.into());
+        if duration < MIN_TRACK_DURATION {
+            None
+        } else {
+            Some((duration / 2).min(MAX_PLAYED_DURATION))
+        }
+    }
+}
+
+struct Playing {
+    track: Scrobble,
+    // Not counting since it was last resumed
+    played: Duration,
+    resumed_at: Option<Instant>,
+    scrobbled: bool,
+}
+
+impl Playing {
+    fn played_at(&self, now: Instant) -> Duration {
+        self.played + self.resumed_at.map(|at| now - at).unwrap_or_default()
+    }
+}
+
+// Keeps track of how long the current track was actually played, seeking and pausing included
+#[derive(Default)]
+pub struct PlayTracker {
+    current: Option<Playing>,
+}
+
+impl PlayTracker {
+    pub fn start(&mut self, track: Scrobble, now: Instant) {
+        self.current = Some(Playing {
+            track,
+            played: Duration::ZERO,
+            resumed_at: Some(now),
+            scrobbled: false,
+        });
+    }
+
+    pub fn pause(&mut self, now: Instant) {
+        if let Some(playing) = self.current.as_mut() {
+            playing.played = playing.played_at(now);
+            playing.resumed_at = None;
+        }
+    }
+
+    pub fn resume(&mut self, now: Instant) {
+        if let Some(playing) = self.current.as_mut() {
+            if playing.resumed_at.is_none() {
+                playing.resumed_at = Some(now);
+            }
+        }
+    }
+
+    pub fn stop(&mut self) {
+        self.current = None;
+    }
+
+    // When the current track is due to be scrobbled, if it keeps playing
+    pub fn deadline(&self) -> Option<Instant> {
+        let playing = self.current.as_ref().filter(|p| !p.scrobbled)?;
+        let resumed_at = playing.resumed_at?;
+        let threshold = playing.track.threshold()?;
+        Some(resumed_at + threshold.saturating_sub(playing.played))
+    }
+
+    // The current track, once (and only once) it's been played long enough
+    pub fn take_due(&mut self, now: Instant) -> Option<Scrobble> {
+        let playing = self.current.as_mut().filter(|p| !p.scrobbled)?;
+        let threshold = playing.track.threshold()?;
+        if playing.played_at(now) < threshold {
+            return None;
+        }
+        playing.scrobbled = true;
+        Some(playing.track.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(duration_secs: u32) -> Scrobble {
+        Scrobble {
+            artist: "Artist".to_string(),
+            title: "Title".to_string(),
+            album: "Album".to_string(),
+            duration_ms: duration_secs * 1000,
+            timestamp: 0,
+        }
+    }
+
+    #[test]
+    fn test_scrobble_at_half() {
+        let now = Instant::now();
+        let mut tracker = PlayTracker::default();
+        tracker.start(track(180), now);
+
+        assert_eq!(tracker.deadline(), Some(now + Duration::from_secs(90)));
+        assert_eq!(tracker.take_due(now + Duration::from_secs(89)), None);
+        assert_eq!(
+            tracker.take_due(now + Duration::from_secs(90)),
+            Some(track(180))
+        );
+        // Only once
+        assert_eq!(tracker.take_due(now + Duration::from_secs(120)), None);
+        assert_eq!(tracker.deadline(), None);
+    }
+
+    #[test]
+    fn test_scrobble_long_track() {
+        let now = Instant::now();
+        let mut tracker = PlayTracker::default();
+        tracker.start(track(20 * 60), now);
+
+        assert_eq!(tracker.deadline(), Some(now + MAX_PLAYED_DURATION));
+    }
+
+    #[test]
+    fn test_no_scrobble_short_track() {
+        let now = Instant::now();
+        let mut tracker = PlayTracker::default();
+        tracker.start(track(20), now);
+
+        assert_eq!(tracker.deadline(), None);
+        assert_eq!(tracker.take_due(now + Duration::from_secs(20)), None);
+    }
+
+    #[test]
+    fn test_pause_delays_scrobble() {
+        let now = Instant::now();
+        let mut tracker = PlayTracker::default();
+        tracker.start(track(180), now);
+        tracker.pause(now + Duration::from_secs(60));
+
+        assert_eq!(tracker.deadline(), None);
+        assert_eq!(tracker.take_due(now + Duration::from_secs(100)), None);
+
+        tracker.resume(now + Duration::from_secs(100));
+        assert_eq!(tracker.deadline(), Some(now + Duration::from_secs(130)));
+        assert_eq!(
+            tracker.take_due(now + Duration::from_secs(130)),
+            Some(track(180))
+        );
+    }
+
+    #[test]
+    fn test_stop_forgets_track() {
+        let now = Instant::now();
+        let mut tracker = PlayTracker::default();
+        tracker.start(track(180), now);
+        tracker.stop();
+
+        assert_eq!(tracker.deadline(), None);
+        assert_eq!(tracker.take_due(now + Duration::from_secs(180)), None);
+    }
+}
@@ -1,17 +1,26 @@
 use crate::{
     app::{
-        components::EventListener,
+        components::{utils::Debouncer, EventListener},
         models::RepeatMode,
-        state::{PlaybackAction, PlaybackEvent},
-        AppAction, AppEvent,
+        state::{LoginEvent, PlaybackAction, PlaybackEvent, PlaybackState, ScreenName},
+        AppAction, AppEvent, AppModel, BrowserEvent,
+    },
+    player::{
+        AudioBackend, AudioMixer, BitrateSetting, SpotifyPlayerSettings, DEFAULT_REDIRECT_PORT,
     },
-    player::{AudioBackend, SpotifyPlayerSettings},
 };
-use gio::prelude::SettingsExt;
+use gio::prelude::{SettingsExt, SettingsExtManual};
+use glib::prelude::ToVariant;
 use libadwaita::ColorScheme;
-use librespot::playback::config::Bitrate;
+use librespot::playback::config::{AudioFormat, Bitrate, NormalisationType};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::rc::Rc;
+use std::time::Duration;
 
 const SETTINGS: &str = "dev.diegovsky.Riff";
+// Riff is a fork of Spot, people coming from it might still have its settings around
+const SPOT_SETTINGS: &str = "dev.alextren.Spot";
 
 #[derive(Clone, Debug, Default)]
 pub struct WindowGeometry {
@@ -43,15 +52,215 @@ impl WindowGeometry {
     }
 }
 
+// Where the user was when the app was last closed
+#[derive(Clone, Debug, Default)]
+pub struct SavedNavigation {
+    // Home excluded, it's always there
+    pub screens: Vec<ScreenName>,
+    pub home_page: String,
+}
+
+impl SavedNavigation {
+    // None if the user would rather start from scratch every time
+    pub fn new_from_gsettings() -> Option<Self> {
+        let settings = gio::Settings::new(SETTINGS);
+        if !settings.boolean("restore-navigation") {
+            return None;
+        }
+        let screens = settings
+            .strv("navigation-stack")
+            .iter()
+            .filter_map(|s| ScreenName::from_identifier(s.as_str()))
+            // There's no search query to go with it anyway
+            .filter(|s| !matches!(s, ScreenName::Home | ScreenName::Search))
+            .collect();
+        Some(Self {
+            screens,
+            home_page: settings.string("home-page").to_string(),
+        })
+    }
+}
+
+// What was playing when the app was last closed
+#[derive(Clone, Debug, Default)]
+pub struct SavedPlayback {
+    // The album or playlist it was played from, as a Spotify URI
+    pub context: Option<String>,
+    pub track: String,
+    // Where the track was in that context
+    pub index: usize,
+    // In milliseconds
+    pub position: u32,
+}
+
+impl SavedPlayback {
+    pub fn new_from_gsettings() -> Option<Self> {
+        let settings = gio::Settings::new(SETTINGS);
+        let track = settings.string("last-track").to_string();
+        if track.is_empty() {
+            return None;
+        }
+        let context = settings.string("last-context").to_string();
+        Some(Self {
+            context: Some(context).filter(|c| !c.is_empty()),
+            track,
+            index: settings.uint("last-track-index") as usize,
+            position: settings.uint("last-track-position"),
+        })
+    }
+
+    pub fn from_playback(playback: &PlaybackState) -> Option<Self> {
+        let track = playback.current_song_id()?;
+        Some(Self {
+            context: playback.current_source().and_then(|s| s.spotify_uri()),
+            index: playback.songs().find_index(&track).unwrap_or(0),
+            position: playback.position_ms(),
+            track,
+        })
+    }
+
+    pub fn save(&self) -> Option<()> {
+        let settings = gio::Settings::new(SETTINGS);
+        settings.delay();
+        settings.set_string("last-track", &self.track).ok()?;
+        settings
+            .set_string("last-context", self.context.as_deref().unwrap_or(""))
+            .ok()?;
+        settings
+            .set_uint(
+                "last-track-index",
+                u32::try_from(self.index).unwrap_or(u32::MAX),
+            )
+            .ok()?;
+        settings
+            .set_uint("last-track-position", self.position)
+            .ok()?;
+        settings.apply();
+        Some(())
+    }
+
+    pub fn forget() -> Option<()> {
+        Self::default().save()
+    }
+}
+
+// Read on demand so that toggling it in the preferences applies right away
+pub fn track_notifications_enabled() -> bool {
+    gio::Settings::new(SETTINGS).boolean("track-notifications")
+}
+
+// In bytes, from megabytes in the settings where 0 means there's no limit
+pub fn api_cache_limit() -> Option<u64> {
+    match gio::Settings::new(SETTINGS).uint("api-cache-size") {
+        0 => None,
+        x => Some(x as u64 * 1024 * 1024),
+    }
+}
+
+// Whatever the environment says, unless one was set
+pub fn proxy() -> Option<String> {
+    let settings = gio::Settings::new(SETTINGS);
+    settings
+        .boolean("proxy-enabled")
+        .then(|| settings.string("proxy-url").trim().to_string())
+        .filter(|proxy| !proxy.is_empty())
+        .or_else(|| {
+            ["https_proxy", "HTTPS_PROXY", "http_proxy", "HTTP_PROXY"]
+                .iter()
+                .find_map(|var| std::env::var(var).ok())
+                .filter(|proxy| !proxy.is_empty())
+        })
+}
+
+pub fn scrobbling_enabled() -> bool {
+    gio::Settings::new(SETTINGS).boolean("scrobbling")
+}
+
+pub fn background_playback_enabled() -> bool {
+    gio::Settings::new(SETTINGS).boolean("background-playback")
+}
+
+// On top of whatever the account settings say
+pub fn block_explicit_enabled() -> bool {
+    gio::Settings::new(SETTINGS).boolean("block-explicit")
+}
+
+pub fn session_health_enabled() -> bool {
+    gio::Settings::new(SETTINGS).boolean("session-health")
+}
+
+// The one from the preferences, if any (the default one is the player's business)
+pub fn custom_client_id() -> Option<String> {
+    let client_id = gio::Settings::new(SETTINGS).string("client-id");
+    Some(client_id.trim().to_string()).filter(|client_id| !client_id.is_empty())
+}
+
+pub fn is_album_gapless(id: &str) -> bool {
+    gio::Settings::new(SETTINGS)
+        .strv("gapless-albums")
+        .iter()
+        .any(|album| album.as_str() == id)
+}
+
+pub fn set_album_gapless(id: &str, gapless: bool) {
+    let settings = gio::Settings::new(SETTINGS);
+    let mut albums: Vec<String> = settings
+        .strv("gapless-albums")
+        .iter()
+        .map(|album| album.to_string())
+        .filter(|album| album != id)
+        .collect();
+    if gapless {
+        albums.push(id.to_string());
+    }
+    if let Err(e) = settings.set_strv("gapless-albums", albums) {
+        error!("Trying to update gsettings: {e}");
+    }
+}
+
+// Where we left podcast episodes, in milliseconds
+pub fn episode_position(id: &str) -> Option<u32> {
+    gio::Settings::new(SETTINGS)
+        .get::<HashMap<String, u32>>("episode-positions")
+        .get(id)
+        .copied()
+}
+
+// 0 means we're done with it (or never started), no need to remember that
+pub fn set_episode_position(id: &str, position: u32) {
+    let settings = gio::Settings::new(SETTINGS);
+    let mut positions: HashMap<String, u32> = settings.get("episode-positions");
+    if position > 0 {
+        positions.insert(id.to_string(), position);
+    } else if positions.remove(id).is_none() {
+        return;
+    }
+    if let Err(e) = settings.set("episode-positions", positions.to_variant()) {
+        error!("Trying to update gsettings: {e}");
+    }
+}
+
+fn bitrate_from_gsettings(settings: &gio::Settings, key: &str) -> Option<Bitrate> {
+    match settings.enum_(key) {
+        0 => Some(Bitrate::Bitrate96),
+        1 => Some(Bitrate::Bitrate160),
+        2 => Some(Bitrate::Bitrate320),
+        _ => None,
+    }
+}
+
 // Player (librespot) settings
 impl SpotifyPlayerSettings {
     fn new_from_gsettings(settings: &gio::Settings) -> Option<Self> {
         let bitrate = match settings.enum_("player-bitrate") {
-            0 => Some(Bitrate::Bitrate96),
-            1 => Some(Bitrate::Bitrate160),
-            2 => Some(Bitrate::Bitrate320),
-            _ => None,
-        }?;
+            3 => BitrateSetting::Automatic {
+                metered: bitrate_from_gsettings(settings, "player-bitrate-metered")
+                    .unwrap_or(Bitrate::Bitrate96),
+                unmetered: bitrate_from_gsettings(settings, "player-bitrate-unmetered")
+                    .unwrap_or(Bitrate::Bitrate320),
+            },
+            _ => BitrateSetting::Fixed(bitrate_from_gsettings(settings, "player-bitrate")?),
+        };
         let backend = match settings.enum_("audio-backend") {
             0 => Some(AudioBackend::PulseAudio),
             1 => Some(AudioBackend::Alsa(
@@ -60,9 +269,38 @@ impl SpotifyPlayerSettings {
             2 => Some(AudioBackend::GStreamer(
                 "audioconvert dithering=none ! audioresample ! pipewiresink".to_string(), // This should be configurable eventually
             )),
+            3 => Some(AudioBackend::PipeWire(
+                Some(settings.string("pipewire-target").as_str().to_string())
+                    .filter(|target| !target.is_empty()),
+            )),
             _ => None,
         }?;
+        // The hardware mixer only makes sense when we're talking to the card directly
+        let mixer = match backend {
+            AudioBackend::Alsa(_) if settings.boolean("alsa-hardware-mixer") => AudioMixer::Alsa {
+                device: settings.string("alsa-mixer-device").as_str().to_string(),
+                control: settings.string("alsa-mixer-control").as_str().to_string(),
+            },
+            _ => AudioMixer::Soft,
+        };
+        let audio_format = match settings.enum_("audio-format") {
+            1 => AudioFormat::S24,
+            2 => AudioFormat::S24_3,
+            3 => AudioFormat::S32,
+            4 => AudioFormat::F32,
+            5 => AudioFormat::F64,
+            _ => AudioFormat::S16,
+        };
         let gapless = settings.boolean("gapless-playback");
+        let fade_duration = Duration::from_millis(settings.uint("fade-duration") as u64);
+        let normalization = settings
+            .boolean("volume-normalization")
+            .then(|| settings.double("volume-normalization-pregain"));
+        let normalization_type = match settings.enum_("volume-normalization-type") {
+            1 => NormalisationType::Track,
+            2 => NormalisationType::Album,
+            _ => NormalisationType::Auto,
+        };
 
         let ap_port_val = settings.uint("ap-port");
         if ap_port_val > 65535 {
@@ -77,6 +315,23 @@ impl SpotifyPlayerSettings {
             x => Some(x as u16),
         };
 
+        let ap_timeout = Duration::from_secs(settings.uint("ap-timeout").max(1) as u64);
+
+        let proxy = proxy();
+
+        // Needs to be a valid port, or we could never complete a login
+        let redirect_port = match settings.uint("oauth-redirect-port") {
+            x @ 1..=65535 => x as u16,
+            _ => DEFAULT_REDIRECT_PORT,
+        };
+
+        let cache_audio = settings.boolean("cache-audio");
+        // In megabytes, 0 meaning there's no limit
+        let audio_cache_limit = match settings.uint("audio-cache-size") {
+            0 => None,
+            x => Some(x as u64 * 1024 * 1024),
+        };
+
         let volume = settings.double("volume");
         let shuffle = settings.boolean("shuffle");
         let repeat = match settings.string("repeat").as_str() {
@@ -92,8 +347,18 @@ impl SpotifyPlayerSettings {
 
             bitrate,
             backend,
+            mixer,
+            audio_format,
             gapless,
+            fade_duration,
+            normalization,
+            normalization_type,
             ap_port,
+            ap_timeout,
+            proxy,
+            redirect_port,
+            cache_audio,
+            audio_cache_limit,
         })
     }
     pub fn actions(&self) -> Vec<AppAction> {
@@ -111,12 +376,52 @@ pub struct RiffSettings {
     pub theme_preference: ColorScheme,
     pub player_settings: SpotifyPlayerSettings,
     pub window: WindowGeometry,
+    // Only ever true the first time the settings are read
+    pub imported_from_spot: bool,
+}
+
+// Copies what was changed in Spot's settings and is still at its default here, the first time only
+fn import_spot_settings(settings: &gio::Settings) -> bool {
+    if settings.boolean("spot-settings-imported") {
+        return false;
+    }
+    let _ = settings.set_boolean("spot-settings-imported", true);
+
+    // Looking the schema up first, creating settings for a schema that isn't installed aborts
+    let spot_schema =
+        gio::SettingsSchemaSource::default().and_then(|source| source.lookup(SPOT_SETTINGS, true));
+    let (Some(spot_schema), Some(schema)) = (spot_schema, settings.settings_schema()) else {
+        return false;
+    };
+    let spot_settings = gio::Settings::new(SPOT_SETTINGS);
+
+    let mut imported = 0;
+    for key in spot_schema.list_keys() {
+        let Some(value) = spot_settings.user_value(&key) else {
+            continue;
+        };
+        if !schema.has_key(&key) || settings.user_value(&key).is_some() {
+            continue;
+        }
+        // The type or the allowed values might have changed since
+        if !schema.key(&key).range_check(&value) {
+            continue;
+        }
+        if settings.set_value(&key, &value).is_ok() {
+            imported += 1;
+        }
+    }
+    if imported > 0 {
+        info!("Imported {imported} settings from Spot");
+    }
+    imported > 0
 }
 
 // Application settings
 impl RiffSettings {
     pub fn new_from_gsettings() -> Option<Self> {
         let settings = gio::Settings::new(SETTINGS);
+        let imported_from_spot = import_spot_settings(&settings);
         let theme_preference = match settings.enum_("theme-preference") {
             0 => Some(ColorScheme::ForceLight),
             1 => Some(ColorScheme::ForceDark),
@@ -127,6 +432,7 @@ impl RiffSettings {
             theme_preference,
             player_settings: SpotifyPlayerSettings::new_from_gsettings(&settings)?,
             window: WindowGeometry::new_from_gsettings(),
+            imported_from_spot,
         })
     }
 }
@@ -137,25 +443,54 @@ impl Default for RiffSettings {
             theme_preference: ColorScheme::PreferDark,
             player_settings: Default::default(),
             window: Default::default(),
+            imported_from_spot: false,
         }
     }
 }
 
+// Seeking around can sync the position quite a few times in a row
+const SAVE_PLAYBACK_DELAY_MS: u32 = 2000;
+
 /// Observes some app state changes and records them into GSettings.
 pub struct StateTracker {
+    app_model: Rc<AppModel>,
     settings: gio::Settings,
+    // Mirrors the navigation stack (minus Home) so we can save it as it changes
+    navigation: Vec<ScreenName>,
+    save_playback: Debouncer,
 }
 
 type GResult = Result<(), glib::error::BoolError>;
 impl StateTracker {
-    pub fn new_from_gsettings() -> Self {
+    pub fn new_from_gsettings(app_model: Rc<AppModel>) -> Self {
         Self {
+            app_model,
             settings: gio::Settings::new(SETTINGS),
+            navigation: vec![],
+            save_playback: Debouncer::new(),
         }
     }
+
+    // So that we can pick up from there next time
+    fn save_playback_later(&self) {
+        let app_model = Rc::clone(&self.app_model);
+        self.save_playback
+            .debounce(SAVE_PLAYBACK_DELAY_MS, move || {
+                let saved = SavedPlayback::from_playback(&app_model.get_state().playback);
+                match saved {
+                    Some(saved) => saved.save(),
+                    // Stopped, nothing to pick up later
+                    None => SavedPlayback::forget(),
+                };
+            });
+    }
+
     fn on_playback_event(&self, event: &PlaybackEvent) -> GResult {
         use PlaybackEvent::*;
         match event {
+            TrackChanged(_) | SeekSynced(_) | TrackSeeked(_) | PlaybackPaused | PlaybackStopped => {
+                self.save_playback_later()
+            }
             VolumeSet(volume) => self.settings.set_double("volume", *volume)?,
             ShuffleChanged(shuffle) => self.settings.set_boolean("shuffle", *shuffle)?,
             RepeatModeChanged(repeat) => self.settings.set_string(
@@ -166,14 +501,55 @@ impl StateTracker {
                     RepeatMode::None => "none",
                 },
             )?,
+            EpisodeProgress(id, position) => set_episode_position(id, *position),
             _ => (),
         }
         Ok(())
     }
 
-    fn handle_event(&self, event: &AppEvent) -> GResult {
+    fn on_browser_event(&mut self, event: &BrowserEvent) -> GResult {
+        use BrowserEvent::*;
+        match event {
+            NavigationPushed(name) => self.navigation.push(name.clone()),
+            NavigationPopped => {
+                self.navigation.pop();
+            }
+            NavigationPoppedTo(name) => {
+                let len = self
+                    .navigation
+                    .iter()
+                    .position(|s| s == name)
+                    .map(|i| i + 1)
+                    .unwrap_or(0);
+                self.navigation.truncate(len);
+            }
+            HomeVisiblePageChanged(page) => return self.settings.set_string("home-page", page),
+            _ => return Ok(()),
+        }
+        self.save_navigation()
+    }
+
+    fn save_navigation(&self) -> GResult {
+        let screens: Vec<String> = self
+            .navigation
+            .iter()
+            .filter(|s| !matches!(s, ScreenName::Search))
+            .map(|s| s.identifier().into_owned())
+            .collect();
+        self.settings.set_strv("navigation-stack", screens)
+    }
+
+    fn handle_event(&mut self, event: &AppEvent) -> GResult {
         match event {
             AppEvent::PlaybackEvent(event) => self.on_playback_event(event)?,
+            AppEvent::BrowserEvent(event) => self.on_browser_event(event)?,
+            // The next user shouldn't land on the previous one's pages
+            AppEvent::LoginEvent(LoginEvent::LogoutCompleted | LoginEvent::AddAccountStarted) => {
+                self.navigation.clear();
+                self.save_navigation()?;
+                self.save_playback.cancel();
+                SavedPlayback::forget();
+            }
             _ => (),
         }
         Ok(())